@@ -0,0 +1,87 @@
+//! Derive macros for SnapFire. Use them through the `snapfire` crate, which
+//! re-exports them next to their traits.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Ident, LitStr, parse_macro_input};
+
+/// Derives `snapfire::IntoTemplateContext`, exposing each named field under
+/// its name.
+///
+/// Field attributes:
+/// - `#[template_context(skip)]` keeps the field out of templates.
+/// - `#[template_context(rename = "name")]` exposes it under another name.
+///
+/// Struct attributes:
+/// - `#[template_context(computed = "method")]` also exposes the value of
+///   `self.method()`, under the method's name. Repeat it for several.
+#[proc_macro_derive(IntoTemplateContext, attributes(template_context))]
+pub fn derive_into_template_context(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+  let Data::Struct(data) = &input.data else {
+    return Err(syn::Error::new_spanned(&input.ident, "IntoTemplateContext can only be derived for structs"));
+  };
+  let Fields::Named(fields) = &data.fields else {
+    return Err(syn::Error::new_spanned(
+      &input.ident,
+      "IntoTemplateContext can only be derived for structs with named fields",
+    ));
+  };
+
+  let mut computed = Vec::new();
+  for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("template_context")) {
+    attr.parse_nested_meta(|meta| {
+      if meta.path.is_ident("computed") {
+        let method: LitStr = meta.value()?.parse()?;
+        computed.push(Ident::new(&method.value(), method.span()));
+        Ok(())
+      } else {
+        Err(meta.error("expected `computed = \"method\"`"))
+      }
+    })?;
+  }
+
+  let mut inserts = Vec::new();
+  for field in &fields.named {
+    let ident = field.ident.as_ref().expect("named fields have names");
+    let mut skip = false;
+    let mut name = LitStr::new(ident.to_string().trim_start_matches("r#"), Span::call_site());
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("template_context")) {
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("skip") {
+          skip = true;
+          Ok(())
+        } else if meta.path.is_ident("rename") {
+          name = meta.value()?.parse()?;
+          Ok(())
+        } else {
+          Err(meta.error("expected `skip` or `rename = \"name\"`"))
+        }
+      })?;
+    }
+    if !skip {
+      inserts.push(quote! { context.insert(#name, &self.#ident); });
+    }
+  }
+  for method in &computed {
+    let name = LitStr::new(&method.to_string(), method.span());
+    inserts.push(quote! { context.insert(#name, &self.#method()); });
+  }
+
+  let ident = &input.ident;
+  let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::snapfire::IntoTemplateContext for #ident #type_generics #where_clause {
+      fn into_template_context(self) -> ::snapfire::__private::Context {
+        let mut context = ::snapfire::__private::Context::new();
+        #(#inserts)*
+        context
+      }
+    }
+  })
+}
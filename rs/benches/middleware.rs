@@ -0,0 +1,50 @@
+use actix_web::{App, HttpResponse, test, web};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use snapfire::{TeraWeb, actix::dev::InjectSnapFireScript};
+use tempfile::tempdir;
+
+// A page of roughly `size` bytes with a closing body tag at the end.
+fn html_page(size: usize) -> String {
+  let filler = "<p>Lorem ipsum dolor sit amet.</p>".repeat(size / 34 + 1);
+  format!("<html><head></head><body>{filler}</body></html>")
+}
+
+fn bench_injection(c: &mut Criterion) {
+  let system = actix_rt::System::new();
+  let temp_dir = tempdir().unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).build().unwrap();
+
+  let mut group = c.benchmark_group("middleware_injection");
+
+  for size in [1_024, 16_384, 262_144] {
+    let page = html_page(size);
+    let service = system.block_on(test::init_service(
+      App::new()
+        .app_data(web::Data::new(snapfire_app.clone()))
+        .wrap(InjectSnapFireScript::default())
+        .route(
+          "/",
+          web::get().to(move || {
+            let page = page.clone();
+            async move { HttpResponse::Ok().content_type("text/html").body(page) }
+          }),
+        ),
+    ));
+
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_function(BenchmarkId::new("bytes", size), |b| {
+      b.iter(|| {
+        system.block_on(async {
+          let req = test::TestRequest::get().uri("/").to_request();
+          test::call_and_read_body(&service, req).await
+        })
+      })
+    });
+  }
+
+  group.finish();
+}
+
+criterion_group!(benches, bench_injection);
+criterion_main!(benches);
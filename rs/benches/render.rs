@@ -0,0 +1,62 @@
+use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
+use snapfire::TeraWeb;
+use std::fs;
+use tempfile::tempdir;
+use tera::Context;
+
+const PAGE: &str = "<html><head><title>{{ site_name }} | {{ title }}</title></head>\
+<body><ul>{% for item in items %}<li>{{ item }}</li>{% endfor %}</ul></body></html>";
+
+// Builds an app with `global_count` extra globals and a single page template.
+fn setup_app(global_count: usize) -> (TeraWeb, tempfile::TempDir) {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("index.html"), PAGE).unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+  let mut builder = TeraWeb::builder(&glob_path).add_global("site_name", "SnapFire Bench");
+  for i in 0..global_count {
+    builder = builder.add_global(format!("global_{i}"), format!("value number {i}"));
+  }
+
+  (builder.build().unwrap(), temp_dir)
+}
+
+fn page_context(item_count: usize) -> Context {
+  let mut context = Context::new();
+  context.insert("title", "Benchmark");
+  context.insert("items", &(0..item_count).map(|i| format!("item {i}")).collect::<Vec<_>>());
+  context
+}
+
+fn bench_render(c: &mut Criterion) {
+  let (app, _temp_dir) = setup_app(10);
+  let mut group = c.benchmark_group("render");
+
+  for item_count in [10, 100, 1_000] {
+    let context = page_context(item_count);
+    let stats = snapfire::bench::render_throughput(&app, "index.html", &context).unwrap();
+    group.throughput(Throughput::Bytes(stats.bytes_per_render as u64));
+    group.bench_with_input(BenchmarkId::new("items", item_count), &context, |b, context| {
+      b.iter(|| snapfire::bench::render_once(&app, "index.html", black_box(context.clone())).unwrap())
+    });
+  }
+
+  group.finish();
+}
+
+fn bench_context_merge(c: &mut Criterion) {
+  let mut group = c.benchmark_group("context_merge");
+
+  for global_count in [10, 100, 1_000] {
+    let (app, _temp_dir) = setup_app(global_count);
+    let context = page_context(10);
+    group.bench_with_input(BenchmarkId::new("globals", global_count), &context, |b, context| {
+      b.iter(|| snapfire::bench::merge_context(&app, black_box(context.clone())))
+    });
+  }
+
+  group.finish();
+}
+
+criterion_group!(benches, bench_render, bench_context_merge);
+criterion_main!(benches);
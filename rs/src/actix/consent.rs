@@ -0,0 +1,115 @@
+use crate::core::consent::ConsentState;
+use actix_web::{
+  Error, HttpMessage,
+  cookie::{Cookie, CookieJar, Key, SameSite},
+  dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::{self, LocalBoxFuture};
+use std::rc::Rc;
+
+/// The default name of the signed consent cookie.
+pub const DEFAULT_COOKIE_NAME: &str = "snapfire_consent";
+
+/// An Actix middleware that reads the visitor's consent choices from a signed cookie.
+///
+/// The resulting [`ConsentState`] is stored in the request extensions, and every
+/// `Template` rendered for that request exposes it as `consent` in the context.
+/// A missing or tampered cookie yields the default state (nothing granted).
+#[derive(Clone)]
+pub struct CookieConsent {
+  key: Key,
+  cookie_name: String,
+}
+
+impl CookieConsent {
+  /// Creates the middleware with the key used to sign and verify the cookie.
+  pub fn new(key: Key) -> Self {
+    Self {
+      key,
+      cookie_name: DEFAULT_COOKIE_NAME.to_string(),
+    }
+  }
+
+  /// Sets the name of the consent cookie.
+  ///
+  /// Defaults to `snapfire_consent`.
+  pub fn cookie_name(mut self, name: &str) -> Self {
+    self.cookie_name = name.to_string();
+    self
+  }
+
+  /// Builds a signed cookie recording `state`.
+  ///
+  /// Use this in the handler behind your consent banner's "accept" form to
+  /// persist the visitor's choices.
+  pub fn cookie(&self, state: ConsentState) -> Cookie<'static> {
+    let mut jar = CookieJar::new();
+    let mut cookie = Cookie::new(self.cookie_name.clone(), state.encode());
+    cookie.set_path("/");
+    cookie.set_http_only(true);
+    cookie.set_same_site(SameSite::Lax);
+    jar.signed_mut(&self.key).add(cookie);
+    jar.get(&self.cookie_name).cloned().expect("cookie was just added")
+  }
+
+  /// Verifies and decodes the consent cookie from a request.
+  fn read(&self, req: &ServiceRequest) -> ConsentState {
+    let Some(cookie) = req.cookie(&self.cookie_name) else {
+      return ConsentState::default();
+    };
+
+    let mut jar = CookieJar::new();
+    jar.add_original(cookie);
+    match jar.signed(&self.key).get(&self.cookie_name) {
+      Some(verified) => ConsentState::decode(verified.value()),
+      None => {
+        log::warn!("Ignoring consent cookie with an invalid signature");
+        ConsentState::default()
+      }
+    }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CookieConsent
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Transform = CookieConsentMiddleware<S>;
+  type InitError = ();
+  type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    future::ok(CookieConsentMiddleware {
+      service: Rc::new(service),
+      config: Rc::new(self.clone()),
+    })
+  }
+}
+
+pub struct CookieConsentMiddleware<S> {
+  service: Rc<S>,
+  config: Rc<CookieConsent>,
+}
+
+impl<S, B> Service<ServiceRequest> for CookieConsentMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let state = self.config.read(&req);
+    req.extensions_mut().insert(state);
+
+    let service = self.service.clone();
+    Box::pin(async move { service.call(req).await })
+  }
+}
@@ -0,0 +1,109 @@
+use crate::core::a11y::{self, A11Y_PATH, OUTLINE_PATH};
+use actix_web::{
+  Error, HttpResponse,
+  body::{BoxBody, MessageBody},
+  dev::{Service, ServiceRequest, ServiceResponse, Transform},
+  http::{
+    Method, Uri,
+    header::{self, ContentType},
+  },
+  web,
+};
+use futures_util::future::{self, LocalBoxFuture};
+use std::{collections::HashMap, rc::Rc, task::Poll};
+
+/// Serves an accessibility report of any route at
+/// `/_snapfire/a11y?path=/route`, and its heading and landmark outline at
+/// `/_snapfire/outline?path=/route`, in dev mode.
+///
+/// The middleware renders the route through the app, with the report
+/// request's cookies and headers, and runs [`crate::core::a11y::audit`] or
+/// [`crate::core::a11y::outline`] over the page. Wrap the `App` with it so
+/// every route can be reached; without the `devel` feature it does nothing.
+#[derive(Debug, Clone, Default)]
+pub struct A11yAudit;
+
+impl<S, B> Transform<S, ServiceRequest> for A11yAudit
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+  type Error = Error;
+  type Transform = A11yAuditMiddleware<S>;
+  type InitError = ();
+  type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    future::ok(A11yAuditMiddleware {
+      service: Rc::new(service),
+    })
+  }
+}
+
+pub struct A11yAuditMiddleware<S> {
+  service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for A11yAuditMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(&self, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(cx)
+  }
+
+  fn call(&self, mut req: ServiceRequest) -> Self::Future {
+    let is_outline = req.path() == OUTLINE_PATH;
+    if req.method() != Method::GET || !(is_outline || req.path() == A11Y_PATH) {
+      let response = self.service.call(req);
+      return Box::pin(async move { Ok(response.await?.map_into_boxed_body()) });
+    }
+
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok();
+    let target = query
+      .as_ref()
+      .and_then(|query| query.get("path"))
+      .filter(|path| path.starts_with('/') && !path.starts_with("//"))
+      .and_then(|path| path.parse::<Uri>().ok().map(|uri| (path.clone(), uri)));
+    let Some((path, uri)) = target else {
+      let response = HttpResponse::BadRequest().body("`path` must be a route of the app, e.g. `?path=/profile`");
+      return Box::pin(async move { Ok(req.into_response(response)) });
+    };
+
+    // Render the route in place of this request; the page is read whole, so
+    // ask for it uncompressed.
+    req.match_info_mut().get_mut().update(&uri);
+    req.head_mut().uri = uri;
+    req.headers_mut().remove(header::ACCEPT_ENCODING);
+
+    let service = self.service.clone();
+    Box::pin(async move {
+      let response = service.call(req).await?;
+      let status = response.status();
+      let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+      let (request, response) = response.into_parts();
+      let body = actix_web::body::to_bytes(response.into_body())
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to read the page"))?;
+
+      let page = (is_html && status.is_success()).then(|| String::from_utf8_lossy(&body).into_owned());
+      let html = if is_outline {
+        a11y::outline_html(&path, status.as_u16(), page.map(|page| a11y::outline(&page)).as_deref())
+      } else {
+        a11y::report_html(&path, status.as_u16(), page.map(|page| a11y::audit(&page)).as_deref())
+      };
+      let report = HttpResponse::Ok().content_type(ContentType::html()).body(html);
+      Ok(ServiceResponse::new(request, report))
+    })
+  }
+}
@@ -0,0 +1,35 @@
+use crate::actix::rendered_response;
+use crate::core::client::{CLIENT_SCRIPT_PATH, ClientScript};
+use actix_web::{HttpRequest, HttpResponse, http::header};
+
+/// The names the WebSocket and client script routes are registered under, so
+/// their paths can be resolved wherever they're mounted.
+pub(crate) const WS_ROUTE: &str = "snapfire_ws";
+pub(crate) const CLIENT_SCRIPT_ROUTE: &str = "snapfire_client";
+
+impl ClientScript {
+  /// The client for `req`'s app, with the routes' paths as mounted: inside a
+  /// `web::scope("/app")`, the WebSocket is at `/app` + `ws_path`.
+  pub(crate) fn for_request(req: &HttpRequest, ws_path: &str) -> Self {
+    Self::new(
+      &mounted_path(req, WS_ROUTE, ws_path),
+      &mounted_path(req, CLIENT_SCRIPT_ROUTE, CLIENT_SCRIPT_PATH),
+    )
+  }
+
+  /// Serves the script. Requests for the current versioned URL are cacheable
+  /// forever; the unversioned URL is revalidated against the ETag.
+  pub(crate) fn respond(&self, req: &HttpRequest) -> HttpResponse {
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).and_then(|value| value.to_str().ok());
+    rendered_response(self.response(req.query_string(), if_none_match))
+  }
+}
+
+/// The full path of a named route, including the prefixes of the scopes it's
+/// registered in, or `fallback` when it isn't registered.
+fn mounted_path(req: &HttpRequest, route: &str, fallback: &str) -> String {
+  match req.url_for_static(route) {
+    Ok(url) => url.path().to_string(),
+    Err(_) => fallback.to_string(),
+  }
+}
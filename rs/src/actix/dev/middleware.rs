@@ -1,19 +1,21 @@
+use crate::actix::security::CspNonce;
+use crate::core::app::TeraWeb;
+use crate::core::client::{BODY_TAG, ClientScript, find_case_insensitive};
 use actix_web::{
-  Error,
+  Error, HttpMessage, HttpRequest,
   body::{BoxBody, MessageBody},
   dev::{Service, ServiceRequest, ServiceResponse, Transform},
   http::header::CONTENT_TYPE,
+  web,
 };
 use bytes::{Bytes, BytesMut};
 use futures_util::future::{self, LocalBoxFuture};
-use std::{rc::Rc, task::Poll};
+use std::{rc::Rc, sync::Arc, task::Poll};
 
-const SCRIPT_TAG_START: &[u8] = b"<script data-snapfire-reload=\"true\">";
-const SCRIPT_CONTENT: &[u8] = include_bytes!("injected.js");
-const SCRIPT_TAG_END: &[u8] = b"</script>";
-const BODY_TAG: &[u8] = b"</body>";
+const DEFAULT_WS_PATH: &str = "/_snapfire/ws";
 
 #[derive(Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct InjectSnapFireScript;
 
 impl<S, B> Transform<S, ServiceRequest> for InjectSnapFireScript
@@ -58,13 +60,46 @@ where
     // This handle can be moved into the async block.
     let service = self.service.clone();
 
+    // Respect the builder's `auto_inject_script` and `external_script`
+    // settings when the app state is registered.
+    let reloader = req.app_data::<web::Data<TeraWeb>>().map(|app| Arc::clone(&app.reloader));
+    let inject = reloader.as_ref().is_none_or(|reloader| reloader.options.auto_inject_script);
+    // Built from the response's request, so the `SecurityHeaders` nonce is
+    // there whichever middleware wraps the other.
+    let snippet = move |req: &HttpRequest| {
+      let ws_path = reloader.as_ref().map_or(DEFAULT_WS_PATH, |reloader| &reloader.options.ws_path);
+      let script = ClientScript::for_request(req, ws_path);
+      let nonce = req
+        .extensions()
+        .get::<CspNonce>()
+        .map(|nonce| format!(" nonce=\"{}\"", nonce.as_str()))
+        .unwrap_or_default();
+      if reloader.as_ref().is_some_and(|reloader| reloader.options.external_script) {
+        Bytes::from(format!(
+          "<script src=\"{}\"{nonce} data-snapfire-reload=\"true\"></script>",
+          script.url()
+        ))
+      } else {
+        let mut snippet = BytesMut::new();
+        snippet.extend_from_slice(format!("<script{nonce} data-snapfire-reload=\"true\">").as_bytes());
+        snippet.extend_from_slice(&script.body);
+        snippet.extend_from_slice(b"</script>");
+        snippet.freeze()
+      }
+    };
+
     Box::pin(async move {
       let res = service.call(req).await?;
+      let snippet = inject.then(|| snippet(res.request()));
+
+      let Some(snippet) = snippet else {
+        return Ok(res.map_into_boxed_body());
+      };
 
       let is_html = res
         .headers()
         .get(CONTENT_TYPE)
-        .map_or(false, |val| val.to_str().unwrap_or("").contains("text/html"));
+        .is_some_and(|val| val.to_str().unwrap_or("").contains("text/html"));
 
       if !is_html {
         return Ok(res.map_into_boxed_body());
@@ -83,27 +118,13 @@ where
             }
           };
 
-          let new_body = if let Some(body_end_index) = find_case_insensitive(&body_bytes, BODY_TAG) {
-            let new_body_len = body_bytes.len() + SCRIPT_TAG_START.len() + SCRIPT_CONTENT.len() + SCRIPT_TAG_END.len();
-            let mut new_body = BytesMut::with_capacity(new_body_len);
-
-            new_body.extend_from_slice(&body_bytes[..body_end_index]);
-            new_body.extend_from_slice(SCRIPT_TAG_START);
-            new_body.extend_from_slice(SCRIPT_CONTENT);
-            new_body.extend_from_slice(SCRIPT_TAG_END);
-            new_body.extend_from_slice(&body_bytes[body_end_index..]);
-            new_body.freeze()
-          } else {
-            // If no body tag, append it all at the end
-            let new_body_len = body_bytes.len() + SCRIPT_TAG_START.len() + SCRIPT_CONTENT.len() + SCRIPT_TAG_END.len();
-            let mut new_body = BytesMut::with_capacity(new_body_len);
-
-            new_body.extend_from_slice(&body_bytes);
-            new_body.extend_from_slice(SCRIPT_TAG_START);
-            new_body.extend_from_slice(SCRIPT_CONTENT);
-            new_body.extend_from_slice(SCRIPT_TAG_END);
-            new_body.freeze()
-          };
+          // Insert before `</body>`, or append it all at the end if there is none.
+          let body_end_index = find_case_insensitive(&body_bytes, BODY_TAG).unwrap_or(body_bytes.len());
+          let mut new_body = BytesMut::with_capacity(body_bytes.len() + snippet.len());
+          new_body.extend_from_slice(&body_bytes[..body_end_index]);
+          new_body.extend_from_slice(&snippet);
+          new_body.extend_from_slice(&body_bytes[body_end_index..]);
+          let new_body = new_body.freeze();
 
           Ok::<_, Error>(new_body)
         };
@@ -118,9 +139,3 @@ where
     })
   }
 }
-
-fn find_case_insensitive(haystack: &[u8], needle: &[u8]) -> Option<usize> {
-  haystack
-    .windows(needle.len())
-    .position(|window| window.eq_ignore_ascii_case(needle))
-}
@@ -4,11 +4,27 @@
 // When `devel` is enabled, we declare the real implementation
 // modules and publicly export the real middleware struct.
 #[cfg(feature = "devel")]
+mod a11y;
+#[cfg(feature = "devel")]
+mod client;
+#[cfg(feature = "devel")]
 mod middleware;
 #[cfg(feature = "devel")]
+mod replay;
+#[cfg(feature = "devel")]
 pub(crate) mod ws;
 #[cfg(feature = "devel")]
+pub use crate::core::client::CLIENT_SCRIPT_PATH;
+#[cfg(feature = "devel")]
+pub(crate) use crate::core::client::ClientScript;
+#[cfg(feature = "devel")]
+pub(crate) use client::{CLIENT_SCRIPT_ROUTE, WS_ROUTE};
+#[cfg(feature = "devel")]
+pub use a11y::A11yAudit;
+#[cfg(feature = "devel")]
 pub use middleware::InjectSnapFireScript;
+#[cfg(feature = "devel")]
+pub use replay::RequestReplay;
 
 // === DUMMY IMPLEMENTATION ===
 // When `devel` is NOT enabled, we provide a dummy struct
@@ -20,6 +36,7 @@ use std::future::{Ready, ready};
 
 #[cfg(not(feature = "devel"))]
 #[derive(Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct InjectSnapFireScript;
 
 #[cfg(not(feature = "devel"))]
@@ -38,3 +55,52 @@ where
     ready(Ok(service))
   }
 }
+
+#[cfg(not(feature = "devel"))]
+#[derive(Debug, Clone, Default)]
+pub struct A11yAudit;
+
+#[cfg(not(feature = "devel"))]
+impl<S, B> Transform<S, ServiceRequest> for A11yAudit
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  B: actix_web::body::MessageBody,
+{
+  type Response = ServiceResponse<B>;
+  type Error = actix_web::Error;
+  type Transform = S;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(service))
+  }
+}
+
+#[cfg(not(feature = "devel"))]
+#[derive(Debug, Clone, Default)]
+pub struct RequestReplay;
+
+#[cfg(not(feature = "devel"))]
+impl RequestReplay {
+  pub fn new() -> Self {
+    Self
+  }
+}
+
+#[cfg(not(feature = "devel"))]
+impl<S, B> Transform<S, ServiceRequest> for RequestReplay
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  B: actix_web::body::MessageBody,
+{
+  type Response = ServiceResponse<B>;
+  type Error = actix_web::Error;
+  type Transform = S;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(service))
+  }
+}
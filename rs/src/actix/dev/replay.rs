@@ -0,0 +1,219 @@
+use crate::core::replay::{self, MAX_BODY, REPLAY_PATH, ReplayLog, Replayed};
+use actix_web::{
+  Error, HttpMessage, HttpResponse,
+  body::{BoxBody, MessageBody},
+  dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform},
+  http::{
+    Method, Uri,
+    header::{self, ContentType, HeaderName, HeaderValue},
+  },
+  web,
+};
+use bytes::{Bytes, BytesMut};
+use futures_util::{
+  StreamExt,
+  future::{self, LocalBoxFuture},
+};
+use std::{collections::HashMap, rc::Rc, sync::Arc, task::Poll};
+
+/// Records the app's recent requests in dev mode, and serves a panel at
+/// `/_snapfire/replay` that sends them again and shows how the status and
+/// page changed, so a form's result page can be checked after a template
+/// edit without filling in the form again.
+///
+/// See [`crate::core::replay`] for what is recorded. Wrap the `App` with it
+/// so every route is seen, creating it once outside the `HttpServer`
+/// factory so the workers share one log:
+///
+/// ```rust,no_run
+/// # use actix_web::{App, HttpServer};
+/// # use snapfire::actix::dev::RequestReplay;
+/// # async fn run() -> std::io::Result<()> {
+/// let replay = RequestReplay::new();
+/// HttpServer::new(move || App::new().wrap(replay.clone()))
+///   .bind(("127.0.0.1", 8080))?
+///   .run()
+///   .await
+/// # }
+/// ```
+///
+/// Without the `devel` feature it does nothing.
+#[derive(Debug, Clone, Default)]
+pub struct RequestReplay {
+  log: Arc<ReplayLog>,
+}
+
+impl RequestReplay {
+  /// A middleware with an empty log.
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestReplay
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+  type Error = Error;
+  type Transform = RequestReplayMiddleware<S>;
+  type InitError = ();
+  type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    future::ok(RequestReplayMiddleware {
+      service: Rc::new(service),
+      log: Arc::clone(&self.log),
+    })
+  }
+}
+
+pub struct RequestReplayMiddleware<S> {
+  service: Rc<S>,
+  log: Arc<ReplayLog>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestReplayMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<BoxBody>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(&self, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.service.poll_ready(cx)
+  }
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let service = self.service.clone();
+    let log = Arc::clone(&self.log);
+    if req.path() == REPLAY_PATH {
+      return Box::pin(panel(service, log, req));
+    }
+    // Bodies of unknown length or over `MAX_BODY` aren't recorded, nor are
+    // the dev routes.
+    let recordable_length = match req.headers().get(header::CONTENT_LENGTH) {
+      Some(length) => length.to_str().ok().and_then(|length| length.parse::<usize>().ok()).filter(|&length| length <= MAX_BODY),
+      None => (!req.headers().contains_key(header::TRANSFER_ENCODING)).then_some(0),
+    };
+    let skipped = req.path().starts_with("/_snapfire/") || req.method() == Method::HEAD;
+    let Some(length) = recordable_length.filter(|_| !skipped) else {
+      let response = service.call(req);
+      return Box::pin(async move { Ok(response.await?.map_into_boxed_body()) });
+    };
+
+    Box::pin(async move {
+      let mut req = req;
+      let body = read_body(&mut req, length).await?;
+      let method = req.method().to_string();
+      let uri = req.uri().path_and_query().map_or_else(|| req.path().to_string(), ToString::to_string);
+      let headers = req
+        .headers()
+        .iter()
+        .filter(|(name, _)| replay::is_recorded_header(name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+
+      let response = service.call(req).await?;
+      let (response, page) = read_page(response).await?;
+      if page.is_some() || method != Method::GET.as_str() {
+        log.record(&method, &uri, headers, body, response.status().as_u16(), page);
+      }
+      Ok(response)
+    })
+  }
+}
+
+/// Serves the panel, replaying the request named by the `id` of a `POST`.
+async fn panel<S, B>(service: Rc<S>, log: Arc<ReplayLog>, mut req: ServiceRequest) -> Result<ServiceResponse<BoxBody>, Error>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: MessageBody + 'static,
+{
+  if req.method() != Method::POST {
+    let html = replay::panel_html(&log.entries(), None);
+    return Ok(req.into_response(HttpResponse::Ok().content_type(ContentType::html()).body(html)));
+  }
+  let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok();
+  let recorded = query
+    .as_ref()
+    .and_then(|query| query.get("id"))
+    .and_then(|id| id.parse().ok())
+    .and_then(|id| log.get(id));
+  let target = recorded.and_then(|request| {
+    let method = Method::from_bytes(request.method.as_bytes()).ok()?;
+    let uri = request.uri.parse::<Uri>().ok()?;
+    Some((request, method, uri))
+  });
+  let Some((request, method, uri)) = target else {
+    let response = HttpResponse::NotFound().body("No recorded request with this `id`; it may have been dropped for newer ones");
+    return Ok(req.into_response(response));
+  };
+
+  // Send the recorded request in place of this one, with the browser's
+  // current cookies; the page is read whole, so ask for it uncompressed.
+  req.match_info_mut().get_mut().update(&uri);
+  req.head_mut().method = method;
+  req.head_mut().uri = uri;
+  let headers = req.headers_mut();
+  let own: Vec<HeaderName> = headers.keys().filter(|name| replay::is_recorded_header(name.as_str())).cloned().collect();
+  for name in own {
+    headers.remove(name);
+  }
+  headers.remove(header::ACCEPT_ENCODING);
+  for (name, value) in &request.headers {
+    if let (Ok(name), Ok(value)) = (HeaderName::try_from(name.as_str()), HeaderValue::try_from(value.as_str())) {
+      headers.append(name, value);
+    }
+  }
+  headers.insert(header::CONTENT_LENGTH, HeaderValue::from(request.body.len()));
+  req.set_payload(Payload::from(request.body.clone()));
+
+  let (response, page) = read_page(service.call(req).await?).await?;
+  let status = response.status().as_u16();
+  log.update(request.id, status, page.clone());
+  let replayed = Replayed { request, status, page };
+  let html = replay::panel_html(&log.entries(), Some(&replayed));
+  let panel = HttpResponse::Ok().content_type(ContentType::html()).body(html);
+  Ok(ServiceResponse::new(response.request().clone(), panel))
+}
+
+/// Reads the request body of `length` bytes, then puts it back for the app.
+async fn read_body(req: &mut ServiceRequest, length: usize) -> Result<Bytes, Error> {
+  if length == 0 {
+    return Ok(Bytes::new());
+  }
+  let mut payload = req.take_payload();
+  let mut body = BytesMut::with_capacity(length);
+  while let Some(chunk) = payload.next().await {
+    body.extend_from_slice(&chunk?);
+  }
+  let body = body.freeze();
+  req.set_payload(Payload::from(body.clone()));
+  Ok(body)
+}
+
+/// The response, with its page read out if it's uncompressed HTML.
+async fn read_page<B>(response: ServiceResponse<B>) -> Result<(ServiceResponse<BoxBody>, Option<String>), Error>
+where
+  B: MessageBody + 'static,
+{
+  let is_html = response
+    .headers()
+    .get(header::CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .is_some_and(|value| value.starts_with("text/html"));
+  if !is_html || response.headers().contains_key(header::CONTENT_ENCODING) {
+    return Ok((response.map_into_boxed_body(), None));
+  }
+  let (request, response) = response.into_parts();
+  let (response, body) = response.into_parts();
+  let body = actix_web::body::to_bytes(body)
+    .await
+    .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to read the page"))?;
+  let page = String::from_utf8_lossy(&body).into_owned();
+  Ok((ServiceResponse::new(request, response.set_body(BoxBody::new(body))), Some(page)))
+}
@@ -1,29 +1,25 @@
-use crate::core::reload::ReloadMessage;
+use crate::core::broadcast::Broadcaster;
+use crate::core::reload::{Heartbeat, ReloadReceiver};
 use actix_web::{HttpRequest, HttpResponse};
-use actix_ws::{AggregatedMessage, Message, MessageStream, Session};
+use actix_ws::{AggregatedMessage, MessageStream, Session};
 use futures_util::StreamExt;
-use std::time::{Duration, Instant};
-use tokio::sync::broadcast;
+use std::time::Instant;
 use tokio::time::interval;
 
-/// How often heartbeat pings are sent to the client.
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
-/// How long to wait for a pong response before timing out.
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
-
 /// The main entry point function for handling a new WebSocket connection request.
 /// This function is the Actix handler.
 pub(crate) async fn websocket_handler(
   req: HttpRequest,
   body: actix_web::web::Payload,
-  broadcaster: broadcast::Sender<ReloadMessage>,
+  broadcaster: Broadcaster,
+  heartbeat: Heartbeat,
 ) -> Result<HttpResponse, actix_web::Error> {
   log::info!("New WebSocket connection request");
 
   let (response, session, msg_stream) = actix_ws::handle(&req, body)?;
 
   // Spawn a new task to handle the WebSocket session.
-  actix_web::rt::spawn(handle_connection(session, msg_stream, broadcaster.subscribe()));
+  actix_web::rt::spawn(handle_connection(session, msg_stream, broadcaster.subscribe(), heartbeat));
 
   // Return the response that finishes the WebSocket handshake.
   Ok(response)
@@ -32,11 +28,12 @@ pub(crate) async fn websocket_handler(
 /// Handles the lifecycle of a single WebSocket connection.
 async fn handle_connection(
   mut session: Session,
-  mut msg_stream: MessageStream,
-  mut reloader_rx: broadcast::Receiver<ReloadMessage>,
+  msg_stream: MessageStream,
+  mut reloader_rx: ReloadReceiver,
+  heartbeat: Heartbeat,
 ) {
   let mut last_heartbeat = Instant::now();
-  let mut interval = interval(HEARTBEAT_INTERVAL);
+  let mut interval = interval(heartbeat.interval);
 
   let mut msg_stream = msg_stream.aggregate_continuations();
   let close_reason = loop {
@@ -44,7 +41,7 @@ async fn handle_connection(
       // Heartbeat timer tick
       _ = interval.tick() => {
         // Check if the client has timed out
-        if Instant::now().duration_since(last_heartbeat) > CLIENT_TIMEOUT {
+        if Instant::now().duration_since(last_heartbeat) > heartbeat.timeout {
           log::info!("WebSocket client heartbeat failed, disconnecting!");
           break None;
         }
@@ -76,11 +73,12 @@ async fn handle_connection(
       }
 
       // An outgoing message from our `DevReloader` broadcaster
-      Ok(reload_msg) = reloader_rx.recv() => {
-        let message_text = match reload_msg {
-          ReloadMessage::Reload => "reload",
-          ReloadMessage::ReloadCss => "reload-css",
+      received = reloader_rx.recv() => {
+        // A client that fell behind a burst of changes gets a single reload.
+        let Some(message) = received else {
+          break None;
         };
+        let message_text = message.as_str();
         log::debug!("Broadcasting WebSocket message: {}", message_text);
 
         if session.text(message_text).await.is_err() {
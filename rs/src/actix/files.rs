@@ -0,0 +1,308 @@
+use crate::core::assets::{AssetFiles, content_type};
+use actix_web::{
+  HttpRequest, HttpResponse,
+  http::{
+    StatusCode,
+    header::{self, HeaderMap},
+  },
+  web,
+};
+use actix_web::body::SizedStream;
+use bytes::{Bytes, BytesMut};
+use futures_util::{Stream, TryStreamExt, stream};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+/// The most ranges served from one request; more are answered with the
+/// whole file rather than hundreds of tiny parts.
+const MAX_RANGES: usize = 16;
+
+/// How much of a file is read at a time while streaming it.
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// The precompressed variants looked for, by extension and content coding,
+/// in order of preference.
+const PRECOMPRESSED: [(&str, &str); 2] = [("br", "br"), ("gz", "gzip")];
+
+/// What a `Range` header asks for.
+#[derive(Debug, PartialEq, Eq)]
+enum Ranges {
+  /// The whole file: no header, or one that doesn't apply.
+  Full,
+  Partial(Vec<Range<u64>>),
+  /// Every range starts past the end of the file.
+  Unsatisfiable,
+}
+
+/// Parses a `Range` header for a file of `len` bytes. Malformed headers are
+/// ignored, as RFC 9110 asks.
+fn parse_ranges(value: &str, len: u64) -> Ranges {
+  let Some(specs) = value
+    .split_once('=')
+    .filter(|(unit, _)| unit.trim().eq_ignore_ascii_case("bytes"))
+    .map(|(_, specs)| specs)
+  else {
+    return Ranges::Full;
+  };
+
+  let mut ranges = Vec::new();
+  for spec in specs.split(',').map(str::trim).filter(|spec| !spec.is_empty()) {
+    let Some((first, last)) = spec.split_once('-') else {
+      return Ranges::Full;
+    };
+    let range = if first.is_empty() {
+      // The last `n` bytes.
+      match last.parse::<u64>() {
+        Ok(0) => continue,
+        Ok(suffix) => len.saturating_sub(suffix)..len,
+        Err(_) => return Ranges::Full,
+      }
+    } else {
+      let Ok(start) = first.parse::<u64>() else {
+        return Ranges::Full;
+      };
+      match last.parse::<u64>() {
+        _ if last.is_empty() => start..len,
+        Ok(end) if start <= end => start..end.saturating_add(1).min(len),
+        _ => return Ranges::Full,
+      }
+    };
+    if range.start < len {
+      ranges.push(range);
+    }
+  }
+
+  // Overlapping and adjacent ranges are served as one.
+  ranges.sort_by_key(|range| range.start);
+  let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+  for range in ranges {
+    match merged.last_mut() {
+      Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+      _ => merged.push(range),
+    }
+  }
+
+  match merged.len() {
+    0 => Ranges::Unsatisfiable,
+    count if count > MAX_RANGES => Ranges::Full,
+    _ => Ranges::Partial(merged),
+  }
+}
+
+/// Whether `value` (an `Accept-Encoding` header) allows `coding`.
+fn accepts(value: &str, coding: &str) -> bool {
+  value.split(',').any(|item| {
+    let mut parts = item.split(';').map(str::trim);
+    let name = parts.next().unwrap_or_default();
+    let refused = parts.any(|parameter| {
+      parameter
+        .strip_prefix("q=")
+        .and_then(|q| q.parse::<f32>().ok())
+        .is_some_and(|q| q == 0.0)
+    });
+    (name.eq_ignore_ascii_case(coding) || name == "*") && !refused
+  })
+}
+
+/// Whether an `If-None-Match` header matches `etag`, weakly.
+fn etag_matches(value: &str, etag: &str) -> bool {
+  value
+    .split(',')
+    .map(|tag| tag.trim().trim_start_matches("W/"))
+    .any(|tag| tag == etag || tag == "*")
+}
+
+/// `file` with `extension` appended, e.g. `app.js.br`.
+fn variant(file: &Path, extension: &str) -> PathBuf {
+  let mut variant = file.as_os_str().to_owned();
+  variant.push(".");
+  variant.push(extension);
+  PathBuf::from(variant)
+}
+
+/// The precompressed variant of `file` the client accepts, if one exists:
+/// `.br` before `.gz`.
+fn precompressed(headers: &HeaderMap, file: &Path) -> Option<(PathBuf, &'static str)> {
+  let accept = headers.get(header::ACCEPT_ENCODING)?.to_str().ok()?;
+  PRECOMPRESSED.into_iter().find_map(|(extension, coding)| {
+    let variant = variant(file, extension);
+    (accepts(accept, coding) && variant.is_file()).then_some((variant, coding))
+  })
+}
+
+/// A piece of a response body: text of its own, or a range of the file.
+enum Segment {
+  Text(Bytes),
+  File(Range<u64>),
+}
+
+impl Segment {
+  fn len(&self) -> u64 {
+    match self {
+      Segment::Text(text) => text.len() as u64,
+      Segment::File(range) => range.end - range.start,
+    }
+  }
+}
+
+/// Streams `segments`, reading the file ranges from `file` a chunk at a
+/// time on the blocking thread pool.
+fn stream_segments(file: File, segments: Vec<Segment>) -> impl Stream<Item = io::Result<Bytes>> {
+  stream::try_unfold((file, segments.into_iter(), None), |(file, mut segments, mut reading)| async move {
+    loop {
+      if let Some(range) = reading.take().filter(|range: &Range<u64>| !range.is_empty()) {
+        let len = (range.end - range.start).min(CHUNK_SIZE);
+        let (file, chunk) = web::block(move || {
+          let mut file = file;
+          let mut chunk = BytesMut::zeroed(len as usize);
+          file.seek(SeekFrom::Start(range.start))?;
+          file.read_exact(&mut chunk)?;
+          Ok::<_, io::Error>((file, chunk.freeze()))
+        })
+        .await
+        .map_err(io::Error::other)??;
+        return Ok(Some((chunk, (file, segments, Some(range.start + len..range.end)))));
+      }
+      match segments.next() {
+        Some(Segment::Text(text)) => return Ok(Some((text, (file, segments, None)))),
+        Some(Segment::File(range)) => reading = Some(range),
+        None => return Ok(None),
+      }
+    }
+  })
+}
+
+/// Serves a file of the fingerprinted asset directory: manifest outputs are
+/// cached forever, other files revalidated, and `Range`, precompressed
+/// variants, and conditional requests are honoured.
+pub(crate) async fn serve_asset(req: HttpRequest, files: Arc<AssetFiles>) -> HttpResponse {
+  let path = req.match_info().query("path").to_string();
+  let Some(file) = files.resolve(&path) else {
+    return HttpResponse::NotFound().finish();
+  };
+  let accepted = precompressed(req.headers(), &file);
+  // Caches must key on the encoding even for clients that accepted none.
+  let has_variants = accepted.is_some() || PRECOMPRESSED.iter().any(|(extension, _)| variant(&file, extension).is_file());
+  let (body_file, encoding) = match accepted {
+    Some((variant, coding)) => (variant, Some(coding)),
+    None => (file, None),
+  };
+  let Ok(file) = File::open(&body_file) else {
+    return HttpResponse::NotFound().finish();
+  };
+  let Ok(metadata) = file.metadata() else {
+    return HttpResponse::NotFound().finish();
+  };
+
+  let len = metadata.len();
+  let modified = metadata
+    .modified()
+    .ok()
+    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    .map_or(0, |since| since.as_secs());
+  let etag = format!("\"{len:x}-{modified:x}{}\"", encoding.map(|coding| format!("-{coding}")).unwrap_or_default());
+  let cache_control = if files.is_fingerprinted(&path) {
+    "public, max-age=31536000, immutable"
+  } else {
+    "no-cache"
+  };
+
+  let header = |name| req.headers().get(name).and_then(|value| value.to_str().ok());
+  let mut response = HttpResponse::Ok();
+  response
+    .insert_header((header::ETAG, etag.as_str()))
+    .insert_header((header::CACHE_CONTROL, cache_control))
+    .insert_header((header::ACCEPT_RANGES, "bytes"));
+  if has_variants {
+    response.insert_header((header::VARY, "Accept-Encoding"));
+  }
+  // A 304 has no body, so no encoding to declare.
+  if header(header::IF_NONE_MATCH).is_some_and(|value| etag_matches(value, &etag)) {
+    return response.status(StatusCode::NOT_MODIFIED).finish();
+  }
+  if let Some(encoding) = encoding {
+    response.insert_header((header::CONTENT_ENCODING, encoding));
+  }
+
+  // A range of an older version of the file would corrupt the client's copy.
+  let ranges = match header(header::RANGE) {
+    Some(_) if header(header::IF_RANGE).is_some_and(|value| value != etag) => Ranges::Full,
+    Some(value) => parse_ranges(value, len),
+    None => Ranges::Full,
+  };
+  let content_type = content_type(&path);
+  let segments = match ranges {
+    Ranges::Unsatisfiable => {
+      return HttpResponse::RangeNotSatisfiable()
+        .insert_header((header::CONTENT_RANGE, format!("bytes */{len}")))
+        .finish();
+    }
+    Ranges::Full => {
+      response.content_type(content_type);
+      vec![Segment::File(0..len)]
+    }
+    Ranges::Partial(ranges) => {
+      response.status(StatusCode::PARTIAL_CONTENT);
+      if let [range] = ranges.as_slice() {
+        response
+          .insert_header((header::CONTENT_RANGE, format!("bytes {}-{}/{len}", range.start, range.end - 1)))
+          .content_type(content_type);
+        vec![Segment::File(range.clone())]
+      } else {
+        let boundary = format!("snapfire-{}", etag.trim_matches('"'));
+        response.content_type(format!("multipart/byteranges; boundary={boundary}"));
+        let mut segments = Vec::with_capacity(ranges.len() * 3 + 1);
+        for range in ranges {
+          let part_header = format!(
+            "--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: bytes {}-{}/{len}\r\n\r\n",
+            range.start,
+            range.end - 1
+          );
+          segments.push(Segment::Text(Bytes::from(part_header)));
+          segments.push(Segment::File(range));
+          segments.push(Segment::Text(Bytes::from_static(b"\r\n")));
+        }
+        segments.push(Segment::Text(Bytes::from(format!("--{boundary}--\r\n"))));
+        segments
+      }
+    }
+  };
+
+  let size = segments.iter().map(Segment::len).sum();
+  let body = stream_segments(file, segments).inspect_err(move |e| log::error!("Failed to read asset {path}: {e}"));
+  response.body(SizedStream::new(size, body))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  #[allow(clippy::single_range_in_vec_init)]
+  fn test_parses_range_headers() {
+    assert_eq!(parse_ranges("bytes=0-99", 1000), Ranges::Partial(vec![0..100]));
+    assert_eq!(parse_ranges("bytes=900-", 1000), Ranges::Partial(vec![900..1000]));
+    assert_eq!(parse_ranges("bytes=-100", 1000), Ranges::Partial(vec![900..1000]));
+    assert_eq!(parse_ranges("bytes=-5000", 1000), Ranges::Partial(vec![0..1000]));
+    assert_eq!(parse_ranges("bytes=990-2000", 1000), Ranges::Partial(vec![990..1000]));
+    assert_eq!(parse_ranges("bytes=0-0, 10-19", 1000), Ranges::Partial(vec![0..1, 10..20]));
+    assert_eq!(parse_ranges("bytes=1000-", 1000), Ranges::Unsatisfiable);
+    assert_eq!(parse_ranges("bytes=5-1", 1000), Ranges::Full);
+    assert_eq!(parse_ranges("items=0-1", 1000), Ranges::Full);
+    assert_eq!(parse_ranges("bytes=abc", 1000), Ranges::Full);
+    assert_eq!(parse_ranges("bytes=50-59, 0-9, 5-19, 20-29", 1000), Ranges::Partial(vec![0..30, 50..60]));
+    assert_eq!(parse_ranges("bytes=0-99, 10-19", 1000), Ranges::Partial(vec![0..100]));
+    let many = (0..20).map(|i| format!("{}-{}", i * 2, i * 2)).collect::<Vec<_>>().join(",");
+    assert_eq!(parse_ranges(&format!("bytes={many}"), 1000), Ranges::Full);
+
+    assert!(accepts("gzip, deflate, br;q=1.0", "br"));
+    assert!(!accepts("gzip, br;q=0", "br"));
+    assert!(accepts("*", "gzip"));
+    assert!(!accepts("identity", "gzip"));
+    assert!(etag_matches("W/\"a\", \"b\"", "\"a\""));
+  }
+}
@@ -0,0 +1,302 @@
+use crate::core::app::TeraWeb;
+use crate::core::forms::DEFAULT_HONEYPOT_FIELD;
+use actix_web::{
+  Error, HttpMessage, HttpResponse,
+  dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+  error::{ErrorBadRequest, ErrorPayloadTooLarge, InternalError},
+  http::{Method, header},
+  web::{self, Bytes, BytesMut},
+};
+use futures_util::{
+  StreamExt,
+  future::{self, LocalBoxFuture},
+};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The largest form body `SpamGuard` reads by default.
+const DEFAULT_BODY_LIMIT: usize = 256 * 1024;
+
+/// How many clients the rate limiter tracks; past it, it forgets the oldest
+/// half.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// An Actix middleware that rejects spam form submissions.
+///
+/// It checks every request with a body (`POST`, `PUT`, `PATCH`, `DELETE`):
+///
+/// - URL-encoded forms that fill in the honeypot field rendered by the
+///   `honeypot_field()` template function are rejected with `400 Bad
+///   Request`. Other bodies, such as multipart uploads, are passed on as-is.
+/// - With `rate_limit`, clients (by IP address) that submit more than
+///   the limit within the window get `429 Too Many Requests` with a
+///   `Retry-After` header.
+///
+/// Wrap the resources that accept submissions, and create the guard outside
+/// the `HttpServer::new` closure so all workers share the rate limit:
+///
+/// ```rust,no_run
+/// # use actix_web::{App, HttpServer, web};
+/// # use snapfire::actix::forms::SpamGuard;
+/// # use std::time::Duration;
+/// # async fn post_comment() -> &'static str { "" }
+/// # async fn run() -> std::io::Result<()> {
+/// let guard = SpamGuard::new().rate_limit(5, Duration::from_secs(60));
+/// HttpServer::new(move || {
+///   App::new().service(web::resource("/comments").wrap(guard.clone()).route(web::post().to(post_comment)))
+/// })
+/// # ; Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SpamGuard {
+  honeypot_field: Option<String>,
+  rate_limit: Option<(u32, Duration)>,
+  body_limit: usize,
+  /// The submissions per client in the current window, shared by clones.
+  windows: Arc<Mutex<Windows>>,
+}
+
+/// The rate limiter's state.
+#[derive(Default)]
+struct Windows {
+  /// When each client's window started, and its submissions in it.
+  clients: HashMap<IpAddr, (Instant, u32)>,
+  /// When expired windows were last dropped.
+  pruned: Option<Instant>,
+}
+
+impl Default for SpamGuard {
+  fn default() -> Self {
+    Self {
+      honeypot_field: Some(DEFAULT_HONEYPOT_FIELD.to_string()),
+      rate_limit: None,
+      body_limit: DEFAULT_BODY_LIMIT,
+      windows: Arc::default(),
+    }
+  }
+}
+
+impl SpamGuard {
+  /// Checks the default honeypot field, without a rate limit.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets the name of the honeypot field, matching `honeypot_field(name=...)`.
+  ///
+  /// Defaults to `website`.
+  pub fn honeypot_field(mut self, name: &str) -> Self {
+    self.honeypot_field = Some(name.to_string());
+    self
+  }
+
+  /// Turns the honeypot check off, leaving only the rate limit.
+  pub fn without_honeypot(mut self) -> Self {
+    self.honeypot_field = None;
+    self
+  }
+
+  /// Allows each client `max` submissions per `window`.
+  ///
+  /// Clients are told apart by the IP address of the connection, or, with
+  /// the app's `TeraWeb` registered as `web::Data`, by the address its
+  /// `trusted_proxies` forwarded for. Behind any other proxy every client
+  /// counts as the proxy.
+  ///
+  /// Up to 10,000 clients are tracked; past that, the ones whose windows
+  /// started longest ago are forgotten, half at a time.
+  pub fn rate_limit(mut self, max: u32, window: Duration) -> Self {
+    self.rate_limit = Some((max, window));
+    self
+  }
+
+  /// Sets the largest form body read for the honeypot check; larger forms
+  /// are rejected with `413 Payload Too Large`.
+  ///
+  /// Defaults to 256 KiB.
+  pub fn body_limit(mut self, bytes: usize) -> Self {
+    self.body_limit = bytes;
+    self
+  }
+
+  /// Counts a submission from `ip`, returning how long until it may submit
+  /// again if it is over the limit.
+  fn retry_after(&self, ip: IpAddr, now: Instant) -> Option<Duration> {
+    let (max, window) = self.rate_limit?;
+    let mut windows = self.windows.lock();
+    // Expired windows are dropped at most once per window, and a map full of
+    // live ones loses its oldest half, so a client rotating through addresses
+    // costs each request amortized constant work and bounded memory.
+    if windows.pruned.is_none_or(|pruned| now.duration_since(pruned) >= window) {
+      windows.clients.retain(|_, (start, _)| now.duration_since(*start) < window);
+      windows.pruned = Some(now);
+    }
+    if windows.clients.len() >= MAX_TRACKED_CLIENTS && !windows.clients.contains_key(&ip) {
+      let mut starts: Vec<Instant> = windows.clients.values().map(|(start, _)| *start).collect();
+      let median = *starts.select_nth_unstable(MAX_TRACKED_CLIENTS / 2).1;
+      windows.clients.retain(|_, (start, _)| *start > median);
+    }
+    let (start, count) = windows.clients.entry(ip).or_insert((now, 0));
+    if now.duration_since(*start) >= window {
+      (*start, *count) = (now, 0);
+    }
+    if *count >= max {
+      return Some(window - now.duration_since(*start));
+    }
+    *count += 1;
+    None
+  }
+
+  /// Whether the URL-encoded `body` has a value for the honeypot field.
+  fn is_honeypot_filled(&self, body: &[u8]) -> bool {
+    let Some(field) = &self.honeypot_field else {
+      return false;
+    };
+    body.split(|&byte| byte == b'&').any(|pair| {
+      let (name, value) = match pair.iter().position(|&byte| byte == b'=') {
+        Some(at) => (&pair[..at], &pair[at + 1..]),
+        None => (pair, &[][..]),
+      };
+      !value.is_empty() && url_decode(name) == field.as_bytes()
+    })
+  }
+}
+
+/// Decodes a `application/x-www-form-urlencoded` name.
+fn url_decode(encoded: &[u8]) -> Vec<u8> {
+  let mut decoded = Vec::with_capacity(encoded.len());
+  let mut index = 0;
+  while index < encoded.len() {
+    let hex = encoded
+      .get(index + 1..index + 3)
+      .and_then(|hex| std::str::from_utf8(hex).ok())
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    match (encoded[index], hex) {
+      (b'+', _) => decoded.push(b' '),
+      (b'%', Some(byte)) => {
+        decoded.push(byte);
+        index += 2;
+      }
+      (byte, _) => decoded.push(byte),
+    }
+    index += 1;
+  }
+  decoded
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SpamGuard
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Transform = SpamGuardMiddleware<S>;
+  type InitError = ();
+  type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    future::ok(SpamGuardMiddleware {
+      service: Rc::new(service),
+      config: Rc::new(self.clone()),
+    })
+  }
+}
+
+pub struct SpamGuardMiddleware<S> {
+  service: Rc<S>,
+  config: Rc<SpamGuard>,
+}
+
+impl<S, B> Service<ServiceRequest> for SpamGuardMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, mut req: ServiceRequest) -> Self::Future {
+    let service = self.service.clone();
+    let config = self.config.clone();
+    Box::pin(async move {
+      if !matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH | Method::DELETE) {
+        return service.call(req).await;
+      }
+
+      // Behind trusted proxies, limit the client rather than the proxy.
+      let client_ip = match req.app_data::<web::Data<TeraWeb>>() {
+        Some(app) => app.request_origin(req.request()).client_ip,
+        None => req.peer_addr().map(|addr| addr.ip()),
+      };
+      if let Some(ip) = client_ip
+        && let Some(retry_after) = config.retry_after(ip, Instant::now())
+      {
+        log::info!("Rejecting a submission to {} from {}: rate limit exceeded", req.path(), ip);
+        let response = HttpResponse::TooManyRequests()
+          .insert_header((header::RETRY_AFTER, retry_after.as_secs_f64().ceil().to_string()))
+          .finish();
+        return Err(InternalError::from_response("Too many submissions", response).into());
+      }
+
+      let is_form = req.content_type() == "application/x-www-form-urlencoded";
+      if config.honeypot_field.is_some() && is_form {
+        let mut payload = req.take_payload();
+        let mut body = BytesMut::new();
+        while let Some(chunk) = payload.next().await {
+          let chunk = chunk?;
+          if body.len() + chunk.len() > config.body_limit {
+            return Err(ErrorPayloadTooLarge("Form body too large"));
+          }
+          body.extend_from_slice(&chunk);
+        }
+        if config.is_honeypot_filled(&body) {
+          log::info!("Rejecting a submission to {}: honeypot field filled in", req.path());
+          return Err(ErrorBadRequest("Submission rejected"));
+        }
+        req.set_payload(Payload::from(Bytes::from(body)));
+      }
+
+      service.call(req).await
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_honeypot_and_rate_limit() {
+    let guard = SpamGuard::new().honeypot_field("your url");
+    assert!(guard.is_honeypot_filled(b"body=hi&your+url=http%3A%2F%2Fspam"));
+    assert!(guard.is_honeypot_filled(b"your%20url=x"));
+    assert!(!guard.is_honeypot_filled(b"body=hi&your+url=&website=x"));
+    assert!(!SpamGuard::new().without_honeypot().is_honeypot_filled(b"website=x"));
+
+    let guard = SpamGuard::new().rate_limit(2, Duration::from_secs(60));
+    let (ip, other): (IpAddr, IpAddr) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+    let start = Instant::now();
+    assert_eq!(guard.retry_after(ip, start), None);
+    assert_eq!(guard.retry_after(ip, start + Duration::from_secs(10)), None);
+    assert_eq!(guard.retry_after(ip, start + Duration::from_secs(20)), Some(Duration::from_secs(40)));
+    assert_eq!(guard.clone().retry_after(other, start), None);
+    assert_eq!(guard.retry_after(ip, start + Duration::from_secs(60)), None);
+
+    // Clients past the cap push out the oldest ones, however recent.
+    for client in 0..MAX_TRACKED_CLIENTS as u32 {
+      let ip = IpAddr::from(std::net::Ipv4Addr::from(0x0a00_0000 + client));
+      guard.retry_after(ip, start + Duration::from_secs(61) + Duration::from_millis(client.into()));
+    }
+    assert!(guard.windows.lock().clients.len() <= MAX_TRACKED_CLIENTS / 2 + 1);
+    assert!(!guard.windows.lock().clients.contains_key(&ip));
+  }
+}
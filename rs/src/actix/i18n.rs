@@ -0,0 +1,95 @@
+use crate::core::{app::TeraWeb, i18n::Locale};
+use actix_web::{
+  Error, HttpMessage,
+  dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+  http::{Uri, uri::PathAndQuery},
+  web,
+};
+use futures_util::future::{self, LocalBoxFuture};
+use std::rc::Rc;
+
+/// An Actix middleware that routes locale-prefixed URLs.
+///
+/// For a request to `/fr/about`, it stores `Locale("fr")` in the request
+/// extensions and rewrites the path to `/about` before routing, so every route
+/// is declared once for all locales. Unprefixed paths get the default locale.
+/// Every `Template` rendered for the request exposes the locale as `locale`.
+///
+/// The locales come from `TeraWebBuilder::locales` on the `TeraWeb` app data;
+/// without them, requests pass through untouched.
+#[derive(Clone, Default)]
+pub struct LocalePrefix;
+
+impl<S, B> Transform<S, ServiceRequest> for LocalePrefix
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Transform = LocalePrefixMiddleware<S>;
+  type InitError = ();
+  type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    future::ok(LocalePrefixMiddleware {
+      service: Rc::new(service),
+    })
+  }
+}
+
+pub struct LocalePrefixMiddleware<S> {
+  service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for LocalePrefixMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, mut req: ServiceRequest) -> Self::Future {
+    let locales = req
+      .app_data::<web::Data<TeraWeb>>()
+      .and_then(|app| app.locales.clone());
+
+    if let Some(locales) = locales {
+      let path = req.path().to_string();
+      let (locale, rest) = locales.split_path(&path);
+      req.extensions_mut().insert(Locale(locale.to_string()));
+
+      if rest.len() != path.len() {
+        strip_prefix(&mut req, rest);
+      }
+    }
+
+    let service = self.service.clone();
+    Box::pin(async move { service.call(req).await })
+  }
+}
+
+/// Replaces the request path (keeping the query) and re-targets routing at it.
+fn strip_prefix(req: &mut ServiceRequest, path: &str) {
+  let head = req.head_mut();
+  let path_and_query = match head.uri.query() {
+    Some(query) => format!("{path}?{query}"),
+    None => path.to_string(),
+  };
+
+  let mut parts = head.uri.clone().into_parts();
+  let Ok(path_and_query) = PathAndQuery::try_from(path_and_query) else {
+    return;
+  };
+  parts.path_and_query = Some(path_and_query);
+  let Ok(uri) = Uri::from_parts(parts) else {
+    return;
+  };
+
+  req.match_info_mut().get_mut().update(&uri);
+  req.head_mut().uri = uri;
+}
@@ -1,71 +1,422 @@
-use crate::core::app::{Template, TeraWeb};
+use crate::core::{
+  app::{RequestParts, Template, TeraWeb},
+  consent::ConsentState,
+  context_ext::SnapFireContextExt,
+  env::RenderEnv,
+  flush,
+  icons::ICON_SPRITE_PATH,
+  i18n::Locale,
+  proxy::RequestOrigin,
+  response::{Caching, RenderedResponse, for_request, versioned},
+  rtl,
+  scope::ScopeValues,
+  vary::VaryOn,
+};
 use actix_web::{
-  HttpRequest, HttpResponse, Responder,
+  HttpMessage, HttpRequest, HttpResponse, Responder,
   body::BoxBody,
-  http::{StatusCode, header::ContentType},
-  web,
+  cookie::Cookie,
+  http::{
+    StatusCode,
+    header::{self, ContentType},
+  },
   web::ServiceConfig,
 };
-use futures_util::stream;
+#[cfg(feature = "devel")]
+use crate::core::fixtures::PreviewError;
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use request_id::RequestId;
+use security::CspNonce;
+use serde::Serialize;
+use std::sync::Arc;
+#[cfg(feature = "devel")]
+use std::collections::HashMap;
 
+pub mod consent;
 pub mod dev;
+mod files;
+pub mod forms;
+pub mod i18n;
+mod pages;
+pub mod request_id;
+pub mod security;
+pub mod signed;
+#[cfg(feature = "uploads")]
+pub mod uploads;
+
+pub use pages::{UserRoles, pages_handler};
 
 impl Responder for Template {
   type Body = BoxBody;
 
-  fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+  fn respond_to(mut self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+    // The request headers the page depends on, and whether it holds request
+    // data of unknown origin, for the caching headers.
+    let recorded_vary = req.extensions().get::<VaryOn>().cloned();
+    let mut unknown_origin = req.extensions().contains::<RenderEnv>();
+    let mut vary = recorded_vary.clone().unwrap_or_default();
+
+    // Layer the handler's context over the values added by middleware.
+    if let Some(extension) = req.extensions().get::<SnapFireContextExt>() {
+      self.context = extension.merged_with(self.context);
+      self.sensitive.extend(extension.sensitive.iter().cloned());
+      unknown_origin = true;
+    }
+    // Expose the consent state set by the `CookieConsent` middleware, unless
+    // the handler already provided its own.
+    if let Some(consent) = req.extensions().get::<ConsentState>()
+      && !self.context.contains_key("consent")
+    {
+      self.context.insert("consent", consent);
+      vary.header("Cookie");
+    }
+    // Scripts and styles carrying the `SecurityHeaders` nonce may run.
+    let nonce = req.extensions().get::<CspNonce>().cloned();
+    if let Some(nonce) = &nonce
+      && !self.context.contains_key("csp_nonce")
+    {
+      self.context.insert("csp_nonce", nonce.as_str());
+    }
+    // The environment resolved by middleware, with the locale `LocalePrefix`
+    // detected unless it names its own.
+    let mut env = req.extensions().get::<RenderEnv>().cloned();
+    if let Some(Locale(locale)) = req.extensions().get::<Locale>() {
+      env.get_or_insert_default().locale.get_or_insert_with(|| locale.clone());
+    }
+    if let Some(locale) = env.as_ref().and_then(|env| env.locale.as_deref())
+      && !self.context.contains_key("locale")
+    {
+      self.context.insert("locale", locale);
+    }
+    // Layouts can write `<html dir="{{ dir }}">` without per-locale branches.
+    let direction = self.context.get("locale").and_then(|locale| locale.as_str()).map(rtl::text_direction);
+    if let Some(direction) = direction
+      && !self.context.contains_key("dir")
+    {
+      self.context.insert("is_rtl", &(direction == "rtl"));
+      self.context.insert("dir", direction);
+    }
+
+    // Values middleware attached for the app's filters and functions.
+    let values = req.extensions().get::<ScopeValues>().cloned();
+    // The `RequestIds` id, for error pages and logs.
+    let request_id = req.extensions().get::<RequestId>().cloned();
+    if let Some(id) = &request_id
+      && !self.context.contains_key("request_id")
+    {
+      self.context.insert("request_id", id.as_str());
+    }
+
+    // Absolute URLs use the client's scheme and host, even behind a proxy.
+    let origin = self.app_state.request_origin(req);
+    if !self.context.contains_key("canonical_url") {
+      self.context.insert("canonical_url", &format!("{}{}", origin.url(), req.path()));
+    }
+
+    let caching = Caching {
+      vary,
+      private: unknown_origin && recorded_vary.is_none(),
+    };
+
+    // Render on a blocking thread, sending the page as it's produced.
+    if self.app_state.early_flush && self.prerendered.is_none() {
+      let (status, headers) = self.head(caching);
+      let mut response = HttpResponse::build(StatusCode::from_u16(status).unwrap_or(StatusCode::OK));
+      response.content_type(ContentType::html());
+      for (name, value) in headers {
+        response.append_header((name, value));
+      }
+      let (sender, chunks) = flush::chunk_channel();
+      let (host, path, nonce) = (origin.host, req.path().to_string(), nonce);
+      let logged_id = request_id.clone();
+      actix_web::rt::task::spawn_blocking(move || {
+        let request = RequestParts {
+          host: Some(&host),
+          path: Some(&path),
+          nonce: nonce.as_ref().map(CspNonce::as_str),
+          env: env.as_ref(),
+          values: values.as_ref(),
+          request_id: request_id.as_ref().map(RequestId::as_str),
+          sensitive: &self.sensitive,
+        };
+        let result = self
+          .app_state
+          .render_flushing_for_request(&self.template_name, request, self.context, |chunk| sender.send(chunk));
+        sender.finish(result);
+      });
+      return response.streaming(chunks.map_err(move |e| {
+        log::error!("Template rendering error{}: {:?}", for_request(logged_id.as_ref().map(RequestId::as_str)), e);
+        actix_web::error::ErrorInternalServerError(e)
+      }));
+    }
+
     // This is a synchronous call, as required.
-    let result = self.app_state.render_with_context(&self.template_name, self.context);
-
-    // Create a single-item stream that will resolve immediately with the result.
-    let body_stream = stream::once(async {
-      result
-        .map(|s| s.into()) // Convert String to Bytes
-        .map_err(|e| {
-          log::error!("Template rendering error: {:?}", e);
-          // Convert our internal error into an Actix-compatible error.
-          actix_web::error::ErrorInternalServerError(e)
-        })
-    });
-
-    // Construct the response.
-    HttpResponse::build(StatusCode::OK)
-      .content_type(ContentType::html())
-      .streaming(body_stream)
+    let request = RequestParts {
+      host: Some(&origin.host),
+      path: Some(req.path()),
+      nonce: nonce.as_ref().map(CspNonce::as_str),
+      env: env.as_ref(),
+      values: values.as_ref(),
+      request_id: request_id.as_ref().map(RequestId::as_str),
+      ..RequestParts::default()
+    };
+    rendered_response(self.into_rendered_for(request, caching))
+  }
+}
+
+#[cfg(feature = "pdf")]
+impl Responder for crate::core::pdf::Pdf {
+  type Body = BoxBody;
+
+  fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+    let mut response = HttpResponse::Ok();
+    response.content_type("application/pdf");
+    if let Some(filename) = &self.filename {
+      response.insert_header(header::ContentDisposition::attachment(filename.as_str()));
+    }
+    response.body(self.bytes)
+  }
+}
+
+#[cfg(feature = "screenshot")]
+impl Responder for crate::core::screenshot::Screenshot {
+  type Body = BoxBody;
+
+  fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+    HttpResponse::Ok().content_type("image/png").body(self.bytes)
+  }
+}
+
+impl Template {
+  /// Sends `cookie` with the page, in a `Set-Cookie` header of its own.
+  ///
+  /// # Arguments
+  ///
+  /// * `cookie` - The cookie, e.g. `Cookie::build("theme", "dark").path("/").finish()`.
+  pub fn add_cookie(self, cookie: Cookie<'_>) -> Self {
+    self.append_header("set-cookie", &cookie.to_string())
+  }
+}
+
+impl SnapFireContextExt {
+  /// Adds a value to the request's `SnapFireContextExt`, creating it on first
+  /// use. Call it from middleware with the `ServiceRequest`, or from a
+  /// handler with the `HttpRequest`.
+  pub fn insert_into<R, T>(req: &R, key: &str, value: &T)
+  where
+    R: HttpMessage,
+    T: Serialize + ?Sized,
+  {
+    let mut extensions = req.extensions_mut();
+    match extensions.get_mut::<SnapFireContextExt>() {
+      Some(extension) => {
+        extension.insert(key, value);
+      }
+      None => {
+        let mut extension = SnapFireContextExt::new();
+        extension.insert(key, value);
+        extensions.insert(extension);
+      }
+    }
+  }
+}
+
+impl VaryOn {
+  /// Adds `header` to the request's `VaryOn`, creating it on first use.
+  /// Call it from the middleware deriving render data from the header.
+  pub fn record<R: HttpMessage>(req: &R, header: &str) {
+    req.extensions_mut().get_or_insert_with(VaryOn::new).header(header);
   }
 }
 
-// This block adds the `configure_routes` method.
-// It is gated by the `devel` feature.
-#[cfg(feature = "devel")]
 impl TeraWeb {
-  /// Configures Actix services needed by SnapFire for development.
+  /// The scheme, host, and client address of `req`, from the forwarding
+  /// headers if the peer is one of the builder's `trusted_proxies`.
+  pub fn request_origin(&self, req: &HttpRequest) -> RequestOrigin {
+    let config = req.app_config();
+    let scheme = req.uri().scheme_str().unwrap_or(if config.secure() { "https" } else { "http" });
+    let host = req
+      .headers()
+      .get(header::HOST)
+      .and_then(|host| host.to_str().ok())
+      .or_else(|| req.uri().authority().map(|authority| authority.as_str()))
+      .unwrap_or_else(|| config.host());
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    self
+      .trusted_proxies
+      .resolve(peer, scheme, host, |name| req.headers().get(name).and_then(|value| value.to_str().ok()))
+  }
+
+  /// Configures the Actix services SnapFire needs.
+  ///
+  /// This serves the search index (when `search_index` is configured), the
+  /// icon sprite (when `icons_dir` is configured) and, in
+  /// dev mode, adds the WebSocket route handler for live reloading at the
+  /// `ws_path` set in the builder, the fixture previews under
+  /// `/_snapfire/preview/`, and the styleguide when configured. Without
+  /// either, this is a no-op, so user code doesn't need `#[cfg]` attributes
+  /// around it.
   ///
-  /// Currently, this adds the WebSocket route handler for live reloading.
-  /// The route is determined by the `ws_path` set in the builder.
+  /// The routes can be mounted inside a scope, e.g.
+  /// `web::scope("/app").configure(|cfg| app.configure_routes(cfg))`; the
+  /// injected live-reload client then connects to `/app` + `ws_path`.
   pub fn configure_routes(&self, cfg: &mut ServiceConfig) {
+    if let Some(search) = &self.search {
+      let app = self.clone();
+      cfg.route(
+        &search.path,
+        actix_web::web::get().to(move || {
+          let result = app.search_index_json();
+          async move {
+            match result {
+              Ok(json) => HttpResponse::Ok().content_type(ContentType::json()).body(json),
+              Err(e) => {
+                log::error!("Search index error: {:?}", e);
+                HttpResponse::InternalServerError().finish()
+              }
+            }
+          }
+        }),
+      );
+    }
+
+    if let Some(icons) = &self.icons {
+      let icons = Arc::clone(icons);
+      cfg.route(
+        ICON_SPRITE_PATH,
+        actix_web::web::get().to(move |req: HttpRequest| {
+          let sprite = icons.sprite();
+          async move { versioned_response(&req, &sprite.version, "image/svg+xml", sprite.body.clone()) }
+        }),
+      );
+    }
+
+    if let Some(files) = &self.asset_files {
+      let files = Arc::clone(files);
+      cfg.route(
+        &format!("{}{{path:.+}}", files.prefix()),
+        actix_web::web::route()
+          .guard(actix_web::guard::Any(actix_web::guard::Get()).or(actix_web::guard::Head()))
+          .to(move |req: HttpRequest| files::serve_asset(req, Arc::clone(&files))),
+      );
+    }
+
+    #[cfg(feature = "devel")]
+    self.configure_dev_routes(cfg);
+  }
+
+  #[cfg(feature = "devel")]
+  fn configure_dev_routes(&self, cfg: &mut ServiceConfig) {
     log::info!(
       "🔥 SnapFire devel enabled. Attaching WebSocket at {}",
-      self.reloader.ws_path
+      self.reloader.options.ws_path
     );
 
     let broadcaster = self.get_reloader_broadcaster();
+    let options = &self.reloader.options;
+    let ws_path = options.ws_path.clone();
+    let heartbeat = crate::core::reload::Heartbeat {
+      interval: options.heartbeat_interval,
+      timeout: options.client_timeout,
+    };
 
+    // Named, so the client can find the routes inside whatever scope they're
+    // mounted in.
+    cfg.service(
+      actix_web::web::resource(&options.ws_path)
+        .name(dev::WS_ROUTE)
+        .get(move |req, stream| {
+          // We clone the broadcaster for each new connection.
+          dev::ws::websocket_handler(req, stream, broadcaster.clone(), heartbeat)
+        }),
+    );
+    if let Some(poke_path) = &options.poke_path {
+      let sink = self.change_sink();
+      cfg.route(
+        poke_path,
+        actix_web::web::post().to(move |req: HttpRequest| {
+          let kind = req
+            .query_string()
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("kind="))
+            .unwrap_or("page");
+          let response = match kind {
+            "templates" => {
+              sink.templates_changed();
+              HttpResponse::NoContent().finish()
+            }
+            "css" => {
+              sink.css_changed();
+              HttpResponse::NoContent().finish()
+            }
+            "page" => {
+              sink.page_changed();
+              HttpResponse::NoContent().finish()
+            }
+            _ => HttpResponse::BadRequest().body("`kind` must be `templates`, `css`, or `page`"),
+          };
+          async move { response }
+        }),
+      );
+    }
+    let app = self.clone();
+    cfg.route(
+      &format!("{}/{{template:.+}}", crate::core::fixtures::PREVIEW_PATH),
+      actix_web::web::get().to(move |req: HttpRequest, query: actix_web::web::Query<HashMap<String, String>>| {
+        let template = req.match_info().get("template").unwrap_or_default().to_string();
+        let response = match app.preview_context(&template, query.get("fixture").map(String::as_str)) {
+          Ok(context) => app.render(&template, context).respond_to(&req),
+          Err(PreviewError::NotFound(message)) => HttpResponse::NotFound().body(message),
+          Err(PreviewError::Invalid(message)) => HttpResponse::InternalServerError().body(message),
+        };
+        async move { response }
+      }),
+    );
+    if let Some(styleguide) = &options.styleguide {
+      let app = self.clone();
+      let styleguide = styleguide.clone();
+      cfg.route(
+        crate::core::styleguide::STYLEGUIDE_PATH,
+        actix_web::web::get().to(move || {
+          let html = app.render_styleguide(&styleguide);
+          async move { HttpResponse::Ok().content_type(ContentType::html()).body(html) }
+        }),
+      );
+    }
+    let app = self.clone();
     cfg.route(
-      &self.reloader.ws_path,
-      web::get().to(move |req, stream| {
-        // We clone the broadcaster for each new connection.
-        dev::ws::websocket_handler(req, stream, broadcaster.clone())
+      crate::core::lsp::LSP_PATH,
+      actix_web::web::get().to(move || {
+        let metadata = app.editor_metadata();
+        async move { HttpResponse::Ok().insert_header((header::CACHE_CONTROL, "no-store")).json(metadata) }
       }),
     );
+    cfg.service(
+      actix_web::web::resource(dev::CLIENT_SCRIPT_PATH)
+        .name(dev::CLIENT_SCRIPT_ROUTE)
+        .get(move |req: HttpRequest| {
+          let response = dev::ClientScript::for_request(&req, &ws_path).respond(&req);
+          async move { response }
+        }),
+    );
   }
 }
 
-#[cfg(not(feature = "devel"))]
-impl TeraWeb {
-  /// In release builds, this is a no-op that allows user code to compile
-  /// without having to add `#[cfg]` attributes.
-  pub fn configure_routes(&self, _cfg: &mut ServiceConfig) {
-    // Does nothing.
+/// Serves an asset with a content hash `version`: cacheable forever when
+/// requested at its versioned URL (`?v=<version>`), revalidated against the
+/// ETag otherwise.
+pub(crate) fn versioned_response(req: &HttpRequest, version: &str, content_type: &str, body: Bytes) -> HttpResponse {
+  let if_none_match = req.headers().get(header::IF_NONE_MATCH).and_then(|value| value.to_str().ok());
+  rendered_response(versioned(version, content_type, body, req.query_string(), if_none_match))
+}
+
+/// Converts a [`RenderedResponse`] into an Actix response.
+pub(crate) fn rendered_response(rendered: RenderedResponse) -> HttpResponse {
+  let mut response = HttpResponse::build(StatusCode::from_u16(rendered.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR));
+  response.content_type(rendered.content_type);
+  for (name, value) in rendered.headers {
+    response.append_header((name, value));
   }
+  response.body(rendered.body)
 }
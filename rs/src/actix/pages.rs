@@ -0,0 +1,115 @@
+use crate::core::app::TeraWeb;
+use actix_web::{
+  HttpMessage, HttpRequest, HttpResponse, Responder, Route,
+  http::{Method, header},
+  web,
+};
+use tera::Context;
+
+/// A catch-all handler rendering the template under `dir` that matches the
+/// request path, so pages without data need no handler of their own.
+///
+/// `GET /about/team` renders `{dir}/about/team.html`, or else
+/// `{dir}/about/team/index.html`; `/` renders `{dir}/index.html`. Pages render
+/// like `TeraWeb::render` with an empty context, so they get the globals and
+/// the request's context (locale, values from middleware). Paths without a
+/// template, and paths with a segment starting with `.` or `_` (partials by
+/// convention), are answered with 404.
+///
+/// The page's front matter is honored: `cache` sets the `Cache-Control`
+/// header, and a page with a `role` is only served to requests whose
+/// [`UserRoles`] include it, and answered with 403 otherwise.
+///
+/// Register it after the app's own routes, as the default service or on a
+/// catch-all route; inside a scope, use a `path` segment so the page is
+/// looked up relative to the scope:
+///
+/// ```rust,no_run
+/// # use actix_web::{App, web};
+/// # fn app(snapfire: snapfire::TeraWeb) {
+/// App::new()
+///   .app_data(web::Data::new(snapfire))
+///   .service(web::scope("/docs").route("/{path:.*}", snapfire::actix::pages_handler("docs")))
+///   .default_service(snapfire::actix::pages_handler("pages"));
+/// # }
+/// ```
+///
+/// The `TeraWeb` app data must be registered; the builder's
+/// `template_resolver` rules apply on top of the `.html` convention.
+pub fn pages_handler(dir: &str) -> Route {
+  let dir = dir.trim_matches('/').to_string();
+  web::route().to(move |req: HttpRequest| {
+    let dir = dir.clone();
+    async move { render_page(&req, &dir) }
+  })
+}
+
+fn render_page(req: &HttpRequest, dir: &str) -> HttpResponse {
+  if req.method() != Method::GET && req.method() != Method::HEAD {
+    return HttpResponse::MethodNotAllowed().insert_header(("Allow", "GET, HEAD")).finish();
+  }
+  let Some(app) = req.app_data::<web::Data<TeraWeb>>() else {
+    log::error!("pages_handler needs the TeraWeb app data to be registered");
+    return HttpResponse::InternalServerError().finish();
+  };
+
+  let path = req.match_info().get("path").unwrap_or_else(|| req.path());
+  let Some(template) = page_template(app, dir, path) else {
+    return HttpResponse::NotFound().finish();
+  };
+
+  let front_matter = app.front_matter(&template).unwrap_or_default();
+  if let Some(role) = front_matter.role() {
+    let allowed = req
+      .extensions()
+      .get::<UserRoles>()
+      .is_some_and(|roles| roles.0.iter().any(|granted| granted == role));
+    if !allowed {
+      return HttpResponse::Forbidden().finish();
+    }
+  }
+
+  let mut response = app.render(&template, Context::new()).respond_to(req);
+  if let Some(cache) = front_matter.cache()
+    && let Ok(value) = header::HeaderValue::from_str(cache)
+  {
+    response.headers_mut().insert(header::CACHE_CONTROL, value);
+  }
+  response
+}
+
+/// The roles of the request's user, for pages whose front matter requires
+/// a `role`. Authentication middleware inserts it into the request
+/// extensions:
+///
+/// ```rust,no_run
+/// # use actix_web::HttpMessage;
+/// # fn authenticate(req: &actix_web::dev::ServiceRequest) {
+/// req.extensions_mut().insert(snapfire::actix::UserRoles(vec!["staff".to_string()]));
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserRoles(pub Vec<String>);
+
+/// The template for the page at `path`, if there is one.
+fn page_template(app: &TeraWeb, dir: &str, path: &str) -> Option<String> {
+  let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+  if segments
+    .iter()
+    .any(|segment| segment.starts_with(['.', '_']) || segment.contains(['\\', '%']))
+  {
+    return None;
+  }
+
+  let base = std::iter::once(dir)
+    .chain(segments)
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join("/");
+  let candidates = match base.as_str() {
+    "" => vec!["index.html".to_string()],
+    _ if path.ends_with('/') => vec![format!("{base}/index.html")],
+    _ => vec![format!("{base}.html"), format!("{base}/index.html")],
+  };
+  candidates.iter().find_map(|candidate| app.resolve_template(candidate))
+}
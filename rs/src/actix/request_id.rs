@@ -0,0 +1,180 @@
+use actix_web::{
+  Error, HttpMessage,
+  cookie::Key,
+  dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+  http::header::{HeaderName, HeaderValue},
+};
+use futures_util::future::{self, LocalBoxFuture};
+use std::{fmt::Write as _, rc::Rc};
+
+/// The header [`RequestIds`] reads and echoes by default.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The longest incoming request id that is kept; longer ones are replaced.
+const MAX_INCOMING_LEN: usize = 128;
+
+/// The id of the current request, in the request extensions.
+///
+/// Set by [`RequestIds`]; every `Template` rendered for the request exposes
+/// it as `request_id`, filters and functions read it from
+/// `RenderScope::request_id`, and SnapFire's render error logs name it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+  /// A new random id: 32 hex digits.
+  fn generate() -> Self {
+    let key = Key::generate();
+    let mut id = String::with_capacity(32);
+    for byte in &key.signing()[..16] {
+      let _ = write!(id, "{byte:02x}");
+    }
+    Self(id)
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl std::fmt::Display for RequestId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+/// An Actix middleware giving every request an id, so a report about a page
+/// can be traced to the render that produced it.
+///
+/// The id comes from the request's `X-Request-Id` header when a proxy or
+/// load balancer already assigned one (printable ASCII, up to 128
+/// characters), and is generated otherwise. It's stored as a [`RequestId`]
+/// in the request extensions; templates show it with `{{ request_id }}`,
+/// e.g. in an error page's "quote this id" line.
+#[derive(Debug, Clone)]
+pub struct RequestIds {
+  header: HeaderName,
+  trust_incoming: bool,
+  echo: bool,
+}
+
+impl Default for RequestIds {
+  fn default() -> Self {
+    Self {
+      header: HeaderName::from_static(REQUEST_ID_HEADER),
+      trust_incoming: true,
+      echo: false,
+    }
+  }
+}
+
+impl RequestIds {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Reads and echoes the id in another header, e.g. `x-correlation-id`.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The header's name.
+  ///
+  /// # Panics
+  ///
+  /// If `name` isn't a valid header name.
+  pub fn header(mut self, name: &str) -> Self {
+    self.header = HeaderName::try_from(name).expect("invalid header name");
+    self
+  }
+
+  /// Whether to keep the id an incoming request carries. Defaults to
+  /// `true`; turn it off when clients reach the app directly, so they can't
+  /// choose the ids in your logs.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to keep incoming ids.
+  pub fn trust_incoming(mut self, enabled: bool) -> Self {
+    self.trust_incoming = enabled;
+    self
+  }
+
+  /// Whether to send the id back in the response header, unless the
+  /// handler set it. Defaults to `false`.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to echo the id.
+  pub fn echo(mut self, enabled: bool) -> Self {
+    self.echo = enabled;
+    self
+  }
+
+  /// The usable id `req` carries, if incoming ids are trusted.
+  fn incoming(&self, req: &ServiceRequest) -> Option<RequestId> {
+    if !self.trust_incoming {
+      return None;
+    }
+    let value = req.headers().get(&self.header)?.to_str().ok()?.trim();
+    let usable = !value.is_empty() && value.len() <= MAX_INCOMING_LEN && value.bytes().all(|byte| byte.is_ascii_graphic());
+    usable.then(|| RequestId(value.to_string()))
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIds
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Transform = RequestIdsMiddleware<S>;
+  type InitError = ();
+  type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    future::ok(RequestIdsMiddleware {
+      service: Rc::new(service),
+      config: Rc::new(self.clone()),
+    })
+  }
+}
+
+pub struct RequestIdsMiddleware<S> {
+  service: Rc<S>,
+  config: Rc<RequestIds>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdsMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let id = self.config.incoming(&req).unwrap_or_else(RequestId::generate);
+    let echo = self
+      .config
+      .echo
+      .then(|| HeaderValue::try_from(id.as_str()).ok())
+      .flatten()
+      .map(|value| (self.config.header.clone(), value));
+    req.extensions_mut().insert(id);
+
+    let service = self.service.clone();
+    Box::pin(async move {
+      let mut response = service.call(req).await?;
+      if let Some((name, value)) = echo
+        && !response.headers().contains_key(&name)
+      {
+        response.headers_mut().insert(name, value);
+      }
+      Ok(response)
+    })
+  }
+}
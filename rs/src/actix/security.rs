@@ -0,0 +1,315 @@
+use crate::core::app::TeraWeb;
+use actix_web::{
+  Error, HttpMessage,
+  cookie::Key,
+  dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+  http::header::{self, HeaderMap, HeaderName, HeaderValue},
+  web,
+};
+use futures_util::future::{self, LocalBoxFuture};
+use std::{fmt::Write as _, rc::Rc, time::Duration};
+
+/// The request's Content Security Policy nonce, in the request extensions.
+///
+/// Set by [`SecurityHeaders`]; every `Template` rendered for the request
+/// exposes it as `csp_nonce`, for `<script nonce="{{ csp_nonce }}">`.
+#[derive(Debug, Clone)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+  /// A new random nonce.
+  fn generate() -> Self {
+    let key = Key::generate();
+    let mut nonce = String::with_capacity(32);
+    for byte in &key.signing()[..16] {
+      let _ = write!(nonce, "{byte:02x}");
+    }
+    Self(nonce)
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+/// The preset of [`SecurityHeaders`]; see there.
+pub fn security_headers() -> SecurityHeaders {
+  SecurityHeaders::default()
+}
+
+/// An Actix middleware that adds security headers to every response,
+/// including a Content Security Policy that permits what SnapFire emits.
+///
+/// The preset sends:
+///
+/// - `Content-Security-Policy: default-src 'self'; script-src 'self'
+///   'nonce-…'; style-src 'self' 'nonce-…'; img-src 'self'; object-src
+///   'none'; base-uri 'self'; form-action 'self'; frame-ancestors 'none'`,
+///   with a fresh nonce per request (see [`CspNonce`]);
+/// - `X-Content-Type-Options: nosniff`, `X-Frame-Options: DENY`,
+///   `Referrer-Policy: strict-origin-when-cross-origin`,
+///   `Cross-Origin-Opener-Policy: same-origin`, and a `Permissions-Policy`
+///   denying the camera, microphone, geolocation, and payments.
+///
+/// With the app's `TeraWeb` registered as `web::Data`, the policy also
+/// allows what its helpers emit: the `avatar` function's `data:` and
+/// Gravatar images, KaTeX `style` attributes with `render_math`, and in dev
+/// mode the live-reload WebSocket. SnapFire's own dev pages (styleguide,
+/// accessibility audit, outline) are served without a policy. The scripts
+/// the engine injects (live reload, `timeago_script`) carry the nonce.
+/// Headers a handler already set are left alone.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+  directives: Vec<(String, Vec<String>)>,
+  headers: Vec<(HeaderName, HeaderValue)>,
+  report_only: bool,
+}
+
+impl Default for SecurityHeaders {
+  fn default() -> Self {
+    let directives = [
+      ("default-src", "'self'"),
+      ("script-src", "'self'"),
+      ("style-src", "'self'"),
+      ("img-src", "'self'"),
+      ("object-src", "'none'"),
+      ("base-uri", "'self'"),
+      ("form-action", "'self'"),
+      ("frame-ancestors", "'none'"),
+    ];
+    let headers = [
+      (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+      (header::X_FRAME_OPTIONS, "DENY"),
+      (header::REFERRER_POLICY, "strict-origin-when-cross-origin"),
+      (header::CROSS_ORIGIN_OPENER_POLICY, "same-origin"),
+      (
+        HeaderName::from_static("permissions-policy"),
+        "camera=(), microphone=(), geolocation=(), payment=()",
+      ),
+    ];
+    Self {
+      directives: directives
+        .into_iter()
+        .map(|(name, source)| (name.to_string(), vec![source.to_string()]))
+        .collect(),
+      headers: headers
+        .into_iter()
+        .map(|(name, value)| (name, HeaderValue::from_static(value)))
+        .collect(),
+      report_only: false,
+    }
+  }
+}
+
+impl SecurityHeaders {
+  /// Allows `source` in the CSP `directive`, adding the directive if the
+  /// policy has none.
+  ///
+  /// # Arguments
+  ///
+  /// * `directive` - The directive, e.g. `"img-src"`.
+  /// * `source` - The source to allow, e.g. `"https://images.example.com"`.
+  pub fn csp_source(mut self, directive: &str, source: &str) -> Self {
+    let sources = self.directive_mut(directive);
+    if !sources.iter().any(|existing| existing == source) {
+      sources.push(source.to_string());
+    }
+    self
+  }
+
+  /// Replaces the sources of the CSP `directive`; an empty list removes it.
+  ///
+  /// The nonce is still added to `script-src` and `style-src`.
+  ///
+  /// # Arguments
+  ///
+  /// * `directive` - The directive, e.g. `"frame-ancestors"`.
+  /// * `sources` - Its sources, e.g. `&["'self'", "https://partner.example.com"]`.
+  pub fn csp_directive(mut self, directive: &str, sources: &[&str]) -> Self {
+    if sources.is_empty() {
+      self.directives.retain(|(name, _)| name != directive);
+    } else {
+      *self.directive_mut(directive) = sources.iter().map(|source| source.to_string()).collect();
+    }
+    self
+  }
+
+  /// Sends the policy as `Content-Security-Policy-Report-Only`, to try it
+  /// without blocking anything.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether violations are only reported.
+  pub fn report_only(mut self, enabled: bool) -> Self {
+    self.report_only = enabled;
+    self
+  }
+
+  /// Sets a header of the preset, or removes it with `None`.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The header, e.g. `"x-frame-options"`.
+  /// * `value` - Its value, e.g. `Some("SAMEORIGIN")`.
+  ///
+  /// # Panics
+  ///
+  /// If `name` or `value` isn't a valid header name or value.
+  pub fn header(mut self, name: &str, value: Option<&str>) -> Self {
+    let name = HeaderName::try_from(name).expect("invalid header name");
+    self.headers.retain(|(existing, _)| *existing != name);
+    if let Some(value) = value {
+      self.headers.push((name, HeaderValue::try_from(value).expect("invalid header value")));
+    }
+    self
+  }
+
+  /// Sends `Strict-Transport-Security` with `max_age` and
+  /// `includeSubDomains`. Off by default, since it pins browsers to HTTPS.
+  ///
+  /// # Arguments
+  ///
+  /// * `max_age` - How long browsers only use HTTPS for the site.
+  pub fn hsts(self, max_age: Duration) -> Self {
+    let value = format!("max-age={}; includeSubDomains", max_age.as_secs());
+    self.header(header::STRICT_TRANSPORT_SECURITY.as_str(), Some(&value))
+  }
+
+  fn directive_mut(&mut self, directive: &str) -> &mut Vec<String> {
+    let index = match self.directives.iter().position(|(name, _)| name == directive) {
+      Some(index) => index,
+      None => {
+        self.directives.push((directive.to_string(), Vec::new()));
+        self.directives.len() - 1
+      }
+    };
+    &mut self.directives[index].1
+  }
+
+  /// The policy for a request with `nonce`, adding the sources `app` needs.
+  fn policy(&self, req: &ServiceRequest, nonce: &CspNonce) -> String {
+    let mut directives = self.directives.clone();
+    let mut allow = |directive: &str, source: String| match directives.iter_mut().find(|(name, _)| name == directive) {
+      Some((_, sources)) if !sources.contains(&source) => sources.push(source),
+      Some(_) => {}
+      None => {
+        // A missing fetch directive falls back to `default-src`, so start
+        // from its sources to only widen the policy.
+        let mut sources = match directives.iter().find(|(name, _)| name == "default-src") {
+          Some((_, sources)) if directive.ends_with("-src") => sources.clone(),
+          _ => Vec::new(),
+        };
+        sources.push(source);
+        directives.push((directive.to_string(), sources));
+      }
+    };
+    for directive in ["script-src", "style-src"] {
+      allow(directive, format!("'nonce-{}'", nonce.as_str()));
+    }
+
+    if let Some(app) = req.app_data::<web::Data<TeraWeb>>() {
+      for (directive, source) in app.csp_sources.iter() {
+        allow(directive, source.to_string());
+      }
+      // The live-reload client connects back to the page's host.
+      #[cfg(feature = "devel")]
+      {
+        let origin = app.request_origin(req.request());
+        let scheme = if origin.is_secure() { "wss" } else { "ws" };
+        allow("connect-src", format!("{scheme}://{}", origin.host));
+      }
+    }
+
+    directives
+      .iter()
+      .filter(|(_, sources)| !sources.is_empty())
+      .map(|(name, sources)| format!("{name} {}", sources.join(" ")))
+      .collect::<Vec<_>>()
+      .join("; ")
+  }
+}
+
+/// The dev pages SnapFire serves itself, which use inline styles without
+/// the nonce.
+#[cfg(feature = "devel")]
+fn is_dev_page(path: &str) -> bool {
+  [
+    crate::core::a11y::A11Y_PATH,
+    crate::core::a11y::OUTLINE_PATH,
+    crate::core::styleguide::STYLEGUIDE_PATH,
+    crate::core::replay::REPLAY_PATH,
+  ]
+  .contains(&path)
+}
+
+#[cfg(not(feature = "devel"))]
+fn is_dev_page(_path: &str) -> bool {
+  false
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeaders
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Transform = SecurityHeadersMiddleware<S>;
+  type InitError = ();
+  type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    future::ok(SecurityHeadersMiddleware {
+      service: Rc::new(service),
+      config: Rc::new(self.clone()),
+    })
+  }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+  service: Rc<S>,
+  config: Rc<SecurityHeaders>,
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let nonce = CspNonce::generate();
+    let policy = (!is_dev_page(req.path())).then(|| self.config.policy(&req, &nonce));
+    req.extensions_mut().insert(nonce);
+
+    let config = self.config.clone();
+    let service = self.service.clone();
+    Box::pin(async move {
+      let mut response = service.call(req).await?;
+      let headers = response.headers_mut();
+      let csp_header = if config.report_only {
+        header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+      } else {
+        header::CONTENT_SECURITY_POLICY
+      };
+      if let Some(policy) = policy.and_then(|policy| HeaderValue::try_from(policy).ok()) {
+        insert_missing(headers, csp_header, policy);
+      }
+      for (name, value) in &config.headers {
+        insert_missing(headers, name.clone(), value.clone());
+      }
+      Ok(response)
+    })
+  }
+}
+
+fn insert_missing(headers: &mut HeaderMap, name: HeaderName, value: HeaderValue) {
+  if !headers.contains_key(&name) {
+    headers.insert(name, value);
+  }
+}
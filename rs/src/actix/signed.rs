@@ -0,0 +1,90 @@
+use crate::core::signed::UrlSigner;
+use actix_web::{
+  Error,
+  dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+  error::ErrorForbidden,
+};
+use futures_util::future::{self, LocalBoxFuture};
+use std::rc::Rc;
+
+/// An Actix middleware that only lets through URLs signed by a
+/// [`UrlSigner`], such as those of the `signed_asset()` template function.
+///
+/// Requests without a valid, unexpired signature for their path get `403
+/// Forbidden`. Wrap the scope serving the protected files, with the signer
+/// given to `TeraWebBuilder::url_signer`:
+///
+/// ```rust,no_run
+/// # use actix_web::{App, HttpServer, web};
+/// # use snapfire::actix::signed::SignedUrls;
+/// # use snapfire::core::signed::UrlSigner;
+/// # async fn download() -> &'static str { "" }
+/// # async fn run() -> std::io::Result<()> {
+/// let signer = UrlSigner::new(std::env::var("DOWNLOAD_KEY").unwrap());
+/// HttpServer::new(move || {
+///   App::new().service(
+///     web::scope("/downloads")
+///       .wrap(SignedUrls::new(signer.clone()))
+///       .route("/{file}", web::get().to(download)),
+///   )
+/// })
+/// # ; Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SignedUrls {
+  signer: UrlSigner,
+}
+
+impl SignedUrls {
+  /// Creates the middleware with the signer whose URLs it lets through.
+  pub fn new(signer: UrlSigner) -> Self {
+    Self { signer }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SignedUrls
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Transform = SignedUrlsMiddleware<S>;
+  type InitError = ();
+  type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    future::ok(SignedUrlsMiddleware {
+      service: Rc::new(service),
+      signer: self.signer.clone(),
+    })
+  }
+}
+
+pub struct SignedUrlsMiddleware<S> {
+  service: Rc<S>,
+  signer: UrlSigner,
+}
+
+impl<S, B> Service<ServiceRequest> for SignedUrlsMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    // The full path, as signed, even inside a scope.
+    if let Err(error) = self.signer.verify(req.path(), req.query_string()) {
+      log::info!("Rejecting a request for {}: {error}", req.path());
+      return Box::pin(future::err(ErrorForbidden("Invalid or expired link")));
+    }
+    let service = self.service.clone();
+    Box::pin(async move { service.call(req).await })
+  }
+}
@@ -0,0 +1,191 @@
+use crate::core::app::TeraWeb;
+use actix_multipart::Multipart;
+use actix_web::{Error, error::ErrorPayloadTooLarge, web::Bytes, web::BytesMut};
+use futures_util::StreamExt;
+use std::collections::HashMap;
+
+/// The largest text field `read_uploads` reads.
+const TEXT_FIELD_LIMIT: usize = 64 * 1024;
+
+/// How often, at most, `read_uploads` reports a file's progress in
+/// development, so a large upload doesn't crowd the reload channel.
+#[cfg(feature = "devel")]
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// A file from a multipart form.
+#[derive(Debug, Clone)]
+pub struct UploadedFile {
+  /// The form field it was sent in.
+  pub field: String,
+  /// The file name the client sent.
+  pub filename: String,
+  /// The content type the client declared, if any.
+  pub content_type: Option<String>,
+  /// The file's contents.
+  pub bytes: Bytes,
+}
+
+/// A multipart form read by `read_uploads`.
+#[derive(Debug, Default)]
+pub struct Uploads {
+  /// The files that passed their field's rule, in the order they were sent.
+  pub files: Vec<UploadedFile>,
+  /// The form's text fields; for repeated names, the last value.
+  pub fields: HashMap<String, String>,
+  /// A message per upload field that failed its rule, for the `errors`
+  /// argument of `upload_field`.
+  pub errors: HashMap<String, String>,
+}
+
+impl Uploads {
+  /// Whether every upload field passed its rule.
+  pub fn is_valid(&self) -> bool {
+    self.errors.is_empty()
+  }
+
+  /// The first file sent in the field `name`.
+  pub fn file(&self, name: &str) -> Option<&UploadedFile> {
+    self.files.iter().find(|file| file.field == name)
+  }
+}
+
+/// Reads a multipart form, checking its files against the rules declared
+/// with `TeraWebBuilder::upload_field`.
+///
+/// Files that break a rule (too large, of a type not accepted, a second file
+/// in a single-file field, or in a field that wasn't declared) are read past
+/// without keeping them, and a message is recorded for the field, as for
+/// required fields with no file. Render them back with the form:
+///
+/// ```rust,no_run
+/// # use actix_multipart::Multipart;
+/// # use actix_web::web;
+/// # use snapfire::{Template, TeraWeb};
+/// # use tera::Context;
+/// async fn upload(app: web::Data<TeraWeb>, form: Multipart) -> actix_web::Result<Template> {
+///   let uploads = snapfire::actix::uploads::read_uploads(&app, form).await?;
+///   if !uploads.is_valid() {
+///     let mut context = Context::new();
+///     context.insert("upload_errors", &uploads.errors);
+///     return Ok(app.render("upload.html", context));
+///   }
+///   // Store uploads.file("avatar") ...
+///   Ok(app.render("done.html", Context::new()))
+/// }
+/// ```
+///
+/// With `devel`, the progress of each file read is pushed to the pages on the
+/// live-reload WebSocket as it arrives, at most every 250 ms and once more
+/// when it's complete, for a progress bar while prototyping:
+///
+/// ```js
+/// SnapFire.on("upload-progress", ({ field, filename, received, done }) => { ... });
+/// ```
+///
+/// `received` is in bytes; browsers know the file's total size. Every page
+/// receives every upload's progress, so this is for development only.
+///
+/// Requires the `uploads` feature.
+pub async fn read_uploads(app: &TeraWeb, mut multipart: Multipart) -> Result<Uploads, Error> {
+  let mut uploads = Uploads::default();
+  let mut counts: HashMap<String, usize> = HashMap::new();
+
+  while let Some(field) = multipart.next().await {
+    let mut field = field?;
+    let name = field.name().unwrap_or_default().to_string();
+    let filename = field
+      .content_disposition()
+      .and_then(|disposition| disposition.get_filename())
+      .map(str::to_string);
+
+    let Some(filename) = filename else {
+      let mut value = BytesMut::new();
+      while let Some(chunk) = field.next().await {
+        let chunk = chunk?;
+        if value.len() + chunk.len() > TEXT_FIELD_LIMIT {
+          return Err(ErrorPayloadTooLarge(format!("The form field `{name}` is too large")));
+        }
+        value.extend_from_slice(&chunk);
+      }
+      uploads.fields.insert(name, String::from_utf8_lossy(&value).into_owned());
+      continue;
+    };
+    // Browsers send an empty file part for file inputs left empty.
+    if filename.is_empty() {
+      drain(&mut field).await?;
+      continue;
+    }
+
+    let content_type = field.content_type().map(ToString::to_string);
+    let Some(rule) = app.upload_rules.get(&name) else {
+      drain(&mut field).await?;
+      uploads.errors.insert(name, "This field doesn't accept files".to_string());
+      continue;
+    };
+    let count = counts.entry(name.clone()).or_default();
+    *count += 1;
+    if *count > 1 && !rule.multiple {
+      drain(&mut field).await?;
+      uploads.errors.insert(name, "Please choose only one file".to_string());
+      continue;
+    }
+    // The type is known up front; the size only while reading.
+    if let Err(message) = rule.check(&filename, content_type.as_deref(), 0) {
+      drain(&mut field).await?;
+      uploads.errors.insert(name, message);
+      continue;
+    }
+
+    let mut bytes = BytesMut::new();
+    let mut size = 0;
+    #[cfg(feature = "devel")]
+    let mut reported = std::time::Instant::now();
+    while let Some(chunk) = field.next().await {
+      let chunk = chunk?;
+      size += chunk.len() as u64;
+      if rule.max_size.is_none_or(|max_size| size <= max_size) {
+        bytes.extend_from_slice(&chunk);
+      }
+      #[cfg(feature = "devel")]
+      if reported.elapsed() >= PROGRESS_INTERVAL {
+        report_progress(app, &name, &filename, size, false);
+        reported = std::time::Instant::now();
+      }
+    }
+    #[cfg(feature = "devel")]
+    report_progress(app, &name, &filename, size, true);
+    match rule.check(&filename, content_type.as_deref(), size) {
+      Ok(()) => uploads.files.push(UploadedFile {
+        field: name,
+        filename,
+        content_type,
+        bytes: bytes.freeze(),
+      }),
+      Err(message) => {
+        uploads.errors.insert(name, message);
+      }
+    }
+  }
+
+  for (name, rule) in app.upload_rules.iter() {
+    if rule.required && !counts.contains_key(name) {
+      uploads.errors.entry(name.clone()).or_insert_with(|| "Please choose a file".to_string());
+    }
+  }
+  Ok(uploads)
+}
+
+/// Pushes how much of a file has arrived to the pages in development.
+#[cfg(feature = "devel")]
+fn report_progress(app: &TeraWeb, field: &str, filename: &str, received: u64, done: bool) {
+  let progress = serde_json::json!({ "field": field, "filename": filename, "received": received, "done": done });
+  app.dev_event("upload-progress", progress);
+}
+
+/// Reads the rest of a field without keeping it, so the next one can be read.
+async fn drain(field: &mut actix_multipart::Field) -> Result<(), Error> {
+  while let Some(chunk) = field.next().await {
+    chunk?;
+  }
+  Ok(())
+}
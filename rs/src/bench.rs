@@ -0,0 +1,88 @@
+//! Helpers for benchmarking template sets, available with the `bench` feature.
+//!
+//! These let you measure your own templates against engine changes without
+//! reaching into crate internals:
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! use snapfire::TeraWeb;
+//! use tera::Context;
+//!
+//! let app = TeraWeb::builder("templates/**/*.html").build()?;
+//! let stats = snapfire::bench::render_throughput(&app, "index.html", &Context::new())?;
+//! println!("{:.0} renders/sec", stats.renders_per_sec());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::core::app::TeraWeb;
+use crate::error::Result;
+use std::time::{Duration, Instant};
+use tera::Context;
+
+/// How many renders `render_throughput` performs before it starts measuring.
+const WARMUP_ITERATIONS: u32 = 10;
+/// How many measured renders `render_throughput` performs.
+const MEASURED_ITERATIONS: u32 = 1_000;
+
+/// The result of a throughput measurement.
+#[derive(Debug, Clone, Copy)]
+pub struct Throughput {
+  /// How many renders were measured.
+  pub iterations: u32,
+  /// The total wall-clock time spent on the measured renders.
+  pub elapsed: Duration,
+  /// The size of one rendered page, in bytes.
+  pub bytes_per_render: usize,
+}
+
+impl Throughput {
+  /// The average number of renders per second.
+  pub fn renders_per_sec(&self) -> f64 {
+    f64::from(self.iterations) / self.elapsed.as_secs_f64()
+  }
+
+  /// The average time spent on a single render.
+  pub fn mean_render_time(&self) -> Duration {
+    self.elapsed / self.iterations
+  }
+}
+
+/// Renders `tpl` repeatedly through the full SnapFire path (global merge
+/// included) and reports the throughput.
+///
+/// Fails with the first render error, so a broken template is reported
+/// instead of producing a misleadingly fast measurement.
+pub fn render_throughput(app: &TeraWeb, tpl: &str, ctx: &Context) -> Result<Throughput> {
+  let mut bytes_per_render = 0;
+  for _ in 0..WARMUP_ITERATIONS {
+    bytes_per_render = app.render_to_string(tpl, ctx.clone())?.len();
+  }
+
+  let start = Instant::now();
+  for _ in 0..MEASURED_ITERATIONS {
+    std::hint::black_box(app.render_to_string(tpl, ctx.clone())?);
+  }
+
+  Ok(Throughput {
+    iterations: MEASURED_ITERATIONS,
+    elapsed: start.elapsed(),
+    bytes_per_render,
+  })
+}
+
+/// Renders `tpl` once through the full SnapFire path, for use inside a
+/// benchmark harness's own measurement loop.
+pub fn render_once(app: &TeraWeb, tpl: &str, ctx: Context) -> Result<String> {
+  app.render_to_string(tpl, ctx)
+}
+
+/// Performs only the context layering (globals, then `ctx`) that precedes
+/// every render, including the cleanup that follows it.
+///
+/// Useful for measuring how much of a render's cost comes from context handling.
+pub fn merge_context(app: &TeraWeb, ctx: Context) {
+  app.globals.with_merged(None, ctx, |merged| {
+    std::hint::black_box(merged);
+  });
+}
@@ -0,0 +1,142 @@
+//! The `snapfire` command-line tool, available with the `cli` feature.
+
+use snapfire::core::catalog;
+use snapfire::core::export::{self, StaticExport};
+use snapfire::core::links::LinkChecker;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: snapfire <command> [args]
+
+Commands:
+  build-templates <glob> <output>  Compile the templates matched by <glob> into a bundle file
+  export <glob> <pages> <out-dir>  Render the pages listed in the YAML file <pages> into <out-dir>
+         [--watch]                 ...and keep re-rendering the pages affected by changes
+  check-links <glob> <pages>       Report the broken internal links in the pages listed in the YAML file <pages>
+         [--static <prefix> <dir>]...
+                                   ...accepting links to the files in <dir>, served under <prefix>
+         [--route <url>]...        ...and to routes that aren't pages
+  extract-translations <glob> <catalog>...
+                                   Add the keys of t(...) calls to each .pot, .po, or .ftl catalog";
+
+fn main() -> ExitCode {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+
+  let result = match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+    ["build-templates", glob, output] => build_templates(glob, output),
+    ["export", glob, pages, out_dir] => export(glob, pages, out_dir, false),
+    ["export", glob, pages, out_dir, "--watch"] => export(glob, pages, out_dir, true),
+    ["check-links", glob, pages, options @ ..] => match LinkOptions::parse(options) {
+      Some(options) => match check_links(glob, pages, &options) {
+        // The broken links are already reported.
+        Ok(false) => return ExitCode::FAILURE,
+        result => result.map(drop),
+      },
+      None => {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+      }
+    },
+    ["extract-translations", glob, catalogs @ ..] if !catalogs.is_empty() => extract_translations(glob, catalogs),
+    ["help"] | ["--help"] | ["-h"] => {
+      println!("{USAGE}");
+      return ExitCode::SUCCESS;
+    }
+    _ => {
+      eprintln!("{USAGE}");
+      return ExitCode::from(2);
+    }
+  };
+
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("error: {e}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn build_templates(glob: &str, output: &str) -> snapfire::Result<()> {
+  let bundle = snapfire::core::bundle::build_templates(glob, output)?;
+  let count = bundle.template_names().count();
+  println!("Bundled {count} template(s) into {output}");
+  Ok(())
+}
+
+fn export(glob: &str, pages: &str, out_dir: &str, watch: bool) -> snapfire::Result<()> {
+  let app = snapfire::TeraWeb::builder(glob).build()?;
+  let mut export = StaticExport::new(&app, out_dir, export::load_pages(Path::new(pages))?);
+
+  if watch {
+    println!("Exporting to {out_dir} and watching for changes (Ctrl-C to stop)");
+    return export.watch(Duration::from_millis(300));
+  }
+  let report = export.build()?;
+  println!("Exported {} page(s) to {out_dir}", report.written.len());
+  Ok(())
+}
+
+/// The options of `check-links`.
+#[derive(Default)]
+struct LinkOptions<'a> {
+  static_dirs: Vec<(&'a str, &'a str)>,
+  routes: Vec<&'a str>,
+}
+
+impl<'a> LinkOptions<'a> {
+  /// Parses `--static <prefix> <dir>` and `--route <url>` options, or
+  /// returns `None` if they are malformed.
+  fn parse(mut args: &[&'a str]) -> Option<Self> {
+    let mut options = Self::default();
+    loop {
+      args = match args {
+        [] => return Some(options),
+        ["--static", prefix, dir, rest @ ..] => {
+          options.static_dirs.push((*prefix, *dir));
+          rest
+        }
+        ["--route", url, rest @ ..] => {
+          options.routes.push(*url);
+          rest
+        }
+        _ => return None,
+      };
+    }
+  }
+}
+
+/// Reports the broken links, returning whether there were none.
+fn check_links(glob: &str, pages: &str, options: &LinkOptions) -> snapfire::Result<bool> {
+  let app = snapfire::TeraWeb::builder(glob).build()?;
+  let mut checker = LinkChecker::new(&app, export::load_pages(Path::new(pages))?);
+  for (prefix, dir) in &options.static_dirs {
+    checker = checker.static_dir(prefix, dir);
+  }
+  for route in &options.routes {
+    checker = checker.route(route);
+  }
+
+  let report = checker.check()?;
+  for broken in &report.broken {
+    println!("{}: broken link to {}", broken.page, broken.link);
+  }
+  println!("Checked {} link(s), {} broken", report.checked, report.broken.len());
+  Ok(report.is_ok())
+}
+
+fn extract_translations(glob: &str, catalogs: &[&str]) -> snapfire::Result<()> {
+  let app = snapfire::TeraWeb::builder(glob).build()?;
+  let keys = app.translation_keys()?;
+  println!("Found {} translation key(s)", keys.len());
+
+  for path in catalogs {
+    let report = catalog::update_catalog(Path::new(path), &keys)?;
+    println!("{path}: added {} missing key(s)", report.missing.len());
+    if !report.unused.is_empty() {
+      println!("  unused: {}", report.unused.join(", "));
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,811 @@
+//! Static accessibility checks and outlines of rendered pages.
+//!
+//! [`audit`] scans a page's HTML for common problems that can be found
+//! without a browser:
+//!
+//! - `html-lang`, `document-title`: a page without a `lang` or a `<title>`;
+//! - `img-alt`: images without an `alt` attribute;
+//! - `control-label`: form fields without a label, `aria-label`, or
+//!   `aria-labelledby`;
+//! - `link-name`, `button-name`: links and buttons without text;
+//! - `heading-order`: headings that skip a level;
+//! - `duplicate-id`, `aria-reference`: repeated ids, and ARIA attributes
+//!   referring to ids that don't exist;
+//! - `aria-role`, `aria-hidden-focus`, `tabindex`: unknown roles, focusable
+//!   elements hidden from screen readers, and positive `tabindex` values;
+//! - `color-contrast`: text colors set in `style` attributes with less than
+//!   4.5:1 contrast against the nearest inline background (white if none).
+//!
+//! They are heuristics over the markup: styles from stylesheets and changes
+//! made by scripts aren't seen, so a clean report doesn't replace testing
+//! with assistive technology. [`outline`] lists the page's headings and
+//! landmark regions, as screen reader users navigate it.
+//!
+//! In dev mode, the `A11yAudit` Actix middleware serves both for any route,
+//! at `/_snapfire/a11y?path=/route` and `/_snapfire/outline?path=/route`.
+
+#[cfg(all(feature = "devel", feature = "actix"))]
+use crate::core::avatar::percent_encode;
+use crate::core::links::{attributes, unescape};
+use std::collections::{HashMap, HashSet};
+#[cfg(all(feature = "devel", feature = "actix"))]
+use std::fmt::Write as _;
+
+/// Where the `A11yAudit` middleware serves audit reports.
+#[cfg(all(feature = "devel", feature = "actix"))]
+pub(crate) const A11Y_PATH: &str = "/_snapfire/a11y";
+
+/// Where the `A11yAudit` middleware serves outlines.
+#[cfg(all(feature = "devel", feature = "actix"))]
+pub(crate) const OUTLINE_PATH: &str = "/_snapfire/outline";
+
+/// Elements without contents or a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+  "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+/// The roles defined by WAI-ARIA 1.2.
+const ARIA_ROLES: &[&str] = &[
+  "alert", "alertdialog", "application", "article", "banner", "blockquote", "button", "caption", "cell", "checkbox",
+  "code", "columnheader", "combobox", "complementary", "contentinfo", "definition", "deletion", "dialog", "document",
+  "emphasis", "feed", "figure", "form", "generic", "grid", "gridcell", "group", "heading", "img", "insertion", "link",
+  "list", "listbox", "listitem", "log", "main", "marquee", "math", "menu", "menubar", "menuitem", "menuitemcheckbox",
+  "menuitemradio", "meter", "navigation", "none", "note", "option", "paragraph", "presentation", "progressbar",
+  "radio", "radiogroup", "region", "row", "rowgroup", "rowheader", "scrollbar", "search", "searchbox", "separator",
+  "slider", "spinbutton", "status", "strong", "subscript", "superscript", "switch", "tab", "table", "tablist",
+  "tabpanel", "term", "textbox", "time", "timer", "toolbar", "tooltip", "tree", "treegrid", "treeitem",
+];
+
+/// The landmark roles of WAI-ARIA 1.2.
+const LANDMARK_ROLES: &[&str] = &[
+  "banner", "complementary", "contentinfo", "form", "main", "navigation", "region", "search",
+];
+
+/// Elements inside which `<header>` and `<footer>` aren't landmarks.
+const SECTIONING_ELEMENTS: &[&str] = &["article", "aside", "main", "nav", "section"];
+
+/// The ARIA attributes whose values are lists of ids.
+const ID_REFERENCES: &[&str] = &["aria-labelledby", "aria-describedby", "aria-controls", "aria-owns", "aria-details"];
+
+/// The minimum contrast ratio for normal text (WCAG 2 AA).
+const MIN_CONTRAST: f64 = 4.5;
+
+/// A problem found by [`audit`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+  /// The check that found it, e.g. `img-alt`.
+  pub rule: &'static str,
+  /// What is wrong, in English.
+  pub message: String,
+  /// The line of the element in the page, from 1.
+  pub line: usize,
+  /// The element's opening tag, shortened to 120 characters.
+  pub element: String,
+}
+
+/// An entry of a page's [`outline`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineItem {
+  pub kind: OutlineKind,
+  /// The heading's text, or the landmark's `aria-label` or `title`; empty
+  /// if it has none.
+  pub label: String,
+  /// The number of landmarks it is in.
+  pub depth: usize,
+  /// The line of the element in the page, from 1.
+  pub line: usize,
+}
+
+/// What an [`OutlineItem`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineKind {
+  /// A heading, with its level from 1 to 6.
+  Heading(u8),
+  /// A landmark region, with its ARIA role, e.g. `navigation`.
+  Landmark(&'static str),
+}
+
+/// An element that is still open, with what its checks need.
+struct OpenElement {
+  name: String,
+  /// The inline background color it sets.
+  background: Option<Rgb>,
+  /// For links and buttons, whether they have an accessible name yet.
+  named: Option<bool>,
+  offset: usize,
+  tag: String,
+}
+
+/// A form field that needs a label, checked once all labels are known.
+struct Control {
+  id: Option<String>,
+  offset: usize,
+  tag: String,
+}
+
+/// Checks `html` for common accessibility problems, in page order.
+pub fn audit(html: &str) -> Vec<Issue> {
+  let mut issues = Vec::new();
+  let mut issue = |rule: &'static str, message: String, offset: usize, tag: &str| {
+    issues.push(Issue {
+      rule,
+      message,
+      line: line_of(html, offset),
+      element: shorten(tag),
+    });
+  };
+
+  let mut open: Vec<OpenElement> = Vec::new();
+  let mut ids: HashMap<String, usize> = HashMap::new();
+  let mut label_targets: HashSet<String> = HashSet::new();
+  let mut references: Vec<(String, usize, String)> = Vec::new();
+  let mut controls: Vec<Control> = Vec::new();
+  let mut last_heading = 0;
+  let mut html_tag: Option<(usize, String)> = None;
+  let mut title: Option<String> = None;
+
+  for token in Tokens::new(html) {
+    let element = match token {
+      Token::Text(text) => {
+        if !text.trim().is_empty() {
+          for element in open.iter_mut() {
+            if element.named == Some(false) {
+              element.named = Some(true);
+            }
+          }
+          if open.last().is_some_and(|element| element.name == "title") {
+            title.get_or_insert_with(String::new).push_str(text.trim());
+          }
+        }
+        continue;
+      }
+      Token::Close(name) => {
+        if let Some(index) = open.iter().rposition(|element| element.name == name) {
+          for element in open.drain(index..).rev() {
+            if element.named == Some(false) {
+              let rule = if element.name == "a" { "link-name" } else { "button-name" };
+              let kind = if element.name == "a" { "Link" } else { "Button" };
+              issue(rule, format!("{kind} has no text or accessible name"), element.offset, &element.tag);
+            }
+          }
+        }
+        continue;
+      }
+      Token::Open(element) => element,
+    };
+    let (start, tag, name) = (element.offset, element.tag, element.name.as_str());
+    let attribute = |name: &str| element.attribute(name);
+    let has_name = element.has_name();
+
+    if let Some(id) = attribute("id").filter(|id| !id.is_empty()) {
+      let count = ids.entry(id.to_string()).or_default();
+      *count += 1;
+      if *count == 2 {
+        issue("duplicate-id", format!("The id `{id}` is used more than once"), start, tag);
+      }
+    }
+    for reference in ID_REFERENCES {
+      if let Some(value) = attribute(reference) {
+        for id in value.split_whitespace() {
+          references.push((id.to_string(), start, format!("{reference}|{tag}")));
+        }
+      }
+    }
+
+    if let Some(role) = attribute("role") {
+      let unknown: Vec<&str> = role.split_whitespace().filter(|role| !ARIA_ROLES.contains(role)).collect();
+      if !unknown.is_empty() {
+        issue("aria-role", format!("Unknown ARIA role `{}`", unknown.join(" ")), start, tag);
+      }
+    }
+    let tabindex = attribute("tabindex").and_then(|value| value.trim().parse::<i32>().ok());
+    if tabindex.is_some_and(|tabindex| tabindex > 0) {
+      issue("tabindex", "A positive tabindex changes the tab order; use 0 or rearrange the markup".to_string(), start, tag);
+    }
+    let is_focusable = match name {
+      "a" => attribute("href").is_some(),
+      "button" | "select" | "textarea" => true,
+      "input" => attribute("type") != Some("hidden"),
+      _ => false,
+    } || tabindex.is_some_and(|tabindex| tabindex >= 0);
+    if is_focusable && attribute("aria-hidden") == Some("true") && tabindex.is_none_or(|tabindex| tabindex >= 0) {
+      issue("aria-hidden-focus", "A focusable element is hidden from screen readers with aria-hidden".to_string(), start, tag);
+    }
+
+    let style = attribute("style").map(parse_style).unwrap_or_default();
+    if let Some(color) = style.color {
+      let background = style
+        .background
+        .or_else(|| open.iter().rev().find_map(|element| element.background))
+        .unwrap_or(Rgb(255, 255, 255));
+      let ratio = contrast(color, background);
+      if ratio < MIN_CONTRAST {
+        issue(
+          "color-contrast",
+          format!("Text contrast is {ratio:.2}:1, below {MIN_CONTRAST}:1"),
+          start,
+          tag,
+        );
+      }
+    }
+
+    let is_presentational = matches!(attribute("role"), Some("presentation" | "none")) || attribute("aria-hidden") == Some("true");
+    match name {
+      "html" => {
+        html_tag = Some((start, tag.to_string()));
+        if attribute("lang").is_none_or(|lang| lang.trim().is_empty()) {
+          issue("html-lang", "The page doesn't declare its language with `lang`".to_string(), start, tag);
+        }
+      }
+      "img" if attribute("alt").is_none() && !is_presentational => {
+        issue("img-alt", "Image has no `alt` text; use `alt=\"\"` for decorative images".to_string(), start, tag);
+      }
+      "img" if attribute("alt").is_some_and(|alt| !alt.trim().is_empty()) => {
+        for element in open.iter_mut() {
+          if element.named == Some(false) {
+            element.named = Some(true);
+          }
+        }
+      }
+      "input" | "select" | "textarea" => {
+        let kind = attribute("type").unwrap_or("text").to_ascii_lowercase();
+        let labelled_by_value = name == "input" && matches!(kind.as_str(), "submit" | "reset" | "button") && attribute("value").is_some();
+        let needs_label = !matches!(kind.as_str(), "hidden" | "image") || (kind == "image" && attribute("alt").is_none());
+        let in_label = open.iter().any(|element| element.name == "label");
+        if needs_label && !has_name && !in_label && !labelled_by_value {
+          controls.push(Control {
+            id: attribute("id").map(str::to_string),
+            offset: start,
+            tag: tag.to_string(),
+          });
+        }
+      }
+      "label" => {
+        if let Some(target) = attribute("for") {
+          label_targets.insert(target.to_string());
+        }
+      }
+      heading if heading.len() == 2 && heading.starts_with('h') => {
+        if let Some(level) = heading[1..].parse::<u32>().ok().filter(|level| (1..=6).contains(level)) {
+          if last_heading > 0 && level > last_heading + 1 {
+            issue(
+              "heading-order",
+              format!("Heading level skips from h{last_heading} to h{level}"),
+              start,
+              tag,
+            );
+          }
+          last_heading = level;
+        }
+      }
+      _ => {}
+    }
+
+    if !element.is_empty {
+      let needs_name = (name == "a" && attribute("href").is_some() || name == "button") && !has_name && !is_presentational;
+      open.push(OpenElement {
+        named: needs_name.then_some(false),
+        name: element.name.clone(),
+        background: style.background,
+        offset: start,
+        tag: tag.to_string(),
+      });
+    }
+  }
+
+  for control in controls {
+    if !control.id.as_ref().is_some_and(|id| label_targets.contains(id)) {
+      issue("control-label", "Form field has no label".to_string(), control.offset, &control.tag);
+    }
+  }
+  for (id, offset, source) in references {
+    let (reference, tag) = source.split_once('|').unwrap_or_default();
+    if !ids.contains_key(&id) {
+      issue("aria-reference", format!("`{reference}` refers to the missing id `{id}`"), offset, tag);
+    }
+  }
+  if let Some((offset, tag)) = html_tag
+    && title.as_deref().is_none_or(|title| title.is_empty())
+  {
+    issue("document-title", "The page has no `<title>`".to_string(), offset, &tag);
+  }
+
+  // Stable, so issues on a line keep the order of the checks.
+  issues.sort_by_key(|issue| issue.line);
+  issues
+}
+
+/// The headings and landmark regions of `html`, in page order.
+///
+/// Landmarks are the elements with a landmark `role`, and those that have
+/// one implicitly: `<main>`, `<nav>`, `<aside>`, `<search>`, `<header>` and
+/// `<footer>` outside sectioning elements, and `<form>` and `<section>`
+/// when they have an accessible name. Headings are `<h1>` to `<h6>` and
+/// elements with `role="heading"` (`aria-level`, or 2).
+pub fn outline(html: &str) -> Vec<OutlineItem> {
+  let mut items: Vec<OutlineItem> = Vec::new();
+  // The open elements, and whether each is a landmark.
+  let mut open: Vec<(String, bool)> = Vec::new();
+  // The heading whose text is being read: its place in `open` and `items`.
+  let mut heading: Option<(usize, usize)> = None;
+
+  for token in Tokens::new(html) {
+    match token {
+      Token::Text(text) => {
+        if let Some((_, index)) = heading {
+          items[index].label.push_str(&unescape(text));
+        }
+      }
+      Token::Close(name) => {
+        if let Some(index) = open.iter().rposition(|(open, _)| *open == name) {
+          open.truncate(index);
+        }
+        if heading.is_some_and(|(position, _)| open.len() <= position) {
+          heading = None;
+        }
+      }
+      Token::Open(element) => {
+        if let Some((_, index)) = heading {
+          if let Some(alt) = element.attribute("alt") {
+            let label = &mut items[index].label;
+            label.push(' ');
+            label.push_str(alt);
+            label.push(' ');
+          }
+        } else {
+          let depth = open.iter().filter(|(_, is_landmark)| *is_landmark).count();
+          let line = line_of(html, element.offset);
+          let role = landmark_role(&element, &open);
+          if let Some(role) = role {
+            items.push(OutlineItem {
+              kind: OutlineKind::Landmark(role),
+              label: element.label(),
+              depth,
+              line,
+            });
+          } else if let Some(level) = heading_level(&element) {
+            if !element.is_empty {
+              heading = Some((open.len(), items.len()));
+            }
+            items.push(OutlineItem {
+              kind: OutlineKind::Heading(level),
+              label: String::new(),
+              depth,
+              line,
+            });
+          }
+          if !element.is_empty {
+            open.push((element.name, role.is_some()));
+          }
+          continue;
+        }
+        if !element.is_empty {
+          open.push((element.name, false));
+        }
+      }
+    }
+  }
+
+  for item in &mut items {
+    item.label = item.label.split_whitespace().collect::<Vec<_>>().join(" ");
+  }
+  items
+}
+
+/// The landmark role of `element`, given the elements it is in.
+fn landmark_role(element: &Element, open: &[(String, bool)]) -> Option<&'static str> {
+  if let Some(role) = element.attribute("role") {
+    // An explicit role replaces the implicit one.
+    return role
+      .split_whitespace()
+      .find_map(|role| LANDMARK_ROLES.iter().find(|landmark| **landmark == role).copied());
+  }
+  let in_sectioning = || open.iter().any(|(name, _)| SECTIONING_ELEMENTS.contains(&name.as_str()));
+  match element.name.as_str() {
+    "main" => Some("main"),
+    "nav" => Some("navigation"),
+    "aside" => Some("complementary"),
+    "search" => Some("search"),
+    "header" if !in_sectioning() => Some("banner"),
+    "footer" if !in_sectioning() => Some("contentinfo"),
+    "form" if element.has_name() => Some("form"),
+    "section" if element.has_name() => Some("region"),
+    _ => None,
+  }
+}
+
+/// The heading level of `element`, if it is a heading.
+fn heading_level(element: &Element) -> Option<u8> {
+  if element.attribute("role").is_some_and(|role| role.split_whitespace().next() == Some("heading")) {
+    let level = element.attribute("aria-level").and_then(|level| level.trim().parse::<u8>().ok());
+    return Some(level.unwrap_or(2).clamp(1, 6));
+  }
+  let level = element.name.strip_prefix('h')?.parse::<u8>().ok()?;
+  (1..=6).contains(&level).then_some(level)
+}
+
+/// A piece of a page's markup.
+enum Token<'a> {
+  Text(&'a str),
+  Open(Element<'a>),
+  /// A closing tag, with the element's lowercase name.
+  Close(String),
+}
+
+/// An opening tag.
+struct Element<'a> {
+  /// The lowercase element name.
+  name: String,
+  /// The tag's source, from `<` to `>`.
+  tag: &'a str,
+  /// Where the tag starts in the page.
+  offset: usize,
+  /// The attributes, with lowercase names and unescaped values.
+  attributes: HashMap<String, String>,
+  /// Whether it has no contents: a void element or a `/>` tag.
+  is_empty: bool,
+}
+
+impl Element<'_> {
+  fn attribute(&self, name: &str) -> Option<&str> {
+    self.attributes.get(name).map(String::as_str)
+  }
+
+  /// Whether it is named by an attribute rather than its contents.
+  fn has_name(&self) -> bool {
+    ["aria-label", "aria-labelledby", "title"]
+      .iter()
+      .any(|name| self.attribute(name).is_some_and(|value| !value.trim().is_empty()))
+  }
+
+  /// Its `aria-label` or `title`, trimmed.
+  fn label(&self) -> String {
+    ["aria-label", "title"]
+      .iter()
+      .find_map(|name| self.attribute(name).map(str::trim).filter(|value| !value.is_empty()))
+      .unwrap_or_default()
+      .to_string()
+  }
+}
+
+/// The tokens of a page, without comments, doctypes, or the contents of
+/// scripts and styles.
+struct Tokens<'a> {
+  html: &'a str,
+  position: usize,
+  /// The script or style element whose contents come next.
+  raw: Option<String>,
+}
+
+impl<'a> Tokens<'a> {
+  fn new(html: &'a str) -> Self {
+    Self {
+      html,
+      position: 0,
+      raw: None,
+    }
+  }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+  type Item = Token<'a>;
+
+  fn next(&mut self) -> Option<Token<'a>> {
+    let html = self.html;
+    if let Some(name) = self.raw.take() {
+      // Their contents are code, not markup.
+      let closing = format!("</{name}");
+      let position = self.position;
+      self.position = html[position..].to_ascii_lowercase().find(&closing).map_or(html.len(), |skip| position + skip);
+    }
+    loop {
+      let position = self.position;
+      if position >= html.len() {
+        return None;
+      }
+      let start = html[position..].find('<').map_or(html.len(), |start| position + start);
+      if start > position {
+        self.position = start;
+        return Some(Token::Text(&html[position..start]));
+      }
+
+      let rest = &html[start..];
+      if rest.starts_with("<!--") {
+        self.position = rest.find("-->").map_or(html.len(), |end| start + end + 3);
+        continue;
+      }
+      let end = rest.find('>').map_or(html.len(), |end| start + end + 1);
+      let tag = &html[start..end];
+      self.position = end;
+      if let Some(closing) = tag.strip_prefix("</") {
+        return Some(Token::Close(tag_name(closing)));
+      }
+      let name = tag_name(&tag[1..]);
+      if name.is_empty() {
+        // A doctype or a stray `<`.
+        continue;
+      }
+      let attributes = attributes(tag.get(1..tag.len().saturating_sub(1)).unwrap_or_default())
+        .into_iter()
+        .map(|(attribute, value)| (attribute.to_ascii_lowercase(), unescape(value)))
+        .collect();
+      if name == "script" || name == "style" {
+        self.raw = Some(name.clone());
+      }
+      return Some(Token::Open(Element {
+        is_empty: VOID_ELEMENTS.contains(&name.as_str()) || tag.ends_with("/>"),
+        name,
+        tag,
+        offset: start,
+        attributes,
+      }));
+    }
+  }
+}
+
+/// The line of `offset` in `html`, from 1.
+fn line_of(html: &str, offset: usize) -> usize {
+  html[..offset].matches('\n').count() + 1
+}
+
+/// The lowercase element name at the start of a tag's source.
+fn tag_name(source: &str) -> String {
+  source
+    .chars()
+    .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+    .map(|c| c.to_ascii_lowercase())
+    .collect()
+}
+
+/// `tag` on one line, shortened for the report.
+fn shorten(tag: &str) -> String {
+  let tag = tag.split_whitespace().collect::<Vec<_>>().join(" ");
+  match tag.char_indices().nth(120) {
+    Some((at, _)) => format!("{}…", &tag[..at]),
+    None => tag,
+  }
+}
+
+/// An sRGB color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rgb(u8, u8, u8);
+
+/// The colors set by a `style` attribute.
+#[derive(Debug, Default)]
+struct Style {
+  color: Option<Rgb>,
+  background: Option<Rgb>,
+}
+
+fn parse_style(style: &str) -> Style {
+  let mut parsed = Style::default();
+  for declaration in style.split(';') {
+    let Some((property, value)) = declaration.split_once(':') else {
+      continue;
+    };
+    let value = value.trim().trim_end_matches("!important").trim();
+    match property.trim().to_ascii_lowercase().as_str() {
+      "color" => parsed.color = parse_color(value),
+      "background-color" => parsed.background = parse_color(value),
+      // The shorthand may hold images and positions too; use its color.
+      "background" => parsed.background = value.split_whitespace().find_map(parse_color).or(parsed.background),
+      _ => {}
+    }
+  }
+  parsed
+}
+
+/// An opaque CSS color: hex, `rgb()`, or a common keyword. Others, including
+/// translucent colors, are unknown.
+fn parse_color(value: &str) -> Option<Rgb> {
+  let value = value.trim().to_ascii_lowercase();
+  if let Some(hex) = value.strip_prefix('#') {
+    let channel = |index: usize, width: usize| u8::from_str_radix(&hex[index * width..index * width + width], 16).ok();
+    return match hex.len() {
+      3 => Some(Rgb(channel(0, 1)? * 17, channel(1, 1)? * 17, channel(2, 1)? * 17)),
+      6 => Some(Rgb(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+      _ => None,
+    };
+  }
+  if let Some(arguments) = value.strip_prefix("rgb(").and_then(|rest| rest.strip_suffix(')')) {
+    let channels: Vec<u8> = arguments
+      .split([',', ' '])
+      .filter(|channel| !channel.is_empty())
+      .map(|channel| channel.parse::<u8>().ok())
+      .collect::<Option<_>>()?;
+    return match channels[..] {
+      [red, green, blue] => Some(Rgb(red, green, blue)),
+      _ => None,
+    };
+  }
+  let rgb = match value.as_str() {
+    "black" => Rgb(0, 0, 0),
+    "white" => Rgb(255, 255, 255),
+    "gray" | "grey" => Rgb(128, 128, 128),
+    "silver" => Rgb(192, 192, 192),
+    "red" => Rgb(255, 0, 0),
+    "green" => Rgb(0, 128, 0),
+    "blue" => Rgb(0, 0, 255),
+    "yellow" => Rgb(255, 255, 0),
+    "orange" => Rgb(255, 165, 0),
+    _ => return None,
+  };
+  Some(rgb)
+}
+
+/// The WCAG contrast ratio of two colors, from 1 to 21.
+fn contrast(first: Rgb, second: Rgb) -> f64 {
+  let luminance = |Rgb(red, green, blue): Rgb| {
+    let linear = |channel: u8| {
+      let channel = f64::from(channel) / 255.0;
+      if channel <= 0.04045 { channel / 12.92 } else { ((channel + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * linear(red) + 0.7152 * linear(green) + 0.0722 * linear(blue)
+  };
+  let (first, second) = (luminance(first), luminance(second));
+  (first.max(second) + 0.05) / (first.min(second) + 0.05)
+}
+
+/// The audit report page for `path`, which answered with `status`.
+#[cfg(all(feature = "devel", feature = "actix"))]
+pub(crate) fn report_html(path: &str, status: u16, issues: Option<&[Issue]>) -> String {
+  let mut body = String::new();
+  match issues {
+    None => {
+      let _ = write!(body, "<p>The route answered with status {status} and no HTML page to check.</p>");
+    }
+    Some([]) => body.push_str("<p>No issues found by the static checks.</p>"),
+    Some(issues) => {
+      let _ = write!(
+        body,
+        "<p>{} issue{} found.</p><table><thead><tr><th>Line</th><th>Rule</th><th>Issue</th><th>Element</th></tr></thead><tbody>",
+        issues.len(),
+        if issues.len() == 1 { "" } else { "s" }
+      );
+      for issue in issues {
+        let _ = write!(
+          body,
+          "<tr><td>{}</td><td>{}</td><td>{}</td><td><code>{}</code></td></tr>",
+          issue.line,
+          issue.rule,
+          tera::escape_html(&issue.message),
+          tera::escape_html(&issue.element)
+        );
+      }
+      body.push_str("</tbody></table>");
+    }
+  }
+  body.push_str("<p><small>Static checks of the markup only: styles from stylesheets and scripts aren't seen.</small></p>");
+  report_page("Accessibility audit", path, &body)
+}
+
+/// The outline page for `path`, which answered with `status`.
+#[cfg(all(feature = "devel", feature = "actix"))]
+pub(crate) fn outline_html(path: &str, status: u16, items: Option<&[OutlineItem]>) -> String {
+  let mut body = String::new();
+  match items {
+    None => {
+      let _ = write!(body, "<p>The route answered with status {status} and no HTML page to outline.</p>");
+    }
+    Some([]) => body.push_str("<p>The page has no headings or landmarks.</p>"),
+    Some(items) => {
+      body.push_str("<ul class=\"outline\">");
+      let mut last_heading = 0;
+      for item in items {
+        let label = tera::escape_html(&item.label);
+        let (indent, class, kind, label) = match item.kind {
+          OutlineKind::Heading(level) => {
+            let skipped = last_heading > 0 && level > last_heading + 1;
+            last_heading = level;
+            let label = if label.is_empty() { "<em>(empty heading)</em>".to_string() } else { label };
+            let label = if skipped { format!("{label} <strong>skips a level</strong>") } else { label };
+            (item.depth + usize::from(level) - 1, "heading", format!("h{level}"), label)
+          }
+          OutlineKind::Landmark(role) => (item.depth, "landmark", role.to_string(), label),
+        };
+        let _ = write!(
+          body,
+          "<li class=\"{class}\" style=\"margin-left:{}rem\"><code>{kind}</code> {label} <small>line {}</small></li>",
+          indent as f32 * 1.5,
+          item.line
+        );
+      }
+      body.push_str("</ul>");
+    }
+  }
+  report_page("Outline", path, &body)
+}
+
+/// A dev report page about `path`, linking to the others.
+#[cfg(all(feature = "devel", feature = "actix"))]
+fn report_page(title: &str, path: &str, body: &str) -> String {
+  let query = percent_encode(path);
+  let path = tera::escape_html(path);
+  format!(
+    "<!DOCTYPE html>\n<html lang=\"en\"><head><meta charset=\"utf-8\"><title>{title}: {path}</title>\
+     <style>body{{font:15px/1.5 sans-serif;margin:2rem;color:#222}}table{{border-collapse:collapse;width:100%}}\
+     th,td{{text-align:left;padding:.4rem .6rem;border-bottom:1px solid #ddd;vertical-align:top}}\
+     code{{font-size:13px;color:#444}}.outline{{list-style:none;padding:0}}.outline li{{padding:.2rem 0}}\
+     .outline .landmark>code{{color:#6a1b9a}}.outline strong{{color:#b00020}}</style></head>\
+     <body><nav><a href=\"{A11Y_PATH}?path={query}\">Accessibility audit</a> · <a href=\"{OUTLINE_PATH}?path={query}\">Outline</a></nav>\
+     <h1>{title}: <a href=\"{path}\">{path}</a></h1>{body}</body></html>"
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rules(html: &str) -> Vec<&'static str> {
+    audit(html).into_iter().map(|issue| issue.rule).collect()
+  }
+
+  #[test]
+  fn test_audit_finds_common_problems() {
+    let page = "<!DOCTYPE html>\n<html>\n<head></head><body>\n\
+      <img src=\"a.png\">\n<img src=\"b.png\" alt=\"\">\n\
+      <a href=\"/x\"><img src=\"c.png\" alt=\"Home\"></a>\n<a href=\"/y\"><svg></svg></a>\n\
+      <h1>Title</h1><h3>Skipped</h3>\n\
+      <label>Name <input name=\"name\"></label><input id=\"email\"><label for=\"email\">Email</label><input name=\"q\">\n\
+      <div id=\"dup\"></div><p id=\"dup\" role=\"banana\" aria-describedby=\"nope\">x</p>\n\
+      <button aria-hidden=\"true\">Go</button><span tabindex=\"2\">x</span>\n\
+      <p style=\"color: #999\">faint</p><div style=\"background:#000 url(x.png)\"><p style=\"color:rgb(40, 40, 40)\">dark</p></div>\n\
+      </body></html>";
+    assert_eq!(
+      rules(page),
+      [
+        "html-lang",
+        "document-title",
+        "img-alt",
+        "link-name",
+        "heading-order",
+        "control-label",
+        "duplicate-id",
+        "aria-role",
+        "aria-reference",
+        "aria-hidden-focus",
+        "tabindex",
+        "color-contrast",
+        "color-contrast",
+      ]
+    );
+    let issues = audit(page);
+    assert_eq!(issues[2].line, 4);
+    assert_eq!(issues[2].element, "<img src=\"a.png\">");
+    assert_eq!(issues[11].message, "Text contrast is 2.85:1, below 4.5:1");
+
+    let clean = "<html lang=\"en\"><head><title>Home</title><style>a<b{}</style></head>\
+      <body><button type=\"submit\"><span>Save</span></button><input type=\"submit\" value=\"Send\"><input type=\"hidden\"></body></html>";
+    assert!(audit(clean).is_empty(), "{:?}", audit(clean));
+    assert_eq!(parse_color("#FfF"), Some(Rgb(255, 255, 255)));
+    assert_eq!(parse_color("rgba(0, 0, 0, 0.5)"), None);
+  }
+
+  #[test]
+  fn test_outline_lists_headings_and_landmarks() {
+    let page = "<body><header><h1>Site <img src=\"logo.png\" alt=\"logo\"></h1></header>\n\
+      <nav aria-label=\"Main\"><a href=\"/\">Home</a></nav>\n\
+      <main><article><header><h2>Post &amp; <em>news</em></h2></header><section><h3></h3></section>\
+      <div role=\"heading\" aria-level=\"4\">Aside <span>note</span></div></article></main>\n\
+      <form></form><footer>(c)</footer></body>";
+    let items = outline(page);
+    let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+    assert_eq!(labels, ["", "Site logo", "Main", "", "Post & news", "", "Aside note", ""]);
+    let kinds: Vec<OutlineKind> = items.iter().map(|item| item.kind).collect();
+    assert_eq!(
+      kinds,
+      [
+        OutlineKind::Landmark("banner"),
+        OutlineKind::Heading(1),
+        OutlineKind::Landmark("navigation"),
+        OutlineKind::Landmark("main"),
+        OutlineKind::Heading(2),
+        OutlineKind::Heading(3),
+        OutlineKind::Heading(4),
+        OutlineKind::Landmark("contentinfo"),
+      ]
+    );
+    let depths: Vec<(usize, usize)> = items.iter().map(|item| (item.depth, item.line)).collect();
+    assert_eq!(depths, [(0, 1), (1, 1), (0, 2), (0, 3), (1, 3), (1, 3), (1, 3), (0, 4)]);
+  }
+}
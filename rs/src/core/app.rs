@@ -1,12 +1,82 @@
-use crate::error::{Result, SnapFireError};
+#[cfg(feature = "actix")]
+use crate::core::assets::AssetFiles;
+use crate::core::assets::{AssetFunction, Assets, ModuleScriptsFunction};
+use crate::core::audit::{AuditLog, TemplateChange};
+use crate::core::avatar::AvatarFunction;
+use crate::core::breaker::CircuitBreaker;
+use crate::core::buffer::BufferPool;
+use crate::core::bundle::TemplateBundle;
+use crate::core::cache::{self, CacheDimensions, CacheFunction, CachedFetchFunction, DataLoader, FragmentCache};
+use crate::core::charts::{ChartFunction, SparklineFunction};
+use crate::core::comments;
+use crate::core::consent;
+use crate::core::context::GlobalContext;
+use crate::core::deferred::{AwaitBlockFunction, BlockRenderer, DeferredBlock, DeferredBlocks, render_with_blocks};
+use crate::core::flush::FlushFunction;
+#[cfg(feature = "actix")]
+use crate::core::flush::{FlushWriter, with_flushing};
+use crate::core::forms::HoneypotField;
+use crate::core::front_matter::{self, FrontMatter, PageIndex, RawTemplate};
+use crate::core::hints::{EarlyHints, ResourceHints, origin_of};
+use crate::core::i18n::{AlternateLinks, Locales, LocalizedUrl};
+use crate::core::icons::{IconFunction, IconSprite};
+use crate::core::lsp::HelperDoc;
+use crate::core::csp::EngineSources;
+use crate::core::env::RenderEnv;
+use crate::core::hooks::{ContextHooks, PostProcessors, RenderInfo};
+use crate::core::nav::{NavFunction, NavItem, Navigation};
+use crate::core::ordering;
+use crate::core::plural::Plural;
+#[cfg(feature = "actix")]
+use crate::core::proxy::TrustedProxies;
+use crate::core::purge::{CdnPurger, CdnPurgers, Purge};
+use crate::core::report::{self, ErrorReporter, RenderFailure};
+use crate::core::scope::{RenderScope, ScopeValues, with_scope};
+use crate::core::resolver::TemplateResolver;
+use crate::core::response;
+use crate::core::rtl::{Dir, LogicalCss, TextAlignStart};
+use crate::core::schema::ContextSchema;
+use crate::core::search::{self, SearchIndex, SearchIndexUrl, SearchPage};
+use crate::core::sensitive;
+use crate::core::signed::{SignedAssetFunction, UrlSigner};
+use crate::core::svg::{SvgFiles, SvgFunction};
+use crate::core::timeago::{self, TimeagoFilter};
+use crate::core::view::IntoTemplateContext;
+use crate::core::xss;
+use crate::error::{Result, SnapFireError, panic_message};
 
+use bytes::{BufMut, Bytes};
 use parking_lot::RwLock;
 use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tera::{Context, Tera};
+use std::time::Duration;
+use tera::{Context, Tera, Value};
 
+#[cfg(feature = "barcode")]
+use crate::core::barcode::{BarcodeFunction, QrCodeFunction};
+#[cfg(feature = "content")]
+use crate::core::content::{Content, ContentFunction, TaxonomyFunction};
+#[cfg(feature = "math")]
+use crate::core::math::{MathOptions, MathRenderer};
+#[cfg(feature = "pdf")]
+use crate::core::pdf::{Chromium, Pdf, PdfBackend, PdfOptions};
+#[cfg(feature = "screenshot")]
+use crate::core::screenshot::{self, Screenshot, ScreenshotBackend, Viewport};
 #[cfg(feature = "devel")]
-use crate::core::reload::DevReloader;
+use crate::core::reload::{ChangeSink, DevReloader, ReloadMessage, ReloadReceiver, WatchSource};
+#[cfg(feature = "sanitize")]
+use crate::core::sanitize::{self, MarkdownFilter, SanitizeHtml, SanitizePolicy};
+#[cfg(feature = "schedule")]
+use crate::core::schedule::{RerenderJob, Scheduler};
+#[cfg(feature = "typography")]
+use crate::core::typography::{EmojiFilter, SmartypantsFilter};
+#[cfg(feature = "uploads")]
+use crate::core::uploads::{UploadFieldFunction, UploadRule};
 
 /// A framework-agnostic representation of a template to be rendered.
 ///
@@ -18,6 +88,99 @@ pub struct Template {
   pub(crate) app_state: TeraWeb,
   pub(crate) template_name: String,
   pub(crate) context: Context,
+  /// An already rendered page to serve instead, e.g. from a scheduled render.
+  pub(crate) prerendered: Option<Bytes>,
+  /// The status the page is sent with, unless rendering fails.
+  pub(crate) status: u16,
+  /// Headers the handler added, with lowercase names, sent with the page.
+  pub(crate) headers: Vec<(String, String)>,
+  /// The keys of the values added with `insert_sensitive`.
+  pub(crate) sensitive: Vec<String>,
+}
+
+impl Template {
+  /// Sends the page with `status` instead of `200`, e.g. a "not found" page
+  /// with `404` or a form with errors with `422`. A failed render or an
+  /// open circuit still gets its own error response.
+  ///
+  /// # Arguments
+  ///
+  /// * `status` - The status code, e.g. `StatusCode::NOT_FOUND` from Actix,
+  ///   hyper, or the `http` crate, or `404`.
+  ///
+  /// # Panics
+  ///
+  /// If `status` isn't a valid status code (100 to 999).
+  pub fn with_status(mut self, status: impl Into<u16>) -> Self {
+    let status = status.into();
+    assert!((100..1000).contains(&status), "Invalid HTTP status code {status}");
+    self.status = status;
+    self
+  }
+
+  /// Sends `name: value` with the page, replacing any earlier value of the
+  /// header, including those SnapFire sets (such as `Cache-Control` or
+  /// `Vary`).
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The header, e.g. `"cache-control"`.
+  /// * `value` - Its value, e.g. `"public, max-age=600"`.
+  ///
+  /// # Panics
+  ///
+  /// If `name` or `value` isn't a valid header name or value.
+  pub fn insert_header(mut self, name: &str, value: &str) -> Self {
+    self.headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+    self.append_header(name, value)
+  }
+
+  /// Adds a value that renders normally but is kept out of diagnostics:
+  /// error reports show `[scrubbed]` in its place, and a render error
+  /// quoting it has it replaced by `[scrubbed]`. The key is kept beside the
+  /// context, so templates never see the mark.
+  ///
+  /// # Arguments
+  ///
+  /// * `key` - The name templates use, e.g. `"email"`. Everything under it
+  ///   is sensitive, including nested values.
+  /// * `value` - The value, replacing any earlier one with the same key.
+  pub fn insert_sensitive<T: Serialize + ?Sized>(mut self, key: &str, value: &T) -> Self {
+    self.context.insert(key, value);
+    self.sensitive.push(key.to_string());
+    self
+  }
+
+  /// Renders the page now, for use outside of a response: a file, a queue
+  /// message, a test. The page renders without a request (no host globals,
+  /// path, or middleware values), and the status and headers are dropped.
+  pub fn into_bytes(self) -> Result<Bytes> {
+    match self.prerendered {
+      Some(page) => Ok(page),
+      None => {
+        let request = RequestParts {
+          sensitive: &self.sensitive,
+          ..RequestParts::default()
+        };
+        self.app_state.render_bytes_for_request(&self.template_name, request, self.context)
+      }
+    }
+  }
+
+  /// Renders the page now into a `String`, as `into_bytes` does.
+  pub fn into_string(self) -> Result<String> {
+    let page = self.into_bytes()?;
+    Ok(String::from_utf8(page.into()).expect("Tera always renders valid UTF-8"))
+  }
+
+  /// Sends `name: value` with the page, keeping the header's other values,
+  /// as for `Set-Cookie`.
+  pub(crate) fn append_header(mut self, name: &str, value: &str) -> Self {
+    assert!(response::is_header_name(name), "invalid header name `{name}`");
+    assert!(response::is_header_value(value), "invalid header value for `{name}`");
+    self.headers.push((name.to_ascii_lowercase(), value.to_string()));
+    self
+  }
 }
 
 /// The primary application state for SnapFire, designed to be shared across threads.
@@ -26,10 +189,83 @@ pub struct Template {
 /// the `TeraWeb::builder()` method and shared with Actix handlers via `web::Data`.
 #[derive(Clone, Debug)]
 pub struct TeraWeb {
-  /// The Tera instance, wrapped for thread-safe access and mutability (for reloads).
-  pub(crate) tera: Arc<RwLock<Tera>>,
-  /// The pre-built global context, shared across all requests.
-  pub(crate) global_context: Arc<Context>,
+  /// The active Tera instance, swappable as a whole on reload.
+  pub(crate) tera: SharedTera,
+  /// The pre-built global context (with per-host overrides), shared across all requests.
+  pub(crate) globals: Arc<GlobalContext>,
+  /// Context schemas, keyed by template name, checked in debug builds.
+  pub(crate) schemas: Arc<HashMap<String, ContextSchema>>,
+  /// Reusable output buffers for the response render path.
+  pub(crate) buffers: Arc<BufferPool>,
+  /// Assets to preload per template, sent as `Link` headers.
+  pub(crate) early_hints: Arc<EarlyHints>,
+  /// Functions run, in order, over every merged context before rendering.
+  pub(crate) context_hooks: Arc<ContextHooks>,
+  /// Functions run, in order, over every rendered page.
+  pub(crate) post_processors: Arc<PostProcessors>,
+  /// The pages to index for client-side search, if configured.
+  pub(crate) search: Option<Arc<SearchIndex>>,
+  /// The locales the site is served in, if configured.
+  #[cfg(feature = "actix")]
+  pub(crate) locales: Option<Arc<Locales>>,
+  /// The rules mapping requested template names to loaded ones, if configured.
+  pub(crate) resolver: Option<Arc<TemplateResolver>>,
+  /// The sprite behind the `icon` function, if an icon directory is set.
+  pub(crate) icons: Option<Arc<IconSprite>>,
+  /// The asset directory `configure_routes` serves, if set.
+  #[cfg(feature = "actix")]
+  pub(crate) asset_files: Option<Arc<AssetFiles>>,
+  /// Whether the Actix responder streams pages in chunks as they render.
+  #[cfg(feature = "actix")]
+  pub(crate) early_flush: bool,
+  /// Whether any blocks are declared for `await_block`.
+  pub(crate) deferred_blocks: bool,
+  /// The site's default render environment, exposed as `env`.
+  pub(crate) render_env: Arc<RenderEnv>,
+  /// Whether debug builds warn about user-looking values marked safe.
+  pub(crate) xss_audit: bool,
+  /// Whether the keys of the context's objects are sorted before rendering.
+  pub(crate) sort_context_keys: bool,
+  /// Whether a panic during a render becomes a `SnapFireError::Panic`.
+  pub(crate) catch_panics: bool,
+  /// The function failed renders are reported to, if any.
+  pub(crate) error_reporter: Option<ErrorReporter>,
+  /// The breaker serving a fallback for templates that keep failing, if any.
+  pub(crate) circuit_breaker: Option<Arc<CircuitBreaker>>,
+  /// The template failed renders are answered with, if any.
+  pub(crate) error_template: Option<String>,
+  /// The fragments cached by the `cache` function, if it's enabled.
+  pub(crate) fragment_cache: Option<Arc<FragmentCache>>,
+  /// The values cache keys vary by, and the `Vary` header naming their source.
+  pub(crate) cache_dimensions: Option<Arc<CacheDimensions>>,
+  /// Whether pages are sent with their cache tags as `Surrogate-Key`.
+  pub(crate) surrogate_keys: bool,
+  /// The CDNs purged when templates, content, or tagged data change.
+  pub(crate) cdn_purgers: Arc<CdnPurgers>,
+  /// The CSP sources the engine's output needs, for `SecurityHeaders`.
+  #[cfg(feature = "actix")]
+  pub(crate) csp_sources: Arc<EngineSources>,
+  /// The proxies whose forwarding headers are believed.
+  #[cfg(feature = "actix")]
+  pub(crate) trusted_proxies: Arc<TrustedProxies>,
+  /// The custom helpers described for editor tooling.
+  pub(crate) helper_docs: Arc<Vec<HelperDoc>>,
+  /// The converter behind `render_pdf`, with the `pdf` feature.
+  #[cfg(feature = "pdf")]
+  pub(crate) pdf_backend: Arc<dyn PdfBackend>,
+
+  /// The capturer behind `render_screenshot`, with the `screenshot` feature.
+  #[cfg(feature = "screenshot")]
+  pub(crate) screenshot_backend: Arc<dyn ScreenshotBackend>,
+  /// The rules of the declared upload fields, with the `uploads` feature.
+  #[cfg(feature = "uploads")]
+  pub(crate) upload_rules: Arc<HashMap<String, UploadRule>>,
+  /// The collections behind the `content` function, with the `content` feature.
+  #[cfg(feature = "content")]
+  pub(crate) content: Option<Arc<Content>>,
+  /// The thread re-rendering scheduled pages, with the `schedule` feature.
+  #[cfg(feature = "schedule")]
+  pub(crate) scheduler: Option<Arc<Scheduler>>,
   /// The live-reload controller, present only when the `devel` feature is enabled.
   #[cfg(feature = "devel")]
   pub(crate) reloader: Arc<DevReloader>,
@@ -44,27 +280,519 @@ impl TeraWeb {
   ///
   /// * `templates_glob` - A glob pattern (e.g., "templates/**/*.html") for Tera to find templates.
   pub fn builder(templates_glob: &str) -> TeraWebBuilder {
-    TeraWebBuilder::new(templates_glob)
+    TeraWebBuilder::new(TemplateSource::Glob(templates_glob.to_string()))
+  }
+
+  /// Creates a new `TeraWebBuilder` whose templates come from a precompiled bundle
+  /// instead of the filesystem.
+  ///
+  /// Template changes are not watched in this mode, since there are no template
+  /// files; static paths are still watched in dev mode.
+  ///
+  /// # Arguments
+  ///
+  /// * `bundle` - The bundle, typically built by `snapfire build-templates`.
+  pub fn builder_from_bundle(bundle: TemplateBundle) -> TeraWebBuilder {
+    TeraWebBuilder::new(TemplateSource::Bundle(bundle))
+  }
+
+  /// Renders a template to a `String`, outside of any request: for emails,
+  /// background jobs, or tests.
+  ///
+  /// The context is merged with the globals as in `render`, but without a
+  /// request there are no host globals, path, or middleware values.
+  ///
+  /// # Arguments
+  ///
+  /// * `tpl` - The name of the template to render.
+  /// * `context` - A `tera::Context`, or a view model implementing [`IntoTemplateContext`].
+  pub fn render_to_string(&self, tpl: &str, context: impl IntoTemplateContext) -> Result<String> {
+    self.render_for_request(tpl, RequestParts::default(), context.into_template_context())
+  }
+
+  /// Like `render_to_string`, but for a specific request: the globals
+  /// registered for its host (via `add_host_globals`) are layered between the
+  /// site-wide globals and the user context.
+  pub(crate) fn render_for_request(&self, tpl: &str, request: RequestParts, user_context: Context) -> Result<String> {
+    let mut output = Vec::new();
+    self.render_into_for_request(tpl, request, user_context, &mut output)?;
+
+    Ok(String::from_utf8(output).expect("Tera always renders valid UTF-8"))
+  }
+
+  /// Renders a template straight into `writer`, without building an
+  /// intermediate `String`.
+  ///
+  /// This is the streaming primitive behind the other render paths: the
+  /// context is merged with the globals exactly as in `render`, and the output
+  /// is written as Tera produces it.
+  ///
+  /// # Arguments
+  ///
+  /// * `tpl` - The name of the template to render.
+  /// * `context` - The context for this render.
+  /// * `writer` - Where to write the rendered output (a file, socket, buffer...).
+  pub fn render_into(&self, tpl: &str, context: Context, writer: &mut impl Write) -> Result<()> {
+    self.render_into_for_request(tpl, RequestParts::default(), context, writer)
+  }
+
+  /// `render_into` for a specific request, with its per-host globals applied.
+  pub(crate) fn render_into_for_request<W: Write>(
+    &self,
+    tpl: &str,
+    request: RequestParts,
+    user_context: Context,
+    writer: &mut W,
+  ) -> Result<()> {
+    let Some(breaker) = &self.circuit_breaker else {
+      return self.render_reported(tpl, request, user_context, writer);
+    };
+    if breaker.open_for(tpl).is_some() {
+      let mut context = user_context;
+      context.insert("unavailable_template", tpl);
+      return self.render_reported(breaker.fallback(), request, context, writer);
+    }
+    let result = self.render_reported(tpl, request, user_context, writer);
+    breaker.record(tpl, result.is_ok());
+    result
+  }
+
+  /// How long `tpl` is served the circuit breaker's fallback for, if its
+  /// circuit is open.
+  pub(crate) fn circuit_open_for(&self, tpl: &str) -> Option<Duration> {
+    self.circuit_breaker.as_ref()?.open_for(tpl)
+  }
+
+  /// The `error_template` page for a render for `request` that failed with
+  /// `error`, unless there's none or it fails too.
+  pub(crate) fn render_error_page(&self, request: RequestParts, error: &SnapFireError) -> Option<Bytes> {
+    let tpl = self.error_template.as_deref()?;
+    let mut context = Context::new();
+    context.insert("status", &500);
+    if let Some(id) = request.request_id {
+      context.insert("request_id", id);
+    }
+    if let Some(nonce) = request.nonce {
+      context.insert("csp_nonce", nonce);
+    }
+    // Render errors have their sensitive values scrubbed already.
+    if cfg!(feature = "devel") {
+      context.insert("error", &crate::error::error_chain(error));
+    }
+    match self.render_bytes_for_request(tpl, request, context) {
+      Ok(page) => Some(page),
+      Err(e) => {
+        log::error!("Failed to render the error template `{}`: {:?}", tpl, e);
+        None
+      }
+    }
   }
 
-  /// The internal, framework-agnostic rendering function.
+  /// Renders, catching panics if configured and reporting failures.
+  fn render_reported<W: Write>(
+    &self,
+    tpl: &str,
+    request: RequestParts,
+    user_context: Context,
+    writer: &mut W,
+  ) -> Result<()> {
+    let mut summary = None;
+    let result = if self.catch_panics {
+      // The thread-locals a render sets restore themselves as the panic
+      // unwinds, and a merged context it was using is dropped, not pooled.
+      catch_unwind(AssertUnwindSafe(|| {
+        self.render_unguarded(tpl, request, user_context, writer, &mut summary)
+      }))
+      .unwrap_or_else(|panic| {
+        let message = panic_message(panic.as_ref());
+        log::error!(
+          "Panic while rendering `{tpl}` (host: {}, path: {}, request: {}): {message}",
+          request.host.unwrap_or("-"),
+          request.path.unwrap_or("-"),
+          request.request_id.unwrap_or("-")
+        );
+        Err(SnapFireError::Panic {
+          template: tpl.to_string(),
+          message,
+        })
+      })
+    } else {
+      self.render_unguarded(tpl, request, user_context, writer, &mut summary)
+    };
+
+    if let Err(error) = &result
+      && let Some(reporter) = &self.error_reporter
+    {
+      let tera = Arc::clone(&self.tera.read());
+      reporter.report(&RenderFailure::new(&tera, tpl, &request, error, summary.unwrap_or_default()));
+    }
+    result
+  }
+
+  /// Renders without catching panics, leaving a summary of the merged
+  /// context in `summary` when the render fails and errors are reported.
+  fn render_unguarded<W: Write>(
+    &self,
+    tpl: &str,
+    request: RequestParts,
+    user_context: Context,
+    writer: &mut W,
+    summary: &mut Option<BTreeMap<String, String>>,
+  ) -> Result<()> {
+    // Take a snapshot of the active template set. The lock is only held for the
+    // clone of the `Arc`, so a reload swapping in a new set never waits on renders.
+    let tera = Arc::clone(&self.tera.read());
+    let resolved = self.resolve_in(&tera, tpl);
+    let tpl = resolved.as_ref();
+    #[cfg(feature = "testing")]
+    crate::testing::coverage::record(&tera, tpl);
+
+    // Expose the template's front matter as `page`.
+    let mut user_context = user_context;
+    if let Some(page) = front_matter::merged(&tera, &tera.pages, tpl) {
+      front_matter::insert_page(&mut user_context, page);
+    }
+
+    // The request's environment, over the site's defaults.
+    let env = match request.env {
+      Some(env) => {
+        let env = env.clone().or(&self.render_env);
+        if !user_context.contains_key("env") {
+          user_context.insert("env", &env);
+        }
+        env
+      }
+      None => RenderEnv::clone(&self.render_env),
+    };
+
+    let normalized_host = request.host.map(normalize_host);
+    let info = RenderInfo {
+      template: tpl,
+      host: normalized_host.as_deref(),
+      path: request.path,
+      nonce: request.nonce,
+    };
+
+    let mut render_merged = |final_context: &Context| -> Result<()> {
+      // Loops over maps run in key order whatever order the maps keep.
+      let sorted;
+      let final_context = if self.sort_context_keys {
+        sorted = ordering::sorted(final_context);
+        &sorted
+      } else {
+        if cfg!(debug_assertions) {
+          ordering::warn_about_map_loops(&tera, tpl);
+        }
+        final_context
+      };
+      self.validate_context(tpl, final_context)?;
+      self.audit_escaping(&tera, tpl, final_context);
+
+      // Post-processors need the whole page, so only buffer it when there are any.
+      let render_page = || -> Result<()> {
+        let render_page = |writer: &mut W| -> Result<()> {
+          if self.post_processors.is_empty() {
+            tera.render_to(tpl, final_context, writer)?;
+          } else {
+            let mut output = tera.render(tpl, final_context)?;
+            self.post_processors.run(&mut output, &info);
+            writer.write_all(output.as_bytes())?;
+          }
+          Ok(())
+        };
+        // Only hand `await_block` the render when there are blocks to await.
+        if !self.deferred_blocks {
+          return render_page(writer);
+        }
+        let renderer = BlockRenderer {
+          tera: Arc::clone(&tera),
+          globals: Arc::clone(&self.globals),
+          host: request.host.map(str::to_string),
+          nonce: request.nonce.map(str::to_string),
+        };
+        render_with_blocks(renderer, writer, render_page)
+      };
+
+      // Describe the render to filters and functions (`nav`, `plural`, the
+      // app's own...); a `locale` the page sets itself wins.
+      let mut env = env.clone();
+      if let Some(locale) = final_context.get("locale").and_then(Value::as_str) {
+        env.locale = Some(locale.to_string());
+      }
+      let scope = RenderScope {
+        template: tpl.to_string(),
+        host: normalized_host.clone(),
+        path: request.path.map(str::to_string),
+        request_id: request.request_id.map(str::to_string),
+        env,
+        values: request.values.cloned().unwrap_or_default(),
+        templates: Some(Arc::clone(&tera)),
+      };
+      with_scope(scope, render_page)
+    };
+    let mut render = |final_context: &Context| {
+      let result = render_merged(final_context).map_err(|error| scrub_error(final_context, request.sensitive, error));
+      if result.is_err() && self.error_reporter.is_some() {
+        *summary = Some(report::summarize(final_context, request.sensitive));
+      }
+      result
+    };
+
+    // Layer the user's values over the shared globals (the user's values win)
+    // and render, without deep-cloning the globals.
+    if self.context_hooks.is_empty() {
+      self.globals.with_merged(request.host, user_context, render)
+    } else {
+      self.globals.with_merged_mut(request.host, user_context, |final_context| {
+        self.context_hooks.run(final_context, &info);
+        render(final_context)
+      })
+    }
+  }
+
+  /// Renders like `render_into_for_request`, handing the page to `send` in
+  /// chunks that end at `</head>` and at each `flush()`. With
+  /// post-processors, the page is sent in one chunk once complete.
+  #[cfg(feature = "actix")]
+  pub(crate) fn render_flushing_for_request(
+    &self,
+    tpl: &str,
+    request: RequestParts,
+    user_context: Context,
+    mut send: impl FnMut(Bytes),
+  ) -> Result<()> {
+    if !self.post_processors.is_empty() {
+      send(self.render_bytes_for_request(tpl, request, user_context)?);
+      return Ok(());
+    }
+    let mut writer = FlushWriter::new(send);
+    with_flushing(|| self.render_into_for_request(tpl, request, user_context, &mut writer))?;
+    writer.finish();
+    Ok(())
+  }
+
+  /// Checks the merged context against the template's registered schema.
+  ///
+  /// This only runs in debug builds; release builds skip it entirely.
+  fn validate_context(&self, tpl: &str, context: &Context) -> Result<()> {
+    if !cfg!(debug_assertions) {
+      return Ok(());
+    }
+    let Some(schema) = self.schemas.get(tpl) else {
+      return Ok(());
+    };
+
+    let violations = schema.validate(&context.clone().into_json());
+    if violations.is_empty() {
+      Ok(())
+    } else {
+      Err(SnapFireError::Schema {
+        template: tpl.to_string(),
+        violations: violations.iter().map(ToString::to_string).collect(),
+      })
+    }
+  }
+
+  /// Logs the `| safe` values of the render that look user-typed, when
+  /// `xss_audit` is on.
   ///
-  /// This takes a template name and a user-provided context, merges it with the
-  /// global context, and renders the template to a string.
-  pub(crate) fn render_with_context(&self, tpl: &str, user_context: Context) -> Result<String> {
+  /// This only runs in debug builds; release builds skip it entirely.
+  fn audit_escaping(&self, tera: &Tera, tpl: &str, context: &Context) {
+    if !cfg!(debug_assertions) || !self.xss_audit {
+      return;
+    }
+    for warning in xss::audit(tera, tpl, &context.clone().into_json()) {
+      log::warn!("{warning}");
+    }
+  }
+
+  /// Renders into a pooled buffer and returns the output as `Bytes`, ready to
+  /// be used as a response body without further copies.
+  pub(crate) fn render_bytes_for_request(
+    &self,
+    tpl: &str,
+    request: RequestParts,
+    user_context: Context,
+  ) -> Result<Bytes> {
+    let mut buffer = self.buffers.take();
+    let result = self.render_into_for_request(tpl, request, user_context, &mut (&mut buffer).writer());
+    let body = buffer.split().freeze();
+    self.buffers.give(buffer);
+
+    result.map(|()| body)
+  }
+
+  /// Renders like `render_bytes_for_request`, along with the page's
+  /// `Surrogate-Key` header value when the builder's `surrogate_keys` is on
+  /// and the page used tagged fragments or data.
+  pub(crate) fn render_keyed_for_request(
+    &self,
+    tpl: &str,
+    request: RequestParts,
+    user_context: Context,
+  ) -> (Result<Bytes>, Option<String>) {
+    if !self.surrogate_keys {
+      return (self.render_bytes_for_request(tpl, request, user_context), None);
+    }
+    let (result, tags) = cache::collecting_tags(|| self.render_bytes_for_request(tpl, request, user_context));
+    let keys = result.as_ref().ok().and_then(|_| cache::surrogate_key(&tags));
+    (result, keys)
+  }
+
+  /// The `Vary` header pages are sent with, if cache keys have dimensions.
+  pub(crate) fn vary(&self) -> Option<&str> {
+    self.cache_dimensions.as_ref().map(|dimensions| dimensions.vary.as_str()).filter(|vary| !vary.is_empty())
+  }
+
+  /// The `Link: rel=preload` header value for `tpl`, if it (or a template it
+  /// extends) has declared early hints.
+  pub(crate) fn early_hints_for(&self, tpl: &str) -> Option<String> {
+    if self.early_hints.is_empty() {
+      return None;
+    }
+    let tera = self.tera.read();
+    self.early_hints.link_header(&tera, &self.resolve_in(&tera, tpl))
+  }
+
+  /// The name of the loaded template `tpl` refers to, following the builder's
+  /// `template_resolver` rules, or `None` if there is no such template.
+  pub fn resolve_template(&self, tpl: &str) -> Option<String> {
     let tera = self.tera.read();
+    match &self.resolver {
+      Some(resolver) => resolver.resolve(&tera, tpl),
+      None => tera.get_template(tpl).is_ok().then(|| tpl.to_string()),
+    }
+  }
+
+  /// The front matter of the template `tpl` refers to, merged over that of the
+  /// templates it extends, if any of them has one.
+  pub fn front_matter(&self, tpl: &str) -> Option<FrontMatter> {
+    let tera = Arc::clone(&self.tera.read());
+    front_matter::merged(&tera, &tera.pages, &self.resolve_in(&tera, tpl))
+  }
+
+  /// The changes applied to the templates since the app was built (live
+  /// reloads, static export rebuilds), oldest first. Only the latest 500
+  /// are kept.
+  pub fn template_audit(&self) -> Vec<TemplateChange> {
+    self.tera.read().audit.changes()
+  }
+
+  /// Drops every fragment the `cache` function cached under `tag`, in every
+  /// template, returning how many there were. Without a
+  /// `TeraWebBuilder::fragment_cache`, there is nothing to drop. The CDNs
+  /// added with `TeraWebBuilder::add_cdn_purger` purge the tag too.
+  ///
+  /// # Arguments
+  ///
+  /// * `tag` - A tag passed to `cache`, e.g. `user:42`.
+  pub fn invalidate_tag(&self, tag: &str) -> usize {
+    self.cdn_purgers.purge(Purge::Tags(vec![tag.to_string()]));
+    self.fragment_cache.as_ref().map_or(0, |cache| cache.invalidate_tag(tag))
+  }
+
+  /// Re-parses the templates from the builder's glob and swaps them in, e.g.
+  /// after a deploy replaced the files, then purges the CDNs added with
+  /// `TeraWebBuilder::add_cdn_purger`. Renders in flight finish with the old
+  /// templates.
+  ///
+  /// # Errors
+  ///
+  /// If a template fails to parse (the old ones stay active), or the app was
+  /// built from a bundle.
+  pub fn reload_templates(&self) -> Result<()> {
+    reload_templates(&self.tera, "reload_templates")?;
+    self.cdn_purgers.purge(Purge::Everything);
+    Ok(())
+  }
+
+  /// Re-reads the content collections, e.g. after new posts were synced to
+  /// the content directory, then purges the CDNs added with
+  /// `TeraWebBuilder::add_cdn_purger`. Without a `content_dir`, this does
+  /// nothing. Requires the `content` feature.
+  ///
+  /// # Errors
+  ///
+  /// If an entry fails to load; the previous collections stay active.
+  #[cfg(feature = "content")]
+  pub fn reload_content(&self) -> Result<()> {
+    let Some(content) = &self.content else {
+      return Ok(());
+    };
+    content.reload()?;
+    self.cdn_purgers.purge(Purge::Everything);
+    Ok(())
+  }
+
+  /// The entries of the content collection `collection`, as templates see
+  /// them through `content(name=...)`, e.g. for a handler rendering a single
+  /// post by its `slug`. Empty if there is no such collection.
+  #[cfg(feature = "content")]
+  pub fn content(&self, collection: &str) -> Vec<Value> {
+    self
+      .content
+      .as_ref()
+      .and_then(|content| content.collection(collection))
+      .map_or_else(Vec::new, |entries| entries.as_ref().clone())
+  }
+
+  /// The terms of the taxonomy `taxonomy`, as templates see them through
+  /// `taxonomy(name=...)`: each with its `name`, `slug`, `count`, and `pages`.
+  /// Empty if the taxonomy isn't declared.
+  #[cfg(feature = "content")]
+  pub fn taxonomy(&self, taxonomy: &str) -> Vec<Value> {
+    self
+      .content
+      .as_ref()
+      .and_then(|content| content.taxonomy(taxonomy))
+      .map_or_else(Vec::new, |terms| terms.as_ref().clone())
+  }
 
-    // 1. Start with a clone of our base globals.
-    let mut final_context = (*self.global_context).clone();
+  /// The term of `taxonomy` with the slug (or name) `term`, or `None`, e.g.
+  /// for a tag page handler to answer unknown tags with 404.
+  #[cfg(feature = "content")]
+  pub fn taxonomy_term(&self, taxonomy: &str, term: &str) -> Option<Value> {
+    self.content.as_ref()?.taxonomy_term(taxonomy, term)
+  }
 
-    // 2. Extend it with the context the user supplied.
-    //    The user's values will overwrite the globals, which is correct.
-    final_context.extend(user_context);
+  /// Renders `tpl` and converts the page to PDF with the builder's
+  /// `pdf_backend`, for documents like invoices that share templates with
+  /// the site. Requires the `pdf` feature.
+  ///
+  /// The conversion blocks on an external process with the built-in
+  /// backends; in async handlers, call this inside `web::block`.
+  #[cfg(feature = "pdf")]
+  pub fn render_pdf(&self, tpl: &str, context: Context, options: &PdfOptions) -> Result<Pdf> {
+    let html = self.render_for_request(tpl, RequestParts::default(), context)?;
+    let bytes = self.pdf_backend.convert(&options.apply(&html))?;
+    Ok(Pdf {
+      bytes: Bytes::from(bytes),
+      filename: None,
+    })
+  }
 
-    // 3. Render.
-    let body = tera.render(tpl, &final_context).map_err(SnapFireError::Tera)?;
+  /// Renders `tpl` and captures the page as a PNG at `viewport` with the
+  /// builder's `screenshot_backend`, e.g. for OpenGraph preview images.
+  /// Requires the `screenshot` feature.
+  ///
+  /// The capture blocks on an external process with the built-in backend;
+  /// in async handlers, call this inside `web::block`.
+  #[cfg(feature = "screenshot")]
+  pub fn render_screenshot(&self, tpl: &str, context: Context, viewport: &Viewport) -> Result<Screenshot> {
+    let html = self.render_for_request(tpl, RequestParts::default(), context)?;
+    let bytes = self.screenshot_backend.capture(&viewport.apply(&html), viewport)?;
+    Ok(Screenshot {
+      bytes: Bytes::from(bytes),
+    })
+  }
 
-    Ok(body)
+  /// `tpl` resolved in `tera`, or as given when it doesn't resolve, so a
+  /// missing template fails with Tera's usual error.
+  fn resolve_in<'a>(&self, tera: &Tera, tpl: &'a str) -> Cow<'a, str> {
+    match &self.resolver {
+      Some(resolver) => resolver.resolve(tera, tpl).map_or(Cow::Borrowed(tpl), Cow::Owned),
+      None => Cow::Borrowed(tpl),
+    }
   }
 
   /// Prepares a template for rendering.
@@ -72,42 +800,385 @@ impl TeraWeb {
   /// This method is synchronous and returns a `Template` struct, which can then
   /// be returned from an Actix handler. The actual rendering is performed
   /// asynchronously by the framework when the response is being sent.
-  pub fn render(&self, tpl: &str, context: Context) -> Template {
+  ///
+  /// `context` is a `tera::Context` or a view model implementing
+  /// [`IntoTemplateContext`].
+  pub fn render(&self, tpl: &str, context: impl IntoTemplateContext) -> Template {
     Template {
       app_state: self.clone(),
       template_name: tpl.to_string(),
-      context,
+      context: context.into_template_context(),
+      prerendered: None,
+      status: 200,
+      headers: Vec::new(),
+      sensitive: Vec::new(),
     }
   }
 
+  /// A sink for triggering reloads programmatically, e.g. from tests or
+  /// after an external build step, without touching the file system.
+  #[cfg(feature = "devel")]
+  pub fn change_sink(&self) -> ChangeSink {
+    self.reloader.sink.clone()
+  }
+
+  /// Subscribes to live-reload messages, for serving live reload from a
+  /// framework other than Actix, including sync servers and non-tokio runtimes.
+  #[cfg(feature = "devel")]
+  pub fn subscribe_reloads(&self) -> ReloadReceiver {
+    self.reloader.broadcaster.subscribe()
+  }
+
+  /// Sends `data` to every page connected to the live-reload WebSocket,
+  /// returning how many received it. Pages handle it with
+  /// `SnapFire.on("custom", data => ...)` in the injected script, which
+  /// makes it a quick way to prototype push features in development.
+  ///
+  /// Like reloads, a page that has fallen far behind gets a full reload in
+  /// place of the messages it missed.
   #[cfg(feature = "devel")]
-  pub(crate) fn get_reloader_broadcaster(&self) -> tokio::sync::broadcast::Sender<crate::core::reload::ReloadMessage> {
+  pub fn dev_push(&self, data: serde_json::Value) -> usize {
+    self.reloader.broadcaster.send(ReloadMessage::custom(&data))
+  }
+
+  /// Sends `data` to the pages' `SnapFire.on(event, ...)` handlers, for
+  /// dev-only events of snapfire's own, such as upload progress.
+  #[cfg(all(feature = "devel", feature = "uploads"))]
+  pub(crate) fn dev_event(&self, event: &str, data: serde_json::Value) -> usize {
+    self.reloader.broadcaster.send(ReloadMessage::event(event, &data))
+  }
+
+  #[cfg(all(feature = "devel", feature = "actix"))]
+  pub(crate) fn get_reloader_broadcaster(&self) -> crate::core::broadcast::Broadcaster {
     self.reloader.broadcaster.clone()
   }
 }
 
+/// `error`, with the values of `context` under the `sensitive` keys it
+/// quotes scrubbed.
+fn scrub_error(context: &Context, sensitive: &[String], error: SnapFireError) -> SnapFireError {
+  let SnapFireError::Tera(tera_error) = &error else {
+    return error;
+  };
+  match sensitive::scrub(context, sensitive, &crate::error::error_chain(tera_error)) {
+    Some(message) => SnapFireError::Tera(tera::Error::msg(message)),
+    None => error,
+  }
+}
+
+/// The parts of the incoming request that a render depends on.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RequestParts<'a> {
+  /// The raw `Host` header value.
+  pub(crate) host: Option<&'a str>,
+  /// The request path, e.g. `/docs/intro`.
+  pub(crate) path: Option<&'a str>,
+  /// The CSP nonce set by the `SecurityHeaders` middleware.
+  pub(crate) nonce: Option<&'a str>,
+  /// The render environment resolved for the request.
+  pub(crate) env: Option<&'a RenderEnv>,
+  /// The values middleware attached for filters and functions.
+  pub(crate) values: Option<&'a ScopeValues>,
+  /// The id the `RequestIds` middleware gave the request.
+  pub(crate) request_id: Option<&'a str>,
+  /// The keys of the context values to keep out of diagnostics.
+  pub(crate) sensitive: &'a [String],
+}
+
+impl<'a> RequestParts<'a> {
+  /// Request parts carrying only a host.
+  #[cfg(test)]
+  pub(crate) fn with_host(host: &'a str) -> Self {
+    Self {
+      host: Some(host),
+      ..Self::default()
+    }
+  }
+}
+
+/// Normalizes a `Host` header value for lookups: lowercased, without the port.
+pub(crate) fn normalize_host(host: &str) -> String {
+  let host = host.trim();
+  // Bracketed IPv6 literals keep their colons, e.g. `[::1]:8080`.
+  let without_port = match host.rfind(':') {
+    Some(index) if !host[index..].contains(']') => &host[..index],
+    _ => host,
+  };
+  without_port.to_ascii_lowercase()
+}
+
+/// The shared, swappable template set.
+///
+/// Readers clone the inner `Arc` and render without holding the lock; reloads
+/// build a complete replacement off to the side and swap it in.
+pub(crate) type SharedTera = Arc<RwLock<Arc<TemplateSet>>>;
+
+/// A `Tera` instance with the front matter stripped from its templates.
+///
+/// It derefs to the `Tera` instance, so it renders like one.
+#[derive(Debug, Clone)]
+pub(crate) struct TemplateSet {
+  tera: Tera,
+  /// The front matter of the templates that have one.
+  pub(crate) pages: PageIndex,
+  /// The glob the templates were loaded from, for reloads.
+  glob: Option<String>,
+  /// The changes applied to the app's templates, carried over on reload.
+  pub(crate) audit: Arc<AuditLog>,
+}
+
+impl TemplateSet {
+  /// Loads every template matched by `glob`.
+  pub(crate) fn from_glob(glob: &str) -> Result<Self> {
+    let mut tera = Tera::default();
+    let pages = front_matter::load(&mut tera, front_matter::glob_sources(glob)?)?;
+    Ok(Self {
+      tera,
+      pages,
+      glob: Some(glob.to_string()),
+      audit: Arc::default(),
+    })
+  }
+
+  /// Loads templates from memory; they can't be reloaded.
+  pub(crate) fn from_sources(sources: Vec<RawTemplate>) -> Result<Self> {
+    let mut tera = Tera::default();
+    let pages = front_matter::load(&mut tera, sources)?;
+    Ok(Self {
+      tera,
+      pages,
+      glob: None,
+      audit: Arc::default(),
+    })
+  }
+
+  /// The templates matched by `glob`, with the filters, functions and
+  /// testers registered on this set.
+  pub(crate) fn with_templates(&self, glob: &str) -> Result<Self> {
+    let mut tera = self.tera.clone();
+    let pages = front_matter::load(&mut tera, front_matter::glob_sources(glob)?)?;
+    Ok(Self {
+      tera,
+      pages,
+      glob: Some(glob.to_string()),
+      audit: Arc::clone(&self.audit),
+    })
+  }
+}
+
+impl std::ops::Deref for TemplateSet {
+  type Target = Tera;
+
+  fn deref(&self) -> &Tera {
+    &self.tera
+  }
+}
+
+impl std::ops::DerefMut for TemplateSet {
+  fn deref_mut(&mut self) -> &mut Tera {
+    &mut self.tera
+  }
+}
+
+/// Re-parses every template into a fresh set and swaps it in.
+///
+/// The new set is built from a clone of the active instance (which keeps the
+/// registered filters, functions and testers) without holding any lock, so
+/// in-flight renders keep using the old set and new renders never block on a
+/// slow reload. If parsing fails, the old set stays active. The changes are
+/// recorded in the audit log as applied by `actor`.
+pub(crate) fn reload_templates(tera: &SharedTera, actor: &str) -> Result<()> {
+  let current = Arc::clone(&tera.read());
+  let Some(glob) = &current.glob else {
+    return Err(tera::Error::msg("Reloading is only available if you are using a glob").into());
+  };
+  let next = current.with_templates(glob)?;
+  current.audit.record(actor, &current, &next);
+  *tera.write() = Arc::new(next);
+  Ok(())
+}
+
+/// A closure run on the `Tera` instance for advanced configuration.
+type TeraConfigurator = Box<dyn FnOnce(&mut Tera)>;
+
+/// Where a `TeraWebBuilder` loads its templates from.
+enum TemplateSource {
+  /// Template files matched by a glob, reloadable in dev mode.
+  Glob(String),
+  /// A precompiled, in-memory bundle.
+  Bundle(TemplateBundle),
+}
+
 /// A builder for creating a configured `TeraWeb` instance.
 pub struct TeraWebBuilder {
-  templates_glob: String,
+  templates: TemplateSource,
   globals: Context,
+  host_globals: HashMap<String, Context>,
+  schemas: HashMap<String, ContextSchema>,
+  early_hints: EarlyHints,
+  context_hooks: ContextHooks,
+  post_processors: PostProcessors,
+  csp_sources: EngineSources,
+  #[cfg(feature = "actix")]
+  trusted_proxies: Vec<String>,
+  preconnect_origins: Vec<String>,
+  timeago_script: bool,
+  strip_comments: bool,
+  #[cfg(feature = "actix")]
+  early_flush: bool,
+  deferred_blocks: DeferredBlocks,
+  render_env: RenderEnv,
+  xss_audit: bool,
+  sort_context_keys: bool,
+  catch_panics: bool,
+  error_reporter: Option<ErrorReporter>,
+  circuit_breaker: Option<CircuitBreaker>,
+  error_template: Option<String>,
+  fragment_cache: Option<FragmentCache>,
+  data_loaders: HashMap<String, DataLoader>,
+  cache_dimensions: Option<CacheDimensions>,
+  surrogate_keys: bool,
+  cdn_purgers: CdnPurgers,
+  nav_menus: HashMap<String, Vec<NavItem>>,
+  nav_file: Option<PathBuf>,
+  search_pages: Option<Vec<SearchPage>>,
+  search_index_path: String,
+  locales: Option<Locales>,
+  resolver: Option<TemplateResolver>,
+  #[cfg(feature = "sanitize")]
+  sanitize_policies: HashMap<String, SanitizePolicy>,
+  #[cfg(feature = "pdf")]
+  pdf_backend: Option<Arc<dyn PdfBackend>>,
+  #[cfg(feature = "screenshot")]
+  screenshot_backend: Option<Arc<dyn ScreenshotBackend>>,
+  #[cfg(feature = "uploads")]
+  upload_rules: HashMap<String, UploadRule>,
+  #[cfg(feature = "content")]
+  content_dir: Option<PathBuf>,
+  #[cfg(feature = "content")]
+  taxonomies: Vec<String>,
+  #[cfg(feature = "content")]
+  content_preview: bool,
+  #[cfg(feature = "schedule")]
+  rerender_jobs: Vec<(String, String, crate::core::schedule::ContextProvider)>,
+  #[cfg(feature = "schedule")]
+  schedule_timezone: chrono_tz::Tz,
   // A closure to run on the Tera instance for advanced configuration.
   // We use `Box<dyn...>` to store the closure in the struct.
-  tera_configurator: Option<Box<dyn FnOnce(&mut Tera)>>,
+  tera_configurator: Option<TeraConfigurator>,
+  helper_docs: Vec<HelperDoc>,
+  static_dir: Option<PathBuf>,
+  icons_dir: Option<PathBuf>,
+  asset_manifest: Option<(PathBuf, String)>,
+  asset_base_url: Option<String>,
+  #[cfg(feature = "actix")]
+  asset_dir: Option<PathBuf>,
+  url_signer: Option<UrlSigner>,
   static_paths_to_watch: Vec<String>,
   ws_path: String,
   auto_inject_script: bool,
+  external_script: bool,
+  ws_heartbeat_interval: Duration,
+  ws_client_timeout: Duration,
+  reload_channel_capacity: usize,
+  reload_quiet_period: Duration,
+  reload_min_interval: Duration,
+  #[cfg(feature = "actix")]
+  reload_poke_path: Option<String>,
+  #[cfg(feature = "actix")]
+  styleguide_dir: Option<String>,
+  #[cfg(feature = "actix")]
+  styleguide_stylesheets: Vec<String>,
+  #[cfg(feature = "devel")]
+  watch_sources: Vec<Box<dyn WatchSource>>,
+  #[cfg(feature = "notifications")]
+  desktop_notifications: bool,
 }
 
 impl TeraWebBuilder {
-  /// Creates a new builder with a specified template glob pattern.
-  pub(crate) fn new(templates_glob: &str) -> Self {
+  /// Creates a new builder loading templates from the given source.
+  fn new(templates: TemplateSource) -> Self {
     Self {
-      templates_glob: templates_glob.to_string(),
+      templates,
       globals: Context::new(),
+      host_globals: HashMap::new(),
+      schemas: HashMap::new(),
+      early_hints: EarlyHints::default(),
+      context_hooks: ContextHooks::default(),
+      post_processors: PostProcessors::default(),
+      csp_sources: EngineSources::default(),
+      #[cfg(feature = "actix")]
+      trusted_proxies: Vec::new(),
+      preconnect_origins: Vec::new(),
+      timeago_script: false,
+      strip_comments: false,
+      #[cfg(feature = "actix")]
+      early_flush: false,
+      deferred_blocks: HashMap::new(),
+      render_env: RenderEnv::default(),
+      xss_audit: false,
+      sort_context_keys: false,
+      catch_panics: false,
+      error_reporter: None,
+      circuit_breaker: None,
+      error_template: None,
+      fragment_cache: None,
+      data_loaders: HashMap::new(),
+      cache_dimensions: None,
+      surrogate_keys: false,
+      cdn_purgers: CdnPurgers::default(),
+      nav_menus: HashMap::new(),
+      nav_file: None,
+      search_pages: None,
+      search_index_path: search::DEFAULT_INDEX_PATH.to_string(),
+      locales: None,
+      resolver: None,
+      #[cfg(feature = "sanitize")]
+      sanitize_policies: HashMap::new(),
+      #[cfg(feature = "pdf")]
+      pdf_backend: None,
+      #[cfg(feature = "screenshot")]
+      screenshot_backend: None,
+      #[cfg(feature = "uploads")]
+      upload_rules: HashMap::new(),
+      #[cfg(feature = "content")]
+      content_dir: None,
+      #[cfg(feature = "content")]
+      taxonomies: Vec::new(),
+      #[cfg(feature = "content")]
+      content_preview: cfg!(feature = "devel"),
+      #[cfg(feature = "schedule")]
+      rerender_jobs: Vec::new(),
+      #[cfg(feature = "schedule")]
+      schedule_timezone: chrono_tz::UTC,
       tera_configurator: None,
+      helper_docs: Vec::new(),
+      static_dir: None,
+      icons_dir: None,
+      asset_manifest: None,
+      asset_base_url: None,
+      #[cfg(feature = "actix")]
+      asset_dir: None,
+      url_signer: None,
       static_paths_to_watch: Vec::new(),
       ws_path: "/_snapfire/ws".to_string(),
       auto_inject_script: true,
+      external_script: false,
+      ws_heartbeat_interval: Duration::from_secs(5),
+      ws_client_timeout: Duration::from_secs(10),
+      reload_channel_capacity: 16,
+      reload_quiet_period: Duration::from_millis(50),
+      reload_min_interval: Duration::from_millis(200),
+      #[cfg(feature = "actix")]
+      reload_poke_path: None,
+      #[cfg(feature = "actix")]
+      styleguide_dir: None,
+      #[cfg(feature = "actix")]
+      styleguide_stylesheets: Vec::new(),
+      #[cfg(feature = "devel")]
+      watch_sources: Vec::new(),
+      #[cfg(feature = "notifications")]
+      desktop_notifications: false,
     }
   }
 
@@ -122,37 +1193,941 @@ impl TeraWebBuilder {
   /// * `key` - The name of the variable in the template (e.g., "site_name").
   /// * `value` - Any value that can be serialized (e.g., a string, a number, a struct).
   pub fn add_global<S: Into<String>, T: Serialize>(mut self, key: S, value: T) -> Self {
-    self.globals.insert(&key.into(), &value);
+    self.globals.insert(key.into(), &value);
     self
   }
 
-  /// Provides a closure to run for advanced configuration of the `Tera` instance.
+  /// Adds global variables that only apply to requests for a specific host.
+  ///
+  /// At render time, the request's `Host` header (ignoring case and port) selects
+  /// these overrides, which are merged over the site-wide globals but under the
+  /// handler's own context. This lets multi-domain deployments brand pages
+  /// differently without per-handler logic. Calling this again for the same
+  /// host extends the existing overrides.
+  ///
+  /// # Arguments
+  ///
+  /// * `host` - The host name to match (e.g., "admin.example.com").
+  /// * `globals` - The variables to apply for that host.
+  pub fn add_host_globals<S: Into<String>>(mut self, host: S, globals: Context) -> Self {
+    self
+      .host_globals
+      .entry(normalize_host(&host.into()))
+      .or_default()
+      .extend(globals);
+    self
+  }
+
+  /// Declares the context a template expects.
+  ///
+  /// In debug builds, every render of `tpl` first checks its merged context
+  /// (globals included) against `schema` and fails with
+  /// `SnapFireError::Schema` listing precise problems such as
+  /// `missing field user.email`. Release builds skip the check.
+  ///
+  /// # Arguments
+  ///
+  /// * `tpl` - The template name (e.g., "user.html").
+  /// * `schema` - The expected shape of its context.
+  pub fn context_schema(mut self, tpl: &str, schema: ContextSchema) -> Self {
+    self.schemas.insert(tpl.to_string(), schema);
+    self
+  }
+
+  /// Declares assets to preload whenever `tpl` is rendered.
+  ///
+  /// Responses for `tpl` carry a `Link: <asset>; rel=preload` header for each
+  /// asset, computed before the body is rendered. CDNs and proxies that
+  /// support 103 Early Hints (e.g. Cloudflare, Fastly) turn these into an
+  /// early response, so the browser starts fetching CSS/JS while a slow page
+  /// is still rendering. Templates inherit the assets of the templates they
+  /// extend, so site-wide assets can be declared once on the base template.
+  ///
+  /// # Arguments
+  ///
+  /// * `tpl` - The template name (e.g., "base.html").
+  /// * `assets` - Asset URLs; the preload type is inferred from the extension.
+  pub fn early_hints<I, S>(mut self, tpl: &str, assets: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    self.early_hints.add(tpl, assets);
+    self
+  }
+
+  /// Adds a function that prepares the context of every render.
+  ///
+  /// Hooks run in the order they were added, after the globals, host globals
+  /// and handler context have been merged, and just before the template is
+  /// rendered (and schema-checked). Use them to inject cross-cutting data such
+  /// as navigation menus or notification counts centrally instead of in every
+  /// handler. Values a hook sets override the handler's own.
+  ///
+  /// Note that with hooks registered, each render works on its own copy of
+  /// the globals rather than a pooled one.
+  ///
+  /// # Arguments
+  ///
+  /// * `hook` - A function (or closure) editing the merged context in place.
+  pub fn add_context_hook<F>(mut self, hook: F) -> Self
+  where
+    F: Fn(&mut Context, &RenderInfo) + Send + Sync + 'static,
+  {
+    self.context_hooks.push(hook);
+    self
+  }
+
+  /// Adds a function that transforms every rendered page before it is sent.
+  ///
+  /// Post-processors run in the order they were added, each receiving the
+  /// output of the previous one along with the `RenderInfo` of the render.
+  /// This is the place for cross-cutting rewrites such as minification, SRI
+  /// attributes, tracking snippets or link rewriting. Pages are rendered
+  /// straight into the response buffer when no post-processors are registered.
+  ///
+  /// # Arguments
+  ///
+  /// * `processor` - A function (or closure) editing the rendered HTML in place.
+  pub fn add_post_processor<F>(mut self, processor: F) -> Self
+  where
+    F: Fn(&mut String, &RenderInfo) + Send + Sync + 'static,
+  {
+    self.post_processors.push(processor);
+    self
+  }
+
+  /// Injects a small script into pages using the `timeago` filter that keeps
+  /// their relative times current, in the page's language.
+  ///
+  /// Runs after the other post-processors; see [`crate::core::timeago`].
+  /// Defaults to `false`, leaving the times as they were rendered.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to inject the script.
+  pub fn timeago_script(mut self, enabled: bool) -> Self {
+    self.timeago_script = enabled;
+    self
+  }
+
+  /// Removes the HTML comments from every page, keeping conditional
+  /// comments (`<!--[if IE]>`) and those marked with a `!` (`<!--! MIT -->`).
+  ///
+  /// Runs after the other post-processors. `<!--` inside `script`, `style`,
+  /// `textarea`, and `title` elements is left alone. Defaults to `false`. Meant for production, e.g.
+  /// `.strip_comments(!cfg!(debug_assertions))`, so the notes stay visible
+  /// while developing.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to strip comments.
+  pub fn strip_comments(mut self, enabled: bool) -> Self {
+    self.strip_comments = enabled;
+    self
+  }
+
+  /// Streams pages from the Actix `Template` responder as they render: the
+  /// part up to `</head>` first, so the browser starts on the stylesheets
+  /// while the body renders, then each part ending at a `{{ flush() }}`.
+  ///
+  /// Rendering moves to a blocking thread. Pages are still sent whole when
+  /// post-processors are configured, since those need the complete page.
+  /// The status is sent with the first chunk, so a render error after it
+  /// cuts the page short rather than turning into a `500`. Defaults to
+  /// `false`; see [`crate::core::flush`].
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to flush early.
+  #[cfg(feature = "actix")]
+  pub fn early_flush(mut self, enabled: bool) -> Self {
+    self.early_flush = enabled;
+    self
+  }
+
+  /// Warns, in debug builds, when a context value printed with `| safe`
+  /// looks user-typed (a `<script>` tag, an event handler, a stray `<`...),
+  /// naming the template, line, and variable.
+  ///
+  /// Release builds skip the check. Defaults to `false`; see
+  /// [`crate::core::xss`].
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to audit renders.
+  pub fn xss_audit(mut self, enabled: bool) -> Self {
+    self.xss_audit = enabled;
+    self
+  }
+
+  /// Sorts the keys of every object in the context before each render, so
+  /// `{% for key, value in map %}` loops render the same on every run.
+  ///
+  /// This only matters when `serde_json` keeps maps in insertion order (its
+  /// `preserve_order` feature), and costs a copy of the context per render,
+  /// so it's meant for tests and development. Defaults to `false`; see
+  /// [`crate::core::ordering`].
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to sort the context's keys.
+  pub fn sort_context_keys(mut self, enabled: bool) -> Self {
+    self.sort_context_keys = enabled;
+    self
+  }
+
+  /// Catches panics in filters, functions, hooks, and post-processors
+  /// during a render, turning them into a `SnapFireError::Panic` that
+  /// names the template, instead of letting them take down the worker.
+  ///
+  /// The panic is logged with the request's host and path. The process's
+  /// panic hook still runs first, so the usual panic message is printed
+  /// too. Worth enabling when filters come from plugins or tenants.
+  /// Defaults to `false`.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to catch panics.
+  pub fn catch_panics(mut self, enabled: bool) -> Self {
+    self.catch_panics = enabled;
+    self
+  }
+
+  /// Reports every failed render to `reporter`, e.g. an error tracker, with
+  /// the failing template and line, the whole error, a summary of the
+  /// context with secrets scrubbed, and the request's id.
+  ///
+  /// The reporter runs on the rendering thread after the render, before the
+  /// error is returned. See [`crate::core::report`]; with the `sentry`
+  /// feature, `report::sentry_reporter()` is ready to use.
+  ///
+  /// # Arguments
+  ///
+  /// * `reporter` - Called with each failure.
+  pub fn error_reporter<F>(mut self, reporter: F) -> Self
+  where
+    F: Fn(&RenderFailure) + Send + Sync + 'static,
+  {
+    self.error_reporter = Some(ErrorReporter::new(reporter));
+    self
+  }
+
+  /// Serves a fallback template, without trying the page, for a while
+  /// after a template's renders keep failing.
+  ///
+  /// Each template's error rate is tracked separately. See
+  /// [`crate::core::breaker`] for the thresholds and their defaults.
+  ///
+  /// # Arguments
+  ///
+  /// * `breaker` - The thresholds and the fallback template.
+  pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+    self.circuit_breaker = Some(breaker);
+    self
+  }
+
+  /// Answers failed renders with `tpl` as the `500` page, instead of an
+  /// empty error response.
+  ///
+  /// The page is rendered with the globals, `status` (`500`), and the
+  /// request's `request_id` and `csp_nonce` when it has them. With the
+  /// `devel` feature, `error` holds the failure's message too, so the
+  /// details never reach visitors of a production build. If the error page
+  /// fails as well, that's logged and the empty `500` is sent. Pages
+  /// streamed with `early_flush` have sent their status already, so they're
+  /// still cut short.
+  ///
+  /// # Arguments
+  ///
+  /// * `tpl` - The error page's template, e.g. `errors/500.html`.
+  pub fn error_template(mut self, tpl: &str) -> Self {
+    self.error_template = Some(tpl.to_string());
+    self
+  }
+
+  /// Enables the `cache` function, which serves the output of a partial
+  /// from `cache` until it expires or one of its tags is invalidated with
+  /// `TeraWeb::invalidate_tag`.
+  ///
+  /// See [`crate::core::cache`] for the function's arguments.
+  ///
+  /// # Arguments
+  ///
+  /// * `cache` - The size and default lifetime of the cache.
+  pub fn fragment_cache(mut self, cache: FragmentCache) -> Self {
+    self.fragment_cache = Some(cache);
+    self
+  }
+
+  /// Declares a loader templates pull data from with
+  /// `{{ cached_fetch(loader=..., key=..., ttl=...) }}`, caching its value.
+  ///
+  /// The loader gets the call's other arguments (e.g. `base="EUR"`). Values
+  /// live in the `fragment_cache` (a default one if none is configured), so
+  /// `TeraWeb::invalidate_tag` drops them too. In dev mode, the loader runs
+  /// on every call. See [`crate::core::cache`].
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The name templates call the loader by.
+  /// * `loader` - Loads the value for the call's arguments; an error fails the render.
+  pub fn data_loader<F>(mut self, name: &str, loader: F) -> Self
+  where
+    F: Fn(&HashMap<String, Value>) -> tera::Result<Value> + Send + Sync + 'static,
+  {
+    self.data_loaders.insert(name.to_string(), Arc::new(loader));
+    self
+  }
+
+  /// Keys cached fragments and data by values computed for each render
+  /// (locale, currency, experiment bucket...), so groups of users never see
+  /// each other's entries, and sends `vary` as the pages' `Vary` header so
+  /// HTTP caches split them the same way.
+  ///
+  /// # Arguments
+  ///
+  /// * `vary` - The request headers the values come from, e.g. `Accept-Language`.
+  /// * `dimensions` - The values for the render, from its [`RenderScope`].
+  pub fn cache_key_dimensions<F>(mut self, vary: &[&str], dimensions: F) -> Self
+  where
+    F: Fn(&RenderScope) -> Vec<String> + Send + Sync + 'static,
+  {
+    self.cache_dimensions = Some(CacheDimensions {
+      vary: vary.join(", "),
+      values: Box::new(dimensions),
+    });
+    self
+  }
+
+  /// Sends pages with the tags of the `cache` and `cached_fetch` calls they
+  /// made as a space-separated `Surrogate-Key` header, so edge caches such
+  /// as Fastly and Varnish can purge them by the same tags the app
+  /// invalidates. Tags holding whitespace are left out.
+  ///
+  /// The header is only known once the page has rendered, so pages sent
+  /// with `early_flush` go without it. Defaults to `false`.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to send the header.
+  pub fn surrogate_keys(mut self, enabled: bool) -> Self {
+    self.surrogate_keys = enabled;
+    self
+  }
+
+  /// Adds a CDN to purge when `TeraWeb::reload_templates` or
+  /// `TeraWeb::reload_content` change the pages (everything), and when
+  /// `TeraWeb::invalidate_tag` drops a tag (the pages tagged with it).
+  ///
+  /// See [`crate::core::purge`] for the built-in CDNs.
+  ///
+  /// # Arguments
+  ///
+  /// * `purger` - The CDN, e.g. `Fastly::new(service_id, token)`.
+  pub fn add_cdn_purger<P: CdnPurger + 'static>(mut self, purger: P) -> Self {
+    self.cdn_purgers.push(purger);
+    self
+  }
+
+  /// Sets the site's default render environment: the locale, timezone,
+  /// currency, theme, and tenant pages are rendered for, exposed to every
+  /// template as `env`.
+  ///
+  /// A `RenderEnv` middleware stores in the request extensions overrides
+  /// these per request, field by field. Without a default locale, the
+  /// `locales` default is used. See [`crate::core::env`].
+  ///
+  /// # Arguments
+  ///
+  /// * `env` - The defaults.
+  pub fn render_env(mut self, env: RenderEnv) -> Self {
+    self.render_env = env;
+    self
+  }
+
+  /// Declares a block pages place with `{{ await_block(name=...) }}`,
+  /// rendered from `template` with the context `loader` returns.
+  ///
+  /// The loader gets the call's other arguments (e.g. `user_id=user.id`).
+  /// With `early_flush`, it runs on a shared pool of loader threads while the
+  /// rest of the page renders, and the block is streamed after the page and
+  /// swapped into place; otherwise the block renders inline. An error it
+  /// returns fails the render. See [`crate::core::deferred`].
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The name pages await the block by.
+  /// * `template` - The template rendering the block.
+  /// * `loader` - Builds the block's context from the call's arguments.
+  pub fn deferred_block<F>(mut self, name: &str, template: &str, loader: F) -> Self
+  where
+    F: Fn(&HashMap<String, Value>) -> Result<Context> + Send + Sync + 'static,
+  {
+    self.deferred_blocks.insert(
+      name.to_string(),
+      DeferredBlock {
+        template: template.to_string(),
+        loader: Arc::new(loader),
+      },
+    );
+    self
+  }
+
+  /// Renders the TeX between `$$` and `$` delimiters in every page to HTML
+  /// with KaTeX, so pages need KaTeX's stylesheet but not its script.
+  ///
+  /// Runs as a post-processor, in registration order with the others; see
+  /// [`crate::core::math`] for which text is searched. Requires the `math`
+  /// feature.
+  ///
+  /// # Arguments
+  ///
+  /// * `options` - Whether `$` delimits inline math, and the TeX macros.
+  #[cfg(feature = "math")]
+  pub fn render_math(mut self, options: MathOptions) -> Self {
+    let renderer = MathRenderer::new(options);
+    self.post_processors.push(move |html, _| renderer.render(html));
+    // KaTeX sizes its output with `style` attributes.
+    self.csp_sources.add("style-src-attr", "'unsafe-inline'");
+    self
+  }
+
+  /// Declares a navigation menu for the `nav` template function.
+  ///
+  /// Templates render it with `nav(name="main")`, which returns the items
+  /// with `current` and `active` flags computed from the request path.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The menu name (e.g., "main").
+  /// * `items` - The top-level entries, which may have children.
+  pub fn nav<S: Into<String>>(mut self, name: S, items: Vec<NavItem>) -> Self {
+    self.nav_menus.insert(name.into(), items);
+    self
+  }
+
+  /// Loads navigation menus from a YAML file mapping menu names to items.
+  ///
+  /// Menus from the file take precedence over those declared with `nav`. In
+  /// dev mode the file is watched, and editing it updates the menus and
+  /// reloads the page.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The path to the YAML file (e.g., "nav.yaml").
+  pub fn nav_from_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+    self.nav_file = Some(path.into());
+    self
+  }
+
+  /// Builds a client-side search index from the given pages.
+  ///
+  /// Each page is rendered through the normal render path and reduced to its
+  /// title and visible text. `configure_routes` serves the result as
+  /// lunr-compatible JSON, and templates link to it with `search_index_url()`.
+  /// The index is built on first request; call `TeraWeb::refresh_search_index`
+  /// to rebuild it when the underlying data changes.
+  ///
+  /// # Arguments
+  ///
+  /// * `pages` - The pages to index, each with its URL, template and context.
+  pub fn search_index(mut self, pages: Vec<SearchPage>) -> Self {
+    self.search_pages.get_or_insert_with(Vec::new).extend(pages);
+    self
+  }
+
+  /// Sets the URL the search index is served at.
+  ///
+  /// Defaults to `/_snapfire/search-index.json`.
+  pub fn search_index_path(mut self, path: &str) -> Self {
+    self.search_index_path = path.to_string();
+    self
+  }
+
+  /// Serves the site in several locales, with locale-prefixed URLs.
+  ///
+  /// Registers the `localized_url(path, locale)` and `alternate_links()`
+  /// template functions. Pair it with the `actix::i18n::LocalePrefix`
+  /// middleware, which routes `/fr/about` to the `/about` handler and exposes
+  /// the request's locale to templates as `locale`.
+  ///
+  /// # Arguments
+  ///
+  /// * `locales` - The default and supported locales.
+  pub fn locales(mut self, locales: Locales) -> Self {
+    self.locales = Some(locales);
+    self
+  }
+
+  /// Believes the `Forwarded` and `X-Forwarded-*` headers of requests from
+  /// these reverse proxies, for the scheme, host, and client address.
+  ///
+  /// Behind a proxy terminating TLS, this makes the `canonical_url` template
+  /// value, the request's per-host globals, the `SecurityHeaders` live-reload
+  /// source, and `SpamGuard`'s rate limit see the client's view of the
+  /// request. The headers of any other peer are ignored, since clients can
+  /// send them too. See [`crate::core::proxy`].
+  ///
+  /// # Arguments
+  ///
+  /// * `proxies` - Addresses or CIDR ranges, e.g. `["127.0.0.1", "10.0.0.0/8"]`.
+  ///   An entry that is neither fails `build()`.
+  #[cfg(feature = "actix")]
+  pub fn trusted_proxies<I, S>(mut self, proxies: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.trusted_proxies.extend(proxies.into_iter().map(Into::into));
+    self
+  }
+
+  /// Adds third-party origins for the `resource_hints()` template function
+  /// to `preconnect` to, e.g. a font or analytics host.
+  ///
+  /// `resource_hints()` emits a `<link rel="preconnect" crossorigin>` and a
+  /// `<link rel="dns-prefetch">` for each, plus the asset CDN's origin (see
+  /// `asset_base_url`). Call it early in `<head>`.
+  ///
+  /// # Arguments
+  ///
+  /// * `origins` - `http(s)` URLs, e.g. `["https://fonts.gstatic.com"]`; only
+  ///   their origins are used. Anything else fails `build()`.
+  pub fn preconnect_origins<I, S>(mut self, origins: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.preconnect_origins.extend(origins.into_iter().map(Into::into));
+    self
+  }
+
+  /// Sets the rules for resolving template names that aren't exact, so e.g.
+  /// `render("blog/post")` can find `blog/post.html` or `blog/post/index.html`.
+  ///
+  /// Resolution applies wherever a template is named: renders, schemas, and
+  /// early hints are all looked up by the resolved name.
+  ///
+  /// # Arguments
+  ///
+  /// * `resolver` - The extensions, index files, and case rules to apply.
+  pub fn template_resolver(mut self, resolver: TemplateResolver) -> Self {
+    self.resolver = Some(resolver);
+    self
+  }
+
+  /// Registers a named allowlist for the `sanitize_html` and `markdown` filters.
+  ///
+  /// Templates pick a policy per field with `policy="name"`, so e.g. user
+  /// comments can allow far less markup than staff-authored articles.
+  /// Registering `"default"` replaces the policy used when no name is given,
+  /// which is otherwise `SanitizePolicy::permissive()`. Requires the
+  /// `sanitize` feature.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The name templates refer to the policy by (e.g., "comments").
+  /// * `policy` - The allowed tags, attributes, and URL schemes.
+  #[cfg(feature = "sanitize")]
+  pub fn sanitize_policy(mut self, name: &str, policy: SanitizePolicy) -> Self {
+    self.sanitize_policies.insert(name.to_string(), policy);
+    self
+  }
+
+  /// Sets the converter `TeraWeb::render_pdf` uses, such as `WeasyPrint` or
+  /// a custom `PdfBackend`. Defaults to headless `Chromium`. Requires the
+  /// `pdf` feature.
+  ///
+  /// # Arguments
+  ///
+  /// * `backend` - The HTML to PDF converter.
+  #[cfg(feature = "pdf")]
+  pub fn pdf_backend<B: PdfBackend + 'static>(mut self, backend: B) -> Self {
+    self.pdf_backend = Some(Arc::new(backend));
+    self
+  }
+
+  /// Sets the capturer `TeraWeb::render_screenshot` uses, such as
+  /// `screenshot::Chromium` with another browser binary, or a custom
+  /// `ScreenshotBackend`. Defaults to headless `Chromium`. Requires the
+  /// `screenshot` feature.
+  ///
+  /// # Arguments
+  ///
+  /// * `backend` - The HTML to PNG capturer.
+  #[cfg(feature = "screenshot")]
+  pub fn screenshot_backend<B: ScreenshotBackend + 'static>(mut self, backend: B) -> Self {
+    self.screenshot_backend = Some(Arc::new(backend));
+    self
+  }
+
+  /// Declares a file upload field for the `upload_field(name="...")`
+  /// template function and `snapfire::actix::uploads::read_uploads`, which
+  /// render and enforce the same limits. Requires the `uploads` feature.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The form field name (e.g., "avatar").
+  /// * `rule` - The field's size limit, accepted types, and whether it takes
+  ///   several files or is required.
+  #[cfg(feature = "uploads")]
+  pub fn upload_field(mut self, name: &str, rule: UploadRule) -> Self {
+    self.upload_rules.insert(name.to_string(), rule);
+    self
+  }
+
+  /// Loads the Markdown files under `path` into collections for the
+  /// `content(name="...")` template function, one per subdirectory.
+  ///
+  /// Each entry has its front matter values, a `slug`, and its body rendered
+  /// to HTML as `content`, sanitized with the `"default"` policy; see
+  /// [`crate::core::content`]. In dev mode the directory is watched, and
+  /// editing a file updates the collections and reloads the page. Requires
+  /// the `content` feature.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The content directory (e.g., "content").
+  #[cfg(feature = "content")]
+  pub fn content_dir<P: Into<PathBuf>>(mut self, path: P) -> Self {
+    self.content_dir = Some(path.into());
+    self
+  }
+
+  /// Declares a taxonomy over the content collections, grouping their
+  /// entries by the front matter key `name` (a string or list of strings).
+  ///
+  /// Templates list the terms with `taxonomy(name="tags")`, each with its
+  /// `name`, `slug`, `count`, and `pages`, and get one with
+  /// `taxonomy(name="tags", term=slug)`. Requires the `content` feature and a
+  /// `content_dir`.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The front matter key to group by (e.g., "tags").
+  #[cfg(feature = "content")]
+  pub fn taxonomy(mut self, name: &str) -> Self {
+    self.taxonomies.push(name.to_string());
+    self
+  }
+
+  /// Shows content drafts (`draft: true`) and entries whose `publish_at` is
+  /// still to come, e.g. for an editors' preview deployment.
+  ///
+  /// Defaults to `true` in dev builds (with the `devel` feature) and `false`
+  /// otherwise. Requires the `content` feature.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to show unpublished content.
+  #[cfg(feature = "content")]
+  pub fn content_preview(mut self, enabled: bool) -> Self {
+    self.content_preview = enabled;
+    self
+  }
+
+  /// Re-renders `tpl` on a cron schedule, for serving with `TeraWeb::render_cached`.
+  ///
+  /// The page is rendered on a background thread at startup and then at every
+  /// time matched by `cron_expr`, with a fresh context from `context_provider`
+  /// each time (e.g. rebuild the homepage hourly with the latest data). A
+  /// failed render logs an error and keeps serving the previous page.
+  /// Scheduled pages are rendered without a request, so host globals don't
+  /// apply. Requires the `schedule` feature.
+  ///
+  /// # Arguments
+  ///
+  /// * `tpl` - The template name (e.g., "home.html").
+  /// * `cron_expr` - A cron expression with a seconds field, e.g. `0 0 * * * *` (hourly).
+  /// * `context_provider` - Builds the context for each render.
+  #[cfg(feature = "schedule")]
+  pub fn schedule_rerender<F>(mut self, tpl: &str, cron_expr: &str, context_provider: F) -> Self
+  where
+    F: Fn() -> Context + Send + Sync + 'static,
+  {
+    self
+      .rerender_jobs
+      .push((tpl.to_string(), cron_expr.to_string(), Arc::new(context_provider)));
+    self
+  }
+
+  /// Sets the timezone scheduled re-render expressions are evaluated in.
+  ///
+  /// Defaults to UTC. Requires the `schedule` feature.
+  #[cfg(feature = "schedule")]
+  pub fn schedule_timezone(mut self, timezone: chrono_tz::Tz) -> Self {
+    self.schedule_timezone = timezone;
+    self
+  }
+
+  /// Provides a closure to run for advanced configuration of the `Tera` instance.
+  ///
+  /// This is the escape hatch for power users to register custom functions,
+  /// filters, testers, or modify Tera settings before the app is finalized.
+  pub fn configure_tera<F>(mut self, configurator: F) -> Self
+  where
+    F: FnOnce(&mut Tera) + 'static,
+  {
+    self.tera_configurator = Some(Box::new(configurator));
+    self
+  }
+
+  /// Describes a filter, function, or tester registered through
+  /// `configure_tera`, so `TeraWeb::editor_metadata` (and the dev
+  /// `/_snapfire/lsp.json` endpoint) offer it to editor tooling. Tera can't
+  /// list them otherwise. It's listed as whichever kinds of helper `name` is
+  /// registered as.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The name the helper is registered under.
+  /// * `signature` - Its call as shown in completions, e.g. `money(currency="EUR")`.
+  /// * `description` - A one-line summary.
+  pub fn describe_helper(mut self, name: &str, signature: &str, description: &str) -> Self {
+    self.helper_docs.retain(|doc| doc.name != name);
+    self.helper_docs.push(HelperDoc {
+      name: name.to_string(),
+      signature: signature.to_string(),
+      description: description.to_string(),
+    });
+    self
+  }
+
+  /// Sets the path for the devel WebSocket endpoint.
+  ///
+  /// Defaults to `/_snapfire/ws`.
+  pub fn ws_path(mut self, path: &str) -> Self {
+    self.ws_path = path.to_string();
+    self
+  }
+
+  /// Enables or disables the automatic injection of the
+  /// live-reload JavaScript.
+  ///
+  /// Defaults to `true`. Set this to `false` if you want to manually
+  /// include the script in your base template.
+  pub fn auto_inject_script(mut self, enabled: bool) -> Self {
+    self.auto_inject_script = enabled;
+    self
+  }
+
+  /// Injects the live-reload script as `<script src>` instead of inline.
+  ///
+  /// Defaults to `false`. The script is served from `/_snapfire/client.js`
+  /// with a versioned URL and immutable caching, so it isn't re-sent with
+  /// every page. The route is available either way, so pages served by
+  /// another dev server (e.g. Vite) can include it manually; it connects
+  /// back to the server that served it.
+  pub fn external_script(mut self, enabled: bool) -> Self {
+    self.external_script = enabled;
+    self
+  }
+
+  /// Sets how often the live-reload WebSocket pings each browser.
+  ///
+  /// Defaults to 5 seconds. Flaky connections (e.g. over a VPN) that drop
+  /// idle sockets may need shorter intervals.
+  pub fn ws_heartbeat_interval(mut self, interval: Duration) -> Self {
+    self.ws_heartbeat_interval = interval;
+    self
+  }
+
+  /// Sets how long a browser may go without answering pings before its
+  /// live-reload WebSocket is closed.
+  ///
+  /// Defaults to 10 seconds. Keep it longer than the heartbeat interval; high
+  /// latency connections may need more.
+  pub fn ws_client_timeout(mut self, timeout: Duration) -> Self {
+    self.ws_client_timeout = timeout;
+    self
+  }
+
+  /// Sets how many reload messages are buffered for a browser that is behind.
+  ///
+  /// Defaults to 16. A browser that falls further behind (e.g. during a
+  /// `git checkout` touching hundreds of templates) gets a single full reload
+  /// instead of the messages it missed. Values below 1 are treated as 1.
+  pub fn reload_channel_capacity(mut self, capacity: usize) -> Self {
+    self.reload_channel_capacity = capacity.max(1);
+    self
+  }
+
+  /// Sets how long file changes must stop before they are applied.
+  ///
+  /// Defaults to 50 milliseconds. Changes arriving within this period of
+  /// each other, such as a `git checkout` touching hundreds of templates, are
+  /// coalesced into one template reload and one browser reload at the end.
+  pub fn reload_quiet_period(mut self, period: Duration) -> Self {
+    self.reload_quiet_period = period;
+    self
+  }
+
+  /// Sets the minimum time between two reloads, capping the reload rate while
+  /// files keep changing.
+  ///
+  /// Defaults to 200 milliseconds.
+  pub fn reload_min_interval(mut self, interval: Duration) -> Self {
+    self.reload_min_interval = interval;
+    self
+  }
+
+  /// Raises a desktop notification when a dev reload fails (a template that
+  /// doesn't parse, a data file that doesn't load), as well as logging it,
+  /// so the error is seen with the terminal hidden. Notifications go through
+  /// D-Bus on Linux, Notification Center on macOS, and toasts on Windows.
+  ///
+  /// Defaults to `false`.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to notify.
+  #[cfg(feature = "notifications")]
+  pub fn desktop_notifications(mut self, enabled: bool) -> Self {
+    self.desktop_notifications = enabled;
+    self
+  }
+
+  /// Adds an endpoint that triggers a reload when it receives a `POST`, so
+  /// CI or tooling (e.g. a remote asset pipeline) can reload connected
+  /// browsers.
+  ///
+  /// Disabled by default. The `kind` query parameter selects what changed:
+  /// `templates` (re-parse them, then reload), `css` (reload stylesheets
+  /// only), or, by default, `page` (reload). The route is added by
+  /// `configure_routes` in dev mode only.
+  #[cfg(feature = "actix")]
+  pub fn reload_poke_path(mut self, path: &str) -> Self {
+    self.reload_poke_path = Some(path.to_string());
+    self
+  }
+
+  /// Serves a gallery of the component templates in `dir` at
+  /// `/_snapfire/styleguide`, each rendered with its fixtures
+  /// (`button.html.fixtures/*.json`) or the examples in the `.json` file
+  /// next to it (e.g. `button.json`); see `snapfire::core::styleguide`. Editing a component or a fixture reloads
+  /// the gallery. The route is added by `configure_routes` in dev mode only.
+  ///
+  /// # Arguments
+  ///
+  /// * `dir` - The component directory, relative to the template root (e.g., "components").
+  /// * `stylesheets` - Stylesheet URLs the gallery links, so components look as on the site.
+  #[cfg(feature = "actix")]
+  pub fn styleguide<I, S>(mut self, dir: &str, stylesheets: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    self.styleguide_dir = Some(dir.to_string());
+    self.styleguide_stylesheets = stylesheets.into_iter().map(|url| url.as_ref().to_string()).collect();
+    self
+  }
+
+  /// Adds a source of reload triggers, alongside the file watcher.
+  ///
+  /// Use `PollingSource` where file system events don't arrive (some network
+  /// and container mounts), or implement `WatchSource` to reload from a
+  /// message queue or other tooling. Can be called multiple times.
+  #[cfg(feature = "devel")]
+  pub fn watch_source<W: WatchSource>(mut self, source: W) -> Self {
+    self.watch_sources.push(Box::new(source));
+    self
+  }
+
+  /// Sets the directory of the site's static files, which template helpers
+  /// read from.
+  ///
+  /// Registers the `svg(path=...)` template function, which inlines a
+  /// sanitized SVG file from the directory; see [`crate::core::svg`]. In dev
+  /// mode the directory is watched, as with `watch_static`.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The static directory (e.g., "static").
+  pub fn static_dir<P: Into<PathBuf>>(mut self, path: P) -> Self {
+    self.static_dir = Some(path.into());
+    self
+  }
+
+  /// Compiles the `*.svg` files of a directory into a sprite, served by
+  /// `configure_routes` at a fingerprinted URL.
+  ///
+  /// Registers the `icon(name=...)` template function, which references an
+  /// icon of the sprite by file name; see [`crate::core::icons`]. An icon
+  /// that can't be read or parsed fails `build()`. In dev mode the directory
+  /// is watched, and changes recompile the sprite and reload the page.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The icon directory (e.g., "static/icons").
+  pub fn icons_dir<P: Into<PathBuf>>(mut self, path: P) -> Self {
+    self.icons_dir = Some(path.into());
+    self
+  }
+
+  /// Reads fingerprinted asset URLs from a build tool's JSON manifest.
+  ///
+  /// Registers the `asset(path=...)` and `script_module_nomodule(entry=...)`
+  /// template functions; see [`crate::core::assets`] for the manifest
+  /// formats. A missing or invalid manifest fails `build()`. In dev mode the
+  /// manifest is watched, and re-read when the build rewrites it.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The manifest file (e.g., "static/manifest.json").
+  /// * `url_prefix` - Where the manifest's relative files are served (e.g., "/static/").
+  pub fn asset_manifest<P: Into<PathBuf>>(mut self, path: P, url_prefix: &str) -> Self {
+    self.asset_manifest = Some((path.into(), url_prefix.to_string()));
+    self
+  }
+
+  /// Serves the site's assets from a CDN: the URLs `asset(path=...)` and
+  /// `script_module_nomodule` produce for site paths (`/static/app.css`)
+  /// move to this base URL, while absolute URLs are left alone.
+  ///
+  /// Registers `asset` even without a manifest. The origin is also added to
+  /// `resource_hints()` and to the sources `SecurityHeaders` allows. Dev
+  /// builds (with the `devel` feature) ignore it and keep serving assets
+  /// locally. An invalid URL fails `build()`.
+  ///
+  /// # Arguments
   ///
-  /// This is the escape hatch for power users to register custom functions,
-  /// filters, testers, or modify Tera settings before the app is finalized.
-  pub fn configure_tera<F>(mut self, configurator: F) -> Self
-  where
-    F: FnOnce(&mut Tera) + 'static,
-  {
-    self.tera_configurator = Some(Box::new(configurator));
+  /// * `url` - The CDN's `http(s)` URL, optionally with a path (e.g., "https://cdn.example.com/v2").
+  pub fn asset_base_url(mut self, url: &str) -> Self {
+    self.asset_base_url = Some(url.to_string());
     self
   }
 
-  /// Sets the path for the devel WebSocket endpoint.
+  /// Serves the directory the build writes the `asset_manifest` files to,
+  /// from the Actix `configure_routes`, under the manifest's URL prefix.
   ///
-  /// Defaults to `/_snapfire/ws`.
-  pub fn ws_path(mut self, path: &str) -> Self {
-    self.ws_path = path.to_string();
+  /// Files listed in the manifest are fingerprinted, so they're cached with
+  /// `Cache-Control: public, max-age=31536000, immutable`; others (e.g.
+  /// images copied as-is) are revalidated by ETag. Precompressed `.br` and
+  /// `.gz` siblings are served to clients that accept them, and `Range`
+  /// requests get partial content. Requires `asset_manifest` with a site path
+  /// prefix such as `/static/`, or `build()` fails.
+  ///
+  /// # Arguments
+  ///
+  /// * `dir` - The build's output directory (e.g., "static").
+  #[cfg(feature = "actix")]
+  pub fn serve_assets<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+    self.asset_dir = Some(dir.into());
     self
   }
 
-  /// Enables or disables the automatic injection of the
-  /// live-reload JavaScript.
+  /// Signs links to protected files with a secret key, registering the
+  /// `signed_asset(path=..., ttl=...)` template function.
   ///
-  /// Defaults to `true`. Set this to `false` if you want to manually
-  /// include the script in your base template.
-  pub fn auto_inject_script(mut self, enabled: bool) -> Self {
-    self.auto_inject_script = enabled;
+  /// Wrap the routes serving the files in the `SignedUrls` Actix middleware
+  /// with the same signer; see [`crate::core::signed`].
+  ///
+  /// # Arguments
+  ///
+  /// * `signer` - The signer holding the key.
+  pub fn url_signer(mut self, signer: UrlSigner) -> Self {
+    self.url_signer = Some(signer);
     self
   }
 
@@ -168,37 +2143,392 @@ impl TeraWebBuilder {
   ///
   /// This method will initialize the Tera engine and, if the `devel` feature
   /// is enabled, spawn the file watcher.
-  pub fn build(self) -> Result<TeraWeb> {
+  pub fn build(mut self) -> Result<TeraWeb> {
+    if self.timeago_script {
+      self.post_processors.push(|html, info| timeago::inject_script(html, info.nonce));
+    }
+    if self.strip_comments {
+      self.post_processors.push(|html, _| comments::strip_comments(html));
+    }
+
+    #[cfg(feature = "actix")]
+    let trusted_proxies = TrustedProxies::parse(&self.trusted_proxies).map_err(|message| SnapFireError::Config {
+      path: "trusted_proxies".into(),
+      message,
+    })?;
+
+    #[cfg(feature = "schedule")]
+    let rerender_jobs = self
+      .rerender_jobs
+      .iter()
+      .map(|(tpl, expression, provider)| RerenderJob::new(tpl, expression, Arc::clone(provider)))
+      .collect::<Result<Vec<_>>>()?;
+
     // 1. Create the initial Tera instance.
-    let mut tera = Tera::new(&self.templates_glob)?;
+    let mut tera = match &self.templates {
+      TemplateSource::Glob(glob) => TemplateSet::from_glob(glob)?,
+      TemplateSource::Bundle(bundle) => bundle.to_tera()?,
+    };
+    tera.register_tester("consented", consent::consented_tester);
+    if let Some(breaker) = &self.circuit_breaker
+      && tera.get_template(breaker.fallback()).is_err()
+    {
+      return Err(SnapFireError::Config {
+        path: "circuit_breaker".into(),
+        message: format!("the fallback template `{}` is not loaded", breaker.fallback()),
+      });
+    }
+    if let Some(tpl) = &self.error_template
+      && tera.get_template(tpl).is_err()
+    {
+      return Err(SnapFireError::Config {
+        path: "error_template".into(),
+        message: format!("the error template `{tpl}` is not loaded"),
+      });
+    }
+
+    #[cfg_attr(not(feature = "devel"), allow(unused_variables))]
+    let navigation = if self.nav_menus.is_empty() && self.nav_file.is_none() {
+      None
+    } else {
+      let navigation = Arc::new(Navigation::new(self.nav_menus, self.nav_file)?);
+      tera.register_function("nav", NavFunction(Arc::clone(&navigation)));
+      Some(navigation)
+    };
+
+    let search = self.search_pages.map(|pages| {
+      tera.register_function("search_index_url", SearchIndexUrl(self.search_index_path.clone()));
+      Arc::new(SearchIndex {
+        #[cfg(feature = "actix")]
+        path: self.search_index_path,
+        pages,
+        cached: RwLock::new(None),
+      })
+    });
+
+    let default_locale = self.locales.as_ref().map_or("en", Locales::default_locale);
+    if let Some(locales) = &self.locales
+      && self.render_env.locale.is_none()
+    {
+      self.render_env.locale = Some(locales.default_locale().to_string());
+    }
+    if !self.globals.contains_key("env") {
+      self.globals.insert("env", &self.render_env);
+    }
+    let globals = Arc::new(GlobalContext::new(self.globals, self.host_globals));
+    let cache_dimensions = self.cache_dimensions.map(Arc::new);
+    let mut fragment_cache = self.fragment_cache.map(|cache| {
+      let cache = Arc::new(cache);
+      tera.register_function(
+        "cache",
+        CacheFunction {
+          cache: Arc::clone(&cache),
+          globals: Arc::clone(&globals),
+          dimensions: cache_dimensions.clone(),
+        },
+      );
+      cache
+    });
+    if !self.data_loaders.is_empty() {
+      let cache = fragment_cache.get_or_insert_with(|| Arc::new(FragmentCache::new()));
+      tera.register_function(
+        "cached_fetch",
+        CachedFetchFunction {
+          cache: Arc::clone(cache),
+          loaders: Arc::new(self.data_loaders),
+          dimensions: cache_dimensions.clone(),
+        },
+      );
+    }
+    tera.register_filter(
+      "plural",
+      Plural {
+        default_locale: default_locale.to_string(),
+      },
+    );
+    tera.register_function("dir", Dir(default_locale.to_string()));
+    tera.register_function("text_align_start", TextAlignStart(default_locale.to_string()));
+    tera.register_filter("logical_css", LogicalCss);
+    tera.register_function("sparkline", SparklineFunction);
+    tera.register_function("chart", ChartFunction);
+    tera.register_function("avatar", AvatarFunction);
+    tera.register_filter("timeago", TimeagoFilter);
+    tera.register_function("honeypot_field", HoneypotField);
+    tera.register_function("flush", FlushFunction);
+    let deferred_blocks = !self.deferred_blocks.is_empty();
+    if deferred_blocks {
+      tera.register_function("await_block", AwaitBlockFunction(Arc::new(self.deferred_blocks)));
+    }
+    if let Some(url) = &self.asset_base_url
+      && origin_of(url).is_none()
+    {
+      return Err(SnapFireError::Config {
+        path: "asset_base_url".into(),
+        message: format!("`{url}` is not an http(s) URL"),
+      });
+    }
+    let assets = if self.asset_manifest.is_some() || self.asset_base_url.is_some() {
+      // Dev builds serve the files being rebuilt, not the deployed ones.
+      let asset_base_url = self.asset_base_url.filter(|_| !cfg!(feature = "devel"));
+      let (file, prefix) = self.asset_manifest.unzip();
+      let assets = Arc::new(Assets::new(file, prefix.as_deref().unwrap_or("/"), asset_base_url.as_deref())?);
+      tera.register_function("asset", AssetFunction(Arc::clone(&assets)));
+      if assets.file().is_some() {
+        tera.register_function("script_module_nomodule", ModuleScriptsFunction(Arc::clone(&assets)));
+      }
+      if let Some(origin) = assets.origin() {
+        for directive in ["script-src", "style-src", "img-src", "font-src"] {
+          self.csp_sources.add(directive, origin.clone());
+        }
+      }
+      Some(assets)
+    } else {
+      None
+    };
+    #[cfg(feature = "actix")]
+    let asset_files = match (self.asset_dir, &assets) {
+      (Some(dir), Some(assets)) if assets.file().is_some() => {
+        let files = AssetFiles::new(dir, Arc::clone(assets)).map_err(|message| SnapFireError::Config {
+          path: "serve_assets".into(),
+          message,
+        })?;
+        Some(Arc::new(files))
+      }
+      (Some(_), _) => {
+        return Err(SnapFireError::Config {
+          path: "serve_assets".into(),
+          message: "serving assets requires an `asset_manifest`".to_string(),
+        });
+      }
+      (None, _) => None,
+    };
+    if let Some(signer) = self.url_signer {
+      tera.register_function("signed_asset", SignedAssetFunction(signer));
+    }
+    let mut preconnect_origins = Vec::new();
+    for url in &self.preconnect_origins {
+      let origin = origin_of(url).ok_or_else(|| SnapFireError::Config {
+        path: "preconnect_origins".into(),
+        message: format!("`{url}` is not an http(s) URL"),
+      })?;
+      preconnect_origins.push(origin);
+    }
+    preconnect_origins.extend(assets.as_ref().and_then(|assets| assets.origin()));
+    let mut seen = HashSet::new();
+    preconnect_origins.retain(|origin| seen.insert(origin.clone()));
+    tera.register_function("resource_hints", ResourceHints(preconnect_origins));
+    let icons = match self.icons_dir {
+      Some(dir) => {
+        let icons = Arc::new(IconSprite::new(dir)?);
+        tera.register_function("icon", IconFunction(Arc::clone(&icons)));
+        Some(icons)
+      }
+      None => None,
+    };
+    if let Some(dir) = &self.static_dir {
+      tera.register_function("svg", SvgFunction(Arc::new(SvgFiles::new(dir.clone()))));
+      let dir = dir.display().to_string();
+      if !self.static_paths_to_watch.contains(&dir) {
+        self.static_paths_to_watch.push(dir);
+      }
+    }
+
+    let locales = self.locales.map(Arc::new);
+    if let Some(locales) = &locales {
+      tera.register_function("localized_url", LocalizedUrl(Arc::clone(locales)));
+      tera.register_function("alternate_links", AlternateLinks(Arc::clone(locales)));
+    }
+
+    #[cfg(feature = "sanitize")]
+    let policies = {
+      let mut policies = self.sanitize_policies;
+      policies.entry(sanitize::DEFAULT_POLICY.to_string()).or_default();
+      let policies = Arc::new(policies);
+      tera.register_filter("sanitize_html", SanitizeHtml(Arc::clone(&policies)));
+      tera.register_filter("markdown", MarkdownFilter(Arc::clone(&policies)));
+      policies
+    };
+    #[cfg(all(feature = "sanitize", not(feature = "content")))]
+    let _ = policies;
+
+    #[cfg(feature = "typography")]
+    {
+      tera.register_filter("smartypants", SmartypantsFilter);
+      tera.register_filter("emoji", EmojiFilter);
+    }
+
+    #[cfg(feature = "barcode")]
+    {
+      tera.register_function("qr_code", QrCodeFunction);
+      tera.register_function("barcode", BarcodeFunction);
+    }
+
+    #[cfg(feature = "uploads")]
+    let upload_rules = {
+      let upload_rules = Arc::new(self.upload_rules);
+      tera.register_function("upload_field", UploadFieldFunction(Arc::clone(&upload_rules)));
+      upload_rules
+    };
+
+    #[cfg(feature = "content")]
+    let content = match self.content_dir {
+      Some(dir) => {
+        let policy = policies[sanitize::DEFAULT_POLICY].clone();
+        let content = Arc::new(Content::new(dir, policy, self.taxonomies, self.content_preview)?);
+        tera.register_function("content", ContentFunction(Arc::clone(&content)));
+        tera.register_function("taxonomy", TaxonomyFunction(Arc::clone(&content)));
+        Some(content)
+      }
+      None => None,
+    };
 
     // 2. Run the power-user configuration closure if it exists.
     if let Some(configurator) = self.tera_configurator {
       configurator(&mut tera);
     }
 
+    // Data files whose changes reload them, then the page.
+    #[cfg(feature = "devel")]
+    let mut watched_files: Vec<_> = navigation
+      .iter()
+      .filter_map(|navigation| {
+        let navigation = Arc::clone(navigation);
+        Some(crate::core::reload::WatchedFile {
+          path: navigation.file()?.to_path_buf(),
+          on_change: Box::new(move || navigation.reload()),
+        })
+      })
+      .collect();
+    #[cfg(feature = "devel")]
+    if let Some(assets) = &assets {
+      let assets = Arc::clone(assets);
+      if let Some(path) = assets.file().map(std::path::Path::to_path_buf) {
+        watched_files.push(crate::core::reload::WatchedFile {
+          path,
+          on_change: Box::new(move || assets.reload()),
+        });
+      }
+    }
+    #[cfg(feature = "devel")]
+    if let Some(icons) = &icons {
+      let icons = Arc::clone(icons);
+      watched_files.push(crate::core::reload::WatchedFile {
+        path: icons.dir().to_path_buf(),
+        on_change: Box::new(move || icons.reload()),
+      });
+    }
+    #[cfg(all(feature = "devel", feature = "content"))]
+    if let Some(content) = &content {
+      let content = Arc::clone(content);
+      watched_files.push(crate::core::reload::WatchedFile {
+        path: content.dir().to_path_buf(),
+        on_change: Box::new(move || content.reload()),
+      });
+    }
+
     // 3. Wrap the Tera instance for thread-safe sharing.
-    let tera = Arc::new(RwLock::new(tera));
+    let tera: SharedTera = Arc::new(RwLock::new(Arc::new(tera)));
 
     // 4. Construct the final TeraWeb state.
-    Ok(TeraWeb {
+    #[cfg_attr(not(feature = "schedule"), allow(unused_mut))]
+    let mut app = TeraWeb {
       // Conditionally start the reloader if the `devel` feature is enabled.
       #[cfg(feature = "devel")]
       reloader: {
         let reloader = DevReloader::start(
           Arc::clone(&tera),
-          &self.templates_glob,
+          match &self.templates {
+            TemplateSource::Glob(glob) => Some(glob.as_str()),
+            TemplateSource::Bundle(_) => None,
+          },
           self.static_paths_to_watch,
-          self.ws_path,
-          self.auto_inject_script,
+          watched_files,
+          self.watch_sources,
+          crate::core::reload::ReloadOptions {
+            #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+            ws_path: self.ws_path,
+            #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+            auto_inject_script: self.auto_inject_script,
+            #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+            external_script: self.external_script,
+            #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+            heartbeat_interval: self.ws_heartbeat_interval,
+            #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+            client_timeout: self.ws_client_timeout,
+            channel_capacity: self.reload_channel_capacity,
+            quiet_period: self.reload_quiet_period,
+            min_reload_interval: self.reload_min_interval,
+            #[cfg(feature = "actix")]
+            poke_path: self.reload_poke_path,
+            #[cfg(feature = "actix")]
+            styleguide: self.styleguide_dir.map(|dir| crate::core::styleguide::StyleguideOptions {
+              dir,
+              stylesheets: self.styleguide_stylesheets,
+            }),
+            #[cfg(feature = "notifications")]
+            desktop_notifications: self.desktop_notifications,
+          },
         )?;
         Arc::new(reloader)
       },
       // If `devel` is not enabled, the `reloader` field does not exist.
       // The code in the block above is not compiled.
       tera, // This moves the `tera` Arc into the struct
-      global_context: Arc::new(self.globals),
-    })
+      globals,
+      schemas: Arc::new(self.schemas),
+      buffers: Arc::new(BufferPool::default()),
+      early_hints: Arc::new(self.early_hints),
+      context_hooks: Arc::new(self.context_hooks),
+      post_processors: Arc::new(self.post_processors),
+      search,
+      #[cfg(feature = "actix")]
+      locales,
+      resolver: self.resolver.map(Arc::new),
+      icons,
+      #[cfg(feature = "actix")]
+      asset_files,
+      #[cfg(feature = "actix")]
+      early_flush: self.early_flush,
+      deferred_blocks,
+      render_env: Arc::new(self.render_env),
+      xss_audit: self.xss_audit,
+      sort_context_keys: self.sort_context_keys,
+      catch_panics: self.catch_panics,
+      error_reporter: self.error_reporter,
+      circuit_breaker: self.circuit_breaker.map(Arc::new),
+      error_template: self.error_template,
+      fragment_cache,
+      cache_dimensions,
+      surrogate_keys: self.surrogate_keys,
+      cdn_purgers: Arc::new(self.cdn_purgers),
+      #[cfg(feature = "actix")]
+      csp_sources: Arc::new(self.csp_sources),
+      #[cfg(feature = "actix")]
+      trusted_proxies: Arc::new(trusted_proxies),
+      helper_docs: Arc::new(self.helper_docs),
+      #[cfg(feature = "pdf")]
+      pdf_backend: self.pdf_backend.unwrap_or_else(|| Arc::new(Chromium::new())),
+      #[cfg(feature = "screenshot")]
+      screenshot_backend: self
+        .screenshot_backend
+        .unwrap_or_else(|| Arc::new(screenshot::Chromium::new())),
+      #[cfg(feature = "uploads")]
+      upload_rules,
+      #[cfg(feature = "content")]
+      content,
+      #[cfg(feature = "schedule")]
+      scheduler: None,
+    };
+
+    // 5. Start re-rendering scheduled pages. The scheduler renders through a
+    // clone without itself, so dropping the app stops it.
+    #[cfg(feature = "schedule")]
+    if !rerender_jobs.is_empty() {
+      let scheduler = Scheduler::start(app.clone(), rerender_jobs, self.schedule_timezone);
+      app.scheduler = Some(Arc::new(scheduler));
+    }
+
+    Ok(app)
   }
 }
 
@@ -228,7 +2558,7 @@ mod tests {
     let app = setup_test_app("site_name", "SnapFire Test", "Hello, {{ site_name }}!").await;
     let user_context = Context::new(); // Empty user context
 
-    let result = app.render_with_context("index.html", user_context);
+    let result = app.render_to_string("index.html", user_context);
 
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), "Hello, SnapFire Test!");
@@ -240,7 +2570,7 @@ mod tests {
     let mut user_context = Context::new();
     user_context.insert("user_name", "Alice");
 
-    let result = app.render_with_context("index.html", user_context);
+    let result = app.render_to_string("index.html", user_context);
 
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), "Hello, Alice!");
@@ -252,12 +2582,375 @@ mod tests {
     let mut user_context = Context::new();
     user_context.insert("title", "Page Title"); // This should win
 
-    let result = app.render_with_context("index.html", user_context);
+    let result = app.render_to_string("index.html", user_context);
 
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), "Title: Page Title");
   }
 
+  #[test]
+  fn test_host_globals_override_site_globals() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "{{ brand }} / {{ site_name }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+    let mut admin = Context::new();
+    admin.insert("brand", "Admin");
+
+    let app = TeraWeb::builder(&glob_path)
+      .add_global("brand", "Public")
+      .add_global("site_name", "SnapFire")
+      .add_host_globals("Admin.Example.com", admin)
+      .build()
+      .unwrap();
+
+    let admin_page = app.render_for_request("index.html", RequestParts::with_host("admin.example.com:8080"), Context::new());
+    assert_eq!(admin_page.unwrap(), "Admin / SnapFire");
+
+    let public_page = app.render_for_request("index.html", RequestParts::with_host("www.example.com"), Context::new());
+    assert_eq!(public_page.unwrap(), "Public / SnapFire");
+  }
+
+  #[test]
+  fn test_render_into_writer() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "Hello, {{ name }}!").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path).build().unwrap();
+
+    let mut context = Context::new();
+    context.insert("name", "Writer");
+    let mut output = Vec::new();
+    app.render_into("index.html", context, &mut output).unwrap();
+
+    assert_eq!(output, b"Hello, Writer!");
+  }
+
+  #[test]
+  fn test_pooled_buffers_do_not_mix_renders() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "{{ body }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path).build().unwrap();
+
+    let render = |body: &str| {
+      let mut context = Context::new();
+      context.insert("body", body);
+      app.render_bytes_for_request("index.html", RequestParts::default(), context).unwrap()
+    };
+
+    let first = render("a much longer first body");
+    let second = render("short");
+    assert_eq!(&first[..], b"a much longer first body");
+    assert_eq!(&second[..], b"short");
+
+    // A failed render doesn't leave partial output behind for the next one.
+    let mut context = Context::new();
+    context.insert("body", "fine");
+    assert!(app.render_bytes_for_request("missing.html", RequestParts::default(), Context::new()).is_err());
+    assert_eq!(&app.render_bytes_for_request("index.html", RequestParts::default(), context).unwrap()[..], b"fine");
+  }
+
+  #[test]
+  fn test_post_processors_run_in_order() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<p>  {{ name }}  </p>").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path)
+      .add_post_processor(|html, _| *html = html.replace("  ", ""))
+      .add_post_processor(|html, info| {
+        html.push_str(&format!("<!-- {} @ {} -->", info.template, info.host.unwrap_or("-")))
+      })
+      .build()
+      .unwrap();
+
+    let mut context = Context::new();
+    context.insert("name", "Processed");
+    let body = app
+      .render_bytes_for_request("index.html", RequestParts::with_host("Example.com:8080"), context)
+      .unwrap();
+    assert_eq!(&body[..], b"<p>Processed</p><!-- index.html @ example.com -->");
+  }
+
+  #[test]
+  fn test_timeago_script_is_injected_after_post_processors() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<body>{{ posted | timeago }}</body>").unwrap();
+    fs::write(temp_dir.path().join("plain.html"), "<body>x</body>").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path)
+      .timeago_script(true)
+      .add_post_processor(|html, _| html.push_str("<!-- end -->"))
+      .build()
+      .unwrap();
+
+    let mut context = Context::new();
+    context.insert("posted", "2001-01-01");
+    let body = app.render_bytes_for_request("index.html", RequestParts::default(), context).unwrap();
+    let body = std::str::from_utf8(&body).unwrap();
+    assert!(body.starts_with("<body><time datetime=\"2001-01-01T00:00:00Z\""), "{body}");
+    assert!(body.contains("years ago</time><script>") && body.ends_with("</script></body><!-- end -->"), "{body}");
+    let body = app.render_bytes_for_request("plain.html", RequestParts::default(), Context::new()).unwrap();
+    assert_eq!(&body[..], b"<body>x</body><!-- end -->");
+
+    let mut context = Context::new();
+    context.insert("posted", "2001-01-01");
+    let request = RequestParts {
+      nonce: Some("abc"),
+      ..RequestParts::default()
+    };
+    let body = app.render_bytes_for_request("index.html", request, context).unwrap();
+    assert!(std::str::from_utf8(&body).unwrap().contains("</time><script nonce=\"abc\">"));
+  }
+
+  #[test]
+  fn test_svg_inlines_files_from_the_static_dir() {
+    let temp_dir = tempdir().unwrap();
+    fs::create_dir(temp_dir.path().join("icons")).unwrap();
+    fs::write(
+      temp_dir.path().join("icons/check.svg"),
+      "<svg viewBox=\"0 0 8 8\"><path fill=\"red\" d=\"M0 0\"/></svg>",
+    )
+    .unwrap();
+    fs::write(
+      temp_dir.path().join("index.html"),
+      "{{ svg(path=\"icons/check.svg\", class=\"icon\", size=16, current_color=true) }}",
+    )
+    .unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path).static_dir(temp_dir.path()).build().unwrap();
+
+    let body = app.render_bytes_for_request("index.html", RequestParts::default(), Context::new()).unwrap();
+    assert_eq!(
+      std::str::from_utf8(&body).unwrap(),
+      "<svg viewBox=\"0 0 8 8\" width=\"16\" height=\"16\" aria-hidden=\"true\" focusable=\"false\" class=\"icon\">\
+       <path fill=\"currentColor\" d=\"M0 0\"/></svg>"
+    );
+  }
+
+  #[test]
+  fn test_deferred_blocks_render_inline_outside_early_flush() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<aside>{{ await_block(name=\"recs\", user=\"ana\") }}</aside>").unwrap();
+    fs::write(temp_dir.path().join("recs.html"), "<p>{{ user }} on {{ site }}</p>").unwrap();
+    fs::write(temp_dir.path().join("typo.html"), "{{ await_block(name=\"rec\") }}").unwrap();
+    fs::write(temp_dir.path().join("down.html"), "{{ await_block(name=\"down\") }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path)
+      .add_global("site", "Example")
+      .deferred_block("recs", "recs.html", |args| {
+        let mut context = Context::new();
+        context.insert("user", &args["user"]);
+        Ok(context)
+      })
+      .deferred_block("down", "recs.html", |_| Err(std::io::Error::other("recommendations are down").into()))
+      .build()
+      .unwrap();
+
+    let body = app.render_bytes_for_request("index.html", RequestParts::default(), Context::new()).unwrap();
+    assert_eq!(std::str::from_utf8(&body).unwrap(), "<aside><p>ana on Example</p></aside>");
+    let error = app.render_to_string("typo.html", Context::new()).unwrap_err();
+    assert!(format!("{error:?}").contains("declared blocks: down, recs"));
+    let error = app.render_to_string("down.html", Context::new()).unwrap_err();
+    assert!(format!("{error:?}").contains("recommendations are down"));
+  }
+
+  #[test]
+  fn test_resource_hints_include_the_asset_origin() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("manifest.json"), r#"{"app.css": "app-3f9c.css"}"#).unwrap();
+    fs::write(temp_dir.path().join("index.html"), "{{ resource_hints() }}{{ asset(path=\"app.css\") }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path)
+      .preconnect_origins(["https://fonts.gstatic.com/s/inter", "https://CDN.example.com"])
+      .asset_manifest(temp_dir.path().join("manifest.json"), "https://cdn.example.com/static")
+      .build()
+      .unwrap();
+
+    let body = app.render_bytes_for_request("index.html", RequestParts::default(), Context::new()).unwrap();
+    assert_eq!(
+      std::str::from_utf8(&body).unwrap(),
+      "<link rel=\"preconnect\" href=\"https://fonts.gstatic.com\" crossorigin>\n\
+       <link rel=\"dns-prefetch\" href=\"https://fonts.gstatic.com\">\n\
+       <link rel=\"preconnect\" href=\"https://cdn.example.com\" crossorigin>\n\
+       <link rel=\"dns-prefetch\" href=\"https://cdn.example.com\">\n\
+       https://cdn.example.com/static/app-3f9c.css"
+    );
+
+    let invalid = TeraWeb::builder(&glob_path).preconnect_origins(["fonts.gstatic.com"]).build();
+    assert!(matches!(invalid, Err(SnapFireError::Config { .. })));
+  }
+
+  #[test]
+  fn test_asset_base_url_is_bypassed_in_dev_builds() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "{{ asset(path=\"/img/logo.png\") }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path).asset_base_url("https://cdn.example.com/").build().unwrap();
+
+    let body = app.render_bytes_for_request("index.html", RequestParts::default(), Context::new()).unwrap();
+    let expected = if cfg!(feature = "devel") {
+      "/img/logo.png"
+    } else {
+      "https://cdn.example.com/img/logo.png"
+    };
+    assert_eq!(std::str::from_utf8(&body).unwrap(), expected);
+    #[cfg(feature = "actix")]
+    assert_eq!(
+      app.csp_sources.iter().any(|source| source == ("img-src", "https://cdn.example.com")),
+      !cfg!(feature = "devel")
+    );
+
+    let invalid = TeraWeb::builder(&glob_path).asset_base_url("cdn.example.com").build();
+    assert!(matches!(invalid, Err(SnapFireError::Config { .. })));
+  }
+
+  #[test]
+  fn test_context_hooks_see_merged_context() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "{{ nav }}|{{ greeting }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path)
+      .add_global("site_name", "SnapFire")
+      .add_context_hook(|context, info| context.insert("nav", &format!("nav for {}", info.template)))
+      .add_context_hook(|context, _| {
+        let greeting = format!("{} welcomes {}", context.get("site_name").unwrap(), context.get("user").unwrap());
+        context.insert("greeting", &greeting);
+      })
+      .build()
+      .unwrap();
+
+    let mut context = Context::new();
+    context.insert("user", "Alice");
+    let output = app.render_to_string("index.html", context).unwrap();
+    assert_eq!(output, "nav for index.html|&quot;SnapFire&quot; welcomes &quot;Alice&quot;");
+
+    // A hook's changes don't leak into the next render's globals.
+    let mut context = Context::new();
+    context.insert("user", "Bob");
+    let output = app.render_to_string("index.html", context).unwrap();
+    assert!(output.ends_with("welcomes &quot;Bob&quot;"));
+  }
+
+  #[test]
+  fn test_nav_uses_request_path() {
+    let temp_dir = tempdir().unwrap();
+    let template = "{% for item in nav(name=\"main\") %}{{ item.title }}{% if item.active %}*{% endif %} {% endfor %}";
+    fs::write(temp_dir.path().join("index.html"), template).unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path)
+      .nav("main", vec![NavItem::new("Home", "/"), NavItem::new("Blog", "/blog")])
+      .build()
+      .unwrap();
+
+    let request = RequestParts {
+      path: Some("/blog/hello"),
+      ..RequestParts::default()
+    };
+    let output = app.render_for_request("index.html", request, Context::new()).unwrap();
+    assert_eq!(output, "Home Blog* ");
+  }
+
+  #[cfg(feature = "content")]
+  #[test]
+  fn test_content_collections_and_taxonomies() {
+    let temp_dir = tempdir().unwrap();
+    let template = "{% for post in content(name=\"blog\") | sort(attribute=\"date\") %}{{ post.slug }}:{{ post.content | safe }}{% endfor %}";
+    fs::write(temp_dir.path().join("index.html"), template).unwrap();
+    let blog = temp_dir.path().join("content/blog");
+    fs::create_dir_all(&blog).unwrap();
+    fs::write(blog.join("a.md"), "---\ndate: 2024-02-01\ntags: [News]\n---\nLater").unwrap();
+    fs::write(blog.join("b.md"), "---\ndate: 2023-01-01\n---\nEarlier").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path)
+      .content_dir(temp_dir.path().join("content"))
+      .taxonomy("tags")
+      .build()
+      .unwrap();
+
+    let output = app.render_for_request("index.html", RequestParts::default(), Context::new()).unwrap();
+    assert_eq!(output, "b:<p>Earlier</p>\na:<p>Later</p>\n");
+    assert_eq!(app.content("blog").len(), 2);
+    assert_eq!(app.taxonomy("tags").len(), 1);
+    assert_eq!(app.taxonomy_term("tags", "news").unwrap()["pages"][0]["slug"], "a");
+    assert!(app.taxonomy_term("tags", "sports").is_none());
+    assert!(app.content("missing").is_empty());
+  }
+
+  #[test]
+  fn test_search_index_renders_pages() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(
+      temp_dir.path().join("page.html"),
+      "<title>{{ title }}</title><body><p>{{ text }}</p><a href=\"{{ search_index_url() }}\">i</a></body>",
+    )
+    .unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+    let mut about = Context::new();
+    about.insert("title", "About");
+    about.insert("text", "Who we are");
+    let app = TeraWeb::builder(&glob_path)
+      .search_index(vec![SearchPage::new("/about", "page.html").context(about)])
+      .build()
+      .unwrap();
+
+    let json: serde_json::Value = serde_json::from_slice(&app.search_index_json().unwrap()).unwrap();
+    assert_eq!(
+      json,
+      serde_json::json!([{ "url": "/about", "title": "About", "body": "Who we are i" }])
+    );
+  }
+
+  #[test]
+  fn test_build_from_bundle() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "{{ site_name }}: {{ title }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let bytes = TemplateBundle::from_glob(&glob_path).unwrap().to_bytes();
+    // The app must not need the template files once the bundle exists.
+    drop(temp_dir);
+
+    let app = TeraWeb::builder_from_bundle(TemplateBundle::from_bytes(&bytes).unwrap())
+      .add_global("site_name", "Bundled")
+      .build()
+      .unwrap();
+
+    let mut context = Context::new();
+    context.insert("title", "Home");
+    assert_eq!(app.render_to_string("index.html", context).unwrap(), "Bundled: Home");
+  }
+
+  #[test]
+  fn test_context_schema_reports_missing_fields() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("user.html"), "{{ user.email }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+    let schema = ContextSchema::from_json(serde_json::json!({
+      "type": "object",
+      "required": ["user"],
+      "properties": { "user": { "type": "object", "required": ["email"] } },
+    }));
+    let app = TeraWeb::builder(&glob_path)
+      .context_schema("user.html", schema)
+      .build()
+      .unwrap();
+
+    let mut context = Context::new();
+    context.insert("user", &serde_json::json!({ "name": "Alice" }));
+    let err = app.render_to_string("user.html", context).unwrap_err();
+
+    assert!(matches!(&err, SnapFireError::Schema { violations, .. } if violations == &["missing field user.email"]));
+  }
+
+  #[test]
+  fn test_normalize_host() {
+    assert_eq!(normalize_host("Example.COM:443"), "example.com");
+    assert_eq!(normalize_host("[::1]:8080"), "[::1]");
+    assert_eq!(normalize_host("[::1]"), "[::1]");
+  }
+
   #[test]
   fn test_bad_glob_behavior() {
     let builder = TeraWeb::builder("/invalid/path/that/does/not/exist/*.html");
@@ -277,7 +2970,7 @@ mod tests {
       // In release mode, Tera::new() succeeds even with a bad glob.
       let app = builder.build().unwrap();
       // The error should only occur when we try to render.
-      let result = app.render_with_context("non_existent.html", Context::new());
+      let result = app.render_to_string("non_existent.html", Context::new());
       assert!(matches!(result.unwrap_err(), SnapFireError::Tera(_)));
     }
   }
@@ -307,8 +3000,104 @@ mod tests {
 
     let mut context = Context::new();
     context.insert("name", "world");
-    let result = app.render_with_context("index.html", context);
+    let result = app.render_to_string("index.html", context);
 
     assert_eq!(result.unwrap(), "Hello, WORLD!");
   }
+
+  #[test]
+  fn test_error_reporter_sees_failed_renders() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "{{ site }}\n{{ missing.title }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let failures = Arc::new(parking_lot::Mutex::new(Vec::new()));
+    let reported = Arc::clone(&failures);
+    let app = TeraWeb::builder(&glob_path)
+      .add_global("site", "Example")
+      .error_reporter(move |failure| reported.lock().push(failure.clone()))
+      .build()
+      .unwrap();
+
+    let mut context = Context::new();
+    context.insert("session_id", "s3cr3t");
+    let request = RequestParts {
+      path: Some("/about"),
+      request_id: Some("req-1"),
+      ..RequestParts::default()
+    };
+    assert!(app.render_for_request("index.html", request, context).is_err());
+    let failures = failures.lock();
+    let [failure] = failures.as_slice() else {
+      panic!("expected one failure, got {failures:?}");
+    };
+    assert_eq!((failure.template.as_str(), failure.line), ("index.html", Some(2)));
+    assert_eq!(failure.request_id.as_deref(), Some("req-1"));
+    assert_eq!(failure.context["site"], "\"Example\"");
+    assert_eq!(failure.context["session_id"], "[scrubbed]");
+  }
+
+  #[test]
+  fn test_sensitive_values_render_but_are_scrubbed_from_errors() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "{{ email }}").unwrap();
+    fs::write(temp_dir.path().join("profile.html"), "{{ email }} {{ born | strict_date }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let failures = Arc::new(parking_lot::Mutex::new(Vec::new()));
+    let reported = Arc::clone(&failures);
+    let app = TeraWeb::builder(&glob_path)
+      .error_reporter(move |failure| reported.lock().push(failure.clone()))
+      .configure_tera(|tera| {
+        tera.register_filter("strict_date", |value: &Value, _: &HashMap<String, Value>| -> tera::Result<Value> {
+          Err(tera::Error::msg(format!("`{}` is not a date", value.as_str().unwrap_or_default())))
+        });
+      })
+      .build()
+      .unwrap();
+
+    let render = |tpl| {
+      app
+        .render(tpl, Context::new())
+        .insert_sensitive("email", "ada@example.com")
+        .insert_sensitive("born", "10/12/1815")
+        .into_string()
+    };
+    assert_eq!(render("index.html").unwrap(), "ada@example.com");
+
+    let error = render("profile.html").unwrap_err();
+    let message = crate::error::error_chain(&error);
+    assert!(message.contains("`[scrubbed]` is not a date"), "{message}");
+    assert!(!message.contains("1815"));
+    let failures = failures.lock();
+    assert!(!failures[0].message.contains("1815"));
+    assert!(!failures[0].context.keys().any(|key| key.contains("sensitive")), "{:?}", failures[0].context);
+    assert_eq!(failures[0].context["email"], "[scrubbed]");
+  }
+
+  #[test]
+  fn test_catch_panics_turns_a_panicking_filter_into_an_error() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "{{ site }}: {{ name | explode }}").unwrap();
+    fs::write(temp_dir.path().join("ok.html"), "{{ site }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path)
+      .add_global("site", "Example")
+      .catch_panics(true)
+      .configure_tera(|tera| {
+        tera.register_filter("explode", |_: &Value, _: &HashMap<String, Value>| -> tera::Result<Value> {
+          panic!("the filter blew up")
+        });
+      })
+      .build()
+      .unwrap();
+
+    let mut context = Context::new();
+    context.insert("name", "ana");
+    let error = app.render_to_string("index.html", context).unwrap_err();
+    assert!(matches!(
+      &error,
+      SnapFireError::Panic { template, message } if template == "index.html" && message == "the filter blew up"
+    ));
+    // The app keeps rendering afterwards.
+    assert_eq!(app.render_to_string("ok.html", Context::new()).unwrap(), "Example");
+  }
 }
@@ -0,0 +1,393 @@
+//! Fingerprinted and CDN asset URLs.
+//!
+//! Build tools write hashed file names (`app-3f9c2a.js`) and a JSON manifest
+//! mapping each source name to its output. With
+//! `TeraWebBuilder::asset_manifest("static/manifest.json", "/static/")`,
+//! templates link to assets by source name:
+//!
+//! ```jinja
+//! <link rel="stylesheet" href="{{ asset(path="app.css") }}">
+//! {{ script_module_nomodule(entry="app.js", nonce=csp_nonce) }}
+//! ```
+//!
+//! The manifest's values are either the output file, as written by
+//! `webpack-manifest-plugin` and similar (`{"app.js": "app-3f9c2a.js"}`), or
+//! an object with a `file` and optional `integrity`, as written by Vite and
+//! its SRI plugins (`{"app.js": {"file": "assets/app-3f9c2a.js",
+//! "integrity": "sha384-…"}}`). Relative files are served under the URL
+//! prefix given with the manifest.
+//!
+//! `asset(path=...)` returns the URL of a manifest entry, or of the path
+//! itself when the manifest doesn't list it (e.g. images copied as-is):
+//! relative paths under the prefix, site paths (`/favicon.ico`) as they are.
+//! Absolute URLs (`https://…`, `//…`, `data:`) pass through untouched.
+//!
+//! `TeraWebBuilder::asset_base_url("https://cdn.example.com")` serves the
+//! site paths `asset` produces from a CDN instead, so
+//! `/static/app-3f9c2a.css` becomes
+//! `https://cdn.example.com/static/app-3f9c2a.css`. It registers `asset`
+//! without a manifest too. Dev builds (with the `devel` feature) ignore the
+//! base URL, so pages load the files the dev server is rebuilding.
+//!
+//! `script_module_nomodule(entry=...)` emits the modern bundle as
+//! `<script type="module">` and the legacy one as `<script nomodule defer>`,
+//! so each browser runs exactly one. The legacy entry is found by the
+//! `-legacy` suffix `@vitejs/plugin-legacy` uses (`app-legacy.js` for
+//! `app.js`), or named with `legacy=...`. Entries with an `integrity` get it
+//! with `crossorigin="anonymous"`, and `nonce` adds a CSP nonce. Entries
+//! missing from the manifest fail the render, so a changed build setup shows
+//! up as an error rather than a page without scripts.
+//!
+//! In dev mode the manifest is watched and re-read when the build rewrites it.
+
+use crate::core::i18n::escape_attribute;
+use crate::error::{Result, SnapFireError};
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tera::{Function, Value};
+
+/// A manifest value: the output file, or an object describing it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum RawEntry {
+  File(String),
+  Described {
+    file: String,
+    #[serde(default)]
+    integrity: Option<String>,
+  },
+}
+
+/// A built asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Asset {
+  pub(crate) url: String,
+  pub(crate) integrity: Option<String>,
+}
+
+/// How asset URLs are built: the entries of a build manifest, re-read when
+/// it changes in dev mode, and the CDN serving them.
+#[derive(Debug)]
+pub(crate) struct Assets {
+  file: Option<PathBuf>,
+  /// The URL prefix of relative files, ending with `/`.
+  prefix: String,
+  /// The CDN origin (and path) site paths are served from, without a trailing `/`.
+  base_url: Option<String>,
+  entries: RwLock<Arc<HashMap<String, RawEntry>>>,
+}
+
+impl Assets {
+  /// Loads the manifest `file`, if any, whose relative files are served
+  /// under `prefix`, with site paths served from `base_url`.
+  pub(crate) fn new(file: Option<PathBuf>, prefix: &str, base_url: Option<&str>) -> Result<Self> {
+    let assets = Self {
+      file,
+      prefix: format!("{}/", prefix.trim_end_matches('/')),
+      base_url: base_url.map(|base_url| base_url.trim_end_matches('/').to_string()),
+      entries: RwLock::new(Arc::new(HashMap::new())),
+    };
+    assets.reload()?;
+    Ok(assets)
+  }
+
+  /// The manifest file.
+  pub(crate) fn file(&self) -> Option<&Path> {
+    self.file.as_deref()
+  }
+
+  /// Re-reads the manifest. On failure, the previous entries stay active.
+  pub(crate) fn reload(&self) -> Result<()> {
+    let Some(file) = &self.file else {
+      return Ok(());
+    };
+    let config_error = |message: String| SnapFireError::Config {
+      path: file.display().to_string(),
+      message,
+    };
+    let source = std::fs::read_to_string(file).map_err(|e| config_error(e.to_string()))?;
+    let entries: HashMap<String, RawEntry> = serde_json::from_str(&source).map_err(|e| config_error(e.to_string()))?;
+    *self.entries.write() = Arc::new(entries);
+    Ok(())
+  }
+
+  /// The built asset for the source `name`, if the manifest lists it.
+  pub(crate) fn get(&self, name: &str) -> Option<Asset> {
+    let entries = Arc::clone(&self.entries.read());
+    let (file, integrity) = match entries.get(name.trim_start_matches('/'))? {
+      RawEntry::File(file) => (file, None),
+      RawEntry::Described { file, integrity } => (file, integrity.clone()),
+    };
+    Some(Asset {
+      url: self.url(file),
+      integrity,
+    })
+  }
+
+  /// The origin the assets are served from, when it's another host.
+  pub(crate) fn origin(&self) -> Option<String> {
+    crate::core::hints::origin_of(self.base_url.as_deref().unwrap_or(&self.prefix))
+  }
+
+  /// The URL of `path`: from the manifest if listed, else the path itself.
+  pub(crate) fn asset_url(&self, path: &str) -> String {
+    if is_absolute(path) {
+      return path.to_string();
+    }
+    self.get(path).map_or_else(|| self.url(path), |asset| asset.url)
+  }
+
+  /// The URL of a manifest `file` or requested path: relative ones under the
+  /// prefix, and site paths on the CDN.
+  fn url(&self, file: &str) -> String {
+    if is_absolute(file) {
+      return file.to_string();
+    }
+    let path = if file.starts_with('/') {
+      file.to_string()
+    } else {
+      format!("{}{file}", self.prefix)
+    };
+    match &self.base_url {
+      // The prefix may already be absolute, e.g. a CDN per manifest.
+      Some(base_url) if path.starts_with('/') => format!("{base_url}{path}"),
+      _ => path,
+    }
+  }
+}
+
+/// Whether `url` names its own host or is self-contained.
+fn is_absolute(url: &str) -> bool {
+  url.starts_with("//") || url.contains("://") || url.starts_with("data:")
+}
+
+/// The directory the fingerprinted assets are built into, served by the
+/// Actix `configure_routes` under the manifest's URL prefix.
+#[cfg(feature = "actix")]
+#[derive(Debug)]
+pub(crate) struct AssetFiles {
+  dir: PathBuf,
+  pub(crate) assets: Arc<Assets>,
+}
+
+#[cfg(feature = "actix")]
+impl AssetFiles {
+  /// Serves `dir` under the URL prefix of `assets`, which must be a site path
+  /// other than `/`.
+  pub(crate) fn new(dir: PathBuf, assets: Arc<Assets>) -> std::result::Result<Self, String> {
+    if !assets.prefix.starts_with('/') || assets.prefix.starts_with("//") || assets.prefix == "/" {
+      return Err(format!(
+        "the asset URL prefix `{}` must be a site path like `/static/` to serve files under it",
+        assets.prefix
+      ));
+    }
+    Ok(Self { dir, assets })
+  }
+
+  /// The URL path the files are served under, ending with `/`.
+  pub(crate) fn prefix(&self) -> &str {
+    &self.assets.prefix
+  }
+
+  /// The file for the decoded path below the prefix, if it exists inside the
+  /// directory. Hidden files and `..` segments are never served.
+  pub(crate) fn resolve(&self, path: &str) -> Option<PathBuf> {
+    let safe = path
+      .split('/')
+      .all(|segment| !segment.is_empty() && !segment.starts_with('.') && !segment.contains(['\\', '\0']));
+    if !safe {
+      return None;
+    }
+    let file = self.dir.join(path).canonicalize().ok()?;
+    // Symlinks may still point out of the directory.
+    (file.starts_with(self.dir.canonicalize().ok()?) && file.is_file()).then_some(file)
+  }
+
+  /// Whether `path`, below the prefix, is a build output listed in the
+  /// manifest, whose name changes with its contents.
+  pub(crate) fn is_fingerprinted(&self, path: &str) -> bool {
+    let entries = Arc::clone(&self.assets.entries.read());
+    entries.values().any(|entry| {
+      let file = match entry {
+        RawEntry::File(file) | RawEntry::Described { file, .. } => file,
+      };
+      let site_path = self.assets.prefix.trim_end_matches('/');
+      file.strip_prefix(site_path).and_then(|file| file.strip_prefix('/')).unwrap_or(file) == path
+    })
+  }
+}
+
+/// The `Content-Type` for a file, by extension.
+#[cfg(any(feature = "actix", feature = "deploy"))]
+pub(crate) fn content_type(file: &str) -> &'static str {
+  match Path::new(file).extension().and_then(|e| e.to_str()).unwrap_or_default() {
+    "html" | "htm" => "text/html; charset=utf-8",
+    "css" => "text/css; charset=utf-8",
+    "js" | "mjs" => "text/javascript; charset=utf-8",
+    "json" | "map" => "application/json",
+    "xml" => "application/xml",
+    "txt" => "text/plain; charset=utf-8",
+    "svg" => "image/svg+xml",
+    "png" => "image/png",
+    "jpg" | "jpeg" => "image/jpeg",
+    "gif" => "image/gif",
+    "webp" => "image/webp",
+    "avif" => "image/avif",
+    "ico" => "image/x-icon",
+    "woff" => "font/woff",
+    "woff2" => "font/woff2",
+    "ttf" => "font/ttf",
+    "otf" => "font/otf",
+    "mp4" => "video/mp4",
+    "webm" => "video/webm",
+    "mp3" => "audio/mpeg",
+    "pdf" => "application/pdf",
+    "wasm" => "application/wasm",
+    _ => "application/octet-stream",
+  }
+}
+
+/// The legacy entry `@vitejs/plugin-legacy` builds for `entry`:
+/// `app-legacy.js` for `app.js`.
+fn legacy_name(entry: &str) -> String {
+  let file_start = entry.rfind('/').map_or(0, |slash| slash + 1);
+  match entry[file_start..].find('.') {
+    Some(dot) => {
+      let (stem, extension) = entry.split_at(file_start + dot);
+      format!("{stem}-legacy{extension}")
+    }
+    None => format!("{entry}-legacy"),
+  }
+}
+
+/// Appends a `<script>` tag of `kind` for `asset`, with `extra` attributes last.
+fn script_tag(html: &mut String, kind: &str, asset: &Asset, nonce: Option<&str>, extra: &str) {
+  let _ = write!(html, "<script {kind} src=\"{}\"", escape_attribute(&asset.url));
+  if let Some(integrity) = &asset.integrity {
+    let _ = write!(html, " integrity=\"{}\" crossorigin=\"anonymous\"", escape_attribute(integrity));
+  }
+  if let Some(nonce) = nonce {
+    let _ = write!(html, " nonce=\"{}\"", escape_attribute(nonce));
+  }
+  html.push_str(extra);
+  html.push_str("></script>");
+}
+
+/// The `asset(path=...)` Tera function.
+pub(crate) struct AssetFunction(pub(crate) Arc<Assets>);
+
+impl Function for AssetFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let path = args
+      .get("path")
+      .and_then(Value::as_str)
+      .ok_or_else(|| tera::Error::msg("The `asset` function requires a `path` string, e.g. asset(path=\"app.css\")"))?;
+    // Escaped here rather than by Tera, which would also escape every `/`.
+    Ok(Value::String(escape_attribute(&self.0.asset_url(path))))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// The `script_module_nomodule(entry=..., legacy=..., nonce=...)` Tera function.
+pub(crate) struct ModuleScriptsFunction(pub(crate) Arc<Assets>);
+
+impl Function for ModuleScriptsFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let string = |name: &str| match args.get(name) {
+      Some(Value::String(value)) => Ok(Some(value.as_str())),
+      Some(Value::Null) | None => Ok(None),
+      Some(_) => Err(tera::Error::msg(format!("The `{name}` argument of `script_module_nomodule` must be a string"))),
+    };
+    let entry = string("entry")?.ok_or_else(|| {
+      tera::Error::msg("The `script_module_nomodule` function requires an `entry` string, e.g. script_module_nomodule(entry=\"app.js\")")
+    })?;
+    let legacy = string("legacy")?.map_or_else(|| legacy_name(entry), str::to_string);
+    let nonce = string("nonce")?;
+
+    let lookup = |name: &str| {
+      self.0.get(name).ok_or_else(|| {
+        tera::Error::msg(format!(
+          "`{name}` is not in the asset manifest {}; check the build's entry names",
+          self.0.file().unwrap_or_else(|| Path::new("(none)")).display()
+        ))
+      })
+    };
+    let (modern, legacy) = (lookup(entry)?, lookup(&legacy)?);
+    let mut html = String::new();
+    script_tag(&mut html, "type=\"module\"", &modern, nonce, "");
+    html.push('\n');
+    script_tag(&mut html, "nomodule", &legacy, nonce, " defer");
+    Ok(Value::String(html))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_manifest_urls_and_script_pairs() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("manifest.json");
+    fs::write(
+      &file,
+      r#"{
+        "app.js": {"file": "assets/app-3f9c.js", "integrity": "sha384-abc"},
+        "app-legacy.js": {"file": "assets/app-legacy-1a2b.js", "isEntry": true},
+        "admin.js": "/static/admin-77e1.js",
+        "admin-legacy.js": "https://cdn.example.com/admin-legacy-0d4c.js"
+      }"#,
+    )
+    .unwrap();
+    let manifest = Arc::new(Assets::new(Some(file.clone()), "/static", None).unwrap());
+    assert_eq!(manifest.asset_url("app.js"), "/static/assets/app-3f9c.js");
+    assert_eq!(manifest.asset_url("/img/logo.png"), "/img/logo.png");
+    assert_eq!(manifest.asset_url("img/logo.png"), "/static/img/logo.png");
+
+    let scripts = ModuleScriptsFunction(Arc::clone(&manifest));
+    let args = HashMap::from([
+      ("entry".to_string(), Value::from("app.js")),
+      ("nonce".to_string(), Value::from("n0nce")),
+    ]);
+    assert_eq!(
+      scripts.call(&args).unwrap(),
+      Value::from(
+        "<script type=\"module\" src=\"/static/assets/app-3f9c.js\" integrity=\"sha384-abc\" crossorigin=\"anonymous\" nonce=\"n0nce\"></script>\n\
+         <script nomodule src=\"/static/assets/app-legacy-1a2b.js\" nonce=\"n0nce\" defer></script>"
+      )
+    );
+    let args = HashMap::from([("entry".to_string(), Value::from("admin.js"))]);
+    assert!(scripts.call(&args).unwrap().as_str().unwrap().contains("src=\"https://cdn.example.com/admin-legacy-0d4c.js\""));
+    let args = HashMap::from([("entry".to_string(), Value::from("missing.js"))]);
+    assert!(scripts.call(&args).is_err());
+    assert_eq!(legacy_name("src/main.tsx"), "src/main-legacy.tsx");
+    assert_eq!(legacy_name("v1.2/main"), "v1.2/main-legacy");
+
+    // On a CDN, site paths move to its host; absolute URLs stay.
+    let cdn = Assets::new(Some(file), "/static", Some("https://cdn.example.com/v2/")).unwrap();
+    assert_eq!(cdn.asset_url("app.js"), "https://cdn.example.com/v2/static/assets/app-3f9c.js");
+    assert_eq!(cdn.asset_url("admin.js"), "https://cdn.example.com/v2/static/admin-77e1.js");
+    assert_eq!(cdn.asset_url("/favicon.ico"), "https://cdn.example.com/v2/favicon.ico");
+    assert_eq!(cdn.asset_url("admin-legacy.js"), "https://cdn.example.com/admin-legacy-0d4c.js");
+    assert_eq!(cdn.asset_url("//fonts.example.net/inter.css"), "//fonts.example.net/inter.css");
+    assert_eq!(cdn.asset_url("data:image/gif;base64,R0lG"), "data:image/gif;base64,R0lG");
+    assert_eq!(cdn.origin(), Some("https://cdn.example.com".to_string()));
+
+    // Without a manifest, paths are only moved to the CDN.
+    let plain = Assets::new(None, "/", Some("https://cdn.example.com")).unwrap();
+    assert_eq!(plain.asset_url("img/logo.png"), "https://cdn.example.com/img/logo.png");
+  }
+}
@@ -0,0 +1,153 @@
+//! An audit trail of the template changes applied at runtime.
+//!
+//! Every time the active template set is replaced (by live reload, a
+//! `StaticExport` rebuild, or a test's `FakeReloader`), SnapFire compares the
+//! new templates with the old ones and, if any were added, removed, or
+//! modified, records a [`TemplateChange`]: who applied it, what changed, and
+//! when. [`TeraWeb::template_audit`] returns the recorded changes, oldest
+//! first:
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! let app = snapfire::TeraWeb::builder("templates/**/*.html").build()?;
+//! for change in app.template_audit() {
+//!   println!("{change}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only the latest 500 changes are kept in memory. Each one is also logged
+//! at the `info` level under the `snapfire::audit` target, so it can be
+//! routed to durable storage with the rest of the app's logs.
+//!
+//! [`TeraWeb::template_audit`]: crate::TeraWeb::template_audit
+
+use parking_lot::Mutex;
+use serde::{Serialize, Serializer};
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tera::Tera;
+
+/// How many changes the audit log keeps.
+const CAPACITY: usize = 500;
+
+/// A change to the active templates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateChange {
+  /// When the change was applied; serialized as seconds since the Unix epoch.
+  #[serde(serialize_with = "unix_seconds")]
+  pub at: SystemTime,
+  /// What applied it: `live reload`, `static export`, or `fake reloader`.
+  pub actor: String,
+  /// The templates that didn't exist before, sorted.
+  pub added: Vec<String>,
+  /// The templates that no longer exist, sorted.
+  pub removed: Vec<String>,
+  /// The templates whose content changed, sorted.
+  pub modified: Vec<String>,
+}
+
+impl std::fmt::Display for TemplateChange {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let seconds = self.at.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or_default();
+    write!(f, "[{seconds}] {}:", self.actor)?;
+    for (sign, names) in [("+", &self.added), ("-", &self.removed), ("~", &self.modified)] {
+      for name in names {
+        write!(f, " {sign}{name}")?;
+      }
+    }
+    Ok(())
+  }
+}
+
+fn unix_seconds<S: Serializer>(at: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_u64(at.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or_default())
+}
+
+/// The recorded changes, shared by every template set of an app.
+#[derive(Debug, Default)]
+pub(crate) struct AuditLog {
+  changes: Mutex<VecDeque<TemplateChange>>,
+}
+
+impl AuditLog {
+  /// Records the differences between `before` and `after`, if there are any.
+  pub(crate) fn record(&self, actor: &str, before: &Tera, after: &Tera) {
+    let (mut added, mut removed, mut modified) = (Vec::new(), Vec::new(), Vec::new());
+    for (name, template) in &after.templates {
+      match before.templates.get(name) {
+        None => added.push(name.clone()),
+        Some(old) if !same_content(old, template) => modified.push(name.clone()),
+        Some(_) => {}
+      }
+    }
+    removed.extend(
+      before
+        .templates
+        .keys()
+        .filter(|name| !after.templates.contains_key(*name))
+        .cloned(),
+    );
+    if added.is_empty() && removed.is_empty() && modified.is_empty() {
+      return;
+    }
+    added.sort();
+    removed.sort();
+    modified.sort();
+
+    let change = TemplateChange {
+      at: SystemTime::now(),
+      actor: actor.to_string(),
+      added,
+      removed,
+      modified,
+    };
+    log::info!(target: "snapfire::audit", "{change}");
+    let mut changes = self.changes.lock();
+    if changes.len() == CAPACITY {
+      changes.pop_front();
+    }
+    changes.push_back(change);
+  }
+
+  pub(crate) fn changes(&self) -> Vec<TemplateChange> {
+    self.changes.lock().iter().cloned().collect()
+  }
+}
+
+/// Whether two parsed templates render the same, ignoring where they were
+/// loaded from.
+fn same_content(a: &tera::Template, b: &tera::Template) -> bool {
+  a.ast == b.ast && a.parent == b.parent && a.imported_macro_files == b.imported_macro_files && a.macros == b.macros
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_records_what_changed() {
+    let mut before = Tera::default();
+    before
+      .add_raw_templates([("index.html", "<h1>Hi</h1>"), ("about.html", "About"), ("old.html", "Old")])
+      .unwrap();
+    let mut after = Tera::default();
+    after
+      .add_raw_templates([("index.html", "<h1>Hello</h1>"), ("about.html", "About"), ("new.html", "New")])
+      .unwrap();
+
+    let log = AuditLog::default();
+    log.record("live reload", &before, &before);
+    assert!(log.changes().is_empty());
+    log.record("live reload", &before, &after);
+    let [change] = &log.changes()[..] else {
+      panic!("one change recorded");
+    };
+    assert_eq!(
+      (&change.added[..], &change.removed[..], &change.modified[..]),
+      (&["new.html".to_string()][..], &["old.html".to_string()][..], &["index.html".to_string()][..])
+    );
+    assert!(change.to_string().ends_with("] live reload: +new.html -old.html ~index.html"));
+  }
+}
@@ -0,0 +1,138 @@
+//! Avatar images for users.
+//!
+//! The `avatar` template function, registered on every app, returns an image
+//! URL for an email address or a name:
+//!
+//! - For an email, the [Gravatar](https://gravatar.com) image, looked up by
+//!   the SHA-256 hash of the address. Users without one get Gravatar's
+//!   `default` image (`identicon` unless given, or e.g. `mp` or `retro`).
+//! - For a name, or an email with `gravatar=false`, a generated SVG with the
+//!   initials on a background color derived from the name, as a `data:` URL,
+//!   so the same person always gets the same color without any request.
+//!
+//! ```jinja
+//! <img src="{{ avatar(value=user.email, size=40) }}" width="40" height="40" alt="">
+//! <img src="{{ avatar(value=comment.author_name, size=32) }}" alt="">
+//! ```
+//!
+//! `size` is in pixels, 80 by default. The same images are available to Rust
+//! code with [`gravatar_url`] and [`initials_svg`].
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Write;
+use tera::{Function, Value};
+
+/// The URL of the Gravatar image for `email`, `size` pixels square, falling
+/// back to the Gravatar `default` image (e.g. `identicon`, `mp`, or `404`).
+pub fn gravatar_url(email: &str, size: u32, default: &str) -> String {
+  let hash = Sha256::digest(email.trim().to_lowercase().as_bytes());
+  let mut url = String::from("https://www.gravatar.com/avatar/");
+  for byte in hash {
+    let _ = write!(url, "{byte:02x}");
+  }
+  let _ = write!(url, "?s={}&d={}", size, percent_encode(default));
+  url
+}
+
+/// An SVG avatar `size` pixels square with the initials of `name` on a
+/// background color derived from it.
+pub fn initials_svg(name: &str, size: u32) -> String {
+  // The hue is a stable hash of the name, so colors don't change between builds.
+  let key = name.trim().to_lowercase();
+  let hash = key
+    .bytes()
+    .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+  format!(
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\" viewBox=\"0 0 100 100\">\
+     <rect width=\"100\" height=\"100\" fill=\"hsl({}, 55%, 45%)\"/>\
+     <text x=\"50\" y=\"50\" dy=\"0.35em\" text-anchor=\"middle\" font-family=\"system-ui, sans-serif\" font-size=\"42\" fill=\"#fff\">{}</text></svg>",
+    hash % 360,
+    tera::escape_html(&initials(name))
+  )
+}
+
+/// Up to two initials: of the first and last words, or of the only one.
+/// Emails use their local part, split at dots, dashes, and underscores.
+fn initials(name: &str) -> String {
+  let name = name.split('@').next().unwrap_or_default();
+  let words: Vec<&str> = name.split(|c: char| c.is_whitespace() || ".-_+".contains(c)).filter(|word| !word.is_empty()).collect();
+  let initial = |word: &str| word.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default();
+  match words.as_slice() {
+    [] => "?".to_string(),
+    [word] => initial(word),
+    [first, .., last] => initial(first) + &initial(last),
+  }
+}
+
+/// Percent-encodes everything but unreserved URL characters.
+pub(crate) fn percent_encode(value: &str) -> String {
+  let mut encoded = String::with_capacity(value.len());
+  for byte in value.bytes() {
+    if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+      encoded.push(byte as char);
+    } else {
+      let _ = write!(encoded, "%{byte:02X}");
+    }
+  }
+  encoded
+}
+
+/// The `avatar(value=..., size=80)` Tera function.
+pub(crate) struct AvatarFunction;
+
+impl Function for AvatarFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let value = args.get("value").and_then(Value::as_str).ok_or_else(|| {
+      tera::Error::msg("The `avatar` function requires a `value` string, an email or a name, e.g. avatar(value=user.email)")
+    })?;
+    let size = match args.get("size") {
+      Some(size) => size
+        .as_u64()
+        .and_then(|size| u32::try_from(size).ok())
+        .filter(|&size| size > 0)
+        .ok_or_else(|| tera::Error::msg("The `size` argument of `avatar` must be a positive integer"))?,
+      None => 80,
+    };
+    let gravatar = match args.get("gravatar") {
+      Some(gravatar) => gravatar
+        .as_bool()
+        .ok_or_else(|| tera::Error::msg("The `gravatar` argument of `avatar` must be a boolean"))?,
+      None => true,
+    };
+
+    if gravatar && value.contains('@') {
+      let default = match args.get("default") {
+        Some(default) => default
+          .as_str()
+          .ok_or_else(|| tera::Error::msg("The `default` argument of `avatar` must be a string"))?,
+        None => "identicon",
+      };
+      return Ok(Value::String(gravatar_url(value, size, default)));
+    }
+    Ok(Value::String(format!("data:image/svg+xml,{}", percent_encode(&initials_svg(value, size)))))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_gravatar_and_initials() {
+    assert_eq!(
+      gravatar_url(" Test@Example.com ", 40, "mp"),
+      "https://www.gravatar.com/avatar/973dfe463ec85785f5f95af5ba3906eedb2d931c24e69824a89ea65dba4e813b?s=40&d=mp"
+    );
+    assert_eq!(initials("Ada King Lovelace"), "AL");
+    assert_eq!(initials("grace.hopper@example.com"), "GH");
+    assert_eq!(initials("émile"), "É");
+    assert_eq!(initials("  "), "?");
+
+    let svg = initials_svg("Ada Lovelace", 32);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"32\" height=\"32\""), "{svg}");
+    assert!(svg.contains(">AL</text>"), "{svg}");
+    assert_eq!(svg, initials_svg("ada lovelace", 32));
+    assert_ne!(svg, initials_svg("Alan Lovelace", 32));
+  }
+}
@@ -0,0 +1,218 @@
+//! QR code and barcode template functions.
+//!
+//! With the `barcode` feature, two functions are registered on every app.
+//! Both return an inline SVG element, marked safe, for tickets, invoices,
+//! and 2FA enrollment pages:
+//!
+//! - `qr_code(data=..., size=200)` → a QR code `size` pixels square. `ec`
+//!   sets the error correction level: `L`, `M` (the default), `Q`, or `H`.
+//! - `barcode(data=..., kind="code128", height=80)` → a linear barcode, each
+//!   bar `module` pixels (2 by default) wide. `kind` is `code128`, `code39`,
+//!   `code93`, `code11`, `codabar`, `ean13`, `ean8`, or `itf`; EAN codes
+//!   take their digits without the check digit, which is computed.
+//!
+//! Both take `color` and `background` CSS colors (black on white by default)
+//! and include the quiet zone scanners need around the code:
+//!
+//! ```jinja
+//! <div class="ticket">{{ qr_code(data=ticket.url, size=160) }}</div>
+//! <div class="invoice-number">{{ barcode(data=invoice.number, height=60) }}</div>
+//! ```
+//!
+//! Data the code can't hold, such as text too long for a QR code or letters
+//! in an EAN code, fails the render.
+
+use barcoders::sym::{codabar::Codabar, code11::Code11, code39::Code39, code93::Code93, code128::Code128, ean8::EAN8, ean13::EAN13, tf::TF};
+use qrcode::{EcLevel, QrCode};
+use std::collections::HashMap;
+use std::fmt::Write;
+use tera::{Function, Value};
+
+/// The light modules around a QR code, as its specification requires.
+const QR_QUIET_ZONE: usize = 4;
+
+/// The bar widths of space before and after a linear barcode.
+const BARCODE_QUIET_ZONE: usize = 10;
+
+/// The `qr_code(data=..., size=200)` Tera function.
+pub(crate) struct QrCodeFunction;
+
+impl Function for QrCodeFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let data = data_argument(args, "qr_code")?;
+    let size = number_argument(args, "qr_code", "size")?.unwrap_or(200);
+    let ec = match string_argument(args, "qr_code", "ec")?.unwrap_or("M") {
+      "L" | "l" => EcLevel::L,
+      "M" | "m" => EcLevel::M,
+      "Q" | "q" => EcLevel::Q,
+      "H" | "h" => EcLevel::H,
+      other => return Err(tera::Error::msg(format!("Unknown `ec` level `{other}` for `qr_code`; use L, M, Q, or H"))),
+    };
+
+    // The data may be a secret, like a 2FA enrollment URI; keep it out of errors.
+    let code = QrCode::with_error_correction_level(data.as_bytes(), ec)
+      .map_err(|e| tera::Error::msg(format!("`qr_code` could not encode its data: {e}")))?;
+    let width = code.width();
+    let dark: Vec<bool> = code.to_colors().iter().map(|color| *color == qrcode::Color::Dark).collect();
+    let mut path = String::new();
+    for (y, row) in dark.chunks(width).enumerate() {
+      push_runs(&mut path, row, QR_QUIET_ZONE, y + QR_QUIET_ZONE);
+    }
+
+    let modules = width + 2 * QR_QUIET_ZONE;
+    let attributes = format!("width=\"{size}\" height=\"{size}\" viewBox=\"0 0 {modules} {modules}\"");
+    Ok(Value::String(svg(&attributes, &Colors::from_args(args, "qr_code")?, &path)))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// The `barcode(data=..., kind="code128")` Tera function.
+pub(crate) struct BarcodeFunction;
+
+impl Function for BarcodeFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let data = data_argument(args, "barcode")?;
+    let kind = string_argument(args, "barcode", "kind")?.unwrap_or("code128");
+    let height = number_argument(args, "barcode", "height")?.unwrap_or(80);
+    let module = number_argument(args, "barcode", "module")?.unwrap_or(2);
+
+    let encoded = match kind {
+      // Character set B holds printable ASCII; data may also pick its own sets.
+      "code128" if data.starts_with(['À', 'Ɓ', 'Ć']) => Code128::new(&data).map(|code| code.encode()),
+      "code128" => Code128::new(format!("Ɓ{data}")).map(|code| code.encode()),
+      "code39" => Code39::new(&data).map(|code| code.encode()),
+      "code93" => Code93::new(&data).map(|code| code.encode()),
+      "code11" => Code11::new(&data).map(|code| code.encode()),
+      "codabar" => Codabar::new(&data).map(|code| code.encode()),
+      "ean13" => EAN13::new(&data).map(|code| code.encode()),
+      "ean8" => EAN8::new(&data).map(|code| code.encode()),
+      "itf" => TF::interleaved(&data).map(|code| code.encode()),
+      other => return Err(tera::Error::msg(format!("Unknown barcode kind `{other}`"))),
+    }
+    .map_err(|e| tera::Error::msg(format!("Could not encode `{data}` as barcode kind `{kind}`: {e}")))?;
+
+    let bars: Vec<bool> = encoded.iter().map(|&bar| bar == 1).collect();
+    let mut path = String::new();
+    push_runs(&mut path, &bars, BARCODE_QUIET_ZONE, 0);
+
+    // One unit of height, stretched: bars keep their width at any height.
+    let modules = bars.len() + 2 * BARCODE_QUIET_ZONE;
+    let attributes = format!(
+      "width=\"{}\" height=\"{height}\" viewBox=\"0 0 {modules} 1\" preserveAspectRatio=\"none\"",
+      modules as u64 * module
+    );
+    Ok(Value::String(svg(&attributes, &Colors::from_args(args, "barcode")?, &path)))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// The foreground and background colors, escaped for attributes.
+struct Colors {
+  color: String,
+  background: String,
+}
+
+impl Colors {
+  fn from_args(args: &HashMap<String, Value>, function: &str) -> tera::Result<Self> {
+    let color = string_argument(args, function, "color")?.unwrap_or("#000");
+    let background = string_argument(args, function, "background")?.unwrap_or("#fff");
+    Ok(Self {
+      color: tera::escape_html(color),
+      background: tera::escape_html(background),
+    })
+  }
+}
+
+/// Appends a path drawing each run of dark modules in `row` as one
+/// rectangle, offset by `x` and at line `y`.
+fn push_runs(path: &mut String, row: &[bool], x: usize, y: usize) {
+  let mut start = None;
+  for (index, &dark) in row.iter().chain([&false]).enumerate() {
+    match (dark, start) {
+      (true, None) => start = Some(index),
+      (false, Some(from)) => {
+        let _ = write!(path, "M{} {}h{}v1h-{}z", x + from, y, index - from, index - from);
+        start = None;
+      }
+      _ => {}
+    }
+  }
+}
+
+fn svg(attributes: &str, colors: &Colors, path: &str) -> String {
+  format!(
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" {} shape-rendering=\"crispEdges\"><rect width=\"100%\" height=\"100%\" fill=\"{}\"/><path fill=\"{}\" d=\"{}\"/></svg>",
+    attributes, colors.background, colors.color, path
+  )
+}
+
+/// The required `data` argument; numbers are encoded as their digits.
+fn data_argument(args: &HashMap<String, Value>, function: &str) -> tera::Result<String> {
+  match args.get("data") {
+    Some(Value::String(data)) => Ok(data.clone()),
+    Some(Value::Number(number)) => Ok(number.to_string()),
+    Some(_) => Err(tera::Error::msg(format!("The `data` argument of `{function}` must be a string or a number"))),
+    None => Err(tera::Error::msg(format!(
+      "The `{function}` function requires a `data` argument, e.g. {function}(data=\"...\")"
+    ))),
+  }
+}
+
+fn string_argument<'a>(args: &'a HashMap<String, Value>, function: &str, name: &str) -> tera::Result<Option<&'a str>> {
+  args
+    .get(name)
+    .map(|value| {
+      value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg(format!("The `{name}` argument of `{function}` must be a string")))
+    })
+    .transpose()
+}
+
+fn number_argument(args: &HashMap<String, Value>, function: &str, name: &str) -> tera::Result<Option<u64>> {
+  args
+    .get(name)
+    .map(|value| {
+      value
+        .as_u64()
+        .filter(|&number| number > 0)
+        .ok_or_else(|| tera::Error::msg(format!("The `{name}` argument of `{function}` must be a positive integer")))
+    })
+    .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn call(function: &dyn Function, args: Value) -> tera::Result<String> {
+    let args: HashMap<String, Value> = serde_json::from_value(args).unwrap();
+    function.call(&args).map(|value| value.as_str().unwrap().to_string())
+  }
+
+  #[test]
+  fn test_qr_code_and_barcode_svg() {
+    let qr = call(&QrCodeFunction, json!({ "data": "https://example.com", "size": 120, "color": "\"red" })).unwrap();
+    // Version 2 is 25 modules wide, plus the quiet zone on both sides.
+    assert!(qr.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"120\" height=\"120\" viewBox=\"0 0 33 33\""), "{qr}");
+    assert!(qr.contains("fill=\"&quot;red\" d=\"M4 4h7v1h-7z"), "{qr}");
+
+    let barcode = call(&BarcodeFunction, json!({ "data": 1234567, "kind": "ean8", "height": 40 })).unwrap();
+    // EAN-8 is 67 bars wide, starting with the 101 guard.
+    assert!(barcode.contains("width=\"174\" height=\"40\" viewBox=\"0 0 87 1\""), "{barcode}");
+    assert!(barcode.contains("d=\"M10 0h1v1h-1zM12 0h1v1h-1z"), "{barcode}");
+    assert!(call(&BarcodeFunction, json!({ "data": "INV-42" })).is_ok());
+
+    let error = call(&BarcodeFunction, json!({ "data": "abc", "kind": "ean13" })).unwrap_err();
+    assert!(error.to_string().contains("as barcode kind `ean13`"), "{error}");
+    assert!(call(&QrCodeFunction, json!({ "data": "x".repeat(5000) })).is_err());
+    assert!(call(&QrCodeFunction, json!({ "size": 10 })).is_err());
+  }
+}
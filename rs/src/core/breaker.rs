@@ -0,0 +1,222 @@
+//! A circuit breaker for templates that keep failing.
+//!
+//! When a bad deploy breaks one page under heavy traffic, every request for
+//! it still pays for a render that fails. With
+//! `TeraWebBuilder::circuit_breaker`, each template's renders are counted
+//! over a window; once enough of them fail, the template's circuit opens and,
+//! for the cooldown, requests for it are served the fallback template
+//! without trying it. The fallback sees the page's context, plus
+//! `unavailable_template` naming the page. After the cooldown, the next
+//! render is a trial: success closes the circuit, failure opens it again.
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! use snapfire::{TeraWeb, core::breaker::CircuitBreaker};
+//! use std::time::Duration;
+//!
+//! let app = TeraWeb::builder("templates/**/*.html")
+//!   .circuit_breaker(CircuitBreaker::new("errors/unavailable.html").error_rate(0.5).cooldown(Duration::from_secs(30)))
+//!   .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The Actix `Template` responder answers `503 Service Unavailable` with a
+//! `Retry-After` header while a page's circuit is open.
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// When to stop rendering a failing template, and what to serve instead.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+  fallback: String,
+  error_rate: f64,
+  min_renders: u32,
+  window: Duration,
+  cooldown: Duration,
+  circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+/// The recent renders of one template.
+#[derive(Debug)]
+struct Circuit {
+  window_start: Instant,
+  renders: u32,
+  failures: u32,
+  state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+  Closed,
+  /// Serving the fallback until the instant passes.
+  Open(Instant),
+  /// The cooldown is over and a trial render is running.
+  Trial,
+}
+
+impl CircuitBreaker {
+  /// A breaker serving `fallback` for open circuits. By default a circuit
+  /// opens when half of at least 20 renders in a minute fail, and stays
+  /// open for 30 seconds.
+  ///
+  /// # Arguments
+  ///
+  /// * `fallback` - The template to render instead of a failing one.
+  pub fn new(fallback: &str) -> Self {
+    Self {
+      fallback: fallback.to_string(),
+      error_rate: 0.5,
+      min_renders: 20,
+      window: Duration::from_secs(60),
+      cooldown: Duration::from_secs(30),
+      circuits: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// The share of failed renders, from `0.0` to `1.0`, that opens a circuit.
+  ///
+  /// # Arguments
+  ///
+  /// * `rate` - The threshold, e.g. `0.5`.
+  pub fn error_rate(mut self, rate: f64) -> Self {
+    self.error_rate = rate.clamp(0.0, 1.0);
+    self
+  }
+
+  /// How many renders a window needs before its error rate counts, so a
+  /// single early failure doesn't open the circuit.
+  ///
+  /// # Arguments
+  ///
+  /// * `renders` - The minimum, at least `1`.
+  pub fn min_renders(mut self, renders: u32) -> Self {
+    self.min_renders = renders.max(1);
+    self
+  }
+
+  /// How long renders are counted before the counts start over.
+  ///
+  /// # Arguments
+  ///
+  /// * `window` - The counting window.
+  pub fn window(mut self, window: Duration) -> Self {
+    self.window = window;
+    self
+  }
+
+  /// How long an open circuit serves the fallback before trying the
+  /// template again.
+  ///
+  /// # Arguments
+  ///
+  /// * `cooldown` - The time spent open.
+  pub fn cooldown(mut self, cooldown: Duration) -> Self {
+    self.cooldown = cooldown;
+    self
+  }
+
+  pub(crate) fn fallback(&self) -> &str {
+    &self.fallback
+  }
+
+  /// The time left before `tpl` is tried again, if its circuit is open.
+  pub(crate) fn open_for(&self, tpl: &str) -> Option<Duration> {
+    let mut circuits = self.circuits.lock();
+    let circuit = circuits.get_mut(tpl)?;
+    let State::Open(until) = circuit.state else {
+      return None;
+    };
+    let now = Instant::now();
+    if now < until {
+      return Some(until - now);
+    }
+    // Let this render through as the trial.
+    circuit.state = State::Trial;
+    None
+  }
+
+  /// Counts a render of `tpl`, opening its circuit if it fails too often.
+  pub(crate) fn record(&self, tpl: &str, succeeded: bool) {
+    let now = Instant::now();
+    let mut circuits = self.circuits.lock();
+    let circuit = circuits.entry(tpl.to_string()).or_insert_with(|| Circuit {
+      window_start: now,
+      renders: 0,
+      failures: 0,
+      state: State::Closed,
+    });
+
+    if circuit.state == State::Trial {
+      if succeeded {
+        log::info!("Closing the circuit of `{tpl}`: the trial render succeeded");
+        *circuit = Circuit {
+          window_start: now,
+          renders: 0,
+          failures: 0,
+          state: State::Closed,
+        };
+      } else {
+        log::warn!("Reopening the circuit of `{tpl}`: the trial render failed");
+        circuit.state = State::Open(now + self.cooldown);
+      }
+      return;
+    }
+
+    if now.duration_since(circuit.window_start) > self.window {
+      circuit.window_start = now;
+      circuit.renders = 0;
+      circuit.failures = 0;
+    }
+    circuit.renders += 1;
+    if !succeeded {
+      circuit.failures += 1;
+    }
+    let rate = f64::from(circuit.failures) / f64::from(circuit.renders);
+    if circuit.state == State::Closed && circuit.renders >= self.min_renders && !succeeded && rate >= self.error_rate {
+      log::warn!(
+        "Opening the circuit of `{tpl}` for {:?}: {} of its last {} renders failed; serving `{}`",
+        self.cooldown,
+        circuit.failures,
+        circuit.renders,
+        self.fallback
+      );
+      circuit.state = State::Open(now + self.cooldown);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_opens_after_failures_and_closes_after_a_good_trial() {
+    let breaker = CircuitBreaker::new("fallback.html")
+      .error_rate(0.5)
+      .min_renders(4)
+      .cooldown(Duration::from_millis(30));
+
+    for succeeded in [true, false, true] {
+      breaker.record("page.html", succeeded);
+    }
+    assert_eq!(breaker.open_for("page.html"), None);
+    // Four renders, half of them failed.
+    breaker.record("page.html", false);
+    assert!(breaker.open_for("page.html").is_some());
+    assert_eq!(breaker.open_for("other.html"), None);
+
+    // A failed trial opens it again, a good one closes it.
+    std::thread::sleep(Duration::from_millis(40));
+    assert_eq!(breaker.open_for("page.html"), None);
+    breaker.record("page.html", false);
+    assert!(breaker.open_for("page.html").is_some());
+    std::thread::sleep(Duration::from_millis(40));
+    assert_eq!(breaker.open_for("page.html"), None);
+    breaker.record("page.html", true);
+    breaker.record("page.html", false);
+    assert_eq!(breaker.open_for("page.html"), None);
+  }
+}
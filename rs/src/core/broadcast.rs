@@ -0,0 +1,240 @@
+//! The channel carrying reload messages to connected clients.
+//!
+//! It depends on no async runtime: receivers can block a thread
+//! ([`ReloadReceiver::recv_timeout`]), poll ([`ReloadReceiver::try_recv`]), or
+//! be awaited on any executor ([`ReloadReceiver::recv`]). Framework adapters
+//! bridge it to their transport, like the Actix WebSocket handler does.
+
+use crate::core::reload::ReloadMessage;
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// The sending side: a cheap handle shared by the reloader and the adapters.
+/// Receivers see the channel as closed once every handle is dropped.
+pub(crate) struct Broadcaster {
+  shared: Arc<Shared>,
+}
+
+struct Shared {
+  /// The live receivers' queues; dropped receivers are pruned on send.
+  subscribers: Mutex<Vec<Weak<Queue>>>,
+  /// How many messages a receiver may fall behind before they are collapsed.
+  capacity: usize,
+  senders: AtomicUsize,
+}
+
+struct Queue {
+  state: Mutex<QueueState>,
+  ready: Condvar,
+}
+
+#[derive(Default)]
+struct QueueState {
+  messages: VecDeque<ReloadMessage>,
+  /// The task awaiting `recv`, if any.
+  waker: Option<Waker>,
+  closed: bool,
+}
+
+impl Broadcaster {
+  /// A channel buffering up to `capacity` messages per receiver.
+  pub(crate) fn new(capacity: usize) -> Self {
+    Self {
+      shared: Arc::new(Shared {
+        subscribers: Mutex::new(Vec::new()),
+        capacity: capacity.max(1),
+        senders: AtomicUsize::new(1),
+      }),
+    }
+  }
+
+  /// A receiver of every message sent from now on.
+  pub(crate) fn subscribe(&self) -> ReloadReceiver {
+    let queue = Arc::new(Queue {
+      state: Mutex::new(QueueState::default()),
+      ready: Condvar::new(),
+    });
+    self.shared.subscribers.lock().push(Arc::downgrade(&queue));
+    ReloadReceiver { queue }
+  }
+
+  /// Sends `message` to every receiver, returning how many there were.
+  ///
+  /// A receiver that is `capacity` messages behind (e.g. during a burst of
+  /// changes from a `git checkout`) has its backlog replaced by a single
+  /// full reload, which brings it up to date whatever it missed.
+  pub(crate) fn send(&self, message: ReloadMessage) -> usize {
+    let mut subscribers = self.shared.subscribers.lock();
+    subscribers.retain(|queue| queue.strong_count() > 0);
+    for queue in subscribers.iter().filter_map(Weak::upgrade) {
+      let mut state = queue.state.lock();
+      if state.messages.len() >= self.shared.capacity {
+        log::debug!("A live-reload client missed {} reload messages", state.messages.len());
+        state.messages.clear();
+        state.messages.push_back(ReloadMessage::Reload);
+      } else {
+        state.messages.push_back(message.clone());
+      }
+      queue.notify(&mut state);
+    }
+    subscribers.len()
+  }
+
+  /// The number of live receivers.
+  pub(crate) fn receiver_count(&self) -> usize {
+    self.shared.subscribers.lock().iter().filter(|queue| queue.strong_count() > 0).count()
+  }
+}
+
+impl Clone for Broadcaster {
+  fn clone(&self) -> Self {
+    self.shared.senders.fetch_add(1, Ordering::Relaxed);
+    Self {
+      shared: Arc::clone(&self.shared),
+    }
+  }
+}
+
+impl Drop for Broadcaster {
+  fn drop(&mut self) {
+    if self.shared.senders.fetch_sub(1, Ordering::AcqRel) != 1 {
+      return;
+    }
+    for queue in self.shared.subscribers.lock().iter().filter_map(Weak::upgrade) {
+      let mut state = queue.state.lock();
+      state.closed = true;
+      queue.notify(&mut state);
+    }
+  }
+}
+
+impl std::fmt::Debug for Broadcaster {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Broadcaster")
+      .field("receivers", &self.receiver_count())
+      .field("capacity", &self.shared.capacity)
+      .finish()
+  }
+}
+
+impl Queue {
+  fn notify(&self, state: &mut QueueState) {
+    self.ready.notify_all();
+    if let Some(waker) = state.waker.take() {
+      waker.wake();
+    }
+  }
+}
+
+/// Receives live-reload messages, from [`TeraWeb::subscribe_reloads`].
+///
+/// Use it to drive live reload from a framework without a built-in adapter,
+/// blocking a thread per client in sync servers or awaiting
+/// [`recv`](Self::recv) in async ones.
+///
+/// [`TeraWeb::subscribe_reloads`]: crate::TeraWeb::subscribe_reloads
+#[derive(Debug)]
+pub struct ReloadReceiver {
+  queue: Arc<Queue>,
+}
+
+impl ReloadReceiver {
+  /// The next message, if one is waiting. `Disconnected` means the app was
+  /// dropped and no more messages will come.
+  pub fn try_recv(&self) -> Result<ReloadMessage, TryRecvError> {
+    let mut state = self.queue.state.lock();
+    match state.messages.pop_front() {
+      Some(message) => Ok(message),
+      None if state.closed => Err(TryRecvError::Disconnected),
+      None => Err(TryRecvError::Empty),
+    }
+  }
+
+  /// Blocks the thread until a message arrives or `timeout` passes.
+  pub fn recv_timeout(&self, timeout: Duration) -> Result<ReloadMessage, RecvTimeoutError> {
+    let deadline = Instant::now() + timeout;
+    let mut state = self.queue.state.lock();
+    loop {
+      if let Some(message) = state.messages.pop_front() {
+        return Ok(message);
+      }
+      if state.closed {
+        return Err(RecvTimeoutError::Disconnected);
+      }
+      if self.queue.ready.wait_until(&mut state, deadline).timed_out() && state.messages.is_empty() {
+        return Err(if state.closed { RecvTimeoutError::Disconnected } else { RecvTimeoutError::Timeout });
+      }
+    }
+  }
+
+  /// Waits for the next message on any executor. Resolves to `None` once
+  /// the app is dropped.
+  pub fn recv(&mut self) -> Recv<'_> {
+    Recv { receiver: self }
+  }
+}
+
+impl std::fmt::Debug for Queue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Queue").field("pending", &self.state.lock().messages.len()).finish()
+  }
+}
+
+/// The future returned by [`ReloadReceiver::recv`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct Recv<'a> {
+  receiver: &'a mut ReloadReceiver,
+}
+
+impl Future for Recv<'_> {
+  type Output = Option<ReloadMessage>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let mut state = self.receiver.queue.state.lock();
+    if let Some(message) = state.messages.pop_front() {
+      return Poll::Ready(Some(message));
+    }
+    if state.closed {
+      return Poll::Ready(None);
+    }
+    state.waker = Some(cx.waker().clone());
+    Poll::Pending
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_slow_receivers_get_one_reload() {
+    let broadcaster = Broadcaster::new(2);
+    let receiver = broadcaster.subscribe();
+    let css_only = broadcaster.subscribe();
+    assert_eq!(broadcaster.send(ReloadMessage::ReloadCss), 2);
+    assert!(matches!(css_only.try_recv(), Ok(ReloadMessage::ReloadCss)));
+    drop(css_only);
+
+    for _ in 0..5 {
+      broadcaster.send(ReloadMessage::ReloadCss);
+    }
+    assert_eq!(broadcaster.receiver_count(), 1);
+    assert!(matches!(receiver.try_recv(), Ok(ReloadMessage::Reload)));
+    assert!(matches!(receiver.try_recv(), Ok(ReloadMessage::ReloadCss)));
+    assert!(matches!(receiver.try_recv(), Err(TryRecvError::Empty)));
+
+    // A blocked thread wakes up when the last sender goes away.
+    let waiter = std::thread::spawn(move || receiver.recv_timeout(Duration::from_secs(5)));
+    std::thread::sleep(Duration::from_millis(50));
+    drop(broadcaster);
+    assert!(matches!(waiter.join().unwrap(), Err(RecvTimeoutError::Disconnected)));
+  }
+}
@@ -0,0 +1,44 @@
+use bytes::BytesMut;
+use parking_lot::Mutex;
+
+/// The initial capacity of a freshly allocated render buffer.
+const INITIAL_CAPACITY: usize = 8 * 1024;
+/// Buffers that grew beyond this are dropped instead of pooled, so one huge
+/// page doesn't pin its memory for the lifetime of the app.
+const MAX_RETAINED_CAPACITY: usize = 1024 * 1024;
+/// Upper bound on the number of idle buffers kept for reuse.
+const MAX_POOLED_BUFFERS: usize = 64;
+
+/// A pool of render output buffers.
+///
+/// A render takes a buffer, writes into it, and splits the output off as a
+/// frozen `Bytes` that becomes the response body without being copied. The
+/// buffer goes back to the pool; once the response body is dropped,
+/// `BytesMut::reserve` reclaims the original allocation, so hot pages stop
+/// allocating a fresh `String` per request.
+#[derive(Debug, Default)]
+pub(crate) struct BufferPool {
+  buffers: Mutex<Vec<BytesMut>>,
+}
+
+impl BufferPool {
+  /// Takes a buffer from the pool, or allocates a new one.
+  pub(crate) fn take(&self) -> BytesMut {
+    let mut buffer = self.buffers.lock().pop().unwrap_or_default();
+    buffer.reserve(INITIAL_CAPACITY);
+    buffer
+  }
+
+  /// Returns a buffer to the pool.
+  pub(crate) fn give(&self, mut buffer: BytesMut) {
+    if buffer.capacity() > MAX_RETAINED_CAPACITY {
+      return;
+    }
+    buffer.clear();
+
+    let mut buffers = self.buffers.lock();
+    if buffers.len() < MAX_POOLED_BUFFERS {
+      buffers.push(buffer);
+    }
+  }
+}
@@ -0,0 +1,205 @@
+use crate::error::{Result, SnapFireError};
+use std::path::Path;
+use crate::core::app::TemplateSet;
+use crate::core::front_matter::RawTemplate;
+
+/// Identifies a SnapFire template bundle.
+const MAGIC: &[u8; 8] = b"SNAPFIRE";
+/// The bundle format version, bumped on incompatible layout changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// A set of templates compiled into a single binary artifact.
+///
+/// For immutable production deploys, a bundle removes the runtime filesystem
+/// dependency entirely: build it once with [`build_templates`] (or
+/// `snapfire build-templates`), then embed it with `include_bytes!` or load it
+/// at startup and pass it to `TeraWeb::builder_from_bundle`.
+///
+/// Every template is validated when the bundle is built, so a bundle that
+/// builds is known to parse. Tera's AST itself is not serializable, so the
+/// templates are parsed once, from memory, when the app starts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateBundle {
+  templates: Vec<(String, String)>,
+}
+
+impl TemplateBundle {
+  /// Collects every template matched by `templates_glob` into a bundle.
+  ///
+  /// The templates are parsed (including their inheritance chains) as part of
+  /// this, so errors surface at build time rather than at startup.
+  pub fn from_glob(templates_glob: &str) -> Result<Self> {
+    let tera = TemplateSet::from_glob(templates_glob)?;
+
+    let mut names: Vec<&str> = tera.get_template_names().collect();
+    names.sort_unstable();
+
+    let mut templates = Vec::with_capacity(names.len());
+    for name in names {
+      if let Some(path) = &tera.get_template(name)?.path {
+        templates.push((name.to_string(), std::fs::read_to_string(path)?));
+      }
+    }
+
+    Ok(Self { templates })
+  }
+
+  /// Decodes a bundle previously produced by [`TemplateBundle::to_bytes`].
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(MAGIC.len())? != MAGIC {
+      return Err(SnapFireError::Bundle("not a SnapFire template bundle".to_string()));
+    }
+    let version = reader.u32()?;
+    if version != FORMAT_VERSION {
+      return Err(SnapFireError::Bundle(format!(
+        "unsupported bundle version {} (expected {})",
+        version, FORMAT_VERSION
+      )));
+    }
+
+    let count = reader.u32()? as usize;
+    let mut templates = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+      let name = reader.string()?;
+      let source = reader.string()?;
+      templates.push((name, source));
+    }
+
+    if !reader.bytes.is_empty() {
+      return Err(SnapFireError::Bundle("trailing data after the last template".to_string()));
+    }
+
+    Ok(Self { templates })
+  }
+
+  /// Encodes the bundle into its binary form.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let size: usize = self.templates.iter().map(|(name, source)| 8 + name.len() + source.len()).sum();
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 8 + size);
+
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(self.templates.len() as u32).to_le_bytes());
+    for (name, source) in &self.templates {
+      for field in [name, source] {
+        bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(field.as_bytes());
+      }
+    }
+
+    bytes
+  }
+
+  /// Loads a bundle from a file on disk.
+  pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+    Self::from_bytes(&std::fs::read(path)?)
+  }
+
+  /// Writes the bundle to a file on disk.
+  pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+    std::fs::write(path, self.to_bytes())?;
+    Ok(())
+  }
+
+  /// The names of the bundled templates, in sorted order.
+  pub fn template_names(&self) -> impl Iterator<Item = &str> {
+    self.templates.iter().map(|(name, _)| name.as_str())
+  }
+
+  /// Builds the template set from the bundled templates.
+  pub(crate) fn to_tera(&self) -> Result<TemplateSet> {
+    TemplateSet::from_sources(
+      self
+        .templates
+        .iter()
+        .map(|(name, source)| RawTemplate {
+          name: name.clone(),
+          path: None,
+          source: source.clone(),
+        })
+        .collect(),
+    )
+  }
+}
+
+/// Compiles the templates matched by `templates_glob` into a bundle file at `output`.
+///
+/// This is the API behind the `snapfire build-templates` command.
+pub fn build_templates(templates_glob: &str, output: impl AsRef<Path>) -> Result<TemplateBundle> {
+  let bundle = TemplateBundle::from_glob(templates_glob)?;
+  bundle.write(output)?;
+  Ok(bundle)
+}
+
+/// A cursor over the encoded bundle.
+struct Reader<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+    if self.bytes.len() < len {
+      return Err(SnapFireError::Bundle("unexpected end of bundle".to_string()));
+    }
+    let (head, rest) = self.bytes.split_at(len);
+    self.bytes = rest;
+    Ok(head)
+  }
+
+  fn u32(&mut self) -> Result<u32> {
+    let bytes = self.take(4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  fn string(&mut self) -> Result<String> {
+    let len = self.u32()? as usize;
+    String::from_utf8(self.take(len)?.to_vec())
+      .map_err(|_| SnapFireError::Bundle("template is not valid UTF-8".to_string()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  fn bundle_from_dir() -> TemplateBundle {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("base.html"), "<b>{% block body %}{% endblock %}</b>").unwrap();
+    fs::write(
+      temp_dir.path().join("page.html"),
+      "{% extends \"base.html\" %}{% block body %}{{ name }}{% endblock %}",
+    )
+    .unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    TemplateBundle::from_glob(&glob_path).unwrap()
+  }
+
+  #[test]
+  fn test_roundtrip() {
+    let bundle = bundle_from_dir();
+    assert_eq!(bundle.template_names().collect::<Vec<_>>(), ["base.html", "page.html"]);
+
+    let decoded = TemplateBundle::from_bytes(&bundle.to_bytes()).unwrap();
+    assert_eq!(decoded, bundle);
+
+    let mut context = tera::Context::new();
+    context.insert("name", "bundled");
+    let rendered = decoded.to_tera().unwrap().render("page.html", &context).unwrap();
+    assert_eq!(rendered, "<b>bundled</b>");
+  }
+
+  #[test]
+  fn test_rejects_corrupt_bundles() {
+    let bytes = bundle_from_dir().to_bytes();
+
+    assert!(matches!(TemplateBundle::from_bytes(b"NOTABUNDLE"), Err(SnapFireError::Bundle(_))));
+    assert!(matches!(
+      TemplateBundle::from_bytes(&bytes[..bytes.len() - 1]),
+      Err(SnapFireError::Bundle(_))
+    ));
+  }
+}
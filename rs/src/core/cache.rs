@@ -0,0 +1,637 @@
+//! Cached fragments and data, purged by tag.
+//!
+//! With `TeraWebBuilder::fragment_cache`, templates can cache the output of
+//! an expensive partial with the `cache` function. It renders `template`
+//! with the page's globals and the remaining arguments, and serves the same
+//! HTML until the entry expires or one of its tags is invalidated:
+//!
+//! ```jinja
+//! {{ cache(template="partials/profile.html", key=user.id, tags=["user:" ~ user.id, "posts"], user=user) }}
+//! ```
+//!
+//! When the application changes the data behind a fragment, it purges every
+//! fragment tagged with it, whichever templates cached them:
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! use snapfire::{TeraWeb, core::cache::FragmentCache};
+//! use std::time::Duration;
+//!
+//! let app = TeraWeb::builder("templates/**/*.html")
+//!   .fragment_cache(FragmentCache::new().default_ttl(Duration::from_secs(300)))
+//!   .build()?;
+//! // After user 42 edits their profile:
+//! app.invalidate_tag("user:42");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Entries are keyed by the template, the request's host, and `key`, or all
+//! the other arguments when there is no `key`. A `ttl` argument, in seconds,
+//! overrides the default lifetime. Fragments cached before a template reload
+//! are never served by the reloaded templates, and are the first dropped
+//! when the cache is full.
+//!
+//! The same cache holds semi-static data (nav counts, exchange rates) that
+//! templates pull with `cached_fetch`, from loaders declared with
+//! `TeraWebBuilder::data_loader`. The loader gets the call's other
+//! arguments, and its value is cached under `loader` and `key` with the
+//! same `ttl` and `tags` arguments:
+//!
+//! ```jinja
+//! {% set rates = cached_fetch(loader="exchange_rates", key="EUR", ttl=600, base="EUR") %}
+//! ```
+//!
+//! In dev mode (the `devel` feature), `cached_fetch` calls the loader every
+//! time, so changes to the data show up on reload.
+//!
+//! When the same fragment renders differently for different groups of
+//! users (by locale, currency, A/B bucket...), the entries must be keyed by
+//! those too, or one group is served another's HTML.
+//! `TeraWebBuilder::cache_key_dimensions` adds values computed from the
+//! render's [`RenderScope`] to every key, and names the request headers they
+//! come from, which pages then send as `Vary`:
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! use snapfire::{TeraWeb, core::cache::FragmentCache};
+//!
+//! let app = TeraWeb::builder("templates/**/*.html")
+//!   .fragment_cache(FragmentCache::new())
+//!   .cache_key_dimensions(&["Accept-Language", "Cookie"], |scope| {
+//!     vec![scope.locale().unwrap_or_default().to_string(), scope.env().currency.clone().unwrap_or_default()]
+//!   })
+//!   .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With `TeraWebBuilder::surrogate_keys(true)`, pages are sent with the
+//! tags of the fragments and data they used as a `Surrogate-Key` header, so
+//! an edge cache (Fastly, Varnish) can drop the pages using a tag when
+//! `TeraWeb::invalidate_tag` drops its fragments.
+
+use crate::core::app::TemplateSet;
+use crate::core::context::GlobalContext;
+use crate::core::scope::RenderScope;
+use crate::core::response;
+use parking_lot::Mutex;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tera::{Context, Function, Value};
+
+/// The arguments of `cache` and `cached_fetch` that aren't passed on to the
+/// fragment or loader.
+const OWN_ARGS: [&str; 5] = ["template", "loader", "key", "ttl", "tags"];
+
+thread_local! {
+  /// The tags used by the render on this thread, when they're collected.
+  static RENDER_TAGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, returning the tags the `cache` and `cached_fetch` calls it
+/// made were given, in first-use order.
+pub(crate) fn collecting_tags<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+  /// Restores the outer collection, even if the render panics.
+  struct Restore(Option<Vec<String>>);
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      let outer = self.0.take();
+      RENDER_TAGS.with(|tags| *tags.borrow_mut() = outer);
+    }
+  }
+
+  let _restore = Restore(RENDER_TAGS.with(|tags| tags.replace(Some(Vec::new()))));
+  let result = f();
+  let tags = RENDER_TAGS.with(|tags| tags.borrow_mut().take()).unwrap_or_default();
+  (result, tags)
+}
+
+/// Adds `tags` to those collected for the render on this thread, if any.
+fn record_tags(tags: &[String]) {
+  RENDER_TAGS.with(|collected| {
+    if let Some(collected) = collected.borrow_mut().as_mut() {
+      for tag in tags {
+        if !collected.contains(tag) {
+          collected.push(tag.clone());
+        }
+      }
+    }
+  });
+}
+
+/// The `Surrogate-Key` header value for `tags`: the space-separated tags,
+/// leaving out those that can't be sent as one key. `None` without any.
+pub(crate) fn surrogate_key(tags: &[String]) -> Option<String> {
+  let keys: Vec<&str> = tags
+    .iter()
+    .map(String::as_str)
+    .filter(|tag| !tag.is_empty() && !tag.contains(char::is_whitespace) && response::is_header_value(tag))
+    .collect();
+  (!keys.is_empty()).then(|| keys.join(" "))
+}
+
+/// The rendered fragments, with the tags they were cached under.
+#[derive(Debug)]
+pub struct FragmentCache {
+  max_entries: usize,
+  default_ttl: Option<Duration>,
+  state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+  entries: HashMap<String, Entry>,
+  /// The keys of the entries cached under each tag.
+  tags: HashMap<String, HashSet<String>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+  /// The `key` argument, or the arguments standing in for it.
+  key: String,
+  /// The fragment's HTML, or the loaded data.
+  value: Value,
+  tags: Vec<String>,
+  inserted: Instant,
+  expires: Option<Instant>,
+  /// The templates a fragment was rendered with, whose address is part of
+  /// the key: holding on to it keeps the address from being reused.
+  templates: Option<Weak<TemplateSet>>,
+}
+
+impl Default for FragmentCache {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl FragmentCache {
+  /// A cache of up to 1000 fragments that live until they're invalidated.
+  pub fn new() -> Self {
+    Self {
+      max_entries: 1000,
+      default_ttl: None,
+      state: Mutex::new(State::default()),
+    }
+  }
+
+  /// How many fragments are kept. Past it, expired fragments are dropped
+  /// first, then the oldest.
+  ///
+  /// # Arguments
+  ///
+  /// * `entries` - The maximum, at least `1`.
+  pub fn max_entries(mut self, entries: usize) -> Self {
+    self.max_entries = entries.max(1);
+    self
+  }
+
+  /// How long fragments without a `ttl` argument are served.
+  ///
+  /// # Arguments
+  ///
+  /// * `ttl` - The lifetime of a fragment.
+  pub fn default_ttl(mut self, ttl: Duration) -> Self {
+    self.default_ttl = Some(ttl);
+    self
+  }
+
+  /// The fragment cached under `key`, if it hasn't expired.
+  pub(crate) fn get(&self, key: &str) -> Option<Value> {
+    let mut state = self.state.lock();
+    let entry = state.entries.get(key)?;
+    if entry.expires.is_none_or(|expires| Instant::now() < expires) {
+      return Some(entry.value.clone());
+    }
+    state.remove(key);
+    None
+  }
+
+  /// Caches `value` under `id` (the `key` argument, made unique to the
+  /// template and host, or the loader) and `tags`, for `ttl` or the default
+  /// lifetime.
+  pub(crate) fn insert(&self, id: String, key: String, value: Value, tags: Vec<String>, ttl: Option<Duration>, templates: Option<&Arc<TemplateSet>>) {
+    let now = Instant::now();
+    let mut state = self.state.lock();
+    state.remove(&id);
+    if state.entries.len() >= self.max_entries {
+      state.evict(now, self.max_entries - 1);
+    }
+    for tag in &tags {
+      state.tags.entry(tag.clone()).or_default().insert(id.clone());
+    }
+    state.entries.insert(
+      id,
+      Entry {
+        key,
+        value,
+        tags,
+        inserted: now,
+        expires: ttl.or(self.default_ttl).map(|ttl| now + ttl),
+        templates: templates.map(Arc::downgrade),
+      },
+    );
+  }
+
+  /// Drops every fragment cached under `tag`, returning how many there were.
+  pub fn invalidate_tag(&self, tag: &str) -> usize {
+    let mut state = self.state.lock();
+    let Some(keys) = state.tags.remove(tag) else {
+      return 0;
+    };
+    keys.iter().filter(|key| state.remove(key)).count()
+  }
+
+  /// Drops every fragment cached with `key` as its `key` argument, whatever
+  /// its template, returning how many there were.
+  pub fn invalidate_key(&self, key: &str) -> usize {
+    let mut state = self.state.lock();
+    let ids: Vec<String> = state
+      .entries
+      .iter()
+      .filter(|(_, entry)| entry.key == key)
+      .map(|(id, _)| id.clone())
+      .collect();
+    ids.iter().filter(|id| state.remove(id)).count()
+  }
+
+  /// Drops every fragment.
+  pub fn clear(&self) {
+    *self.state.lock() = State::default();
+  }
+
+  /// How many fragments are cached, including expired ones not yet dropped.
+  pub fn len(&self) -> usize {
+    self.state.lock().entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl State {
+  /// Drops the entry under `key` and its tags, returning whether there was one.
+  fn remove(&mut self, key: &str) -> bool {
+    let Some(entry) = self.entries.remove(key) else {
+      return false;
+    };
+    for tag in &entry.tags {
+      if let Some(keys) = self.tags.get_mut(tag) {
+        keys.remove(key);
+        if keys.is_empty() {
+          self.tags.remove(tag);
+        }
+      }
+    }
+    true
+  }
+
+  /// Drops expired entries and those of replaced templates, then the
+  /// oldest, until at most `len` are left.
+  fn evict(&mut self, now: Instant, len: usize) {
+    let expired: Vec<String> = self
+      .entries
+      .iter()
+      .filter(|(_, entry)| entry.expires.is_some_and(|expires| expires <= now) || entry.templates.as_ref().is_some_and(|templates| templates.strong_count() == 0))
+      .map(|(key, _)| key.clone())
+      .collect();
+    for key in &expired {
+      self.remove(key);
+    }
+    if self.entries.len() <= len {
+      return;
+    }
+    let mut by_age: Vec<(Instant, String)> = self.entries.iter().map(|(key, entry)| (entry.inserted, key.clone())).collect();
+    by_age.sort();
+    let excess = self.entries.len() - len;
+    for (_, key) in by_age.into_iter().take(excess) {
+      self.remove(&key);
+    }
+  }
+}
+
+/// The arguments `cache` and `cached_fetch` share, and the others.
+struct CacheArgs<'a> {
+  /// The `key` argument, or the others standing in for it.
+  key: String,
+  tags: Vec<String>,
+  ttl: Option<Duration>,
+  rest: BTreeMap<&'a String, &'a Value>,
+}
+
+impl<'a> CacheArgs<'a> {
+  fn parse(args: &'a HashMap<String, Value>) -> tera::Result<Self> {
+    let tags = match args.get("tags") {
+      None => Vec::new(),
+      Some(Value::String(tag)) => vec![tag.clone()],
+      Some(Value::Array(tags)) => tags
+        .iter()
+        .map(|tag| match tag {
+          Value::String(tag) => tag.clone(),
+          other => other.to_string(),
+        })
+        .collect(),
+      Some(other) => return Err(tera::Error::msg(format!("`tags` must be a list of strings, got `{other}`"))),
+    };
+    let ttl = match args.get("ttl") {
+      None => None,
+      Some(ttl) => Some(Duration::from_secs(ttl.as_u64().ok_or_else(|| {
+        tera::Error::msg(format!("`ttl` must be a whole number of seconds, got `{ttl}`"))
+      })?)),
+    };
+    let rest: BTreeMap<&String, &Value> = args.iter().filter(|(name, _)| !OWN_ARGS.contains(&name.as_str())).collect();
+    let key = match args.get("key") {
+      Some(Value::String(key)) => key.clone(),
+      Some(key) => key.to_string(),
+      None => serde_json::to_string(&rest).unwrap_or_default(),
+    };
+    Ok(Self { key, tags, ttl, rest })
+  }
+}
+
+/// Computes the values cache keys vary by, for a render.
+pub(crate) type DimensionValues = Box<dyn Fn(&RenderScope) -> Vec<String> + Send + Sync>;
+
+/// The values cache keys vary by, and the headers they come from.
+pub(crate) struct CacheDimensions {
+  /// The `Vary` header value.
+  pub(crate) vary: String,
+  pub(crate) values: DimensionValues,
+}
+
+impl std::fmt::Debug for CacheDimensions {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("CacheDimensions").field("vary", &self.vary).finish_non_exhaustive()
+  }
+}
+
+/// The part of a key coming from the dimensions, for the render in progress.
+fn dimensions_key(dimensions: Option<&CacheDimensions>, scope: Option<&RenderScope>) -> String {
+  match (dimensions, scope) {
+    (Some(dimensions), Some(scope)) => (dimensions.values)(scope).join("\u{1f}"),
+    _ => String::new(),
+  }
+}
+
+/// `cache(template, key?, ttl?, tags?, ...)`: the fragment rendered by
+/// `template`, from the cache when it's there.
+pub(crate) struct CacheFunction {
+  pub(crate) cache: Arc<FragmentCache>,
+  pub(crate) globals: Arc<GlobalContext>,
+  pub(crate) dimensions: Option<Arc<CacheDimensions>>,
+}
+
+impl Function for CacheFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let template = args.get("template").and_then(Value::as_str).ok_or_else(|| {
+      tera::Error::msg("The `cache` function requires a `template` string, e.g. cache(template=\"partials/sidebar.html\")")
+    })?;
+    let CacheArgs { key, tags, ttl, rest } = CacheArgs::parse(args)?;
+    record_tags(&tags);
+
+    let scope = RenderScope::current()
+      .ok_or_else(|| tera::Error::msg("The `cache` function only works in pages rendered by SnapFire"))?;
+    let Some(templates) = &scope.templates else {
+      return Err(tera::Error::msg("The `cache` function only works in pages rendered by SnapFire"));
+    };
+    // Reloaded templates have their own entries.
+    let id = format!(
+      "{:p}\u{1f}{}\u{1f}{}\u{1f}{template}\u{1f}{key}",
+      Arc::as_ptr(templates),
+      scope.host().unwrap_or_default(),
+      dimensions_key(self.dimensions.as_deref(), Some(&scope))
+    );
+    if let Some(html) = self.cache.get(&id) {
+      return Ok(html);
+    }
+
+    // Fragments see the page's environment, as the page does.
+    let mut context = Context::new();
+    context.insert("env", scope.env());
+    for (name, value) in rest {
+      context.insert(name.as_str(), value);
+    }
+    let html = self
+      .globals
+      .with_merged(scope.host(), context, |context| templates.render(template, context))
+      .map_err(|e| tera::Error::chain(format!("Failed to render the cached fragment `{template}`"), e))?;
+    let html = Value::String(html);
+    self.cache.insert(id, key, html.clone(), tags, ttl, Some(templates));
+    Ok(html)
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// Loads a value for `cached_fetch` from the call's other arguments.
+pub(crate) type DataLoader = Arc<dyn Fn(&HashMap<String, Value>) -> tera::Result<Value> + Send + Sync>;
+
+/// `cached_fetch(loader, key?, ttl?, tags?, ...)`: the value `loader`
+/// returns for the other arguments, from the cache when it's there.
+pub(crate) struct CachedFetchFunction {
+  pub(crate) cache: Arc<FragmentCache>,
+  pub(crate) loaders: Arc<HashMap<String, DataLoader>>,
+  pub(crate) dimensions: Option<Arc<CacheDimensions>>,
+}
+
+impl Function for CachedFetchFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let name = args.get("loader").and_then(Value::as_str).ok_or_else(|| {
+      tera::Error::msg("The `cached_fetch` function requires a `loader` string, e.g. cached_fetch(loader=\"exchange_rates\")")
+    })?;
+    let loader = self.loaders.get(name).ok_or_else(|| {
+      let mut declared: Vec<_> = self.loaders.keys().map(String::as_str).collect();
+      declared.sort_unstable();
+      tera::Error::msg(format!("No data loader named `{name}`; declared loaders: {}", declared.join(", ")))
+    })?;
+    let CacheArgs { key, tags, ttl, rest } = CacheArgs::parse(args)?;
+    record_tags(&tags);
+    let load = || {
+      let loader_args: HashMap<String, Value> = rest.iter().map(|(name, value)| ((*name).clone(), (*value).clone())).collect();
+      loader(&loader_args).map_err(|e| tera::Error::chain(format!("The `{name}` loader failed"), e))
+    };
+
+    // In dev mode, the data is always fresh.
+    if cfg!(feature = "devel") {
+      return load();
+    }
+    let scope = RenderScope::current();
+    let id = format!(
+      "{name}\u{1f}{}\u{1f}{key}",
+      dimensions_key(self.dimensions.as_deref(), scope.as_deref())
+    );
+    if let Some(value) = self.cache.get(&id) {
+      return Ok(value);
+    }
+    let value = load()?;
+    self.cache.insert(id, key, value.clone(), tags, ttl, None);
+    Ok(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::TeraWeb;
+
+  #[test]
+  fn test_invalidating_a_tag_drops_only_its_fragments() {
+    let templates = Arc::new(TemplateSet::from_sources(Vec::new()).unwrap());
+    let cache = FragmentCache::new().max_entries(2);
+    cache.insert("a".into(), "a".into(), "A".into(), vec!["user:42".into(), "posts".into()], None, Some(&templates));
+    cache.insert("b".into(), "b".into(), "B".into(), vec!["posts".into()], None, Some(&templates));
+    cache.insert("c".into(), "c".into(), "C".into(), vec!["user:7".into()], None, Some(&templates));
+    // The oldest made room for `c`.
+    assert_eq!(cache.get("a"), None);
+    assert_eq!(cache.invalidate_tag("posts"), 1);
+    assert_eq!(cache.get("b"), None);
+    assert_eq!(cache.get("c"), Some("C".into()));
+    assert_eq!(cache.invalidate_tag("posts"), 0);
+
+    cache.insert("d".into(), "d".into(), "D".into(), Vec::new(), Some(Duration::ZERO), Some(&templates));
+    assert_eq!(cache.get("d"), None);
+
+    // Fragments of replaced templates go before older ones.
+    let reloaded = Arc::new(TemplateSet::clone(&templates));
+    cache.insert("e".into(), "e".into(), "E".into(), Vec::new(), None, Some(&reloaded));
+    drop(reloaded);
+    cache.insert("f".into(), "f".into(), "F".into(), Vec::new(), None, Some(&templates));
+    assert_eq!((cache.get("c"), cache.get("e")), (Some("C".into()), None));
+  }
+
+  #[test]
+  fn test_pages_send_the_tags_they_used_as_surrogate_keys() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+      temp_dir.path().join("page.html"),
+      "{{ cache(template=\"profile.html\", key=1, tags=[\"user:1\", \"posts\"]) }}\
+       {{ cache(template=\"profile.html\", key=2, tags=[\"posts\", \"bad tag\"]) }}",
+    )
+    .unwrap();
+    std::fs::write(temp_dir.path().join("profile.html"), "<b>profile</b>").unwrap();
+    let build = |enabled: bool| {
+      TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+        .fragment_cache(FragmentCache::new())
+        .surrogate_keys(enabled)
+        .build()
+        .unwrap()
+    };
+
+    let app = build(true);
+    // Cached fragments are reported as well as freshly rendered ones.
+    for _ in 0..2 {
+      let page = app.render("page.html", Context::new()).into_rendered();
+      assert!(page.headers.contains(&("Surrogate-Key".to_string(), "user:1 posts".to_string())));
+    }
+    let page = build(false).render("page.html", Context::new()).into_rendered();
+    assert!(page.headers.iter().all(|(name, _)| name != "Surrogate-Key"));
+    let (_, tags) = collecting_tags(|| ());
+    assert!(tags.is_empty());
+  }
+
+  #[test]
+  fn test_pages_serve_cached_fragments_until_their_tag_is_invalidated() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+      temp_dir.path().join("page.html"),
+      "<main>{{ cache(template=\"profile.html\", key=user_id, tags=[\"user:\" ~ user_id], name=name) }}</main>",
+    )
+    .unwrap();
+    std::fs::write(temp_dir.path().join("profile.html"), "<b>{{ name }}</b> on {{ site }}").unwrap();
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .add_global("site", "Example")
+      .fragment_cache(FragmentCache::new())
+      .build()
+      .unwrap();
+
+    let render = |name: &str| {
+      let mut context = Context::new();
+      context.insert("user_id", &42);
+      context.insert("name", name);
+      app.render_to_string("page.html", context).unwrap()
+    };
+    assert_eq!(render("Ada"), "<main><b>Ada</b> on Example</main>");
+    assert_eq!(render("Grace"), "<main><b>Ada</b> on Example</main>");
+    assert_eq!(app.invalidate_tag("user:7"), 0);
+    assert_eq!(app.invalidate_tag("user:42"), 1);
+    assert_eq!(render("Grace"), "<main><b>Grace</b> on Example</main>");
+  }
+
+  #[test]
+  fn test_cached_fetch_calls_the_loader_once_per_key() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+      temp_dir.path().join("page.html"),
+      "{% set rates = cached_fetch(loader=\"rates\", key=base, tags=[\"rates\"], base=base) %}{{ rates.base }} {{ rates.calls }}",
+    )
+    .unwrap();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let loader_calls = Arc::clone(&calls);
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .data_loader("rates", move |args| {
+        let calls = loader_calls.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(serde_json::json!({"base": args["base"], "calls": calls}))
+      })
+      .build()
+      .unwrap();
+    let render = |base: &str| {
+      let mut context = Context::new();
+      context.insert("base", base);
+      app.render_to_string("page.html", context).unwrap()
+    };
+
+    assert_eq!(render("EUR"), "EUR 1");
+    assert_eq!(render("USD"), "USD 2");
+    // Dev mode always loads fresh data.
+    let cached = if cfg!(feature = "devel") { "EUR 3" } else { "EUR 1" };
+    assert_eq!(render("EUR"), cached);
+    app.invalidate_tag("rates");
+    assert_eq!(render("EUR"), if cfg!(feature = "devel") { "EUR 4" } else { "EUR 3" });
+  }
+
+  #[test]
+  fn test_fragments_vary_by_the_key_dimensions() {
+    use crate::core::app::RequestParts;
+    use crate::core::env::RenderEnv;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("page.html"), "{{ cache(template=\"price.html\", key=\"price\", amount=amount) }}").unwrap();
+    std::fs::write(temp_dir.path().join("price.html"), "{{ amount }} {{ env.currency }}").unwrap();
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .fragment_cache(FragmentCache::new())
+      .cache_key_dimensions(&["Cookie"], |scope| vec![scope.env().currency.clone().unwrap_or_default()])
+      .build()
+      .unwrap();
+    let render = |currency: &str, amount: u32| {
+      let env = RenderEnv {
+        currency: Some(currency.to_string()),
+        ..RenderEnv::default()
+      };
+      let request = RequestParts {
+        env: Some(&env),
+        ..RequestParts::default()
+      };
+      let mut context = Context::new();
+      context.insert("amount", &amount);
+      let page = app.render_bytes_for_request("page.html", request, context).unwrap();
+      String::from_utf8(page.to_vec()).unwrap()
+    };
+
+    assert_eq!(render("EUR", 10), "10 EUR");
+    assert_eq!(render("USD", 12), "12 USD");
+    assert_eq!(render("EUR", 99), "10 EUR");
+    let mut context = Context::new();
+    context.insert("amount", &1);
+    let page = app.render("page.html", context).into_rendered();
+    assert_eq!(page.status, 200);
+    assert!(page.headers.contains(&("Vary".to_string(), "Cookie".to_string())));
+  }
+}
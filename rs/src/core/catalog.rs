@@ -0,0 +1,312 @@
+//! Extracting translation keys from templates into message catalogs.
+//!
+//! Templates mark translatable text with `t("key")` or `t(key="key")` calls.
+//! [`TeraWeb::translation_keys`] scans the template sources for them, and
+//! [`update_catalog`] brings a catalog in line with the templates:
+//!
+//! - gettext catalogs (`.pot`, `.po`) are rewritten with every key and its
+//!   `#:` template references, keeping existing translations and the header.
+//!   Keys no template uses any more are kept as obsolete `#~` entries.
+//! - Fluent catalogs (`.ftl`) are left as written; missing messages are
+//!   appended with the key as a placeholder value.
+//!
+//! Either way, the returned [`CatalogReport`] lists the keys that were missing
+//! and the ones no template uses, so translators know what changed.
+
+use crate::core::app::TeraWeb;
+use crate::error::{Result, SnapFireError};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Translation keys, each with the `template:line` references where it's used.
+pub type TranslationKeys = BTreeMap<String, Vec<String>>;
+
+/// How a catalog differed from the templates before it was updated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CatalogReport {
+  /// Keys used in templates that the catalog didn't have, now added.
+  pub missing: Vec<String>,
+  /// Keys in the catalog that no template uses.
+  pub unused: Vec<String>,
+}
+
+impl TeraWeb {
+  /// Scans the source of every template loaded from a file for `t("...")`
+  /// and `t(key="...")` calls.
+  pub fn translation_keys(&self) -> Result<TranslationKeys> {
+    let tera = self.tera.read().clone();
+    let mut keys = TranslationKeys::new();
+
+    let mut names: Vec<&str> = tera.get_template_names().collect();
+    names.sort_unstable();
+    for name in names {
+      let Some(path) = tera.get_template(name).ok().and_then(|template| template.path.as_ref()) else {
+        continue;
+      };
+      let source = std::fs::read_to_string(path)?;
+      for (key, line) in scan_source(&source) {
+        keys.entry(key).or_default().push(format!("{name}:{line}"));
+      }
+    }
+    Ok(keys)
+  }
+}
+
+/// Creates or updates the catalog at `path` with `keys`. The format follows
+/// the extension: `.pot` and `.po` for gettext, `.ftl` for Fluent.
+pub fn update_catalog(path: &Path, keys: &TranslationKeys) -> Result<CatalogReport> {
+  let config_error = |message: String| SnapFireError::Config {
+    path: path.display().to_string(),
+    message,
+  };
+  let existing = match std::fs::read_to_string(path) {
+    Ok(source) => source,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+    Err(e) => return Err(e.into()),
+  };
+
+  let (updated, report) = match path.extension().and_then(|e| e.to_str()) {
+    Some("pot" | "po") => update_gettext(&existing, keys),
+    Some("ftl") => update_fluent(&existing, keys).map_err(config_error)?,
+    _ => return Err(config_error("Unknown catalog format; expected .pot, .po, or .ftl".to_string())),
+  };
+  if updated != existing {
+    std::fs::write(path, updated)?;
+  }
+  Ok(report)
+}
+
+/// The keys of the `t(...)` calls in a template source, with their line numbers.
+fn scan_source(source: &str) -> Vec<(String, usize)> {
+  let mut keys = Vec::new();
+  let mut position = 0;
+
+  while let Some(offset) = source[position..].find("t(") {
+    let start = position + offset;
+    position = start + 2;
+    // Skip calls of other functions ending in `t`, like `format(...)`.
+    if source[..start]
+      .chars()
+      .next_back()
+      .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    {
+      continue;
+    }
+
+    let mut rest = source[position..].trim_start();
+    if let Some(after) = rest.strip_prefix("key") {
+      match after.trim_start().strip_prefix('=') {
+        Some(value) => rest = value.trim_start(),
+        None => continue,
+      }
+    }
+    // Tera strings have no escapes, so the key ends at the next matching quote.
+    let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\'' | '`')) else {
+      continue;
+    };
+    let Some(end) = rest[1..].find(quote) else {
+      continue;
+    };
+    let line = source[..start].matches('\n').count() + 1;
+    keys.push((rest[1..1 + end].to_string(), line));
+  }
+  keys
+}
+
+fn update_gettext(existing: &str, keys: &TranslationKeys) -> (String, CatalogReport) {
+  let (header, translations) = parse_gettext(existing);
+  let report = CatalogReport {
+    missing: keys.keys().filter(|key| !translations.contains_key(*key)).cloned().collect(),
+    unused: translations.keys().filter(|key| !keys.contains_key(*key)).cloned().collect(),
+  };
+
+  let mut output = String::from("msgid \"\"\nmsgstr \"\"\n");
+  let header = header.unwrap_or_else(|| "Content-Type: text/plain; charset=UTF-8\n".to_string());
+  for line in header.split_inclusive('\n') {
+    let _ = writeln!(output, "\"{}\"", escape_gettext(line));
+  }
+  for (key, references) in keys {
+    let translation = translations.get(key).map_or("", String::as_str);
+    let _ = write!(
+      output,
+      "\n#: {}\nmsgid \"{}\"\nmsgstr \"{}\"\n",
+      references.join(" "),
+      escape_gettext(key),
+      escape_gettext(translation)
+    );
+  }
+  for key in &report.unused {
+    let _ = write!(
+      output,
+      "\n#~ msgid \"{}\"\n#~ msgstr \"{}\"\n",
+      escape_gettext(key),
+      escape_gettext(&translations[key])
+    );
+  }
+  (output, report)
+}
+
+/// The header and the translations of a gettext catalog, including obsolete
+/// entries, so their translations come back if a template uses them again.
+fn parse_gettext(source: &str) -> (Option<String>, BTreeMap<String, String>) {
+  let mut header = None;
+  let mut translations = BTreeMap::new();
+  let mut entry: Option<(String, String)> = None;
+  let mut in_msgstr = false;
+
+  let mut finish = |entry: Option<(String, String)>| match entry {
+    Some((id, translation)) if id.is_empty() => header = Some(translation),
+    Some((id, translation)) => {
+      translations.insert(id, translation);
+    }
+    None => {}
+  };
+
+  for line in source.lines() {
+    let line = line.strip_prefix("#~").unwrap_or(line).trim();
+    if let Some(id) = line.strip_prefix("msgid ") {
+      finish(entry.take());
+      entry = Some((unquote_gettext(id), String::new()));
+      in_msgstr = false;
+    } else if let Some(translation) = line.strip_prefix("msgstr ")
+      && let Some((_, current)) = &mut entry
+    {
+      *current = unquote_gettext(translation);
+      in_msgstr = true;
+    } else if line.starts_with('"')
+      && let Some((id, translation)) = &mut entry
+    {
+      let target = if in_msgstr { translation } else { id };
+      target.push_str(&unquote_gettext(line));
+    }
+  }
+  finish(entry);
+  (header, translations)
+}
+
+fn unquote_gettext(quoted: &str) -> String {
+  let quoted = quoted.trim();
+  let inner = quoted.strip_prefix('"').and_then(|q| q.strip_suffix('"')).unwrap_or(quoted);
+  let mut output = String::with_capacity(inner.len());
+  let mut chars = inner.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      output.push(c);
+      continue;
+    }
+    match chars.next() {
+      Some('n') => output.push('\n'),
+      Some('t') => output.push('\t'),
+      Some(other) => output.push(other),
+      None => {}
+    }
+  }
+  output
+}
+
+fn escape_gettext(text: &str) -> String {
+  text
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+    .replace('\t', "\\t")
+}
+
+fn update_fluent(existing: &str, keys: &TranslationKeys) -> std::result::Result<(String, CatalogReport), String> {
+  if let Some(key) = keys.keys().find(|key| !is_fluent_identifier(key)) {
+    return Err(format!("`{key}` is not a valid Fluent message identifier"));
+  }
+
+  // Messages start unindented, with their identifier followed by `=`.
+  let messages: Vec<&str> = existing
+    .lines()
+    .filter_map(|line| line.split_once('=').map(|(identifier, _)| identifier.trim_end()))
+    .filter(|identifier| is_fluent_identifier(identifier))
+    .collect();
+  let report = CatalogReport {
+    missing: keys.keys().filter(|key| !messages.contains(&key.as_str())).cloned().collect(),
+    unused: messages
+      .iter()
+      .filter(|message| !keys.contains_key(**message))
+      .map(|message| message.to_string())
+      .collect(),
+  };
+
+  let mut output = existing.to_string();
+  if !report.missing.is_empty() {
+    if !output.is_empty() {
+      output.push_str(if output.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+    output.push_str("# Added from templates; replace the placeholders with translations.\n");
+    for key in &report.missing {
+      let _ = writeln!(output, "# {}\n{key} = {key}", keys[key].join(" "));
+    }
+  }
+  Ok((output, report))
+}
+
+fn is_fluent_identifier(identifier: &str) -> bool {
+  let mut chars = identifier.chars();
+  chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  fn keys(entries: &[(&str, &str)]) -> TranslationKeys {
+    entries
+      .iter()
+      .map(|(key, reference)| (key.to_string(), vec![reference.to_string()]))
+      .collect()
+  }
+
+  #[test]
+  fn test_scans_translation_calls() {
+    let source = "<h1>{{ t(\"welcome\") }}</h1>\n{{ t( key = 'nav-home' ) }} {{ format(\"x\") }}\n{% set label = t(key=title) %}";
+
+    assert_eq!(
+      scan_source(source),
+      vec![("welcome".to_string(), 1), ("nav-home".to_string(), 2)]
+    );
+  }
+
+  #[test]
+  fn test_updates_catalogs() {
+    let temp_dir = tempdir().unwrap();
+    let templates = keys(&[("welcome", "index.html:1"), ("goodbye", "index.html:4")]);
+
+    let po = temp_dir.path().join("fr.po");
+    fs::write(
+      &po,
+      "msgid \"\"\nmsgstr \"\"\n\"Language: fr\\n\"\n\nmsgid \"welcome\"\nmsgstr \"Bienvenue\"\n\nmsgid \"old\"\nmsgstr \"Ancien\"\n",
+    )
+    .unwrap();
+    let report = update_catalog(&po, &templates).unwrap();
+    assert_eq!(report.missing, vec!["goodbye"]);
+    assert_eq!(report.unused, vec!["old"]);
+    assert_eq!(
+      fs::read_to_string(&po).unwrap(),
+      "msgid \"\"\nmsgstr \"\"\n\"Language: fr\\n\"\n\n#: index.html:4\nmsgid \"goodbye\"\nmsgstr \"\"\n\n#: index.html:1\nmsgid \"welcome\"\nmsgstr \"Bienvenue\"\n\n#~ msgid \"old\"\n#~ msgstr \"Ancien\"\n"
+    );
+    assert!(update_catalog(&po, &templates).unwrap().missing.is_empty());
+
+    let ftl = temp_dir.path().join("fr.ftl");
+    fs::write(&ftl, "welcome = Bienvenue\nold = Ancien\n    .title = Titre\n").unwrap();
+    let report = update_catalog(&ftl, &templates).unwrap();
+    assert_eq!(report, CatalogReport {
+      missing: vec!["goodbye".to_string()],
+      unused: vec!["old".to_string()],
+    });
+    assert_eq!(
+      fs::read_to_string(&ftl).unwrap(),
+      "welcome = Bienvenue\nold = Ancien\n    .title = Titre\n\n# Added from templates; replace the placeholders with translations.\n# index.html:4\ngoodbye = goodbye\n"
+    );
+
+    assert!(update_catalog(&ftl, &keys(&[("nav.home", "index.html:1")])).is_err());
+  }
+}
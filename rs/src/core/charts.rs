@@ -0,0 +1,467 @@
+//! Small server-side charts for templates.
+//!
+//! Two functions, registered on every app, return inline SVG (marked safe),
+//! so dashboards rendered with templates can show small visualizations
+//! without a JavaScript charting library:
+//!
+//! - `sparkline(values=[...])` → a word-sized line chart, 100x20 by default,
+//!   drawn in `currentColor` so it takes the surrounding text color. It also
+//!   takes `width`, `height`, `color`, `fill` (an area color under the line),
+//!   `stroke_width`, `min`, and `max`.
+//! - `chart(spec=...)` → a bar or line chart with axes, labels, a legend for
+//!   named series, and a tooltip per value. The spec is an object, usually
+//!   from the context since templates can't write object literals; its
+//!   fields may also be given (or overridden) as arguments:
+//!
+//! ```jinja
+//! <td>{{ sparkline(values=stats.daily_signups) }}</td>
+//! {{ chart(spec=revenue_chart, height=240) }}
+//! {{ chart(type="line", labels=months, values=visits, title="Visits") }}
+//! ```
+//!
+//! A spec has a `type` (`bar`, the default, or `line`), `labels` for the x
+//! axis, and either `values` or `series`, a list of `{name, values, color}`.
+//! `width` (400), `height` (200), `min`, `max`, and `title` are optional;
+//! the y axis starts at zero unless values are negative or `min` is set.
+//! Null values are gaps. Charts with a `label` (sparklines) or `title`
+//! (charts) are announced by screen readers with it; others are hidden
+//! from them, as decoration next to the numbers they summarize.
+
+use serde_json::Map;
+use std::collections::HashMap;
+use std::fmt::Write;
+use tera::{Function, Value};
+
+/// Series colors, in order, for series without their own.
+const PALETTE: &[&str] = &[
+  "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7", "#9c755f", "#bab0ac",
+];
+
+/// The `sparkline(values=[...])` Tera function.
+pub(crate) struct SparklineFunction;
+
+impl Function for SparklineFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let values = values_argument(args.get("values"), "sparkline")?;
+    let width = number_argument(args.get("width"), "sparkline", "width")?.unwrap_or(100.0);
+    let height = number_argument(args.get("height"), "sparkline", "height")?.unwrap_or(20.0);
+    let stroke_width = number_argument(args.get("stroke_width"), "sparkline", "stroke_width")?.unwrap_or(1.5);
+    let color = string_argument(args.get("color"), "sparkline", "color")?.unwrap_or("currentColor");
+    let fill = string_argument(args.get("fill"), "sparkline", "fill")?;
+    let label = string_argument(args.get("label"), "sparkline", "label")?;
+    let (min, max) = value_range(
+      &values,
+      number_argument(args.get("min"), "sparkline", "min")?,
+      number_argument(args.get("max"), "sparkline", "max")?,
+      false,
+    );
+
+    // Keep the stroke inside the image at the extremes.
+    let inset = stroke_width / 2.0;
+    let step = if values.len() > 1 { width / (values.len() - 1) as f64 } else { 0.0 };
+    let points: Vec<Option<(f64, f64)>> = values
+      .iter()
+      .enumerate()
+      .map(|(index, value)| {
+        let x = if values.len() > 1 { index as f64 * step } else { width / 2.0 };
+        value.map(|value| (x, inset + scale(value, min, max) * (height - 2.0 * inset)))
+      })
+      .collect();
+
+    let mut svg = open_svg(width, height, "snapfire-sparkline", label);
+    if let Some(fill) = fill {
+      for run in runs(&points) {
+        let (first, last) = (run[0], run[run.len() - 1]);
+        let _ = write!(
+          svg,
+          "<path d=\"M{} {}{}L{} {}Z\" fill=\"{}\" stroke=\"none\"/>",
+          num(first.0),
+          num(height),
+          line_to(&run),
+          num(last.0),
+          num(height),
+          tera::escape_html(fill)
+        );
+      }
+    }
+    let d: String = runs(&points).iter().map(|run| line(run)).collect();
+    let _ = write!(
+      svg,
+      "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\" stroke-linecap=\"round\"/></svg>",
+      d,
+      tera::escape_html(color),
+      num(stroke_width)
+    );
+    Ok(Value::String(svg))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// The `chart(spec=...)` Tera function.
+pub(crate) struct ChartFunction;
+
+impl Function for ChartFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let mut spec = match args.get("spec") {
+      Some(Value::Object(spec)) => spec.clone(),
+      Some(_) => return Err(tera::Error::msg("The `spec` argument of `chart` must be an object")),
+      None => Map::new(),
+    };
+    for (name, value) in args {
+      if name != "spec" {
+        spec.insert(name.clone(), value.clone());
+      }
+    }
+    Ok(Value::String(ChartSpec::parse(&spec)?.render()))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// A data series in a chart.
+struct Series {
+  name: Option<String>,
+  values: Vec<Option<f64>>,
+  color: String,
+}
+
+struct ChartSpec {
+  line: bool,
+  labels: Vec<String>,
+  series: Vec<Series>,
+  width: f64,
+  height: f64,
+  min: Option<f64>,
+  max: Option<f64>,
+  title: Option<String>,
+}
+
+impl ChartSpec {
+  fn parse(spec: &Map<String, Value>) -> tera::Result<Self> {
+    let line = match string_argument(spec.get("type"), "chart", "type")?.unwrap_or("bar") {
+      "bar" => false,
+      "line" => true,
+      other => return Err(tera::Error::msg(format!("Unknown chart type `{other}`; use `bar` or `line`"))),
+    };
+    let labels = match spec.get("labels") {
+      Some(Value::Array(labels)) => labels
+        .iter()
+        .map(|label| match label {
+          Value::String(label) => label.clone(),
+          other => other.to_string(),
+        })
+        .collect(),
+      Some(_) => return Err(tera::Error::msg("The `labels` of a chart must be a list")),
+      None => Vec::new(),
+    };
+
+    let series = match (spec.get("series"), spec.get("values")) {
+      (Some(Value::Array(series)), _) => series
+        .iter()
+        .enumerate()
+        .map(|(index, series)| {
+          let series = series
+            .as_object()
+            .ok_or_else(|| tera::Error::msg("Each chart series must be an object with `values`"))?;
+          Ok(Series {
+            name: string_argument(series.get("name"), "chart", "name")?.map(str::to_string),
+            values: values_argument(series.get("values"), "chart")?,
+            color: string_argument(series.get("color"), "chart", "color")?
+              .unwrap_or(PALETTE[index % PALETTE.len()])
+              .to_string(),
+          })
+        })
+        .collect::<tera::Result<Vec<_>>>()?,
+      (Some(_), _) => return Err(tera::Error::msg("The `series` of a chart must be a list")),
+      (None, Some(values)) => vec![Series {
+        name: None,
+        values: values_argument(Some(values), "chart")?,
+        color: string_argument(spec.get("color"), "chart", "color")?
+          .unwrap_or(PALETTE[0])
+          .to_string(),
+      }],
+      (None, None) => return Err(tera::Error::msg("A chart needs `values` or `series`")),
+    };
+
+    Ok(Self {
+      line,
+      labels,
+      series,
+      width: number_argument(spec.get("width"), "chart", "width")?.unwrap_or(400.0),
+      height: number_argument(spec.get("height"), "chart", "height")?.unwrap_or(200.0),
+      min: number_argument(spec.get("min"), "chart", "min")?,
+      max: number_argument(spec.get("max"), "chart", "max")?,
+      title: string_argument(spec.get("title"), "chart", "title")?.map(str::to_string),
+    })
+  }
+
+  fn render(&self) -> String {
+    let all: Vec<Option<f64>> = self.series.iter().flat_map(|series| series.values.iter().copied()).collect();
+    let (min, max) = value_range(&all, self.min, self.max, true);
+    let count = self
+      .series
+      .iter()
+      .map(|series| series.values.len())
+      .chain([self.labels.len()])
+      .max()
+      .unwrap_or(0)
+      .max(1);
+    let has_legend = self.series.iter().any(|series| series.name.is_some());
+
+    // The plot area, leaving room for the y axis labels, legend, and x axis labels.
+    let (left, right) = (40.0, self.width - 8.0);
+    let top = if has_legend { 24.0 } else { 8.0 };
+    let bottom = self.height - if self.labels.is_empty() { 8.0 } else { 20.0 };
+    let y = |value: f64| top + scale(value, min, max) * (bottom - top);
+    let slot = (right - left) / count as f64;
+
+    let mut svg = open_svg(self.width, self.height, "snapfire-chart", self.title.as_deref());
+    svg.push_str("<g font-size=\"10\" fill=\"currentColor\">");
+    let mut ticks = vec![min, max];
+    if min < 0.0 && max > 0.0 {
+      ticks.push(0.0);
+    }
+    for tick in ticks {
+      let _ = write!(
+        svg,
+        "<line x1=\"{}\" x2=\"{}\" y1=\"{y}\" y2=\"{y}\" stroke=\"currentColor\" stroke-opacity=\"0.2\"/><text x=\"{}\" y=\"{y}\" dy=\"0.32em\" text-anchor=\"end\">{}</text>",
+        num(left),
+        num(right),
+        num(left - 4.0),
+        num(tick),
+        y = num(y(tick))
+      );
+    }
+    for (index, label) in self.labels.iter().enumerate() {
+      let _ = write!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+        num(left + slot * (index as f64 + 0.5)),
+        num(self.height - 6.0),
+        tera::escape_html(label)
+      );
+    }
+    let mut legend_x = left;
+    for series in &self.series {
+      if let Some(name) = &series.name {
+        let _ = write!(
+          svg,
+          "<rect x=\"{}\" y=\"6\" width=\"10\" height=\"10\" fill=\"{}\"/><text x=\"{}\" y=\"15\">{}</text>",
+          num(legend_x),
+          tera::escape_html(&series.color),
+          num(legend_x + 14.0),
+          tera::escape_html(name)
+        );
+        legend_x += 14.0 + 6.0 * name.chars().count() as f64 + 12.0;
+      }
+    }
+    svg.push_str("</g>");
+
+    let baseline = y(0.0_f64.clamp(min, max));
+    let bar_width = slot * 0.8 / self.series.len().max(1) as f64;
+    for (series_index, series) in self.series.iter().enumerate() {
+      let color = tera::escape_html(&series.color);
+      let points: Vec<Option<(f64, f64)>> = series
+        .values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| value.map(|value| (left + slot * (index as f64 + 0.5), y(value))))
+        .collect();
+      if self.line {
+        let d: String = runs(&points).iter().map(|run| line(run)).collect();
+        let _ = write!(svg, "<path d=\"{d}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"2\" stroke-linejoin=\"round\"/>");
+      }
+      for (index, value) in series.values.iter().enumerate() {
+        let Some(value) = value else {
+          continue;
+        };
+        let tooltip = self.tooltip(series, index, *value);
+        if self.line {
+          let _ = write!(
+            svg,
+            "<circle cx=\"{}\" cy=\"{}\" r=\"3\" fill=\"{color}\"><title>{tooltip}</title></circle>",
+            num(left + slot * (index as f64 + 0.5)),
+            num(y(*value))
+          );
+        } else {
+          let top = y(*value).min(baseline);
+          let _ = write!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{color}\"><title>{tooltip}</title></rect>",
+            num(left + slot * (index as f64 + 0.1) + bar_width * series_index as f64),
+            num(top),
+            num(bar_width),
+            num((y(*value) - baseline).abs())
+          );
+        }
+      }
+    }
+    svg.push_str("</svg>");
+    svg
+  }
+
+  /// The tooltip for a value: its label, series name, and the value.
+  fn tooltip(&self, series: &Series, index: usize, value: f64) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    if let Some(label) = self.labels.get(index) {
+      parts.push(label);
+    }
+    if let Some(name) = &series.name {
+      parts.push(name);
+    }
+    parts.push("");
+    tera::escape_html(&format!("{}{}", parts.join(": "), num(value)))
+  }
+}
+
+/// The opening `<svg>` tag, labelled for screen readers or hidden from them.
+fn open_svg(width: f64, height: f64, class: &str, label: Option<&str>) -> String {
+  let accessibility = match label {
+    Some(label) => format!("role=\"img\" aria-label=\"{}\"", tera::escape_html(label)),
+    None => "aria-hidden=\"true\"".to_string(),
+  };
+  format!(
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" class=\"{class}\" {accessibility}>",
+    w = num(width),
+    h = num(height)
+  )
+}
+
+/// The range the y axis spans: `min` and `max` if given, otherwise the
+/// values' (from zero when `from_zero`), never empty.
+fn value_range(values: &[Option<f64>], min: Option<f64>, max: Option<f64>, from_zero: bool) -> (f64, f64) {
+  let present = values.iter().flatten().copied();
+  let low = min.unwrap_or_else(|| {
+    let low = present.clone().fold(f64::INFINITY, f64::min);
+    if from_zero { low.min(0.0) } else { low }
+  });
+  let high = max.unwrap_or_else(|| present.fold(f64::NEG_INFINITY, f64::max));
+  match (low.is_finite(), high.is_finite()) {
+    (true, true) if high > low => (low, high),
+    // A flat line is centered, except all zeros from zero, which sit on the axis.
+    (true, true) if !(from_zero && low == 0.0) => (low - 1.0, low + 1.0),
+    (true, _) => (low, low + 1.0),
+    _ => (0.0, 1.0),
+  }
+}
+
+/// How far down from `max` a value is drawn, from 0 (at `max`) to 1 (at `min`).
+fn scale(value: f64, min: f64, max: f64) -> f64 {
+  ((max - value) / (max - min)).clamp(0.0, 1.0)
+}
+
+/// The runs of consecutive points between gaps.
+fn runs(points: &[Option<(f64, f64)>]) -> Vec<Vec<(f64, f64)>> {
+  points
+    .split(Option::is_none)
+    .filter(|run| !run.is_empty())
+    .map(|run| run.iter().flatten().copied().collect())
+    .collect()
+}
+
+/// A path through `run`, starting with a move to its first point.
+fn line(run: &[(f64, f64)]) -> String {
+  let (x, y) = run[0];
+  if run.len() == 1 {
+    // A lone point shows as a dot with the round line cap.
+    return format!("M{} {}h0", num(x), num(y));
+  }
+  format!("M{} {}{}", num(x), num(y), line_to(&run[1..]))
+}
+
+fn line_to(points: &[(f64, f64)]) -> String {
+  points.iter().map(|(x, y)| format!("L{} {}", num(*x), num(*y))).collect()
+}
+
+/// `value` with at most two decimals and no trailing zeros.
+fn num(value: f64) -> String {
+  let formatted = format!("{:.2}", value);
+  let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+  if trimmed == "-0" { "0".to_string() } else { trimmed.to_string() }
+}
+
+/// A list of numbers, where nulls are gaps.
+fn values_argument(value: Option<&Value>, function: &str) -> tera::Result<Vec<Option<f64>>> {
+  let error = || tera::Error::msg(format!("The `values` of `{function}` must be a list of numbers"));
+  match value {
+    Some(Value::Array(values)) => values
+      .iter()
+      .map(|value| match value {
+        Value::Null => Ok(None),
+        value => value.as_f64().map(Some).ok_or_else(error),
+      })
+      .collect(),
+    Some(_) => Err(error()),
+    None => Err(tera::Error::msg(format!("The `{function}` function requires `values`"))),
+  }
+}
+
+fn number_argument(value: Option<&Value>, function: &str, name: &str) -> tera::Result<Option<f64>> {
+  value
+    .map(|value| {
+      value
+        .as_f64()
+        .ok_or_else(|| tera::Error::msg(format!("The `{name}` argument of `{function}` must be a number")))
+    })
+    .transpose()
+}
+
+fn string_argument<'a>(value: Option<&'a Value>, function: &str, name: &str) -> tera::Result<Option<&'a str>> {
+  value
+    .map(|value| {
+      value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg(format!("The `{name}` argument of `{function}` must be a string")))
+    })
+    .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn call(function: &dyn Function, args: Value) -> tera::Result<String> {
+    let args: HashMap<String, Value> = serde_json::from_value(args).unwrap();
+    function.call(&args).map(|value| value.as_str().unwrap().to_string())
+  }
+
+  #[test]
+  fn test_sparkline() {
+    let svg = call(&SparklineFunction, json!({ "values": [1, 3, null, 2, 3], "stroke_width": 0 })).unwrap();
+    assert_eq!(
+      svg,
+      "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"20\" viewBox=\"0 0 100 20\" class=\"snapfire-sparkline\" aria-hidden=\"true\">\
+       <path d=\"M0 20L25 0M75 10L100 0\" fill=\"none\" stroke=\"currentColor\" stroke-width=\"0\" stroke-linejoin=\"round\" stroke-linecap=\"round\"/></svg>"
+    );
+    let svg = call(&SparklineFunction, json!({ "values": [5], "fill": "#eef", "label": "Signups" })).unwrap();
+    assert!(svg.contains("role=\"img\" aria-label=\"Signups\""), "{svg}");
+    assert!(svg.contains("<path d=\"M50 20L50 10L50 20Z\" fill=\"#eef\"") && svg.contains("d=\"M50 10h0\""), "{svg}");
+    assert!(call(&SparklineFunction, json!({ "values": ["x"] })).is_err());
+  }
+
+  #[test]
+  fn test_chart() {
+    let spec = json!({
+      "labels": ["Jan", "Feb"],
+      "series": [{ "name": "2024", "values": [10, -5] }, { "name": "<b>", "values": [20, 5], "color": "red" }],
+    });
+    let svg = call(&ChartFunction, json!({ "spec": spec, "width": 240, "title": "Revenue" })).unwrap();
+    assert!(svg.contains("width=\"240\" height=\"200\"") && svg.contains("aria-label=\"Revenue\""), "{svg}");
+    // The y axis spans -5 to 20, with a zero line.
+    assert!(svg.contains(">-5</text>") && svg.contains(">20</text>") && svg.contains(">0</text>"), "{svg}");
+    assert!(svg.contains("<text x=\"88\" y=\"194\" text-anchor=\"middle\">Jan</text>"), "{svg}");
+    assert!(svg.contains("fill=\"#4e79a7\"><title>Feb: 2024: -5</title></rect>"), "{svg}");
+    assert!(svg.contains("fill=\"red\"><title>Jan: &lt;b&gt;: 20</title></rect>"), "{svg}");
+
+    let svg = call(&ChartFunction, json!({ "type": "line", "values": [1, 2] })).unwrap();
+    assert!(svg.contains("<path d=\"M128 100L304 8\""), "{svg}");
+    assert!(call(&ChartFunction, json!({ "type": "pie", "values": [1] })).is_err());
+    assert!(call(&ChartFunction, json!({ "labels": ["a"] })).is_err());
+  }
+}
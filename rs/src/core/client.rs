@@ -0,0 +1,88 @@
+//! The live-reload client script, shared by the framework integrations.
+
+#[cfg(any(feature = "warp", feature = "hyper"))]
+use crate::core::app::TeraWeb;
+use crate::core::response::{RenderedResponse, versioned};
+use bytes::Bytes;
+
+/// Where the live-reload client is served, for external injection and for
+/// pages served by other dev servers to include manually.
+pub const CLIENT_SCRIPT_PATH: &str = "/_snapfire/client.js";
+
+/// Where the script is injected into a page: before its closing body tag.
+pub(crate) const BODY_TAG: &[u8] = b"</body>";
+
+const SCRIPT_SOURCE: &str = include_str!("injected.js");
+const WS_PATH_PLACEHOLDER: &str = "__SNAPFIRE_WS_PATH__";
+
+/// The live-reload client, configured for a WebSocket path.
+pub(crate) struct ClientScript {
+  pub(crate) body: Bytes,
+  /// A hash of the body, used as the ETag and in the versioned URL.
+  pub(crate) version: String,
+  /// Where the script itself is served.
+  path: String,
+}
+
+impl ClientScript {
+  pub(crate) fn new(ws_path: &str, script_path: &str) -> Self {
+    let ws_path = serde_json::to_string(ws_path).expect("a string always serializes");
+    let body = SCRIPT_SOURCE.replace(WS_PATH_PLACEHOLDER, &ws_path);
+    let version = format!("{:016x}", fnv1a(body.as_bytes()));
+    Self {
+      body: Bytes::from(body),
+      version,
+      path: script_path.to_string(),
+    }
+  }
+
+  /// The versioned URL of the script, safe to cache forever.
+  pub(crate) fn url(&self) -> String {
+    format!("{}?v={}", self.path, self.version)
+  }
+
+  /// Serves the script for a request with `query` and `if_none_match`.
+  /// Requests for the current versioned URL are cacheable forever; the
+  /// unversioned URL is revalidated against the ETag.
+  pub(crate) fn response(&self, query: &str, if_none_match: Option<&str>) -> RenderedResponse {
+    versioned(&self.version, "text/javascript; charset=utf-8", self.body.clone(), query, if_none_match)
+  }
+}
+
+/// `body` with the live-reload script added before `</body>`, unless the
+/// builder turned `auto_inject_script` off.
+#[cfg(any(feature = "warp", feature = "hyper"))]
+pub(crate) fn with_reload_script(app: &TeraWeb, body: Bytes) -> Bytes {
+  let options = &app.reloader.options;
+  if !options.auto_inject_script {
+    return body;
+  }
+  let script = ClientScript::new(&options.ws_path, CLIENT_SCRIPT_PATH);
+  let snippet = if options.external_script {
+    format!("<script src=\"{}\" data-snapfire-reload=\"true\"></script>", script.url())
+  } else {
+    format!(
+      "<script data-snapfire-reload=\"true\">{}</script>",
+      String::from_utf8_lossy(&script.body)
+    )
+  };
+  let at = find_case_insensitive(&body, BODY_TAG).unwrap_or(body.len());
+  let mut page = Vec::with_capacity(body.len() + snippet.len());
+  page.extend_from_slice(&body[..at]);
+  page.extend_from_slice(snippet.as_bytes());
+  page.extend_from_slice(&body[at..]);
+  page.into()
+}
+
+pub(crate) fn find_case_insensitive(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack
+    .windows(needle.len())
+    .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// 64-bit FNV-1a, stable across builds unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+    (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+  })
+}
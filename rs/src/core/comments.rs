@@ -0,0 +1,108 @@
+//! Stripping HTML comments from rendered pages.
+//!
+//! Notes left in templates as `<!-- ... -->` end up in every response, where
+//! they cost bytes and can leak internal details. With
+//! `TeraWebBuilder::strip_comments(true)`, they are removed from every page,
+//! except:
+//!
+//! - conditional comments, `<!--[if IE]> ... <![endif]-->` and the
+//!   `<!--[if !IE]><!-->` / `<!--<![endif]-->` pairs around content for
+//!   other browsers;
+//! - comments marked to be kept with a `!`, such as `<!--! License: MIT -->`,
+//!   which are sent as they are.
+//!
+//! The contents of `script`, `style`, `textarea`, and `title` elements are
+//! not HTML, so `<!--` in them is left alone, as is a `<!--` that is never
+//! closed. Tera's own `{# ... #}` comments never reach the output and need
+//! no stripping.
+
+/// Elements whose contents are copied without looking for comments.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title"];
+
+/// Removes the comments from `html` that aren't kept; see the module docs.
+pub(crate) fn strip_comments(html: &mut String) {
+  if !html.contains("<!--") {
+    return;
+  }
+
+  let mut output = String::with_capacity(html.len());
+  let mut rest = html.as_str();
+  while let Some(start) = rest.find('<') {
+    let (text, from) = rest.split_at(start);
+    output.push_str(text);
+    let end = if let Some(body) = from.strip_prefix("<!--") {
+      // An unterminated comment isn't one; it's sent as it is.
+      let Some(end) = body.find("-->").map(|end| end + 7) else {
+        output.push_str(from);
+        rest = "";
+        break;
+      };
+      if is_kept(body) {
+        output.push_str(&from[..end]);
+      }
+      end
+    } else {
+      let end = raw_text_end(from).unwrap_or(1);
+      output.push_str(&from[..end]);
+      end
+    };
+    rest = &from[end..];
+  }
+  output.push_str(rest);
+  *html = output;
+}
+
+/// Whether a comment, given what follows its `<!--`, is kept.
+fn is_kept(body: &str) -> bool {
+  body.starts_with('[') || body.starts_with("<![") || body.starts_with('!')
+}
+
+/// The length of the raw text element `from` starts with, through its
+/// closing tag, or `None` if it starts with another tag.
+fn raw_text_end(from: &str) -> Option<usize> {
+  let name_len = from[1..].bytes().take_while(u8::is_ascii_alphanumeric).count();
+  let name = &from[1..1 + name_len];
+  if !RAW_TEXT_ELEMENTS.iter().any(|element| element.eq_ignore_ascii_case(name)) {
+    return None;
+  }
+  // Search for the closing tag in place: lowercasing the rest of the page
+  // for every element would make stripping quadratic.
+  let closing = from
+    .match_indices("</")
+    .map(|(at, _)| at)
+    .find(|at| from.as_bytes()[at + 2..].get(..name_len).is_some_and(|tag| tag.eq_ignore_ascii_case(name.as_bytes())));
+  let Some(closing) = closing else {
+    return Some(from.len());
+  };
+  Some(from[closing..].find('>').map_or(from.len(), |close| closing + close + 1))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn strip(html: &str) -> String {
+    let mut html = html.to_string();
+    strip_comments(&mut html);
+    html
+  }
+
+  #[test]
+  fn test_strips_comments_but_keeps_conditional_and_marked_ones() {
+    assert_eq!(
+      strip("<p>a<!-- TODO: ask legal --></p><!--! MIT --><!--[if IE]><p>old</p><![endif]-->"),
+      "<p>a</p><!--! MIT --><!--[if IE]><p>old</p><![endif]-->"
+    );
+    assert_eq!(
+      strip("<!--[if !IE]><!--><p>new</p><!--<![endif]--><!--\nmulti\nline\n-->x"),
+      "<!--[if !IE]><!--><p>new</p><!--<![endif]-->x"
+    );
+    assert_eq!(strip("<p>a</p><!-- unterminated<p>b</p>"), "<p>a</p><!-- unterminated<p>b</p>");
+  }
+
+  #[test]
+  fn test_leaves_raw_text_elements_alone() {
+    let html = "<script>if (a <!-- b) {}</script><STYLE>/* <!-- */</Style><textarea><!-- typed --></textarea>";
+    assert_eq!(strip(&format!("{html}<!-- note -->")), html);
+  }
+}
@@ -0,0 +1,369 @@
+//! Previewing a template release before deploying it.
+//!
+//! [`TeraWeb::compare_sets`] renders a list of routes twice, with the active
+//! templates and with a candidate set loaded from another directory, and
+//! reports for each route a line diff of the HTML and how its render time
+//! changed. Both sets share the app's globals, context hooks, filters, and
+//! functions, so only the templates differ:
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! use snapfire::{TeraWeb, core::export::ExportPage};
+//!
+//! let app = TeraWeb::builder("templates/**/*.html").build()?;
+//! let routes = vec![ExportPage::new("/", "index.html"), ExportPage::new("/about", "about.html")];
+//! let comparison = app.compare_sets("release/templates/**/*.html", &routes)?;
+//! print!("{comparison}");
+//! if comparison.failures().next().is_some() {
+//!   std::process::exit(1);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each route is rendered a few times per set and the fastest render is kept,
+//! so one slow run doesn't pass for a regression. Neither set's renders reach
+//! the error reporter or the circuit breaker.
+
+use crate::core::app::{RequestParts, TeraWeb};
+use crate::core::export::{ExportPage, load_data};
+use crate::error::{Result, error_chain};
+use parking_lot::RwLock;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tera::Context;
+
+/// How many times each route is rendered with each set.
+const RUNS: u32 = 5;
+
+/// The unchanged lines shown around each change in a diff.
+const CONTEXT_LINES: usize = 2;
+
+/// How one route renders with the active and the candidate templates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteComparison {
+  pub url: String,
+  pub template: String,
+  /// A unified diff from the active set's HTML to the candidate's; empty
+  /// when both are the same.
+  pub diff: String,
+  /// The fastest render with the active set.
+  pub current_time: Duration,
+  /// The fastest render with the candidate set.
+  pub candidate_time: Duration,
+  /// Why the render with the active set failed, if it did.
+  pub current_error: Option<String>,
+  /// Why the render with the candidate set failed, if it did.
+  pub candidate_error: Option<String>,
+}
+
+impl RouteComparison {
+  /// Whether the candidate renders the route differently, or fails
+  /// differently.
+  pub fn changed(&self) -> bool {
+    !self.diff.is_empty() || self.current_error != self.candidate_error
+  }
+
+  /// How much slower the candidate renders the route, in milliseconds;
+  /// negative when it's faster.
+  pub fn delta_ms(&self) -> f64 {
+    (self.candidate_time.as_secs_f64() - self.current_time.as_secs_f64()) * 1000.0
+  }
+}
+
+/// The routes compared by [`TeraWeb::compare_sets`], in the order given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetComparison {
+  /// The glob the candidate templates were loaded from.
+  pub candidate: String,
+  pub routes: Vec<RouteComparison>,
+}
+
+impl SetComparison {
+  /// The routes the candidate renders differently.
+  pub fn changed(&self) -> impl Iterator<Item = &RouteComparison> {
+    self.routes.iter().filter(|route| route.changed())
+  }
+
+  /// The routes the candidate fails to render.
+  pub fn failures(&self) -> impl Iterator<Item = &RouteComparison> {
+    self.routes.iter().filter(|route| route.candidate_error.is_some())
+  }
+}
+
+impl std::fmt::Display for SetComparison {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+      f,
+      "Compared {} routes with `{}`: {} changed, {} failing",
+      self.routes.len(),
+      self.candidate,
+      self.changed().count(),
+      self.failures().count()
+    )?;
+    for route in &self.routes {
+      let current_ms = route.current_time.as_secs_f64() * 1000.0;
+      let status = match (&route.candidate_error, route.changed()) {
+        (Some(_), _) => "fails",
+        (None, true) => "changed",
+        (None, false) => "same",
+      };
+      write!(
+        f,
+        "\n{} ({}): {status}, {current_ms:.2}ms -> {:.2}ms",
+        route.url,
+        route.template,
+        current_ms + route.delta_ms()
+      )?;
+      if current_ms > 0.0 {
+        write!(f, " ({:+.0}%)", route.delta_ms() / current_ms * 100.0)?;
+      }
+      writeln!(f)?;
+      if let Some(error) = &route.candidate_error {
+        writeln!(f, "  {error}")?;
+      }
+      f.write_str(&route.diff)?;
+    }
+    Ok(())
+  }
+}
+
+impl TeraWeb {
+  /// Renders `routes` with the active templates and with the candidate
+  /// templates matched by `candidate`, and compares the results.
+  ///
+  /// # Arguments
+  ///
+  /// * `candidate` - A glob matching the candidate templates, e.g.
+  ///   `release/templates/**/*.html`.
+  /// * `routes` - The pages to render, as for a static export.
+  ///
+  /// # Errors
+  ///
+  /// If the candidate templates can't be loaded, or a route's data file
+  /// can't be read. Failed renders are reported in the comparison instead.
+  pub fn compare_sets(&self, candidate: &str, routes: &[ExportPage]) -> Result<SetComparison> {
+    let current = TeraWeb {
+      error_reporter: None,
+      circuit_breaker: None,
+      ..self.clone()
+    };
+    let candidate_set = self.tera.read().with_templates(candidate)?;
+    let candidate_app = TeraWeb {
+      tera: Arc::new(RwLock::new(Arc::new(candidate_set))),
+      ..current.clone()
+    };
+
+    let mut comparison = SetComparison {
+      candidate: candidate.to_string(),
+      routes: Vec::with_capacity(routes.len()),
+    };
+    for page in routes {
+      let mut context = page.context.clone();
+      for data in &page.data_files {
+        context.extend(load_data(data)?);
+      }
+      let (current_html, current_time) = fastest_render(&current, page, &context);
+      let (candidate_html, candidate_time) = fastest_render(&candidate_app, page, &context);
+      comparison.routes.push(RouteComparison {
+        url: page.url.clone(),
+        template: page.template.clone(),
+        diff: line_diff(
+          current_html.as_deref().unwrap_or_default(),
+          candidate_html.as_deref().unwrap_or_default(),
+        ),
+        current_time,
+        candidate_time,
+        current_error: current_html.err(),
+        candidate_error: candidate_html.err(),
+      });
+    }
+    Ok(comparison)
+  }
+}
+
+/// The output of rendering `page` with `app`, and the fastest of `RUNS`
+/// renders. A failing render isn't repeated.
+fn fastest_render(app: &TeraWeb, page: &ExportPage, context: &Context) -> (std::result::Result<String, String>, Duration) {
+  let mut fastest = Duration::MAX;
+  let mut output = Err(String::new());
+  for _ in 0..RUNS {
+    let request = RequestParts {
+      path: Some(&page.url),
+      ..RequestParts::default()
+    };
+    let start = Instant::now();
+    let result = app.render_for_request(&page.template, request, context.clone());
+    fastest = fastest.min(start.elapsed());
+    output = result.map_err(|e| error_chain(&e));
+    if output.is_err() {
+      break;
+    }
+  }
+  (output, fastest)
+}
+
+/// A line of a diff.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Line<'a> {
+  Same(&'a str),
+  Removed(&'a str),
+  Added(&'a str),
+}
+
+/// A unified diff from `old` to `new`, with `CONTEXT_LINES` of context
+/// around each change; empty when they are equal.
+pub(crate) fn line_diff(old: &str, new: &str) -> String {
+  if old == new {
+    return String::new();
+  }
+  let lines = diff_lines(&old.lines().collect::<Vec<_>>(), &new.lines().collect::<Vec<_>>());
+
+  // The lines of each side before each diff line, for the hunk headers.
+  let mut positions = Vec::with_capacity(lines.len() + 1);
+  let (mut old_line, mut new_line) = (0, 0);
+  for line in &lines {
+    positions.push((old_line, new_line));
+    match line {
+      Line::Same(_) => (old_line, new_line) = (old_line + 1, new_line + 1),
+      Line::Removed(_) => old_line += 1,
+      Line::Added(_) => new_line += 1,
+    }
+  }
+  positions.push((old_line, new_line));
+
+  let changes: Vec<usize> = (0..lines.len()).filter(|&i| !matches!(lines[i], Line::Same(_))).collect();
+  let mut diff = String::new();
+  let mut index = 0;
+  while index < changes.len() {
+    // Merge the changes whose context would touch.
+    let first = changes[index];
+    let mut last = first;
+    while index + 1 < changes.len() && changes[index + 1] - last <= 2 * CONTEXT_LINES + 1 {
+      index += 1;
+      last = changes[index];
+    }
+    index += 1;
+
+    let start = first.saturating_sub(CONTEXT_LINES);
+    let end = (last + 1 + CONTEXT_LINES).min(lines.len());
+    let ((old_start, new_start), (old_end, new_end)) = (positions[start], positions[end]);
+    let _ = writeln!(
+      diff,
+      "@@ -{},{} +{},{} @@",
+      old_start + 1,
+      old_end - old_start,
+      new_start + 1,
+      new_end - new_start
+    );
+    for line in &lines[start..end] {
+      let _ = match line {
+        Line::Same(text) => writeln!(diff, " {text}"),
+        Line::Removed(text) => writeln!(diff, "-{text}"),
+        Line::Added(text) => writeln!(diff, "+{text}"),
+      };
+    }
+  }
+  diff
+}
+
+/// The lines of `old` and `new`, aligned on their longest common
+/// subsequence.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+  // Only the middle between the common prefix and suffix needs the table.
+  let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+  let suffix = old[prefix..]
+    .iter()
+    .rev()
+    .zip(new[prefix..].iter().rev())
+    .take_while(|(a, b)| a == b)
+    .count();
+  let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+  // `common[i][j]`: the longest common subsequence of `old_middle[i..]` and
+  // `new_middle[j..]`.
+  let width = new_middle.len() + 1;
+  let mut common = vec![0u32; (old_middle.len() + 1) * width];
+  for i in (0..old_middle.len()).rev() {
+    for j in (0..new_middle.len()).rev() {
+      common[i * width + j] = if old_middle[i] == new_middle[j] {
+        common[(i + 1) * width + j + 1] + 1
+      } else {
+        common[(i + 1) * width + j].max(common[i * width + j + 1])
+      };
+    }
+  }
+
+  let mut lines: Vec<_> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+  let (mut i, mut j) = (0, 0);
+  while i < old_middle.len() || j < new_middle.len() {
+    if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+      lines.push(Line::Same(old_middle[i]));
+      (i, j) = (i + 1, j + 1);
+    } else if i < old_middle.len() && (j == new_middle.len() || common[(i + 1) * width + j] >= common[i * width + j + 1]) {
+      lines.push(Line::Removed(old_middle[i]));
+      i += 1;
+    } else {
+      lines.push(Line::Added(new_middle[j]));
+      j += 1;
+    }
+  }
+  lines.extend(old[old.len() - suffix..].iter().map(|line| Line::Same(line)));
+  lines
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_line_diff_shows_changes_with_context() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj";
+    let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk";
+    assert_eq!(line_diff(old, old), "");
+    assert_eq!(
+      line_diff(old, new),
+      "@@ -1,4 +1,4 @@\n a\n-b\n+B\n c\n d\n@@ -9,2 +9,3 @@\n i\n j\n+k\n"
+    );
+  }
+
+  #[test]
+  fn test_compares_the_active_and_candidate_sets() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (current_dir, candidate_dir) = (temp_dir.path().join("current"), temp_dir.path().join("candidate"));
+    for dir in [&current_dir, &candidate_dir] {
+      std::fs::create_dir(dir).unwrap();
+      std::fs::write(dir.join("about.html"), "<h1>About</h1>").unwrap();
+    }
+    std::fs::write(current_dir.join("index.html"), "<h1>{{ title }}</h1>\n<p>Hi</p>").unwrap();
+    std::fs::write(candidate_dir.join("index.html"), "<h1>{{ title | upper }}</h1>\n<p>Hi</p>").unwrap();
+    std::fs::write(current_dir.join("team.html"), "<ul></ul>").unwrap();
+    std::fs::write(candidate_dir.join("team.html"), "{{ missing }}").unwrap();
+
+    let app = TeraWeb::builder(current_dir.join("*.html").to_str().unwrap())
+      .add_global("title", "Welcome")
+      .build()
+      .unwrap();
+    let routes = vec![
+      ExportPage::new("/", "index.html"),
+      ExportPage::new("/about", "about.html"),
+      ExportPage::new("/team", "team.html"),
+    ];
+    let comparison = app.compare_sets(candidate_dir.join("*.html").to_str().unwrap(), &routes).unwrap();
+
+    let [index, about, team] = &comparison.routes[..] else {
+      panic!("one comparison per route");
+    };
+    assert_eq!(index.diff, "@@ -1,2 +1,2 @@\n-<h1>Welcome</h1>\n+<h1>WELCOME</h1>\n <p>Hi</p>\n");
+    assert!(!about.changed());
+    assert!(team.candidate_error.as_deref().unwrap().contains("missing"));
+    assert_eq!(comparison.changed().count(), 2);
+    assert!(comparison.to_string().starts_with("Compared 3 routes with `"));
+    assert!(comparison.to_string().contains("\n/team (team.html): fails, "));
+
+    // The active set is left as it was.
+    let html = app.render_for_request("index.html", RequestParts::default(), Context::new()).unwrap();
+    assert_eq!(html, "<h1>Welcome</h1>\n<p>Hi</p>");
+  }
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use tera::Value;
+
+/// The visitor's cookie-consent choices.
+///
+/// When the consent middleware is active, this is exposed to every render
+/// context as `consent`, so templates can write `{% if consent.analytics %}`
+/// or use the `consented` tester: `{% if consent is consented("marketing") %}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsentState {
+  /// Whether the visitor allowed analytics/measurement scripts.
+  pub analytics: bool,
+  /// Whether the visitor allowed marketing/advertising scripts.
+  pub marketing: bool,
+}
+
+impl ConsentState {
+  /// A state with every category granted.
+  pub fn all() -> Self {
+    Self {
+      analytics: true,
+      marketing: true,
+    }
+  }
+
+  /// Encodes the state as a comma-separated list of granted categories.
+  ///
+  /// This is the format stored (signed) in the consent cookie.
+  pub fn encode(&self) -> String {
+    let mut granted = Vec::new();
+    if self.analytics {
+      granted.push("analytics");
+    }
+    if self.marketing {
+      granted.push("marketing");
+    }
+    granted.join(",")
+  }
+
+  /// Decodes a state previously produced by [`ConsentState::encode`].
+  ///
+  /// Unknown categories are ignored so the cookie format can grow.
+  pub fn decode(raw: &str) -> Self {
+    let mut state = Self::default();
+    for category in raw.split(',').map(str::trim) {
+      match category {
+        "analytics" => state.analytics = true,
+        "marketing" => state.marketing = true,
+        _ => (),
+      }
+    }
+    state
+  }
+}
+
+/// The `consented` Tera tester: `{% if consent is consented("analytics") %}`.
+///
+/// Anything other than an explicit `true` for the category counts as "no consent".
+pub(crate) fn consented_tester(value: Option<&Value>, args: &[Value]) -> tera::Result<bool> {
+  let category = match args.first().and_then(Value::as_str) {
+    Some(category) => category,
+    None => return Err(tera::Error::msg("The `consented` test requires a category name argument")),
+  };
+
+  Ok(
+    value
+      .and_then(|consent| consent.get(category))
+      .and_then(Value::as_bool)
+      .unwrap_or(false),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_encode_decode_roundtrip() {
+    let state = ConsentState {
+      analytics: true,
+      marketing: false,
+    };
+    assert_eq!(state.encode(), "analytics");
+    assert_eq!(ConsentState::decode(&state.encode()), state);
+    assert_eq!(ConsentState::decode("marketing, unknown,analytics"), ConsentState::all());
+  }
+
+  #[test]
+  fn test_consented_tester() {
+    let consent = tera::to_value(ConsentState::all()).unwrap();
+    let args = [Value::from("analytics")];
+    assert!(consented_tester(Some(&consent), &args).unwrap());
+    assert!(!consented_tester(None, &args).unwrap());
+    assert!(consented_tester(Some(&consent), &[]).is_err());
+  }
+}
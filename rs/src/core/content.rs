@@ -0,0 +1,480 @@
+//! Collections of Markdown pages with front matter, loaded from a directory.
+//!
+//! With `TeraWebBuilder::content_dir("content")`, every subdirectory of
+//! `content` is a collection of the `.md` files under it:
+//!
+//! ```text
+//! content/
+//!   blog/
+//!     hello-world.md
+//!     2024/launch.md
+//! ```
+//!
+//! Templates list a collection with the `content` function, and use Tera's
+//! filters to order and filter it:
+//!
+//! ```jinja
+//! {% for post in content(name="blog") | sort(attribute="date") | reverse %}
+//!   <a href="/blog/{{ post.slug }}">{{ post.title }}</a>
+//! {% endfor %}
+//! ```
+//!
+//! Each entry has the values of its front matter (the same YAML or TOML
+//! block templates use), plus:
+//!
+//! - `slug`: the path within the collection without `.md`, e.g. `2024/launch`
+//! - `content`: the body rendered through the `markdown` filter's pipeline,
+//!   sanitized with the `default` policy, to output with `{{ post.content | safe }}`
+//! - `raw_content`: the Markdown body as written
+//!
+//! Files and directories starting with `_` or `.` are skipped. In dev mode the
+//! directory is watched, and a change reloads the collections and the page.
+//!
+//! # Drafts and scheduled entries
+//!
+//! Entries with `draft: true` are left out, and entries with a `publish_at`
+//! in the future (an RFC 3339 time like `2024-05-01T09:00:00Z`, or a date,
+//! taken as midnight UTC) are left out until then: the collections and
+//! taxonomies are rebuilt at the first use after that moment, without a
+//! restart. In preview mode, which `TeraWebBuilder::content_preview` sets and
+//! which is on in dev builds, both are shown, and templates can mark them by
+//! their `draft` and `publish_at` values.
+//!
+//! # Taxonomies
+//!
+//! A taxonomy declared with `TeraWebBuilder::taxonomy("tags")` groups the
+//! entries of every collection by the `tags` in their front matter, a string
+//! or a list of strings. `taxonomy(name="tags")` lists its terms, sorted by
+//! slug, each with its `name`, `slug`, `count`, and `pages`:
+//!
+//! ```jinja
+//! {% for tag in taxonomy(name="tags") %}
+//!   <a href="/tags/{{ tag.slug }}">{{ tag.name }} ({{ tag.count }})</a>
+//! {% endfor %}
+//! ```
+//!
+//! A tag page gets its term with `taxonomy(name="tags", term=slug)`, and its
+//! handler can answer 404 for unknown terms with `TeraWeb::taxonomy_term`.
+//! Terms differing only in case or punctuation, like `Rust` and `rust`, share
+//! a slug and so are one term, named as first seen.
+
+use crate::core::front_matter;
+use crate::core::sanitize::{SanitizePolicy, markdown_to_html};
+use crate::error::{Result, SnapFireError};
+use chrono::{DateTime, NaiveDate, Utc};
+use parking_lot::{RwLock, RwLockUpgradableReadGuard};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tera::Function;
+
+/// Entries by collection name, each collection sorted by path.
+type Collections = BTreeMap<String, Arc<Vec<Value>>>;
+
+/// A loaded entry, with the time it is published at if it is scheduled.
+type Scheduled = (Option<DateTime<Utc>>, Value);
+
+/// The entries as loaded, and what is visible of them now.
+#[derive(Debug, Default)]
+struct State {
+  /// Every loaded entry by collection, sorted by path; replaced on reload.
+  loaded: Arc<BTreeMap<String, Vec<Scheduled>>>,
+  view: Arc<View>,
+}
+
+/// The published entries, and the taxonomies over them.
+#[derive(Debug, Default)]
+struct View {
+  collections: Collections,
+  /// The terms of each declared taxonomy, by taxonomy name.
+  taxonomies: BTreeMap<String, Arc<Vec<Value>>>,
+  /// When the next scheduled entry is published, making this view stale.
+  expires: Option<DateTime<Utc>>,
+}
+
+/// The content collections behind the `content` function.
+#[derive(Debug)]
+pub(crate) struct Content {
+  dir: PathBuf,
+  /// The policy bodies are sanitized with.
+  policy: SanitizePolicy,
+  /// The front matter keys to group entries by.
+  taxonomies: Vec<String>,
+  /// Whether drafts and scheduled entries are shown too.
+  preview: bool,
+  state: RwLock<State>,
+}
+
+impl Content {
+  /// Loads the collections under `dir`, and groups them by `taxonomies`.
+  pub(crate) fn new(dir: PathBuf, policy: SanitizePolicy, taxonomies: Vec<String>, preview: bool) -> Result<Self> {
+    let content = Self {
+      dir,
+      policy,
+      taxonomies,
+      preview,
+      state: RwLock::new(State::default()),
+    };
+    content.reload()?;
+    Ok(content)
+  }
+
+  /// The content directory.
+  #[cfg_attr(not(feature = "devel"), allow(dead_code))]
+  pub(crate) fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  /// Re-reads every collection. On failure, the previous ones stay active.
+  pub(crate) fn reload(&self) -> Result<()> {
+    let mut loaded = BTreeMap::new();
+    for entry in std::fs::read_dir(&self.dir).map_err(|e| self.error(&self.dir, e.to_string()))? {
+      let path = entry?.path();
+      if path.is_dir() && !is_hidden(&path) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let mut files = Vec::new();
+        markdown_files(&path, &mut files)?;
+        files.sort();
+        let mut entries = Vec::new();
+        for file in &files {
+          let entry = self.load_entry(&path, file)?;
+          if self.preview || entry["draft"] != true {
+            entries.push((publish_at(&entry).map_err(|message| self.error(file, message))?, entry));
+          }
+        }
+        loaded.insert(name, entries);
+      }
+    }
+
+    let loaded = Arc::new(loaded);
+    let view = Arc::new(self.view_at(&loaded, Utc::now()));
+    *self.state.write() = State { loaded, view };
+    Ok(())
+  }
+
+  /// The entries of the collection called `name`, if there is one.
+  pub(crate) fn collection(&self, name: &str) -> Option<Arc<Vec<Value>>> {
+    self.view().collections.get(name).cloned()
+  }
+
+  /// The terms of the taxonomy called `name`, if it is declared.
+  pub(crate) fn taxonomy(&self, name: &str) -> Option<Arc<Vec<Value>>> {
+    self.view().taxonomies.get(name).cloned()
+  }
+
+  /// What is visible now, rebuilt once a scheduled entry's time has come.
+  fn view(&self) -> Arc<View> {
+    let now = Utc::now();
+    let state = self.state.upgradable_read();
+    if state.view.expires.is_none_or(|expires| expires > now) {
+      return Arc::clone(&state.view);
+    }
+    let mut state = RwLockUpgradableReadGuard::upgrade(state);
+    state.view = Arc::new(self.view_at(&state.loaded, now));
+    Arc::clone(&state.view)
+  }
+
+  /// The entries published at `now`, or all of them when previewing.
+  fn view_at(&self, loaded: &BTreeMap<String, Vec<Scheduled>>, now: DateTime<Utc>) -> View {
+    let mut expires: Option<DateTime<Utc>> = None;
+    let collections: Collections = loaded
+      .iter()
+      .map(|(name, entries)| {
+        let published = entries
+          .iter()
+          .filter(|(publish_at, _)| match publish_at {
+            Some(publish_at) if *publish_at > now && !self.preview => {
+              expires = Some(expires.map_or(*publish_at, |expires| expires.min(*publish_at)));
+              false
+            }
+            _ => true,
+          })
+          .map(|(_, entry)| entry.clone())
+          .collect();
+        (name.clone(), Arc::new(published))
+      })
+      .collect();
+    let taxonomies = self
+      .taxonomies
+      .iter()
+      .map(|taxonomy| (taxonomy.clone(), Arc::new(terms(&collections, taxonomy))))
+      .collect();
+    View {
+      collections,
+      taxonomies,
+      expires,
+    }
+  }
+
+  /// The term of taxonomy `name` with the slug (or name) `term`, if any.
+  pub(crate) fn taxonomy_term(&self, name: &str, term: &str) -> Option<Value> {
+    let slug = slugify(term);
+    self
+      .taxonomy(name)?
+      .iter()
+      .find(|candidate| candidate["slug"] == slug.as_str())
+      .cloned()
+  }
+
+  fn load_entry(&self, collection_dir: &Path, file: &Path) -> Result<Value> {
+    let source = std::fs::read_to_string(file)?;
+    let (front_matter, body) = front_matter::split(&source).map_err(|message| self.error(file, message))?;
+
+    let mut entry: Map<String, Value> = front_matter.map(|front_matter| front_matter.values().clone()).unwrap_or_default();
+    let slug = file
+      .strip_prefix(collection_dir)
+      .unwrap_or(file)
+      .with_extension("")
+      .components()
+      .map(|component| component.as_os_str().to_string_lossy())
+      .collect::<Vec<_>>()
+      .join("/");
+    entry.insert("slug".to_string(), Value::from(slug));
+    entry.insert("content".to_string(), Value::from(self.policy.clean(&markdown_to_html(body))));
+    entry.insert("raw_content".to_string(), Value::from(body));
+    Ok(Value::Object(entry))
+  }
+
+  fn error(&self, path: &Path, message: String) -> SnapFireError {
+    SnapFireError::Config {
+      path: path.display().to_string(),
+      message,
+    }
+  }
+}
+
+/// Collects the `.md` files under `dir`, skipping hidden and `_` entries.
+fn markdown_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+  for entry in std::fs::read_dir(dir)? {
+    let path = entry?.path();
+    if is_hidden(&path) {
+      continue;
+    }
+    if path.is_dir() {
+      markdown_files(&path, files)?;
+    } else if path.extension().is_some_and(|extension| extension == "md") {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
+
+/// Groups the entries of every collection by their values for `taxonomy`.
+fn terms(collections: &Collections, taxonomy: &str) -> Vec<Value> {
+  // Term name and entries, by slug.
+  let mut terms: BTreeMap<String, (String, Vec<Value>)> = BTreeMap::new();
+  for entry in collections.values().flat_map(|entries| entries.iter()) {
+    let names = match &entry[taxonomy] {
+      Value::String(name) => vec![name.as_str()],
+      Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+      _ => continue,
+    };
+    // An entry listing a term twice is still one page of it.
+    let mut seen = HashSet::new();
+    for name in names {
+      let slug = slugify(name);
+      if slug.is_empty() || !seen.insert(slug.clone()) {
+        continue;
+      }
+      let (_, pages) = terms.entry(slug).or_insert_with(|| (name.to_string(), Vec::new()));
+      pages.push(entry.clone());
+    }
+  }
+
+  terms
+    .into_iter()
+    .map(|(slug, (name, pages))| {
+      serde_json::json!({
+        "name": name,
+        "slug": slug,
+        "count": pages.len(),
+        "pages": pages,
+      })
+    })
+    .collect()
+}
+
+/// `term` lowercased, with runs of other characters than letters and digits
+/// replaced by `-`, e.g. `Rust & WASM` becomes `rust-wasm`.
+fn slugify(term: &str) -> String {
+  let mut slug = String::with_capacity(term.len());
+  for c in term.chars().flat_map(char::to_lowercase) {
+    if c.is_alphanumeric() {
+      slug.push(c);
+    } else if !slug.is_empty() && !slug.ends_with('-') {
+      slug.push('-');
+    }
+  }
+  slug.trim_end_matches('-').to_string()
+}
+
+/// The entry's `publish_at`, an RFC 3339 date and time or a date (taken as
+/// midnight UTC).
+fn publish_at(entry: &Value) -> std::result::Result<Option<DateTime<Utc>>, String> {
+  let Some(value) = entry.get("publish_at") else {
+    return Ok(None);
+  };
+  let text = value
+    .as_str()
+    .ok_or_else(|| format!("`publish_at` must be a date or date and time, not {value}"))?;
+  if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+    return Ok(Some(time.with_timezone(&Utc)));
+  }
+  NaiveDate::parse_from_str(text, "%Y-%m-%d")
+    .map(|date| Some(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()))
+    .map_err(|_| format!("Invalid `publish_at` `{text}`: expected e.g. `2024-05-01` or `2024-05-01T09:00:00Z`"))
+}
+
+fn is_hidden(path: &Path) -> bool {
+  path
+    .file_name()
+    .is_some_and(|name| name.to_string_lossy().starts_with(['.', '_']))
+}
+
+/// The `content(name="blog")` Tera function.
+pub(crate) struct ContentFunction(pub(crate) Arc<Content>);
+
+impl Function for ContentFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let name = args.get("name").and_then(Value::as_str).ok_or_else(|| {
+      tera::Error::msg("The `content` function requires a `name` argument, e.g. content(name=\"blog\")")
+    })?;
+    let entries = self
+      .0
+      .collection(name)
+      .ok_or_else(|| tera::Error::msg(format!("Unknown content collection `{name}`")))?;
+    Ok(Value::Array(entries.as_ref().clone()))
+  }
+}
+
+/// The `taxonomy(name="tags")` Tera function, or `taxonomy(name="tags",
+/// term="rust")` for a single term.
+pub(crate) struct TaxonomyFunction(pub(crate) Arc<Content>);
+
+impl Function for TaxonomyFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let name = args.get("name").and_then(Value::as_str).ok_or_else(|| {
+      tera::Error::msg("The `taxonomy` function requires a `name` argument, e.g. taxonomy(name=\"tags\")")
+    })?;
+    match args.get("term") {
+      Some(term) => {
+        let term = term
+          .as_str()
+          .ok_or_else(|| tera::Error::msg("The `term` argument of `taxonomy` must be a string"))?;
+        self
+          .0
+          .taxonomy_term(name, term)
+          .ok_or_else(|| tera::Error::msg(format!("Unknown term `{term}` in taxonomy `{name}`")))
+      }
+      None => {
+        let terms = self
+          .0
+          .taxonomy(name)
+          .ok_or_else(|| tera::Error::msg(format!("Unknown taxonomy `{name}`")))?;
+        Ok(Value::Array(terms.as_ref().clone()))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_loads_collections() {
+    let temp_dir = tempdir().unwrap();
+    let blog = temp_dir.path().join("blog");
+    fs::create_dir_all(blog.join("2024")).unwrap();
+    fs::create_dir_all(blog.join("_drafts")).unwrap();
+    fs::write(blog.join("hello.md"), "---\ntitle: Hello\ndate: 2023-05-01\n---\n# Hi <script>x</script>").unwrap();
+    fs::write(blog.join("2024/launch.md"), "+++\ntitle = \"Launch\"\n+++\n*new*").unwrap();
+    fs::write(blog.join("_drafts/wip.md"), "wip").unwrap();
+    fs::write(blog.join("notes.txt"), "not content").unwrap();
+
+    let content = Content::new(temp_dir.path().to_path_buf(), SanitizePolicy::permissive(), Vec::new(), false).unwrap();
+    let entries = ContentFunction(Arc::new(content))
+      .call(&HashMap::from([("name".to_string(), Value::from("blog"))]))
+      .unwrap();
+    assert_eq!(
+      entries,
+      serde_json::json!([
+        { "title": "Launch", "slug": "2024/launch", "content": "<p><em>new</em></p>\n", "raw_content": "*new*" },
+        {
+          "title": "Hello",
+          "date": "2023-05-01",
+          "slug": "hello",
+          "content": "<h1>Hi </h1>\n",
+          "raw_content": "# Hi <script>x</script>",
+        },
+      ])
+    );
+  }
+
+  #[test]
+  fn test_groups_entries_by_taxonomy() {
+    let temp_dir = tempdir().unwrap();
+    let blog = temp_dir.path().join("blog");
+    let notes = temp_dir.path().join("notes");
+    fs::create_dir_all(&blog).unwrap();
+    fs::create_dir_all(&notes).unwrap();
+    fs::write(blog.join("a.md"), "---\ntags: [Rust, WASM, rust]\n---\nA").unwrap();
+    fs::write(blog.join("b.md"), "---\ntags: [rust]\n---\nB").unwrap();
+    fs::write(notes.join("c.md"), "+++\ntags = \"Rust & WASM\"\n+++\nC").unwrap();
+    fs::write(notes.join("d.md"), "D").unwrap();
+
+    let content = Content::new(temp_dir.path().to_path_buf(), SanitizePolicy::permissive(), vec!["tags".to_string()], false).unwrap();
+    let summary = |term: &Value| {
+      let slugs: Vec<&str> = term["pages"].as_array().unwrap().iter().map(|page| page["slug"].as_str().unwrap()).collect();
+      (term["name"].as_str().unwrap().to_string(), term["slug"].as_str().unwrap().to_string(), term["count"].as_u64().unwrap(), slugs.join(","))
+    };
+    let terms: Vec<_> = content.taxonomy("tags").unwrap().iter().map(summary).collect();
+    assert_eq!(
+      terms,
+      [
+        ("Rust".to_string(), "rust".to_string(), 2, "a,b".to_string()),
+        ("Rust & WASM".to_string(), "rust-wasm".to_string(), 1, "c".to_string()),
+        ("WASM".to_string(), "wasm".to_string(), 1, "a".to_string()),
+      ]
+    );
+    assert_eq!(summary(&content.taxonomy_term("tags", "RUST").unwrap()).2, 2);
+    assert!(content.taxonomy_term("tags", "go").is_none());
+    assert!(content.taxonomy("categories").is_none());
+  }
+
+  #[test]
+  fn test_hides_drafts_and_scheduled_entries() {
+    let temp_dir = tempdir().unwrap();
+    let blog = temp_dir.path().join("blog");
+    fs::create_dir_all(&blog).unwrap();
+    fs::write(blog.join("draft.md"), "---\ndraft: true\ntags: [rust]\n---\nWIP").unwrap();
+    fs::write(blog.join("future.md"), "---\npublish_at: 2999-01-01T09:00:00+02:00\ntags: [rust]\n---\nSoon").unwrap();
+    fs::write(blog.join("past.md"), "---\npublish_at: 2020-01-01\ntags: [rust]\n---\nOut").unwrap();
+    let load = |preview| Content::new(temp_dir.path().to_path_buf(), SanitizePolicy::permissive(), vec!["tags".to_string()], preview).unwrap();
+    let slugs = |content: &Content| -> Vec<String> {
+      content.collection("blog").unwrap().iter().map(|entry| entry["slug"].as_str().unwrap().to_string()).collect()
+    };
+
+    let published = load(false);
+    assert_eq!(slugs(&published), ["past"]);
+    assert_eq!(published.taxonomy_term("tags", "rust").unwrap()["count"], 1);
+    let expires = published.view().expires.unwrap();
+    assert_eq!(expires, DateTime::parse_from_rfc3339("2999-01-01T07:00:00Z").unwrap());
+
+    // Once the time comes, the entry is published.
+    let state = published.state.read();
+    let view = published.view_at(&state.loaded, expires);
+    assert_eq!(view.collections["blog"].len(), 2);
+    assert_eq!(view.expires, None);
+    drop(state);
+
+    assert_eq!(slugs(&load(true)), ["draft", "future", "past"]);
+
+    fs::write(blog.join("bad.md"), "---\npublish_at: tomorrow\n---\n").unwrap();
+    assert!(published.reload().is_err());
+    assert_eq!(slugs(&published), ["past"]);
+  }
+}
@@ -0,0 +1,165 @@
+use crate::core::app::normalize_host;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use tera::{Context, Value};
+
+/// Upper bound on the number of idle merged contexts kept for reuse.
+const MAX_POOLED_CONTEXTS: usize = 64;
+
+/// The immutable global context, layered under each render's own context.
+///
+/// Tera renders from a single `Context`, so the globals and the user context
+/// have to live in one map. Instead of deep-cloning the globals for every
+/// render, we keep a small pool of ready-made copies. A render takes one,
+/// overlays the host and user layers, and afterwards restores only the keys
+/// it touched before returning it to the pool. The cost of a render's merge is
+/// therefore proportional to the size of its own context, not the globals.
+#[derive(Debug)]
+pub(crate) struct GlobalContext {
+  base: Context,
+  /// Per-host overrides, keyed by normalized host, with their keys precomputed.
+  hosts: HashMap<String, (Context, Vec<String>)>,
+  pool: Mutex<Vec<Context>>,
+}
+
+impl GlobalContext {
+  pub(crate) fn new(base: Context, hosts: HashMap<String, Context>) -> Self {
+    let hosts = hosts
+      .into_iter()
+      .map(|(host, context)| {
+        let keys = context_keys(context.clone());
+        (host, (context, keys))
+      })
+      .collect();
+
+    Self {
+      base,
+      hosts,
+      pool: Mutex::new(Vec::new()),
+    }
+  }
+
+  /// The site-wide globals.
+  pub(crate) fn base(&self) -> &Context {
+    &self.base
+  }
+
+  /// The per-host overrides, keyed by normalized host.
+  pub(crate) fn hosts(&self) -> impl Iterator<Item = (&str, &Context)> {
+    self.hosts.iter().map(|(host, (context, _))| (host.as_str(), context))
+  }
+
+  /// Runs `f` with the fully merged context: globals, then the overrides for
+  /// `host`, then `user_context`.
+  pub(crate) fn with_merged<R>(&self, host: Option<&str>, user_context: Context, f: impl FnOnce(&Context) -> R) -> R {
+    let (mut merged, touched) = self.merge(host, user_context);
+    let result = f(&merged);
+
+    // If `f` panicked we never get here, and the dirty context is simply dropped.
+    self.restore(&mut merged, touched);
+    let mut pool = self.pool.lock();
+    if pool.len() < MAX_POOLED_CONTEXTS {
+      pool.push(merged);
+    }
+
+    result
+  }
+
+  /// Like `with_merged`, but lets `f` change the merged context arbitrarily.
+  ///
+  /// Since there is no telling which keys `f` touched, the context is dropped
+  /// afterwards instead of being restored and pooled.
+  pub(crate) fn with_merged_mut<R>(
+    &self,
+    host: Option<&str>,
+    user_context: Context,
+    f: impl FnOnce(&mut Context) -> R,
+  ) -> R {
+    let (mut merged, _) = self.merge(host, user_context);
+    f(&mut merged)
+  }
+
+  /// Builds the merged context and lists the keys layered over the globals.
+  fn merge(&self, host: Option<&str>, user_context: Context) -> (Context, Vec<String>) {
+    let mut merged = self.pool.lock().pop().unwrap_or_else(|| self.base.clone());
+    let mut touched = Vec::new();
+
+    if let Some((host_context, keys)) = host.and_then(|host| self.hosts.get(&normalize_host(host))) {
+      merged.extend(host_context.clone());
+      touched.extend(keys.iter().cloned());
+    }
+
+    // Move the user's values in without copying them, remembering their keys.
+    let Value::Object(user_values) = user_context.into_json() else {
+      unreachable!("a tera::Context always serializes to an object")
+    };
+    touched.extend(user_values.keys().cloned());
+    merged.extend(Context::from_value(Value::Object(user_values)).expect("an object is always a valid context"));
+
+    (merged, touched)
+  }
+
+  /// Puts back the global value (or removes the entry) for every touched key.
+  fn restore(&self, merged: &mut Context, touched: Vec<String>) {
+    for key in touched {
+      match self.base.get(&key) {
+        Some(value) => merged.insert(key, value),
+        None => {
+          merged.remove(&key);
+        }
+      }
+    }
+  }
+}
+
+/// Lists the top-level keys of a context.
+fn context_keys(context: Context) -> Vec<String> {
+  match context.into_json() {
+    Value::Object(map) => map.into_iter().map(|(key, _)| key).collect(),
+    _ => Vec::new(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn globals() -> GlobalContext {
+    let mut base = Context::new();
+    base.insert("site", "SnapFire");
+    base.insert("title", "Default");
+    let mut admin = Context::new();
+    admin.insert("site", "Admin");
+    GlobalContext::new(base, HashMap::from([("admin.example.com".to_string(), admin)]))
+  }
+
+  fn merged(globals: &GlobalContext, host: Option<&str>, user: Context) -> Context {
+    globals.with_merged(host, user, Context::clone)
+  }
+
+  #[test]
+  fn test_layers_apply_in_order() {
+    let globals = globals();
+    let mut user = Context::new();
+    user.insert("title", "Page");
+
+    let merged = merged(&globals, Some("admin.example.com"), user);
+    assert_eq!(merged.get("site"), Some(&Value::from("Admin")));
+    assert_eq!(merged.get("title"), Some(&Value::from("Page")));
+  }
+
+  #[test]
+  fn test_pooled_context_does_not_leak_between_renders() {
+    let globals = globals();
+    let mut user = Context::new();
+    user.insert("title", "Page");
+    user.insert("secret", "only for the first render");
+    globals.with_merged(Some("admin.example.com"), user, |_| ());
+
+    // The second render reuses the pooled context from the first.
+    let merged = merged(&globals, None, Context::new());
+    assert_eq!(merged.get("site"), Some(&Value::from("SnapFire")));
+    assert_eq!(merged.get("title"), Some(&Value::from("Default")));
+    assert!(!merged.contains_key("secret"));
+  }
+}
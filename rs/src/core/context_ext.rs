@@ -0,0 +1,62 @@
+//! View data contributed by middleware.
+//!
+//! Middleware that knows something every page should show (the signed-in
+//! user, the current tenant, a feature flag set) adds it to the request's
+//! [`SnapFireContextExt`], and every template rendered for that request sees
+//! it, without handlers passing it along. With Actix:
+//!
+//! ```rust,no_run
+//! # use actix_web::{dev::ServiceRequest, HttpMessage};
+//! # use snapfire::SnapFireContextExt;
+//! # fn authenticate(req: &ServiceRequest) {
+//! SnapFireContextExt::insert_into(req, "current_user", "ada");
+//! # }
+//! ```
+//!
+//! Values the handler puts in its own context take precedence.
+
+use serde::Serialize;
+use tera::Context;
+
+/// Render context values stored in the request extensions, merged into the
+/// context of every template rendered for the request.
+#[derive(Debug, Clone, Default)]
+pub struct SnapFireContextExt {
+  pub(crate) context: Context,
+  /// The keys of the values added with `insert_sensitive`.
+  pub(crate) sensitive: Vec<String>,
+}
+
+impl SnapFireContextExt {
+  /// An empty set of values.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a value, replacing any earlier one with the same key.
+  pub fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> &mut Self {
+    self.context.insert(key, value);
+    self
+  }
+
+  /// Adds a value that renders normally but is scrubbed from error reports
+  /// and error messages, like `Template::insert_sensitive`.
+  pub fn insert_sensitive<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> &mut Self {
+    self.context.insert(key, value);
+    self.sensitive.push(key.to_string());
+    self
+  }
+
+  /// The values added so far.
+  pub fn context(&self) -> &Context {
+    &self.context
+  }
+
+  /// Layers `handler_context` over these values, so the handler's win.
+  #[cfg(feature = "actix")]
+  pub(crate) fn merged_with(&self, handler_context: Context) -> Context {
+    let mut merged = self.context.clone();
+    merged.extend(handler_context);
+    merged
+  }
+}
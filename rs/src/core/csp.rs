@@ -0,0 +1,40 @@
+//! The Content Security Policy sources SnapFire's own output needs.
+//!
+//! The `SecurityHeaders` Actix middleware adds these to its policy, so the
+//! engine's helpers keep working under it: the `avatar` function's Gravatar
+//! and `data:` images, KaTeX's `style` attributes with `render_math`, and
+//! the CDN of `asset_base_url`. Scripts the engine injects carry the
+//! request's nonce instead.
+
+use std::borrow::Cow;
+
+/// The sources each directive needs, beyond `'self'` and the nonce.
+#[derive(Debug, Clone)]
+pub(crate) struct EngineSources {
+  sources: Vec<(&'static str, Cow<'static, str>)>,
+}
+
+impl Default for EngineSources {
+  fn default() -> Self {
+    Self {
+      // The `avatar` function is always registered.
+      sources: vec![("img-src", "data:".into()), ("img-src", "https://www.gravatar.com".into())],
+    }
+  }
+}
+
+impl EngineSources {
+  /// Allows `source` in `directive`, once.
+  pub(crate) fn add(&mut self, directive: &'static str, source: impl Into<Cow<'static, str>>) {
+    let source = source.into();
+    if !self.sources.iter().any(|(name, known)| *name == directive && *known == source) {
+      self.sources.push((directive, source));
+    }
+  }
+
+  /// The `(directive, source)` pairs, in the order they were added.
+  #[cfg(feature = "actix")]
+  pub(crate) fn iter(&self) -> impl Iterator<Item = (&'static str, &str)> + '_ {
+    self.sources.iter().map(|(directive, source)| (*directive, source.as_ref()))
+  }
+}
@@ -0,0 +1,245 @@
+//! Blocks whose data loads while the rest of the page renders.
+//!
+//! A block is declared with `TeraWebBuilder::deferred_block`: a name, the
+//! template rendering it, and a loader producing that template's context.
+//! Pages place it with `await_block`, passing the loader what it needs:
+//!
+//! ```jinja
+//! <aside>{{ await_block(name="recommendations", user_id=user.id) }}</aside>
+//! ```
+//!
+//! In early-flush renders (`TeraWebBuilder::early_flush`), the loader starts
+//! at that point on a small pool of threads shared by every render, and the
+//! page renders on with an empty `<template>` in the block's place. Once the
+//! page is sent, each block follows in document order, as soon as its data
+//! is in, with a small script moving it into place (carrying the request's
+//! CSP nonce). Tera 1 has no custom tags, so the marker is a function rather
+//! than `{% await %}`.
+//!
+//! Every other render, e.g. `render_into` or a page with post-processors,
+//! loads and renders the block right where it stands, so the output is the
+//! same HTML, just not progressive. Either way, a loader's error fails the
+//! render like any other render error.
+
+use crate::core::app::TemplateSet;
+use crate::core::context::GlobalContext;
+use crate::core::flush::{FLUSH_MARKER, is_flushing};
+use crate::error::{Result, SnapFireError, panic_message};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, LazyLock};
+use tera::{Context, Function, Value};
+
+/// Produces a block's context from the arguments of its `await_block` call.
+pub(crate) type BlockLoader = Arc<dyn Fn(&HashMap<String, Value>) -> Result<Context> + Send + Sync>;
+
+/// How many loaders run at once, across every render.
+const LOADER_THREADS: usize = 8;
+
+/// A loader call queued on the pool.
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The queue of the threads running loaders in early-flush renders, started
+/// on first use. A page awaiting many blocks queues them rather than
+/// starting a thread for each. `None` if no thread could be started.
+static LOADERS: LazyLock<Option<parking_lot::Mutex<Sender<Job>>>> = LazyLock::new(|| {
+  let (sender, receiver) = mpsc::channel::<Job>();
+  let receiver = Arc::new(parking_lot::Mutex::new(receiver));
+  let started = (0..LOADER_THREADS)
+    .filter(|i| {
+      let receiver = Arc::clone(&receiver);
+      let worker = std::thread::Builder::new().name(format!("snapfire-await-{i}")).spawn(move || {
+        // The lock is only held while waiting, so idle workers take turns.
+        while let Ok(job) = receiver.lock().recv() {
+          job();
+        }
+      });
+      worker.inspect_err(|e| log::error!("Failed to start a deferred block loader thread: {e}")).is_ok()
+    })
+    .count();
+  (started > 0).then(|| parking_lot::Mutex::new(sender))
+});
+
+/// Queues `loader` on the pool, or runs it right away without one; the
+/// receiver gets its result, or its panic.
+fn spawn_loader(loader: BlockLoader, args: HashMap<String, Value>) -> Receiver<std::thread::Result<Result<Context>>> {
+  let (result, receiver) = mpsc::channel();
+  let job: Job = Box::new(move || {
+    let _ = result.send(catch_unwind(AssertUnwindSafe(|| loader(&args))));
+  });
+  match LOADERS.as_ref() {
+    Some(loaders) => {
+      // The workers never exit, so the queue is always open.
+      let _ = loaders.lock().send(job);
+    }
+    None => job(),
+  }
+  receiver
+}
+
+/// Moves the `<template>` before it into its placeholder, then removes itself.
+const SWAP_SCRIPT: &str = "(function(s){var t=s.previousElementSibling,p=document.getElementById(t.dataset.snapfireFill);if(p)p.replaceWith(t.content);t.remove();s.remove()})(document.currentScript)";
+
+/// A declared block.
+pub(crate) struct DeferredBlock {
+  pub(crate) template: String,
+  pub(crate) loader: BlockLoader,
+}
+
+/// The blocks `await_block` can place, by name.
+pub(crate) type DeferredBlocks = HashMap<String, DeferredBlock>;
+
+/// What `await_block` needs of the render it's called in.
+pub(crate) struct BlockRenderer {
+  pub(crate) tera: Arc<TemplateSet>,
+  pub(crate) globals: Arc<GlobalContext>,
+  pub(crate) host: Option<String>,
+  pub(crate) nonce: Option<String>,
+}
+
+/// A block whose data is loading, to be sent after the page.
+struct Pending {
+  id: usize,
+  name: String,
+  template: String,
+  loading: Receiver<std::thread::Result<Result<Context>>>,
+}
+
+/// The render in progress on this thread, and the blocks it left behind.
+struct Scope {
+  render: Arc<BlockRenderer>,
+  pending: Vec<Pending>,
+  next_id: usize,
+}
+
+thread_local! {
+  static SCOPE: RefCell<Option<Scope>> = const { RefCell::new(None) };
+}
+
+/// Renders the page with `render_page`, giving `await_block` calls access to
+/// `render`. In early-flush renders, the deferred blocks are then written to
+/// `writer` in document order, each flushed as soon as it's rendered.
+pub(crate) fn render_with_blocks<W: Write>(
+  render: BlockRenderer,
+  writer: &mut W,
+  render_page: impl FnOnce(&mut W) -> Result<()>,
+) -> Result<()> {
+  /// Restores the outer scope, even if the render panics.
+  struct Restore(Option<Scope>);
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      let outer = self.0.take();
+      SCOPE.with(|scope| *scope.borrow_mut() = outer);
+    }
+  }
+
+  let render = Arc::new(render);
+  let outer = SCOPE.with(|scope| {
+    scope.borrow_mut().replace(Scope {
+      render: Arc::clone(&render),
+      pending: Vec::new(),
+      next_id: 0,
+    })
+  });
+  let _restore = Restore(outer);
+
+  render_page(writer)?;
+  // Blocks may await blocks of their own, which join the end of the queue.
+  let mut sent_page = false;
+  while let Some(block) = SCOPE.with(|scope| {
+    let mut scope = scope.borrow_mut();
+    let pending = &mut scope.as_mut().expect("the scope is set for the render").pending;
+    (!pending.is_empty()).then(|| pending.remove(0))
+  }) {
+    if !sent_page {
+      // Let the page out before waiting on the first loader.
+      writer.write_all(FLUSH_MARKER)?;
+      sent_page = true;
+    }
+    let loaded = block.loading.recv().map_err(|_| {
+      SnapFireError::Io(std::io::Error::other(format!("the deferred block `{}` was never loaded", block.name)))
+    })?;
+    let context = loaded.map_err(|panic| SnapFireError::Panic {
+      template: block.template.clone(),
+      message: format!("the loader of the deferred block `{}` panicked: {}", block.name, panic_message(panic.as_ref())),
+    })??;
+    let html = render_block(&render, &block.template, context)?;
+    writer.write_all(fill(block.id, &html, render.nonce.as_deref()).as_bytes())?;
+    writer.write_all(FLUSH_MARKER)?;
+  }
+  Ok(())
+}
+
+fn render_block(render: &BlockRenderer, template: &str, context: Context) -> Result<String> {
+  Ok(render.globals.with_merged(render.host.as_deref(), context, |context| render.tera.render(template, context))?)
+}
+
+/// The id of the placeholder of the `id`-th deferred block.
+fn placeholder_id(id: usize) -> String {
+  format!("snapfire-await-{id}")
+}
+
+/// The markup sending a block to its placeholder.
+fn fill(id: usize, html: &str, nonce: Option<&str>) -> String {
+  let mut fill = format!("<template data-snapfire-fill=\"{}\">{html}</template><script", placeholder_id(id));
+  if let Some(nonce) = nonce {
+    let _ = write!(fill, " nonce=\"{}\"", tera::escape_html(nonce));
+  }
+  let _ = write!(fill, ">{SWAP_SCRIPT}</script>");
+  fill
+}
+
+/// The `await_block(name=..., ...)` Tera function.
+pub(crate) struct AwaitBlockFunction(pub(crate) Arc<DeferredBlocks>);
+
+impl Function for AwaitBlockFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let name = args.get("name").and_then(Value::as_str).ok_or_else(|| {
+      tera::Error::msg("The `await_block` function requires a `name` string, e.g. await_block(name=\"recommendations\")")
+    })?;
+    let block = self.0.get(name).ok_or_else(|| {
+      let mut declared: Vec<_> = self.0.keys().map(String::as_str).collect();
+      declared.sort_unstable();
+      tera::Error::msg(format!(
+        "No deferred block named `{name}`; declared blocks: {}",
+        declared.join(", ")
+      ))
+    })?;
+    let mut loader_args = args.clone();
+    loader_args.remove("name");
+
+    let render = SCOPE
+      .with(|scope| scope.borrow().as_ref().map(|scope| Arc::clone(&scope.render)))
+      .ok_or_else(|| tera::Error::msg("The `await_block` function only works in pages rendered by SnapFire"))?;
+
+    if !is_flushing() {
+      let context = (block.loader)(&loader_args).map_err(|e| tera::Error::msg(e.to_string()))?;
+      let html = render_block(&render, &block.template, context).map_err(|e| tera::Error::msg(e.to_string()))?;
+      return Ok(Value::String(html));
+    }
+
+    let loading = spawn_loader(Arc::clone(&block.loader), loader_args);
+    let id = SCOPE.with(|scope| {
+      let mut scope = scope.borrow_mut();
+      let scope = scope.as_mut().expect("checked above");
+      let id = scope.next_id;
+      scope.next_id += 1;
+      scope.pending.push(Pending {
+        id,
+        name: name.to_string(),
+        template: block.template.clone(),
+        loading,
+      });
+      id
+    });
+    Ok(Value::String(format!("<template id=\"{}\"></template>", placeholder_id(id))))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
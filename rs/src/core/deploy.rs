@@ -0,0 +1,389 @@
+//! Publishing an exported site: to S3, to Netlify, or to a git branch.
+//!
+//! Each target implements [`ExportTarget`], so a build script can export and
+//! publish in one go:
+//!
+//! ```rust,no_run
+//! # use snapfire::core::{deploy::S3Target, export::{ExportPage, StaticExport}};
+//! # fn run(app: &snapfire::TeraWeb) -> snapfire::Result<()> {
+//! let mut export = StaticExport::new(app, "dist", vec![ExportPage::new("/", "index.html")]);
+//! export.build()?;
+//! export.publish(&S3Target::new("my-site", "eu-west-1", "AKIA...", "secret"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::core::assets::content_type;
+use crate::core::aws::{SigV4, amz_date, hex};
+use crate::core::export::ExportTarget;
+use crate::error::{Result, SnapFireError};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+/// Uploads the site to an S3 bucket (or an S3-compatible store), with each
+/// file's content type and a cache policy.
+///
+/// HTML is served with `Cache-Control: public, max-age=0, must-revalidate`
+/// so new deploys are picked up immediately, and everything else with
+/// `public, max-age=86400`; override per extension with `cache_control`.
+#[derive(Debug, Clone)]
+pub struct S3Target {
+  bucket: String,
+  region: String,
+  access_key: String,
+  secret_key: String,
+  session_token: Option<String>,
+  endpoint: Option<String>,
+  prefix: String,
+  cache_control: BTreeMap<String, String>,
+}
+
+impl S3Target {
+  /// A target uploading to `bucket` in `region` with the given credentials.
+  pub fn new(bucket: &str, region: &str, access_key: &str, secret_key: &str) -> Self {
+    Self {
+      bucket: bucket.to_string(),
+      region: region.to_string(),
+      access_key: access_key.to_string(),
+      secret_key: secret_key.to_string(),
+      session_token: None,
+      endpoint: None,
+      prefix: String::new(),
+      cache_control: BTreeMap::from([("html".to_string(), "public, max-age=0, must-revalidate".to_string())]),
+    }
+  }
+
+  /// Sets the session token of temporary credentials.
+  pub fn session_token(mut self, token: &str) -> Self {
+    self.session_token = Some(token.to_string());
+    self
+  }
+
+  /// Uploads to an S3-compatible endpoint (e.g. MinIO or R2) with path-style
+  /// URLs, instead of AWS.
+  pub fn endpoint(mut self, endpoint: &str) -> Self {
+    self.endpoint = Some(endpoint.trim_end_matches('/').to_string());
+    self
+  }
+
+  /// Uploads under a key prefix, e.g. `site/`.
+  pub fn prefix(mut self, prefix: &str) -> Self {
+    self.prefix = prefix.trim_matches('/').to_string();
+    if !self.prefix.is_empty() {
+      self.prefix.push('/');
+    }
+    self
+  }
+
+  /// Sets the `Cache-Control` header for files with `extension`.
+  pub fn cache_control(mut self, extension: &str, value: &str) -> Self {
+    self.cache_control.insert(extension.to_string(), value.to_string());
+    self
+  }
+
+  fn upload(&self, key: &str, body: &[u8], now: SystemTime) -> Result<()> {
+    let (host, path) = match &self.endpoint {
+      Some(endpoint) => {
+        let host = endpoint.split_once("://").map_or(endpoint.as_str(), |(_, host)| host);
+        (host.to_string(), format!("/{}/{}", self.bucket, key))
+      }
+      None => (format!("{}.s3.{}.amazonaws.com", self.bucket, self.region), format!("/{key}")),
+    };
+    let scheme = match &self.endpoint {
+      Some(endpoint) if endpoint.starts_with("http://") => "http",
+      _ => "https",
+    };
+    let extension = Path::new(key).extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let cache_control = self
+      .cache_control
+      .get(extension)
+      .map_or("public, max-age=86400", String::as_str);
+
+    let mut headers = vec![
+      ("cache-control".to_string(), cache_control.to_string()),
+      ("content-type".to_string(), content_type(key).to_string()),
+      ("host".to_string(), host),
+      ("x-amz-content-sha256".to_string(), hex(&Sha256::digest(body))),
+      ("x-amz-date".to_string(), amz_date(now)),
+    ];
+    if let Some(token) = &self.session_token {
+      headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    let path = uri_encode(&path);
+    let authorization = SigV4 {
+      access_key: &self.access_key,
+      secret_key: &self.secret_key,
+      region: &self.region,
+      service: "s3",
+    }
+    .authorization("PUT", &path, &headers);
+
+    let url = format!("{scheme}://{}{path}", headers[2].1);
+    let request = headers
+      .iter()
+      .filter(|(name, _)| name != "host")
+      .fold(ureq::put(&url), |request, (name, value)| request.set(name, value))
+      .set("authorization", &authorization);
+    request.send_bytes(body).map_err(|e| deploy_error("s3", e))?;
+    Ok(())
+  }
+}
+
+impl ExportTarget for S3Target {
+  fn publish(&self, out_dir: &Path) -> Result<()> {
+    let now = SystemTime::now();
+    for file in site_files(out_dir)? {
+      let key = format!("{}{}", self.prefix, url_path(&file));
+      self.upload(&key, &std::fs::read(out_dir.join(&file))?, now)?;
+      log::info!("Uploaded s3://{}/{}", self.bucket, key);
+    }
+    Ok(())
+  }
+}
+
+/// Deploys the site to Netlify through its file digest API, uploading only
+/// the files Netlify doesn't already have.
+#[derive(Debug, Clone)]
+pub struct NetlifyTarget {
+  site_id: String,
+  token: String,
+  api_base: String,
+}
+
+impl NetlifyTarget {
+  /// A target deploying to the site `site_id` with a personal access token.
+  pub fn new(site_id: &str, token: &str) -> Self {
+    Self {
+      site_id: site_id.to_string(),
+      token: token.to_string(),
+      api_base: "https://api.netlify.com/api/v1".to_string(),
+    }
+  }
+
+  /// Overrides the API URL, e.g. for a proxy.
+  pub fn api_base(mut self, api_base: &str) -> Self {
+    self.api_base = api_base.trim_end_matches('/').to_string();
+    self
+  }
+}
+
+impl ExportTarget for NetlifyTarget {
+  fn publish(&self, out_dir: &Path) -> Result<()> {
+    let mut digests = BTreeMap::new();
+    let mut files_by_digest = BTreeMap::new();
+    for file in site_files(out_dir)? {
+      let digest = hex(&Sha1::digest(std::fs::read(out_dir.join(&file))?));
+      digests.insert(format!("/{}", url_path(&file)), digest.clone());
+      files_by_digest.insert(digest, file);
+    }
+
+    let authorization = format!("Bearer {}", self.token);
+    let url = format!("{}/sites/{}/deploys", self.api_base, self.site_id);
+    let response = ureq::post(&url)
+      .set("authorization", &authorization)
+      .set("content-type", "application/json")
+      .send_string(&serde_json::json!({ "files": digests }).to_string())
+      .map_err(|e| deploy_error("netlify", e))?
+      .into_string()?;
+    let deploy: serde_json::Value = serde_json::from_str(&response).map_err(|e| deploy_error("netlify", e))?;
+
+    let id = deploy["id"]
+      .as_str()
+      .ok_or_else(|| deploy_error("netlify", "the deploy response has no id"))?;
+    let required = deploy["required"].as_array().cloned().unwrap_or_default();
+    for digest in required.iter().filter_map(serde_json::Value::as_str) {
+      let Some(file) = files_by_digest.get(digest) else {
+        continue;
+      };
+      let url = format!("{}/deploys/{id}/files/{}", self.api_base, uri_encode(&url_path(file)));
+      ureq::put(&url)
+        .set("authorization", &authorization)
+        .set("content-type", "application/octet-stream")
+        .send_bytes(&std::fs::read(out_dir.join(file))?)
+        .map_err(|e| deploy_error("netlify", e))?;
+      log::info!("Uploaded {} to Netlify", file.display());
+    }
+    Ok(())
+  }
+}
+
+/// Commits the site to a branch of a git repository (e.g. `gh-pages` for
+/// GitHub Pages), without touching the repository's working tree, and
+/// optionally pushes it.
+///
+/// Commits use the repository's configured author. GitHub Pages runs sites
+/// through Jekyll unless the export contains a `.nojekyll` file, which hides
+/// paths starting with `_` (such as the search index).
+#[derive(Debug, Clone)]
+pub struct GitBranch {
+  repo: PathBuf,
+  branch: String,
+  remote: Option<String>,
+  message: String,
+}
+
+impl GitBranch {
+  /// A target committing to `branch` of the repository at `repo`.
+  pub fn new<P: Into<PathBuf>>(repo: P, branch: &str) -> Self {
+    Self {
+      repo: repo.into(),
+      branch: branch.to_string(),
+      remote: None,
+      message: "Publish site".to_string(),
+    }
+  }
+
+  /// Pushes the branch to `remote` (e.g. `origin`) after committing.
+  pub fn push_to(mut self, remote: &str) -> Self {
+    self.remote = Some(remote.to_string());
+    self
+  }
+
+  /// Sets the commit message.
+  pub fn message(mut self, message: &str) -> Self {
+    self.message = message.to_string();
+    self
+  }
+
+  fn git(&self, args: &[&str], index: &Path) -> Result<String> {
+    let output = Command::new("git")
+      .current_dir(&self.repo)
+      .env("GIT_INDEX_FILE", index)
+      .args(args)
+      .output()?;
+    if !output.status.success() {
+      return Err(deploy_error("git", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+  }
+}
+
+impl ExportTarget for GitBranch {
+  fn publish(&self, out_dir: &Path) -> Result<()> {
+    // A throwaway index, so the repository's own staging area is untouched,
+    // in a directory of its own so concurrent publishes don't share one.
+    let index_dir = tempfile::Builder::new().prefix("snapfire-export-").tempdir()?;
+    let index = index_dir.path().join("index");
+    let out_dir = out_dir.canonicalize()?;
+    let work_tree = format!("--work-tree={}", out_dir.display());
+    let reference = format!("refs/heads/{}", self.branch);
+
+    let result = (|| {
+      self.git(&[&work_tree, "add", "--all", "--force", "."], &index)?;
+      let tree = self.git(&["write-tree"], &index)?;
+      let parent = self.git(&["rev-parse", "--verify", "--quiet", &reference], &index).ok();
+
+      if let Some(parent) = &parent
+        && self.git(&["rev-parse", &format!("{parent}^{{tree}}")], &index)? == tree
+      {
+        log::info!("{} is already up to date", self.branch);
+      } else {
+        let mut args = vec!["commit-tree", tree.as_str(), "-m", self.message.as_str()];
+        if let Some(parent) = &parent {
+          args.extend(["-p", parent.as_str()]);
+        }
+        let commit = self.git(&args, &index)?;
+        self.git(&["update-ref", &reference, &commit], &index)?;
+        log::info!("Committed the site to {} ({})", self.branch, commit);
+      }
+
+      if let Some(remote) = &self.remote {
+        self.git(&["push", remote, &reference], &index)?;
+      }
+      Ok(())
+    })();
+
+    let _ = index_dir.close();
+    result
+  }
+}
+
+/// Every file under `out_dir`, relative to it, sorted.
+fn site_files(out_dir: &Path) -> Result<Vec<PathBuf>> {
+  fn walk(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+      let path = entry?.path();
+      if path.is_dir() {
+        walk(root, &path, files)?;
+      } else {
+        files.push(path.strip_prefix(root).expect("walked from the root").to_path_buf());
+      }
+    }
+    Ok(())
+  }
+
+  let mut files = Vec::new();
+  walk(out_dir, out_dir, &mut files)?;
+  files.sort();
+  Ok(files)
+}
+
+/// A relative file path with `/` separators, as used in URLs and keys.
+fn url_path(file: &Path) -> String {
+  file
+    .components()
+    .map(|component| component.as_os_str().to_string_lossy())
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+/// Percent-encodes a URL path, keeping `/` and the unreserved characters.
+fn uri_encode(path: &str) -> String {
+  path
+    .bytes()
+    .map(|byte| match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+      _ => format!("%{byte:02X}"),
+    })
+    .collect()
+}
+
+fn deploy_error(target: &str, error: impl std::fmt::Display) -> SnapFireError {
+  SnapFireError::Deploy {
+    target: target.to_string(),
+    message: error.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_commits_to_git_branch() {
+    let temp_dir = tempdir().unwrap();
+    let repo = temp_dir.path().join("repo");
+    let site = temp_dir.path().join("site");
+    fs::create_dir_all(site.join("about")).unwrap();
+    fs::write(site.join("index.html"), "home").unwrap();
+    fs::write(site.join("about/index.html"), "about").unwrap();
+
+    let git = |args: &[&str]| {
+      let output = Command::new("git").current_dir(&repo).args(args).output().unwrap();
+      assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+      String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    fs::create_dir(&repo).unwrap();
+    git(&["init", "--quiet"]);
+    git(&["config", "user.name", "Test"]);
+    git(&["config", "user.email", "test@example.com"]);
+
+    let target = GitBranch::new(&repo, "gh-pages").message("Deploy");
+    target.publish(&site).unwrap();
+    assert_eq!(git(&["show", "gh-pages:about/index.html"]), "about");
+
+    // An unchanged site doesn't add a commit; a changed one builds on the last.
+    target.publish(&site).unwrap();
+    assert_eq!(git(&["rev-list", "--count", "gh-pages"]), "1");
+    fs::write(site.join("index.html"), "new home").unwrap();
+    target.publish(&site).unwrap();
+    assert_eq!(git(&["rev-list", "--count", "gh-pages"]), "2");
+    assert_eq!(git(&["show", "gh-pages:index.html"]), "new home");
+  }
+}
@@ -0,0 +1,62 @@
+//! Desktop notifications of failed dev reloads. Only available with the
+//! `notifications` feature.
+//!
+//! A template that stops parsing is only reported in the terminal running
+//! the server, which is easy to miss with the editor in front and the
+//! browser showing the last good page. With
+//! `TeraWebBuilder::desktop_notifications(true)`, each failed template or
+//! data file reload also raises a notification through the OS (D-Bus on
+//! Linux and BSD, Notification Center on macOS, toasts on Windows).
+
+use crate::error::SnapFireError;
+use notify_rust::Notification;
+use std::error::Error;
+
+/// The longest message shown, in characters; notification servers clip
+/// long bodies anyway.
+const MAX_BODY_CHARS: usize = 300;
+
+/// Shows a notification that reloading `what` failed with `error`. A
+/// notification that can't be shown is only logged.
+pub(crate) fn notify_failure(what: &str, error: &SnapFireError) {
+  let shown = Notification::new()
+    .appname("SnapFire")
+    .summary(&format!("Failed to reload {what}"))
+    .body(&body(error))
+    .show();
+  if let Err(e) = shown {
+    log::debug!("Failed to show a desktop notification: {}", e);
+  }
+}
+
+/// The notification text for `error`: its chain of causes, each once, cut
+/// short.
+fn body(error: &SnapFireError) -> String {
+  let mut message = error.to_string();
+  let mut source = error.source();
+  while let Some(cause) = source {
+    let text = cause.to_string();
+    if !message.contains(&text) {
+      message.push_str(": ");
+      message.push_str(&text);
+    }
+    source = cause.source();
+  }
+  match message.char_indices().nth(MAX_BODY_CHARS) {
+    Some((end, _)) => format!("{}…", &message[..end]),
+    None => message,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_body_is_the_error_chain_cut_short() {
+    let error = SnapFireError::Tera(tera::Error::chain("Failed to parse `index.html`", tera::Error::msg("unexpected `}}`")));
+    assert_eq!(body(&error), "Tera rendering error: Failed to parse `index.html`: unexpected `}}`");
+    let long = SnapFireError::Tera(tera::Error::msg("é".repeat(400)));
+    assert_eq!(body(&long).chars().count(), MAX_BODY_CHARS + 1);
+  }
+}
@@ -0,0 +1,87 @@
+//! The per-request rendering environment.
+//!
+//! A [`RenderEnv`] gathers what a page is rendered *for*: the locale,
+//! timezone, currency, theme, and tenant. Site-wide defaults are set with
+//! `TeraWebBuilder::render_env`; middleware resolves the request's own once
+//! (from a cookie, the user's settings, the subdomain...) and stores it in the
+//! request extensions, where the Actix `Template` responder picks it up:
+//!
+//! ```rust,no_run
+//! # use actix_web::{dev::ServiceRequest, HttpMessage};
+//! # use snapfire::core::env::RenderEnv;
+//! # fn resolve(req: &ServiceRequest) {
+//! req.extensions_mut().insert(RenderEnv {
+//!   timezone: Some("Europe/Paris".to_string()),
+//!   theme: Some("dark".to_string()),
+//!   ..RenderEnv::default()
+//! });
+//! # }
+//! ```
+//!
+//! Fields the request leaves unset fall back to the defaults, and the
+//! locale to the one `LocalePrefix` detected. Templates see the result as
+//! `env`, e.g. `{{ post.date | date(timezone=env.timezone) }}` or
+//! `<html data-theme="{{ env.theme }}">`, and locale-aware helpers such as
+//! `plural` and `dir` read `env.locale` when the page sets no `locale`.
+
+use serde::Serialize;
+
+/// The locale, timezone, currency, theme, and tenant a page is rendered for.
+///
+/// Unset fields render as `null`, so templates can test them with `{% if
+/// env.theme %}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RenderEnv {
+  /// A BCP 47 language tag, e.g. `fr` or `pt-BR`.
+  pub locale: Option<String>,
+  /// An IANA timezone name, e.g. `Europe/Paris`.
+  pub timezone: Option<String>,
+  /// An ISO 4217 currency code, e.g. `EUR`.
+  pub currency: Option<String>,
+  /// The name of the visual theme, e.g. `dark`.
+  pub theme: Option<String>,
+  /// The tenant the request belongs to, in multi-tenant sites.
+  pub tenant: Option<String>,
+}
+
+impl RenderEnv {
+  /// This environment, with the fields it leaves unset taken from `defaults`.
+  pub fn or(self, defaults: &RenderEnv) -> RenderEnv {
+    let or = |value: Option<String>, default: &Option<String>| value.or_else(|| default.clone());
+    RenderEnv {
+      locale: or(self.locale, &defaults.locale),
+      timezone: or(self.timezone, &defaults.timezone),
+      currency: or(self.currency, &defaults.currency),
+      theme: or(self.theme, &defaults.theme),
+      tenant: or(self.tenant, &defaults.tenant),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_request_values_win_over_defaults() {
+    let defaults = RenderEnv {
+      locale: Some("en".to_string()),
+      currency: Some("USD".to_string()),
+      ..RenderEnv::default()
+    };
+    let request = RenderEnv {
+      currency: Some("EUR".to_string()),
+      tenant: Some("acme".to_string()),
+      ..RenderEnv::default()
+    };
+    assert_eq!(
+      request.or(&defaults),
+      RenderEnv {
+        locale: Some("en".to_string()),
+        currency: Some("EUR".to_string()),
+        tenant: Some("acme".to_string()),
+        ..RenderEnv::default()
+      }
+    );
+  }
+}
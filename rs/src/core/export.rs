@@ -0,0 +1,429 @@
+//! Static export with incremental rebuilds.
+//!
+//! [`StaticExport`] renders a list of pages to files in an output directory.
+//! While building, it records which templates (the page's own, the ones it
+//! extends, includes, or imports macros from) and data files each page was
+//! rendered from. After a change, only the pages depending on the changed
+//! inputs are rendered again, and [`StaticExport::watch`] keeps the output in
+//! sync during development by polling the inputs.
+//!
+//! Page URLs map to files the way static hosts serve them: `/` becomes
+//! `index.html`, `/about` becomes `about/index.html`, and URLs with an
+//! extension (`/feed.xml`) are written as-is.
+
+use crate::core::app::{RequestParts, TeraWeb, reload_templates};
+use crate::error::{Result, SnapFireError};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tera::ast::Node;
+use tera::{Context, Tera};
+
+/// A page to export.
+#[derive(Debug, Clone)]
+pub struct ExportPage {
+  pub(crate) url: String,
+  pub(crate) template: String,
+  pub(crate) context: Context,
+  pub(crate) data_files: Vec<PathBuf>,
+}
+
+impl ExportPage {
+  /// A page served at `url` and rendered from `template`.
+  pub fn new<U: Into<String>, T: Into<String>>(url: U, template: T) -> Self {
+    Self {
+      url: url.into(),
+      template: template.into(),
+      context: Context::new(),
+      data_files: Vec::new(),
+    }
+  }
+
+  /// Sets the context the page is rendered with.
+  pub fn context(mut self, context: Context) -> Self {
+    self.context = context;
+    self
+  }
+
+  /// Adds a YAML or JSON file whose top-level keys are merged into the
+  /// context. The file is re-read, and the page re-rendered, when it changes.
+  pub fn data_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+    self.data_files.push(path.into());
+    self
+  }
+
+  /// The file the page is written to, relative to the output directory.
+  pub fn output_path(&self) -> Result<PathBuf> {
+    let trimmed = self.url.trim_matches('/');
+    let mut path = PathBuf::new();
+    for component in Path::new(trimmed).components() {
+      match component {
+        Component::Normal(part) => path.push(part),
+        _ => {
+          return Err(SnapFireError::Config {
+            path: self.url.clone(),
+            message: "export URLs must not contain `.`, `..`, or absolute components".to_string(),
+          });
+        }
+      }
+    }
+    if path.extension().is_none() {
+      path.push("index.html");
+    }
+    Ok(path)
+  }
+}
+
+/// Reads a list of pages from a YAML (or JSON) file, for the `snapfire export` command:
+///
+/// ```yaml
+/// - url: /
+///   template: index.html
+///   data: [data/home.yaml]
+/// - url: /about
+///   template: about.html
+///   context: { title: About }
+/// ```
+///
+/// Data file paths are relative to the pages file.
+pub fn load_pages(path: &Path) -> Result<Vec<ExportPage>> {
+  #[derive(Deserialize)]
+  struct PageSpec {
+    url: String,
+    template: String,
+    #[serde(default)]
+    context: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    data: Vec<PathBuf>,
+  }
+
+  let config_error = |message: String| SnapFireError::Config {
+    path: path.display().to_string(),
+    message,
+  };
+  let source = std::fs::read_to_string(path).map_err(|e| config_error(e.to_string()))?;
+  let specs: Vec<PageSpec> = serde_yaml::from_str(&source).map_err(|e| config_error(e.to_string()))?;
+  let base = path.parent().unwrap_or(Path::new(""));
+
+  specs
+    .into_iter()
+    .map(|spec| {
+      let context =
+        Context::from_value(serde_json::Value::Object(spec.context)).map_err(|e| config_error(e.to_string()))?;
+      let page = ExportPage::new(spec.url, spec.template).context(context);
+      Ok(spec.data.into_iter().fold(page, |page, data| page.data_file(base.join(data))))
+    })
+    .collect()
+}
+
+/// A destination the exported site can be published to, such as a bucket, a
+/// hosting provider, or a git branch.
+///
+/// Implementations for S3, Netlify, and git branches live in
+/// `snapfire::core::deploy` (with the `deploy` feature).
+pub trait ExportTarget {
+  /// Publishes the contents of `out_dir`, the whole exported site.
+  fn publish(&self, out_dir: &Path) -> Result<()>;
+}
+
+/// What an export run wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportReport {
+  /// The files written, relative to the output directory.
+  pub written: Vec<PathBuf>,
+  /// The pages left untouched because none of their inputs changed.
+  pub skipped: usize,
+}
+
+/// An export of pages to a directory, able to rebuild only what changed.
+pub struct StaticExport {
+  app: TeraWeb,
+  out_dir: PathBuf,
+  pages: Vec<ExportPage>,
+  /// For each page, the templates it was last rendered from.
+  dependencies: Vec<BTreeSet<String>>,
+  /// The modification time of every input at the last build.
+  modified: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl StaticExport {
+  /// Prepares an export of `pages` into `out_dir`. Nothing is written until
+  /// `build` is called.
+  pub fn new<P: Into<PathBuf>>(app: &TeraWeb, out_dir: P, pages: Vec<ExportPage>) -> Self {
+    Self {
+      app: app.clone(),
+      out_dir: out_dir.into(),
+      dependencies: vec![BTreeSet::new(); pages.len()],
+      pages,
+      modified: HashMap::new(),
+    }
+  }
+
+  /// Publishes the output directory to `target`, typically after `build`.
+  pub fn publish(&self, target: &dyn ExportTarget) -> Result<()> {
+    target.publish(&self.out_dir)
+  }
+
+  /// Renders every page.
+  pub fn build(&mut self) -> Result<ExportReport> {
+    self.render((0..self.pages.len()).collect())
+  }
+
+  /// Re-renders only the pages depending on the `changed` files (templates or
+  /// data files). Changed templates are reloaded first.
+  pub fn rebuild(&mut self, changed: &[PathBuf]) -> Result<ExportReport> {
+    let changed: BTreeSet<PathBuf> = changed.iter().map(|path| canonical(path)).collect();
+    let tera = self.app.tera.read().clone();
+    let changed_templates: BTreeSet<String> = template_files(&tera)
+      .into_iter()
+      .filter(|(_, path)| changed.contains(path))
+      .map(|(name, _)| name)
+      .collect();
+
+    if !changed_templates.is_empty() {
+      reload_templates(&self.app.tera, "static export")?;
+    }
+
+    let affected = (0..self.pages.len())
+      .filter(|index| {
+        !self.dependencies[*index].is_disjoint(&changed_templates)
+          || self.pages[*index].data_files.iter().any(|data| changed.contains(&canonical(data)))
+      })
+      .collect();
+    self.render(affected)
+  }
+
+  /// Checks the inputs of every page for modifications since the last build
+  /// and rebuilds the affected pages. Returns `None` if nothing changed.
+  pub fn poll(&mut self) -> Result<Option<ExportReport>> {
+    let changed: Vec<PathBuf> = self
+      .modified
+      .iter()
+      .filter(|(path, modified)| modified_time(path) != **modified)
+      .map(|(path, _)| path.clone())
+      .collect();
+
+    if changed.is_empty() {
+      return Ok(None);
+    }
+    self.rebuild(&changed).map(Some)
+  }
+
+  /// Builds everything, then keeps the output in sync with the inputs,
+  /// checking for changes every `interval`. Only returns on a failed initial
+  /// build; later failures are logged and the previous output is kept.
+  pub fn watch(&mut self, interval: Duration) -> Result<()> {
+    let report = self.build()?;
+    log::info!("Exported {} page(s) to {}", report.written.len(), self.out_dir.display());
+
+    loop {
+      std::thread::sleep(interval);
+      match self.poll() {
+        Ok(Some(report)) => log::info!(
+          "Re-exported {} page(s), {} unchanged",
+          report.written.len(),
+          report.skipped
+        ),
+        Ok(None) => {}
+        Err(e) => log::error!("Export failed: {}", e),
+      }
+    }
+  }
+
+  fn render(&mut self, indices: Vec<usize>) -> Result<ExportReport> {
+    let tera = self.app.tera.read().clone();
+    let files = template_files(&tera);
+    let mut report = ExportReport {
+      written: Vec::with_capacity(indices.len()),
+      skipped: self.pages.len() - indices.len(),
+    };
+
+    for index in indices {
+      let page = &self.pages[index];
+      let mut context = page.context.clone();
+      for data in &page.data_files {
+        context.extend(load_data(data)?);
+      }
+
+      let request = RequestParts {
+        path: Some(&page.url),
+        ..RequestParts::default()
+      };
+      let html = self.app.render_for_request(&page.template, request, context)?;
+
+      let relative = page.output_path()?;
+      let target = self.out_dir.join(&relative);
+      if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+      }
+      std::fs::write(&target, html)?;
+      report.written.push(relative);
+
+      // Record what the page was rendered from, for the next rebuild.
+      let dependencies = template_dependencies(&tera, &page.template);
+      let inputs = dependencies
+        .iter()
+        .filter_map(|name| files.get(name).cloned())
+        .chain(page.data_files.iter().map(|data| canonical(data)));
+      for input in inputs {
+        let modified = modified_time(&input);
+        self.modified.insert(input, modified);
+      }
+      self.dependencies[index] = dependencies;
+    }
+
+    Ok(report)
+  }
+}
+
+impl TeraWeb {
+  /// Renders `pages` into `out_dir` for static hosting.
+  ///
+  /// For incremental rebuilds or a watch mode, use `StaticExport` directly.
+  pub fn export_static<P: Into<PathBuf>>(&self, out_dir: P, pages: Vec<ExportPage>) -> Result<ExportReport> {
+    StaticExport::new(self, out_dir, pages).build()
+  }
+}
+
+/// The template `tpl` and every template it extends, includes, or imports
+/// macros from, transitively.
+fn template_dependencies(tera: &Tera, tpl: &str) -> BTreeSet<String> {
+  let mut seen = BTreeSet::new();
+  let mut pending = vec![tpl.to_string()];
+
+  while let Some(name) = pending.pop() {
+    if !seen.insert(name.clone()) {
+      continue;
+    }
+    let Ok(template) = tera.get_template(&name) else {
+      continue;
+    };
+    pending.extend(template.parents.iter().cloned());
+    pending.extend(template.imported_macro_files.iter().map(|(file, _)| file.clone()));
+    collect_includes(&template.ast, &mut pending);
+  }
+  seen
+}
+
+fn collect_includes(nodes: &[Node], includes: &mut Vec<String>) {
+  for node in nodes {
+    match node {
+      Node::Include(_, names, _) => includes.extend(names.iter().cloned()),
+      Node::MacroDefinition(_, definition, _) => collect_includes(&definition.body, includes),
+      Node::FilterSection(_, section, _) => collect_includes(&section.body, includes),
+      Node::Block(_, block, _) => collect_includes(&block.body, includes),
+      Node::Forloop(_, forloop, _) => {
+        collect_includes(&forloop.body, includes);
+        if let Some(empty_body) = &forloop.empty_body {
+          collect_includes(empty_body, includes);
+        }
+      }
+      Node::If(condition, _) => {
+        for (_, _, body) in &condition.conditions {
+          collect_includes(body, includes);
+        }
+        if let Some((_, body)) = &condition.otherwise {
+          collect_includes(body, includes);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// The file each template was loaded from. Bundled templates have none.
+fn template_files(tera: &Tera) -> HashMap<String, PathBuf> {
+  tera
+    .get_template_names()
+    .filter_map(|name| {
+      let path = tera.get_template(name).ok()?.path.as_ref()?;
+      Some((name.to_string(), canonical(Path::new(path))))
+    })
+    .collect()
+}
+
+pub(crate) fn load_data(path: &Path) -> Result<Context> {
+  let config_error = |message: String| SnapFireError::Config {
+    path: path.display().to_string(),
+    message,
+  };
+  let source = std::fs::read_to_string(path).map_err(|e| config_error(e.to_string()))?;
+  let value: serde_json::Value = serde_yaml::from_str(&source).map_err(|e| config_error(e.to_string()))?;
+  Context::from_value(value).map_err(|e| config_error(e.to_string()))
+}
+
+fn canonical(path: &Path) -> PathBuf {
+  path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+  std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_output_paths() {
+    let path = |url: &str| ExportPage::new(url, "x.html").output_path();
+    assert_eq!(path("/").unwrap(), PathBuf::from("index.html"));
+    assert_eq!(path("/docs/guide/").unwrap(), PathBuf::from("docs/guide/index.html"));
+    assert_eq!(path("/feed.xml").unwrap(), PathBuf::from("feed.xml"));
+    assert!(path("/../etc/passwd").is_err());
+  }
+
+  #[test]
+  fn test_rebuilds_only_affected_pages() {
+    let temp_dir = tempdir().unwrap();
+    let templates = temp_dir.path().join("templates");
+    fs::create_dir(&templates).unwrap();
+    fs::write(templates.join("base.html"), "<main>{% block body %}{% endblock %}</main>").unwrap();
+    fs::write(templates.join("footer.html"), "<footer>v1</footer>").unwrap();
+    fs::write(
+      templates.join("post.html"),
+      "{% extends \"base.html\" %}{% block body %}{{ title }}{% if true %}{% include \"footer.html\" %}{% endif %}{% endblock %}",
+    )
+    .unwrap();
+    fs::write(templates.join("about.html"), "About {{ name }}").unwrap();
+    let data = temp_dir.path().join("about.yaml");
+    fs::write(&data, "name: Ada").unwrap();
+
+    let glob_path = templates.join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path).build().unwrap();
+    let mut context = Context::new();
+    context.insert("title", "Hello");
+    let out = temp_dir.path().join("out");
+    let mut export = StaticExport::new(
+      &app,
+      &out,
+      vec![
+        ExportPage::new("/", "post.html").context(context),
+        ExportPage::new("/about", "about.html").data_file(&data),
+      ],
+    );
+
+    let report = export.build().unwrap();
+    assert_eq!(report.written.len(), 2);
+    assert_eq!(fs::read_to_string(out.join("index.html")).unwrap(), "<main>Hello<footer>v1</footer></main>");
+    assert_eq!(fs::read_to_string(out.join("about/index.html")).unwrap(), "About Ada");
+
+    // An included template only affects the page including it.
+    fs::write(templates.join("footer.html"), "<footer>v2</footer>").unwrap();
+    let report = export.rebuild(&[templates.join("footer.html")]).unwrap();
+    assert_eq!(report.written, [PathBuf::from("index.html")]);
+    assert_eq!(report.skipped, 1);
+    assert_eq!(fs::read_to_string(out.join("index.html")).unwrap(), "<main>Hello<footer>v2</footer></main>");
+
+    // So does a data file.
+    fs::write(&data, "name: Grace").unwrap();
+    let report = export.rebuild(std::slice::from_ref(&data)).unwrap();
+    assert_eq!(report.written, [PathBuf::from("about/index.html")]);
+    assert_eq!(fs::read_to_string(out.join("about/index.html")).unwrap(), "About Grace");
+
+    assert_eq!(export.poll().unwrap(), None);
+  }
+}
@@ -0,0 +1,125 @@
+//! Example contexts for templates, read from JSON files in dev mode.
+//!
+//! A template's fixtures are the `.json` files in the directory named after
+//! it with `.fixtures` appended, one context object per file:
+//!
+//! ```text
+//! templates/user.html
+//! templates/user.html.fixtures/default.json
+//! templates/user.html.fixtures/admin.json
+//! ```
+//!
+//! `configure_routes` serves `/_snapfire/preview/user.html?fixture=admin` in
+//! dev mode, which renders the template like a page with that fixture, so
+//! templates can be built before their handlers exist. Without `fixture`,
+//! `default` is used if there is one, and an empty context otherwise. The
+//! styleguide shows every fixture of a component as an example. Editing a
+//! fixture reloads the page.
+
+#[cfg(feature = "actix")]
+use crate::core::app::TeraWeb;
+#[cfg(feature = "actix")]
+use serde_json::Value;
+use std::path::Path;
+#[cfg(feature = "actix")]
+use std::path::PathBuf;
+#[cfg(feature = "actix")]
+use tera::Context;
+
+/// Where previews are served, followed by the template name.
+#[cfg(feature = "actix")]
+pub(crate) const PREVIEW_PATH: &str = "/_snapfire/preview";
+
+/// The fixture used when a preview doesn't name one.
+#[cfg(feature = "actix")]
+const DEFAULT_FIXTURE: &str = "default";
+
+/// Why a preview can't be rendered.
+#[cfg(feature = "actix")]
+#[derive(Debug)]
+pub(crate) enum PreviewError {
+  /// The template or fixture doesn't exist.
+  NotFound(String),
+  /// The fixture can't be read or isn't a JSON object.
+  Invalid(String),
+}
+
+#[cfg(feature = "actix")]
+impl TeraWeb {
+  /// The context to preview `tpl` with: its fixture `name`, or `default`
+  /// (if there is one) when no name is given.
+  pub(crate) fn preview_context(&self, tpl: &str, name: Option<&str>) -> Result<Context, PreviewError> {
+    let set = self.tera.read().clone();
+    let template = set
+      .get_template(tpl)
+      .map_err(|_| PreviewError::NotFound(format!("There is no template `{tpl}`")))?;
+    let dir = template.path.as_deref().map(fixture_dir);
+    let available = dir.as_deref().map(names).unwrap_or_default();
+
+    let name = match name {
+      Some(name) => name,
+      None if available.iter().any(|fixture| fixture == DEFAULT_FIXTURE) => DEFAULT_FIXTURE,
+      None => return Ok(Context::new()),
+    };
+    let Some(dir) = dir.filter(|_| available.iter().any(|fixture| fixture == name)) else {
+      return Err(PreviewError::NotFound(format!(
+        "`{tpl}` has no fixture `{name}`; available: {}",
+        if available.is_empty() { "none".to_string() } else { available.join(", ") }
+      )));
+    };
+    load(&dir.join(format!("{name}.json"))).map_err(PreviewError::Invalid)
+  }
+}
+
+/// The fixture directory of the template file at `path`.
+#[cfg(feature = "actix")]
+pub(crate) fn fixture_dir(path: &str) -> PathBuf {
+  PathBuf::from(format!("{path}.fixtures"))
+}
+
+/// The names of the fixtures in `dir`, sorted; none if it doesn't exist.
+#[cfg(feature = "actix")]
+pub(crate) fn names(dir: &Path) -> Vec<String> {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return Vec::new();
+  };
+  let mut names: Vec<String> = entries
+    .filter_map(|entry| {
+      let path = entry.ok()?.path();
+      if path.extension()? != "json" {
+        return None;
+      }
+      path.file_stem()?.to_str().map(str::to_string)
+    })
+    .collect();
+  names.sort_unstable();
+  names
+}
+
+/// Reads the fixture file at `path` as a context.
+#[cfg(feature = "actix")]
+pub(crate) fn load(path: &Path) -> Result<Context, String> {
+  let source = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+  let value: Value = serde_json::from_str(&source).map_err(|e| format!("Invalid fixture {}: {}", path.display(), e))?;
+  to_context(value).map_err(|message| format!("Invalid fixture {}: {}", path.display(), message))
+}
+
+/// A fixture value as a context; it must be an object.
+#[cfg(feature = "actix")]
+pub(crate) fn to_context(value: Value) -> Result<Context, String> {
+  if !value.is_object() {
+    return Err("expected an object".to_string());
+  }
+  Context::from_value(value).map_err(|e| e.to_string())
+}
+
+/// Whether `path` is a fixture: a `.json` file in a fixture directory, or
+/// next to a template (the styleguide's single-file fixtures).
+pub(crate) fn is_fixture(path: &Path) -> bool {
+  path.extension().is_some_and(|extension| extension == "json")
+    && (path
+      .parent()
+      .and_then(Path::to_str)
+      .is_some_and(|parent| parent.ends_with(".fixtures"))
+      || ["html", "tera", "jinja"].iter().any(|extension| path.with_extension(extension).exists()))
+}
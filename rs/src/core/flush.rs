@@ -0,0 +1,308 @@
+//! Sending the start of a page before the rest has rendered.
+//!
+//! With `TeraWebBuilder::early_flush(true)`, the Actix `Template` responder
+//! renders on a blocking thread and streams the page in chunks: one ending
+//! at `</head>`, so the browser fetches the stylesheets and scripts it links
+//! while the body's data is still being rendered, and one at each
+//! `{{ flush() }}` a template places further down:
+//!
+//! ```jinja
+//! </head>
+//! <body>
+//!   {% include "header.html" %}
+//!   {{ flush() }}
+//!   {% for row in slow_report() %}...{% endfor %}
+//! ```
+//!
+//! `flush()` renders nothing itself, and nothing at all outside early-flush
+//! renders. Post-processors need the whole page, so with any configured
+//! (e.g. `strip_comments`) pages are sent in one piece as before. Once the
+//! first chunk is sent the status is fixed, so a later render error can
+//! only cut the response short; it is logged.
+
+#[cfg(feature = "actix")]
+use crate::error::{Result, SnapFireError};
+#[cfg(feature = "actix")]
+use bytes::Bytes;
+#[cfg(feature = "actix")]
+use futures_util::Stream;
+#[cfg(feature = "actix")]
+use parking_lot::Mutex;
+use std::cell::Cell;
+use std::collections::HashMap;
+#[cfg(feature = "actix")]
+use std::collections::VecDeque;
+#[cfg(feature = "actix")]
+use std::io::{self, Write};
+#[cfg(feature = "actix")]
+use std::pin::Pin;
+#[cfg(feature = "actix")]
+use std::sync::Arc;
+#[cfg(feature = "actix")]
+use std::task::{Context, Poll, Waker};
+use tera::{Function, Value};
+
+/// What `flush()` renders during an early-flush render, removed again by
+/// [`FlushWriter`].
+pub(crate) const FLUSH_MARKER: &[u8] = b"<!--snapfire:flush-->";
+
+/// The end of the head, sent along with the chunk before it.
+#[cfg(feature = "actix")]
+const HEAD_END: &[u8] = b"</head>";
+
+thread_local! {
+  static FLUSHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` with `flush()` emitting markers on this thread.
+#[cfg(feature = "actix")]
+pub(crate) fn with_flushing<R>(f: impl FnOnce() -> R) -> R {
+  /// Restores the outer value, even if the render panics.
+  struct Restore(bool);
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      FLUSHING.with(|flushing| flushing.set(self.0));
+    }
+  }
+
+  let _restore = Restore(FLUSHING.with(|flushing| flushing.replace(true)));
+  f()
+}
+
+/// Whether this thread is in an early-flush render.
+pub(crate) fn is_flushing() -> bool {
+  FLUSHING.with(Cell::get)
+}
+
+/// The `flush()` Tera function.
+pub(crate) struct FlushFunction;
+
+impl Function for FlushFunction {
+  fn call(&self, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let marker = if is_flushing() {
+      std::str::from_utf8(FLUSH_MARKER).expect("the marker is ASCII")
+    } else {
+      ""
+    };
+    Ok(Value::String(marker.to_string()))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// A writer handing the output to `send` in chunks, cut after `</head>` and
+/// at each flush marker.
+#[cfg(feature = "actix")]
+pub(crate) struct FlushWriter<F: FnMut(Bytes)> {
+  pending: Vec<u8>,
+  /// How much of `pending` is known to hold no marker.
+  searched: usize,
+  send: F,
+}
+
+#[cfg(feature = "actix")]
+impl<F: FnMut(Bytes)> FlushWriter<F> {
+  pub(crate) fn new(send: F) -> Self {
+    Self {
+      pending: Vec::new(),
+      searched: 0,
+      send,
+    }
+  }
+
+  /// Sends whatever is left.
+  pub(crate) fn finish(mut self) {
+    if !self.pending.is_empty() {
+      (self.send)(Bytes::from(std::mem::take(&mut self.pending)));
+    }
+  }
+
+  /// The first marker at or after `from`: where it starts, and whether it's
+  /// the head's end (kept in the output) rather than a flush marker.
+  fn find_marker(&self, from: usize) -> Option<(usize, bool)> {
+    let rest = &self.pending[from..];
+    let find = |marker: &[u8]| rest.windows(marker.len()).position(|window| window == marker);
+    match (find(HEAD_END), find(FLUSH_MARKER)) {
+      (Some(head), Some(flush)) if flush < head => Some((from + flush, false)),
+      (Some(head), _) => Some((from + head, true)),
+      (None, Some(flush)) => Some((from + flush, false)),
+      (None, None) => None,
+    }
+  }
+}
+
+#[cfg(feature = "actix")]
+impl<F: FnMut(Bytes)> Write for FlushWriter<F> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.pending.extend_from_slice(buf);
+    // A marker may have started in the previous write.
+    let overlap = FLUSH_MARKER.len().max(HEAD_END.len()) - 1;
+    while let Some((start, is_head)) = self.find_marker(self.searched.saturating_sub(overlap)) {
+      let (end, skip) = if is_head {
+        (start + HEAD_END.len(), 0)
+      } else {
+        (start, FLUSH_MARKER.len())
+      };
+      let rest = self.pending.split_off(end + skip);
+      self.pending.truncate(end);
+      let chunk = std::mem::replace(&mut self.pending, rest);
+      if !chunk.is_empty() {
+        (self.send)(Bytes::from(chunk));
+      }
+      self.searched = 0;
+    }
+    self.searched = self.pending.len();
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// The chunks of a page rendering on another thread, as a stream.
+#[cfg(feature = "actix")]
+pub(crate) struct ChunkReceiver {
+  shared: Arc<Mutex<ChunkState>>,
+}
+
+/// Hands chunks to the [`ChunkReceiver`]; dropping it without `finish` ends
+/// the stream with an error.
+#[cfg(feature = "actix")]
+pub(crate) struct ChunkSender {
+  shared: Arc<Mutex<ChunkState>>,
+  finished: bool,
+}
+
+#[cfg(feature = "actix")]
+#[derive(Default)]
+struct ChunkState {
+  chunks: VecDeque<Bytes>,
+  /// How the render ended, once it has.
+  outcome: Option<Result<()>>,
+  waker: Option<Waker>,
+}
+
+/// A channel for the chunks of one render.
+#[cfg(feature = "actix")]
+pub(crate) fn chunk_channel() -> (ChunkSender, ChunkReceiver) {
+  let shared = Arc::new(Mutex::new(ChunkState::default()));
+  (
+    ChunkSender {
+      shared: Arc::clone(&shared),
+      finished: false,
+    },
+    ChunkReceiver { shared },
+  )
+}
+
+#[cfg(feature = "actix")]
+impl ChunkSender {
+  pub(crate) fn send(&self, chunk: Bytes) {
+    let mut state = self.shared.lock();
+    state.chunks.push_back(chunk);
+    if let Some(waker) = state.waker.take() {
+      waker.wake();
+    }
+  }
+
+  /// Ends the stream with the render's result.
+  pub(crate) fn finish(mut self, result: Result<()>) {
+    self.finished = true;
+    self.end(result);
+  }
+
+  fn end(&self, result: Result<()>) {
+    let mut state = self.shared.lock();
+    state.outcome = Some(result);
+    if let Some(waker) = state.waker.take() {
+      waker.wake();
+    }
+  }
+}
+
+#[cfg(feature = "actix")]
+impl Drop for ChunkSender {
+  fn drop(&mut self) {
+    if !self.finished {
+      self.end(Err(SnapFireError::Io(io::Error::other("the render stopped before the page was complete"))));
+    }
+  }
+}
+
+#[cfg(feature = "actix")]
+impl Stream for ChunkReceiver {
+  type Item = Result<Bytes>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    let mut state = self.shared.lock();
+    if let Some(chunk) = state.chunks.pop_front() {
+      return Poll::Ready(Some(Ok(chunk)));
+    }
+    match state.outcome.take() {
+      Some(Ok(())) => {
+        // Keep reporting the end on later polls.
+        state.outcome = Some(Ok(()));
+        Poll::Ready(None)
+      }
+      Some(Err(e)) => {
+        state.outcome = Some(Ok(()));
+        Poll::Ready(Some(Err(e)))
+      }
+      None => {
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+      }
+    }
+  }
+}
+
+#[cfg(all(test, feature = "actix"))]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_flushes_after_the_head_and_at_markers() {
+    let mut chunks = Vec::new();
+    let mut writer = FlushWriter::new(|chunk: Bytes| chunks.push(String::from_utf8(chunk.to_vec()).unwrap()));
+    // Markers split across writes are still found.
+    for part in ["<html><head><title>T</title></he", "ad><body>header", "<!--snapfire:", "flush-->rows", "</body></html>"] {
+      writer.write_all(part.as_bytes()).unwrap();
+    }
+    writer.finish();
+    assert_eq!(
+      chunks,
+      [
+        "<html><head><title>T</title></head>",
+        "<body>header",
+        "rows</body></html>"
+      ]
+    );
+
+    let flush = FlushFunction;
+    assert_eq!(flush.call(&HashMap::new()).unwrap(), Value::from(""));
+    let marker = with_flushing(|| flush.call(&HashMap::new()).unwrap());
+    assert_eq!(marker, Value::from("<!--snapfire:flush-->"));
+  }
+
+  #[test]
+  fn test_chunk_channel_ends_with_the_render() {
+    let mut context = Context::from_waker(Waker::noop());
+    let (sender, mut receiver) = chunk_channel();
+    assert!(Pin::new(&mut receiver).poll_next(&mut context).is_pending());
+    sender.send(Bytes::from_static(b"<head></head>"));
+    sender.finish(Ok(()));
+    assert!(matches!(Pin::new(&mut receiver).poll_next(&mut context), Poll::Ready(Some(Ok(_)))));
+    assert!(matches!(Pin::new(&mut receiver).poll_next(&mut context), Poll::Ready(None)));
+
+    let (sender, mut receiver) = chunk_channel();
+    drop(sender);
+    assert!(matches!(
+      Pin::new(&mut receiver).poll_next(&mut context),
+      Poll::Ready(Some(Err(SnapFireError::Io(_))))
+    ));
+    assert!(matches!(Pin::new(&mut receiver).poll_next(&mut context), Poll::Ready(None)));
+  }
+}
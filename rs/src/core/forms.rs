@@ -0,0 +1,51 @@
+//! Spam protection for forms.
+//!
+//! The `honeypot_field()` template function, registered on every app, adds a
+//! text input hidden from people (off screen, out of the tab order, and
+//! hidden from screen readers) that form-filling bots tend to complete:
+//!
+//! ```jinja
+//! <form method="post" action="/comments">
+//!   {{ honeypot_field() }}
+//!   <textarea name="body"></textarea>
+//! </form>
+//! ```
+//!
+//! The `SpamGuard` Actix middleware rejects submissions where it is filled
+//! in, and can limit how often each client submits. Both default to the
+//! field name [`DEFAULT_HONEYPOT_FIELD`]; pass `name="..."` to the function
+//! and the same name to `SpamGuard::honeypot_field` to change it.
+
+use std::collections::HashMap;
+use tera::{Function, Value};
+
+/// The default name of the honeypot input.
+pub const DEFAULT_HONEYPOT_FIELD: &str = "website";
+
+/// The markup of a honeypot input named `name`.
+pub fn honeypot_html(name: &str) -> String {
+  format!(
+    "<div style=\"position:absolute;left:-10000px;top:auto;width:1px;height:1px;overflow:hidden\" aria-hidden=\"true\">\
+     <label>Leave this field empty <input type=\"text\" name=\"{}\" value=\"\" tabindex=\"-1\" autocomplete=\"off\"></label></div>",
+    tera::escape_html(name)
+  )
+}
+
+/// The `honeypot_field(name=...)` Tera function.
+pub(crate) struct HoneypotField;
+
+impl Function for HoneypotField {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let name = match args.get("name") {
+      Some(name) => name
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("The `name` argument of `honeypot_field` must be a string"))?,
+      None => DEFAULT_HONEYPOT_FIELD,
+    };
+    Ok(Value::String(honeypot_html(name)))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
@@ -0,0 +1,393 @@
+//! Per-page metadata in a front-matter block at the top of templates.
+//!
+//! A template may start with YAML between `---` lines, or TOML between `+++`
+//! lines:
+//!
+//! ```text
+//! ---
+//! title: Our team
+//! layout: layouts/page.html
+//! cache: public, max-age=3600
+//! role: staff
+//! ---
+//! <h1>{{ page.title }}</h1>
+//! ```
+//!
+//! The block is blanked out before Tera parses the template, so error lines
+//! still match the file, and its values are
+//! available to the render as `page.*`, merged over the front matter of the
+//! templates it extends. A `page` object in the handler's context wins key by
+//! key. Some keys have a meaning of their own:
+//!
+//! - `layout`: a template to render the page in, when it doesn't `extends`
+//!   one itself. The page body becomes the layout's `content` block.
+//! - `cache`: the `Cache-Control` header `pages_handler` serves the page with.
+//! - `role`: the role `pages_handler` requires of the request (see
+//!   `snapfire::actix::UserRoles`).
+//!
+//! TOML dates and times are exposed as strings, as written.
+
+use crate::error::{Result, SnapFireError};
+use serde_json::{Map, Number, Value};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tera::{Context, Template, Tera};
+
+/// The front matter of a template.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontMatter(Map<String, Value>);
+
+impl FrontMatter {
+  /// The value of `key`, if set.
+  pub fn get(&self, key: &str) -> Option<&Value> {
+    self.0.get(key)
+  }
+
+  /// The page title, from `title`.
+  pub fn title(&self) -> Option<&str> {
+    self.str("title")
+  }
+
+  /// The layout template the page renders in, from `layout`.
+  pub fn layout(&self) -> Option<&str> {
+    self.str("layout")
+  }
+
+  /// The page's `Cache-Control` policy, from `cache`.
+  pub fn cache(&self) -> Option<&str> {
+    self.str("cache")
+  }
+
+  /// The role required to view the page, from `role`.
+  pub fn role(&self) -> Option<&str> {
+    self.str("role")
+  }
+
+  /// All values, as exposed to templates under `page`.
+  pub fn values(&self) -> &Map<String, Value> {
+    &self.0
+  }
+
+  fn str(&self, key: &str) -> Option<&str> {
+    self.0.get(key).and_then(Value::as_str)
+  }
+}
+
+/// The front matter of every template that has one, by template name.
+pub(crate) type PageIndex = HashMap<String, FrontMatter>;
+
+/// The front matter of `tpl` over that of the templates it extends, or
+/// `None` when none of them has any.
+pub(crate) fn merged(tera: &Tera, pages: &PageIndex, tpl: &str) -> Option<FrontMatter> {
+  if pages.is_empty() {
+    return None;
+  }
+  let parents = tera.get_template(tpl).map(|template| template.parents.as_slice()).unwrap_or_default();
+
+  // The parents are listed from the closest to the root.
+  let mut page: Option<FrontMatter> = None;
+  for name in parents.iter().rev().map(String::as_str).chain([tpl]) {
+    if let Some(front_matter) = pages.get(name) {
+      let page = page.get_or_insert_with(FrontMatter::default);
+      page.0.extend(front_matter.0.iter().map(|(key, value)| (key.clone(), value.clone())));
+    }
+  }
+  page
+}
+
+/// Adds `page` to `context`, under the values of a `page` object already there.
+pub(crate) fn insert_page(context: &mut Context, page: FrontMatter) {
+  let mut values = page.0;
+  match context.get("page") {
+    Some(Value::Object(own)) => values.extend(own.clone()),
+    Some(_) => return,
+    None => {}
+  }
+  context.insert("page", &values);
+}
+
+/// Splits the front matter off the top of a template source, returning it
+/// and the rest of the source.
+pub(crate) fn split(source: &str) -> std::result::Result<(Option<FrontMatter>, &str), String> {
+  let Some((delimiter, rest)) = ["---", "+++"].iter().find_map(|delimiter| {
+    let rest = source.strip_prefix(delimiter)?;
+    let rest = rest.trim_start_matches([' ', '\t']);
+    let rest = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n'))?;
+    Some((*delimiter, rest))
+  }) else {
+    return Ok((None, source));
+  };
+
+  // The block ends at the first line holding only the delimiter.
+  let mut offset = 0;
+  for line in rest.split_inclusive('\n') {
+    if line.trim_end() == delimiter {
+      let block = &rest[..offset];
+      let body = &rest[offset + line.len()..];
+      let values = match delimiter {
+        "---" => parse_yaml(block)?,
+        _ => parse_toml(block)?,
+      };
+      return Ok((Some(FrontMatter(values)), body));
+    }
+    offset += line.len();
+  }
+  Err(format!("The front matter has no closing `{delimiter}` line"))
+}
+
+fn parse_yaml(block: &str) -> std::result::Result<Map<String, Value>, String> {
+  if block.trim().is_empty() {
+    return Ok(Map::new());
+  }
+  match serde_yaml::from_str(block) {
+    Ok(Value::Object(values)) => Ok(values),
+    Ok(Value::Null) => Ok(Map::new()),
+    Ok(_) => Err("The front matter must be a mapping of keys to values".to_string()),
+    Err(e) => Err(format!("Invalid YAML front matter: {e}")),
+  }
+}
+
+fn parse_toml(block: &str) -> std::result::Result<Map<String, Value>, String> {
+  let table: toml::Table = block.parse().map_err(|e| format!("Invalid TOML front matter: {e}"))?;
+  Ok(table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect())
+}
+
+/// A TOML value as JSON, with dates and times as written.
+fn toml_to_json(value: toml::Value) -> Value {
+  match value {
+    toml::Value::String(string) => Value::String(string),
+    toml::Value::Integer(integer) => Value::from(integer),
+    toml::Value::Float(float) => Number::from_f64(float).map_or(Value::Null, Value::Number),
+    toml::Value::Boolean(boolean) => Value::Bool(boolean),
+    toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+    toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+    toml::Value::Table(table) => Value::Object(table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect()),
+  }
+}
+
+/// A template source to load, with the path it was read from, if any.
+pub(crate) struct RawTemplate {
+  pub(crate) name: String,
+  pub(crate) path: Option<PathBuf>,
+  pub(crate) source: String,
+}
+
+/// Parses `templates` into `tera` without their front matter, and returns
+/// the front matter found.
+///
+/// A template with a `layout` and no `extends` tag is wrapped in the layout's
+/// `content` block. Templates added through `Tera::extend` are kept; every
+/// other template is replaced, as in `Tera::full_reload`.
+pub(crate) fn load(tera: &mut Tera, templates: Vec<RawTemplate>) -> Result<PageIndex> {
+  tera.templates.retain(|_, template| template.from_extend);
+
+  let mut pages = PageIndex::new();
+  let mut errors = String::new();
+  for template in templates {
+    let path = template.path.as_ref().map(|path| path.to_string_lossy().into_owned());
+    let result = split(&template.source)
+      .map_err(|message| SnapFireError::Config {
+        path: path.clone().unwrap_or_else(|| template.name.clone()),
+        message,
+      })
+      .and_then(|(front_matter, body)| {
+        let lines = template.source[..template.source.len() - body.len()].matches('\n').count();
+        let body = with_layout(front_matter.as_ref(), lines, body);
+        let parsed = Template::new(&template.name, path.clone(), &body)
+          .map_err(|e| tera::Error::chain(format!("Failed to parse '{}'", template.name), e))?;
+        Ok((front_matter, parsed))
+      });
+
+    match result {
+      Ok((front_matter, parsed)) => {
+        if let Some(front_matter) = front_matter {
+          pages.insert(template.name.clone(), front_matter);
+        }
+        tera.templates.insert(template.name, parsed);
+      }
+      Err(e) => {
+        use std::error::Error as _;
+        errors.push_str(&format!("\n* {e}"));
+        let mut cause = e.source();
+        while let Some(e) = cause {
+          errors.push_str(&format!("\n{e}"));
+          cause = e.source();
+        }
+      }
+    }
+  }
+  if !errors.is_empty() {
+    return Err(tera::Error::msg(errors).into());
+  }
+
+  tera.build_inheritance_chains()?;
+  tera.check_macro_files()?;
+  Ok(pages)
+}
+
+/// The template sources matched by `glob`, named like Tera names them: by
+/// their path relative to the glob's base directory, with `/` separators.
+///
+/// Like Tera, a glob whose base directory doesn't exist matches nothing.
+pub(crate) fn glob_sources(glob: &str) -> Result<Vec<RawTemplate>> {
+  let (base, pattern) = glob.split_at(glob.find('*').unwrap_or(glob.len()));
+  let base = std::fs::canonicalize(base).unwrap_or_else(|_| PathBuf::from(base));
+  let full_glob = base.join(pattern).to_string_lossy().into_owned();
+
+  let walker = globwalk::glob_builder(&full_glob)
+    .follow_links(true)
+    .build()
+    .map_err(|e| SnapFireError::Config {
+      path: glob.to_string(),
+      message: e.to_string(),
+    })?;
+  let mut sources = Vec::new();
+  for path in walker.filter_map(std::result::Result::ok).map(|entry| entry.into_path()) {
+    if !path.is_file() {
+      continue;
+    }
+    let name = path
+      .strip_prefix(&base)
+      .unwrap_or(&path)
+      .to_string_lossy()
+      .replace('\\', "/");
+    let source = std::fs::read_to_string(&path)
+      .map_err(|e| tera::Error::chain(format!("Failed to read template '{}'", path.display()), e))?;
+    sources.push(RawTemplate {
+      name,
+      path: Some(path),
+      source,
+    });
+  }
+  Ok(sources)
+}
+
+/// The source to parse for a page body: the body itself, or, with a `layout`
+/// and no `extends` of its own, the body as the layout's `content` block.
+///
+/// The `lines` the front matter took are kept as a comment, so Tera reports
+/// errors on the lines they're on in the file.
+fn with_layout<'a>(front_matter: Option<&FrontMatter>, lines: usize, body: &'a str) -> Cow<'a, str> {
+  let blank = if lines == 0 { String::new() } else { format!("{{#{}#}}", "\n".repeat(lines)) };
+  match front_matter.and_then(FrontMatter::layout) {
+    Some(layout) if !extends_a_template(body) => Cow::Owned(format!(
+      "{{% extends \"{}\" %}}{{% block content %}}{blank}{body}{{% endblock content %}}",
+      layout.replace('"', "\\\"")
+    )),
+    _ if lines == 0 => Cow::Borrowed(body),
+    _ => Cow::Owned(format!("{blank}{body}")),
+  }
+}
+
+/// Whether the source starts (after comments and imports) with an `extends` tag.
+fn extends_a_template(body: &str) -> bool {
+  let mut rest = body.trim_start();
+  loop {
+    if let Some(after) = rest.strip_prefix("{#") {
+      let Some(end) = after.find("#}") else { return false };
+      rest = after[end + 2..].trim_start();
+      continue;
+    }
+    let Some(after) = rest.strip_prefix("{%") else {
+      return false;
+    };
+    let tag = after.trim_start_matches(['-', ' ', '\t', '\r', '\n']);
+    if tag.starts_with("extends") {
+      return true;
+    }
+    if !tag.starts_with("import") {
+      return false;
+    }
+    let Some(end) = after.find("%}") else { return false };
+    rest = after[end + 2..].trim_start();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_splits_yaml_and_toml_front_matter() {
+    let (front_matter, body) = split("---\ntitle: Team\nrole: staff\n---\n<h1>{{ page.title }}</h1>").unwrap();
+    let front_matter = front_matter.unwrap();
+    assert_eq!(front_matter.title(), Some("Team"));
+    assert_eq!(front_matter.role(), Some("staff"));
+    assert_eq!(body, "<h1>{{ page.title }}</h1>");
+
+    let source = "+++\r\ntitle = \"Team \\\"A\\\"\" # comment\r\ntags = ['a', \"b\"]\r\n[cache]\r\nmax_age = 3_600\r\nauthor = { name = 'Ada', active = true }\r\n+++\r\nbody";
+    let (front_matter, body) = split(source).unwrap();
+    assert_eq!(
+      Value::Object(front_matter.unwrap().0),
+      json!({
+        "title": "Team \"A\"",
+        "tags": ["a", "b"],
+        "cache": { "max_age": 3600, "author": { "name": "Ada", "active": true } },
+      })
+    );
+    assert_eq!(body, "body");
+
+    let source = "+++\ntags = [\n  'a',\n  'b',\n]\npublished = 2024-05-27\nmeta = { author = { name = \"Ren\\u00e9e\\tB.\" } }\n[[authors]]\nname = 'Ada'\n[[authors]]\nname = 'Grace'\n+++\n";
+    let (front_matter, _) = split(source).unwrap();
+    assert_eq!(
+      Value::Object(front_matter.unwrap().0),
+      json!({
+        "tags": ["a", "b"],
+        "published": "2024-05-27",
+        "meta": { "author": { "name": "Renée\tB." } },
+        "authors": [{ "name": "Ada" }, { "name": "Grace" }],
+      })
+    );
+
+    assert_eq!(split("--- not front matter").unwrap(), (None, "--- not front matter"));
+    assert!(split("---\ntitle: x\n").is_err());
+    assert!(split("---\n- a list\n---\n").is_err());
+    assert!(split("+++\ntitle = \"x\"\ntitle = \"y\"\n+++\n").is_err());
+  }
+
+  #[test]
+  fn test_layouts_and_inherited_front_matter() {
+    let sources = [
+      ("base.html", "---\nsection: Docs\ntitle: Docs\n---\n<main>{% block content %}{% endblock content %}</main>"),
+      ("page.html", "---\ntitle: Intro\nlayout: base.html\n---\n{# page #}Hello"),
+      ("child.html", "---\nlayout: other.html\n---\n{% extends \"base.html\" %}{% block content %}Child{% endblock content %}"),
+    ];
+    let mut tera = Tera::default();
+    let templates = sources
+      .iter()
+      .map(|(name, source)| RawTemplate {
+        name: name.to_string(),
+        path: None,
+        source: source.to_string(),
+      })
+      .collect();
+    let pages = load(&mut tera, templates).unwrap();
+
+    // The page renders inside its layout, which it inherits values from.
+    assert_eq!(tera.render("page.html", &Context::new()).unwrap(), "<main>Hello</main>");
+    let mut context = Context::new();
+    context.insert("page", &json!({ "title": "From the handler" }));
+    insert_page(&mut context, merged(&tera, &pages, "page.html").unwrap());
+    assert_eq!(
+      context.get("page").unwrap(),
+      &json!({ "section": "Docs", "title": "From the handler", "layout": "base.html" })
+    );
+    // An explicit `extends` wins over `layout`.
+    assert_eq!(tera.render("child.html", &Context::new()).unwrap(), "<main>Child</main>");
+  }
+
+  #[test]
+  fn test_errors_point_at_the_lines_in_the_file() {
+    for source in ["---\ntitle: Broken\n---\n<h1>\n{{ oops( }}", "---\nlayout: base.html\n---\n<h1>\n{{ oops( }}"] {
+      let template = RawTemplate {
+        name: "broken.html".to_string(),
+        path: None,
+        source: source.to_string(),
+      };
+      let error = load(&mut Tera::default(), vec![template]).unwrap_err().to_string();
+      assert!(error.contains("--> 5:"), "{error}");
+    }
+  }
+}
@@ -0,0 +1,55 @@
+//! Running a headless browser on a rendered page.
+//!
+//! Shared by the PDF and screenshot backends, which hand Chromium the page
+//! as a file and read back what it wrote.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Runs the browser `binary` headless on `html`, with `args` and
+/// `<output_flag>=<file>`, and returns the contents of the file. Errors are
+/// the message to report, such as the browser's error output.
+pub(crate) fn run_chromium(binary: &Path, args: &[String], output_flag: &str, extension: &str, html: &str) -> Result<Vec<u8>, String> {
+  // Chromium reads the page from a URL and writes its output to a file, both
+  // in a directory only this user can open, removed when it's dropped.
+  let dir = tempfile::Builder::new()
+    .prefix("snapfire-")
+    .tempdir()
+    .map_err(|e| format!("could not create a temporary directory: {e}"))?;
+  let input = dir.path().join("page.html");
+  let output = dir.path().join(format!("output.{extension}"));
+  std::fs::write(&input, html).map_err(|e| format!("could not write the page: {e}"))?;
+
+  let result = Command::new(binary)
+    .args(["--headless", "--disable-gpu"])
+    .args(args)
+    .arg(format!("{}={}", output_flag, output.display()))
+    .arg(format!("file://{}", input.display()))
+    .stdin(Stdio::null())
+    .output()
+    .map_err(|e| e.to_string())?;
+  if !result.status.success() {
+    return Err(String::from_utf8_lossy(&result.stderr).trim().to_string());
+  }
+  std::fs::read(&output).map_err(|e| format!("no output was written: {e}"))
+}
+
+/// `html` with `head` inserted right after its `<head>` tag, or at the start
+/// when it has none.
+pub(crate) fn insert_in_head(html: &str, head: &str) -> String {
+  if head.is_empty() {
+    return html.to_string();
+  }
+  let lower = html.to_ascii_lowercase();
+  let at = match lower.find("<head") {
+    Some(start) => lower[start..].find('>').map_or(0, |end| start + end + 1),
+    None => 0,
+  };
+  format!("{}{}{}", &html[..at], head, &html[at..])
+}
+
+/// A `<base>` element for `url`, so relative links resolve as if the page
+/// were served there.
+pub(crate) fn base_element(url: &str) -> String {
+  format!("<base href=\"{}\">", url.replace('"', "&quot;"))
+}
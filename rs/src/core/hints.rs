@@ -0,0 +1,160 @@
+use crate::core::i18n::escape_attribute;
+use std::collections::HashMap;
+use std::fmt::Write;
+use tera::{Function, Tera, Value};
+
+/// The assets each template should preload, announced to the client before
+/// the body is rendered.
+///
+/// Assets are declared per template with `TeraWebBuilder::early_hints`. A
+/// template also inherits the assets declared for every template it extends,
+/// so declaring the site stylesheet on `base.html` covers every page.
+#[derive(Debug, Default)]
+pub(crate) struct EarlyHints {
+  /// Preformatted `Link` header entries, keyed by template name.
+  links: HashMap<String, Vec<String>>,
+}
+
+impl EarlyHints {
+  /// Declares `assets` for `tpl`.
+  pub(crate) fn add<I, S>(&mut self, tpl: &str, assets: I)
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    let links = self.links.entry(tpl.to_string()).or_default();
+    for asset in assets {
+      let link = preload_link(asset.as_ref());
+      if !links.contains(&link) {
+        links.push(link);
+      }
+    }
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.links.is_empty()
+  }
+
+  /// The `Link` header value for `tpl`, covering its own assets and those of
+  /// the templates it extends, or `None` if nothing is declared.
+  pub(crate) fn link_header(&self, tera: &Tera, tpl: &str) -> Option<String> {
+    if self.is_empty() {
+      return None;
+    }
+
+    let parents = tera.get_template(tpl).map(|template| template.parents.as_slice()).unwrap_or_default();
+    // Base templates first, so shared assets (the site stylesheet) lead.
+    let mut links: Vec<&str> = Vec::new();
+    for name in parents.iter().rev().map(String::as_str).chain([tpl]) {
+      for link in self.links.get(name).into_iter().flatten() {
+        if !links.contains(&link.as_str()) {
+          links.push(link);
+        }
+      }
+    }
+
+    (!links.is_empty()).then(|| links.join(", "))
+  }
+}
+
+/// Formats a `rel=preload` link, picking `as` from the file extension.
+fn preload_link(asset: &str) -> String {
+  let path = asset.split(['?', '#']).next().unwrap_or(asset);
+  let extension = path.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+  let destination = match extension.as_str() {
+    "css" => "style",
+    "js" | "mjs" => "script",
+    // Fonts are always fetched in CORS mode, so the preload must be too.
+    "woff2" | "woff" | "ttf" | "otf" => "font; crossorigin",
+    "png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "svg" | "ico" => "image",
+    _ => "fetch; crossorigin",
+  };
+  format!("<{asset}>; rel=preload; as={destination}")
+}
+
+/// The origin (`scheme://host[:port]`) of an absolute `http(s)` URL.
+pub(crate) fn origin_of(url: &str) -> Option<String> {
+  let (scheme, rest) = url.split_once("://")?;
+  let scheme = scheme.to_ascii_lowercase();
+  if scheme != "http" && scheme != "https" {
+    return None;
+  }
+  let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+  // Credentials don't belong in a hint, and an empty host isn't an origin.
+  if authority.is_empty() || authority.contains('@') {
+    return None;
+  }
+  Some(format!("{scheme}://{}", authority.to_ascii_lowercase()))
+}
+
+/// The `resource_hints()` Tera function: a `preconnect` link for each
+/// origin, with a `dns-prefetch` fallback for browsers without it.
+///
+/// The preconnects are `crossorigin`, matching the CORS-mode fetches that
+/// third-party origins usually serve: fonts, module scripts, and assets with
+/// `integrity`.
+pub(crate) struct ResourceHints(pub(crate) Vec<String>);
+
+impl Function for ResourceHints {
+  fn call(&self, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let mut html = String::new();
+    for origin in &self.0 {
+      let origin = escape_attribute(origin);
+      let _ = write!(
+        html,
+        "<link rel=\"preconnect\" href=\"{origin}\" crossorigin>\n<link rel=\"dns-prefetch\" href=\"{origin}\">\n"
+      );
+    }
+    Ok(Value::String(html))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_inherits_assets_from_parents() {
+    let mut tera = Tera::default();
+    tera
+      .add_raw_templates([
+        ("base.html", "{% block body %}{% endblock %}"),
+        ("page.html", "{% extends \"base.html\" %}"),
+      ])
+      .unwrap();
+
+    let mut hints = EarlyHints::default();
+    hints.add("base.html", ["/static/site.css"]);
+    hints.add("page.html", ["/static/page.js", "/static/site.css", "/fonts/inter.woff2"]);
+
+    assert_eq!(
+      hints.link_header(&tera, "page.html").unwrap(),
+      "</static/site.css>; rel=preload; as=style, </static/page.js>; rel=preload; as=script, \
+       </fonts/inter.woff2>; rel=preload; as=font; crossorigin"
+    );
+    assert_eq!(hints.link_header(&tera, "missing.html"), None);
+  }
+
+  #[test]
+  fn test_resource_hints_for_origins() {
+    assert_eq!(origin_of("HTTPS://CDN.example.com/static/"), Some("https://cdn.example.com".to_string()));
+    assert_eq!(origin_of("http://localhost:8080"), Some("http://localhost:8080".to_string()));
+    assert_eq!(origin_of("/static/"), None);
+    assert_eq!(origin_of("ftp://example.com"), None);
+    assert_eq!(origin_of("https://user@example.com"), None);
+
+    let hints = ResourceHints(vec!["https://fonts.gstatic.com".to_string()]);
+    assert_eq!(
+      hints.call(&HashMap::new()).unwrap(),
+      Value::from(
+        "<link rel=\"preconnect\" href=\"https://fonts.gstatic.com\" crossorigin>\n\
+         <link rel=\"dns-prefetch\" href=\"https://fonts.gstatic.com\">\n"
+      )
+    );
+    assert_eq!(ResourceHints(Vec::new()).call(&HashMap::new()).unwrap(), Value::from(""));
+  }
+}
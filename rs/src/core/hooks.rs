@@ -0,0 +1,88 @@
+use std::fmt;
+use std::sync::Arc;
+use tera::Context;
+
+/// Information about the render a hook is running for.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderInfo<'a> {
+  /// The name of the template being rendered.
+  pub template: &'a str,
+  /// The normalized request host, when rendering for a request.
+  pub host: Option<&'a str>,
+  /// The request path, when rendering for a request.
+  pub path: Option<&'a str>,
+  /// The request's CSP nonce, set by the `SecurityHeaders` middleware.
+  /// Scripts and styles a post-processor adds should carry it.
+  pub nonce: Option<&'a str>,
+}
+
+/// A function run on every merged context before it is rendered.
+type ContextHook = Arc<dyn Fn(&mut Context, &RenderInfo) + Send + Sync>;
+
+/// A function run on every rendered page before it is sent.
+type PostProcessor = Arc<dyn Fn(&mut String, &RenderInfo) + Send + Sync>;
+
+/// The ordered list of hooks registered with `TeraWebBuilder::add_context_hook`.
+#[derive(Clone, Default)]
+pub(crate) struct ContextHooks {
+  hooks: Vec<ContextHook>,
+}
+
+impl ContextHooks {
+  pub(crate) fn push<F>(&mut self, hook: F)
+  where
+    F: Fn(&mut Context, &RenderInfo) + Send + Sync + 'static,
+  {
+    self.hooks.push(Arc::new(hook));
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.hooks.is_empty()
+  }
+
+  /// Runs every hook over `context`, in registration order.
+  pub(crate) fn run(&self, context: &mut Context, info: &RenderInfo) {
+    for hook in &self.hooks {
+      hook(context, info);
+    }
+  }
+}
+
+impl fmt::Debug for ContextHooks {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ContextHooks").field("len", &self.hooks.len()).finish()
+  }
+}
+
+/// The ordered chain of post-processors registered with
+/// `TeraWebBuilder::add_post_processor`.
+#[derive(Clone, Default)]
+pub(crate) struct PostProcessors {
+  processors: Vec<PostProcessor>,
+}
+
+impl PostProcessors {
+  pub(crate) fn push<F>(&mut self, processor: F)
+  where
+    F: Fn(&mut String, &RenderInfo) + Send + Sync + 'static,
+  {
+    self.processors.push(Arc::new(processor));
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.processors.is_empty()
+  }
+
+  /// Runs every processor over `output`, in registration order.
+  pub(crate) fn run(&self, output: &mut String, info: &RenderInfo) {
+    for processor in &self.processors {
+      processor(output, info);
+    }
+  }
+}
+
+impl fmt::Debug for PostProcessors {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PostProcessors").field("len", &self.processors.len()).finish()
+  }
+}
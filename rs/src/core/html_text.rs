@@ -0,0 +1,66 @@
+//! Rewriting the text of an HTML document, leaving its markup alone.
+//!
+//! Shared by the output transforms that must not touch tags, attributes,
+//! comments, or code, like math rendering and the typography filters.
+
+/// Elements whose contents are never rewritten.
+const SKIPPED_ELEMENTS: &[&str] = &["pre", "code", "kbd", "script", "style", "textarea", "math", "svg"];
+
+/// Rebuilds `html`, passing each run of text between tags to `rewrite`,
+/// which appends its replacement to the output. Tags, comments, and the
+/// contents of `pre`, `code`, `kbd`, `script`, `style`, `textarea`, `math`,
+/// and `svg` elements are copied as they are.
+pub(crate) fn map_text(html: &str, mut rewrite: impl FnMut(&str, &mut String)) -> String {
+  let mut output = String::with_capacity(html.len() + html.len() / 4);
+  let mut rest = html;
+  while let Some(start) = rest.find('<') {
+    let (text, from) = rest.split_at(start);
+    if !text.is_empty() {
+      rewrite(text, &mut output);
+    }
+    let end = markup_end(from);
+    output.push_str(&from[..end]);
+    rest = &from[end..];
+  }
+  if !rest.is_empty() {
+    rewrite(rest, &mut output);
+  }
+  output
+}
+
+/// The length of the tag or comment `from` starts with, extended to the
+/// closing tag for elements whose contents are skipped.
+fn markup_end(from: &str) -> usize {
+  if from.starts_with("<!--") {
+    return from.find("-->").map_or(from.len(), |end| end + 3);
+  }
+  let Some(end) = from.find('>').map(|end| end + 1) else {
+    return from.len();
+  };
+  let name: String = from[1..]
+    .chars()
+    .take_while(|c| c.is_ascii_alphanumeric())
+    .map(|c| c.to_ascii_lowercase())
+    .collect();
+  if !SKIPPED_ELEMENTS.contains(&name.as_str()) || from[..end].ends_with("/>") {
+    return end;
+  }
+
+  let Some(closing) = from[end..].to_ascii_lowercase().find(&format!("</{name}")) else {
+    return from.len();
+  };
+  let closing = end + closing;
+  from[closing..].find('>').map_or(from.len(), |close| closing + close + 1)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_only_text_is_rewritten() {
+    let html = "a<p title=\"a\">a<!-- a > a --></p><CODE>a</code><br/>a<pre class=\"x\">a<b>a</b></pre>a";
+    let output = map_text(html, |text, output| output.push_str(&text.to_uppercase()));
+    assert_eq!(output, "A<p title=\"a\">A<!-- a > a --></p><CODE>a</code><br/>A<pre class=\"x\">a<b>a</b></pre>A");
+  }
+}
@@ -0,0 +1,191 @@
+//! Locale-prefixed URLs and alternate-language links.
+//!
+//! A site configured with [`Locales`] serves its default locale at unprefixed
+//! URLs (`/about`) and every other locale under a prefix (`/fr/about`). The
+//! `snapfire::actix::i18n::LocalePrefix` middleware strips the prefix before
+//! routing, so each route is declared once, and exposes the detected locale to
+//! templates as `locale`. Templates then use:
+//!
+//! - `localized_url(path="/about", locale="fr")` → `/fr/about`
+//! - `alternate_links()` → the `<link rel="alternate" hreflang="...">` tags
+//!   for the current page in every supported locale, plus `x-default`.
+
+use crate::core::scope;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tera::{Function, Value};
+
+/// The locale of the current request, stored in the request extensions by
+/// the `LocalePrefix` middleware.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct Locale(pub String);
+
+/// The locales a site is available in.
+#[derive(Debug, Clone)]
+pub struct Locales {
+  default: String,
+  supported: Vec<String>,
+  base_url: String,
+}
+
+impl Locales {
+  /// Creates a configuration serving `default` unprefixed and every other
+  /// `supported` locale under its own path prefix.
+  pub fn new(default: &str, supported: &[&str]) -> Self {
+    let mut all = vec![default.to_string()];
+    all.extend(supported.iter().filter(|locale| **locale != default).map(|locale| locale.to_string()));
+    Self {
+      default: default.to_string(),
+      supported: all,
+      base_url: String::new(),
+    }
+  }
+
+  /// Sets the absolute origin used in `hreflang` links, e.g. `https://example.com`.
+  ///
+  /// Search engines expect fully-qualified alternate URLs; without a base URL
+  /// the links are root-relative.
+  pub fn base_url(mut self, base_url: &str) -> Self {
+    self.base_url = base_url.trim_end_matches('/').to_string();
+    self
+  }
+
+  /// The default locale.
+  pub fn default_locale(&self) -> &str {
+    &self.default
+  }
+
+  /// Every supported locale, the default first.
+  pub fn supported(&self) -> &[String] {
+    &self.supported
+  }
+
+  /// Splits a request path into its locale and the unprefixed path.
+  ///
+  /// Paths without a known locale prefix belong to the default locale.
+  pub fn split_path<'a>(&'a self, path: &'a str) -> (&'a str, &'a str) {
+    let trimmed = path.strip_prefix('/').unwrap_or(path);
+    let (first, rest) = match trimmed.find('/') {
+      Some(index) => (&trimmed[..index], &trimmed[index..]),
+      None => (trimmed, "/"),
+    };
+
+    match self.supported.iter().find(|locale| locale.eq_ignore_ascii_case(first)) {
+      Some(locale) => (locale, rest),
+      None => (&self.default, path),
+    }
+  }
+
+  /// The URL of `path` in `locale`: unchanged for the default locale,
+  /// prefixed otherwise.
+  pub fn localized_url(&self, path: &str, locale: &str) -> String {
+    let path = if path.starts_with('/') { path.to_string() } else { format!("/{path}") };
+    if locale == self.default {
+      path
+    } else {
+      format!("/{locale}{path}")
+    }
+  }
+
+  /// The `<link rel="alternate">` tags for the unprefixed `path`.
+  pub fn alternate_links(&self, path: &str) -> String {
+    let link = |hreflang: &str, locale: &str| {
+      format!(
+        "<link rel=\"alternate\" hreflang=\"{}\" href=\"{}{}\">",
+        escape_attribute(hreflang),
+        escape_attribute(&self.base_url),
+        escape_attribute(&self.localized_url(path, locale))
+      )
+    };
+
+    let mut links: Vec<String> = self.supported.iter().map(|locale| link(locale, locale)).collect();
+    links.push(link("x-default", &self.default));
+    links.join("\n")
+  }
+}
+
+/// The `localized_url(path=..., locale=...)` Tera function.
+pub(crate) struct LocalizedUrl(pub(crate) Arc<Locales>);
+
+impl Function for LocalizedUrl {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let locale = args
+      .get("locale")
+      .and_then(Value::as_str)
+      .ok_or_else(|| tera::Error::msg("`localized_url` requires a `locale` argument"))?;
+    let path = path_argument(&self.0, args, "localized_url")?;
+    // Escaped here rather than by Tera, which would also escape every `/`.
+    Ok(Value::from(escape_attribute(&self.0.localized_url(&path, locale))))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// The `alternate_links(path=...)` Tera function.
+pub(crate) struct AlternateLinks(pub(crate) Arc<Locales>);
+
+impl Function for AlternateLinks {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let path = path_argument(&self.0, args, "alternate_links")?;
+    Ok(Value::from(self.0.alternate_links(&path)))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// The unprefixed `path` argument, defaulting to the current request path.
+fn path_argument(locales: &Locales, args: &HashMap<String, Value>, function: &str) -> tera::Result<String> {
+  let path = match args.get("path").and_then(Value::as_str) {
+    Some(path) => path.to_string(),
+    None => scope::current_path()
+      .ok_or_else(|| tera::Error::msg(format!("`{function}` needs a `path` argument outside of a request")))?,
+  };
+  Ok(locales.split_path(&path).1.to_string())
+}
+
+/// Escapes `value` for a double-quoted attribute, leaving `/` alone.
+pub(crate) fn escape_attribute(value: &str) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('"', "&quot;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn locales() -> Locales {
+    Locales::new("en", &["en", "fr", "pt-BR"]).base_url("https://example.com/")
+  }
+
+  #[test]
+  fn test_split_and_localize_paths() {
+    let locales = locales();
+    assert_eq!(locales.split_path("/fr/about"), ("fr", "/about"));
+    assert_eq!(locales.split_path("/pt-br"), ("pt-BR", "/"));
+    assert_eq!(locales.split_path("/about"), ("en", "/about"));
+    assert_eq!(locales.split_path("/french/x"), ("en", "/french/x"));
+
+    assert_eq!(locales.localized_url("/about", "en"), "/about");
+    assert_eq!(locales.localized_url("/about", "fr"), "/fr/about");
+  }
+
+  #[test]
+  fn test_alternate_links() {
+    assert_eq!(
+      locales().alternate_links("/about"),
+      "<link rel=\"alternate\" hreflang=\"en\" href=\"https://example.com/about\">\n\
+       <link rel=\"alternate\" hreflang=\"fr\" href=\"https://example.com/fr/about\">\n\
+       <link rel=\"alternate\" hreflang=\"pt-BR\" href=\"https://example.com/pt-BR/about\">\n\
+       <link rel=\"alternate\" hreflang=\"x-default\" href=\"https://example.com/about\">"
+    );
+  }
+}
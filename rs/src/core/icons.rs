@@ -0,0 +1,249 @@
+//! An SVG sprite compiled from a directory of icons.
+//!
+//! With `TeraWebBuilder::icons_dir("static/icons")`, every `*.svg` file in
+//! the directory becomes a `<symbol>` of one sprite, sanitized like the `svg`
+//! function's files (see [`crate::core::svg`]). The Actix `configure_routes`
+//! serves the sprite at [`ICON_SPRITE_PATH`], and the `icon` template
+//! function references a symbol by file name:
+//!
+//! ```jinja
+//! <button>{{ icon(name="check", class="icon", size=16) }} Save</button>
+//! {# <svg class="icon" width="16" height="16" aria-hidden="true" focusable="false"><use href="/_snapfire/icons.svg?v=3f9c…#icon-check"></use></svg> #}
+//! ```
+//!
+//! The sprite's URL carries a hash of its contents, so it can be cached
+//! forever and changes URL when an icon does. `title` labels the icon for
+//! assistive technology; without it the icon is hidden. Since the symbols are
+//! reused rather than inlined, style them through `currentColor` in the icon
+//! files. Unknown names fail the render.
+//!
+//! The sprite is compiled when the app is built. In dev mode the directory
+//! is watched, and adding or editing an icon recompiles it and reloads the
+//! page. [`crate::core::app::TeraWeb::icon_sprite`] returns it for writing
+//! to a CDN or static export.
+
+use crate::core::app::TeraWeb;
+use crate::core::svg::Svg;
+use crate::error::{Result, SnapFireError};
+use bytes::Bytes;
+use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tera::{Function, Value};
+
+/// Where `configure_routes` serves the sprite.
+pub const ICON_SPRITE_PATH: &str = "/_snapfire/icons.svg";
+
+/// The prefix of each symbol's `id`, keeping them apart from the page's own.
+const SYMBOL_PREFIX: &str = "icon-";
+
+/// A compiled sprite.
+#[derive(Debug, Clone)]
+pub(crate) struct Sprite {
+  pub(crate) body: Bytes,
+  /// A hash of the body, used in the URL and as the ETag.
+  pub(crate) version: String,
+  names: Vec<String>,
+}
+
+impl Sprite {
+  /// Compiles the `*.svg` files of `dir`, named by their file stems.
+  fn compile(dir: &Path) -> Result<Self> {
+    let config_error = |message: String| SnapFireError::Config {
+      path: dir.display().to_string(),
+      message,
+    };
+    let mut symbols = BTreeMap::new();
+    for entry in fs::read_dir(dir).map_err(|e| config_error(e.to_string()))? {
+      let path = entry?.path();
+      if path.extension().and_then(|extension| extension.to_str()) != Some("svg") {
+        continue;
+      }
+      let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        continue;
+      };
+      let source = fs::read_to_string(&path)?;
+      let svg = Svg::parse(&source).map_err(|e| config_error(format!("`{}` can't be used as an icon: {e}", path.display())))?;
+      symbols.insert(name.to_string(), svg.to_symbol(&format!("{SYMBOL_PREFIX}{name}")));
+    }
+
+    let mut body = String::from("<svg xmlns=\"http://www.w3.org/2000/svg\">\n");
+    for symbol in symbols.values() {
+      body.push_str(symbol);
+      body.push('\n');
+    }
+    body.push_str("</svg>\n");
+
+    let mut version = String::with_capacity(16);
+    for byte in &Sha256::digest(body.as_bytes())[..8] {
+      let _ = write!(version, "{byte:02x}");
+    }
+    Ok(Self {
+      body: Bytes::from(body),
+      version,
+      names: symbols.into_keys().collect(),
+    })
+  }
+
+  /// The fingerprinted URL of the sprite.
+  pub(crate) fn url(&self) -> String {
+    format!("{ICON_SPRITE_PATH}?v={}", self.version)
+  }
+}
+
+/// The sprite of an icon directory, recompiled when it changes in dev mode.
+#[derive(Debug)]
+pub(crate) struct IconSprite {
+  dir: PathBuf,
+  sprite: RwLock<Arc<Sprite>>,
+}
+
+impl IconSprite {
+  pub(crate) fn new(dir: PathBuf) -> Result<Self> {
+    let sprite = Sprite::compile(&dir)?;
+    Ok(Self {
+      dir,
+      sprite: RwLock::new(Arc::new(sprite)),
+    })
+  }
+
+  /// The current sprite.
+  pub(crate) fn sprite(&self) -> Arc<Sprite> {
+    Arc::clone(&self.sprite.read())
+  }
+
+  /// The icon directory.
+  #[cfg_attr(not(feature = "devel"), allow(dead_code))]
+  pub(crate) fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  /// Compiles the directory again, keeping the old sprite if that fails.
+  #[cfg_attr(not(feature = "devel"), allow(dead_code))]
+  pub(crate) fn reload(&self) -> Result<()> {
+    let sprite = Sprite::compile(&self.dir)?;
+    *self.sprite.write() = Arc::new(sprite);
+    Ok(())
+  }
+}
+
+impl TeraWeb {
+  /// The icon sprite compiled from `TeraWebBuilder::icons_dir`, and its
+  /// fingerprinted URL, e.g. `/_snapfire/icons.svg?v=3f9c…`; `None` without
+  /// an icon directory.
+  pub fn icon_sprite(&self) -> Option<(Bytes, String)> {
+    let sprite = self.icons.as_ref()?.sprite();
+    Some((sprite.body.clone(), sprite.url()))
+  }
+}
+
+/// The `icon(name=..., class=..., size=..., width=..., height=..., title=...)`
+/// Tera function.
+pub(crate) struct IconFunction(pub(crate) Arc<IconSprite>);
+
+impl Function for IconFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let name = args
+      .get("name")
+      .and_then(Value::as_str)
+      .ok_or_else(|| tera::Error::msg("The `icon` function requires a `name` string, e.g. icon(name=\"check\")"))?;
+    let sprite = self.0.sprite();
+    if sprite.names.binary_search_by(|known| known.as_str().cmp(name)).is_err() {
+      return Err(tera::Error::msg(format!(
+        "There is no icon named `{name}` in {}",
+        self.0.dir.display()
+      )));
+    }
+
+    let string = |name: &str| match args.get(name) {
+      Some(Value::String(value)) => Ok(Some(tera::escape_html(value))),
+      Some(Value::Number(value)) => Ok(Some(value.to_string())),
+      Some(_) => Err(tera::Error::msg(format!("The `{name}` argument of `icon` must be a string or a number"))),
+      None => Ok(None),
+    };
+    let size = string("size")?;
+    let mut html = String::from("<svg");
+    if let Some(class) = string("class")? {
+      let _ = write!(html, " class=\"{class}\"");
+    }
+    if let Some(width) = string("width")?.or_else(|| size.clone()) {
+      let _ = write!(html, " width=\"{width}\"");
+    }
+    if let Some(height) = string("height")?.or(size) {
+      let _ = write!(html, " height=\"{height}\"");
+    }
+    match string("title")? {
+      Some(title) => {
+        let _ = write!(html, " role=\"img\" aria-label=\"{title}\"");
+      }
+      None => html.push_str(" aria-hidden=\"true\" focusable=\"false\""),
+    }
+    let _ = write!(
+      html,
+      "><use href=\"{}#{SYMBOL_PREFIX}{}\"></use></svg>",
+      sprite.url(),
+      tera::escape_html(name)
+    );
+    Ok(Value::String(html))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_compiles_icons_into_symbols() {
+    let dir = tempdir().unwrap();
+    fs::write(
+      dir.path().join("check.svg"),
+      "<?xml version=\"1.0\"?><svg xmlns=\"http://www.w3.org/2000/svg\" width=\"24\" height=\"24\" viewBox=\"0 0 24 24\" \
+       fill=\"none\" stroke=\"currentColor\" onload=\"x()\"><path d=\"M20 6 9 17l-5-5\"/></svg>",
+    )
+    .unwrap();
+    fs::write(dir.path().join("arrow.svg"), "<svg viewBox=\"0 0 8 8\"><path d=\"M0 0\"/></svg>").unwrap();
+    fs::write(dir.path().join("notes.txt"), "not an icon").unwrap();
+
+    let icons = IconSprite::new(dir.path().to_path_buf()).unwrap();
+    let sprite = icons.sprite();
+    assert_eq!(
+      std::str::from_utf8(&sprite.body).unwrap(),
+      "<svg xmlns=\"http://www.w3.org/2000/svg\">\n\
+       <symbol id=\"icon-arrow\" viewBox=\"0 0 8 8\"><path d=\"M0 0\"/></symbol>\n\
+       <symbol id=\"icon-check\" viewBox=\"0 0 24 24\" fill=\"none\" stroke=\"currentColor\"><path d=\"M20 6 9 17l-5-5\"/></symbol>\n\
+       </svg>\n"
+    );
+
+    let icon = IconFunction(Arc::new(icons));
+    let args = HashMap::from([
+      ("name".to_string(), Value::from("check")),
+      ("class".to_string(), Value::from("icon")),
+      ("size".to_string(), Value::from(16)),
+    ]);
+    assert_eq!(
+      icon.call(&args).unwrap(),
+      Value::from(format!(
+        "<svg class=\"icon\" width=\"16\" height=\"16\" aria-hidden=\"true\" focusable=\"false\">\
+         <use href=\"/_snapfire/icons.svg?v={}#icon-check\"></use></svg>",
+        sprite.version
+      ))
+    );
+    let missing = HashMap::from([("name".to_string(), Value::from("cross"))]);
+    assert!(icon.call(&missing).is_err());
+
+    // A new icon changes the sprite's version.
+    fs::write(dir.path().join("cross.svg"), "<svg viewBox=\"0 0 8 8\"></svg>").unwrap();
+    icon.0.reload().unwrap();
+    assert_ne!(icon.0.sprite().version, sprite.version);
+    assert!(icon.call(&missing).is_ok());
+  }
+}
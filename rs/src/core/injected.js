@@ -3,12 +3,24 @@
   const MAX_RETRIES = 10;
   let retryCount = 0;
   let ws;
+  // Filled in by the server with the configured `ws_path`.
+  const WS_PATH = __SNAPFIRE_WS_PATH__;
+  // When loaded from `/_snapfire/client.js` (possibly by a page served from
+  // another dev server), connect back to the server that served the script.
+  const script = document.currentScript;
+  const origin = script && script.src ? new URL(script.src) : window.location;
+
+  // Handlers for messages sent with `TeraWeb::dev_push`, by event name.
+  const listeners = {};
+  window.SnapFire = {
+    on: function (event, callback) {
+      (listeners[event] = listeners[event] || []).push(callback);
+    },
+  };
 
   function connect() {
-    const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
-    // The ws_path will be replaced by the build script or configured by the user.
-    // For now, we hardcode the default.
-    const wsUrl = `${protocol}//${window.location.host}/_snapfire/ws`;
+    const protocol = origin.protocol === 'https:' ? 'wss:' : 'ws:';
+    const wsUrl = `${protocol}//${origin.host}${WS_PATH}`;
 
     ws = new WebSocket(wsUrl);
 
@@ -24,6 +36,11 @@
           url.searchParams.set('_', Date.now());
           link.href = url.href;
         });
+      } else if (event.data.charAt(0) === '{') {
+        const message = JSON.parse(event.data);
+        (listeners[message.event] || []).forEach(function (callback) {
+          callback(message.data);
+        });
       }
     };
 
@@ -0,0 +1,235 @@
+//! Cache invalidation across replicas, over a message bus.
+//!
+//! `TeraWeb::invalidate_tag` only purges the fragment cache of the process
+//! it's called in. When several replicas serve the site, each one listens
+//! on a shared Redis pub/sub channel or NATS subject, and whoever changes
+//! the data (any replica, or a backend job) publishes an [`Invalidation`]
+//! there, so every replica drops the affected fragments:
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "redis")]
+//! # async fn run() -> snapfire::Result<()> {
+//! use snapfire::{TeraWeb, core::cache::FragmentCache, core::invalidation::listen_redis};
+//!
+//! let app = TeraWeb::builder("templates/**/*.html").fragment_cache(FragmentCache::new()).build()?;
+//! tokio::spawn(listen_redis(app.clone(), "redis://cache:6379", "snapfire:invalidate"));
+//! # Ok(())
+//! # }
+//! # fn main() {}
+//! ```
+//!
+//! Messages are plain text, so any client can publish them:
+//!
+//! * `tag:<tag>` drops the fragments cached under a tag, e.g. `tag:user:42`.
+//! * `key:<key>` drops the fragments cached with a `key` argument.
+//! * `all` drops every fragment.
+//!
+//! e.g. `redis-cli PUBLISH snapfire:invalidate tag:user:42`. Malformed
+//! messages are logged and skipped. Received messages don't purge the CDNs
+//! of `TeraWebBuilder::add_cdn_purger`; the replica calling
+//! `TeraWeb::invalidate` already did. The listeners need the `redis` or
+//! `nats` feature and a Tokio runtime; they return when the connection is
+//! lost, so callers decide whether and when to reconnect.
+
+use crate::core::app::TeraWeb;
+use crate::core::purge::Purge;
+#[cfg(any(feature = "redis", feature = "nats"))]
+use crate::error::{Result, SnapFireError};
+
+/// What a message asks every replica to drop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invalidation {
+  /// The fragments cached under a tag.
+  Tag(String),
+  /// The fragments cached with a `key` argument.
+  Key(String),
+  /// Every fragment.
+  All,
+}
+
+impl Invalidation {
+  /// Parses a message, or returns `None` if it isn't one.
+  ///
+  /// # Arguments
+  ///
+  /// * `message` - The message's payload, e.g. `tag:user:42`.
+  pub fn parse(message: &str) -> Option<Self> {
+    let message = message.trim();
+    if message == "all" {
+      return Some(Self::All);
+    }
+    match message.split_once(':')? {
+      ("tag", tag) if !tag.is_empty() => Some(Self::Tag(tag.to_string())),
+      ("key", key) if !key.is_empty() => Some(Self::Key(key.to_string())),
+      _ => None,
+    }
+  }
+}
+
+/// The message's payload, as `parse` reads it.
+impl std::fmt::Display for Invalidation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Tag(tag) => write!(f, "tag:{tag}"),
+      Self::Key(key) => write!(f, "key:{key}"),
+      Self::All => f.write_str("all"),
+    }
+  }
+}
+
+impl TeraWeb {
+  /// Drops the cached fragments `invalidation` names, returning how many
+  /// there were. Without a `TeraWebBuilder::fragment_cache`, there is
+  /// nothing to drop. The CDNs added with `TeraWebBuilder::add_cdn_purger`
+  /// purge the tag, or everything.
+  ///
+  /// # Arguments
+  ///
+  /// * `invalidation` - What to drop.
+  pub fn invalidate(&self, invalidation: &Invalidation) -> usize {
+    match invalidation {
+      Invalidation::Tag(tag) => self.cdn_purgers.purge(Purge::Tags(vec![tag.clone()])),
+      Invalidation::All => self.cdn_purgers.purge(Purge::Everything),
+      // Keys name fragments, which CDNs don't know.
+      Invalidation::Key(_) => {}
+    }
+    self.drop_fragments(invalidation)
+  }
+
+  /// Drops the cached fragments `invalidation` names, without purging the
+  /// CDNs: what listeners apply, as the publisher purges them.
+  fn drop_fragments(&self, invalidation: &Invalidation) -> usize {
+    let Some(cache) = &self.fragment_cache else {
+      return 0;
+    };
+    match invalidation {
+      Invalidation::Tag(tag) => cache.invalidate_tag(tag),
+      Invalidation::Key(key) => cache.invalidate_key(key),
+      Invalidation::All => {
+        let dropped = cache.len();
+        cache.clear();
+        dropped
+      }
+    }
+  }
+}
+
+/// Applies one message received on `bus`.
+#[cfg(any(feature = "redis", feature = "nats"))]
+fn apply(app: &TeraWeb, bus: &str, message: &str) {
+  match Invalidation::parse(message) {
+    Some(invalidation) => {
+      let dropped = app.drop_fragments(&invalidation);
+      log::debug!("Invalidation `{invalidation}` from {bus} dropped {dropped} fragments");
+    }
+    None => log::warn!("Skipping a malformed invalidation message from {bus}: `{message}`"),
+  }
+}
+
+/// Applies the invalidations published on a Redis pub/sub `channel` until
+/// the connection is lost. Only available with the `redis` feature.
+///
+/// # Arguments
+///
+/// * `app` - The app whose fragment cache is purged.
+/// * `url` - The Redis server, e.g. `redis://cache:6379`.
+/// * `channel` - The channel invalidations are published on.
+///
+/// # Errors
+///
+/// If the server can't be reached or the subscription is refused.
+#[cfg(feature = "redis")]
+pub async fn listen_redis(app: TeraWeb, url: &str, channel: &str) -> Result<()> {
+  use futures_util::StreamExt;
+
+  let error = |e: redis::RedisError| SnapFireError::Invalidation {
+    bus: "redis".into(),
+    message: e.to_string(),
+  };
+  let client = redis::Client::open(url).map_err(error)?;
+  let mut pubsub = client.get_async_connection().await.map_err(error)?.into_pubsub();
+  pubsub.subscribe(channel).await.map_err(error)?;
+  log::info!("Listening for cache invalidations on Redis channel `{channel}`");
+
+  let mut messages = pubsub.on_message();
+  while let Some(message) = messages.next().await {
+    match message.get_payload::<String>() {
+      Ok(payload) => apply(&app, "redis", &payload),
+      Err(e) => log::warn!("Skipping an unreadable invalidation message from redis: {e}"),
+    }
+  }
+  log::warn!("Lost the Redis connection; cache invalidations are no longer received");
+  Ok(())
+}
+
+/// Applies the invalidations published on a NATS `subject` until the
+/// connection is lost. Only available with the `nats` feature.
+///
+/// # Arguments
+///
+/// * `app` - The app whose fragment cache is purged.
+/// * `url` - The NATS server, e.g. `nats://bus:4222`.
+/// * `subject` - The subject invalidations are published on.
+///
+/// # Errors
+///
+/// If the server can't be reached or the subscription is refused.
+#[cfg(feature = "nats")]
+pub async fn listen_nats(app: TeraWeb, url: &str, subject: &str) -> Result<()> {
+  use futures_util::StreamExt;
+
+  let error = |e: &dyn std::fmt::Display| SnapFireError::Invalidation {
+    bus: "nats".into(),
+    message: e.to_string(),
+  };
+  let client = async_nats::connect(url).await.map_err(|e| error(&e))?;
+  let mut messages = client.subscribe(subject.to_string()).await.map_err(|e| error(&e))?;
+  log::info!("Listening for cache invalidations on NATS subject `{subject}`");
+
+  while let Some(message) = messages.next().await {
+    apply(&app, "nats", &String::from_utf8_lossy(&message.payload));
+  }
+  log::warn!("Lost the NATS connection; cache invalidations are no longer received");
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::cache::FragmentCache;
+  use tera::Context;
+
+  #[test]
+  fn test_messages_drop_the_fragments_they_name() {
+    for message in ["tag:user:42", "key:sidebar", "all"] {
+      assert_eq!(Invalidation::parse(message).unwrap().to_string(), message);
+    }
+    for message in ["", "tag:", "user:42", "everything"] {
+      assert_eq!(Invalidation::parse(message), None);
+    }
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+      temp_dir.path().join("page.html"),
+      "{{ cache(template=\"part.html\", key=\"sidebar\", tags=[\"posts\"], n=n) }}|{{ cache(template=\"part.html\", key=\"footer\", n=n) }}",
+    )
+    .unwrap();
+    std::fs::write(temp_dir.path().join("part.html"), "{{ n }}").unwrap();
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .fragment_cache(FragmentCache::new())
+      .build()
+      .unwrap();
+    let render = |n: u32| {
+      let mut context = Context::new();
+      context.insert("n", &n);
+      app.render_to_string("page.html", context).unwrap()
+    };
+
+    assert_eq!(render(1), "1|1");
+    assert_eq!(app.invalidate(&Invalidation::Key("sidebar".into())), 1);
+    assert_eq!(render(2), "2|1");
+    assert_eq!(app.invalidate(&Invalidation::Tag("posts".into())), 1);
+    assert_eq!(app.invalidate(&Invalidation::All), 1);
+    assert_eq!(render(3), "3|3");
+  }
+}
@@ -0,0 +1,180 @@
+//! Lenient rendering for previews of incomplete data.
+//!
+//! A normal render fails on the first variable missing from the context.
+//! `TeraWeb::render_lenient` instead renders a placeholder for each missing
+//! variable and reports their paths, so a CMS preview can show editors the
+//! page as it stands and list what still needs filling in.
+
+use crate::core::app::TeraWeb;
+use crate::core::hooks::RenderInfo;
+use crate::error::Result;
+use tera::{Context, Tera, Value};
+
+/// The output of `TeraWeb::render_lenient`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LenientRender {
+  /// The rendered page, with placeholders for the missing variables.
+  pub html: String,
+  /// The dotted paths of the missing variables (e.g. `author.name`), in the
+  /// order the template first used them.
+  pub missing: Vec<String>,
+}
+
+impl TeraWeb {
+  /// Renders a template without failing on missing variables.
+  ///
+  /// Each variable missing from the merged context is rendered as
+  /// `[missing: path]` and listed in `LenientRender::missing`. Other errors
+  /// (syntax errors, failing filters, ...) are still returned. The context is
+  /// not checked against the template's schema, since incomplete data is the
+  /// point.
+  ///
+  /// # Arguments
+  ///
+  /// * `tpl` - The name of the template to render.
+  /// * `context` - The (possibly incomplete) context for this render.
+  pub fn render_lenient(&self, tpl: &str, context: Context) -> Result<LenientRender> {
+    let tera = std::sync::Arc::clone(&self.tera.read());
+    let info = RenderInfo {
+      template: tpl,
+      host: None,
+      path: None,
+      nonce: None,
+    };
+
+    self.globals.with_merged_mut(None, context, |final_context| {
+      self.context_hooks.run(final_context, &info);
+
+      let mut missing = Vec::new();
+      let mut html = render_filling_missing(&tera, tpl, final_context, &mut missing)?;
+      self.post_processors.run(&mut html, &info);
+      Ok(LenientRender { html, missing })
+    })
+  }
+}
+
+/// Renders `tpl`, inserting a placeholder and retrying each time Tera reports
+/// a missing variable.
+fn render_filling_missing(tera: &Tera, tpl: &str, context: &mut Context, missing: &mut Vec<String>) -> Result<String> {
+  loop {
+    let error = match tera.render(tpl, context) {
+      Ok(html) => return Ok(html),
+      Err(error) => error,
+    };
+
+    // A path that is still missing after being filled (e.g. under a value
+    // that isn't an object) can't be fixed by another retry.
+    match missing_variable(&error) {
+      Some(path) if !missing.contains(&path) && insert_placeholder(context, &path) => missing.push(path),
+      _ => return Err(error.into()),
+    }
+  }
+}
+
+/// The path of the variable a Tera error reports as missing, if that is the error.
+fn missing_variable(error: &tera::Error) -> Option<String> {
+  let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+  while let Some(error) = source {
+    let message = error.to_string();
+    if let Some(rest) = message.strip_prefix("Variable `")
+      && let Some(end) = rest.find("` not found in context")
+    {
+      return Some(rest[..end].to_string());
+    }
+    source = error.source();
+  }
+  None
+}
+
+/// Sets `path` to its placeholder, creating the objects along the way.
+///
+/// Returns `false` for paths that can't be filled in: indexed lookups
+/// (`items[0]`) or paths running through a value that isn't an object.
+fn insert_placeholder(context: &mut Context, path: &str) -> bool {
+  let segments: Vec<&str> = path.split('.').collect();
+  let is_identifier = |segment: &&str| !segment.is_empty() && segment.chars().all(|c| c.is_alphanumeric() || c == '_');
+  if !segments.iter().all(is_identifier) {
+    return false;
+  }
+
+  let (root, rest) = segments.split_first().expect("split always yields a segment");
+  let mut value = context.get(root).cloned().unwrap_or_else(|| Value::Object(Default::default()));
+  if rest.is_empty() {
+    value = placeholder(path);
+  } else {
+    let mut current = &mut value;
+    for (index, segment) in rest.iter().enumerate() {
+      let Value::Object(object) = current else {
+        return false;
+      };
+      current = object.entry(segment.to_string()).or_insert_with(|| {
+        if index + 1 == rest.len() {
+          placeholder(path)
+        } else {
+          Value::Object(Default::default())
+        }
+      });
+    }
+  }
+
+  context.insert(*root, &value);
+  true
+}
+
+fn placeholder(path: &str) -> Value {
+  Value::from(format!("[missing: {path}]"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::error::SnapFireError;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_reports_missing_variables() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(
+      temp_dir.path().join("post.html"),
+      "<h1>{{ post.title }}</h1><p>{{ post.author.name }}</p><small>{{ site.name }} {{ site.year }}</small>",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("broken.html"), "{{ post.title | nope }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path)
+      .add_global("site", serde_json::json!({ "name": "Blog" }))
+      .build()
+      .unwrap();
+
+    let mut context = Context::new();
+    context.insert("post", &serde_json::json!({ "title": "Draft" }));
+    let output = app.render_lenient("post.html", context).unwrap();
+
+    assert_eq!(
+      output.html,
+      "<h1>Draft</h1><p>[missing: post.author.name]</p><small>Blog [missing: site.year]</small>"
+    );
+    assert_eq!(output.missing, ["post.author.name", "site.year"]);
+
+    // Errors other than missing variables still fail the render.
+    let result = app.render_lenient("broken.html", Context::new());
+    assert!(matches!(result, Err(SnapFireError::Tera(_))));
+  }
+
+  #[test]
+  fn test_fills_nested_paths() {
+    let mut context = Context::new();
+    context.insert("author", &serde_json::json!({ "bio": "Writes things" }));
+
+    assert!(insert_placeholder(&mut context, "author.name"));
+    assert!(insert_placeholder(&mut context, "meta.seo.title"));
+    assert_eq!(context.get("author").unwrap()["bio"], "Writes things");
+    assert_eq!(context.get("author").unwrap()["name"], "[missing: author.name]");
+    assert_eq!(context.get("meta").unwrap()["seo"]["title"], "[missing: meta.seo.title]");
+
+    context.insert("title", "Hello");
+    assert!(!insert_placeholder(&mut context, "title.text"));
+    assert!(!insert_placeholder(&mut context, "items[0]"));
+  }
+}
@@ -0,0 +1,324 @@
+//! Checking the internal links of rendered pages before deploying.
+//!
+//! [`LinkChecker`] renders a list of pages like the static export does,
+//! collects the `href`, `src`, `srcset`, and `poster` URLs in their markup,
+//! and reports every internal one that resolves to neither a page in the
+//! list, an extra route, nor a file in a static directory:
+//!
+//! ```rust,no_run
+//! # use snapfire::core::export::ExportPage;
+//! # use snapfire::core::links::LinkChecker;
+//! # fn main() -> snapfire::Result<()> {
+//! # let app = snapfire::TeraWeb::builder("templates/**/*").build()?;
+//! let pages = vec![ExportPage::new("/", "index.html"), ExportPage::new("/about", "about.html")];
+//! let report = LinkChecker::new(&app, pages)
+//!   .static_dir("/static", "static")
+//!   .route("/api/health")
+//!   .check()?;
+//! for broken in &report.broken {
+//!   eprintln!("{}: broken link to {}", broken.page, broken.link);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! External URLs (with a scheme, or protocol-relative), fragment-only links,
+//! and the query and fragment of internal ones are ignored. Relative links
+//! resolve against the page URL the way browsers do, and trailing slashes
+//! don't matter, so `/about/` matches the page `/about`.
+
+use crate::core::app::{RequestParts, TeraWeb};
+use crate::core::export::{ExportPage, load_data};
+use crate::error::Result;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// The attributes whose values are checked.
+const LINK_ATTRIBUTES: &[&str] = &["href", "src", "srcset", "poster"];
+
+/// A link that doesn't resolve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+  /// The URL of the page the link is on.
+  pub page: String,
+  /// The link as written in the page.
+  pub link: String,
+}
+
+/// What a link check found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkReport {
+  /// The number of internal links checked, over all pages.
+  pub checked: usize,
+  /// The links that don't resolve, in page order.
+  pub broken: Vec<BrokenLink>,
+}
+
+impl LinkReport {
+  /// Whether every link resolved.
+  pub fn is_ok(&self) -> bool {
+    self.broken.is_empty()
+  }
+}
+
+/// A check of the internal links in a list of pages.
+pub struct LinkChecker {
+  app: TeraWeb,
+  pages: Vec<ExportPage>,
+  routes: BTreeSet<String>,
+  static_dirs: Vec<(String, PathBuf)>,
+}
+
+impl LinkChecker {
+  /// Prepares a check of the links in `pages`, which are also the routes
+  /// links may point to.
+  pub fn new(app: &TeraWeb, pages: Vec<ExportPage>) -> Self {
+    Self {
+      app: app.clone(),
+      routes: pages.iter().map(|page| normalize(&page.url)).collect(),
+      pages,
+      static_dirs: Vec::new(),
+    }
+  }
+
+  /// Adds a route that isn't one of the pages, such as an API endpoint.
+  pub fn route(mut self, url: &str) -> Self {
+    self.routes.insert(normalize(url));
+    self
+  }
+
+  /// Resolves links under `url_prefix` to the files in `dir`, like a static
+  /// file service mounted there. A link to a directory resolves if it has an
+  /// `index.html`.
+  pub fn static_dir<P: Into<PathBuf>>(mut self, url_prefix: &str, dir: P) -> Self {
+    self.static_dirs.push((normalize(url_prefix), dir.into()));
+    self
+  }
+
+  /// Renders every page and checks its links.
+  pub fn check(&self) -> Result<LinkReport> {
+    let mut report = LinkReport::default();
+    for page in &self.pages {
+      let mut context = page.context.clone();
+      for data in &page.data_files {
+        context.extend(load_data(data)?);
+      }
+      let request = RequestParts {
+        path: Some(&page.url),
+        ..RequestParts::default()
+      };
+      let html = self.app.render_for_request(&page.template, request, context)?;
+
+      for link in links(&html) {
+        let Some(path) = resolve(&page.url, &link) else {
+          continue;
+        };
+        report.checked += 1;
+        if !self.exists(&path) {
+          report.broken.push(BrokenLink {
+            page: page.url.clone(),
+            link,
+          });
+        }
+      }
+    }
+    Ok(report)
+  }
+
+  fn exists(&self, path: &str) -> bool {
+    if self.routes.contains(path) {
+      return true;
+    }
+    self.static_dirs.iter().any(|(prefix, dir)| {
+      let rest = match path.strip_prefix(prefix.as_str()) {
+        Some(rest) if prefix == "/" || rest.is_empty() || rest.starts_with('/') => rest.trim_start_matches('/'),
+        _ => return false,
+      };
+      let file = dir.join(rest);
+      file.is_file() || file.join("index.html").is_file()
+    })
+  }
+}
+
+impl TeraWeb {
+  /// Renders `pages` and reports their internal links that don't point to
+  /// one of the pages.
+  ///
+  /// To also accept static files and other routes, use `LinkChecker`.
+  pub fn check_links(&self, pages: Vec<ExportPage>) -> Result<LinkReport> {
+    LinkChecker::new(self, pages).check()
+  }
+}
+
+/// The URLs in the link attributes of `html`'s tags, unescaped.
+fn links(html: &str) -> Vec<String> {
+  let mut links = Vec::new();
+  let mut rest = html;
+  while let Some(start) = rest.find('<') {
+    rest = &rest[start + 1..];
+    let end = rest.find('>').unwrap_or(rest.len());
+    let tag = &rest[..end];
+    rest = &rest[end..];
+
+    let name: String = tag.chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+    if name.eq_ignore_ascii_case("script") || name.eq_ignore_ascii_case("style") {
+      // Their contents are code, not markup.
+      let closing = format!("</{}", name.to_ascii_lowercase());
+      let skip = rest.to_ascii_lowercase().find(&closing).unwrap_or(rest.len());
+      rest = &rest[skip..];
+    }
+
+    for (attribute, value) in attributes(tag) {
+      if !LINK_ATTRIBUTES.iter().any(|name| attribute.eq_ignore_ascii_case(name)) {
+        continue;
+      }
+      let value = unescape(value);
+      if attribute.eq_ignore_ascii_case("srcset") {
+        // Candidates are `url [descriptor]`, separated by commas.
+        links.extend(value.split(',').filter_map(|candidate| candidate.split_whitespace().next()).map(str::to_string));
+      } else {
+        links.push(value.trim().to_string());
+      }
+    }
+  }
+  links
+}
+
+/// The `name=value` attributes of a tag's source (after the `<`), with an
+/// empty value for attributes without one.
+pub(crate) fn attributes(tag: &str) -> Vec<(&str, &str)> {
+  let mut attributes = Vec::new();
+  let mut rest = tag.trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+  loop {
+    rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+    let name_end = rest
+      .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+      .unwrap_or(rest.len());
+    if name_end == 0 {
+      return attributes;
+    }
+    let name = &rest[..name_end];
+    rest = rest[name_end..].trim_start();
+    let Some(after) = rest.strip_prefix('=') else {
+      attributes.push((name, ""));
+      continue;
+    };
+    let after = after.trim_start();
+    let (value, remaining) = match after.chars().next() {
+      Some(quote @ ('"' | '\'')) => {
+        let end = after[1..].find(quote).map_or(after.len(), |end| end + 1);
+        (&after[1..end], after.get(end + 1..).unwrap_or(""))
+      }
+      _ => after.split_at(after.find(char::is_whitespace).unwrap_or(after.len())),
+    };
+    attributes.push((name, value));
+    rest = remaining;
+  }
+}
+
+/// The normalized path `link` points to from the page at `page_url`, or
+/// `None` for external and fragment-only links.
+fn resolve(page_url: &str, link: &str) -> Option<String> {
+  let path = link.split(['?', '#']).next().unwrap_or_default();
+  let has_scheme = link
+    .split_once(':')
+    .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains('/'));
+  if path.is_empty() || has_scheme || link.starts_with("//") {
+    return None;
+  }
+
+  let joined = if path.starts_with('/') {
+    path.to_string()
+  } else {
+    let base = &page_url[..page_url.rfind('/').map_or(0, |slash| slash + 1)];
+    format!("/{}{}", base.trim_start_matches('/'), path)
+  };
+
+  let mut segments: Vec<&str> = Vec::new();
+  for segment in joined.split('/') {
+    match segment {
+      "" | "." => {}
+      ".." => {
+        segments.pop();
+      }
+      segment => segments.push(segment),
+    }
+  }
+  Some(format!("/{}", segments.join("/")))
+}
+
+/// `url` without a trailing slash (except for the root), and with a leading one.
+fn normalize(url: &str) -> String {
+  format!("/{}", url.trim_matches('/'))
+}
+
+/// Reverses the HTML escaping of an attribute value, such as the `&#x2F;`
+/// Tera writes for `/`.
+pub(crate) fn unescape(value: &str) -> String {
+  if !value.contains('&') {
+    return value.to_string();
+  }
+  value
+    .replace("&#x2F;", "/")
+    .replace("&#47;", "/")
+    .replace("&quot;", "\"")
+    .replace("&#x27;", "'")
+    .replace("&#39;", "'")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_resolves_relative_links() {
+    assert_eq!(resolve("/blog/post", "other").as_deref(), Some("/blog/other"));
+    assert_eq!(resolve("/blog/post/", "../about/?x=1#top").as_deref(), Some("/blog/about"));
+    assert_eq!(resolve("/", "static/app.css").as_deref(), Some("/static/app.css"));
+    for external in ["https://example.com/", "mailto:a@b.c", "//cdn.example.com/x.js", "#top", "data:image/png;base64,x"] {
+      assert_eq!(resolve("/", external), None, "{external}");
+    }
+  }
+
+  #[test]
+  fn test_reports_broken_links() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(
+      temp_dir.path().join("index.html"),
+      r#"<a href="{{ about }}">About</a> <a class=x href=/missing>x</a> <a href="https://example.com">e</a>
+<img src="/static/logo.png" srcset="/static/logo.png 1x, /static/logo@2x.png 2x" alt="">
+<link rel="stylesheet" href="/static/app.css"><a href='/api/health?full'>h</a><a href="/docs/">d</a>
+<script>if (a<b) { x.innerHTML = '<a href="/nope">'; }</script>"#,
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("about.html"), "<a href=\"./\">Home</a><a href=\"../blog\">Blog</a>").unwrap();
+    let static_dir = temp_dir.path().join("static");
+    fs::create_dir_all(static_dir.join("docs")).unwrap();
+    fs::write(static_dir.join("logo.png"), "").unwrap();
+    fs::write(static_dir.join("app.css"), "").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path).build().unwrap();
+
+    let mut context = tera::Context::new();
+    context.insert("about", "/about/");
+    let pages = vec![ExportPage::new("/", "index.html").context(context), ExportPage::new("/about", "about.html")];
+    let report = LinkChecker::new(&app, pages)
+      .static_dir("/static/", &static_dir)
+      .route("/api/health")
+      .check()
+      .unwrap();
+
+    let broken: Vec<_> = report.broken.iter().map(|broken| (broken.page.as_str(), broken.link.as_str())).collect();
+    assert_eq!(
+      broken,
+      [("/", "/missing"), ("/", "/static/logo@2x.png"), ("/", "/docs/"), ("/about", "../blog")]
+    );
+    assert_eq!(report.checked, 10);
+    assert!(!report.is_ok());
+  }
+}
@@ -0,0 +1,303 @@
+//! Template metadata for editor tooling.
+//!
+//! An editor plugin or Tera language server can complete template names,
+//! blocks, macros, filters, and variables, but it doesn't know which of them
+//! a given app has: the helpers SnapFire registers depend on the builder
+//! and features (`icon` with `icons_dir`, `markdown` with `sanitize`), and
+//! the globals are only known at runtime. [`TeraWeb::editor_metadata`]
+//! describes the app's actual Tera environment as JSON:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "templates": [
+//!     { "name": "base.html", "path": "templates/base.html", "parent": null,
+//!       "blocks": ["content", "title"], "macros": [] }
+//!   ],
+//!   "filters": [{ "name": "timeago", "signature": "timeago", "description": "..." }],
+//!   "functions": [{ "name": "asset", "signature": "asset(path)", "description": "..." }],
+//!   "testers": [{ "name": "consented", "signature": "consented(category)", "description": "..." }],
+//!   "globals": { "site_name": "\"SnapFire\"" },
+//!   "host_globals": { "admin.example.com": { "brand": "\"Admin\"" } }
+//! }
+//! ```
+//!
+//! In dev mode, `configure_routes` serves it at `/_snapfire/lsp.json`, so
+//! tooling can poll the running app; it reflects template reloads.
+//!
+//! Only the filters, functions, and testers SnapFire knows the signature of
+//! are listed: Tera's built-ins, SnapFire's own, and those described with
+//! `TeraWebBuilder::describe_helper`. Tera can't list the ones registered
+//! through `configure_tera` otherwise. Macro arguments are sorted by name,
+//! as Tera doesn't keep their order. Globals are shown as short previews,
+//! with secret-looking values scrubbed as in error reports.
+
+use crate::core::app::TeraWeb;
+use crate::core::report;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use tera::Tera;
+
+/// Where `configure_routes` serves the metadata in dev mode.
+#[cfg(all(feature = "devel", feature = "actix"))]
+pub(crate) const LSP_PATH: &str = "/_snapfire/lsp.json";
+
+/// The version of the metadata's layout, raised when it changes
+/// incompatibly.
+const VERSION: u32 = 1;
+
+/// What a helper is called as in a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+  Filter,
+  Function,
+  Tester,
+}
+
+/// The helpers SnapFire knows the signature of, whether or not the app
+/// registers them: Tera's built-ins, then SnapFire's own.
+const KNOWN_HELPERS: &[(Kind, &str, &str, &str)] = &[
+  (Kind::Filter, "upper", "upper", "Uppercases a string."),
+  (Kind::Filter, "lower", "lower", "Lowercases a string."),
+  (Kind::Filter, "trim", "trim", "Removes leading and trailing whitespace."),
+  (Kind::Filter, "trim_start", "trim_start", "Removes leading whitespace."),
+  (Kind::Filter, "trim_end", "trim_end", "Removes trailing whitespace."),
+  (Kind::Filter, "trim_start_matches", "trim_start_matches(pat)", "Removes leading occurrences of `pat`."),
+  (Kind::Filter, "trim_end_matches", "trim_end_matches(pat)", "Removes trailing occurrences of `pat`."),
+  (Kind::Filter, "truncate", "truncate(length=255, end=\"…\")", "Cuts a string to `length` characters, appending `end`."),
+  (Kind::Filter, "wordcount", "wordcount", "Counts the words in a string."),
+  (Kind::Filter, "replace", "replace(from, to)", "Replaces every `from` with `to`."),
+  (Kind::Filter, "capitalize", "capitalize", "Uppercases the first character and lowercases the rest."),
+  (Kind::Filter, "title", "title", "Capitalizes each word."),
+  (Kind::Filter, "linebreaksbr", "linebreaksbr", "Replaces line breaks with `<br>`."),
+  (Kind::Filter, "indent", "indent(prefix=\"    \", first=false, blank=false)", "Indents each line with `prefix`."),
+  (Kind::Filter, "striptags", "striptags", "Removes HTML tags."),
+  (Kind::Filter, "spaceless", "spaceless", "Removes whitespace between HTML tags."),
+  (Kind::Filter, "urlencode", "urlencode", "Percent-encodes a string, keeping `/`."),
+  (Kind::Filter, "urlencode_strict", "urlencode_strict", "Percent-encodes every non-alphanumeric character."),
+  (Kind::Filter, "escape", "escape", "Escapes HTML."),
+  (Kind::Filter, "escape_xml", "escape_xml", "Escapes XML."),
+  (Kind::Filter, "slugify", "slugify", "Turns a string into a URL slug."),
+  (Kind::Filter, "addslashes", "addslashes", "Escapes quotes with backslashes."),
+  (Kind::Filter, "split", "split(pat)", "Splits a string on `pat`."),
+  (Kind::Filter, "int", "int(default=0, base=10)", "Converts a value to an integer."),
+  (Kind::Filter, "float", "float(default=0.0)", "Converts a value to a float."),
+  (Kind::Filter, "first", "first", "The first element of an array."),
+  (Kind::Filter, "last", "last", "The last element of an array."),
+  (Kind::Filter, "nth", "nth(n)", "The `n`th element of an array."),
+  (Kind::Filter, "join", "join(sep)", "Joins an array with `sep`."),
+  (Kind::Filter, "sort", "sort(attribute)", "Sorts an array, by `attribute` for objects."),
+  (Kind::Filter, "unique", "unique(attribute, case_sensitive=false)", "Removes duplicates from an array."),
+  (Kind::Filter, "slice", "slice(start, end)", "A part of an array."),
+  (Kind::Filter, "group_by", "group_by(attribute)", "Groups an array of objects by `attribute`."),
+  (Kind::Filter, "filter", "filter(attribute, value)", "The objects whose `attribute` equals `value`."),
+  (Kind::Filter, "map", "map(attribute)", "The `attribute` of each object."),
+  (Kind::Filter, "concat", "concat(with)", "Appends `with` to an array."),
+  (Kind::Filter, "abs", "abs", "The absolute value of a number."),
+  (Kind::Filter, "pluralize", "pluralize(singular=\"\", plural=\"s\")", "A suffix for a count."),
+  (Kind::Filter, "round", "round(method=\"common\", precision=0)", "Rounds a number."),
+  (Kind::Filter, "filesizeformat", "filesizeformat(binary=false)", "Formats a byte count for humans."),
+  (Kind::Filter, "length", "length", "The length of an array, object, or string."),
+  (Kind::Filter, "reverse", "reverse", "Reverses an array or string."),
+  (Kind::Filter, "date", "date(format=\"%Y-%m-%d\", timezone, locale)", "Formats a timestamp or date string."),
+  (Kind::Filter, "json_encode", "json_encode(pretty=false)", "Serializes a value to JSON."),
+  (Kind::Filter, "as_str", "as_str", "Converts a value to a string."),
+  (Kind::Filter, "get", "get(key, default)", "The value of `key` in an object."),
+  (Kind::Filter, "safe", "safe", "Marks a value as safe, so it isn't escaped."),
+  (Kind::Filter, "default", "default(value)", "`value` if the variable is undefined."),
+  (Kind::Filter, "plural", "plural(zero, one, two, few, many, other, locale)", "The CLDR plural form of a count for the locale."),
+  (Kind::Filter, "logical_css", "logical_css", "Rewrites physical CSS properties to logical ones."),
+  (Kind::Filter, "timeago", "timeago", "A relative time like `3 hours ago`."),
+  (Kind::Filter, "sanitize_html", "sanitize_html(policy)", "Sanitizes HTML with a named policy."),
+  (Kind::Filter, "markdown", "markdown(policy)", "Renders Markdown to sanitized HTML."),
+  (Kind::Filter, "smartypants", "smartypants", "Typographic quotes, dashes, and ellipses."),
+  (Kind::Filter, "emoji", "emoji", "Replaces `:shortcodes:` with emoji."),
+  (Kind::Function, "range", "range(end, start=0, step_by=1)", "A list of numbers from `start` to `end`, exclusive."),
+  (Kind::Function, "now", "now(timestamp=false, utc=false)", "The current date and time."),
+  (Kind::Function, "throw", "throw(message)", "Fails the render with `message`."),
+  (Kind::Function, "get_random", "get_random(end, start=0)", "A random integer from `start` to `end`, exclusive."),
+  (Kind::Function, "get_env", "get_env(name, default)", "The value of an environment variable."),
+  (Kind::Function, "nav", "nav(name, path)", "The items of a navigation menu, with the current one marked."),
+  (Kind::Function, "search_index_url", "search_index_url()", "The URL of the client-side search index."),
+  (Kind::Function, "cache", "cache(template, key, tags, ttl)", "Renders a partial, cached by `key`."),
+  (Kind::Function, "cached_fetch", "cached_fetch(loader, key, tags, ttl)", "The cached result of a data loader."),
+  (Kind::Function, "dir", "dir(locale)", "`rtl` or `ltr` for the locale."),
+  (Kind::Function, "text_align_start", "text_align_start(locale)", "`right` or `left` for the locale."),
+  (Kind::Function, "sparkline", "sparkline(values, width=100, height=20, stroke_width=1.5, color, fill, label, min, max)", "An inline SVG sparkline."),
+  (Kind::Function, "chart", "chart(spec)", "An SVG bar or line chart."),
+  (Kind::Function, "avatar", "avatar(value, size, gravatar, default)", "An avatar for a name or email."),
+  (Kind::Function, "honeypot_field", "honeypot_field(name)", "A hidden field that catches spam bots."),
+  (Kind::Function, "flush", "flush()", "Sends the page so far, with early flush."),
+  (Kind::Function, "await_block", "await_block(name)", "A deferred block, streamed in after the page."),
+  (Kind::Function, "asset", "asset(path)", "The versioned URL of an asset."),
+  (Kind::Function, "script_module_nomodule", "script_module_nomodule(entry, legacy, nonce)", "Module and legacy script tags for an entry."),
+  (Kind::Function, "signed_asset", "signed_asset(path, ttl)", "A signed, expiring URL."),
+  (Kind::Function, "resource_hints", "resource_hints()", "`preconnect` links for the configured origins."),
+  (Kind::Function, "icon", "icon(name, size, class, width, height, title)", "An icon from the sprite."),
+  (Kind::Function, "svg", "svg(path, size, class, width, height, title, current_color)", "An inlined SVG file."),
+  (Kind::Function, "localized_url", "localized_url(path, locale)", "The URL of a page in a locale."),
+  (Kind::Function, "alternate_links", "alternate_links(path)", "`hreflang` links for every locale."),
+  (Kind::Function, "qr_code", "qr_code(data, size=200, ec=\"M\", color=\"#000\", background=\"#fff\")", "An SVG QR code."),
+  (Kind::Function, "barcode", "barcode(data, kind=\"code128\", height=80, module=2, color=\"#000\", background=\"#fff\")", "An SVG barcode."),
+  (Kind::Function, "upload_field", "upload_field(name, errors)", "A file input with its upload rule."),
+  (Kind::Function, "content", "content(name)", "The entries of a content collection."),
+  (Kind::Function, "taxonomy", "taxonomy(name, term)", "The terms of a taxonomy, or one term's entries."),
+  (Kind::Tester, "defined", "defined", "Whether the variable is defined."),
+  (Kind::Tester, "undefined", "undefined", "Whether the variable is undefined."),
+  (Kind::Tester, "odd", "odd", "Whether a number is odd."),
+  (Kind::Tester, "even", "even", "Whether a number is even."),
+  (Kind::Tester, "string", "string", "Whether the value is a string."),
+  (Kind::Tester, "number", "number", "Whether the value is a number."),
+  (Kind::Tester, "divisibleby", "divisibleby(n)", "Whether a number is divisible by `n`."),
+  (Kind::Tester, "iterable", "iterable", "Whether the value is an array or object."),
+  (Kind::Tester, "object", "object", "Whether the value is an object."),
+  (Kind::Tester, "starting_with", "starting_with(prefix)", "Whether a string starts with `prefix`."),
+  (Kind::Tester, "ending_with", "ending_with(suffix)", "Whether a string ends with `suffix`."),
+  (Kind::Tester, "containing", "containing(needle)", "Whether a string, array, or object contains `needle`."),
+  (Kind::Tester, "matching", "matching(regex)", "Whether a string matches `regex`."),
+  (Kind::Tester, "consented", "consented(category)", "Whether the visitor consented to a cookie category."),
+];
+
+/// A helper registered through `configure_tera`, described with
+/// `TeraWebBuilder::describe_helper`.
+#[derive(Debug, Clone)]
+pub(crate) struct HelperDoc {
+  pub(crate) name: String,
+  pub(crate) signature: String,
+  pub(crate) description: String,
+}
+
+impl TeraWeb {
+  /// The app's templates, filters, functions, testers, and globals, as JSON
+  /// for editor tooling. See [`crate::core::lsp`] for the layout.
+  pub fn editor_metadata(&self) -> Value {
+    let tera = self.tera.read().clone();
+
+    let mut names: Vec<&str> = tera
+      .get_template_names()
+      // Fixtures are loaded as templates too by globs like `templates/**/*`.
+      .filter(|name| !name.ends_with(".json") && !name.contains(".fixtures/"))
+      .collect();
+    names.sort_unstable();
+    let templates: Vec<Value> = names
+      .into_iter()
+      .filter_map(|name| tera.get_template(name).ok())
+      .map(|template| {
+        let mut blocks: Vec<&String> = template.blocks.keys().collect();
+        blocks.sort_unstable();
+        let mut macros: Vec<_> = template.macros.values().collect();
+        macros.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        let macros: Vec<Value> = macros
+          .into_iter()
+          .map(|definition| {
+            let args: BTreeMap<&String, bool> = definition.args.iter().map(|(name, default)| (name, default.is_some())).collect();
+            let args: Vec<Value> = args.into_iter().map(|(name, optional)| json!({ "name": name, "optional": optional })).collect();
+            json!({ "name": definition.name, "args": args })
+          })
+          .collect();
+        json!({
+          "name": template.name,
+          "path": template.path,
+          "parent": template.parent,
+          "blocks": blocks,
+          "macros": macros,
+        })
+      })
+      .collect();
+
+    let host_globals: BTreeMap<&str, _> = self.globals.hosts().map(|(host, context)| (host, report::summarize(context, &[]))).collect();
+    json!({
+      "version": VERSION,
+      "templates": templates,
+      "filters": helpers(&tera, &self.helper_docs, Kind::Filter),
+      "functions": helpers(&tera, &self.helper_docs, Kind::Function),
+      "testers": helpers(&tera, &self.helper_docs, Kind::Tester),
+      "globals": report::summarize(self.globals.base(), &[]),
+      "host_globals": host_globals,
+    })
+  }
+}
+
+/// The registered helpers of `kind` with a known signature, sorted by name.
+/// A description from the builder replaces SnapFire's own.
+fn helpers(tera: &Tera, described: &[HelperDoc], kind: Kind) -> Vec<Value> {
+  let mut helpers: BTreeMap<&str, (&str, &str)> = KNOWN_HELPERS
+    .iter()
+    .filter(|(helper_kind, ..)| *helper_kind == kind)
+    .map(|(_, name, signature, description)| (*name, (*signature, *description)))
+    .collect();
+  for doc in described {
+    helpers.insert(&doc.name, (&doc.signature, &doc.description));
+  }
+  helpers
+    .into_iter()
+    .filter(|(name, _)| is_registered(tera, kind, name))
+    .map(|(name, (signature, description))| json!({ "name": name, "signature": signature, "description": description }))
+    .collect()
+}
+
+/// Whether `tera` has the helper `name` of `kind`.
+fn is_registered(tera: &Tera, kind: Kind, name: &str) -> bool {
+  match kind {
+    // `safe` and `default` are handled by the renderer itself.
+    Kind::Filter => matches!(name, "safe" | "default") || tera.get_filter(name).is_ok(),
+    Kind::Function => tera.get_function(name).is_ok(),
+    Kind::Tester => tera.get_tester(name).is_ok(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashMap;
+  use std::fs;
+  use tempfile::tempdir;
+  use tera::Context;
+
+  #[test]
+  fn test_metadata_lists_the_apps_actual_environment() {
+    let temp_dir = tempdir().unwrap();
+    let templates = [
+      ("base.html", "<title>{% block title %}{% endblock %}</title>{% block content %}{% endblock %}"),
+      ("macros.html", "{% macro price(amount, currency=\"EUR\") %}{{ amount }}{% endmacro %}"),
+      ("page.html", "{% extends \"base.html\" %}{% block content %}{{ 1 | money }}{% endblock %}"),
+    ];
+    for (name, source) in templates {
+      fs::write(temp_dir.path().join(name), source).unwrap();
+    }
+    let mut admin = Context::new();
+    admin.insert("brand", "Admin");
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .add_global("site_name", "SnapFire")
+      .add_global("api_token", "s3cr3t")
+      .add_host_globals("admin.example.com", admin)
+      .configure_tera(|tera| {
+        tera.register_filter("money", |value: &Value, _: &HashMap<String, Value>| Ok(value.clone()));
+        tera.register_filter("undescribed", |value: &Value, _: &HashMap<String, Value>| Ok(value.clone()));
+      })
+      .describe_helper("money", "money(currency=\"EUR\")", "Formats an amount.")
+      .build()
+      .unwrap();
+    let metadata = app.editor_metadata();
+
+    let templates = metadata["templates"].as_array().unwrap();
+    assert_eq!(templates.iter().map(|template| template["name"].as_str().unwrap()).collect::<Vec<_>>(), ["base.html", "macros.html", "page.html"]);
+    assert_eq!(templates[0]["blocks"], json!(["content", "title"]));
+    assert_eq!(templates[1]["macros"], json!([{ "name": "price", "args": [{ "name": "amount", "optional": false }, { "name": "currency", "optional": true }] }]));
+    assert_eq!(templates[2]["parent"], "base.html");
+
+    let names = |kind: &str| metadata[kind].as_array().unwrap().iter().map(|helper| helper["name"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+    let filters = names("filters");
+    assert!(filters.contains(&"money".to_string()) && filters.contains(&"timeago".to_string()) && filters.contains(&"safe".to_string()));
+    // Not described, or not registered without its builder option.
+    assert!(!filters.contains(&"undescribed".to_string()));
+    assert!(!names("functions").contains(&"icon".to_string()));
+    assert!(names("testers").contains(&"consented".to_string()));
+    let money = metadata["filters"].as_array().unwrap().iter().find(|helper| helper["name"] == "money").unwrap();
+    assert_eq!(money["signature"], "money(currency=\"EUR\")");
+
+    assert_eq!(metadata["globals"]["site_name"], "\"SnapFire\"");
+    assert_eq!(metadata["globals"]["api_token"], "[scrubbed]");
+    assert_eq!(metadata["host_globals"]["admin.example.com"]["brand"], "\"Admin\"");
+  }
+}
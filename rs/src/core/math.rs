@@ -0,0 +1,207 @@
+//! Server-side math rendering with KaTeX.
+//!
+//! With `TeraWebBuilder::render_math`, TeX between `$$` delimiters (display
+//! math) and `$` delimiters (inline math) in rendered pages is replaced by
+//! KaTeX's HTML, so math shows up fully laid out on first paint. Pages only
+//! need KaTeX's stylesheet and fonts, not its script:
+//!
+//! ```html
+//! <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/katex@0.16/dist/katex.min.css">
+//! <p>Euler: $e^{i\pi} + 1 = 0$</p>
+//! ```
+//!
+//! Math is only looked for in text: tags and their attributes, and the
+//! contents of `pre`, `code`, `kbd`, `script`, `style`, `textarea`, `math`,
+//! and `svg` elements are left alone. `\$` is a literal dollar sign. Inline
+//! math must not start or end with a space, and its closing `$` must not be
+//! followed by a digit, so prices like "$5 and $10" stay text. TeX that KaTeX
+//! rejects is left as written, with a warning in the log.
+
+use crate::core::html_text;
+use katex::Opts;
+use std::collections::HashMap;
+
+/// How math is rendered, for `TeraWebBuilder::render_math`.
+#[derive(Debug, Clone)]
+pub struct MathOptions {
+  inline: bool,
+  macros: HashMap<String, String>,
+}
+
+impl Default for MathOptions {
+  fn default() -> Self {
+    Self {
+      inline: true,
+      macros: HashMap::new(),
+    }
+  }
+}
+
+impl MathOptions {
+  /// Renders both `$$` display math and `$` inline math.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Whether `$...$` is inline math. Turn it off for sites where single
+  /// dollar signs are mostly currency; `$$...$$` is rendered either way.
+  pub fn inline(mut self, enabled: bool) -> Self {
+    self.inline = enabled;
+    self
+  }
+
+  /// Defines a TeX macro for every formula, e.g. `add_macro(r"\RR", r"\mathbb{R}")`.
+  pub fn add_macro(mut self, name: &str, expansion: &str) -> Self {
+    self.macros.insert(name.to_string(), expansion.to_string());
+    self
+  }
+}
+
+/// Renders the math in pages, with KaTeX options prepared once.
+#[derive(Debug, Clone)]
+pub(crate) struct MathRenderer {
+  inline: Option<Opts>,
+  display: Opts,
+}
+
+impl MathRenderer {
+  pub(crate) fn new(options: MathOptions) -> Self {
+    let opts = |display| {
+      let mut opts = Opts::default();
+      opts.set_display_mode(display);
+      opts.set_throw_on_error(true);
+      for (name, expansion) in &options.macros {
+        opts.add_macro(name.clone(), expansion.clone());
+      }
+      opts
+    };
+    Self {
+      inline: options.inline.then(|| opts(false)),
+      display: opts(true),
+    }
+  }
+
+  /// Replaces the delimited math in `html`'s text with KaTeX's HTML.
+  pub(crate) fn render(&self, html: &mut String) {
+    if html.contains('$') {
+      *html = html_text::map_text(html, |text, output| self.render_text(text, output));
+    }
+  }
+
+  /// Appends `text` to `output`, with its math rendered.
+  fn render_text(&self, text: &str, output: &mut String) {
+    let mut rest = text;
+    while let Some(next) = rest.find(['$', '\\']) {
+      let (before, from) = rest.split_at(next);
+      output.push_str(before);
+      rest = if let Some(after) = from.strip_prefix("\\$") {
+        output.push('$');
+        after
+      } else if let Some(after) = from.strip_prefix('\\') {
+        output.push('\\');
+        after
+      } else if let Some((rendered, len)) = self.formula(from) {
+        output.push_str(&rendered);
+        &from[len..]
+      } else {
+        // An unmatched `$` (or `$$`) is text.
+        let len = if from.starts_with("$$") { 2 } else { 1 };
+        output.push_str(&from[..len]);
+        &from[len..]
+      };
+    }
+    output.push_str(rest);
+  }
+
+  /// The rendering of the formula `from` starts with, and the length of its
+  /// source including the delimiters, if it is one.
+  fn formula(&self, from: &str) -> Option<(String, usize)> {
+    let (tex, len, opts) = if let Some(after) = from.strip_prefix("$$") {
+      let end = after.find("$$")?;
+      (&after[..end], end + 4, &self.display)
+    } else {
+      let opts = self.inline.as_ref()?;
+      let after = &from[1..];
+      let end = closing_dollar(after)?;
+      let tex = &after[..end];
+      let is_price = after[end + 1..].starts_with(|c: char| c.is_ascii_digit());
+      if tex.starts_with(char::is_whitespace) || tex.ends_with(char::is_whitespace) || is_price {
+        return None;
+      }
+      (tex, end + 2, opts)
+    };
+    if tex.trim().is_empty() {
+      return None;
+    }
+
+    match katex::render_with_opts(&unescape(tex), opts) {
+      Ok(rendered) => Some((rendered, len)),
+      Err(e) => {
+        log::warn!("Could not render math `{}`: {}", tex, e);
+        None
+      }
+    }
+  }
+}
+
+/// The index of the `$` closing inline math in `after`, skipping `\$`.
+fn closing_dollar(after: &str) -> Option<usize> {
+  let mut escaped = false;
+  for (index, c) in after.char_indices() {
+    match c {
+      '\\' => escaped = !escaped,
+      '$' if !escaped => return Some(index),
+      _ => escaped = false,
+    }
+  }
+  None
+}
+
+/// Reverses the HTML escaping Tera applies to the TeX in a template.
+fn unescape(tex: &str) -> String {
+  if !tex.contains('&') {
+    return tex.to_string();
+  }
+  tex
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&#x27;", "'")
+    .replace("&#39;", "'")
+    .replace("&#x2F;", "/")
+    .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(options: MathOptions, html: &str) -> String {
+    let mut html = html.to_string();
+    MathRenderer::new(options).render(&mut html);
+    html
+  }
+
+  #[test]
+  fn test_renders_delimited_math_in_text() {
+    let output = render(MathOptions::new(), "<p>$x^2$ and</p><p>$$\\frac{1}{2}$$</p>");
+    assert!(output.starts_with("<p><span class=\"katex\">"), "{output}");
+    assert!(output.contains("</span> and</p><p><span class=\"katex-display\">"), "{output}");
+
+    let output = render(MathOptions::new().add_macro("\\RR", "\\mathbb{R}"), "$\\RR$");
+    assert!(output.contains("mathbb"), "{output}");
+
+    let unchanged = [
+      "Prices: $5 and $10.",
+      "<code>$x$</code> <pre class=\"tex\">$$y$$</pre>",
+      "<a title=\"$x$\">a $ b</a>",
+      "$ x $ and $\\frac{$",
+      "<em>$a</em> b$",
+    ];
+    for html in unchanged {
+      assert_eq!(render(MathOptions::new(), html), html);
+    }
+    assert_eq!(render(MathOptions::new(), "costs \\$3"), "costs $3");
+    assert_eq!(render(MathOptions::new().inline(false), "$x$"), "$x$");
+  }
+}
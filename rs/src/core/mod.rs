@@ -1,4 +1,93 @@
+pub mod a11y;
 pub mod app;
+pub mod assets;
+pub mod audit;
+pub mod avatar;
+#[cfg(any(feature = "deploy", feature = "purge"))]
+pub(crate) mod aws;
+pub mod breaker;
+#[cfg(feature = "barcode")]
+pub(crate) mod barcode;
+#[cfg(feature = "devel")]
+pub(crate) mod broadcast;
+pub(crate) mod buffer;
+pub mod bundle;
+pub mod cache;
+pub mod catalog;
+#[cfg(all(feature = "devel", any(feature = "actix", feature = "hyper", feature = "warp")))]
+pub(crate) mod client;
+pub mod compare;
+pub(crate) mod comments;
+pub(crate) mod charts;
+pub mod consent;
+#[cfg(feature = "content")]
+pub mod content;
+#[cfg(feature = "deploy")]
+pub mod deploy;
+pub mod deferred;
+#[cfg(feature = "notifications")]
+pub(crate) mod desktop;
+pub mod env;
+pub mod export;
+#[cfg(feature = "devel")]
+pub mod fixtures;
+pub mod flush;
+pub mod forms;
+pub mod front_matter;
+pub(crate) mod context;
+pub(crate) mod csp;
+pub mod context_ext;
+#[cfg(any(feature = "pdf", feature = "screenshot"))]
+pub(crate) mod headless;
+pub(crate) mod hints;
+#[cfg(any(feature = "math", feature = "typography"))]
+pub(crate) mod html_text;
+pub mod hooks;
+pub mod i18n;
+pub mod icons;
+pub mod invalidation;
+pub mod lenient;
+pub mod links;
+pub mod lsp;
+#[cfg(feature = "math")]
+pub mod math;
+pub mod nav;
+pub mod ordering;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+pub mod plural;
+pub mod purge;
+#[cfg(feature = "actix")]
+pub mod proxy;
+#[cfg(all(feature = "devel", feature = "actix"))]
+pub(crate) mod replay;
+pub mod report;
+pub mod resolver;
+pub mod response;
+pub mod rtl;
+pub mod schema;
+#[cfg(feature = "sanitize")]
+pub mod sanitize;
+#[cfg(feature = "schedule")]
+pub mod schedule;
+pub mod scope;
+#[cfg(feature = "screenshot")]
+pub mod screenshot;
+pub mod search;
+pub mod sensitive;
+pub mod signed;
+pub mod svg;
+#[cfg(all(feature = "devel", feature = "actix"))]
+pub mod styleguide;
+pub mod timeago;
+pub mod typegen;
+#[cfg(feature = "typography")]
+pub mod typography;
+#[cfg(feature = "uploads")]
+pub mod uploads;
+pub mod vary;
+pub mod view;
+pub mod xss;
 
 #[cfg(feature = "devel")]
 pub mod reload;
\ No newline at end of file
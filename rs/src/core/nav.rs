@@ -0,0 +1,231 @@
+//! Navigation menus.
+//!
+//! Menus are declared in code with `TeraWebBuilder::nav` or in a YAML file
+//! with `TeraWebBuilder::nav_from_file`, and rendered with the `nav` Tera
+//! function. Each returned item is marked `current` when it is the page being
+//! rendered and `active` when the page is it or one of its descendants, based
+//! on the request path.
+//!
+//! ```yaml
+//! main:
+//!   - title: Home
+//!     url: /
+//!   - title: Docs
+//!     url: /docs
+//!     children:
+//!       - title: Guide
+//!         url: /docs/guide
+//! ```
+//!
+//! ```jinja
+//! {% for item in nav(name="main") %}
+//!   <a href="{{ item.url }}" {% if item.active %}class="active"{% endif %}>{{ item.title }}</a>
+//! {% endfor %}
+//! ```
+
+use crate::core::scope;
+use crate::error::{Result, SnapFireError};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tera::{Function, Value};
+
+/// A single entry in a navigation menu.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NavItem {
+  /// The link text.
+  pub title: String,
+  /// The link target, e.g. `/docs`.
+  pub url: String,
+  /// Nested entries, for dropdowns and sidebars.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub children: Vec<NavItem>,
+}
+
+impl NavItem {
+  /// Creates a menu entry without children.
+  pub fn new<T: Into<String>, U: Into<String>>(title: T, url: U) -> Self {
+    Self {
+      title: title.into(),
+      url: url.into(),
+      children: Vec::new(),
+    }
+  }
+
+  /// Adds a nested entry.
+  pub fn child(mut self, item: NavItem) -> Self {
+    self.children.push(item);
+    self
+  }
+
+  /// Serializes the item for a template, marking where `path` is in the tree.
+  fn to_value(&self, path: Option<&str>) -> Value {
+    let children: Vec<Value> = self.children.iter().map(|child| child.to_value(path)).collect();
+    let current = path.is_some_and(|path| trim_slash(path) == trim_slash(&self.url));
+    let active = current
+      || children.iter().any(|child| child["active"] == Value::Bool(true))
+      || path.is_some_and(|path| is_under(path, &self.url));
+
+    serde_json::json!({
+      "title": self.title,
+      "url": self.url,
+      "current": current,
+      "active": active,
+      "children": children,
+    })
+  }
+}
+
+/// Menus by name.
+type Menus = HashMap<String, Vec<NavItem>>;
+
+/// The menus available to the `nav` function.
+#[derive(Debug)]
+pub(crate) struct Navigation {
+  /// Menus declared in code.
+  declared: Menus,
+  /// The YAML file to load menus from, if any. Its menus win over declared ones.
+  file: Option<PathBuf>,
+  /// The combined menus, replaced as a whole when the file is reloaded.
+  menus: RwLock<Arc<Menus>>,
+}
+
+impl Navigation {
+  /// Creates the navigation and loads its file, if any.
+  pub(crate) fn new(declared: Menus, file: Option<PathBuf>) -> Result<Self> {
+    let navigation = Self {
+      menus: RwLock::new(Arc::new(declared.clone())),
+      declared,
+      file,
+    };
+    navigation.reload()?;
+    Ok(navigation)
+  }
+
+  /// The navigation file, if menus are loaded from one.
+  #[cfg_attr(not(feature = "devel"), allow(dead_code))]
+  pub(crate) fn file(&self) -> Option<&Path> {
+    self.file.as_deref()
+  }
+
+  /// Re-reads the navigation file. On failure, the previous menus stay active.
+  pub(crate) fn reload(&self) -> Result<()> {
+    let Some(file) = &self.file else {
+      return Ok(());
+    };
+
+    let config_error = |message: String| SnapFireError::Config {
+      path: file.display().to_string(),
+      message,
+    };
+    let source = std::fs::read_to_string(file).map_err(|e| config_error(e.to_string()))?;
+    let loaded: Menus = serde_yaml::from_str(&source).map_err(|e| config_error(e.to_string()))?;
+
+    let mut menus = self.declared.clone();
+    menus.extend(loaded);
+    *self.menus.write() = Arc::new(menus);
+    Ok(())
+  }
+
+  /// The menu called `name`, marked up for `path`.
+  fn menu(&self, name: &str, path: Option<&str>) -> Option<Value> {
+    let menus = Arc::clone(&self.menus.read());
+    let items = menus.get(name)?;
+    Some(Value::Array(items.iter().map(|item| item.to_value(path)).collect()))
+  }
+}
+
+/// The `nav(name="main")` Tera function.
+///
+/// The current path comes from the request being rendered; pass `path="..."`
+/// to override it (e.g. when rendering outside a request).
+pub(crate) struct NavFunction(pub(crate) Arc<Navigation>);
+
+impl Function for NavFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let name = args
+      .get("name")
+      .and_then(Value::as_str)
+      .ok_or_else(|| tera::Error::msg("The `nav` function requires a `name` argument, e.g. nav(name=\"main\")"))?;
+
+    let path = match args.get("path").and_then(Value::as_str) {
+      Some(path) => Some(path.to_string()),
+      None => scope::current_path(),
+    };
+
+    self
+      .0
+      .menu(name, path.as_deref())
+      .ok_or_else(|| tera::Error::msg(format!("Unknown navigation menu `{}`", name)))
+  }
+}
+
+fn trim_slash(url: &str) -> &str {
+  match url.trim_end_matches('/') {
+    "" => "/",
+    trimmed => trimmed,
+  }
+}
+
+/// Whether `path` is a descendant of `url`. The root only matches itself.
+fn is_under(path: &str, url: &str) -> bool {
+  let url = trim_slash(url);
+  url != "/" && path.strip_prefix(url).is_some_and(|rest| rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  fn call(navigation: &Arc<Navigation>, path: &str) -> Value {
+    let args = HashMap::from([
+      ("name".to_string(), Value::from("main")),
+      ("path".to_string(), Value::from(path)),
+    ]);
+    NavFunction(Arc::clone(navigation)).call(&args).unwrap()
+  }
+
+  #[test]
+  fn test_marks_active_items() {
+    let menus = HashMap::from([(
+      "main".to_string(),
+      vec![
+        NavItem::new("Home", "/"),
+        NavItem::new("Docs", "/docs").child(NavItem::new("Guide", "/docs/guide")),
+      ],
+    )]);
+    let navigation = Arc::new(Navigation::new(menus, None).unwrap());
+
+    let menu = call(&navigation, "/docs/guide/");
+    assert_eq!(menu[0]["active"], false);
+    assert_eq!(menu[1]["active"], true);
+    assert_eq!(menu[1]["current"], false);
+    assert_eq!(menu[1]["children"][0]["current"], true);
+
+    let menu = call(&navigation, "/");
+    assert_eq!(menu[0]["current"], true);
+    assert_eq!(menu[1]["active"], false);
+  }
+
+  #[test]
+  fn test_loads_and_reloads_file() {
+    let temp_dir = tempdir().unwrap();
+    let file = temp_dir.path().join("nav.yaml");
+    fs::write(&file, "main:\n  - title: Home\n    url: /\n").unwrap();
+    let navigation = Arc::new(Navigation::new(Menus::new(), Some(file.clone())).unwrap());
+    assert_eq!(call(&navigation, "/")[0]["title"], "Home");
+
+    fs::write(&file, "main:\n  - title: Start\n    url: /\n").unwrap();
+    navigation.reload().unwrap();
+    assert_eq!(call(&navigation, "/")[0]["title"], "Start");
+
+    // A broken file keeps the last good menus.
+    fs::write(&file, "main: [").unwrap();
+    assert!(matches!(navigation.reload(), Err(SnapFireError::Config { .. })));
+    assert_eq!(call(&navigation, "/")[0]["title"], "Start");
+  }
+}
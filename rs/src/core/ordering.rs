@@ -0,0 +1,165 @@
+//! Deterministic output for templates that loop over maps.
+//!
+//! `{% for key, value in map %}` visits the map's keys in the order
+//! `serde_json` stores them. That order is sorted unless some crate in the
+//! build enables `serde_json`'s `preserve_order` feature, in which case it
+//! becomes insertion order, and a `HashMap` inserts in a different order on
+//! every run, which breaks snapshot tests.
+//!
+//! `TeraWebBuilder::sort_context_keys(true)` sorts the keys of every object
+//! in the context before each render, so such loops always run in key
+//! order. Without it, debug builds in which maps keep insertion order warn
+//! once about each template that loops over a map:
+//!
+//! ```text
+//! orders.html loops over the map `order.totals`, whose key order may change between runs; ...
+//! ```
+
+use parking_lot::Mutex;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use tera::ast::{ExprVal, Node};
+use tera::{Context, Tera};
+
+/// The templates already warned about, so each is only named once.
+static WARNED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// Whether `serde_json` maps keep their insertion order in this build,
+/// rather than sorting their keys.
+pub fn maps_keep_insertion_order() -> bool {
+  static KEEP: LazyLock<bool> = LazyLock::new(|| {
+    let mut map = Map::new();
+    map.insert("b".to_string(), Value::Null);
+    map.insert("a".to_string(), Value::Null);
+    map.keys().next().is_some_and(|key| key == "b")
+  });
+  *KEEP
+}
+
+/// `context`, with the keys of every object in it sorted.
+pub(crate) fn sorted(context: &Context) -> Context {
+  Context::from_value(sort_keys(context.clone().into_json())).expect("a context is an object")
+}
+
+fn sort_keys(value: Value) -> Value {
+  match value {
+    Value::Object(map) => {
+      let mut entries: Vec<_> = map.into_iter().collect();
+      entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+      Value::Object(entries.into_iter().map(|(key, value)| (key, sort_keys(value))).collect())
+    }
+    Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+    value => value,
+  }
+}
+
+/// Logs, once per template, the map loops of `tpl` (with its parents and
+/// includes), when maps keep insertion order.
+pub(crate) fn warn_about_map_loops(tera: &Tera, tpl: &str) {
+  if !maps_keep_insertion_order() || !WARNED.lock().insert(tpl.to_string()) {
+    return;
+  }
+  for (template, container) in map_loops(tera, tpl) {
+    log::warn!(
+      "{template} loops over the map `{container}`, whose key order may change between runs; \
+       enable `sort_context_keys` or sort it in the handler"
+    );
+  }
+}
+
+/// The `(template, container)` of each `for key, value in ...` loop `tpl`
+/// runs, through its parents and includes.
+pub(crate) fn map_loops(tera: &Tera, tpl: &str) -> Vec<(String, String)> {
+  let mut loops = Vec::new();
+  let mut visited = HashSet::new();
+  collect_template(tera, tpl, &mut visited, &mut loops);
+  loops
+}
+
+fn collect_template(tera: &Tera, tpl: &str, visited: &mut HashSet<String>, loops: &mut Vec<(String, String)>) {
+  let Ok(template) = tera.get_template(tpl) else {
+    return;
+  };
+  for name in std::iter::once(tpl).chain(template.parents.iter().map(String::as_str)) {
+    if !visited.insert(name.to_string()) {
+      continue;
+    }
+    if let Ok(template) = tera.get_template(name) {
+      collect_nodes(tera, name, &template.ast, visited, loops);
+    }
+  }
+}
+
+fn collect_nodes(
+  tera: &Tera,
+  template: &str,
+  nodes: &[Node],
+  visited: &mut HashSet<String>,
+  loops: &mut Vec<(String, String)>,
+) {
+  for node in nodes {
+    match node {
+      Node::Include(_, names, _) => {
+        if let Some(name) = names.iter().find(|name| tera.get_template(name).is_ok()) {
+          collect_template(tera, name, visited, loops);
+        }
+      }
+      Node::Forloop(_, forloop, _) => {
+        // Only maps are looped over with a key.
+        if forloop.key.is_some() {
+          let container = match &forloop.container.val {
+            ExprVal::Ident(ident) => ident.clone(),
+            _ => "(expression)".to_string(),
+          };
+          loops.push((template.to_string(), container));
+        }
+        collect_nodes(tera, template, &forloop.body, visited, loops);
+        if let Some(body) = &forloop.empty_body {
+          collect_nodes(tera, template, body, visited, loops);
+        }
+      }
+      Node::Block(_, block, _) => collect_nodes(tera, template, &block.body, visited, loops),
+      Node::FilterSection(_, section, _) => collect_nodes(tera, template, &section.body, visited, loops),
+      Node::If(branches, _) => {
+        for (_, _, body) in &branches.conditions {
+          collect_nodes(tera, template, body, visited, loops);
+        }
+        if let Some((_, body)) = &branches.otherwise {
+          collect_nodes(tera, template, body, visited, loops);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_sorts_nested_keys_and_finds_map_loops() {
+    let mut map = Map::new();
+    map.insert("zeta".to_string(), json!({"b": 2, "a": 1}));
+    map.insert("alpha".to_string(), json!([{"y": 1, "x": 2}]));
+    let sorted = sort_keys(Value::Object(map));
+    assert_eq!(
+      serde_json::to_string(&sorted).unwrap(),
+      r#"{"alpha":[{"x":2,"y":1}],"zeta":{"a":1,"b":2}}"#
+    );
+
+    let mut tera = Tera::default();
+    tera
+      .add_raw_templates([
+        ("row.html", "{% for name, value in row.cells %}{{ value }}{% endfor %}"),
+        (
+          "table.html",
+          "{% for row in rows %}{% include \"row.html\" %}{% endfor %}{% for tag in tags %}{{ tag }}{% endfor %}",
+        ),
+      ])
+      .unwrap();
+    assert_eq!(map_loops(&tera, "table.html"), [("row.html".to_string(), "row.cells".to_string())]);
+  }
+}
@@ -0,0 +1,267 @@
+//! Rendering templates to PDF.
+//!
+//! `TeraWeb::render_pdf` renders a template like any page, then converts the
+//! HTML with the app's [`PdfBackend`], so invoices and reports share layouts,
+//! partials, and globals with the web pages. The backend is set with
+//! `TeraWebBuilder::pdf_backend`; by default it is [`Chromium`] in headless
+//! mode. [`WeasyPrint`] is also built in, and any other converter, such as a
+//! pure-Rust renderer or a conversion service, can implement the trait:
+//!
+//! ```rust,no_run
+//! # use snapfire::core::pdf::{PdfOptions, WeasyPrint};
+//! # use tera::Context;
+//! # fn main() -> snapfire::Result<()> {
+//! let app = snapfire::TeraWeb::builder("templates/**/*")
+//!   .pdf_backend(WeasyPrint::new())
+//!   .build()?;
+//! let options = PdfOptions::new().page_size("A4").margin("20mm").base_url("https://example.com/");
+//! let pdf = app.render_pdf("invoice.html", Context::new(), &options)?.attachment("invoice.pdf");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The page size, orientation, and margins are applied with an `@page` rule
+//! added to the document, which both built-in backends honor; stylesheets
+//! may still override it. Conversion runs an external process, so Actix
+//! handlers should call `render_pdf` inside `web::block`; the resulting
+//! [`Pdf`] is a responder, served as `application/pdf`.
+
+use crate::core::headless;
+use crate::error::{Result, SnapFireError};
+use bytes::Bytes;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Converts rendered HTML to a PDF document.
+pub trait PdfBackend: Send + Sync {
+  /// A short name for error messages, e.g. `chromium`.
+  fn name(&self) -> &str;
+
+  /// Converts the complete HTML document `html` to PDF bytes.
+  fn convert(&self, html: &str) -> Result<Vec<u8>>;
+}
+
+impl std::fmt::Debug for dyn PdfBackend {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("PdfBackend").field(&self.name()).finish()
+  }
+}
+
+/// Page settings for `TeraWeb::render_pdf`.
+#[derive(Debug, Clone, Default)]
+pub struct PdfOptions {
+  page_size: Option<String>,
+  landscape: bool,
+  margin: Option<String>,
+  base_url: Option<String>,
+}
+
+impl PdfOptions {
+  /// The backend's and stylesheets' defaults, usually A4 or Letter, portrait.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The paper size, as a CSS `@page` size: `A4`, `letter`, or a width and
+  /// height like `210mm 297mm`.
+  pub fn page_size(mut self, size: &str) -> Self {
+    self.page_size = Some(size.to_string());
+    self
+  }
+
+  /// Whether pages are in landscape orientation.
+  pub fn landscape(mut self, enabled: bool) -> Self {
+    self.landscape = enabled;
+    self
+  }
+
+  /// The page margins, as a CSS margin value like `20mm` or `1in 0.75in`.
+  pub fn margin(mut self, margin: &str) -> Self {
+    self.margin = Some(margin.to_string());
+    self
+  }
+
+  /// The URL relative links, images, and stylesheets resolve against, since
+  /// the converter doesn't know where the page would have been served.
+  pub fn base_url(mut self, url: &str) -> Self {
+    self.base_url = Some(url.to_string());
+    self
+  }
+
+  /// `html` with the `@page` rule and `<base>` element for these options,
+  /// at the start of its `<head>` so the document's own styles win.
+  pub(crate) fn apply(&self, html: &str) -> String {
+    let mut page = Vec::new();
+    match (&self.page_size, self.landscape) {
+      (Some(size), true) => page.push(format!("size: {size} landscape")),
+      (Some(size), false) => page.push(format!("size: {size}")),
+      (None, true) => page.push("size: landscape".to_string()),
+      (None, false) => {}
+    }
+    if let Some(margin) = &self.margin {
+      page.push(format!("margin: {margin}"));
+    }
+
+    let mut head = self.base_url.as_deref().map(headless::base_element).unwrap_or_default();
+    if !page.is_empty() {
+      head.push_str(&format!("<style>@page {{ {} }}</style>", page.join("; ")));
+    }
+    headless::insert_in_head(html, &head)
+  }
+}
+
+/// A rendered PDF, served by Actix as `application/pdf`.
+#[derive(Debug, Clone)]
+pub struct Pdf {
+  pub(crate) bytes: Bytes,
+  pub(crate) filename: Option<String>,
+}
+
+impl Pdf {
+  /// Serves the document as a download named `filename` (with a
+  /// `Content-Disposition: attachment` header) instead of inline.
+  pub fn attachment(mut self, filename: &str) -> Self {
+    self.filename = Some(filename.to_string());
+    self
+  }
+
+  /// The PDF document.
+  pub fn bytes(&self) -> &Bytes {
+    &self.bytes
+  }
+
+  /// The PDF document, e.g. for writing to a file or attaching to an email.
+  pub fn into_bytes(self) -> Bytes {
+    self.bytes
+  }
+}
+
+/// Converts with Chromium (or Chrome) in headless mode, printing the page as
+/// the browser's "Save as PDF" would, without headers and footers.
+#[derive(Debug, Clone)]
+pub struct Chromium {
+  binary: PathBuf,
+}
+
+impl Default for Chromium {
+  fn default() -> Self {
+    Self {
+      binary: PathBuf::from("chromium"),
+    }
+  }
+}
+
+impl Chromium {
+  /// Runs the `chromium` found on the `PATH`.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The browser executable to run, e.g. `google-chrome`.
+  pub fn binary<P: Into<PathBuf>>(mut self, binary: P) -> Self {
+    self.binary = binary.into();
+    self
+  }
+}
+
+impl PdfBackend for Chromium {
+  fn name(&self) -> &str {
+    "chromium"
+  }
+
+  fn convert(&self, html: &str) -> Result<Vec<u8>> {
+    let args = ["--no-pdf-header-footer".to_string()];
+    headless::run_chromium(&self.binary, &args, "--print-to-pdf", "pdf", html).map_err(|message| SnapFireError::Pdf {
+      backend: self.name().to_string(),
+      message,
+    })
+  }
+}
+
+/// Converts with [WeasyPrint](https://weasyprint.org), which implements CSS
+/// paged media (running headers, page counters) more fully than browsers.
+#[derive(Debug, Clone)]
+pub struct WeasyPrint {
+  binary: PathBuf,
+}
+
+impl Default for WeasyPrint {
+  fn default() -> Self {
+    Self {
+      binary: PathBuf::from("weasyprint"),
+    }
+  }
+}
+
+impl WeasyPrint {
+  /// Runs the `weasyprint` found on the `PATH`.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The `weasyprint` executable to run.
+  pub fn binary<P: Into<PathBuf>>(mut self, binary: P) -> Self {
+    self.binary = binary.into();
+    self
+  }
+}
+
+impl PdfBackend for WeasyPrint {
+  fn name(&self) -> &str {
+    "weasyprint"
+  }
+
+  fn convert(&self, html: &str) -> Result<Vec<u8>> {
+    let error = |message: String| SnapFireError::Pdf {
+      backend: self.name().to_string(),
+      message,
+    };
+    // `-` for both: the HTML on stdin, the PDF on stdout.
+    let mut child = Command::new(&self.binary)
+      .args(["-", "-"])
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .map_err(|e| error(e.to_string()))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // Write from another thread, so a large document can't fill both pipes.
+    let html = html.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(html.as_bytes()));
+    let result = child.wait_with_output().map_err(|e| error(e.to_string()))?;
+    let written = writer.join().unwrap_or(Ok(()));
+
+    // A converter failing early also breaks the pipe; its own error says why.
+    if !result.status.success() {
+      return Err(error(String::from_utf8_lossy(&result.stderr).trim().to_string()));
+    }
+    written.map_err(|e| error(format!("could not send the document: {e}")))?;
+    Ok(result.stdout)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_options_add_page_rule_and_base() {
+    let html = "<!DOCTYPE html><html><HEAD lang=\"en\"><style>body{}</style></head><body>x</body></html>";
+    let options = PdfOptions::new().page_size("A4").landscape(true).margin("20mm").base_url("https://example.com/");
+    assert_eq!(
+      options.apply(html),
+      "<!DOCTYPE html><html><HEAD lang=\"en\"><base href=\"https://example.com/\"><style>@page { size: A4 landscape; margin: 20mm }</style><style>body{}</style></head><body>x</body></html>"
+    );
+    assert_eq!(PdfOptions::new().landscape(true).apply("x"), "<style>@page { size: landscape }</style>x");
+    assert_eq!(PdfOptions::new().apply(html), html);
+  }
+
+  #[test]
+  fn test_missing_converter_is_reported() {
+    let error = WeasyPrint::new().binary("/nonexistent/weasyprint").convert("<p>x</p>").unwrap_err();
+    assert!(matches!(error, SnapFireError::Pdf { ref backend, .. } if backend == "weasyprint"), "{error}");
+    let error = Chromium::new().binary("/nonexistent/chromium").convert("<p>x</p>").unwrap_err();
+    assert!(matches!(error, SnapFireError::Pdf { ref backend, .. } if backend == "chromium"), "{error}");
+  }
+}
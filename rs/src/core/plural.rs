@@ -0,0 +1,291 @@
+//! Locale-aware plural forms, following the CLDR cardinal plural rules.
+//!
+//! The `plural` filter picks the form matching a count in the page's locale:
+//!
+//! ```jinja
+//! {{ files | length | plural(one="{count} file", few="{count} files", many="{count} files", other="{count} files") }}
+//! ```
+//!
+//! The forms are named after the CLDR categories (`zero`, `one`, `two`,
+//! `few`, `many`, `other`); a category without a form falls back to `other`,
+//! which is required. `{count}` is replaced with the count. The locale is the
+//! `locale` context value (set per request by the `LocalePrefix` middleware),
+//! unless a `locale` argument overrides it, and otherwise the default locale.
+
+use crate::core::scope;
+use serde::Serialize;
+use std::collections::HashMap;
+use tera::{Filter, Value};
+
+/// A CLDR plural category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluralCategory {
+  Zero,
+  One,
+  Two,
+  Few,
+  Many,
+  Other,
+}
+
+impl PluralCategory {
+  /// The category's CLDR name, e.g. `few`.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Zero => "zero",
+      Self::One => "one",
+      Self::Two => "two",
+      Self::Few => "few",
+      Self::Many => "many",
+      Self::Other => "other",
+    }
+  }
+}
+
+/// The CLDR operands of a number, as written (so `1.50` has two visible
+/// fraction digits).
+#[derive(Debug, Clone, Copy)]
+struct Operands {
+  /// The absolute value.
+  n: f64,
+  /// The integer digits.
+  i: u64,
+  /// The number of visible fraction digits.
+  v: usize,
+  /// The visible fraction digits, as an integer.
+  f: u64,
+}
+
+impl Operands {
+  fn parse(number: &str) -> Option<Self> {
+    let number = number.trim().trim_start_matches('-');
+    let n: f64 = number.parse().ok().filter(|n: &f64| n.is_finite())?;
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+      // Exponents and other notations: fall back to the numeric value.
+      return Some(Self {
+        n,
+        i: n.trunc() as u64,
+        v: 0,
+        f: 0,
+      });
+    }
+    Some(Self {
+      n,
+      i: integer.parse().ok()?,
+      v: fraction.len(),
+      f: if fraction.is_empty() { 0 } else { fraction.parse().ok()? },
+    })
+  }
+}
+
+/// Whether `x` is a whole number in `low..=high`, as in CLDR range conditions.
+fn within(x: f64, low: u64, high: u64) -> bool {
+  x.fract() == 0.0 && x >= low as f64 && x <= high as f64
+}
+
+/// The plural category of `count` in `locale`, e.g. `few` for 3 in `pl`.
+///
+/// `count` is a number as written, like `"2"` or `"1.5"`; unparseable input
+/// is `other`. Locales without built-in rules use the English ones.
+pub fn plural_category(locale: &str, count: &str) -> PluralCategory {
+  use PluralCategory::*;
+
+  let Some(Operands { n, i, v, f }) = Operands::parse(count) else {
+    return Other;
+  };
+  let locale = locale.to_ascii_lowercase().replace('_', "-");
+  let language = locale.split('-').next().unwrap_or_default();
+  // `many` for exact millions in the Romance languages, e.g. "1 000 000 de fichiers".
+  let million = v == 0 && i != 0 && i % 1_000_000 == 0;
+
+  match language {
+    "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" | "lo" | "my" | "km" => Other,
+    "fr" => match () {
+      _ if i <= 1 => One,
+      _ if million => Many,
+      _ => Other,
+    },
+    "pt" if locale != "pt-pt" => match () {
+      _ if i <= 1 => One,
+      _ if million => Many,
+      _ => Other,
+    },
+    "pt" | "it" | "ca" => match () {
+      _ if i == 1 && v == 0 => One,
+      _ if million => Many,
+      _ => Other,
+    },
+    "es" => match () {
+      _ if n == 1.0 => One,
+      _ if million => Many,
+      _ => Other,
+    },
+    "ru" | "uk" => match () {
+      _ if v != 0 => Other,
+      _ if i % 10 == 1 && i % 100 != 11 => One,
+      _ if (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100)) => Few,
+      _ => Many,
+    },
+    "pl" => match () {
+      _ if v != 0 => Other,
+      _ if i == 1 => One,
+      _ if (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100)) => Few,
+      _ => Many,
+    },
+    "cs" | "sk" => match () {
+      _ if v != 0 => Many,
+      _ if i == 1 => One,
+      _ if (2..=4).contains(&i) => Few,
+      _ => Other,
+    },
+    "hr" | "sr" | "bs" => {
+      let (units, tens) = if v == 0 { (i % 10, i % 100) } else { (f % 10, f % 100) };
+      match () {
+        _ if units == 1 && tens != 11 => One,
+        _ if (2..=4).contains(&units) && !(12..=14).contains(&tens) => Few,
+        _ => Other,
+      }
+    }
+    "sl" => match () {
+      _ if v != 0 => Few,
+      _ if i % 100 == 1 => One,
+      _ if i % 100 == 2 => Two,
+      _ if (3..=4).contains(&(i % 100)) => Few,
+      _ => Other,
+    },
+    "lt" => match () {
+      _ if f != 0 => Many,
+      _ if within(n % 100.0, 11, 19) => Other,
+      _ if within(n % 10.0, 1, 1) => One,
+      _ if within(n % 10.0, 2, 9) => Few,
+      _ => Other,
+    },
+    "ro" => match () {
+      _ if i == 1 && v == 0 => One,
+      _ if v != 0 || n == 0.0 || within(n % 100.0, 1, 19) => Few,
+      _ => Other,
+    },
+    "ar" => match () {
+      _ if n == 0.0 => Zero,
+      _ if n == 1.0 => One,
+      _ if n == 2.0 => Two,
+      _ if within(n % 100.0, 3, 10) => Few,
+      _ if within(n % 100.0, 11, 99) => Many,
+      _ => Other,
+    },
+    "he" => match () {
+      _ if (i == 1 && v == 0) || (i == 0 && v != 0) => One,
+      _ if i == 2 && v == 0 => Two,
+      _ => Other,
+    },
+    "ga" => match () {
+      _ if n == 1.0 => One,
+      _ if n == 2.0 => Two,
+      _ if within(n, 3, 6) => Few,
+      _ if within(n, 7, 10) => Many,
+      _ => Other,
+    },
+    "cy" => match n {
+      0.0 => Zero,
+      1.0 => One,
+      2.0 => Two,
+      3.0 => Few,
+      6.0 => Many,
+      _ => Other,
+    },
+    "hi" | "bn" | "fa" | "gu" | "kn" | "mr" | "zu" | "am" => match () {
+      _ if i == 0 || n == 1.0 => One,
+      _ => Other,
+    },
+    _ => match () {
+      _ if i == 1 && v == 0 => One,
+      _ => Other,
+    },
+  }
+}
+
+/// The `plural(one=..., few=..., other=..., locale=...)` Tera filter.
+pub(crate) struct Plural {
+  /// The locale used when the page has none.
+  pub(crate) default_locale: String,
+}
+
+impl Filter for Plural {
+  fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let count = match value {
+      Value::Number(number) => number.to_string(),
+      Value::String(number) => number.clone(),
+      _ => return Err(tera::Error::msg("The `plural` filter can only be applied to numbers")),
+    };
+    let locale = match args.get("locale") {
+      Some(locale) => locale
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("The `locale` argument of `plural` must be a string"))?
+        .to_string(),
+      None => scope::current_locale().unwrap_or_else(|| self.default_locale.clone()),
+    };
+
+    let category = plural_category(&locale, &count);
+    let form = args
+      .get(category.as_str())
+      .or_else(|| args.get("other"))
+      .ok_or_else(|| tera::Error::msg("The `plural` filter requires an `other` form"))?
+      .as_str()
+      .ok_or_else(|| tera::Error::msg("The forms of `plural` must be strings"))?;
+    Ok(Value::from(form.replace("{count}", &count)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::PluralCategory::*;
+  use super::*;
+
+  #[test]
+  fn test_cldr_categories() {
+    let categories = |locale: &str, counts: &[&str]| -> Vec<PluralCategory> {
+      counts.iter().map(|count| plural_category(locale, count)).collect()
+    };
+
+    assert_eq!(categories("en", &["0", "1", "2", "1.0"]), [Other, One, Other, Other]);
+    assert_eq!(categories("fr", &["0", "1", "1.5", "2", "1000000"]), [One, One, One, Other, Many]);
+    assert_eq!(
+      categories("ru", &["1", "2", "5", "11", "21", "22", "25", "1.5"]),
+      [One, Few, Many, Many, One, Few, Many, Other]
+    );
+    assert_eq!(categories("pl", &["1", "3", "5", "12", "22", "101"]), [One, Few, Many, Many, Few, Many]);
+    assert_eq!(categories("cs", &["1", "3", "5", "1.5"]), [One, Few, Other, Many]);
+    assert_eq!(
+      categories("ar", &["0", "1", "2", "3", "11", "100", "102"]),
+      [Zero, One, Two, Few, Many, Other, Other]
+    );
+    assert_eq!(categories("ja", &["1"]), [Other]);
+    assert_eq!(categories("pt-BR", &["0"]), [One]);
+    assert_eq!(categories("pt_PT", &["0"]), [Other]);
+  }
+
+  #[test]
+  fn test_filter_uses_current_locale() {
+    let filter = Plural {
+      default_locale: "en".to_string(),
+    };
+    let args: HashMap<String, Value> = [("one", "{count} plik"), ("few", "{count} pliki"), ("other", "{count} plików")]
+      .into_iter()
+      .map(|(category, form)| (category.to_string(), Value::from(form)))
+      .collect();
+
+    // Without a page locale, English rules pick `other` for 3.
+    assert_eq!(filter.filter(&Value::from(3), &args).unwrap(), "3 plików");
+    scope::with_locale(Some("pl"), || {
+      assert_eq!(filter.filter(&Value::from(3), &args).unwrap(), "3 pliki");
+      // `many` has no form, so `other` is used.
+      assert_eq!(filter.filter(&Value::from(5), &args).unwrap(), "5 plików");
+    });
+
+    let mut args = args;
+    args.remove("other");
+    assert!(filter.filter(&Value::from(5), &args).is_err());
+  }
+}
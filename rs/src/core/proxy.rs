@@ -0,0 +1,278 @@
+//! The scheme, host, and client address of a request behind proxies.
+//!
+//! A reverse proxy terminating TLS forwards plain HTTP, so the app sees the
+//! wrong scheme (and often host) unless it reads the `Forwarded` or
+//! `X-Forwarded-*` headers the proxy adds. Anyone can send those headers,
+//! so they are only believed from the addresses configured with
+//! `TeraWebBuilder::trusted_proxies`; otherwise the connection's own
+//! scheme, `Host` header, and peer address are used. Clients can also
+//! prepend entries of their own, so only those the trusted proxies added
+//! count: the scheme and host are the ones the outermost of them saw.
+
+use serde::Serialize;
+use std::net::IpAddr;
+
+/// Where a request came from, as the client saw it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RequestOrigin {
+  /// `http` or `https`.
+  pub scheme: String,
+  /// The host, with the port if not the default, e.g. `example.com`.
+  pub host: String,
+  /// The client's address: the peer, or the first untrusted address the
+  /// trusted proxies forwarded for.
+  pub client_ip: Option<IpAddr>,
+}
+
+impl RequestOrigin {
+  /// The origin as a URL without a trailing slash, e.g. `https://example.com`.
+  pub fn url(&self) -> String {
+    format!("{}://{}", self.scheme, self.host)
+  }
+
+  /// Whether the client used TLS.
+  pub fn is_secure(&self) -> bool {
+    self.scheme == "https"
+  }
+}
+
+/// The addresses and CIDR ranges whose forwarding headers are believed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TrustedProxies {
+  ranges: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+  /// Parses addresses (`10.0.0.1`, `::1`) and ranges (`10.0.0.0/8`).
+  pub(crate) fn parse(entries: &[String]) -> Result<Self, String> {
+    let ranges = entries
+      .iter()
+      .map(|entry| {
+        let invalid = || format!("`{entry}` is not an IP address or CIDR range");
+        let (address, prefix) = match entry.trim().split_once('/') {
+          Some((address, prefix)) => (address, Some(prefix)),
+          None => (entry.trim(), None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+          Some(prefix) => prefix.parse::<u8>().ok().filter(|prefix| *prefix <= max).ok_or_else(invalid)?,
+          None => max,
+        };
+        // Match IPv4-mapped ranges against IPv4 peers.
+        match address {
+          IpAddr::V6(v6) if prefix >= 96 && let Some(v4) = v6.to_ipv4_mapped() => Ok((IpAddr::V4(v4), prefix - 96)),
+          _ => Ok((address, prefix)),
+        }
+      })
+      .collect::<Result<_, String>>()?;
+    Ok(Self { ranges })
+  }
+
+  /// Whether `ip` is a trusted proxy.
+  pub(crate) fn contains(&self, ip: IpAddr) -> bool {
+    let ip = canonical(ip);
+    self.ranges.iter().any(|(network, prefix)| match (*network, ip) {
+      (IpAddr::V4(network), IpAddr::V4(ip)) => masked(u32::from(network).into(), *prefix, 32) == masked(u32::from(ip).into(), *prefix, 32),
+      (IpAddr::V6(network), IpAddr::V6(ip)) => masked(u128::from(network), *prefix, 128) == masked(u128::from(ip), *prefix, 128),
+      _ => false,
+    })
+  }
+
+  /// The origin of a request from `peer`, made over `scheme` with the
+  /// `Host` header `host`; `header` looks up the request's other headers.
+  pub(crate) fn resolve<'a>(
+    &self,
+    peer: Option<IpAddr>,
+    scheme: &str,
+    host: &str,
+    header: impl Fn(&str) -> Option<&'a str>,
+  ) -> RequestOrigin {
+    let mut origin = RequestOrigin {
+      scheme: scheme.to_string(),
+      host: host.to_string(),
+      client_ip: peer,
+    };
+    if !peer.is_some_and(|peer| self.contains(peer)) {
+      return origin;
+    }
+
+    // `Forwarded` (RFC 7239) wins over the older `X-Forwarded-*` headers.
+    let forwarded: Vec<Vec<(String, String)>> = header("forwarded").map(parse_forwarded).unwrap_or_default();
+    let param = |element: &Vec<(String, String)>, name: &str| element.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+
+    // Each proxy appends the address it received the request from, so the
+    // client is the last one not added by a trusted proxy.
+    let chain: Vec<Option<IpAddr>> = if forwarded.is_empty() {
+      header("x-forwarded-for")
+        .map(|value| value.split(',').map(|address| parse_address(address.trim())).collect())
+        .unwrap_or_default()
+    } else {
+      forwarded.iter().map(|element| param(element, "for").and_then(|value| parse_address(&value))).collect()
+    };
+    // The number of entries, from the right, that trusted proxies added.
+    let mut trusted_hops = 1;
+    for (hops, address) in chain.iter().rev().enumerate() {
+      trusted_hops = hops + 1;
+      match address {
+        Some(address) if self.contains(*address) => origin.client_ip = Some(*address),
+        Some(address) => {
+          origin.client_ip = Some(*address);
+          break;
+        }
+        // An obfuscated or unknown hop ends what can be traced.
+        None => break,
+      }
+    }
+
+    // The scheme and host are those the outermost trusted proxy saw; entries
+    // left of its own were sent by the client.
+    let outermost = |name: &str| -> Option<String> {
+      if !forwarded.is_empty() {
+        let trusted = &forwarded[forwarded.len().saturating_sub(trusted_hops)..];
+        return trusted.iter().find_map(|element| param(element, name));
+      }
+      let values: Vec<&str> = header(&format!("x-forwarded-{name}"))?.split(',').map(str::trim).collect();
+      values.get(values.len().saturating_sub(trusted_hops)).map(|value| value.to_string())
+    };
+    if let Some(scheme) = outermost("proto").filter(|scheme| matches!(scheme.to_ascii_lowercase().as_str(), "http" | "https")) {
+      origin.scheme = scheme.to_ascii_lowercase();
+    }
+    if let Some(host) = outermost("host").filter(|host| is_host(host)) {
+      origin.host = host;
+    }
+    origin
+  }
+}
+
+/// Whether `value` is a host name or IP address, with an optional port.
+fn is_host(value: &str) -> bool {
+  let (host, port) = match value.strip_prefix('[') {
+    Some(rest) => match rest.split_once(']') {
+      Some((address, port)) if address.parse::<std::net::Ipv6Addr>().is_ok() && (port.is_empty() || port.starts_with(':')) => {
+        (None, port.strip_prefix(':'))
+      }
+      _ => return false,
+    },
+    None => match value.split_once(':') {
+      Some((host, port)) => (Some(host), Some(port)),
+      None => (Some(value), None),
+    },
+  };
+  let valid_name = |host: &str| {
+    host.len() <= 253
+      && host.split('.').all(|label| {
+        !label.is_empty()
+          && label.len() <= 63
+          && !label.starts_with('-')
+          && !label.ends_with('-')
+          && label.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+      })
+  };
+  host.is_none_or(valid_name) && port.is_none_or(|port| port.parse::<u16>().is_ok())
+}
+
+/// `ip` with IPv4-mapped IPv6 addresses as IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+  match ip {
+    IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+    IpAddr::V4(_) => ip,
+  }
+}
+
+/// The first `prefix` bits of a `bits`-bit address.
+fn masked(address: u128, prefix: u8, bits: u32) -> u128 {
+  if prefix == 0 {
+    0
+  } else {
+    address >> (bits - u32::from(prefix))
+  }
+}
+
+/// The elements of a `Forwarded` header, as lowercase-keyed pairs.
+fn parse_forwarded(value: &str) -> Vec<Vec<(String, String)>> {
+  value
+    .split(',')
+    .map(|element| {
+      element
+        .split(';')
+        .filter_map(|pair| {
+          let (key, value) = pair.split_once('=')?;
+          Some((key.trim().to_ascii_lowercase(), value.trim().trim_matches('"').to_string()))
+        })
+        .collect()
+    })
+    .collect()
+}
+
+/// The address in a `for=` value or `X-Forwarded-For` entry, without a port.
+fn parse_address(value: &str) -> Option<IpAddr> {
+  if let Some(rest) = value.strip_prefix('[') {
+    return rest.split(']').next()?.parse().ok();
+  }
+  value
+    .parse()
+    .ok()
+    .or_else(|| value.rsplit_once(':').and_then(|(address, _)| address.parse().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashMap;
+
+  fn resolve(proxies: &[&str], peer: &str, headers: &[(&str, &str)]) -> RequestOrigin {
+    let proxies = TrustedProxies::parse(&proxies.iter().map(|proxy| proxy.to_string()).collect::<Vec<_>>()).unwrap();
+    let headers: HashMap<&str, &str> = headers.iter().copied().collect();
+    proxies.resolve(peer.parse().ok(), "http", "internal:8080", |name| headers.get(name).copied())
+  }
+
+  #[test]
+  fn test_forwarded_headers_are_only_believed_from_trusted_proxies() {
+    let headers = [
+      ("x-forwarded-proto", "https"),
+      ("x-forwarded-host", "example.com"),
+      ("x-forwarded-for", "203.0.113.9, 10.0.0.2"),
+    ];
+    let origin = resolve(&["10.0.0.0/8"], "10.0.0.1", &headers);
+    assert_eq!(origin.url(), "https://example.com");
+    assert_eq!(origin.client_ip, "203.0.113.9".parse().ok());
+
+    let origin = resolve(&["10.0.0.0/8"], "198.51.100.7", &headers);
+    assert_eq!(origin.url(), "http://internal:8080");
+    assert_eq!(origin.client_ip, "198.51.100.7".parse().ok());
+    assert_eq!(resolve(&[], "10.0.0.1", &headers).url(), "http://internal:8080");
+
+    let forwarded = [
+      ("forwarded", "for=\"[2001:db8::1]:4711\";proto=https;host=\"example.com:8443\", for=10.1.2.3"),
+      ("x-forwarded-proto", "http"),
+    ];
+    let origin = resolve(&["10.0.0.0/8", "::ffff:127.0.0.1"], "127.0.0.1", &forwarded);
+    assert_eq!(origin.url(), "https://example.com:8443");
+    assert_eq!(origin.client_ip, "2001:db8::1".parse().ok());
+    assert!(TrustedProxies::parse(&["10.0.0.0/33".to_string()]).is_err());
+    assert!(TrustedProxies::parse(&["proxy.local".to_string()]).is_err());
+  }
+
+  #[test]
+  fn test_client_sent_forwarding_values_are_ignored() {
+    // The client sends its own element first; the proxy appends the real one.
+    let forged = [("forwarded", "host=evil;proto=http, for=203.0.113.9;proto=https;host=example.com")];
+    let origin = resolve(&["10.0.0.0/8"], "10.0.0.1", &forged);
+    assert_eq!(origin.url(), "https://example.com");
+    assert_eq!(origin.client_ip, "203.0.113.9".parse().ok());
+
+    let forged = [
+      ("x-forwarded-host", "evil, example.com"),
+      ("x-forwarded-proto", "http, https"),
+      ("x-forwarded-for", "203.0.113.9"),
+    ];
+    assert_eq!(resolve(&["10.0.0.0/8"], "10.0.0.1", &forged).url(), "https://example.com");
+
+    // A host that isn't a host name is ignored.
+    let invalid = [("forwarded", "for=203.0.113.9;host=\"example.com/evil\"")];
+    assert_eq!(resolve(&["10.0.0.0/8"], "10.0.0.1", &invalid).url(), "http://internal:8080");
+    assert!(is_host("example.com:8443") && is_host("[::1]:80") && is_host("127.0.0.1"));
+    assert!(!is_host("evil.com@example.com") && !is_host("[::1]x") && !is_host("example.com:"));
+  }
+}
@@ -0,0 +1,370 @@
+//! Purging CDN caches when the pages they hold go stale.
+//!
+//! A CDN in front of the site keeps serving pages after their templates,
+//! content, or cached data changed. Each CDN added with
+//! `TeraWebBuilder::add_cdn_purger` is told what to drop when:
+//!
+//! * `TeraWeb::reload_templates` swaps in new templates, or
+//!   `TeraWeb::reload_content` re-reads the content collections: everything.
+//! * `TeraWeb::invalidate_tag` (or `TeraWeb::invalidate`) drops a tag: the
+//!   pages tagged with it, e.g. by a `Surrogate-Key` or `Cache-Tag` header.
+//!
+//! With the `purge` feature, [`Fastly`], [`Cloudflare`], and [`CloudFront`]
+//! call the CDNs' APIs; anything else implements [`CdnPurger`]:
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "purge")]
+//! # fn run() -> snapfire::Result<()> {
+//! use snapfire::{TeraWeb, core::purge::Fastly};
+//!
+//! let app = TeraWeb::builder("templates/**/*.html")
+//!   .add_cdn_purger(Fastly::new("SU1Z0isxPaozGVKXdv0eY", "fastly-api-token"))
+//!   .build()?;
+//! app.invalidate_tag("post:42");
+//! # Ok(())
+//! # }
+//! # fn main() {}
+//! ```
+//!
+//! Purges run on a background thread, so handlers don't wait on the CDNs;
+//! failures are logged. Invalidations received from a message bus (see
+//! [`crate::core::invalidation`]) aren't forwarded, so a purge published to
+//! every replica reaches the CDN once, from the replica that made it.
+
+use crate::error::Result;
+#[cfg(feature = "purge")]
+use crate::error::SnapFireError;
+use std::fmt;
+use std::sync::Arc;
+
+/// What a CDN is asked to drop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Purge {
+  /// Every cached page.
+  Everything,
+  /// The pages cached under any of these tags (Fastly surrogate keys,
+  /// Cloudflare cache tags).
+  Tags(Vec<String>),
+}
+
+/// A CDN whose cache can be purged.
+pub trait CdnPurger: Send + Sync {
+  /// Drops what `purge` names from the CDN's cache.
+  fn purge(&self, purge: &Purge) -> Result<()>;
+}
+
+/// The CDNs registered with `TeraWebBuilder::add_cdn_purger`.
+#[derive(Clone, Default)]
+pub(crate) struct CdnPurgers {
+  purgers: Vec<Arc<dyn CdnPurger>>,
+}
+
+impl CdnPurgers {
+  pub(crate) fn push<P: CdnPurger + 'static>(&mut self, purger: P) {
+    self.purgers.push(Arc::new(purger));
+  }
+
+  /// Sends `purge` to every CDN on a background thread, logging failures.
+  pub(crate) fn purge(&self, purge: Purge) {
+    if self.purgers.is_empty() {
+      return;
+    }
+    let purgers = self.purgers.clone();
+    std::thread::spawn(move || {
+      for purger in purgers {
+        if let Err(e) = purger.purge(&purge) {
+          log::error!("CDN purge of {purge:?} failed: {e}");
+        }
+      }
+    });
+  }
+}
+
+impl fmt::Debug for CdnPurgers {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("CdnPurgers").field("len", &self.purgers.len()).finish()
+  }
+}
+
+/// Purges a Fastly service, by surrogate key or entirely. Only available
+/// with the `purge` feature.
+#[cfg(feature = "purge")]
+#[derive(Debug, Clone)]
+pub struct Fastly {
+  service_id: String,
+  token: String,
+  api_base: String,
+}
+
+#[cfg(feature = "purge")]
+impl Fastly {
+  /// Fastly's limit on the keys of one purge request.
+  const KEYS_PER_REQUEST: usize = 256;
+
+  /// A purger for the service `service_id`, with an API token allowed to
+  /// purge it.
+  pub fn new(service_id: &str, token: &str) -> Self {
+    Self {
+      service_id: service_id.to_string(),
+      token: token.to_string(),
+      api_base: "https://api.fastly.com".to_string(),
+    }
+  }
+
+  /// Overrides the API URL, e.g. for a proxy.
+  pub fn api_base(mut self, api_base: &str) -> Self {
+    self.api_base = api_base.trim_end_matches('/').to_string();
+    self
+  }
+}
+
+#[cfg(feature = "purge")]
+impl CdnPurger for Fastly {
+  fn purge(&self, purge: &Purge) -> Result<()> {
+    let service = format!("{}/service/{}", self.api_base, self.service_id);
+    let request = |path: &str| ureq::post(&format!("{service}/{path}")).set("fastly-key", &self.token);
+    match purge {
+      Purge::Everything => {
+        request("purge_all").call().map_err(|e| purge_error("fastly", e))?;
+      }
+      Purge::Tags(tags) => {
+        for keys in tags.chunks(Self::KEYS_PER_REQUEST) {
+          request("purge")
+            .set("surrogate-key", &keys.join(" "))
+            .call()
+            .map_err(|e| purge_error("fastly", e))?;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Purges a Cloudflare zone, by cache tag or entirely. Only available with
+/// the `purge` feature.
+#[cfg(feature = "purge")]
+#[derive(Debug, Clone)]
+pub struct Cloudflare {
+  zone_id: String,
+  token: String,
+  api_base: String,
+}
+
+#[cfg(feature = "purge")]
+impl Cloudflare {
+  /// Cloudflare's limit on the tags of one purge request.
+  const TAGS_PER_REQUEST: usize = 30;
+
+  /// A purger for the zone `zone_id`, with an API token allowed to purge
+  /// its cache.
+  pub fn new(zone_id: &str, token: &str) -> Self {
+    Self {
+      zone_id: zone_id.to_string(),
+      token: token.to_string(),
+      api_base: "https://api.cloudflare.com/client/v4".to_string(),
+    }
+  }
+
+  /// Overrides the API URL, e.g. for a proxy.
+  pub fn api_base(mut self, api_base: &str) -> Self {
+    self.api_base = api_base.trim_end_matches('/').to_string();
+    self
+  }
+
+  /// The bodies of the requests purging `purge`.
+  fn bodies(purge: &Purge) -> Vec<String> {
+    match purge {
+      Purge::Everything => vec![serde_json::json!({ "purge_everything": true }).to_string()],
+      Purge::Tags(tags) => tags
+        .chunks(Self::TAGS_PER_REQUEST)
+        .map(|tags| serde_json::json!({ "tags": tags }).to_string())
+        .collect(),
+    }
+  }
+}
+
+#[cfg(feature = "purge")]
+impl CdnPurger for Cloudflare {
+  fn purge(&self, purge: &Purge) -> Result<()> {
+    let url = format!("{}/zones/{}/purge_cache", self.api_base, self.zone_id);
+    let authorization = format!("Bearer {}", self.token);
+    for body in Self::bodies(purge) {
+      ureq::post(&url)
+        .set("authorization", &authorization)
+        .set("content-type", "application/json")
+        .send_string(&body)
+        .map_err(|e| purge_error("cloudflare", e))?;
+    }
+    Ok(())
+  }
+}
+
+/// Invalidates paths of a CloudFront distribution. CloudFront has no cache
+/// tags, so every purge invalidates the same paths, `/*` unless set with
+/// `paths`. Only available with the `purge` feature.
+#[cfg(feature = "purge")]
+#[derive(Debug, Clone)]
+pub struct CloudFront {
+  distribution_id: String,
+  access_key: String,
+  secret_key: String,
+  session_token: Option<String>,
+  paths: Vec<String>,
+}
+
+#[cfg(feature = "purge")]
+impl CloudFront {
+  /// A purger for the distribution `distribution_id`, with credentials
+  /// allowed to create invalidations.
+  pub fn new(distribution_id: &str, access_key: &str, secret_key: &str) -> Self {
+    Self {
+      distribution_id: distribution_id.to_string(),
+      access_key: access_key.to_string(),
+      secret_key: secret_key.to_string(),
+      session_token: None,
+      paths: vec!["/*".to_string()],
+    }
+  }
+
+  /// Sets the session token of temporary credentials.
+  pub fn session_token(mut self, token: &str) -> Self {
+    self.session_token = Some(token.to_string());
+    self
+  }
+
+  /// Invalidates these paths (e.g. `["/blog/*", "/"]`) instead of `/*`.
+  pub fn paths(mut self, paths: &[&str]) -> Self {
+    self.paths = paths.iter().map(|path| path.to_string()).collect();
+    self
+  }
+
+  /// The `CreateInvalidation` request body. `reference` tells invalidations
+  /// apart, so retries of the same one aren't applied twice.
+  fn invalidation_batch(&self, reference: &str) -> String {
+    let items: String = self
+      .paths
+      .iter()
+      .map(|path| format!("<Path>{}</Path>", xml_escape(path)))
+      .collect();
+    format!(
+      "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+       <InvalidationBatch xmlns=\"http://cloudfront.amazonaws.com/doc/2020-05-31/\">\
+       <Paths><Quantity>{}</Quantity><Items>{items}</Items></Paths>\
+       <CallerReference>{}</CallerReference></InvalidationBatch>",
+      self.paths.len(),
+      xml_escape(reference)
+    )
+  }
+}
+
+#[cfg(feature = "purge")]
+impl CdnPurger for CloudFront {
+  fn purge(&self, _purge: &Purge) -> Result<()> {
+    use crate::core::aws::{SigV4, amz_date, hex};
+    use sha2::{Digest, Sha256};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let now = SystemTime::now();
+    let reference = format!(
+      "snapfire-{}",
+      now.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_nanos())
+    );
+    let body = self.invalidation_batch(&reference);
+    let host = "cloudfront.amazonaws.com";
+    let path = format!("/2020-05-31/distribution/{}/invalidation", self.distribution_id);
+
+    let mut headers = vec![
+      ("content-type".to_string(), "application/xml".to_string()),
+      ("host".to_string(), host.to_string()),
+      ("x-amz-content-sha256".to_string(), hex(&Sha256::digest(body.as_bytes()))),
+      ("x-amz-date".to_string(), amz_date(now)),
+    ];
+    if let Some(token) = &self.session_token {
+      headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    // CloudFront is a global service, signed for `us-east-1`.
+    let authorization = SigV4 {
+      access_key: &self.access_key,
+      secret_key: &self.secret_key,
+      region: "us-east-1",
+      service: "cloudfront",
+    }
+    .authorization("POST", &path, &headers);
+
+    let request = headers
+      .iter()
+      .filter(|(name, _)| name != "host")
+      .fold(ureq::post(&format!("https://{host}{path}")), |request, (name, value)| {
+        request.set(name, value)
+      })
+      .set("authorization", &authorization);
+    request.send_string(&body).map_err(|e| purge_error("cloudfront", e))?;
+    Ok(())
+  }
+}
+
+#[cfg(feature = "purge")]
+fn xml_escape(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(feature = "purge")]
+fn purge_error(cdn: &str, error: impl fmt::Display) -> SnapFireError {
+  SnapFireError::Purge {
+    cdn: cdn.to_string(),
+    message: error.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::TeraWeb;
+  use std::sync::mpsc;
+  use std::time::Duration;
+
+  struct Recorder(mpsc::Sender<Purge>);
+
+  impl CdnPurger for Recorder {
+    fn purge(&self, purge: &Purge) -> Result<()> {
+      self.0.send(purge.clone()).unwrap();
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_changes_purge_the_cdn() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("index.html"), "v1").unwrap();
+    let (sender, purges) = mpsc::channel();
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .add_cdn_purger(Recorder(sender))
+      .build()
+      .unwrap();
+    let next = || purges.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    app.invalidate_tag("post:42");
+    assert_eq!(next(), Purge::Tags(vec!["post:42".to_string()]));
+
+    std::fs::write(temp_dir.path().join("index.html"), "v2").unwrap();
+    app.reload_templates().unwrap();
+    assert_eq!(next(), Purge::Everything);
+    assert_eq!(app.render_to_string("index.html", tera::Context::new()).unwrap(), "v2");
+  }
+
+  #[cfg(feature = "purge")]
+  #[test]
+  fn test_requests_respect_the_cdn_formats() {
+    let tags: Vec<String> = (0..31).map(|n| format!("t{n}")).collect();
+    let bodies = Cloudflare::bodies(&Purge::Tags(tags));
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[1], r#"{"tags":["t30"]}"#);
+    assert_eq!(Cloudflare::bodies(&Purge::Everything), [r#"{"purge_everything":true}"#]);
+
+    let batch = CloudFront::new("E2QWRUHEXAMPLE", "AKIA", "secret")
+      .paths(&["/blog/*", "/a&b"])
+      .invalidation_batch("ref-1");
+    assert!(batch.contains("<Quantity>2</Quantity><Items><Path>/blog/*</Path><Path>/a&amp;b</Path></Items>"));
+    assert!(batch.contains("<CallerReference>ref-1</CallerReference>"));
+  }
+}
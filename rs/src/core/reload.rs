@@ -1,53 +1,361 @@
+use crate::core::app::{SharedTera, reload_templates};
+use crate::core::broadcast::Broadcaster;
+use crate::core::fixtures;
+#[cfg(feature = "actix")]
+use crate::core::styleguide::StyleguideOptions;
 use crate::error::{Result, SnapFireError};
 use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
-use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tera::Tera;
-use tokio::sync::broadcast;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+pub use crate::core::broadcast::{Recv, ReloadReceiver};
 
 /// A message sent from the reloader to all connected clients.
-#[derive(Debug, Clone)]
-pub(crate) enum ReloadMessage {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReloadMessage {
   /// Instructs the client to do a full page reload.
   Reload,
   /// Instructs the client to only reload CSS stylesheets.
   ReloadCss,
+  /// An app-defined value from `TeraWeb::dev_push`, as the JSON text sent
+  /// to clients: `{"event":"custom","data":...}`.
+  Custom(Arc<str>),
+}
+
+impl ReloadMessage {
+  /// A `Custom` message carrying `data`.
+  pub(crate) fn custom(data: &serde_json::Value) -> Self {
+    Self::event("custom", data)
+  }
+
+  /// A message for the page's `SnapFire.on(event, ...)` handlers.
+  pub(crate) fn event(event: &str, data: &serde_json::Value) -> Self {
+    Self::Custom(serde_json::json!({ "event": event, "data": data }).to_string().into())
+  }
+
+  /// The message as sent over the WebSocket: `reload`, `reload-css`, or the
+  /// JSON text of a custom message.
+  pub fn as_str(&self) -> &str {
+    match self {
+      Self::Reload => "reload",
+      Self::ReloadCss => "reload-css",
+      Self::Custom(text) => text,
+    }
+  }
+}
+
+/// A data file outside the template tree (e.g. a navigation file), or a
+/// directory of them (e.g. the content directory), whose changes reload it and
+/// then the page.
+pub(crate) struct WatchedFile {
+  pub(crate) path: PathBuf,
+  pub(crate) on_change: Box<dyn Fn() -> Result<()> + Send + Sync>,
+}
+
+/// The heartbeat timings of a connection, from the builder.
+#[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Heartbeat {
+  /// How often heartbeat pings are sent to the client.
+  pub(crate) interval: Duration,
+  /// How long to wait for a pong response before timing out.
+  pub(crate) timeout: Duration,
+}
+
+/// The frames [`forward_reloads`] sends and inspects, implemented for the
+/// message type of each WebSocket library an integration uses.
+#[cfg(any(feature = "hyper", feature = "warp"))]
+pub(crate) trait SocketMessage {
+  /// An empty ping.
+  fn ping() -> Self;
+  /// A text frame holding `text`.
+  fn text(text: &str) -> Self;
+  /// Whether the client is closing the connection.
+  fn is_close(&self) -> bool;
+}
+
+/// Forwards reload messages over `socket` to one client until it leaves or
+/// stops answering pings.
+#[cfg(any(feature = "hyper", feature = "warp"))]
+pub(crate) async fn forward_reloads<S, M, E>(socket: S, mut reloads: ReloadReceiver, heartbeat: Heartbeat)
+where
+  S: futures_util::Sink<M> + futures_util::Stream<Item = std::result::Result<M, E>>,
+  M: SocketMessage,
+{
+  use futures_util::{SinkExt, StreamExt};
+
+  let (mut sender, mut incoming) = socket.split();
+  let mut ticks = tokio::time::interval(heartbeat.interval);
+  let mut last_heartbeat = Instant::now();
+
+  loop {
+    tokio::select! {
+      _ = ticks.tick() => {
+        if last_heartbeat.elapsed() > heartbeat.timeout {
+          log::info!("WebSocket client heartbeat failed, disconnecting!");
+          break;
+        }
+        if sender.send(M::ping()).await.is_err() {
+          break;
+        }
+      }
+
+      message = incoming.next() => match message {
+        Some(Ok(message)) if message.is_close() => break,
+        // Pongs and anything else the client sends prove it's alive.
+        Some(Ok(_)) => last_heartbeat = Instant::now(),
+        _ => break,
+      },
+
+      received = reloads.recv() => {
+        let Some(message) = received else {
+          break;
+        };
+        log::debug!("Broadcasting WebSocket message: {}", message.as_str());
+        if sender.send(M::text(message.as_str())).await.is_err() {
+          break;
+        }
+      }
+    }
+  }
+
+  let _ = sender.close().await;
+}
+
+/// Live-reload settings from the builder.
+#[derive(Debug, Clone)]
+pub(crate) struct ReloadOptions {
+  #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+  pub(crate) ws_path: String,
+  #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+  pub(crate) auto_inject_script: bool,
+  #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+  pub(crate) external_script: bool,
+  /// How often heartbeat pings are sent to each client.
+  #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+  pub(crate) heartbeat_interval: Duration,
+  /// How long a client may go without answering before it is disconnected.
+  #[cfg(any(feature = "actix", feature = "hyper", feature = "warp"))]
+  pub(crate) client_timeout: Duration,
+  /// How many reload messages are buffered for a client that is behind.
+  pub(crate) channel_capacity: usize,
+  /// How long file changes must stop before they are applied as one reload.
+  pub(crate) quiet_period: Duration,
+  /// The minimum time between two reloads.
+  pub(crate) min_reload_interval: Duration,
+  /// Where reloads can be triggered over HTTP, if anywhere.
+  #[cfg(feature = "actix")]
+  pub(crate) poke_path: Option<String>,
+  /// The component gallery, if configured.
+  #[cfg(feature = "actix")]
+  pub(crate) styleguide: Option<StyleguideOptions>,
+  /// Whether failed reloads raise desktop notifications.
+  #[cfg(feature = "notifications")]
+  pub(crate) desktop_notifications: bool,
 }
 
 /// The core, framework-agnostic live-reload controller.
 ///
-/// It spawns a background task to watch for file changes and holds a
-/// broadcast channel to send messages to connected clients.
-#[derive(Debug)]
+/// It starts the watch sources, which report changes to a background thread
+/// that reloads templates and data, and holds a runtime-agnostic broadcast
+/// channel to send messages to connected clients.
 pub(crate) struct DevReloader {
   // We only store the sender. Receivers are created on demand.
-  pub(crate) broadcaster: broadcast::Sender<ReloadMessage>,
-  // The watcher is held in the struct to keep it alive. When `DevReloader`
-  // is dropped, the watcher is dropped, and the background task will exit.
-  _watcher: RecommendedWatcher,
+  pub(crate) broadcaster: Broadcaster,
+  /// A sink for triggering reloads programmatically.
+  pub(crate) sink: ChangeSink,
+  // The sources are held in the struct to keep them alive. When `DevReloader`
+  // is dropped, they are dropped, and the background thread exits.
+  _sources: Vec<Box<dyn WatchSource>>,
   // Publicly expose the configuration for the Actix layer to use.
-  pub(crate) ws_path: String,
-  pub(crate) auto_inject_script: bool,
+  pub(crate) options: ReloadOptions,
+}
+
+impl std::fmt::Debug for DevReloader {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("DevReloader")
+      .field("sources", &self._sources.len())
+      .field("options", &self.options)
+      .finish_non_exhaustive()
+  }
 }
 
 impl DevReloader {
-  /// Creates a new `DevReloader` and starts the file watching task.
+  /// Creates a new `DevReloader` and starts the file watcher and `sources`.
   pub(crate) fn start(
-    tera: Arc<RwLock<Tera>>,
-    template_glob: &str,
+    tera: SharedTera,
+    template_glob: Option<&str>,
     static_paths: Vec<String>,
-    ws_path: String,
-    auto_inject_script: bool,
+    watched_files: Vec<WatchedFile>,
+    sources: Vec<Box<dyn WatchSource>>,
+    options: ReloadOptions,
+  ) -> Result<Self> {
+    let file_watcher = FileWatcher {
+      template_glob: template_glob.map(str::to_string),
+      static_paths,
+      watcher: None,
+    };
+    let sources = std::iter::once(Box::new(file_watcher) as Box<dyn WatchSource>)
+      .chain(sources)
+      .collect();
+    Self::with_sources(tera, watched_files, sources, options)
+  }
+
+  /// Creates a new `DevReloader` driven only by `sources`, without watching files.
+  pub(crate) fn with_sources(
+    tera: SharedTera,
+    watched_files: Vec<WatchedFile>,
+    mut sources: Vec<Box<dyn WatchSource>>,
+    options: ReloadOptions,
   ) -> Result<Self> {
-    let (tx, _rx) = broadcast::channel(16);
-    let broadcaster = tx.clone();
+    let broadcaster = Broadcaster::new(options.channel_capacity);
+
+    let (data_paths, data_callbacks): (Vec<PathBuf>, Vec<_>) = watched_files
+      .into_iter()
+      .map(|file| (file.path.canonicalize().unwrap_or(file.path), file.on_change))
+      .unzip();
+
+    // Changes are applied on a separate thread, which coalesces bursts into a
+    // single reload. It exits when every source (and so every sender) is dropped.
+    let (changes_tx, changes_rx) = mpsc::channel();
+    let coalescer = Coalescer {
+      tera,
+      data_callbacks,
+      broadcaster: broadcaster.clone(),
+      quiet_period: options.quiet_period,
+      min_interval: options.min_reload_interval,
+      #[cfg(feature = "notifications")]
+      notify_failures: options.desktop_notifications,
+    };
+    std::thread::Builder::new()
+      .name("snapfire-reloader".to_string())
+      .spawn(move || coalescer.run(changes_rx))?;
+
+    let sink = ChangeSink {
+      changes: changes_tx,
+      data_paths: Arc::new(data_paths),
+    };
+    for source in &mut sources {
+      source.start(sink.clone())?;
+    }
+
+    Ok(Self {
+      broadcaster,
+      sink,
+      _sources: sources,
+      options,
+    })
+  }
+}
+
+/// A source of changes that trigger a reload, such as the file watcher, a
+/// poller, or a message queue consumer.
+///
+/// `start` is called once, when the app is built. The source then reports
+/// changes to the sink, from any thread, for as long as it lives; the app
+/// keeps it until the app is dropped, so a source can stop its work in `Drop`.
+pub trait WatchSource: Send + Sync + 'static {
+  /// Starts reporting changes to `sink`.
+  fn start(&mut self, sink: ChangeSink) -> Result<()>;
+}
+
+/// Where watch sources report changes. Cheap to clone and usable from any
+/// thread; changes reported in quick succession are coalesced into one reload.
+#[derive(Debug, Clone)]
+pub struct ChangeSink {
+  changes: mpsc::Sender<Change>,
+  /// The canonical paths of the watched data files and directories, by index.
+  data_paths: Arc<Vec<PathBuf>>,
+}
+
+impl ChangeSink {
+  /// Reports a changed file, classified like the file watcher does: a data
+  /// file (such as the navigation file, or a file in the content directory)
+  /// is reloaded, a template (`.html`,
+  /// `.tera`, `.jinja`) reloads the templates, a `.css` file the stylesheets,
+  /// and a fixture (a `.json` file in a `.fixtures` directory or next to a
+  /// template) the page.
+  /// Returns `false` for files that don't trigger a reload.
+  pub fn file_changed(&self, path: &Path) -> bool {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let change = if let Some(index) = self.data_paths.iter().position(|data_path| canonical.starts_with(data_path)) {
+      log::info!("🗂️ Data file change detected: {:?}", path);
+      Change::Data(index)
+    } else {
+      match path.extension().and_then(|s| s.to_str()) {
+        Some("html") | Some("tera") | Some("jinja") => {
+          log::info!("📝 Template change detected: {:?}", path);
+          Change::Template
+        }
+        Some("css") => {
+          log::info!("🎨 CSS change detected: {:?}", path);
+          Change::Css
+        }
+        // Inlined by the `svg` function.
+        Some("svg") => {
+          log::info!("🖼️ SVG change detected: {:?}", path);
+          Change::Page
+        }
+        Some("json") if fixtures::is_fixture(path) => {
+          log::info!("🧩 Fixture change detected: {:?}", path);
+          Change::Page
+        }
+        _ => return false,
+      }
+    };
+    self.send(change);
+    true
+  }
 
-    // The watcher needs its own clones to move into the event handler.
-    let tera_clone = tera.clone();
-    let broadcaster_clone = broadcaster.clone();
+  /// Re-parses the templates, then reloads the page.
+  pub fn templates_changed(&self) {
+    self.send(Change::Template);
+  }
+
+  /// Reloads the stylesheets, without reloading the page.
+  pub fn css_changed(&self) {
+    self.send(Change::Css);
+  }
+
+  /// Reloads the page, e.g. after an external asset pipeline has published
+  /// new files.
+  pub fn page_changed(&self) {
+    self.send(Change::Page);
+  }
+
+  fn send(&self, change: Change) {
+    // The reloader is gone when the app has been dropped; nothing to do then.
+    let _ = self.changes.send(change);
+  }
+}
+
+/// Watches the template directory, the static paths, and the directories of
+/// the data files with `notify`.
+struct FileWatcher {
+  template_glob: Option<String>,
+  static_paths: Vec<String>,
+  watcher: Option<RecommendedWatcher>,
+}
+
+impl WatchSource for FileWatcher {
+  fn start(&mut self, sink: ChangeSink) -> Result<()> {
+    // Watch the parent directories of data files, since editors often save by
+    // replacing the file, which would end a watch on the file itself. Data
+    // directories are watched whole.
+    let data_dirs: Vec<(PathBuf, RecursiveMode)> = sink
+      .data_paths
+      .iter()
+      .filter_map(|path| match path.is_dir() {
+        true => Some((path.clone(), RecursiveMode::Recursive)),
+        false => path.parent().map(|parent| (parent.to_path_buf(), RecursiveMode::NonRecursive)),
+      })
+      .collect();
 
     let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
-      // ... event handler logic remains the same ...
       let event = match res {
         Ok(event) => event,
         Err(e) => {
@@ -60,35 +368,30 @@ impl DevReloader {
         return;
       }
 
+      // One change per event is enough; the rest would be coalesced anyway.
       for path in &event.paths {
-        match path.extension().and_then(|s| s.to_str()) {
-          Some("html") | Some("tera") | Some("jinja") => {
-            log::info!("📝 Template change detected: {:?}", path);
-            if let Err(e) = tera_clone.write().full_reload() {
-              log::error!("Failed to reload templates: {}", e);
-            }
-            let _ = broadcaster_clone.send(ReloadMessage::Reload);
-            return;
-          }
-          Some("css") => {
-            log::info!("🎨 CSS change detected: {:?}", path);
-            let _ = broadcaster_clone.send(ReloadMessage::ReloadCss);
-            return;
-          }
-          _ => (),
+        if sink.file_changed(path) {
+          return;
         }
       }
     })?;
 
-    // Use our new, robust function to get the path to watch.
-    let template_watch_path = base_path_from_glob(template_glob);
-    log::debug!("Watching template path: {}", template_watch_path);
-    watcher
-      .watch(std::path::Path::new(template_watch_path), RecursiveMode::Recursive)
-      .map_err(SnapFireError::Watcher)?;
+    // Use our new, robust function to get the path to watch. Bundled templates
+    // have no files on disk, so there is nothing to watch for them.
+    if let Some(template_glob) = &self.template_glob {
+      let template_watch_path = base_path_from_glob(template_glob);
+      log::debug!("Watching template path: {}", template_watch_path);
+      watcher
+        .watch(std::path::Path::new(template_watch_path), RecursiveMode::Recursive)
+        .map_err(SnapFireError::Watcher)?;
+    }
+
+    for (dir, mode) in &data_dirs {
+      watcher.watch(dir, *mode).map_err(SnapFireError::Watcher)?;
+    }
 
     // Watch all specified static asset paths.
-    for path in &static_paths {
+    for path in &self.static_paths {
       if std::path::Path::new(path).exists() {
         watcher
           .watch(path.as_ref(), RecursiveMode::Recursive)
@@ -98,12 +401,194 @@ impl DevReloader {
       }
     }
 
-    Ok(Self {
-      broadcaster,
-      _watcher: watcher,
-      ws_path,
-      auto_inject_script,
-    })
+    self.watcher = Some(watcher);
+    Ok(())
+  }
+}
+
+/// Polls files for modification times instead of relying on file system
+/// events, which don't arrive on some network and container mounts.
+///
+/// ```rust,no_run
+/// # use snapfire::core::reload::PollingSource;
+/// # use std::time::Duration;
+/// let app = snapfire::TeraWeb::builder("templates/**/*.html")
+///   .watch_source(PollingSource::new(["templates", "static"], Duration::from_millis(500)))
+///   .build();
+/// ```
+#[derive(Debug)]
+pub struct PollingSource {
+  paths: Vec<PathBuf>,
+  interval: Duration,
+  stop: Arc<AtomicBool>,
+}
+
+impl PollingSource {
+  /// Polls the files in `paths` (directories are scanned recursively) every `interval`.
+  pub fn new<P: Into<PathBuf>>(paths: impl IntoIterator<Item = P>, interval: Duration) -> Self {
+    Self {
+      paths: paths.into_iter().map(Into::into).collect(),
+      interval,
+      stop: Arc::new(AtomicBool::new(false)),
+    }
+  }
+}
+
+impl WatchSource for PollingSource {
+  fn start(&mut self, sink: ChangeSink) -> Result<()> {
+    let paths = self.paths.clone();
+    let interval = self.interval;
+    let stop = Arc::clone(&self.stop);
+
+    std::thread::Builder::new()
+      .name("snapfire-poller".to_string())
+      .spawn(move || {
+        let mut seen = modification_times(&paths);
+        while !stop.load(Ordering::Relaxed) {
+          std::thread::sleep(interval);
+          let current = modification_times(&paths);
+          for (path, modified) in &current {
+            if seen.get(path) != Some(modified) {
+              sink.file_changed(path);
+            }
+          }
+          seen = current;
+        }
+      })?;
+    Ok(())
+  }
+}
+
+impl Drop for PollingSource {
+  fn drop(&mut self) {
+    self.stop.store(true, Ordering::Relaxed);
+  }
+}
+
+/// The modification time of every file under `paths`.
+fn modification_times(paths: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+  fn visit(path: &Path, times: &mut HashMap<PathBuf, SystemTime>) {
+    let Ok(metadata) = std::fs::metadata(path) else {
+      return;
+    };
+    if metadata.is_dir() {
+      for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+        visit(&entry.path(), times);
+      }
+    } else if let Ok(modified) = metadata.modified() {
+      times.insert(path.to_path_buf(), modified);
+    }
+  }
+
+  let mut times = HashMap::new();
+  for path in paths {
+    visit(path, &mut times);
+  }
+  times
+}
+
+/// A single change reported by a watch source.
+#[derive(Debug, Clone, Copy)]
+enum Change {
+  Template,
+  Css,
+  /// A change that only needs the page reloaded.
+  Page,
+  /// The watched data file at this index.
+  Data(usize),
+}
+
+/// The changes accumulated since the last reload.
+#[derive(Debug, Default)]
+struct Batch {
+  templates: bool,
+  css: bool,
+  page: bool,
+  data_files: Vec<usize>,
+}
+
+impl Batch {
+  fn add(&mut self, change: Change) {
+    match change {
+      Change::Template => self.templates = true,
+      Change::Css => self.css = true,
+      Change::Page => self.page = true,
+      Change::Data(index) if !self.data_files.contains(&index) => self.data_files.push(index),
+      Change::Data(_) => {}
+    }
+  }
+}
+
+/// Turns bursts of file changes (e.g. a `git checkout` touching hundreds of
+/// templates) into a single reload.
+struct Coalescer {
+  tera: SharedTera,
+  data_callbacks: Vec<Box<dyn Fn() -> Result<()> + Send + Sync>>,
+  broadcaster: Broadcaster,
+  /// How long no changes must arrive before the batch is applied.
+  quiet_period: Duration,
+  /// The minimum time between two reloads.
+  min_interval: Duration,
+  /// Whether failed reloads raise desktop notifications.
+  #[cfg(feature = "notifications")]
+  notify_failures: bool,
+}
+
+impl Coalescer {
+  fn run(self, changes: mpsc::Receiver<Change>) {
+    let mut last_reload: Option<Instant> = None;
+
+    while let Ok(first) = changes.recv() {
+      let mut batch = Batch::default();
+      batch.add(first);
+
+      // Keep collecting until the changes go quiet and the rate limit allows
+      // another reload.
+      loop {
+        let quiet_until = Instant::now() + self.quiet_period;
+        let deadline = match last_reload {
+          Some(last) => quiet_until.max(last + self.min_interval),
+          None => quiet_until,
+        };
+        match changes.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+          Ok(change) => batch.add(change),
+          Err(RecvTimeoutError::Timeout) => break,
+          Err(RecvTimeoutError::Disconnected) => return,
+        }
+      }
+
+      self.apply(batch);
+      last_reload = Some(Instant::now());
+    }
+  }
+
+  fn apply(&self, batch: Batch) {
+    for index in &batch.data_files {
+      if let Err(e) = (self.data_callbacks[*index])() {
+        self.report_failure("a data file", &e);
+      }
+    }
+    if batch.templates
+      && let Err(e) = reload_templates(&self.tera, "live reload")
+    {
+      self.report_failure("templates", &e);
+    }
+
+    if batch.templates || batch.page || !batch.data_files.is_empty() {
+      let _ = self.broadcaster.send(ReloadMessage::Reload);
+    }
+    if batch.css {
+      let _ = self.broadcaster.send(ReloadMessage::ReloadCss);
+    }
+  }
+
+  /// Logs that reloading `what` failed, and notifies the desktop if enabled.
+  fn report_failure(&self, what: &str, error: &SnapFireError) {
+    log::error!("Failed to reload {}: {}", what, error);
+    #[cfg(feature = "notifications")]
+    if self.notify_failures {
+      crate::core::desktop::notify_failure(what, error);
+    }
   }
 }
 
@@ -114,7 +599,7 @@ impl DevReloader {
 /// any special glob characters.
 fn base_path_from_glob(glob: &str) -> &str {
   // Find the first occurrence of a glob character
-  if let Some(first_glob_char_index) = glob.find(&['*', '?', '{', '[']) {
+  if let Some(first_glob_char_index) = glob.find(['*', '?', '{', '[']) {
     // Take the slice of the string before that character
     let before_glob = &glob[..first_glob_char_index];
     // Find the last directory separator in that slice
@@ -136,3 +621,109 @@ fn base_path_from_glob(glob: &str) -> &str {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::app::TemplateSet;
+  use parking_lot::RwLock;
+  use std::fs;
+  use tempfile::tempdir;
+  use tera::Context;
+
+  #[test]
+  fn test_reload_swaps_without_disturbing_snapshots() {
+    let temp_dir = tempdir().unwrap();
+    let template_path = temp_dir.path().join("index.html");
+    fs::write(&template_path, "old").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+    let tera: SharedTera = Arc::new(RwLock::new(Arc::new(TemplateSet::from_glob(&glob_path).unwrap())));
+    // An "in-flight render" holding the current set.
+    let snapshot = Arc::clone(&tera.read());
+
+    fs::write(&template_path, "new").unwrap();
+    reload_templates(&tera, "live reload").unwrap();
+
+    assert_eq!(snapshot.render("index.html", &Context::new()).unwrap(), "old");
+    assert_eq!(tera.read().render("index.html", &Context::new()).unwrap(), "new");
+  }
+
+  #[test]
+  fn test_failed_reload_keeps_old_set() {
+    let temp_dir = tempdir().unwrap();
+    let template_path = temp_dir.path().join("index.html");
+    fs::write(&template_path, "ok").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+    let tera: SharedTera = Arc::new(RwLock::new(Arc::new(TemplateSet::from_glob(&glob_path).unwrap())));
+
+    fs::write(&template_path, "{% if %}").unwrap();
+    assert!(reload_templates(&tera, "live reload").is_err());
+    assert_eq!(tera.read().render("index.html", &Context::new()).unwrap(), "ok");
+  }
+
+  #[test]
+  fn test_bursts_are_coalesced_into_one_reload() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "ok").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let tera: SharedTera = Arc::new(RwLock::new(Arc::new(TemplateSet::from_glob(&glob_path).unwrap())));
+
+    let broadcaster = Broadcaster::new(1024);
+    let messages = broadcaster.subscribe();
+    let (changes_tx, changes_rx) = mpsc::channel();
+    let coalescer = Coalescer {
+      tera,
+      data_callbacks: Vec::new(),
+      broadcaster,
+      quiet_period: Duration::from_millis(100),
+      min_interval: Duration::ZERO,
+      #[cfg(feature = "notifications")]
+      notify_failures: false,
+    };
+    let thread = std::thread::spawn(move || coalescer.run(changes_rx));
+
+    // A checkout touching hundreds of templates and a stylesheet.
+    for _ in 0..300 {
+      changes_tx.send(Change::Template).unwrap();
+    }
+    changes_tx.send(Change::Css).unwrap();
+    std::thread::sleep(Duration::from_millis(400));
+    drop(changes_tx);
+    thread.join().unwrap();
+
+    assert_eq!(messages.try_recv(), Ok(ReloadMessage::Reload));
+    assert_eq!(messages.try_recv(), Ok(ReloadMessage::ReloadCss));
+    assert!(messages.try_recv().is_err());
+  }
+
+  #[test]
+  fn test_polling_source_reports_modified_files() {
+    let temp_dir = tempdir().unwrap();
+    let css_path = temp_dir.path().join("style.css");
+    fs::write(&css_path, "body {}").unwrap();
+
+    let (changes_tx, changes_rx) = mpsc::channel();
+    let sink = ChangeSink {
+      changes: changes_tx,
+      data_paths: Arc::new(Vec::new()),
+    };
+    let mut source = PollingSource::new([temp_dir.path()], Duration::from_millis(10));
+    source.start(sink).unwrap();
+
+    // Nothing changed yet.
+    assert!(changes_rx.recv_timeout(Duration::from_millis(50)).is_err());
+
+    let file = fs::File::options().write(true).open(&css_path).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(5)).unwrap();
+    assert!(matches!(changes_rx.recv_timeout(Duration::from_secs(2)), Ok(Change::Css)));
+
+    // Dropping the source stops the poller, which drops its sink.
+    drop(source);
+    assert!(matches!(
+      changes_rx.recv_timeout(Duration::from_secs(2)),
+      Err(RecvTimeoutError::Disconnected)
+    ));
+  }
+}
@@ -0,0 +1,258 @@
+//! Replaying recent requests after template changes, in dev mode.
+//!
+//! Checking a page that answers a form (a validation error, a confirmation)
+//! means submitting the form again after every template edit. The
+//! `RequestReplay` Actix middleware records the app's recent requests (the
+//! method, URL, headers, and body, with the status and page they got) and
+//! serves a panel at `/_snapfire/replay` listing them. Its replay button
+//! sends a request through the app again, with the browser's current
+//! cookies, and shows how the status and page changed since the last time.
+//!
+//! Requests for the dev routes and `GET`s answered with anything but HTML
+//! (assets, JSON) aren't recorded, nor are bodies over 1 MiB or of unknown
+//! length. The panel masks form fields that look like credentials.
+
+use crate::core::compare::line_diff;
+use crate::core::sensitive::SCRUBBED;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+
+/// Where the `RequestReplay` middleware serves its panel.
+pub(crate) const REPLAY_PATH: &str = "/_snapfire/replay";
+
+/// The largest request body recorded.
+pub(crate) const MAX_BODY: usize = 1024 * 1024;
+
+/// How many requests are kept, the oldest dropped first.
+const CAPACITY: usize = 25;
+
+/// The request headers that aren't recorded: the replaying browser sends
+/// its own credentials, and the rest describe the connection or the body.
+const UNRECORDED_HEADERS: &[&str] = &[
+  "cookie",
+  "authorization",
+  "proxy-authorization",
+  "host",
+  "connection",
+  "content-length",
+  "transfer-encoding",
+  "accept-encoding",
+  "upgrade",
+];
+
+/// The form fields the panel masks.
+const SECRET_FIELDS: &[&str] = &["password", "passwd", "secret", "token"];
+
+/// A recorded request, with the response it last got.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RecordedRequest {
+  pub(crate) id: u64,
+  pub(crate) method: String,
+  /// The path and query, e.g. `/signup?plan=pro`.
+  pub(crate) uri: String,
+  pub(crate) headers: Vec<(String, String)>,
+  pub(crate) body: Bytes,
+  pub(crate) status: u16,
+  /// The HTML page of the response, if it was one.
+  pub(crate) page: Option<String>,
+}
+
+/// The recent requests, newest first.
+#[derive(Debug, Default)]
+pub(crate) struct ReplayLog {
+  state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+  next_id: u64,
+  entries: VecDeque<RecordedRequest>,
+}
+
+impl ReplayLog {
+  /// Records a request and its response, replacing an earlier recording of
+  /// the same request.
+  pub(crate) fn record(&self, method: &str, uri: &str, headers: Vec<(String, String)>, body: Bytes, status: u16, page: Option<String>) {
+    let mut state = self.state.lock();
+    state.entries.retain(|entry| !(entry.method == method && entry.uri == uri && entry.body == body));
+    state.next_id += 1;
+    let id = state.next_id;
+    state.entries.push_front(RecordedRequest {
+      id,
+      method: method.to_string(),
+      uri: uri.to_string(),
+      headers,
+      body,
+      status,
+      page,
+    });
+    state.entries.truncate(CAPACITY);
+  }
+
+  /// The recorded request `id`, if it's still kept.
+  pub(crate) fn get(&self, id: u64) -> Option<RecordedRequest> {
+    self.state.lock().entries.iter().find(|entry| entry.id == id).cloned()
+  }
+
+  /// Keeps the response a replay of request `id` got, for the next diff.
+  pub(crate) fn update(&self, id: u64, status: u16, page: Option<String>) {
+    if let Some(entry) = self.state.lock().entries.iter_mut().find(|entry| entry.id == id) {
+      entry.status = status;
+      entry.page = page;
+    }
+  }
+
+  /// The recorded requests, newest first.
+  pub(crate) fn entries(&self) -> Vec<RecordedRequest> {
+    self.state.lock().entries.iter().cloned().collect()
+  }
+}
+
+/// Whether the request header `name` is recorded.
+pub(crate) fn is_recorded_header(name: &str) -> bool {
+  !UNRECORDED_HEADERS.iter().any(|unrecorded| name.eq_ignore_ascii_case(unrecorded))
+}
+
+/// How a replay went: the request as it was, and what it got this time.
+#[derive(Debug)]
+pub(crate) struct Replayed {
+  pub(crate) request: RecordedRequest,
+  pub(crate) status: u16,
+  pub(crate) page: Option<String>,
+}
+
+/// The panel listing `entries`, with the outcome of a replay first.
+pub(crate) fn panel_html(entries: &[RecordedRequest], replayed: Option<&Replayed>) -> String {
+  let mut body = String::new();
+  if let Some(replayed) = replayed {
+    let request = &replayed.request;
+    let _ = write!(
+      body,
+      "<section><h2>{} {}: {} → {}</h2>",
+      request.method,
+      tera::escape_html(&request.uri),
+      request.status,
+      replayed.status
+    );
+    match (&request.page, &replayed.page) {
+      (Some(before), Some(after)) if before == after => body.push_str("<p>The page didn't change.</p>"),
+      (Some(before), Some(after)) => {
+        body.push_str("<pre class=\"diff\">");
+        for line in line_diff(before, after).lines() {
+          let class = match line.as_bytes().first() {
+            Some(b'+') => "added",
+            Some(b'-') => "removed",
+            Some(b'@') => "hunk",
+            _ => "same",
+          };
+          let _ = writeln!(body, "<span class=\"{class}\">{}</span>", tera::escape_html(line));
+        }
+        body.push_str("</pre>");
+      }
+      _ => body.push_str("<p>No HTML page to compare.</p>"),
+    }
+    body.push_str("</section>");
+  }
+
+  if entries.is_empty() {
+    body.push_str("<p>No requests recorded yet. Use the app, then come back to replay them.</p>");
+  } else {
+    body.push_str("<table><thead><tr><th>Request</th><th>Body</th><th>Status</th><th></th></tr></thead><tbody>");
+    for entry in entries {
+      let _ = write!(
+        body,
+        "<tr><td><code>{} {}</code></td><td><code>{}</code></td><td>{}</td>\
+         <td><form method=\"post\" action=\"{REPLAY_PATH}?id={}\"><button>Replay</button></form></td></tr>",
+        entry.method,
+        tera::escape_html(&entry.uri),
+        tera::escape_html(&masked_body(&entry.body)),
+        entry.status,
+        entry.id
+      );
+    }
+    body.push_str("</tbody></table>");
+  }
+
+  format!(
+    "<!DOCTYPE html>\n<html lang=\"en\"><head><meta charset=\"utf-8\"><title>Request replay</title>\
+     <style>body{{font:15px/1.5 sans-serif;margin:2rem;color:#222}}table{{border-collapse:collapse;width:100%}}\
+     th,td{{text-align:left;padding:.4rem .6rem;border-bottom:1px solid #ddd;vertical-align:top}}\
+     code{{font-size:13px;color:#444;word-break:break-all}}.diff{{font-size:13px;background:#f6f6f6;padding:.6rem;overflow:auto}}\
+     .diff .added{{color:#1b5e20}}.diff .removed{{color:#b00020}}.diff .hunk{{color:#6a1b9a}}</style></head>\
+     <body><h1>Request replay</h1>{body}</body></html>"
+  )
+}
+
+/// The request body as shown in the panel, with credential-looking form
+/// fields masked and long bodies cut short.
+fn masked_body(body: &[u8]) -> String {
+  let body = String::from_utf8_lossy(body);
+  let masked = if body.contains('=') && !body.trim_start().starts_with(['{', '[', '<']) {
+    body
+      .split('&')
+      .map(|field| match field.split_once('=') {
+        Some((name, _)) if SECRET_FIELDS.iter().any(|secret| name.to_ascii_lowercase().contains(secret)) => {
+          format!("{name}={SCRUBBED}")
+        }
+        _ => field.to_string(),
+      })
+      .collect::<Vec<_>>()
+      .join("&")
+  } else {
+    body.into_owned()
+  };
+  match masked.char_indices().nth(500) {
+    Some((end, _)) => format!("{}…", &masked[..end]),
+    None => masked,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_log_keeps_the_latest_recording_of_each_request() {
+    let log = ReplayLog::default();
+    log.record("POST", "/signup", Vec::new(), Bytes::from_static(b"name=ada"), 200, None);
+    log.record("GET", "/", Vec::new(), Bytes::new(), 200, Some("<h1>Home</h1>".to_string()));
+    log.record("POST", "/signup", Vec::new(), Bytes::from_static(b"name=ada"), 422, None);
+    let entries = log.entries();
+    assert_eq!(entries.iter().map(|entry| (entry.uri.as_str(), entry.status)).collect::<Vec<_>>(), [("/signup", 422), ("/", 200)]);
+
+    log.update(entries[1].id, 500, None);
+    assert_eq!(log.get(entries[1].id).map(|entry| entry.status), Some(500));
+    for index in 0..CAPACITY {
+      log.record("GET", &format!("/{index}"), Vec::new(), Bytes::new(), 200, None);
+    }
+    assert_eq!(log.entries().len(), CAPACITY);
+    assert_eq!(log.get(entries[0].id), None);
+  }
+
+  #[test]
+  fn test_panel_masks_credentials_and_shows_the_diff() {
+    assert_eq!(masked_body(b"email=ada%40example.com&Password=hunter2"), "email=ada%40example.com&Password=[scrubbed]");
+    assert_eq!(masked_body(br#"{"password":"hunter2"}"#), r#"{"password":"hunter2"}"#);
+
+    let request = RecordedRequest {
+      id: 1,
+      method: "POST".to_string(),
+      uri: "/signup".to_string(),
+      headers: Vec::new(),
+      body: Bytes::from_static(b"name=ada"),
+      status: 200,
+      page: Some("<h1>Welcome</h1>\n<p>ada</p>".to_string()),
+    };
+    let replayed = Replayed {
+      request: request.clone(),
+      status: 422,
+      page: Some("<h1>Sorry</h1>\n<p>ada</p>".to_string()),
+    };
+    let html = panel_html(&[request], Some(&replayed));
+    assert!(html.contains("<h2>POST &#x2F;signup: 200 → 422</h2>"), "{html}");
+    assert!(html.contains("<span class=\"removed\">-&lt;h1&gt;Welcome&lt;&#x2F;h1&gt;</span>"), "{html}");
+    assert!(html.contains("<form method=\"post\" action=\"/_snapfire/replay?id=1\">"), "{html}");
+  }
+}
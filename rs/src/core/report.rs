@@ -0,0 +1,279 @@
+//! Reporting failed renders to an error tracker.
+//!
+//! `TeraWebBuilder::error_reporter` registers a function called with a
+//! [`RenderFailure`] whenever a render fails, just before the error is
+//! returned to the caller. It gets the template (the partial the error happened in,
+//! when Tera names one), the line when it can be found, the whole error
+//! chain, a summary of the render context, and the request's host, path,
+//! and id:
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! use snapfire::TeraWeb;
+//!
+//! let app = TeraWeb::builder("templates/**/*.html")
+//!   .error_reporter(|failure| eprintln!("{failure}"))
+//!   .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The context summary only previews values: strings are cut short, lists
+//! and maps are described by their size and keys, and values under keys that
+//! look secret (`password`, `token`, `secret`, `api_key`, `cookie`...) are
+//! replaced by `[scrubbed]`, as are values inserted with
+//! `insert_sensitive`. With the `sentry` feature, [`sentry_reporter`]
+//! sends failures to Sentry through the client the app initialized.
+
+use crate::core::app::RequestParts;
+use crate::core::sensitive::SCRUBBED;
+use crate::error::SnapFireError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tera::{Context, Tera, Value};
+
+/// The function called with each failed render.
+#[derive(Clone)]
+pub(crate) struct ErrorReporter(Arc<dyn Fn(&RenderFailure) + Send + Sync>);
+
+impl ErrorReporter {
+  pub(crate) fn new(reporter: impl Fn(&RenderFailure) + Send + Sync + 'static) -> Self {
+    Self(Arc::new(reporter))
+  }
+
+  pub(crate) fn report(&self, failure: &RenderFailure) {
+    (self.0)(failure)
+  }
+}
+
+impl std::fmt::Debug for ErrorReporter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("ErrorReporter")
+  }
+}
+
+/// The longest string preview in a context summary, in characters.
+const PREVIEW_CHARS: usize = 80;
+
+/// Parts of context keys whose values are never previewed.
+const SECRET_KEYS: &[&str] = &[
+  "password", "passwd", "secret", "token", "api_key", "apikey", "auth", "cookie", "session", "credit_card", "card_number",
+  "cvv", "ssn", "private_key",
+];
+
+/// A failed render, as handed to the error reporter.
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderFailure {
+  /// The template the error happened in: the page, or a partial or parent
+  /// it uses when Tera names one.
+  pub template: String,
+  /// The template requested for the render.
+  pub requested: String,
+  /// The 1-based line of the error, when it can be found in the template's
+  /// file.
+  pub line: Option<usize>,
+  /// The error and all its causes, joined with `: `.
+  pub message: String,
+  /// A preview of each top-level context value, by key; secrets scrubbed.
+  pub context: BTreeMap<String, String>,
+  pub host: Option<String>,
+  pub path: Option<String>,
+  /// The id the `RequestIds` middleware gave the request.
+  pub request_id: Option<String>,
+}
+
+impl std::fmt::Display for RenderFailure {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Render of `{}` failed", self.requested)?;
+    if let Some(id) = &self.request_id {
+      write!(f, " (request {id})")?;
+    }
+    write!(f, " in {}", self.template)?;
+    if let Some(line) = self.line {
+      write!(f, ":{line}")?;
+    }
+    write!(f, ": {}", self.message)
+  }
+}
+
+impl RenderFailure {
+  pub(crate) fn new(
+    tera: &Tera,
+    requested: &str,
+    request: &RequestParts,
+    error: &SnapFireError,
+    context: BTreeMap<String, String>,
+  ) -> Self {
+    let message = crate::error::error_chain(error);
+    let template = failing_template(&message)
+      .filter(|name| tera.get_template(name).is_ok())
+      .unwrap_or(requested)
+      .to_string();
+    let line = error_line(tera, &template, &message);
+    Self {
+      template,
+      requested: requested.to_string(),
+      line,
+      message,
+      context,
+      host: request.host.map(str::to_string),
+      path: request.path.map(str::to_string),
+      request_id: request.request_id.map(str::to_string),
+    }
+  }
+}
+
+/// The last template an error message says was being rendered.
+fn failing_template(message: &str) -> Option<&str> {
+  let start = message.rfind("rendering '")? + "rendering '".len();
+  let end = message[start..].find('\'')?;
+  Some(&message[start..start + end])
+}
+
+/// The line of `template` the error is about: the position Tera reports, or
+/// else the first line mentioning the `name` in the message.
+fn error_line(tera: &Tera, template: &str, message: &str) -> Option<usize> {
+  if let Some(at) = message.find("--> ") {
+    let position = &message[at + 4..];
+    let line = position.split(':').next()?;
+    if let Ok(line) = line.trim().parse() {
+      return Some(line);
+    }
+  }
+  let start = message.find('`')? + 1;
+  let name = &message[start..start + message[start..].find('`')?];
+  let path = tera.get_template(template).ok()?.path.as_ref()?;
+  let source = std::fs::read_to_string(path).ok()?;
+  source
+    .lines()
+    .position(|line| line.contains(name))
+    .map(|index| index + 1)
+}
+
+/// A preview of each top-level value of `context`, with secrets and the
+/// values under the `sensitive` keys scrubbed.
+pub(crate) fn summarize(context: &Context, sensitive: &[String]) -> BTreeMap<String, String> {
+  let Value::Object(map) = context.clone().into_json() else {
+    return BTreeMap::new();
+  };
+  map
+    .into_iter()
+    .map(|(key, value)| {
+      let preview = if is_secret(&key) || sensitive.contains(&key) {
+        SCRUBBED.to_string()
+      } else {
+        preview(&value)
+      };
+      (key, preview)
+    })
+    .collect()
+}
+
+fn is_secret(key: &str) -> bool {
+  let key = key.to_ascii_lowercase();
+  SECRET_KEYS.iter().any(|secret| key.contains(secret))
+}
+
+fn preview(value: &Value) -> String {
+  match value {
+    Value::String(string) if string.chars().count() > PREVIEW_CHARS => {
+      let cut: String = string.chars().take(PREVIEW_CHARS).collect();
+      format!("{cut:?}… ({} chars)", string.chars().count())
+    }
+    Value::String(string) => format!("{string:?}"),
+    Value::Array(items) => format!("[{} items]", items.len()),
+    Value::Object(map) => {
+      let keys: Vec<_> = map
+        .iter()
+        .map(|(key, _)| if is_secret(key) { format!("{key} (scrubbed)") } else { key.clone() })
+        .collect();
+      format!("{{{}}}", keys.join(", "))
+    }
+    value => value.to_string(),
+  }
+}
+
+/// An error reporter sending failures to Sentry, through the client the
+/// app initialized with `sentry::init`. Only available with the `sentry`
+/// feature.
+///
+/// Each failure becomes an error event whose message is the error chain,
+/// tagged with the template, the requested template, and the request id,
+/// and carrying the line and context summary as extra data. Events are
+/// grouped by failing template and message.
+#[cfg(feature = "sentry")]
+pub fn sentry_reporter() -> impl Fn(&RenderFailure) + Send + Sync + 'static {
+  use sentry_core::protocol::{Event, Level, Map};
+
+  |failure| {
+    let mut tags = Map::new();
+    tags.insert("template".to_string(), failure.template.clone());
+    tags.insert("requested_template".to_string(), failure.requested.clone());
+    if let Some(id) = &failure.request_id {
+      tags.insert("request_id".to_string(), id.clone());
+    }
+    let mut extra = Map::new();
+    if let Some(line) = failure.line {
+      extra.insert("line".to_string(), line.into());
+    }
+    if let Some(host) = &failure.host {
+      extra.insert("host".to_string(), host.clone().into());
+    }
+    extra.insert(
+      "context".to_string(),
+      serde_json::to_value(&failure.context).expect("a summary serializes"),
+    );
+    let event = Event {
+      level: Level::Error,
+      logger: Some("snapfire".to_string()),
+      message: Some(failure.message.clone()),
+      transaction: failure.path.clone(),
+      fingerprint: vec![failure.template.clone().into(), failure.message.clone().into()].into(),
+      tags,
+      extra,
+      ..Event::default()
+    };
+    sentry_core::capture_event(event);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_summary_scrubs_secrets_and_finds_the_failing_line() {
+    let mut context = Context::new();
+    context.insert("title", "Welcome");
+    context.insert("user", &serde_json::json!({"name": "ana", "auth_token": "t0k3n"}));
+    context.insert("password", "hunter2");
+    context.insert("items", &[1, 2, 3]);
+    context.insert("bio", &"x".repeat(100));
+    context.insert("dob", "1815-12-10");
+    let summary = summarize(&context, &["dob".to_string()]);
+    assert_eq!(summary["title"], "\"Welcome\"");
+    assert_eq!(summary["user"], "{auth_token (scrubbed), name}");
+    assert_eq!(summary["password"], "[scrubbed]");
+    assert_eq!(summary["items"], "[3 items]");
+    assert_eq!((summary["dob"].as_str(), summary.len()), ("[scrubbed]", 6));
+    assert!(summary["bio"].ends_with("… (100 chars)"));
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("page.html"), "<h1>Hi</h1>\n{% include \"card.html\" %}").unwrap();
+    std::fs::write(temp_dir.path().join("card.html"), "<div>\n  {{ card.title }}\n</div>").unwrap();
+    let tera = Tera::new(temp_dir.path().join("*.html").to_str().unwrap()).unwrap();
+    let error = SnapFireError::from(tera.render("page.html", &Context::new()).unwrap_err());
+    let request = RequestParts {
+      request_id: Some("abc"),
+      ..RequestParts::default()
+    };
+    let failure = RenderFailure::new(&tera, "page.html", &request, &error, summary);
+    assert_eq!((failure.template.as_str(), failure.line), ("card.html", Some(2)));
+    assert!(
+      failure
+        .to_string()
+        .starts_with("Render of `page.html` failed (request abc) in card.html:2: ")
+    );
+  }
+}
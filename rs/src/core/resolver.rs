@@ -0,0 +1,148 @@
+//! Resolving the template names handlers ask for to the names Tera knows.
+//!
+//! By default a template is rendered by its exact name, like `blog/post.html`.
+//! With a [`TemplateResolver`] registered through the builder's
+//! `template_resolver`, other names resolve too:
+//!
+//! ```rust,no_run
+//! # use snapfire::{TeraWeb, TemplateResolver};
+//! # fn main() -> snapfire::Result<()> {
+//! let app = TeraWeb::builder("templates/**/*")
+//!   .template_resolver(
+//!     TemplateResolver::new()
+//!       .extensions(["html", "tera"])
+//!       .index_files(["index.html"])
+//!       .case_insensitive(true),
+//!   )
+//!   .build()?;
+//! // `blog/post` renders `blog/post.html`, or else `blog/post/index.html`.
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! An exact match always wins. The candidates are then tried in order: the
+//! name with each extension, then the name as a directory with each index
+//! file, and, if enabled, the same again ignoring case. A name that resolves
+//! to nothing is rendered as given, so Tera reports the usual missing
+//! template error.
+
+use tera::Tera;
+
+/// The rules for finding a template by a name that isn't its exact name.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateResolver {
+  extensions: Vec<String>,
+  index_files: Vec<String>,
+  case_insensitive: bool,
+}
+
+impl TemplateResolver {
+  /// A resolver matching exact names only, until rules are added.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Extensions to try, in order, on names without a match, e.g. `["html"]`
+  /// so `about` resolves to `about.html`. A leading `.` is optional.
+  pub fn extensions<I, S>(mut self, extensions: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    self.extensions = extensions
+      .into_iter()
+      .map(|extension| extension.as_ref().trim_start_matches('.').to_string())
+      .collect();
+    self
+  }
+
+  /// Files to try, in order, inside a directory named like the template,
+  /// e.g. `["index.html"]` so `blog` resolves to `blog/index.html`.
+  pub fn index_files<I, S>(mut self, index_files: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.index_files = index_files.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Whether names may match templates whose names differ only in case,
+  /// when no candidate matches exactly.
+  pub fn case_insensitive(mut self, enabled: bool) -> Self {
+    self.case_insensitive = enabled;
+    self
+  }
+
+  /// The name of the template `name` resolves to in `tera`, if any.
+  pub fn resolve(&self, tera: &Tera, name: &str) -> Option<String> {
+    let exists = |candidate: &str| tera.get_template(candidate).is_ok();
+    if exists(name) {
+      return Some(name.to_string());
+    }
+
+    let candidates = self.candidates(name);
+    if let Some(found) = candidates.iter().find(|candidate| exists(candidate)) {
+      return Some(found.clone());
+    }
+    if !self.case_insensitive {
+      return None;
+    }
+
+    // Template names are unique, but may differ only in case; pick the first
+    // in sorted order so the choice doesn't depend on hash order.
+    let mut names: Vec<&str> = tera.get_template_names().collect();
+    names.sort_unstable();
+    std::iter::once(name)
+      .chain(candidates.iter().map(String::as_str))
+      .find_map(|candidate| names.iter().find(|name| name.eq_ignore_ascii_case(candidate)))
+      .map(|name| name.to_string())
+  }
+
+  /// The names to try after the exact one, in order.
+  fn candidates(&self, name: &str) -> Vec<String> {
+    let base = name.trim_end_matches('/');
+    let with_extensions = self.extensions.iter().map(|extension| format!("{base}.{extension}"));
+    let index_files = self.index_files.iter().map(|index| {
+      if base.is_empty() {
+        index.clone()
+      } else {
+        format!("{base}/{index}")
+      }
+    });
+    with_extensions.chain(index_files).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tera(names: &[&str]) -> Tera {
+    let mut tera = Tera::default();
+    tera
+      .add_raw_templates(names.iter().map(|name| (*name, *name)))
+      .unwrap();
+    tera
+  }
+
+  #[test]
+  fn test_resolves_extensions_and_index_files() {
+    let tera = tera(&["about.html", "blog/index.html", "blog/post.tera", "Docs/Intro.html", "index.html"]);
+    let resolver = TemplateResolver::new().extensions([".html", "tera"]).index_files(["index.html"]);
+
+    let resolve = |name: &str| resolver.resolve(&tera, name);
+    assert_eq!(resolve("about.html").as_deref(), Some("about.html"));
+    assert_eq!(resolve("about").as_deref(), Some("about.html"));
+    assert_eq!(resolve("blog/post").as_deref(), Some("blog/post.tera"));
+    assert_eq!(resolve("blog").as_deref(), Some("blog/index.html"));
+    assert_eq!(resolve("blog/").as_deref(), Some("blog/index.html"));
+    assert_eq!(resolve("").as_deref(), Some("index.html"));
+    assert_eq!(resolve("docs/intro"), None);
+
+    let resolver = resolver.case_insensitive(true);
+    assert_eq!(resolver.resolve(&tera, "docs/intro").as_deref(), Some("Docs/Intro.html"));
+    assert_eq!(resolver.resolve(&tera, "ABOUT").as_deref(), Some("about.html"));
+    assert_eq!(resolver.resolve(&tera, "missing"), None);
+  }
+}
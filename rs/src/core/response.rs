@@ -0,0 +1,310 @@
+//! Rendered pages as plain data, for servers SnapFire doesn't integrate with.
+//!
+//! [`Template::into_rendered`] renders a page into a [`RenderedResponse`]:
+//! the status, content type, headers, and body the Actix responder would
+//! send, for handlers that build their own responses (serverless functions,
+//! other frameworks, tests):
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! let app = snapfire::TeraWeb::builder("templates/**/*.html").build()?;
+//! let response = app.render("index.html", tera::Context::new()).into_rendered();
+//! println!("{} {} ({} bytes)", response.status, response.content_type, response.body.len());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The page renders without a request, so without its host, path, or the
+//! values Actix middleware provides; the `lambda` feature's
+//! `snapfire::lambda::render` passes the request's host and path along.
+
+use crate::core::app::{RequestParts, Template};
+use crate::core::vary::VaryOn;
+use bytes::Bytes;
+
+/// A rendered page, or the error response standing in for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedResponse {
+  /// `200` or the `Template::with_status` status, `500` when the render
+  /// failed, or `503` while the page's circuit is open.
+  pub status: u16,
+  /// `text/html; charset=utf-8` for pages and `error_template` pages,
+  /// `text/plain; charset=utf-8` for other errors.
+  pub content_type: String,
+  /// Other headers to send: `Vary` when cache keys have dimensions, the
+  /// page's `Link` early hints, `Retry-After` with a `503`, those of
+  /// `Template::insert_header` and `add_cookie`, and the page's
+  /// `Surrogate-Key` with `surrogate_keys`. Errors are sent with
+  /// `Cache-Control: no-store`. A name can repeat, as `Set-Cookie` does.
+  pub headers: Vec<(String, String)>,
+  /// The page, the `error_template` page on errors, or an empty body
+  /// without one.
+  pub body: Bytes,
+}
+
+/// How a page depends on the request, beyond its template, for the caching
+/// headers sent with it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Caching {
+  /// The request headers the page's data came from, sent as `Vary` along
+  /// with the app's cache key dimensions.
+  pub(crate) vary: VaryOn,
+  /// Whether the page holds request data `vary` doesn't account for, so
+  /// only the client may cache it.
+  pub(crate) private: bool,
+}
+
+impl Template {
+  /// Renders the page into a [`RenderedResponse`]. A failed render is
+  /// logged and becomes a `500`, with the builder's `error_template` page if
+  /// it has one.
+  pub fn into_rendered(self) -> RenderedResponse {
+    self.into_rendered_for(RequestParts::default(), Caching::default())
+  }
+
+  /// Renders the page for `request` into a [`RenderedResponse`].
+  pub(crate) fn into_rendered_for(self, request: RequestParts, caching: Caching) -> RenderedResponse {
+    let request = RequestParts {
+      sensitive: &self.sensitive,
+      ..request
+    };
+    let (status, mut headers) = self.head(caching);
+    let (result, surrogate_key) = match self.prerendered {
+      Some(page) => (Ok(page), None),
+      None => self
+        .app_state
+        .render_keyed_for_request(&self.template_name, request, self.context),
+    };
+    let body = match result {
+      Ok(body) => body,
+      Err(e) => {
+        log::error!("Template rendering error{}: {:?}", for_request(request.request_id), e);
+        return match self.app_state.render_error_page(request, &e) {
+          Some(page) => RenderedResponse {
+            status: 500,
+            content_type: "text/html; charset=utf-8".to_string(),
+            headers: vec![("Cache-Control".to_string(), "no-store".to_string())],
+            body: page,
+          },
+          None => RenderedResponse {
+            status: 500,
+            content_type: "text/plain; charset=utf-8".to_string(),
+            headers: vec![("Cache-Control".to_string(), "no-store".to_string())],
+            body: Bytes::new(),
+          },
+        };
+      }
+    };
+
+    // The page's cache tags, unless the handler sent its own.
+    if let Some(keys) = surrogate_key
+      && !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("surrogate-key"))
+    {
+      headers.push(("Surrogate-Key".to_string(), keys));
+    }
+    RenderedResponse {
+      status,
+      content_type: "text/html; charset=utf-8".to_string(),
+      headers,
+      body,
+    }
+  }
+
+  /// The status and headers the page is sent with, known before it renders:
+  /// SnapFire's caching, early hint, and outage headers, replaced by the
+  /// handler's own.
+  pub(crate) fn head(&self, caching: Caching) -> (u16, Vec<(String, String)>) {
+    let mut vary = caching.vary;
+    if let Some(dimensions) = self.app_state.vary() {
+      vary.header(dimensions);
+    }
+    // A page whose circuit is open gets the fallback, as an outage.
+    let open_for = self
+      .prerendered
+      .is_none()
+      .then(|| self.app_state.circuit_open_for(&self.template_name))
+      .flatten();
+
+    let mut headers = Vec::new();
+    if let Some(vary) = vary.value() {
+      headers.push(("Vary".to_string(), vary));
+    }
+    if caching.private {
+      headers.push(("Cache-Control".to_string(), "private".to_string()));
+    }
+    if let Some(links) = self.app_state.early_hints_for(&self.template_name) {
+      headers.push(("Link".to_string(), links));
+    }
+    if let Some(open_for) = open_for {
+      headers.push(("Retry-After".to_string(), (open_for.as_secs_f64().ceil() as u64).to_string()));
+    }
+    // The handler's headers replace SnapFire's; repeated ones are all sent.
+    headers.retain(|(name, _)| !self.headers.iter().any(|(own, _)| name.eq_ignore_ascii_case(own)));
+    headers.extend(self.headers.iter().cloned());
+    (if open_for.is_some() { 503 } else { self.status }, headers)
+  }
+}
+
+/// Converts a [`RenderedResponse`] into an `http::Response` with a `$body`
+/// body, for the integrations built on the `http` crate. A macro, since warp
+/// and hyper use `http` 0.2 and the lambda adapter `http` 1: the first
+/// argument is the path of the one to use, e.g. `warp::http`. Headers that
+/// aren't valid for `http` are dropped.
+#[cfg(any(feature = "warp", feature = "hyper", feature = "lambda"))]
+macro_rules! into_http_response {
+  ($($http:ident)::+; $rendered:expr, $body:ty) => {{
+    let rendered: $crate::core::response::RenderedResponse = $rendered;
+    let mut response = $($http)::+::Response::new(<$body>::from(rendered.body));
+    *response.status_mut() =
+      $($http)::+::StatusCode::from_u16(rendered.status).unwrap_or($($http)::+::StatusCode::INTERNAL_SERVER_ERROR);
+    let headers = response.headers_mut();
+    if let Ok(content_type) = $($http)::+::header::HeaderValue::try_from(rendered.content_type) {
+      headers.insert($($http)::+::header::CONTENT_TYPE, content_type);
+    }
+    for (name, value) in rendered.headers {
+      if let (Ok(name), Ok(value)) = (
+        $($http)::+::header::HeaderName::try_from(name),
+        $($http)::+::header::HeaderValue::try_from(value),
+      ) {
+        headers.append(name, value);
+      }
+    }
+    response
+  }};
+}
+#[cfg(any(feature = "warp", feature = "hyper", feature = "lambda"))]
+pub(crate) use into_http_response;
+
+/// ` (request <id>)`, to tag a log line, or nothing without an id.
+pub(crate) fn for_request(id: Option<&str>) -> String {
+  id.map(|id| format!(" (request {id})")).unwrap_or_default()
+}
+
+/// Serves an asset with a content hash `version`: cacheable forever when
+/// requested at its versioned URL (`?v=<version>`), revalidated against the
+/// ETag otherwise.
+///
+/// # Arguments
+///
+/// * `version` - The asset's content hash, used as its ETag.
+/// * `content_type` - The asset's `Content-Type`.
+/// * `body` - The asset.
+/// * `query` - The request's query string, without the `?`.
+/// * `if_none_match` - The request's `If-None-Match` header, if any.
+#[cfg(any(feature = "actix", all(feature = "devel", any(feature = "hyper", feature = "warp"))))]
+pub(crate) fn versioned(
+  version: &str,
+  content_type: &str,
+  body: Bytes,
+  query: &str,
+  if_none_match: Option<&str>,
+) -> RenderedResponse {
+  let etag = format!("\"{version}\"");
+  let not_modified =
+    if_none_match.is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+  let cache_control = if query == format!("v={version}") {
+    "public, max-age=31536000, immutable"
+  } else {
+    "no-cache"
+  };
+  RenderedResponse {
+    status: if not_modified { 304 } else { 200 },
+    content_type: content_type.to_string(),
+    headers: vec![
+      ("ETag".to_string(), etag),
+      ("Cache-Control".to_string(), cache_control.to_string()),
+    ],
+    body: if not_modified { Bytes::new() } else { body },
+  }
+}
+
+/// Whether `name` is a valid header name: a token of visible ASCII.
+pub(crate) fn is_header_name(name: &str) -> bool {
+  !name.is_empty()
+    && name
+      .bytes()
+      .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+/// Whether `value` is a valid header value: no control characters but tabs.
+pub(crate) fn is_header_value(value: &str) -> bool {
+  value.bytes().all(|byte| byte == b'\t' || (byte >= 0x20 && byte != 0x7f))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::TeraWeb;
+  use tera::Context;
+
+  #[test]
+  fn test_templates_render_into_plain_responses() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("index.html"), "<h1>{{ title }}</h1>").unwrap();
+    std::fs::write(temp_dir.path().join("broken.html"), "{{ missing }}").unwrap();
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .add_global("title", "Welcome")
+      .build()
+      .unwrap();
+
+    let page = app.render("index.html", Context::new()).into_rendered();
+    assert_eq!((page.status, page.content_type.as_str()), (200, "text/html; charset=utf-8"));
+    assert_eq!(&page.body[..], b"<h1>Welcome</h1>");
+    let missing = app.render("index.html", Context::new()).with_status(404u16).into_rendered();
+    assert_eq!((missing.status, &missing.body[..]), (404, &b"<h1>Welcome</h1>"[..]));
+    let error = app.render("broken.html", Context::new()).with_status(404u16).into_rendered();
+    assert_eq!((error.status, error.body.len()), (500, 0));
+    assert_eq!(error.headers, [("Cache-Control".to_string(), "no-store".to_string())]);
+
+    let page = app
+      .render("index.html", Context::new())
+      .insert_header("Cache-Control", "no-cache")
+      .insert_header("cache-control", "public, max-age=600")
+      .insert_header("X-Frame-Options", "DENY")
+      .into_rendered();
+    assert_eq!(
+      page.headers,
+      [
+        ("cache-control".to_string(), "public, max-age=600".to_string()),
+        ("x-frame-options".to_string(), "DENY".to_string())
+      ]
+    );
+  }
+
+  #[test]
+  fn test_failed_renders_get_the_error_template() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("broken.html"), "{{ missing }}").unwrap();
+    std::fs::write(temp_dir.path().join("500.html"), "<h1>{{ status }} at {{ site }}</h1>{{ error | default(value=\"\") }}").unwrap();
+    let glob = temp_dir.path().join("*.html");
+    let app = TeraWeb::builder(glob.to_str().unwrap())
+      .add_global("site", "Example")
+      .error_template("500.html")
+      .build()
+      .unwrap();
+
+    let error = app.render("broken.html", Context::new()).into_rendered();
+    assert_eq!((error.status, error.content_type.as_str()), (500, "text/html; charset=utf-8"));
+    let page = std::str::from_utf8(&error.body).unwrap();
+    assert!(page.starts_with("<h1>500 at Example</h1>"), "{page}");
+    // The details are only shown in dev mode.
+    assert_eq!(page.contains("missing"), cfg!(feature = "devel"), "{page}");
+
+    let missing = TeraWeb::builder(glob.to_str().unwrap()).error_template("errors/500.html").build();
+    assert!(missing.is_err());
+  }
+
+  #[test]
+  fn test_templates_render_eagerly() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("email.html"), "Hello {{ name }}").unwrap();
+    std::fs::write(temp_dir.path().join("broken.html"), "{{ missing }}").unwrap();
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap()).build().unwrap();
+
+    let mut context = Context::new();
+    context.insert("name", "Ada");
+    let template = app.render("email.html", context.clone()).with_status(404u16);
+    assert_eq!(template.into_string().unwrap(), "Hello Ada");
+    assert_eq!(&app.render("email.html", context).into_bytes().unwrap()[..], b"Hello Ada");
+    assert!(app.render("broken.html", Context::new()).into_string().is_err());
+  }
+}
@@ -0,0 +1,174 @@
+//! Right-to-left layout helpers.
+//!
+//! Pages rendered for a request with a locale get `dir` (`"rtl"` or `"ltr"`)
+//! and `is_rtl` context values, so layouts can write `<html dir="{{ dir }}">`.
+//! The same information is available through template functions and a
+//! filter, which default to the page's `locale` and accept a `locale`
+//! argument:
+//!
+//! - `dir()` → `rtl` or `ltr`
+//! - `text_align_start()` → `right` or `left`, for CSS without logical values
+//! - `css | logical_css` → the CSS with physical properties (`margin-left`,
+//!   `text-align: right`, ...) rewritten to their logical equivalents
+//!   (`margin-inline-start`, `text-align: end`, ...), which flip with `dir`
+
+use crate::core::scope;
+use std::collections::HashMap;
+use tera::{Filter, Function, Value};
+
+/// The primary language subtags written right to left.
+const RTL_LANGUAGES: &[&str] = &[
+  "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ku", "ps", "sd", "syr", "ug", "ur", "yi",
+];
+
+/// Whether `locale` (e.g. `ar` or `he-IL`) is written right to left.
+pub fn is_rtl(locale: &str) -> bool {
+  let language = locale.split(['-', '_']).next().unwrap_or_default();
+  RTL_LANGUAGES.iter().any(|rtl| rtl.eq_ignore_ascii_case(language))
+}
+
+/// The `dir` attribute value for `locale`: `rtl` or `ltr`.
+pub fn text_direction(locale: &str) -> &'static str {
+  if is_rtl(locale) { "rtl" } else { "ltr" }
+}
+
+/// Rewrites physical CSS properties and values to their flow-relative
+/// (logical) equivalents, e.g. `margin-left` to `margin-inline-start`.
+///
+/// Only the horizontal ones are rewritten; `top` and `bottom` stay as they are.
+pub fn logical_css(css: &str) -> String {
+  css
+    .split_inclusive(';')
+    .map(|declaration| {
+      let Some((property, value)) = declaration.split_once(':') else {
+        return declaration.to_string();
+      };
+      let name = property.trim();
+      let property = match LOGICAL_PROPERTIES.iter().find(|(physical, _)| physical.eq_ignore_ascii_case(name)) {
+        Some((_, logical)) => property.replacen(name, logical, 1),
+        None => property.to_string(),
+      };
+      let value = if ["text-align", "float", "clear"].iter().any(|p| p.eq_ignore_ascii_case(name)) {
+        logical_value(name, value)
+      } else {
+        value.to_string()
+      };
+      format!("{property}:{value}")
+    })
+    .collect()
+}
+
+const LOGICAL_PROPERTIES: &[(&str, &str)] = &[
+  ("margin-left", "margin-inline-start"),
+  ("margin-right", "margin-inline-end"),
+  ("padding-left", "padding-inline-start"),
+  ("padding-right", "padding-inline-end"),
+  ("border-left", "border-inline-start"),
+  ("border-right", "border-inline-end"),
+  ("border-left-width", "border-inline-start-width"),
+  ("border-right-width", "border-inline-end-width"),
+  ("border-left-style", "border-inline-start-style"),
+  ("border-right-style", "border-inline-end-style"),
+  ("border-left-color", "border-inline-start-color"),
+  ("border-right-color", "border-inline-end-color"),
+  ("border-top-left-radius", "border-start-start-radius"),
+  ("border-top-right-radius", "border-start-end-radius"),
+  ("border-bottom-left-radius", "border-end-start-radius"),
+  ("border-bottom-right-radius", "border-end-end-radius"),
+  ("left", "inset-inline-start"),
+  ("right", "inset-inline-end"),
+];
+
+/// Swaps `left`/`right` keywords for `text-align`, `float`, and `clear`.
+fn logical_value(property: &str, value: &str) -> String {
+  let (start, end) = if property.eq_ignore_ascii_case("text-align") {
+    ("start", "end")
+  } else {
+    ("inline-start", "inline-end")
+  };
+  let keyword = value.trim().trim_end_matches(';').trim();
+  let logical = match keyword.to_ascii_lowercase().as_str() {
+    "left" => start,
+    "right" => end,
+    _ => return value.to_string(),
+  };
+  value.replacen(keyword, logical, 1)
+}
+
+/// The locale named by the `locale` argument, else the page's, else the default.
+fn locale_argument(default_locale: &str, args: &HashMap<String, Value>, name: &str) -> tera::Result<String> {
+  match args.get("locale") {
+    Some(locale) => locale
+      .as_str()
+      .map(str::to_string)
+      .ok_or_else(|| tera::Error::msg(format!("The `locale` argument of `{name}` must be a string"))),
+    None => Ok(scope::current_locale().unwrap_or_else(|| default_locale.to_string())),
+  }
+}
+
+/// The `dir(locale=...)` Tera function.
+pub(crate) struct Dir(pub(crate) String);
+
+impl Function for Dir {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::from(text_direction(&locale_argument(&self.0, args, "dir")?)))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// The `text_align_start(locale=...)` Tera function.
+pub(crate) struct TextAlignStart(pub(crate) String);
+
+impl Function for TextAlignStart {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let locale = locale_argument(&self.0, args, "text_align_start")?;
+    Ok(Value::from(if is_rtl(&locale) { "right" } else { "left" }))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// The `logical_css` Tera filter.
+pub(crate) struct LogicalCss;
+
+impl Filter for LogicalCss {
+  fn filter(&self, value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let css = value
+      .as_str()
+      .ok_or_else(|| tera::Error::msg("The `logical_css` filter can only be applied to strings"))?;
+    Ok(Value::from(logical_css(css)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_direction_follows_locale() {
+    assert_eq!(text_direction("ar"), "rtl");
+    assert_eq!(text_direction("he-IL"), "rtl");
+    assert_eq!(text_direction("fa_IR"), "rtl");
+    assert_eq!(text_direction("fr"), "ltr");
+
+    let dir = Dir("en".to_string());
+    assert_eq!(dir.call(&HashMap::new()).unwrap(), "ltr");
+    scope::with_locale(Some("ar"), || {
+      assert_eq!(dir.call(&HashMap::new()).unwrap(), "rtl");
+      assert_eq!(TextAlignStart("en".to_string()).call(&HashMap::new()).unwrap(), "right");
+    });
+  }
+
+  #[test]
+  fn test_logical_css() {
+    assert_eq!(
+      logical_css("margin-left: 1rem; Padding-Right:0;text-align: left; float: right; left: 0; top: 0; color: red"),
+      "margin-inline-start: 1rem; padding-inline-end:0;text-align: start; float: inline-end; inset-inline-start: 0; top: 0; color: red"
+    );
+  }
+}
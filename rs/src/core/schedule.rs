@@ -0,0 +1,204 @@
+//! Scheduled re-rendering of pages.
+//!
+//! Pages registered with `TeraWebBuilder::schedule_rerender` are rendered on
+//! a background thread at startup and then on their cron schedule, evaluated
+//! in the timezone set with `TeraWebBuilder::schedule_timezone` (UTC by
+//! default). Handlers serve the latest render with `TeraWeb::render_cached`,
+//! so pages can be cached aggressively without going stale.
+
+use crate::core::app::{RequestParts, Template, TeraWeb};
+use crate::error::{Result, SnapFireError};
+use bytes::Bytes;
+use chrono::Utc;
+use chrono_tz::Tz;
+use cron::Schedule;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use tera::Context;
+
+/// Produces the context for a scheduled render, e.g. by loading fresh data.
+pub(crate) type ContextProvider = Arc<dyn Fn() -> Context + Send + Sync>;
+
+/// A page to re-render on a schedule.
+pub(crate) struct RerenderJob {
+  template: String,
+  schedule: Schedule,
+  provider: ContextProvider,
+}
+
+impl RerenderJob {
+  /// Parses `expression`, a cron expression with a seconds field
+  /// (e.g. `0 0 * * * *` for hourly).
+  pub(crate) fn new(template: &str, expression: &str, provider: ContextProvider) -> Result<Self> {
+    let schedule = Schedule::from_str(expression).map_err(|e| SnapFireError::Schedule {
+      template: template.to_string(),
+      message: e.to_string(),
+    })?;
+    Ok(Self {
+      template: template.to_string(),
+      schedule,
+      provider,
+    })
+  }
+}
+
+/// The background thread re-rendering scheduled pages, and its latest output.
+///
+/// Dropping the scheduler (with the last `TeraWeb` clone) stops the thread.
+pub(crate) struct Scheduler {
+  pages: Arc<RwLock<HashMap<String, Bytes>>>,
+  providers: HashMap<String, ContextProvider>,
+  _shutdown: mpsc::Sender<()>,
+}
+
+impl Scheduler {
+  /// Starts the scheduler thread, rendering through `worker`.
+  ///
+  /// `worker` must not hold the scheduler itself, or the thread would keep
+  /// its own shutdown channel alive.
+  pub(crate) fn start(worker: TeraWeb, jobs: Vec<RerenderJob>, timezone: Tz) -> Self {
+    let pages = Arc::new(RwLock::new(HashMap::new()));
+    let providers = jobs
+      .iter()
+      .map(|job| (job.template.clone(), Arc::clone(&job.provider)))
+      .collect();
+    let (shutdown, stopped) = mpsc::channel();
+
+    let thread_pages = Arc::clone(&pages);
+    std::thread::Builder::new()
+      .name("snapfire-scheduler".to_string())
+      .spawn(move || run(worker, jobs, timezone, thread_pages, stopped))
+      .expect("failed to spawn the scheduler thread");
+
+    Self {
+      pages,
+      providers,
+      _shutdown: shutdown,
+    }
+  }
+}
+
+impl fmt::Debug for Scheduler {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Scheduler")
+      .field("templates", &self.providers.keys().collect::<Vec<_>>())
+      .finish()
+  }
+}
+
+fn run(
+  worker: TeraWeb,
+  jobs: Vec<RerenderJob>,
+  timezone: Tz,
+  pages: Arc<RwLock<HashMap<String, Bytes>>>,
+  stopped: mpsc::Receiver<()>,
+) {
+  let render = |job: &RerenderJob| {
+    match worker.render_bytes_for_request(&job.template, RequestParts::default(), (job.provider)()) {
+      Ok(page) => {
+        log::debug!("Re-rendered scheduled page {}", job.template);
+        pages.write().insert(job.template.clone(), page);
+      }
+      // The previous render keeps being served.
+      Err(e) => log::error!("Scheduled render of {} failed: {}", job.template, e),
+    }
+  };
+
+  jobs.iter().for_each(render);
+
+  loop {
+    let now = Utc::now().with_timezone(&timezone);
+    let upcoming: Vec<_> = jobs
+      .iter()
+      .filter_map(|job| Some((job.schedule.after(&now).next()?, job)))
+      .collect();
+    let Some(next) = upcoming.iter().map(|(time, _)| *time).min() else {
+      return;
+    };
+
+    let wait = (next - Utc::now().with_timezone(&timezone)).to_std().unwrap_or_default();
+    match stopped.recv_timeout(wait) {
+      Err(RecvTimeoutError::Timeout) => {
+        for (_, job) in upcoming.iter().filter(|(time, _)| *time == next) {
+          render(job);
+        }
+      }
+      // The scheduler was dropped.
+      Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+    }
+  }
+}
+
+impl TeraWeb {
+  /// Serves the latest scheduled render of `tpl`.
+  ///
+  /// The page comes from the schedule set with `schedule_rerender`. Until its
+  /// first render completes (or if `tpl` has no schedule), the page is
+  /// rendered on demand like `render`, with the schedule's context when there
+  /// is one.
+  pub fn render_cached(&self, tpl: &str) -> Template {
+    let Some(scheduler) = &self.scheduler else {
+      return self.render(tpl, Context::new());
+    };
+
+    if let Some(page) = scheduler.pages.read().get(tpl).cloned() {
+      let mut template = self.render(tpl, Context::new());
+      template.prerendered = Some(page);
+      return template;
+    }
+
+    let context = scheduler.providers.get(tpl).map(|provider| provider()).unwrap_or_default();
+    self.render(tpl, context)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::time::Duration;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_rerenders_on_schedule() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("home.html"), "render {{ count }}").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let provider_counter = Arc::clone(&counter);
+    let app = TeraWeb::builder(&glob_path)
+      .schedule_timezone(chrono_tz::Europe::Paris)
+      .schedule_rerender("home.html", "* * * * * *", move || {
+        let mut context = Context::new();
+        context.insert("count", &provider_counter.fetch_add(1, Ordering::SeqCst));
+        context
+      })
+      .build()
+      .unwrap();
+
+    // The startup render, then at least one scheduled one a second later.
+    std::thread::sleep(Duration::from_millis(2200));
+    let template = app.render_cached("home.html");
+    let page = template.prerendered.expect("page was rendered");
+    assert!(counter.load(Ordering::SeqCst) >= 2);
+    assert_ne!(&page[..], b"render 0");
+  }
+
+  #[test]
+  fn test_rejects_invalid_expressions() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("home.html"), "").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+    let result = TeraWeb::builder(&glob_path)
+      .schedule_rerender("home.html", "every hour", Context::new)
+      .build();
+    assert!(matches!(result, Err(SnapFireError::Schedule { .. })));
+  }
+}
@@ -0,0 +1,275 @@
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// A declaration of the context a template expects.
+///
+/// Schemas are written in (a practical subset of) JSON Schema: `type`,
+/// `properties`, `required`, `items`, `enum`, `anyOf`/`oneOf`, and local
+/// `$ref`s into `$defs`/`definitions`. With the `schemars` feature, a schema
+/// can also be derived from a typed view model via [`ContextSchema::for_type`].
+///
+/// Schemas are registered per template with `TeraWebBuilder::context_schema`
+/// and checked before every render in debug builds, turning Tera's generic
+/// "variable not found" into precise errors like `missing field user.email`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextSchema {
+  schema: Value,
+}
+
+impl ContextSchema {
+  /// Creates a schema from a JSON Schema document.
+  pub fn from_json(schema: Value) -> Self {
+    Self { schema }
+  }
+
+  /// Derives a schema from a type implementing `schemars::JsonSchema`.
+  #[cfg(feature = "schemars")]
+  pub fn for_type<T: schemars::JsonSchema>() -> Self {
+    Self {
+      schema: schemars::schema_for!(T).to_value(),
+    }
+  }
+
+  /// The underlying JSON Schema document.
+  pub fn as_json(&self) -> &Value {
+    &self.schema
+  }
+
+  /// Checks `context` against the schema and returns every violation found.
+  pub fn validate(&self, context: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    self.check(&self.schema, context, "", &mut violations);
+    violations
+  }
+
+  fn check(&self, schema: &Value, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    let Some(schema) = schema.as_object() else {
+      // `true`/`false` boolean schemas: `false` matches nothing.
+      if schema == &Value::Bool(false) {
+        violations.push(SchemaViolation::new(path, "no value is allowed here"));
+      }
+      return;
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+      match self.resolve(reference) {
+        Some(target) => self.check(target, value, path, violations),
+        None => violations.push(SchemaViolation::new(path, format!("unresolvable schema reference `{reference}`"))),
+      }
+      return;
+    }
+
+    for keyword in ["anyOf", "oneOf"] {
+      if let Some(options) = schema.get(keyword).and_then(Value::as_array) {
+        let matches_one = options.iter().any(|option| {
+          let mut scratch = Vec::new();
+          self.check(option, value, path, &mut scratch);
+          scratch.is_empty()
+        });
+        if !matches_one {
+          violations.push(SchemaViolation::new(path, "does not match any of the allowed shapes"));
+        }
+      }
+    }
+
+    if let Some(expected) = schema.get("type")
+      && !type_matches(expected, value)
+    {
+      violations.push(SchemaViolation::new(
+        path,
+        format!("expected {}, found {}", describe_type(expected), type_name(value)),
+      ));
+      return;
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+      && !allowed.contains(value)
+    {
+      violations.push(SchemaViolation::new(path, format!("{value} is not one of the allowed values")));
+    }
+
+    if let Value::Object(fields) = value {
+      self.check_object(schema, fields, path, violations);
+    }
+
+    if let (Some(items), Value::Array(elements)) = (schema.get("items"), value) {
+      for (index, element) in elements.iter().enumerate() {
+        self.check(items, element, &format!("{path}[{index}]"), violations);
+      }
+    }
+  }
+
+  fn check_object(
+    &self,
+    schema: &Map<String, Value>,
+    fields: &Map<String, Value>,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+  ) {
+    for required in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+      if let Some(name) = required.as_str()
+        && !fields.contains_key(name)
+      {
+        violations.push(SchemaViolation::missing(&join_path(path, name)));
+      }
+    }
+
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+      for (name, property_schema) in properties {
+        if let Some(field) = fields.get(name) {
+          self.check(property_schema, field, &join_path(path, name), violations);
+        }
+      }
+    }
+  }
+
+  /// Resolves a local reference such as `#/$defs/User`.
+  fn resolve(&self, reference: &str) -> Option<&Value> {
+    self.schema.pointer(reference.strip_prefix('#')?)
+  }
+}
+
+/// A single way in which a context failed its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+  /// The dotted path of the offending value (e.g. `user.email`), empty for the root.
+  pub path: String,
+  /// What is wrong with it.
+  pub message: String,
+}
+
+impl SchemaViolation {
+  fn new(path: &str, message: impl Into<String>) -> Self {
+    Self {
+      path: path.to_string(),
+      message: message.into(),
+    }
+  }
+
+  fn missing(path: &str) -> Self {
+    Self::new(path, "missing field")
+  }
+}
+
+impl fmt::Display for SchemaViolation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match (self.message.as_str(), self.path.is_empty()) {
+      ("missing field", _) => write!(f, "missing field {}", self.path),
+      (message, true) => write!(f, "{}", message),
+      (message, false) => write!(f, "{}: {}", self.path, message),
+    }
+  }
+}
+
+fn join_path(parent: &str, name: &str) -> String {
+  if parent.is_empty() {
+    name.to_string()
+  } else {
+    format!("{parent}.{name}")
+  }
+}
+
+fn type_matches(expected: &Value, value: &Value) -> bool {
+  match expected {
+    Value::String(name) => single_type_matches(name, value),
+    Value::Array(names) => names.iter().filter_map(Value::as_str).any(|name| single_type_matches(name, value)),
+    _ => true,
+  }
+}
+
+fn single_type_matches(name: &str, value: &Value) -> bool {
+  match name {
+    "object" => value.is_object(),
+    "array" => value.is_array(),
+    "string" => value.is_string(),
+    "boolean" => value.is_boolean(),
+    "null" => value.is_null(),
+    "number" => value.is_number(),
+    "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+    _ => true,
+  }
+}
+
+fn describe_type(expected: &Value) -> String {
+  match expected {
+    Value::Array(names) => names.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" or "),
+    other => other.as_str().unwrap_or("a valid value").to_string(),
+  }
+}
+
+fn type_name(value: &Value) -> &'static str {
+  match value {
+    Value::Null => "null",
+    Value::Bool(_) => "boolean",
+    Value::Number(_) => "number",
+    Value::String(_) => "string",
+    Value::Array(_) => "array",
+    Value::Object(_) => "object",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn user_schema() -> ContextSchema {
+    ContextSchema::from_json(json!({
+      "type": "object",
+      "required": ["user"],
+      "properties": {
+        "user": { "$ref": "#/$defs/User" },
+      },
+      "$defs": {
+        "User": {
+          "type": "object",
+          "required": ["name", "email"],
+          "properties": {
+            "name": { "type": "string" },
+            "email": { "type": "string" },
+            "roles": { "type": "array", "items": { "enum": ["admin", "editor"] } },
+            "age": { "type": ["integer", "null"] },
+          },
+        },
+      },
+    }))
+  }
+
+  #[test]
+  fn test_valid_context_passes() {
+    let context = json!({ "user": { "name": "Alice", "email": "a@example.com", "roles": ["admin"], "age": null } });
+    assert!(user_schema().validate(&context).is_empty());
+  }
+
+  #[test]
+  fn test_reports_precise_paths() {
+    let context = json!({ "user": { "name": 42, "roles": ["admin", "root"] } });
+    let violations: Vec<String> = user_schema().validate(&context).iter().map(ToString::to_string).collect();
+
+    assert_eq!(
+      violations,
+      [
+        "missing field user.email",
+        "user.name: expected string, found number",
+        "user.roles[1]: \"root\" is not one of the allowed values",
+      ]
+    );
+  }
+
+  #[cfg(feature = "schemars")]
+  #[test]
+  fn test_schema_from_type() {
+    #[derive(schemars::JsonSchema)]
+    #[allow(dead_code)]
+    struct Page {
+      title: String,
+      tags: Vec<String>,
+      subtitle: Option<String>,
+    }
+
+    let schema = ContextSchema::for_type::<Page>();
+    assert!(schema.validate(&json!({ "title": "Hi", "tags": [], "subtitle": null })).is_empty());
+    let violations = schema.validate(&json!({ "tags": [1] }));
+    assert_eq!(violations[0].to_string(), "missing field title");
+  }
+}
@@ -0,0 +1,164 @@
+//! The render in progress, for filters and functions.
+//!
+//! Tera filters and functions only see their arguments, not the render
+//! context or the request. While SnapFire renders a page, the thread it
+//! renders on holds a [`RenderScope`] describing it: the template, the
+//! request's host, path, and id, the render environment, and any values
+//! middleware attached to the request. Custom filters read it with
+//! [`RenderScope::current`] instead of managing thread-locals of their own:
+//!
+//! ```rust
+//! use snapfire::core::scope::RenderScope;
+//! use std::collections::HashMap;
+//! use tera::Value;
+//!
+//! /// The user middleware stored in the request's `ScopeValues`.
+//! struct User {
+//!   name: String,
+//! }
+//!
+//! fn greet(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+//!   let name = RenderScope::current()
+//!     .and_then(|scope| scope.get::<User>().map(|user| user.name.clone()))
+//!     .unwrap_or_else(|| "stranger".to_string());
+//!   Ok(Value::String(format!("{}, {name}", value.as_str().unwrap_or("Hello"))))
+//! }
+//! ```
+//!
+//! To attach values, store a [`ScopeValues`] in the request extensions
+//! (e.g. from middleware), where the Actix `Template` responder picks it up.
+//! The scope is set for the duration of the synchronous render, so values
+//! are only visible to code running on the rendering thread.
+
+use crate::core::app::TemplateSet;
+use crate::core::env::RenderEnv;
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+thread_local! {
+  /// The render in progress on this thread.
+  static CURRENT_SCOPE: RefCell<Option<Arc<RenderScope>>> = const { RefCell::new(None) };
+}
+
+/// Values of any type, one per type, attached to a request for the filters
+/// and functions rendering it.
+#[derive(Clone, Default)]
+pub struct ScopeValues(HashMap<TypeId, Arc<dyn Any + Send + Sync>>);
+
+impl ScopeValues {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds `value`, replacing any earlier value of the same type.
+  pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+    self.0.insert(TypeId::of::<T>(), Arc::new(value));
+  }
+
+  /// The value of type `T`, if one was added.
+  pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+    self.0.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref())
+  }
+}
+
+impl std::fmt::Debug for ScopeValues {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ScopeValues").field("len", &self.0.len()).finish()
+  }
+}
+
+/// The page being rendered on this thread.
+#[derive(Debug, Clone, Default)]
+pub struct RenderScope {
+  pub(crate) template: String,
+  pub(crate) host: Option<String>,
+  pub(crate) path: Option<String>,
+  pub(crate) request_id: Option<String>,
+  pub(crate) env: RenderEnv,
+  pub(crate) values: ScopeValues,
+  /// The templates the page renders with, for functions rendering others.
+  pub(crate) templates: Option<Arc<TemplateSet>>,
+}
+
+impl RenderScope {
+  /// The render in progress on this thread, if any.
+  pub fn current() -> Option<Arc<RenderScope>> {
+    CURRENT_SCOPE.with(|current| current.borrow().clone())
+  }
+
+  /// The name of the template being rendered.
+  pub fn template(&self) -> &str {
+    &self.template
+  }
+
+  /// The request's host, normalized (lowercase, without a default port).
+  pub fn host(&self) -> Option<&str> {
+    self.host.as_deref()
+  }
+
+  /// The request path, e.g. `/docs/intro`.
+  pub fn path(&self) -> Option<&str> {
+    self.path.as_deref()
+  }
+
+  /// The id the `RequestIds` middleware gave the request, to tag logs with.
+  pub fn request_id(&self) -> Option<&str> {
+    self.request_id.as_deref()
+  }
+
+  /// The render environment, with a `locale` the page sets itself applied.
+  pub fn env(&self) -> &RenderEnv {
+    &self.env
+  }
+
+  /// The page's locale, if one is known.
+  pub fn locale(&self) -> Option<&str> {
+    self.env.locale.as_deref()
+  }
+
+  /// The request's value of type `T`, from its [`ScopeValues`].
+  pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+    self.values.get()
+  }
+}
+
+/// Runs `f` with `scope` as the current render.
+pub(crate) fn with_scope<R>(scope: RenderScope, f: impl FnOnce() -> R) -> R {
+  /// Restores the outer scope, even if the render panics.
+  struct Restore(Option<Arc<RenderScope>>);
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      let outer = self.0.take();
+      CURRENT_SCOPE.with(|current| *current.borrow_mut() = outer);
+    }
+  }
+
+  let outer = CURRENT_SCOPE.with(|current| current.replace(Some(Arc::new(scope))));
+  let _restore = Restore(outer);
+  f()
+}
+
+/// Runs `f` with a scope holding only `locale`.
+#[cfg(test)]
+pub(crate) fn with_locale<R>(locale: Option<&str>, f: impl FnOnce() -> R) -> R {
+  let scope = RenderScope {
+    env: RenderEnv {
+      locale: locale.map(str::to_string),
+      ..RenderEnv::default()
+    },
+    ..RenderScope::default()
+  };
+  with_scope(scope, f)
+}
+
+/// The path of the request currently being rendered on this thread, if any.
+pub(crate) fn current_path() -> Option<String> {
+  CURRENT_SCOPE.with(|current| current.borrow().as_ref().and_then(|scope| scope.path.clone()))
+}
+
+/// The locale of the page currently being rendered on this thread, if any.
+pub(crate) fn current_locale() -> Option<String> {
+  CURRENT_SCOPE.with(|current| current.borrow().as_ref().and_then(|scope| scope.env.locale.clone()))
+}
@@ -0,0 +1,177 @@
+//! Capturing rendered pages as PNG images.
+//!
+//! `TeraWeb::render_screenshot` renders a template like any page, then
+//! captures it at a [`Viewport`] with the app's [`ScreenshotBackend`], which
+//! is how OpenGraph preview images and thumbnails can be generated from the
+//! same templates, styles, and data as the site. The backend is set with
+//! `TeraWebBuilder::screenshot_backend`; by default it is [`Chromium`] in
+//! headless mode, and any other capture service can implement the trait:
+//!
+//! ```rust,no_run
+//! # use snapfire::core::screenshot::{Chromium, Viewport};
+//! # use tera::Context;
+//! # fn main() -> snapfire::Result<()> {
+//! let app = snapfire::TeraWeb::builder("templates/**/*")
+//!   .screenshot_backend(Chromium::new().binary("google-chrome"))
+//!   .build()?;
+//! let viewport = Viewport::open_graph().base_url("https://example.com/");
+//! let png = app.render_screenshot("og/post.html", Context::new(), &viewport)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Capturing runs an external process, so Actix handlers should call
+//! `render_screenshot` inside `web::block`; the resulting [`Screenshot`] is a
+//! responder, served as `image/png`.
+
+use crate::core::headless;
+use crate::error::{Result, SnapFireError};
+use bytes::Bytes;
+use std::path::PathBuf;
+
+/// Captures rendered HTML as a PNG image.
+pub trait ScreenshotBackend: Send + Sync {
+  /// A short name for error messages, e.g. `chromium`.
+  fn name(&self) -> &str;
+
+  /// Captures the complete HTML document `html` at `viewport`, returning
+  /// the PNG bytes.
+  fn capture(&self, html: &str, viewport: &Viewport) -> Result<Vec<u8>>;
+}
+
+impl std::fmt::Debug for dyn ScreenshotBackend {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("ScreenshotBackend").field(&self.name()).finish()
+  }
+}
+
+/// The size of the captured area, for `TeraWeb::render_screenshot`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+  /// The width in CSS pixels.
+  pub width: u32,
+  /// The height in CSS pixels.
+  pub height: u32,
+  /// Image pixels per CSS pixel; `2.0` captures at twice the size, for
+  /// high-density displays.
+  pub device_scale: f32,
+  base_url: Option<String>,
+}
+
+impl Viewport {
+  /// A `width` by `height` viewport at a device scale of 1.
+  pub fn new(width: u32, height: u32) -> Self {
+    Self {
+      width,
+      height,
+      device_scale: 1.0,
+      base_url: None,
+    }
+  }
+
+  /// The 1200x630 size recommended for OpenGraph and Twitter card images.
+  pub fn open_graph() -> Self {
+    Self::new(1200, 630)
+  }
+
+  /// Captures at `scale` image pixels per CSS pixel.
+  pub fn device_scale(mut self, scale: f32) -> Self {
+    self.device_scale = scale;
+    self
+  }
+
+  /// The URL relative links, images, and stylesheets resolve against, since
+  /// the browser doesn't know where the page would have been served.
+  pub fn base_url(mut self, url: &str) -> Self {
+    self.base_url = Some(url.to_string());
+    self
+  }
+
+  /// `html` with the `<base>` element for this viewport, if any.
+  pub(crate) fn apply(&self, html: &str) -> String {
+    let head = self.base_url.as_deref().map(headless::base_element).unwrap_or_default();
+    headless::insert_in_head(html, &head)
+  }
+}
+
+/// A captured page, served by Actix as `image/png`.
+#[derive(Debug, Clone)]
+pub struct Screenshot {
+  pub(crate) bytes: Bytes,
+}
+
+impl Screenshot {
+  /// The PNG image.
+  pub fn bytes(&self) -> &Bytes {
+    &self.bytes
+  }
+
+  /// The PNG image, e.g. for writing to a file or uploading to a CDN.
+  pub fn into_bytes(self) -> Bytes {
+    self.bytes
+  }
+}
+
+/// Captures with Chromium (or Chrome) in headless mode, without scrollbars.
+#[derive(Debug, Clone)]
+pub struct Chromium {
+  binary: PathBuf,
+}
+
+impl Default for Chromium {
+  fn default() -> Self {
+    Self {
+      binary: PathBuf::from("chromium"),
+    }
+  }
+}
+
+impl Chromium {
+  /// Runs the `chromium` found on the `PATH`.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The browser executable to run, e.g. `google-chrome`.
+  pub fn binary<P: Into<PathBuf>>(mut self, binary: P) -> Self {
+    self.binary = binary.into();
+    self
+  }
+}
+
+impl ScreenshotBackend for Chromium {
+  fn name(&self) -> &str {
+    "chromium"
+  }
+
+  fn capture(&self, html: &str, viewport: &Viewport) -> Result<Vec<u8>> {
+    let args = [
+      "--hide-scrollbars".to_string(),
+      format!("--window-size={},{}", viewport.width, viewport.height),
+      format!("--force-device-scale-factor={}", viewport.device_scale),
+    ];
+    headless::run_chromium(&self.binary, &args, "--screenshot", "png", html).map_err(|message| SnapFireError::Screenshot {
+      backend: self.name().to_string(),
+      message,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_viewport_sets_base_and_missing_browser_is_reported() {
+    let viewport = Viewport::open_graph().device_scale(2.0).base_url("https://example.com/");
+    assert_eq!((viewport.width, viewport.height), (1200, 630));
+    assert_eq!(
+      viewport.apply("<html><head><title>x</title></head></html>"),
+      "<html><head><base href=\"https://example.com/\"><title>x</title></head></html>"
+    );
+    assert_eq!(Viewport::new(10, 10).apply("<p>x</p>"), "<p>x</p>");
+
+    let error = Chromium::new().binary("/nonexistent/chromium").capture("<p>x</p>", &viewport).unwrap_err();
+    assert!(matches!(error, SnapFireError::Screenshot { ref backend, .. } if backend == "chromium"), "{error}");
+  }
+}
@@ -0,0 +1,240 @@
+//! Client-side search index generation.
+//!
+//! The pages registered with `TeraWebBuilder::search_index` are rendered
+//! through the normal render path, reduced to their title and visible text,
+//! and served as a JSON array of `{ url, title, body }` documents. That is the
+//! shape lunr.js and similar client-side engines index directly:
+//!
+//! ```js
+//! const docs = await (await fetch(searchIndexUrl)).json();
+//! const idx = lunr(function () {
+//!   this.ref("url"); this.field("title"); this.field("body");
+//!   docs.forEach((doc) => this.add(doc));
+//! });
+//! ```
+
+use crate::core::app::{RequestParts, TeraWeb};
+use crate::error::{Result, SnapFireError};
+use bytes::Bytes;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::HashMap;
+use tera::{Context, Function, Value};
+
+/// The default URL the index is served at.
+pub(crate) const DEFAULT_INDEX_PATH: &str = "/_snapfire/search-index.json";
+
+/// A page to include in the search index.
+#[derive(Debug, Clone)]
+pub struct SearchPage {
+  pub(crate) url: String,
+  pub(crate) template: String,
+  pub(crate) context: Context,
+}
+
+impl SearchPage {
+  /// A page served at `url` and rendered from `template`.
+  pub fn new<U: Into<String>, T: Into<String>>(url: U, template: T) -> Self {
+    Self {
+      url: url.into(),
+      template: template.into(),
+      context: Context::new(),
+    }
+  }
+
+  /// Sets the context the page is rendered with.
+  pub fn context(mut self, context: Context) -> Self {
+    self.context = context;
+    self
+  }
+}
+
+/// One indexed page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SearchDocument {
+  /// The page URL, used as the document reference.
+  pub url: String,
+  /// The page `<title>`, or its first `<h1>`.
+  pub title: String,
+  /// The visible text of the page, whitespace-collapsed.
+  pub body: String,
+}
+
+impl SearchDocument {
+  /// Extracts a document from rendered HTML.
+  pub fn from_html(url: &str, html: &str) -> Self {
+    let title = element_text(html, "title")
+      .or_else(|| element_text(html, "h1"))
+      .unwrap_or_default();
+    // The body text shouldn't repeat the `<title>`, which lives in `<head>`.
+    let body = match html.find("<body") {
+      Some(start) => &html[start..],
+      None => html,
+    };
+
+    Self {
+      url: url.to_string(),
+      title,
+      body: extract_text(body),
+    }
+  }
+}
+
+/// The configured pages and the cached, serialized index.
+#[derive(Debug)]
+pub(crate) struct SearchIndex {
+  /// Where the Actix routes serve the index.
+  #[cfg(feature = "actix")]
+  pub(crate) path: String,
+  pub(crate) pages: Vec<SearchPage>,
+  pub(crate) cached: RwLock<Option<Bytes>>,
+}
+
+impl TeraWeb {
+  /// Renders every page registered with `TeraWebBuilder::search_index` and
+  /// extracts its search document. Returns an empty list if none are.
+  pub fn search_documents(&self) -> Result<Vec<SearchDocument>> {
+    let Some(search) = &self.search else {
+      return Ok(Vec::new());
+    };
+
+    search
+      .pages
+      .iter()
+      .map(|page| {
+        let request = RequestParts {
+          path: Some(&page.url),
+          ..RequestParts::default()
+        };
+        let html = self.render_for_request(&page.template, request, page.context.clone())?;
+        Ok(SearchDocument::from_html(&page.url, &html))
+      })
+      .collect()
+  }
+
+  /// The search index as JSON, built on first use and cached afterwards.
+  ///
+  /// In dev mode the index is rebuilt on every call, so it follows template
+  /// edits.
+  pub fn search_index_json(&self) -> Result<Bytes> {
+    if !cfg!(feature = "devel")
+      && let Some(search) = &self.search
+      && let Some(json) = search.cached.read().clone()
+    {
+      return Ok(json);
+    }
+    self.refresh_search_index()
+  }
+
+  /// Rebuilds the search index now and returns it.
+  ///
+  /// Call this on a schedule (or after publishing content) when the indexed
+  /// pages depend on data that changes at runtime.
+  pub fn refresh_search_index(&self) -> Result<Bytes> {
+    let documents = self.search_documents()?;
+    let json = Bytes::from(serde_json::to_vec(&documents).map_err(|e| SnapFireError::Serialization(e.to_string()))?);
+    if let Some(search) = &self.search {
+      *search.cached.write() = Some(json.clone());
+    }
+    Ok(json)
+  }
+}
+
+/// The `search_index_url()` Tera function.
+pub(crate) struct SearchIndexUrl(pub(crate) String);
+
+impl Function for SearchIndexUrl {
+  fn call(&self, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::from(self.0.as_str()))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+/// The text content of the first `<tag>` element, if any.
+fn element_text(html: &str, tag: &str) -> Option<String> {
+  let open = find_tag(html, tag)?;
+  let content_start = open + html[open..].find('>')? + 1;
+  let content_end = content_start + html[content_start..].find(&format!("</{tag}"))?;
+  let text = extract_text(&html[content_start..content_end]);
+  (!text.is_empty()).then_some(text)
+}
+
+/// Finds `<tag` followed by `>` or whitespace, so `<h1` doesn't match `<h10`.
+fn find_tag(html: &str, tag: &str) -> Option<usize> {
+  let needle = format!("<{tag}");
+  let mut offset = 0;
+  while let Some(index) = html[offset..].find(&needle) {
+    let start = offset + index;
+    match html[start + needle.len()..].chars().next() {
+      Some(c) if c == '>' || c.is_whitespace() => return Some(start),
+      _ => offset = start + needle.len(),
+    }
+  }
+  None
+}
+
+/// Strips markup from `html`, dropping `<script>`/`<style>` contents, decoding
+/// the common entities, and collapsing whitespace.
+fn extract_text(html: &str) -> String {
+  let mut text = String::with_capacity(html.len() / 2);
+  let mut rest = html;
+
+  while let Some(open) = rest.find('<') {
+    text.push_str(&rest[..open]);
+    text.push(' ');
+    rest = &rest[open..];
+
+    let skipped = ["script", "style"].into_iter().find(|tag| {
+      rest[1..]
+        .get(..tag.len())
+        .is_some_and(|name| name.eq_ignore_ascii_case(tag))
+    });
+    let end = match skipped {
+      Some(tag) => rest.find(&format!("</{tag}")).and_then(|close| rest[close..].find('>').map(|gt| close + gt)),
+      None => rest.find('>'),
+    };
+    match end {
+      Some(end) => rest = &rest[end + 1..],
+      None => {
+        rest = "";
+        break;
+      }
+    }
+  }
+  text.push_str(rest);
+
+  let decoded = text
+    .replace("&nbsp;", " ")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&#x27;", "'")
+    .replace("&#39;", "'")
+    .replace("&#x2F;", "/")
+    .replace("&amp;", "&");
+  decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_extracts_title_and_visible_text() {
+    let html = "<html><head><title>Docs &amp; Guides</title><style>body { color: red }</style></head>\
+                <body><h1>Welcome</h1><script>var x = '<p>';</script><p>Fast   <b>templates</b>\n&lt;3</p></body></html>";
+    let document = SearchDocument::from_html("/docs", html);
+
+    assert_eq!(document.title, "Docs & Guides");
+    assert_eq!(document.body, "Welcome Fast templates <3");
+  }
+
+  #[test]
+  fn test_falls_back_to_first_heading() {
+    let document = SearchDocument::from_html("/", "<h10>x</h10><h1 class=\"big\">Home</h1>");
+    assert_eq!(document.title, "Home");
+  }
+}
@@ -0,0 +1,79 @@
+//! Context values that render but never show up in diagnostics.
+//!
+//! A value inserted with `Template::insert_sensitive` (or
+//! `SnapFireContextExt::insert_sensitive` from middleware) renders like any
+//! other, but SnapFire's debugging output leaves it out: the context summary
+//! of error reports shows `[scrubbed]` in its place, and a render error whose
+//! message quotes it (a filter rejecting a malformed date of birth, say) has
+//! the value replaced by `[scrubbed]` before the error is returned, reported,
+//! or logged. This keeps error reporting on in staging without leaking
+//! personal data:
+//!
+//! ```rust,no_run
+//! # use snapfire::TeraWeb;
+//! # use tera::Context;
+//! # let app = TeraWeb::builder("templates/**/*.html").build().unwrap();
+//! let page = app
+//!   .render("profile.html", Context::new())
+//!   .insert_sensitive("email", "ada@example.com");
+//! ```
+//!
+//! Marking a key covers everything under it, including nested values. The
+//! sensitive keys travel beside the context, with the request, rather than
+//! in it, so templates, validators and audits never see them.
+
+use serde_json::Value;
+use tera::Context;
+
+/// What sensitive values are replaced with.
+pub(crate) const SCRUBBED: &str = "[scrubbed]";
+
+/// The shortest string that is scrubbed from messages, so a value like `1`
+/// doesn't blank every digit of an error.
+const MIN_SCRUBBED_LEN: usize = 3;
+
+/// Replaces every value of `context` under one of the `sensitive` keys
+/// found in `message`, or returns `None` if there is none.
+pub(crate) fn scrub(context: &Context, sensitive: &[String], message: &str) -> Option<String> {
+  let mut secrets = Vec::new();
+  for value in sensitive.iter().filter_map(|key| context.get(key)) {
+    collect_strings(value, &mut secrets);
+  }
+  // Longest first, so a value containing another is replaced whole.
+  secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+  let mut scrubbed = message.to_string();
+  for secret in secrets.iter().filter(|secret| secret.len() >= MIN_SCRUBBED_LEN) {
+    scrubbed = scrubbed.replace(secret.as_str(), SCRUBBED);
+  }
+  (scrubbed != message).then_some(scrubbed)
+}
+
+fn collect_strings(value: &Value, strings: &mut Vec<String>) {
+  match value {
+    Value::String(string) => strings.push(string.clone()),
+    Value::Number(number) => strings.push(number.to_string()),
+    Value::Array(items) => items.iter().for_each(|item| collect_strings(item, strings)),
+    Value::Object(map) => map.values().for_each(|item| collect_strings(item, strings)),
+    Value::Bool(_) | Value::Null => {}
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_scrubs_sensitive_values_from_messages() {
+    let mut context = Context::new();
+    context.insert("name", "Ada");
+    context.insert("user", &serde_json::json!({"email": "ada@example.com", "born": "1815-12-10", "id": 7}));
+    let sensitive = ["user".to_string(), "missing".to_string()];
+
+    let message = "Filter `date` failed for `1815-12-10` (ada@example.com, Ada, 7)";
+    assert_eq!(
+      scrub(&context, &sensitive, message).unwrap(),
+      "Filter `date` failed for `[scrubbed]` ([scrubbed], Ada, 7)"
+    );
+    assert_eq!(scrub(&context, &sensitive, "nothing to hide"), None);
+  }
+}
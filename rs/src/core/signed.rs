@@ -0,0 +1,217 @@
+//! Time-limited signed URLs for protected files.
+//!
+//! A [`UrlSigner`] given to `TeraWebBuilder::url_signer` registers the
+//! `signed_asset` template function, which links to a file with an expiry
+//! time and an HMAC-SHA256 signature in the query string:
+//!
+//! ```jinja
+//! <a href="{{ signed_asset(path="/downloads/report.pdf", ttl=3600) }}">Report</a>
+//! {# /downloads/report.pdf?expires=1767225600&signature=9c1f… #}
+//! ```
+//!
+//! The `SignedUrls` Actix middleware, wrapped around the protected routes
+//! with the same signer, rejects requests whose signature doesn't match the
+//! path or has expired, so the files need no handler code of their own.
+//! Only the path and expiry are signed; other query parameters are not.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tera::{Function, Value};
+
+/// How many bytes of the HMAC go into the URL; 128 bits can't be guessed.
+const SIGNATURE_BYTES: usize = 16;
+
+/// Why a signed URL was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+  /// The `expires` or `signature` parameter is missing or malformed.
+  Missing,
+  /// The signature doesn't match the path and expiry.
+  Invalid,
+  /// The URL was valid, but its time is up.
+  Expired,
+}
+
+impl fmt::Display for SignatureError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Self::Missing => "the URL is not signed",
+      Self::Invalid => "the URL's signature is invalid",
+      Self::Expired => "the URL has expired",
+    })
+  }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// Signs and verifies URLs with a secret key.
+///
+/// Clones share the key. Use a long random key kept out of the repository,
+/// and the same key on every server; changing it invalidates every link.
+#[derive(Clone)]
+pub struct UrlSigner {
+  key: Arc<[u8]>,
+}
+
+impl fmt::Debug for UrlSigner {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("UrlSigner").finish_non_exhaustive()
+  }
+}
+
+impl UrlSigner {
+  /// A signer using `key`, which may be any length.
+  pub fn new(key: impl AsRef<[u8]>) -> Self {
+    Self {
+      key: Arc::from(key.as_ref()),
+    }
+  }
+
+  /// `path` with a signature valid for `ttl` from now, or `None` if `ttl`
+  /// reaches past the latest time the system clock can represent.
+  ///
+  /// `path` is the URL path as requested, percent-encoded where needed
+  /// (e.g. `/downloads/annual%20report.pdf`).
+  pub fn sign(&self, path: &str, ttl: Duration) -> Option<String> {
+    let expires = SystemTime::now().checked_add(ttl)?;
+    Some(self.sign_until(path, unix_time(expires)))
+  }
+
+  /// `path` with a signature valid until `expires`, in seconds since the
+  /// Unix epoch.
+  pub(crate) fn sign_until(&self, path: &str, expires: u64) -> String {
+    let mut url = format!("{path}?expires={expires}&signature=");
+    for byte in &self.mac(path, expires).finalize().into_bytes()[..SIGNATURE_BYTES] {
+      let _ = write!(url, "{byte:02x}");
+    }
+    url
+  }
+
+  /// Checks the signature in `query` (the URL's query string) for `path`.
+  pub fn verify(&self, path: &str, query: &str) -> Result<(), SignatureError> {
+    self.verify_at(path, query, unix_time(SystemTime::now()))
+  }
+
+  fn verify_at(&self, path: &str, query: &str, now: u64) -> Result<(), SignatureError> {
+    let parameter = |name: &str| {
+      query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')))
+    };
+    let expires = parameter("expires").and_then(|expires| expires.parse::<u64>().ok());
+    let signature = parameter("signature").and_then(decode_hex);
+    let (Some(expires), Some(signature)) = (expires, signature) else {
+      return Err(SignatureError::Missing);
+    };
+    if signature.len() != SIGNATURE_BYTES {
+      return Err(SignatureError::Invalid);
+    }
+    // Compared in constant time, so the signature can't be found byte by byte.
+    self
+      .mac(path, expires)
+      .verify_truncated_left(&signature)
+      .map_err(|_| SignatureError::Invalid)?;
+    if expires < now {
+      return Err(SignatureError::Expired);
+    }
+    Ok(())
+  }
+
+  fn mac(&self, path: &str, expires: u64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+    mac.update(path.as_bytes());
+    mac.update(b"\n");
+    mac.update(expires.to_string().as_bytes());
+    mac
+  }
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+  time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+  if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+    return None;
+  }
+  (0..hex.len())
+    .step_by(2)
+    .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+    .collect()
+}
+
+/// The `signed_asset(path=..., ttl=...)` Tera function.
+pub(crate) struct SignedAssetFunction(pub(crate) UrlSigner);
+
+impl Function for SignedAssetFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let path = args.get("path").and_then(Value::as_str).ok_or_else(|| {
+      tera::Error::msg("The `signed_asset` function requires a `path` string, e.g. signed_asset(path=\"/downloads/report.pdf\", ttl=3600)")
+    })?;
+    if !path.starts_with('/') || path.contains(['?', '#']) {
+      return Err(tera::Error::msg(format!(
+        "The `path` of `signed_asset` must be a site path without a query, got `{path}`"
+      )));
+    }
+    let ttl = args
+      .get("ttl")
+      .and_then(Value::as_u64)
+      .ok_or_else(|| tera::Error::msg("The `signed_asset` function requires a `ttl` in seconds, e.g. ttl=3600"))?;
+    let url = self
+      .0
+      .sign(path, Duration::from_secs(ttl))
+      .ok_or_else(|| tera::Error::msg(format!("The `ttl` of `signed_asset` is too far in the future, got {ttl}")))?;
+    Ok(Value::String(crate::core::i18n::escape_attribute(&url)))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_signed_urls_verify_until_they_expire() {
+    let signer = UrlSigner::new("a long random key");
+    let url = signer.sign_until("/downloads/report.pdf", 1_000);
+    let (path, query) = url.split_once('?').unwrap();
+    assert_eq!(path, "/downloads/report.pdf");
+    assert!(query.starts_with("expires=1000&signature="));
+
+    assert_eq!(signer.verify_at(path, query, 999), Ok(()));
+    assert_eq!(signer.verify_at(path, &format!("download=1&{query}"), 1_000), Ok(()));
+    assert_eq!(signer.verify_at(path, query, 1_001), Err(SignatureError::Expired));
+    assert_eq!(signer.verify_at("/downloads/other.pdf", query, 999), Err(SignatureError::Invalid));
+    let extended = query.replace("expires=1000", "expires=9999");
+    assert_eq!(signer.verify_at(path, &extended, 999), Err(SignatureError::Invalid));
+    assert_eq!(UrlSigner::new("another key").verify_at(path, query, 999), Err(SignatureError::Invalid));
+    assert_eq!(signer.verify_at(path, "expires=1000", 999), Err(SignatureError::Missing));
+    assert_eq!(signer.verify_at(path, "", 999), Err(SignatureError::Missing));
+
+    let function = SignedAssetFunction(signer.clone());
+    let args = HashMap::from([
+      ("path".to_string(), Value::from("/downloads/report.pdf")),
+      ("ttl".to_string(), Value::from(60)),
+    ]);
+    let url = function.call(&args).unwrap();
+    let (path, query) = url.as_str().unwrap().split_once('?').unwrap();
+    assert_eq!(signer.verify(path, &query.replace("&amp;", "&")), Ok(()));
+    let relative = HashMap::from([
+      ("path".to_string(), Value::from("report.pdf")),
+      ("ttl".to_string(), Value::from(60)),
+    ]);
+    assert!(function.call(&relative).is_err());
+    let forever = HashMap::from([
+      ("path".to_string(), Value::from("/downloads/report.pdf")),
+      ("ttl".to_string(), Value::from(u64::MAX)),
+    ]);
+    assert!(function.call(&forever).is_err());
+  }
+}
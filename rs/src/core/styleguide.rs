@@ -0,0 +1,145 @@
+//! A live gallery of the component templates, served in dev mode.
+//!
+//! `TeraWebBuilder::styleguide("components", [...])` serves every template
+//! under `components/` at `/_snapfire/styleguide`, each rendered with its
+//! example contexts:
+//!
+//! - each of its fixtures (`button.html.fixtures/*.json`, see
+//!   [`crate::core::fixtures`]), titled by name;
+//! - otherwise, the `.json` file next to it (`button.json` for
+//!   `button.html`): an object is one example, an array of objects one
+//!   example per element;
+//! - without either, an empty context.
+//!
+//! Templates whose file name starts with `_` are skipped. A component that
+//! fails to render shows its error in place, so one broken component doesn't
+//! hide the others. Editing a component or its fixture reloads the gallery
+//! like any other page, when `InjectSnapFireScript` wraps the app.
+
+use crate::core::app::TeraWeb;
+use crate::core::fixtures;
+use serde_json::Value;
+use std::fmt::Write as _;
+use tera::Context;
+
+/// Where the styleguide is served.
+pub(crate) const STYLEGUIDE_PATH: &str = "/_snapfire/styleguide";
+
+/// The styleguide settings from the builder.
+#[derive(Debug, Clone)]
+pub(crate) struct StyleguideOptions {
+  /// The directory of the components, relative to the template root.
+  pub(crate) dir: String,
+  /// The stylesheets the gallery links, so components look as on the site.
+  pub(crate) stylesheets: Vec<String>,
+}
+
+impl TeraWeb {
+  /// The styleguide page: every component, rendered with each example.
+  pub(crate) fn render_styleguide(&self, options: &StyleguideOptions) -> String {
+    let set = self.tera.read().clone();
+    let prefix = format!("{}/", options.dir.trim_end_matches('/'));
+    let mut names: Vec<&str> = set
+      .get_template_names()
+      .filter(|name| name.starts_with(&prefix) && !name.rsplit('/').next().unwrap_or_default().starts_with('_'))
+      // Fixtures are loaded as templates too by globs like `templates/**/*`.
+      .filter(|name| !name.ends_with(".json") && !name.contains(".fixtures/"))
+      .collect();
+    names.sort_unstable();
+
+    let mut html = String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Styleguide</title>");
+    for stylesheet in &options.stylesheets {
+      let _ = write!(html, "<link rel=\"stylesheet\" href=\"{}\">", tera::escape_html(stylesheet));
+    }
+    html.push_str(concat!(
+      "<style>.snapfire-styleguide>nav{font:14px sans-serif;margin-bottom:2rem}",
+      ".snapfire-component{margin-bottom:3rem}.snapfire-component>h2,.snapfire-example>h3{font:600 14px sans-serif;color:#555}",
+      ".snapfire-example{border:1px dashed #ccc;padding:1rem;margin:.5rem 0}",
+      ".snapfire-error{color:#b00020;white-space:pre-wrap}</style></head><body class=\"snapfire-styleguide\">"
+    ));
+    if names.is_empty() {
+      let _ = write!(html, "<p>No templates under <code>{}</code>.</p>", tera::escape_html(&prefix));
+    }
+
+    html.push_str("<nav><ul>");
+    for name in &names {
+      let name = tera::escape_html(name);
+      let _ = write!(html, "<li><a href=\"#{name}\">{name}</a></li>");
+    }
+    html.push_str("</ul></nav>");
+
+    for name in names {
+      let escaped = tera::escape_html(name);
+      let _ = write!(html, "<section class=\"snapfire-component\" id=\"{escaped}\"><h2>{escaped}</h2>");
+      let path = set.get_template(name).ok().and_then(|template| template.path.clone());
+      for (title, context) in examples(path.as_deref()) {
+        let _ = write!(html, "<div class=\"snapfire-example\"><h3>{}</h3>", tera::escape_html(&title));
+        let output = context.and_then(|context| self.render_to_string(name, context).map_err(|e| error_chain(&e)));
+        match output {
+          Ok(output) => html.push_str(&output),
+          Err(message) => {
+            let _ = write!(html, "<pre class=\"snapfire-error\">{}</pre>", tera::escape_html(&message));
+          }
+        }
+        html.push_str("</div>");
+      }
+      html.push_str("</section>");
+    }
+    html.push_str("</body></html>");
+    html
+  }
+}
+
+/// The titled example contexts of the template at `path`, or why one
+/// can't be read.
+fn examples(path: Option<&str>) -> Vec<(String, Result<Context, String>)> {
+  let default = || vec![("Default".to_string(), Ok(Context::new()))];
+  let Some(path) = path else {
+    return default();
+  };
+  let dir = fixtures::fixture_dir(path);
+  let names = fixtures::names(&dir);
+  if !names.is_empty() {
+    return names
+      .into_iter()
+      .map(|name| {
+        let context = fixtures::load(&dir.join(format!("{name}.json")));
+        (name, context)
+      })
+      .collect();
+  }
+
+  let fixture = std::path::Path::new(path).with_extension("json");
+  let source = match std::fs::read_to_string(&fixture) {
+    Ok(source) => source,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return default(),
+    Err(e) => return vec![("Default".to_string(), Err(format!("Failed to read {}: {}", fixture.display(), e)))],
+  };
+  let invalid = |message: String| format!("Invalid fixture {}: {}", fixture.display(), message);
+  let values = match serde_json::from_str(&source) {
+    Ok(Value::Array(values)) => values,
+    Ok(value) => vec![value],
+    Err(e) => return vec![("Default".to_string(), Err(invalid(e.to_string())))],
+  };
+  let count = values.len();
+  values
+    .into_iter()
+    .enumerate()
+    .map(|(index, value)| {
+      let title = if count == 1 { "Default".to_string() } else { format!("Example {}", index + 1) };
+      (title, fixtures::to_context(value).map_err(invalid))
+    })
+    .collect()
+}
+
+/// Flattens an error and its sources, one per line, since Tera puts the
+/// useful part in the source.
+fn error_chain(error: &dyn std::error::Error) -> String {
+  let mut message = error.to_string();
+  let mut source = error.source();
+  while let Some(cause) = source {
+    let _ = write!(message, "\n{cause}");
+    source = cause.source();
+  }
+  message
+}
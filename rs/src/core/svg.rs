@@ -0,0 +1,439 @@
+//! Inline SVG icons from the static directory.
+//!
+//! The `svg` template function, registered when `TeraWebBuilder::static_dir`
+//! is set, inlines an SVG file into the page, so icons take no extra request
+//! and can be styled with CSS:
+//!
+//! ```jinja
+//! <button>{{ svg(path="icons/check.svg", class="icon", size=16) }} Save</button>
+//! {{ svg(path="logo.svg", title="Acme", width=120, current_color=true) }}
+//! ```
+//!
+//! The file is sanitized before it is inlined: `script` and `foreignObject`
+//! elements, event handler attributes (`onclick`, ...), and links to other
+//! than `http`, `https`, relative, or fragment URLs are dropped, as are the
+//! XML declaration, doctype, and comments. The arguments set attributes of
+//! the root `<svg>`:
+//!
+//! - `class` is added to the file's own classes;
+//! - `width` and `height`, or `size` for both, replace the file's;
+//! - `title` labels the image (`role="img"` and `aria-label`); without it
+//!   the icon is hidden from assistive technology (`aria-hidden="true"`);
+//! - `current_color=true` turns every `fill` and `stroke` color into
+//!   `currentColor`, so the icon takes the color of the surrounding text.
+//!
+//! Files are read once and cached; in dev mode a changed file is read again
+//! and the page reloads.
+
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tera::{Function, Value};
+
+/// Elements dropped with their contents.
+const DROPPED_ELEMENTS: &[&str] = &["script", "foreignobject"];
+
+/// A piece of a sanitized SVG document.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+  Open {
+    name: String,
+    attributes: Vec<(String, String)>,
+    is_empty: bool,
+  },
+  Close(String),
+  /// Text or CDATA, as written in the file.
+  Text(String),
+}
+
+/// A sanitized SVG document.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Svg {
+  nodes: Vec<Node>,
+}
+
+/// The attributes the `svg` function sets on the root element.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SvgAttributes<'a> {
+  pub(crate) class: Option<&'a str>,
+  pub(crate) width: Option<String>,
+  pub(crate) height: Option<String>,
+  pub(crate) title: Option<&'a str>,
+  pub(crate) current_color: bool,
+}
+
+impl Svg {
+  /// Parses and sanitizes `source`, which must have an `<svg>` root.
+  pub(crate) fn parse(source: &str) -> Result<Self, String> {
+    let mut nodes = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find('<') {
+      push_text(&mut nodes, &rest[..start]);
+      let from = &rest[start..];
+      let end = if from.starts_with("<![CDATA[") {
+        let end = from.find("]]>").map_or(from.len(), |end| end + 3);
+        push_text(&mut nodes, &from[..end]);
+        end
+      } else if let Some(body) = from.strip_prefix("<!--") {
+        body.find("-->").map_or(from.len(), |end| end + 7)
+      } else if from.starts_with("<?") || from.starts_with("<!") {
+        from.find('>').map_or(from.len(), |end| end + 1)
+      } else if let Some(closing) = from.strip_prefix("</") {
+        let end = from.find('>').map_or(from.len(), |end| end + 1);
+        nodes.push(Node::Close(tag_name(closing).to_string()));
+        end
+      } else {
+        let end = tag_end(from);
+        let tag = from[1..end].trim_end_matches('>');
+        let is_empty = tag.trim_end().ends_with('/');
+        let name = tag_name(tag).to_string();
+        if DROPPED_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+          if is_empty { end } else { end + element_end(&from[end..], &name) }
+        } else {
+          let attributes = crate::core::links::attributes(tag)
+            .into_iter()
+            .filter(|(name, value)| is_allowed(name, value))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+          nodes.push(Node::Open { name, attributes, is_empty });
+          end
+        }
+      };
+      rest = &from[end..];
+    }
+    push_text(&mut nodes, rest);
+
+    match nodes.iter().find(|node| matches!(node, Node::Open { .. })) {
+      Some(Node::Open { name, .. }) if name == "svg" => {
+        // Drop whatever follows the root, such as trailing whitespace.
+        let mut depth = 0usize;
+        let end = nodes
+          .iter()
+          .position(|node| {
+            match node {
+              Node::Open { is_empty: false, .. } => depth += 1,
+              Node::Close(_) => depth = depth.saturating_sub(1),
+              _ => return false,
+            }
+            depth == 0
+          })
+          .map_or(nodes.len(), |end| end + 1);
+        let start = nodes.iter().position(|node| matches!(node, Node::Open { .. })).unwrap_or(0);
+        Ok(Self {
+          nodes: nodes[start..end].to_vec(),
+        })
+      }
+      _ => Err("it has no <svg> root element".to_string()),
+    }
+  }
+
+  /// The document as HTML, with `attributes` applied to the root.
+  pub(crate) fn render(&self, attributes: &SvgAttributes) -> String {
+    self.render_nodes(attributes.current_color, Some(attributes))
+  }
+
+  fn render_nodes(&self, current_color: bool, root: Option<&SvgAttributes>) -> String {
+    let mut html = String::new();
+    for (index, node) in self.nodes.iter().enumerate() {
+      match node {
+        Node::Open {
+          name,
+          attributes: own,
+          is_empty,
+        } => {
+          html.push('<');
+          html.push_str(name);
+          let mut own = own.clone();
+          if current_color {
+            for (name, value) in &mut own {
+              if (name == "fill" || name == "stroke") && !is_paint_keyword(value) {
+                *value = "currentColor".to_string();
+              }
+            }
+          }
+          if index == 0
+            && let Some(root) = root
+          {
+            apply(&mut own, root);
+          }
+          for (name, value) in &own {
+            html.push(' ');
+            html.push_str(name);
+            html.push_str("=\"");
+            html.push_str(&value.replace('"', "&quot;"));
+            html.push('"');
+          }
+          html.push_str(if *is_empty { "/>" } else { ">" });
+        }
+        Node::Close(name) => {
+          html.push_str("</");
+          html.push_str(name);
+          html.push('>');
+        }
+        Node::Text(text) => html.push_str(text),
+      }
+    }
+    html
+  }
+
+  /// The document as a sprite `<symbol>` with `id`, keeping the root's
+  /// `viewBox` and presentation attributes but not its size or namespaces.
+  pub(crate) fn to_symbol(&self, id: &str) -> String {
+    let mut symbol = self.clone();
+    if let Some(Node::Open { name, attributes, .. }) = symbol.nodes.first_mut() {
+      *name = "symbol".to_string();
+      attributes.retain(|(name, _)| {
+        !(name.starts_with("xmlns") || ["width", "height", "x", "y", "id", "class", "version"].contains(&name.as_str()))
+      });
+      attributes.insert(0, ("id".to_string(), tera::escape_html(id)));
+    }
+    if let Some(Node::Close(name)) = symbol.nodes.last_mut() {
+      *name = "symbol".to_string();
+    }
+    symbol.render_nodes(false, None)
+  }
+}
+
+/// Sets the root's attributes from the function's arguments.
+fn apply(own: &mut Vec<(String, String)>, attributes: &SvgAttributes) {
+  let mut set = |name: &str, value: String| match own.iter_mut().find(|(existing, _)| existing == name) {
+    Some((_, existing)) => *existing = value,
+    None => own.push((name.to_string(), value)),
+  };
+  if let Some(width) = &attributes.width {
+    set("width", tera::escape_html(width));
+  }
+  if let Some(height) = &attributes.height {
+    set("height", tera::escape_html(height));
+  }
+  match attributes.title {
+    Some(title) => {
+      set("role", "img".to_string());
+      set("aria-label", tera::escape_html(title));
+    }
+    None => {
+      set("aria-hidden", "true".to_string());
+      set("focusable", "false".to_string());
+    }
+  }
+  if let Some(class) = attributes.class {
+    let class = tera::escape_html(class);
+    match own.iter_mut().find(|(name, _)| name == "class") {
+      Some((_, existing)) if !existing.is_empty() => *existing = format!("{existing} {class}"),
+      Some((_, existing)) => *existing = class,
+      None => own.push(("class".to_string(), class)),
+    }
+  }
+}
+
+/// Whether a `fill` or `stroke` value is kept with `current_color`.
+fn is_paint_keyword(value: &str) -> bool {
+  let value = value.trim();
+  value.eq_ignore_ascii_case("none") || value.eq_ignore_ascii_case("currentColor") || value.starts_with("url(")
+}
+
+/// Whether an attribute is safe to inline.
+fn is_allowed(name: &str, value: &str) -> bool {
+  let name = name.to_ascii_lowercase();
+  if name.starts_with("on") {
+    return false;
+  }
+  if name == "href" || name.ends_with(":href") {
+    let value = crate::core::links::unescape(value);
+    let value: String = value.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect();
+    return match value.find(':') {
+      // A colon after `/`, `?`, or `#` isn't a scheme.
+      Some(colon) if !value[..colon].contains(['/', '?', '#']) => {
+        let scheme = value[..colon].to_ascii_lowercase();
+        scheme == "http" || scheme == "https"
+      }
+      _ => true,
+    };
+  }
+  true
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+  if !text.is_empty() {
+    nodes.push(Node::Text(text.to_string()));
+  }
+}
+
+/// The element name at the start of `tag`, e.g. `linearGradient`.
+fn tag_name(tag: &str) -> &str {
+  let end = tag
+    .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+    .unwrap_or(tag.len());
+  &tag[..end]
+}
+
+/// The length of the tag `from` starts with, skipping `>` in quoted values.
+fn tag_end(from: &str) -> usize {
+  let mut quote = None;
+  for (index, c) in from.char_indices() {
+    match (quote, c) {
+      (None, '"' | '\'') => quote = Some(c),
+      (Some(open), _) if c == open => quote = None,
+      (None, '>') => return index + 1,
+      _ => {}
+    }
+  }
+  from.len()
+}
+
+/// The length of the contents of a dropped `name` element, through its
+/// closing tag.
+fn element_end(from: &str, name: &str) -> usize {
+  let lowercase = from.to_ascii_lowercase();
+  let Some(closing) = lowercase.find(&format!("</{}", name.to_ascii_lowercase())) else {
+    return from.len();
+  };
+  from[closing..].find('>').map_or(from.len(), |close| closing + close + 1)
+}
+
+/// The SVG files of a directory, sanitized and cached.
+#[derive(Debug)]
+pub(crate) struct SvgFiles {
+  dir: PathBuf,
+  cache: RwLock<HashMap<PathBuf, Cached>>,
+}
+
+/// A parsed file, with its modification time in dev mode.
+#[derive(Debug)]
+struct Cached {
+  modified: Option<SystemTime>,
+  svg: Arc<Svg>,
+}
+
+impl SvgFiles {
+  pub(crate) fn new(dir: PathBuf) -> Self {
+    Self {
+      dir,
+      cache: RwLock::new(HashMap::new()),
+    }
+  }
+
+  /// The file at `path`, relative to the directory.
+  pub(crate) fn load(&self, path: &str) -> Result<Arc<Svg>, String> {
+    let relative = Path::new(path);
+    if relative.as_os_str().is_empty() || !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+      return Err(format!("`{path}` must be a path within the static directory"));
+    }
+    let file = self.dir.join(relative);
+
+    // In dev mode, a file changed since it was cached is read again.
+    let modified = || fs::metadata(&file).and_then(|metadata| metadata.modified()).ok();
+    let current = if cfg!(feature = "devel") { modified() } else { None };
+    if let Some(cached) = self.cache.read().get(relative)
+      && (!cfg!(feature = "devel") || cached.modified == current)
+    {
+      return Ok(Arc::clone(&cached.svg));
+    }
+
+    let source = fs::read_to_string(&file).map_err(|e| format!("Failed to read `{}`: {e}", file.display()))?;
+    let svg = Arc::new(Svg::parse(&source).map_err(|e| format!("`{}` can't be inlined: {e}", file.display()))?);
+    let cached = Cached {
+      modified: current,
+      svg: Arc::clone(&svg),
+    };
+    self.cache.write().insert(relative.to_path_buf(), cached);
+    Ok(svg)
+  }
+}
+
+/// The `svg(path=..., class=..., size=..., width=..., height=..., title=...,
+/// current_color=false)` Tera function.
+pub(crate) struct SvgFunction(pub(crate) Arc<SvgFiles>);
+
+impl Function for SvgFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let path = args.get("path").and_then(Value::as_str).ok_or_else(|| {
+      tera::Error::msg("The `svg` function requires a `path` string, relative to the static directory, e.g. svg(path=\"icons/check.svg\")")
+    })?;
+    let string = |name: &str| match args.get(name) {
+      Some(Value::String(value)) => Ok(Some(value.as_str())),
+      Some(_) => Err(tera::Error::msg(format!("The `{name}` argument of `svg` must be a string"))),
+      None => Ok(None),
+    };
+    let length = |name: &str| match args.get(name) {
+      Some(Value::String(value)) => Ok(Some(value.clone())),
+      Some(Value::Number(value)) => Ok(Some(value.to_string())),
+      Some(_) => Err(tera::Error::msg(format!("The `{name}` argument of `svg` must be a number or a string"))),
+      None => Ok(None),
+    };
+    let size = length("size")?;
+    let attributes = SvgAttributes {
+      class: string("class")?,
+      width: length("width")?.or_else(|| size.clone()),
+      height: length("height")?.or(size),
+      title: string("title")?,
+      current_color: match args.get("current_color") {
+        Some(value) => value
+          .as_bool()
+          .ok_or_else(|| tera::Error::msg("The `current_color` argument of `svg` must be a boolean"))?,
+        None => false,
+      },
+    };
+
+    let svg = self.0.load(path).map_err(tera::Error::msg)?;
+    Ok(Value::String(svg.render(&attributes)))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  const ICON: &str = "<?xml version=\"1.0\"?>\n<!-- Exported by an editor -->\n<svg xmlns=\"http://www.w3.org/2000/svg\" \
+    class=\"raw\" width=\"24\" viewBox=\"0 0 24 24\" onload=\"alert(1)\"><script>alert('<svg>')</script>\
+    <a href=\" java\tscript:alert(1)\"><path fill=\"#000\" stroke=\"none\" d=\"M1 1\"/></a>\
+    <a xlink:href=\"#local\"><foreignObject><div>x</div></foreignObject></a></svg>\n";
+
+  #[test]
+  fn test_sanitizes_and_sets_root_attributes() {
+    let svg = Svg::parse(ICON).unwrap();
+    assert_eq!(
+      svg.render(&SvgAttributes::default()),
+      "<svg xmlns=\"http://www.w3.org/2000/svg\" class=\"raw\" width=\"24\" viewBox=\"0 0 24 24\" aria-hidden=\"true\" focusable=\"false\">\
+       <a><path fill=\"#000\" stroke=\"none\" d=\"M1 1\"/></a><a xlink:href=\"#local\"></a></svg>"
+    );
+
+    let html = svg.render(&SvgAttributes {
+      class: Some("icon"),
+      width: Some("16".to_string()),
+      height: Some("16".to_string()),
+      title: Some("Done \"now\""),
+      current_color: true,
+    });
+    assert!(
+      html.starts_with(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" class=\"raw icon\" width=\"16\" viewBox=\"0 0 24 24\" \
+         height=\"16\" role=\"img\" aria-label=\"Done &quot;now&quot;\">"
+      ),
+      "{html}"
+    );
+    assert!(html.contains("<path fill=\"currentColor\" stroke=\"none\""), "{html}");
+    assert!(Svg::parse("<html></html>").is_err());
+  }
+
+  #[test]
+  fn test_files_stay_within_the_directory() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("icons")).unwrap();
+    fs::write(dir.path().join("icons/check.svg"), ICON).unwrap();
+    let files = SvgFiles::new(dir.path().to_path_buf());
+
+    assert!(files.load("icons/check.svg").is_ok());
+    for path in ["../secret.svg", "/etc/passwd", "icons/../../x.svg", "", "icons/missing.svg"] {
+      assert!(files.load(path).is_err(), "{path}");
+    }
+  }
+}
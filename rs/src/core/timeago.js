@@ -0,0 +1,40 @@
+// Injected by SnapFire to keep `timeago` times current.
+(function () {
+  // The same thresholds as the server, so the first update doesn't change the text.
+  const UNITS = [
+    ['second', 1, 45],
+    ['minute', 60, 45 * 60],
+    ['hour', 3600, 22 * 3600],
+    ['day', 86400, 26 * 86400],
+    ['month', 2629800, 320 * 86400],
+    ['year', 31557600, Infinity],
+  ];
+  const format = new Intl.RelativeTimeFormat(document.documentElement.lang || undefined, { numeric: 'auto' });
+
+  function relative(seconds) {
+    const abs = Math.abs(seconds);
+    for (let i = 0; i < UNITS.length; i++) {
+      const [unit, size, limit] = UNITS[i];
+      if (abs < limit) {
+        const value = i === 0 ? 0 : Math.sign(seconds) * Math.round(abs / size);
+        return format.format(value, unit);
+      }
+    }
+  }
+
+  function update() {
+    const now = Date.now();
+    document.querySelectorAll('time[data-timeago]').forEach(function (time) {
+      const then = Date.parse(time.getAttribute('datetime'));
+      if (!isNaN(then)) {
+        time.textContent = relative(Math.round((then - now) / 1000));
+      }
+    });
+  }
+
+  update();
+  setInterval(update, 30000);
+  document.addEventListener('visibilitychange', function () {
+    if (!document.hidden) update();
+  });
+})();
@@ -0,0 +1,237 @@
+//! Relative times, rendered on the server and kept fresh in the browser.
+//!
+//! The `timeago` filter, registered on every app, turns a time into a
+//! `<time>` element with the relative text, the exact time as a tooltip,
+//! and a `datetime` attribute:
+//!
+//! ```jinja
+//! Posted {{ post.published_at | timeago }}
+//! {# Posted <time datetime="2024-05-01T12:00:00Z" title="2024-05-01 12:00 UTC" data-timeago>3 hours ago</time> #}
+//! ```
+//!
+//! It accepts RFC 3339 strings (`2024-05-01T12:00:00+02:00`; without an
+//! offset, UTC), dates (`2024-05-01`, midnight UTC), and Unix timestamps in
+//! seconds. The server writes English text like `5 minutes ago`,
+//! `yesterday`, or `in 2 months`.
+//!
+//! Cached and long-open pages would keep showing the time they were rendered
+//! at, so `TeraWebBuilder::timeago_script(true)` injects a small script into
+//! pages using the filter. It updates the text every 30 seconds with the
+//! same rounding as the server, in the page's `lang` through the browser's
+//! `Intl.RelativeTimeFormat`. It is separate from the live-reload script and
+//! runs in production.
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tera::{Filter, Value};
+
+/// The script `timeago_script` injects.
+pub const TIMEAGO_SCRIPT: &str = include_str!("timeago.js");
+
+/// The relative time units, with their length in seconds and the distance
+/// below which they are used. Mirrored in `timeago.js`.
+const UNITS: &[(&str, i64, i64)] = &[
+  ("second", 1, 45),
+  ("minute", 60, 45 * 60),
+  ("hour", 3_600, 22 * 3_600),
+  ("day", 86_400, 26 * 86_400),
+  ("month", 2_629_800, 320 * 86_400),
+  ("year", 31_557_600, i64::MAX),
+];
+
+/// `then` relative to `now` (both Unix timestamps in seconds), in English,
+/// e.g. `3 hours ago` or `in 2 days`.
+pub fn humanize(then: i64, now: i64) -> String {
+  let seconds = then - now;
+  let (unit, size, _) = UNITS
+    .iter()
+    .find(|(_, _, limit)| seconds.abs() < *limit)
+    .copied()
+    .unwrap_or(UNITS[UNITS.len() - 1]);
+  let value = if unit == "second" {
+    0
+  } else {
+    seconds.signum() * (seconds.abs() as f64 / size as f64).round() as i64
+  };
+
+  match (value, unit) {
+    (0, _) => "now".to_string(),
+    (-1, "day") => "yesterday".to_string(),
+    (1, "day") => "tomorrow".to_string(),
+    (-1, unit) if unit != "minute" && unit != "hour" => format!("last {unit}"),
+    (1, unit) if unit != "minute" && unit != "hour" => format!("next {unit}"),
+    (value, unit) => {
+      let count = value.abs();
+      let plural = if count == 1 { "" } else { "s" };
+      if value < 0 {
+        format!("{count} {unit}{plural} ago")
+      } else {
+        format!("in {count} {unit}{plural}")
+      }
+    }
+  }
+}
+
+/// The Unix timestamp of an RFC 3339 date-time or `YYYY-MM-DD` date, UTC
+/// unless an offset is given.
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+  let value = value.trim();
+  let number = |range: std::ops::Range<usize>| value.get(range)?.parse::<i64>().ok();
+  if value.get(4..5)? != "-" || value.get(7..8)? != "-" {
+    return None;
+  }
+  let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+  if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+    return None;
+  }
+  let mut timestamp = days_from_civil(year, month, day) * 86_400;
+
+  let rest = &value[10..];
+  if rest.is_empty() {
+    return Some(timestamp);
+  }
+  let time = rest.strip_prefix(['T', 't', ' '])?;
+  let offset_at = time.find(['Z', 'z', '+', '-']).unwrap_or(time.len());
+  let (clock, offset) = time.split_at(offset_at);
+  let mut parts = clock.split(':');
+  let hours: i64 = parts.next()?.parse().ok()?;
+  let minutes: i64 = parts.next()?.parse().ok()?;
+  // Fractions of a second don't change a relative time.
+  let seconds: i64 = match parts.next() {
+    Some(seconds) => seconds.split('.').next()?.parse().ok()?,
+    None => 0,
+  };
+  if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+    return None;
+  }
+  timestamp += hours * 3_600 + minutes * 60 + seconds;
+
+  match offset {
+    "" | "Z" | "z" => Some(timestamp),
+    offset => {
+      let sign = if offset.starts_with('-') { -1 } else { 1 };
+      let (offset_hours, offset_minutes) = offset[1..].split_once(':')?;
+      let offset = offset_hours.parse::<i64>().ok()? * 3_600 + offset_minutes.parse::<i64>().ok()? * 60;
+      Some(timestamp - sign * offset)
+    }
+  }
+}
+
+/// The days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146_097 + day_of_era - 719_468
+}
+
+/// The date of a day since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let days = days + 719_468;
+  let era = days.div_euclid(146_097);
+  let day_of_era = days - era * 146_097;
+  let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let shifted_month = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+  let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+  (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// The `<time>` element for `then`, relative to `now`.
+fn time_element(then: i64, now: i64) -> String {
+  let (year, month, day) = civil_from_days(then.div_euclid(86_400));
+  let seconds = then.rem_euclid(86_400);
+  let (hours, minutes, seconds) = (seconds / 3_600, seconds % 3_600 / 60, seconds % 60);
+  format!(
+    "<time datetime=\"{year:04}-{month:02}-{day:02}T{hours:02}:{minutes:02}:{seconds:02}Z\" title=\"{year:04}-{month:02}-{day:02} {hours:02}:{minutes:02} UTC\" data-timeago>{}</time>",
+    humanize(then, now)
+  )
+}
+
+/// Adds the `timeago` script before `</body>` (or at the end) of pages
+/// using the filter, with the request's CSP `nonce`.
+pub(crate) fn inject_script(html: &mut String, nonce: Option<&str>) {
+  if !html.contains("data-timeago") {
+    return;
+  }
+  let script = match nonce {
+    Some(nonce) => format!("<script nonce=\"{}\">{TIMEAGO_SCRIPT}</script>", tera::escape_html(nonce)),
+    None => format!("<script>{TIMEAGO_SCRIPT}</script>"),
+  };
+  match html.to_ascii_lowercase().rfind("</body>") {
+    Some(at) => html.insert_str(at, &script),
+    None => html.push_str(&script),
+  }
+}
+
+/// The `timeago` Tera filter.
+pub(crate) struct TimeagoFilter;
+
+impl Filter for TimeagoFilter {
+  fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let then = match value {
+      Value::Number(number) => number.as_f64().map(|seconds| seconds as i64),
+      Value::String(text) => parse_timestamp(text),
+      _ => None,
+    }
+    .ok_or_else(|| {
+      tera::Error::msg(format!(
+        "The `timeago` filter needs an RFC 3339 date-time, a YYYY-MM-DD date, or a Unix timestamp, got {value}"
+      ))
+    })?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64);
+    Ok(Value::String(time_element(then, now)))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_humanize() {
+    let now = 1_700_000_000;
+    let cases = [
+      (-30, "now"),
+      (-50, "1 minute ago"),
+      (-90, "2 minutes ago"),
+      (3 * 3_600, "in 3 hours"),
+      (-86_400, "yesterday"),
+      (2 * 86_400, "in 2 days"),
+      (-40 * 86_400, "last month"),
+      (-200 * 86_400, "7 months ago"),
+      (400 * 86_400, "next year"),
+      (-3 * 365 * 86_400, "3 years ago"),
+    ];
+    for (offset, expected) in cases {
+      assert_eq!(humanize(now + offset, now), expected, "{offset}");
+    }
+  }
+
+  #[test]
+  fn test_parses_timestamps() {
+    assert_eq!(parse_timestamp("1970-01-01"), Some(0));
+    assert_eq!(parse_timestamp("2024-02-29T12:30:15.25Z"), Some(1_709_209_815));
+    assert_eq!(parse_timestamp("2024-02-29T14:30:15+02:00"), Some(1_709_209_815));
+    assert_eq!(parse_timestamp("2024-02-29 07:30-05:00"), Some(1_709_209_800));
+    for invalid in ["yesterday", "2024-13-01", "2024-01-01T25:00", "2024-01-01T12"] {
+      assert_eq!(parse_timestamp(invalid), None, "{invalid}");
+    }
+    assert_eq!(civil_from_days(days_from_civil(1999, 12, 31)), (1999, 12, 31));
+
+    assert_eq!(
+      time_element(1_709_209_815, 1_709_209_815 + 7_200),
+      "<time datetime=\"2024-02-29T12:30:15Z\" title=\"2024-02-29 12:30 UTC\" data-timeago>2 hours ago</time>"
+    );
+    let mut html = "<body><time data-timeago>now</time></BODY>".to_string();
+    inject_script(&mut html, None);
+    assert!(html.starts_with("<body><time data-timeago>now</time><script>// Injected by SnapFire") && html.ends_with("</script></BODY>"));
+  }
+}
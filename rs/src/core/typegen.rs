@@ -0,0 +1,286 @@
+//! Type generation from the context schema registry.
+//!
+//! Emits TypeScript interfaces (or the raw JSON Schemas) for every template
+//! with a declared `ContextSchema`, so client-side rendering code and docs stay
+//! in sync with what the server actually passes to each template.
+
+use crate::core::app::TeraWeb;
+use crate::core::schema::ContextSchema;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Generates a TypeScript declaration file with one interface per template schema.
+///
+/// Template `users/profile.html` becomes `UsersProfileContext`; shared
+/// definitions (`$defs`, e.g. from `schemars`) become their own interfaces.
+pub fn typescript(app: &TeraWeb) -> String {
+  let mut generator = Generator::default();
+  for (template, schema) in sorted_schemas(app) {
+    generator.add_context(template, schema);
+  }
+  generator.finish()
+}
+
+/// Writes the output of [`typescript`] to `path` (typically a `.d.ts` file).
+pub fn write_typescript(app: &TeraWeb, path: impl AsRef<Path>) -> crate::Result<()> {
+  std::fs::write(path, typescript(app))?;
+  Ok(())
+}
+
+/// Collects every registered schema into one JSON object keyed by template name.
+pub fn json_schemas(app: &TeraWeb) -> Value {
+  Value::Object(
+    sorted_schemas(app)
+      .into_iter()
+      .map(|(template, schema)| (template.to_string(), schema.as_json().clone()))
+      .collect(),
+  )
+}
+
+fn sorted_schemas(app: &TeraWeb) -> BTreeMap<&str, &ContextSchema> {
+  app.schemas.iter().map(|(template, schema)| (template.as_str(), schema)).collect()
+}
+
+/// The interface name for a template, e.g. `blog/post.html` -> `BlogPostContext`.
+pub fn interface_name(template: &str) -> String {
+  let stem = template.rsplit_once('.').map_or(template, |(stem, _)| stem);
+  let mut name = pascal_case(stem);
+  name.push_str("Context");
+  name
+}
+
+fn pascal_case(input: &str) -> String {
+  let mut output = String::new();
+  let mut upper_next = true;
+  for ch in input.chars() {
+    if ch.is_ascii_alphanumeric() {
+      if upper_next {
+        output.push(ch.to_ascii_uppercase());
+      } else {
+        output.push(ch);
+      }
+      upper_next = false;
+    } else {
+      upper_next = true;
+    }
+  }
+  if output.starts_with(|c: char| c.is_ascii_digit()) {
+    output.insert(0, '_');
+  }
+  output
+}
+
+#[derive(Default)]
+struct Generator {
+  output: String,
+  /// Definitions emitted so far, by interface name, to dedupe shared types.
+  emitted_defs: HashMap<String, Value>,
+}
+
+impl Generator {
+  fn add_context(&mut self, template: &str, schema: &ContextSchema) {
+    let root = schema.as_json();
+    let context_name = interface_name(template);
+
+    // Name each definition, prefixing it when a different type already took the name.
+    let mut def_names = HashMap::new();
+    let mut pending_defs = Vec::new();
+    for defs_key in ["$defs", "definitions"] {
+      for (def, def_schema) in root.get(defs_key).and_then(Value::as_object).into_iter().flatten() {
+        let mut name = pascal_case(def);
+        if self.emitted_defs.get(&name).is_some_and(|existing| existing != def_schema) {
+          name = format!("{context_name}{name}");
+        }
+        def_names.insert(format!("#/{defs_key}/{def}"), name.clone());
+        if !self.emitted_defs.contains_key(&name) {
+          self.emitted_defs.insert(name.clone(), def_schema.clone());
+          pending_defs.push((name, def_schema));
+        }
+      }
+    }
+
+    let renderer = TypeRenderer { def_names: &def_names };
+    for (name, def_schema) in pending_defs {
+      self.emit(&name, None, def_schema, &renderer);
+    }
+    self.emit(&context_name, Some(template), root, &renderer);
+  }
+
+  fn emit(&mut self, name: &str, template: Option<&str>, schema: &Value, renderer: &TypeRenderer) {
+    if let Some(template) = template {
+      let _ = writeln!(self.output, "/** Context for the `{template}` template. */");
+    } else if let Some(description) = schema.get("description").and_then(Value::as_str) {
+      let _ = writeln!(self.output, "/** {description} */");
+    }
+
+    match schema.get("properties").and_then(Value::as_object) {
+      Some(properties) => {
+        let _ = writeln!(self.output, "export interface {name} {{");
+        self.output.push_str(&renderer.properties(schema, properties, 1));
+        self.output.push_str("}\n\n");
+      }
+      None => {
+        let _ = writeln!(self.output, "export type {name} = {};\n", renderer.render(schema, 0));
+      }
+    }
+  }
+
+  fn finish(self) -> String {
+    let mut output = String::from("// Generated by snapfire. Do not edit by hand.\n\n");
+    output.push_str(&self.output);
+    output
+  }
+}
+
+struct TypeRenderer<'a> {
+  def_names: &'a HashMap<String, String>,
+}
+
+impl TypeRenderer<'_> {
+  fn render(&self, schema: &Value, depth: usize) -> String {
+    let Some(schema) = schema.as_object() else {
+      return "unknown".to_string();
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+      return self.def_names.get(reference).cloned().unwrap_or_else(|| "unknown".to_string());
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+      return union(allowed.iter().map(Value::to_string).collect());
+    }
+    if let Some(constant) = schema.get("const") {
+      return constant.to_string();
+    }
+    for keyword in ["anyOf", "oneOf"] {
+      if let Some(options) = schema.get(keyword).and_then(Value::as_array) {
+        return union(options.iter().map(|option| self.render(option, depth)).collect());
+      }
+    }
+
+    match schema.get("type") {
+      Some(Value::String(name)) => self.render_type(name, schema, depth),
+      Some(Value::Array(names)) => union(
+        names
+          .iter()
+          .filter_map(Value::as_str)
+          .map(|name| self.render_type(name, schema, depth))
+          .collect(),
+      ),
+      _ if schema.contains_key("properties") => self.render_type("object", schema, depth),
+      _ => "unknown".to_string(),
+    }
+  }
+
+  fn render_type(&self, name: &str, schema: &Map<String, Value>, depth: usize) -> String {
+    match name {
+      "string" => "string".to_string(),
+      "number" | "integer" => "number".to_string(),
+      "boolean" => "boolean".to_string(),
+      "null" => "null".to_string(),
+      "array" => match schema.get("items") {
+        Some(items) => {
+          let item = self.render(items, depth);
+          if item.contains(' ') { format!("Array<{item}>") } else { format!("{item}[]") }
+        }
+        None => "unknown[]".to_string(),
+      },
+      "object" => match schema.get("properties").and_then(Value::as_object) {
+        Some(properties) => {
+          let value = Value::Object(schema.clone());
+          format!("{{\n{}{}}}", self.properties(&value, properties, depth + 1), "  ".repeat(depth))
+        }
+        None => match schema.get("additionalProperties") {
+          Some(values @ Value::Object(_)) => format!("Record<string, {}>", self.render(values, depth)),
+          _ => "Record<string, unknown>".to_string(),
+        },
+      },
+      _ => "unknown".to_string(),
+    }
+  }
+
+  fn properties(&self, schema: &Value, properties: &Map<String, Value>, depth: usize) -> String {
+    let required: Vec<&str> = schema
+      .get("required")
+      .and_then(Value::as_array)
+      .map(|names| names.iter().filter_map(Value::as_str).collect())
+      .unwrap_or_default();
+
+    let indent = "  ".repeat(depth);
+    let mut output = String::new();
+    for (name, property) in properties {
+      if let Some(description) = property.get("description").and_then(Value::as_str) {
+        let _ = writeln!(output, "{indent}/** {description} */");
+      }
+      let optional = if required.contains(&name.as_str()) { "" } else { "?" };
+      let _ = writeln!(output, "{indent}{}{optional}: {};", property_key(name), self.render(property, depth));
+    }
+    output
+  }
+}
+
+fn union(mut members: Vec<String>) -> String {
+  members.dedup();
+  match members.len() {
+    0 => "never".to_string(),
+    _ => members.join(" | "),
+  }
+}
+
+/// Quotes property names that aren't valid identifiers.
+fn property_key(name: &str) -> String {
+  let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+  if is_identifier {
+    name.to_string()
+  } else {
+    Value::from(name).to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+  use std::fs;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_interface_name() {
+    assert_eq!(interface_name("users/profile.html"), "UsersProfileContext");
+    assert_eq!(interface_name("404.html"), "_404Context");
+  }
+
+  #[test]
+  fn test_generates_interfaces() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("user.html"), "").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+    let schema = ContextSchema::from_json(json!({
+      "type": "object",
+      "required": ["user"],
+      "properties": {
+        "user": { "$ref": "#/$defs/User" },
+        "flash": { "type": ["string", "null"] },
+      },
+      "$defs": {
+        "User": {
+          "type": "object",
+          "required": ["name"],
+          "properties": {
+            "name": { "type": "string" },
+            "roles": { "type": "array", "items": { "enum": ["admin", "editor"] } },
+          },
+        },
+      },
+    }));
+    let app = TeraWeb::builder(&glob_path).context_schema("user.html", schema).build().unwrap();
+
+    let output = typescript(&app);
+    assert!(output.contains("export interface User {\n  name: string;\n  roles?: Array<\"admin\" | \"editor\">;\n}"));
+    assert!(output.contains("export interface UserContext {\n  flash?: string | null;\n  user: User;\n}"));
+    assert!(json_schemas(&app).get("user.html").is_some());
+  }
+}
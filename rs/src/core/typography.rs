@@ -0,0 +1,134 @@
+//! Typographic filters for content sites.
+//!
+//! With the `typography` feature, two filters are registered on every app:
+//!
+//! - `smartypants` turns straight quotes into curly ones, `--` and `---` into
+//!   en and em dashes, and `...` into an ellipsis: `"It's -- well..."` becomes
+//!   `“It’s – well…”`.
+//! - `emoji` replaces GitHub-style shortcodes like `:tada:` with the emoji;
+//!   unknown shortcodes are left as written.
+//!
+//! Both work on plain text and on HTML, such as the output of the `markdown`
+//! filter: tags, attributes, comments, and the contents of `pre`, `code`, and
+//! similar elements are left alone. They don't escape or mark their output as
+//! safe, so HTML input still needs `safe`:
+//!
+//! ```jinja
+//! <h1>{{ post.title | smartypants | emoji }}</h1>
+//! {{ post.body | markdown | smartypants | safe }}
+//! ```
+
+use crate::core::html_text;
+use std::collections::HashMap;
+use tera::{Filter, Value};
+
+/// Applies the `smartypants` substitutions to the text of `html`.
+pub fn smartypants(html: &str) -> String {
+  html_text::map_text(html, smarten)
+}
+
+/// Replaces the `:shortcode:` emoji in the text of `html`.
+pub fn emojify(html: &str) -> String {
+  html_text::map_text(html, replace_shortcodes)
+}
+
+fn smarten(text: &str, output: &mut String) {
+  // Markdown and Tera escape quotes in text; they become curly quotes too.
+  let text = text.replace("&quot;", "\"").replace("&#39;", "'").replace("&#x27;", "'");
+  let mut rest = text.as_str();
+  while let Some(next) = rest.find(['"', '\'', '-', '.']) {
+    let (before, from) = rest.split_at(next);
+    output.push_str(before);
+    let (replacement, len) = if from.starts_with("---") {
+      ("—", 3)
+    } else if from.starts_with("--") {
+      ("–", 2)
+    } else if from.starts_with("...") {
+      ("…", 3)
+    } else if from.starts_with('"') {
+      (if opens_quote(output) { "“" } else { "”" }, 1)
+    } else if from.starts_with('\'') {
+      (if opens_quote(output) { "‘" } else { "’" }, 1)
+    } else {
+      (&from[..1], 1)
+    };
+    output.push_str(replacement);
+    rest = &from[len..];
+  }
+  output.push_str(rest);
+}
+
+/// Whether a quote after `output` opens a quotation, rather than closing one
+/// or being an apostrophe.
+fn opens_quote(output: &str) -> bool {
+  output
+    .chars()
+    .next_back()
+    .is_none_or(|c| c.is_whitespace() || "([{<>-–—“‘".contains(c))
+}
+
+fn replace_shortcodes(text: &str, output: &mut String) {
+  let mut rest = text;
+  while let Some(start) = rest.find(':') {
+    output.push_str(&rest[..start]);
+    let after = &rest[start + 1..];
+    let len = after
+      .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '-'))
+      .unwrap_or(after.len());
+    let emoji = after[len..]
+      .starts_with(':')
+      .then(|| emojis::get_by_shortcode(&after[..len]))
+      .flatten();
+    rest = match emoji {
+      Some(emoji) => {
+        output.push_str(emoji.as_str());
+        &after[len + 1..]
+      }
+      // The closing `:` may open the next shortcode, as in `12:30:tada:`.
+      None => {
+        output.push(':');
+        after
+      }
+    };
+  }
+  output.push_str(rest);
+}
+
+/// The `smartypants` Tera filter.
+pub(crate) struct SmartypantsFilter;
+
+impl Filter for SmartypantsFilter {
+  fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(smartypants(input(value, "smartypants")?)))
+  }
+}
+
+/// The `emoji` Tera filter.
+pub(crate) struct EmojiFilter;
+
+impl Filter for EmojiFilter {
+  fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(emojify(input(value, "emoji")?)))
+  }
+}
+
+fn input<'a>(value: &'a Value, filter: &str) -> tera::Result<&'a str> {
+  value
+    .as_str()
+    .ok_or_else(|| tera::Error::msg(format!("The `{filter}` filter can only be applied to strings")))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_smartypants_and_emoji() {
+    assert_eq!(smartypants("\"It's -- well... 'fine'---ok\""), "“It’s – well… ‘fine’—ok”");
+    assert_eq!(
+      smartypants("<p title=\"a--b\">&quot;Hi&quot; <code>\"x\" -- y</code></p>"),
+      "<p title=\"a--b\">“Hi” <code>\"x\" -- y</code></p>"
+    );
+    assert_eq!(emojify("Done :tada: at 12:30:+1: :nope: <code>:tada:</code>"), "Done 🎉 at 12:30👍 :nope: <code>:tada:</code>");
+  }
+}
@@ -0,0 +1,202 @@
+//! File upload fields, validated on the server and rendered with matching
+//! hints in templates.
+//!
+//! Each upload field is declared once on the builder with its limits:
+//!
+//! ```rust,no_run
+//! # use snapfire::core::uploads::UploadRule;
+//! # fn main() -> snapfire::Result<()> {
+//! let app = snapfire::TeraWeb::builder("templates/**/*")
+//!   .upload_field("avatar", UploadRule::new().max_size(2_000_000).accept(&["image/png", "image/jpeg"]).required(true))
+//!   .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The `upload_field(name="avatar")` template function renders the file input
+//! with the rule's `accept`, `multiple`, and `required` attributes (and the
+//! size limit as `data-max-size`), so browsers filter the file picker. The
+//! handler reads the submission with `snapfire::actix::uploads::read_uploads`,
+//! which enforces the same rules and collects a message per invalid field.
+//! Passing those back to the form shows them next to their inputs:
+//!
+//! ```jinja
+//! <form method="post" enctype="multipart/form-data">
+//!   {{ upload_field(name="avatar", errors=upload_errors) }}
+//! </form>
+//! ```
+//!
+//! With an `errors` object that has a message for the field, the input gets
+//! `aria-invalid` and is described by a `<p class="upload-error">` after it.
+//! File types are checked against the file name's extension and the
+//! content type the browser declared; both come from the client, so don't
+//! rely on them for security.
+//!
+//! In development, `read_uploads` also pushes each file's progress to the
+//! pages over the live-reload WebSocket, as `upload-progress` events.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tera::{Function, Value};
+
+/// The limits of an upload field.
+#[derive(Debug, Clone, Default)]
+pub struct UploadRule {
+  pub(crate) max_size: Option<u64>,
+  pub(crate) accept: Vec<String>,
+  pub(crate) multiple: bool,
+  pub(crate) required: bool,
+}
+
+impl UploadRule {
+  /// One optional file of any type and size.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The largest file accepted, in bytes.
+  pub fn max_size(mut self, bytes: u64) -> Self {
+    self.max_size = Some(bytes);
+    self
+  }
+
+  /// The accepted types, as in HTML's `accept` attribute: content types
+  /// (`image/png`), wildcards (`image/*`), or extensions (`.pdf`).
+  pub fn accept(mut self, types: &[&str]) -> Self {
+    self.accept = types.iter().map(|kind| kind.to_ascii_lowercase()).collect();
+    self
+  }
+
+  /// Whether the field takes several files.
+  pub fn multiple(mut self, enabled: bool) -> Self {
+    self.multiple = enabled;
+    self
+  }
+
+  /// Whether a file must be chosen.
+  pub fn required(mut self, enabled: bool) -> Self {
+    self.required = enabled;
+    self
+  }
+
+  /// Checks an uploaded file against the rule, returning the message to
+  /// show if it doesn't pass.
+  pub fn check(&self, filename: &str, content_type: Option<&str>, size: u64) -> Result<(), String> {
+    if let Some(max_size) = self.max_size
+      && size > max_size
+    {
+      return Err(format!("{} is larger than {}", filename, format_size(max_size)));
+    }
+    if !self.accept.is_empty() && !self.accepts(filename, content_type) {
+      return Err(format!("{} is not an accepted file type ({})", filename, self.accept.join(", ")));
+    }
+    Ok(())
+  }
+
+  fn accepts(&self, filename: &str, content_type: Option<&str>) -> bool {
+    let filename = filename.to_ascii_lowercase();
+    let content_type = content_type.map(|content_type| content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase());
+    self.accept.iter().any(|accepted| {
+      if accepted.starts_with('.') {
+        filename.ends_with(accepted.as_str())
+      } else if let Some(prefix) = accepted.strip_suffix("/*") {
+        content_type
+          .as_deref()
+          .is_some_and(|content_type| content_type.split('/').next() == Some(prefix))
+      } else {
+        content_type.as_deref() == Some(accepted.as_str())
+      }
+    })
+  }
+
+  /// The `<input type="file">` for the field `name`, described by `error`.
+  pub fn input_html(&self, name: &str, error: Option<&str>) -> String {
+    let name = tera::escape_html(name);
+    let mut html = format!("<input type=\"file\" name=\"{name}\" id=\"{name}\"");
+    if !self.accept.is_empty() {
+      html.push_str(&format!(" accept=\"{}\"", tera::escape_html(&self.accept.join(","))));
+    }
+    if let Some(max_size) = self.max_size {
+      html.push_str(&format!(" data-max-size=\"{max_size}\""));
+    }
+    if self.multiple {
+      html.push_str(" multiple");
+    }
+    if self.required {
+      html.push_str(" required");
+    }
+    match error {
+      Some(error) => format!(
+        "{html} aria-invalid=\"true\" aria-describedby=\"{name}-error\"><p class=\"upload-error\" id=\"{name}-error\">{}</p>",
+        tera::escape_html(error)
+      ),
+      None => html + ">",
+    }
+  }
+}
+
+/// A size in bytes for messages, e.g. `2 MB`.
+fn format_size(bytes: u64) -> String {
+  let units = [("GB", 1_000_000_000), ("MB", 1_000_000), ("kB", 1_000)];
+  for (unit, size) in units {
+    if bytes >= size {
+      let value = bytes as f64 / size as f64;
+      let value = format!("{value:.1}");
+      return format!("{} {unit}", value.trim_end_matches(".0"));
+    }
+  }
+  format!("{bytes} bytes")
+}
+
+/// The `upload_field(name=..., errors=...)` Tera function.
+pub(crate) struct UploadFieldFunction(pub(crate) Arc<HashMap<String, UploadRule>>);
+
+impl Function for UploadFieldFunction {
+  fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let name = args
+      .get("name")
+      .and_then(Value::as_str)
+      .ok_or_else(|| tera::Error::msg("The `upload_field` function requires a `name` argument, e.g. upload_field(name=\"avatar\")"))?;
+    let rule = self.0.get(name).ok_or_else(|| {
+      tera::Error::msg(format!("Unknown upload field `{name}`; declare it with `TeraWebBuilder::upload_field`"))
+    })?;
+    let error = match args.get("errors") {
+      Some(Value::Object(errors)) => errors.get(name).and_then(Value::as_str),
+      Some(Value::Null) | None => None,
+      Some(_) => return Err(tera::Error::msg("The `errors` argument of `upload_field` must be an object")),
+    };
+    Ok(Value::String(rule.input_html(name, error)))
+  }
+
+  fn is_safe(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rules_check_and_render() {
+    let rule = UploadRule::new().max_size(1_500_000).accept(&["image/*", ".PDF"]).required(true);
+    assert_eq!(rule.check("cat.png", Some("image/png"), 1_000), Ok(()));
+    assert_eq!(rule.check("scan.pdf", Some("application/octet-stream"), 1_000), Ok(()));
+    assert_eq!(rule.check("big.png", Some("image/png"), 2_000_000), Err("big.png is larger than 1.5 MB".to_string()));
+    assert_eq!(
+      rule.check("run.exe", Some("application/x-msdownload"), 10),
+      Err("run.exe is not an accepted file type (image/*, .pdf)".to_string())
+    );
+    assert_eq!(format_size(2_000_000), "2 MB");
+    assert_eq!(format_size(512), "512 bytes");
+
+    assert_eq!(
+      rule.input_html("avatar", None),
+      "<input type=\"file\" name=\"avatar\" id=\"avatar\" accept=\"image&#x2F;*,.pdf\" data-max-size=\"1500000\" required>"
+    );
+    assert_eq!(
+      UploadRule::new().multiple(true).input_html("docs", Some("<b> too big")),
+      "<input type=\"file\" name=\"docs\" id=\"docs\" multiple aria-invalid=\"true\" aria-describedby=\"docs-error\"><p class=\"upload-error\" id=\"docs-error\">&lt;b&gt; too big</p>"
+    );
+  }
+}
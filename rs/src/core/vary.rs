@@ -0,0 +1,85 @@
+//! Caching hints for pages rendered from request data.
+//!
+//! A page rendered from the request (a theme read from a cookie, a locale
+//! from `Accept-Language`, the signed-in user) must not be served by a CDN
+//! or browser cache to requests that would render it differently. The Actix
+//! `Template` responder tracks what request data went into the page and
+//! sends the matching headers, so handlers don't have to get them right:
+//!
+//! * `Vary` lists the request headers the page depends on: those of the
+//!   builder's `cache_key_dimensions`, `Cookie` when the `CookieConsent`
+//!   state is exposed, and those middleware recorded in a [`VaryOn`].
+//! * `Cache-Control: private` keeps shared caches from storing pages
+//!   rendered with request data of unknown origin: a request `RenderEnv` or
+//!   `SnapFireContextExt` values, when middleware recorded no `VaryOn`.
+//!
+//! Middleware deriving values from headers records them alongside:
+//!
+//! ```rust,no_run
+//! # use actix_web::{dev::ServiceRequest, HttpMessage};
+//! # use snapfire::core::{env::RenderEnv, vary::VaryOn};
+//! # fn resolve(req: &ServiceRequest) {
+//! req.extensions_mut().insert(RenderEnv {
+//!   theme: Some("dark".to_string()),
+//!   ..RenderEnv::default()
+//! });
+//! VaryOn::record(req, "Cookie");
+//! # }
+//! ```
+//!
+//! Recording an empty `VaryOn` declares that the request data doesn't
+//! depend on any header (e.g. it comes from the path), so the page stays
+//! cacheable. A `Cache-Control` set after the responder (such as the
+//! `cache` front matter of `pages_handler`) replaces `private`.
+
+/// The request headers a page depends on, as sent in its `Vary` header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VaryOn {
+  headers: Vec<String>,
+}
+
+impl VaryOn {
+  /// No headers.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds `header`, or each of a comma-separated list, unless already
+  /// there. Names are compared case-insensitively.
+  ///
+  /// # Arguments
+  ///
+  /// * `header` - A request header name, e.g. `Accept-Language`.
+  pub fn header(&mut self, header: &str) -> &mut Self {
+    for name in header.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+      if !self.headers.iter().any(|known| known.eq_ignore_ascii_case(name)) {
+        self.headers.push(name.to_string());
+      }
+    }
+    self
+  }
+
+  /// The headers added so far, in order.
+  pub fn headers(&self) -> &[String] {
+    &self.headers
+  }
+
+  /// The value of the `Vary` header, or `None` without headers.
+  pub(crate) fn value(&self) -> Option<String> {
+    (!self.headers.is_empty()).then(|| self.headers.join(", "))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_headers_are_listed_once() {
+    let mut vary = VaryOn::new();
+    assert_eq!(vary.value(), None);
+    vary.header("Accept-Language, Cookie").header("cookie").header(" ");
+    assert_eq!(vary.headers(), ["Accept-Language", "Cookie"]);
+    assert_eq!(vary.value().as_deref(), Some("Accept-Language, Cookie"));
+  }
+}
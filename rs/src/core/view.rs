@@ -0,0 +1,44 @@
+//! View models: a deliberate boundary between domain types and templates.
+//!
+//! `TeraWeb::render` takes anything implementing [`IntoTemplateContext`],
+//! so a handler can pass a view model instead of filling a `Context` by hand
+//! or serializing a domain struct wholesale, with every field it has. The
+//! derive exposes named fields, minus the skipped ones, plus computed values:
+//!
+//! ```rust
+//! use snapfire::IntoTemplateContext;
+//!
+//! #[derive(IntoTemplateContext)]
+//! #[template_context(computed = "display_name")]
+//! struct ProfilePage {
+//!   first_name: String,
+//!   last_name: String,
+//!   #[template_context(rename = "joined")]
+//!   created_at: String,
+//!   #[template_context(skip)]
+//!   password_hash: String,
+//! }
+//!
+//! impl ProfilePage {
+//!   fn display_name(&self) -> String {
+//!     format!("{} {}", self.first_name, self.last_name)
+//!   }
+//! }
+//! ```
+//!
+//! Types needing more control implement the trait by hand.
+
+use tera::Context;
+
+/// A value that can become the context of a render.
+pub trait IntoTemplateContext {
+  /// The values templates see.
+  fn into_template_context(self) -> Context;
+}
+
+impl IntoTemplateContext for Context {
+  fn into_template_context(self) -> Context {
+    self
+  }
+}
+
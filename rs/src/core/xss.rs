@@ -0,0 +1,319 @@
+//! Loud escaping mistakes in development.
+//!
+//! With `TeraWebBuilder::xss_audit(true)`, debug builds check every render
+//! for context values printed with `{{ value | safe }}` that look like they
+//! came from a user rather than from trusted HTML: a `<script>` tag, an
+//! inline event handler, a `javascript:` URL, an embedded frame, or a `<`
+//! that doesn't start a tag. Each is logged as a warning with the template,
+//! the line (for templates loaded from files), and the variable:
+//!
+//! ```text
+//! Possible XSS in post.html:12: `post.body` is marked safe but contains an inline event handler
+//! ```
+//!
+//! Values reached through `for` loops and `set` are followed back to the
+//! context; values passed through another filter before `safe` (such as
+//! `markdown` or `sanitize_html`) are left alone, as are macro arguments.
+//! Release builds skip the audit entirely.
+
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use tera::Tera;
+use tera::ast::{Expr, ExprVal, Node};
+
+/// A value printed with `| safe` that looks unsafe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct XssWarning {
+  pub(crate) template: String,
+  /// The 1-based line, when the template was loaded from a file.
+  pub(crate) line: Option<usize>,
+  pub(crate) variable: String,
+  pub(crate) reason: &'static str,
+}
+
+impl std::fmt::Display for XssWarning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Possible XSS in {}", self.template)?;
+    if let Some(line) = self.line {
+      write!(f, ":{line}")?;
+    }
+    write!(f, ": `{}` is marked safe but contains {}", self.variable, self.reason)
+  }
+}
+
+/// One step from the context root to a value.
+#[derive(Debug, Clone)]
+enum Step {
+  Key(String),
+  /// Every element of an array, or every value of an object.
+  Each,
+}
+
+/// Checks the `| safe` values `tpl` (with its parents and includes) prints
+/// from `context`.
+pub(crate) fn audit(tera: &Tera, tpl: &str, context: &Value) -> Vec<XssWarning> {
+  let mut auditor = Auditor {
+    tera,
+    context,
+    visited: HashSet::new(),
+    warnings: Vec::new(),
+  };
+  auditor.template(tpl, &HashMap::new());
+  auditor.warnings
+}
+
+struct Auditor<'a> {
+  tera: &'a Tera,
+  context: &'a Value,
+  visited: HashSet<String>,
+  warnings: Vec<XssWarning>,
+}
+
+impl Auditor<'_> {
+  fn template(&mut self, name: &str, scope: &HashMap<String, Vec<Step>>) {
+    if !self.visited.insert(name.to_string()) {
+      return;
+    }
+    let Ok(template) = self.tera.get_template(name) else {
+      return;
+    };
+    // A child's blocks render inside its parents' layout.
+    let mut names = vec![name.to_string()];
+    names.extend(template.parents.iter().cloned());
+    for name in names {
+      if let Ok(template) = self.tera.get_template(&name) {
+        self.visited.insert(name.clone());
+        let mut scope = scope.clone();
+        self.nodes(&name, &template.ast, &mut scope);
+      }
+    }
+  }
+
+  fn nodes(&mut self, template: &str, nodes: &[Node], scope: &mut HashMap<String, Vec<Step>>) {
+    for node in nodes {
+      match node {
+        Node::VariableBlock(_, expr) => self.variable(template, expr, scope),
+        Node::Include(_, names, _) => {
+          if let Some(name) = names.iter().find(|name| self.tera.get_template(name).is_ok()) {
+            self.template(name, scope);
+          }
+        }
+        Node::Set(_, set) => match &set.value.val {
+          ExprVal::Ident(ident) if set.value.filters.is_empty() => {
+            if let Some(path) = resolve(ident, scope) {
+              scope.insert(set.key.clone(), path);
+            }
+          }
+          _ => {
+            scope.remove(&set.key);
+          }
+        },
+        Node::FilterSection(_, section, _) => self.nodes(template, &section.body, scope),
+        Node::Block(_, block, _) => self.nodes(template, &block.body, scope),
+        Node::Forloop(_, forloop, _) => {
+          let mut inner = scope.clone();
+          inner.remove(&forloop.value);
+          if let Some(key) = &forloop.key {
+            inner.remove(key);
+          }
+          if let ExprVal::Ident(ident) = &forloop.container.val
+            && forloop.container.filters.is_empty()
+            && let Some(mut path) = resolve(ident, scope)
+          {
+            path.push(Step::Each);
+            inner.insert(forloop.value.clone(), path);
+          }
+          self.nodes(template, &forloop.body, &mut inner);
+          if let Some(body) = &forloop.empty_body {
+            self.nodes(template, body, &mut scope.clone());
+          }
+        }
+        Node::If(branches, _) => {
+          for (_, _, body) in &branches.conditions {
+            self.nodes(template, body, &mut scope.clone());
+          }
+          if let Some((_, body)) = &branches.otherwise {
+            self.nodes(template, body, &mut scope.clone());
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+
+  fn variable(&mut self, template: &str, expr: &Expr, scope: &HashMap<String, Vec<Step>>) {
+    let ExprVal::Ident(ident) = &expr.val else {
+      return;
+    };
+    // Only a bare `| safe`; any filter before it may well sanitize.
+    if !matches!(expr.filters.as_slice(), [filter] if filter.name == "safe") {
+      return;
+    }
+    let Some(path) = resolve(ident, scope) else {
+      return;
+    };
+    let mut values = Vec::new();
+    collect(self.context, &path, &mut values);
+    let Some(reason) = values.into_iter().find_map(risk) else {
+      return;
+    };
+    let warning = XssWarning {
+      template: template.to_string(),
+      line: self.line_of(template, ident),
+      variable: ident.clone(),
+      reason,
+    };
+    if !self.warnings.contains(&warning) {
+      self.warnings.push(warning);
+    }
+  }
+
+  /// The line printing `ident` with `safe`, read from the template's file.
+  fn line_of(&self, template: &str, ident: &str) -> Option<usize> {
+    let path = self.tera.get_template(template).ok()?.path.as_ref()?;
+    let source = std::fs::read_to_string(path).ok()?;
+    source
+      .lines()
+      .position(|line| line.contains(ident) && line.contains("safe"))
+      .map(|index| index + 1)
+  }
+}
+
+/// The context path of a dotted identifier, following loop and `set`
+/// variables. Subscripts (`items[0]`) aren't followed.
+fn resolve(ident: &str, scope: &HashMap<String, Vec<Step>>) -> Option<Vec<Step>> {
+  if ident.contains('[') {
+    return None;
+  }
+  let mut parts = ident.split('.');
+  let first = parts.next()?;
+  let mut path = scope.get(first).cloned().unwrap_or_else(|| vec![Step::Key(first.to_string())]);
+  path.extend(parts.map(|part| Step::Key(part.to_string())));
+  Some(path)
+}
+
+/// The strings at `path` in `value`.
+fn collect<'a>(value: &'a Value, path: &[Step], strings: &mut Vec<&'a str>) {
+  let Some((step, rest)) = path.split_first() else {
+    if let Value::String(string) = value {
+      strings.push(string);
+    }
+    return;
+  };
+  match (step, value) {
+    (Step::Key(key), Value::Object(map)) => {
+      if let Some(value) = map.get(key) {
+        collect(value, rest, strings);
+      }
+    }
+    (Step::Key(index), Value::Array(items)) => {
+      if let Some(value) = index.parse::<usize>().ok().and_then(|index| items.get(index)) {
+        collect(value, rest, strings);
+      }
+    }
+    (Step::Each, Value::Array(items)) => items.iter().for_each(|item| collect(item, rest, strings)),
+    (Step::Each, Value::Object(map)) => map.values().for_each(|item| collect(item, rest, strings)),
+    _ => {}
+  }
+}
+
+/// Why `value` doesn't look like trusted HTML, if it doesn't.
+fn risk(value: &str) -> Option<&'static str> {
+  let lower = value.to_ascii_lowercase();
+  if lower.contains("<script") {
+    return Some("a <script> tag");
+  }
+  if lower.contains("javascript:") {
+    return Some("a javascript: URL");
+  }
+  if ["<iframe", "<object", "<embed"].iter().any(|tag| lower.contains(tag)) {
+    return Some("an embedded frame");
+  }
+  if has_event_handler(&lower) {
+    return Some("an inline event handler");
+  }
+  // Markup opens tags, comments, and doctypes; people type `a < b`.
+  if lower
+    .match_indices('<')
+    .any(|(at, _)| !lower[at + 1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!'))
+  {
+    return Some("a `<` that doesn't start a tag");
+  }
+  None
+}
+
+/// Whether `html` (lowercased) has an `on...=` attribute inside a tag.
+fn has_event_handler(html: &str) -> bool {
+  let mut in_tag = false;
+  let bytes = html.as_bytes();
+  for (index, &byte) in bytes.iter().enumerate() {
+    match byte {
+      b'<' => in_tag = true,
+      b'>' => in_tag = false,
+      b'o' if in_tag && index > 0 && (bytes[index - 1].is_ascii_whitespace() || bytes[index - 1] == b'/') => {
+        let name_len = bytes[index..].iter().take_while(|byte| byte.is_ascii_alphanumeric()).count();
+        let after = html[index + name_len..].trim_start();
+        if name_len > 2 && bytes[index + 1] == b'n' && after.starts_with('=') {
+          return true;
+        }
+      }
+      _ => {}
+    }
+  }
+  false
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_flags_user_looking_values_marked_safe() {
+    let mut tera = Tera::default();
+    tera
+      .add_raw_templates([
+        ("base.html", "{% block body %}{% endblock %}{{ footer | safe }}"),
+        (
+          "post.html",
+          "{% extends \"base.html\" %}{% block body %}{{ title | safe }}{{ body | markdown | safe }}\
+           {% for comment in comments %}{% set text = comment.text %}{{ text | safe }}{% endfor %}{% endblock %}",
+        ),
+      ])
+      .unwrap();
+    let context = json!({
+      "title": "<em>Hello</em>",
+      "body": "<script>alert(1)</script>",
+      "footer": "<a href=\"/\" onclick = \"steal()\">Home</a>",
+      "comments": [{"text": "fine"}, {"text": "1 < 2"}],
+    });
+    let mut warnings: Vec<_> = audit(&tera, "post.html", &context)
+      .into_iter()
+      .map(|warning| warning.to_string())
+      .collect();
+    warnings.sort();
+    assert_eq!(
+      warnings,
+      [
+        "Possible XSS in base.html: `footer` is marked safe but contains an inline event handler",
+        "Possible XSS in post.html: `text` is marked safe but contains a `<` that doesn't start a tag",
+      ]
+    );
+
+    assert_eq!(risk("<p>Hi, <strong>you</strong></p><!-- note -->"), None);
+    assert_eq!(risk("<img src=x onerror=alert(1)>"), Some("an inline event handler"));
+    assert_eq!(risk("<p>Turn the knob on = off</p>"), None);
+    assert_eq!(risk("<a href=\"JavaScript:go()\">x</a>"), Some("a javascript: URL"));
+  }
+
+  #[test]
+  fn test_names_the_line_of_templates_from_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("bio.html"), "<h1>{{ name }}</h1>\n<p>{{ user.bio | safe }}</p>").unwrap();
+    let tera = Tera::new(temp_dir.path().join("*.html").to_str().unwrap()).unwrap();
+    let warnings = audit(&tera, "bio.html", &json!({"user": {"bio": "<b onmouseover=x()>hi</b>"}}));
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, Some(2));
+    assert_eq!(warnings[0].variable, "user.bio");
+  }
+}
@@ -18,8 +18,124 @@ pub enum SnapFireError {
   #[error("Context serialization error: {0}")]
   Serialization(String),
 
+  /// A render context did not match the schema declared for its template.
+  #[error("Context for `{template}` does not match its schema: {}", .violations.join("; "))]
+  Schema {
+    /// The template being rendered.
+    template: String,
+    /// Every problem found, e.g. `missing field user.email`.
+    violations: Vec<String>,
+  },
+
+  /// A configuration file (such as a navigation file) could not be loaded.
+  #[error("Configuration error in {path}: {message}")]
+  Config {
+    /// The file that failed to load.
+    path: String,
+    /// What went wrong.
+    message: String,
+  },
+
+  /// A scheduled re-render has an invalid cron expression, only available
+  /// with the `schedule` feature.
+  #[cfg(feature = "schedule")]
+  #[error("Invalid schedule for `{template}`: {message}")]
+  Schedule {
+    /// The scheduled template.
+    template: String,
+    /// Why the expression was rejected.
+    message: String,
+  },
+
+  /// Publishing an exported site failed.
+  #[error("Deploy to {target} failed: {message}")]
+  Deploy {
+    /// The export target, e.g. `s3` or `git`.
+    target: String,
+    /// What went wrong.
+    message: String,
+  },
+
+  /// Purging a CDN's cache failed.
+  #[error("Purging {cdn} failed: {message}")]
+  Purge {
+    /// The CDN, e.g. `fastly` or `cloudfront`.
+    cdn: String,
+    /// What went wrong.
+    message: String,
+  },
+
+  /// Converting a page to PDF failed, only available with the `pdf` feature.
+  #[cfg(feature = "pdf")]
+  #[error("PDF conversion with {backend} failed: {message}")]
+  Pdf {
+    /// The PDF backend, e.g. `chromium`.
+    backend: String,
+    /// What went wrong, such as the converter's error output.
+    message: String,
+  },
+
+  /// Capturing a page as an image failed, only available with the
+  /// `screenshot` feature.
+  #[cfg(feature = "screenshot")]
+  #[error("Screenshot with {backend} failed: {message}")]
+  Screenshot {
+    /// The screenshot backend, e.g. `chromium`.
+    backend: String,
+    /// What went wrong, such as the browser's error output.
+    message: String,
+  },
+
+  /// A cache invalidation listener lost or couldn't open its connection,
+  /// only available with the `redis` or `nats` feature.
+  #[cfg(any(feature = "redis", feature = "nats"))]
+  #[error("Invalidation listener on {bus} failed: {message}")]
+  Invalidation {
+    /// The message bus, `redis` or `nats`.
+    bus: String,
+    /// What went wrong.
+    message: String,
+  },
+
+  /// A filter, function, or hook panicked during a render, caught because
+  /// `TeraWebBuilder::catch_panics` is on.
+  #[error("Panic while rendering `{template}`: {message}")]
+  Panic {
+    /// The template being rendered.
+    template: String,
+    /// The panic's message.
+    message: String,
+  },
+
+  /// A template bundle could not be decoded.
+  #[error("Template bundle error: {0}")]
+  Bundle(String),
+
   /// An error from the file watcher, only available with the `devel` feature.
   #[cfg(feature = "devel")]
   #[error("File watcher error: {0}")]
   Watcher(#[from] notify::Error),
-}
\ No newline at end of file
+}
+
+/// The message of a caught panic's payload.
+pub(crate) fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+  if let Some(message) = panic.downcast_ref::<&str>() {
+    message.to_string()
+  } else if let Some(message) = panic.downcast_ref::<String>() {
+    message.clone()
+  } else {
+    "<non-string panic payload>".to_string()
+  }
+}
+
+/// Flattens an error and its sources, since Tera puts the useful part in the source.
+pub(crate) fn error_chain(error: &dyn std::error::Error) -> String {
+  let mut message = error.to_string();
+  let mut source = error.source();
+  while let Some(cause) = source {
+    message.push_str(": ");
+    message.push_str(&cause.to_string());
+    source = cause.source();
+  }
+  message
+}
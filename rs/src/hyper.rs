@@ -0,0 +1,296 @@
+//! Hyper integration. Only available with the `hyper` feature.
+//!
+//! For custom hyper 0.14 servers, a [`Template`] converts into a
+//! `hyper::Response<Body>` (or [`render`] renders one for a request, seeing
+//! its host and path), and [`dev_service`] wraps the app's service to serve
+//! the live-reload WebSocket and client script in dev mode:
+//!
+//! ```rust,ignore
+//! use hyper::service::{make_service_fn, service_fn};
+//! use hyper::{Body, Request, Response, Server};
+//! use snapfire::TeraWeb;
+//! use std::convert::Infallible;
+//!
+//! # async fn run() {
+//! let app = TeraWeb::builder("templates/**/*.html").build().unwrap();
+//! let make_service = make_service_fn(move |_| {
+//!   let app = app.clone();
+//!   let pages = {
+//!     let app = app.clone();
+//!     service_fn(move |request: Request<Body>| {
+//!       let response = snapfire::hyper::render(&app, &request, "index.html", tera::Context::new());
+//!       async move { Ok::<_, Infallible>(response) }
+//!     })
+//!   };
+//!   async move { Ok::<_, Infallible>(snapfire::hyper::dev_service(&app, pages)) }
+//! });
+//! Server::bind(&([127, 0, 0, 1], 3000).into()).serve(make_service).await.unwrap();
+//! # }
+//! ```
+//!
+//! Without the `devel` feature, the wrapper passes every request through,
+//! so it can be used unconditionally. In dev mode, responses converted from
+//! templates carry the live-reload script, as the `InjectSnapFireScript`
+//! middleware does for Actix. The WebSocket connections run on the Tokio
+//! runtime serving the requests.
+
+use crate::core::app::{RequestParts, Template, TeraWeb};
+use crate::core::response::{Caching, RenderedResponse, into_http_response};
+use hyper::header;
+use hyper::service::Service;
+use hyper::{Body, Request, Response};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use tera::Context;
+
+impl From<Template> for Response<Body> {
+  fn from(template: Template) -> Self {
+    let app = template.app_state.clone();
+    into_response(&app, template.into_rendered())
+  }
+}
+
+/// Renders `tpl` for `request`: a `200` with the page, a `500` if the render
+/// failed, or a `503` with `Retry-After` while its circuit is open.
+///
+/// # Arguments
+///
+/// * `app` - The app rendering the page.
+/// * `request` - The request, for its host and path.
+/// * `tpl` - The template to render.
+/// * `context` - The page's context.
+pub fn render<B>(app: &TeraWeb, request: &Request<B>, tpl: &str, context: Context) -> Response<Body> {
+  let host = request
+    .headers()
+    .get(header::HOST)
+    .and_then(|host| host.to_str().ok())
+    .or_else(|| request.uri().authority().map(|authority| authority.as_str()));
+  let parts = RequestParts {
+    host,
+    path: Some(request.uri().path()),
+    ..RequestParts::default()
+  };
+  into_response(app, app.render(tpl, context).into_rendered_for(parts, Caching::default()))
+}
+
+fn into_response(app: &TeraWeb, rendered: RenderedResponse) -> Response<Body> {
+  #[cfg(feature = "devel")]
+  let rendered = if rendered.status == 500 {
+    rendered
+  } else {
+    RenderedResponse {
+      body: crate::core::client::with_reload_script(app, rendered.body),
+      ..rendered
+    }
+  };
+  #[cfg(not(feature = "devel"))]
+  let _ = app;
+  into_http_response!(hyper::http; rendered, Body)
+}
+
+
+/// Wraps `inner`, the app's own service, to serve the live-reload WebSocket
+/// at the builder's `ws_path` and the client script at
+/// `/_snapfire/client.js`. Without the `devel` feature, every request goes to
+/// `inner`.
+///
+/// # Arguments
+///
+/// * `app` - The app whose template reloads are pushed to clients.
+/// * `inner` - The service handling every other request.
+pub fn dev_service<S>(app: &TeraWeb, inner: S) -> DevService<S> {
+  #[cfg(feature = "devel")]
+  log::info!("🔥 SnapFire devel enabled. Attaching WebSocket at {}", app.reloader.options.ws_path);
+  #[cfg(not(feature = "devel"))]
+  let _ = app;
+  DevService {
+    #[cfg(feature = "devel")]
+    app: app.clone(),
+    inner,
+  }
+}
+
+/// A hyper `Service` answering the live-reload requests itself and passing
+/// the rest to the service it wraps. Created with [`dev_service`].
+#[derive(Debug, Clone)]
+pub struct DevService<S> {
+  #[cfg(feature = "devel")]
+  app: TeraWeb,
+  inner: S,
+}
+
+impl<S> Service<Request<Body>> for DevService<S>
+where
+  S: Service<Request<Body>, Response = Response<Body>>,
+  S::Future: Send + 'static,
+{
+  type Response = Response<Body>;
+  type Error = S::Error;
+  type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+  fn poll_ready(&mut self, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+    self.inner.poll_ready(cx)
+  }
+
+  fn call(&mut self, request: Request<Body>) -> Self::Future {
+    #[cfg(feature = "devel")]
+    let request = {
+      let mut request = request;
+      if let Some(response) = dev::respond(&self.app, &mut request) {
+        return Box::pin(async move { Ok(response) });
+      }
+      request
+    };
+    Box::pin(self.inner.call(request))
+  }
+}
+
+#[cfg(feature = "devel")]
+mod dev {
+  use crate::core::app::TeraWeb;
+  use crate::core::client::{CLIENT_SCRIPT_PATH, ClientScript};
+  use crate::core::reload::{Heartbeat, SocketMessage, forward_reloads};
+  use crate::core::response::into_http_response;
+  use hyper::header::{self, HeaderValue};
+  use hyper::{Body, Method, Request, Response, StatusCode};
+  use tokio_tungstenite::WebSocketStream;
+  use tokio_tungstenite::tungstenite::Message;
+  use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+  use tokio_tungstenite::tungstenite::protocol::Role;
+
+  /// Answers `request` if it is for the client script or the WebSocket.
+  pub(super) fn respond(app: &TeraWeb, request: &mut Request<Body>) -> Option<Response<Body>> {
+    let options = &app.reloader.options;
+    if request.uri().path() == CLIENT_SCRIPT_PATH && request.method() == Method::GET {
+      let script = ClientScript::new(&options.ws_path, CLIENT_SCRIPT_PATH);
+      let if_none_match = request.headers().get(header::IF_NONE_MATCH).and_then(|value| value.to_str().ok());
+      let rendered = script.response(request.uri().query().unwrap_or(""), if_none_match);
+      return Some(into_http_response!(hyper::http; rendered, Body));
+    }
+    if request.uri().path() != options.ws_path {
+      return None;
+    }
+
+    let upgrade = request
+      .headers()
+      .get(header::UPGRADE)
+      .is_some_and(|upgrade| upgrade.as_bytes().eq_ignore_ascii_case(b"websocket"));
+    let Some(key) = request.headers().get(header::SEC_WEBSOCKET_KEY).filter(|_| upgrade) else {
+      let mut response = Response::new(Body::empty());
+      *response.status_mut() = StatusCode::BAD_REQUEST;
+      return Some(response);
+    };
+    let accept = derive_accept_key(key.as_bytes());
+
+    let reloads = app.subscribe_reloads();
+    let heartbeat = Heartbeat {
+      interval: options.heartbeat_interval,
+      timeout: options.client_timeout,
+    };
+    let upgrading = hyper::upgrade::on(request);
+    tokio::spawn(async move {
+      match upgrading.await {
+        Ok(upgraded) => {
+          let socket = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+          forward_reloads(socket, reloads, heartbeat).await;
+        }
+        Err(e) => log::warn!("WebSocket upgrade failed: {e}"),
+      }
+    });
+
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    let headers = response.headers_mut();
+    headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+    if let Ok(accept) = HeaderValue::try_from(accept) {
+      headers.insert(header::SEC_WEBSOCKET_ACCEPT, accept);
+    }
+    Some(response)
+  }
+
+  impl SocketMessage for Message {
+    fn ping() -> Self {
+      Message::Ping(Default::default())
+    }
+
+    fn text(text: &str) -> Self {
+      Message::text(text)
+    }
+
+    fn is_close(&self) -> bool {
+      self.is_close()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use hyper::service::service_fn;
+  use hyper::StatusCode;
+  use std::convert::Infallible;
+
+  #[tokio::test]
+  async fn test_templates_are_responses() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("index.html"), "<body><h1>{{ title }}</h1></body>").unwrap();
+    std::fs::write(temp_dir.path().join("broken.html"), "{{ missing }}").unwrap();
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .add_global("title", "Welcome")
+      .build()
+      .unwrap();
+
+    let pages = {
+      let app = app.clone();
+      service_fn(move |request: Request<Body>| {
+        let response = render(&app, &request, request.uri().path().trim_start_matches('/'), Context::new());
+        async move { Ok::<_, Infallible>(response) }
+      })
+    };
+    let mut service = dev_service(&app, pages);
+    let request = |path: &str| Request::builder().uri(path).body(Body::empty()).unwrap();
+
+    let response = service.call(request("/index.html")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html; charset=utf-8");
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert!(body.starts_with(b"<body><h1>Welcome</h1>"));
+    assert_eq!(String::from_utf8_lossy(&body).contains("data-snapfire-reload"), cfg!(feature = "devel"));
+
+    let response = service.call(request("/broken.html")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let response = service.call(request("/_snapfire/client.js")).await.unwrap();
+    assert_eq!(response.status().is_success(), cfg!(feature = "devel"));
+    if cfg!(feature = "devel") {
+      assert_eq!(response.headers()[header::CONTENT_TYPE], "text/javascript; charset=utf-8");
+      assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+      let etag = response.headers()[header::ETAG].clone();
+      let version = etag.to_str().unwrap().trim_matches('"').to_string();
+      let versioned = service.call(request(&format!("/_snapfire/client.js?v={version}"))).await.unwrap();
+      assert_eq!(versioned.headers()[header::CACHE_CONTROL], "public, max-age=31536000, immutable");
+      let revalidated = Request::builder()
+        .uri("/_snapfire/client.js")
+        .header(header::IF_NONE_MATCH, etag)
+        .body(Body::empty())
+        .unwrap();
+      assert_eq!(service.call(revalidated).await.unwrap().status(), StatusCode::NOT_MODIFIED);
+    }
+
+    let upgrade = Request::builder()
+      .uri("/_snapfire/ws")
+      .header(header::UPGRADE, "websocket")
+      .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+      .body(Body::empty())
+      .unwrap();
+    let response = service.call(upgrade).await.unwrap();
+    if cfg!(feature = "devel") {
+      assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+      assert_eq!(response.headers()[header::SEC_WEBSOCKET_ACCEPT], "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    } else {
+      assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  }
+}
@@ -0,0 +1,90 @@
+//! Serverless adapter. Only available with the `lambda` feature.
+//!
+//! Lambda handlers (`lambda_http`, or anything else speaking the `http`
+//! crate's types) have no long-lived server to mount middleware on, and no
+//! use for live reload. [`render`] renders a page for an `http::Request`
+//! into an `http::Response` such handlers return as is:
+//!
+//! ```rust,ignore
+//! use lambda_http::{Error, Request, run, service_fn};
+//! use snapfire::TeraWeb;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!   // Built once per cold start, from templates bundled with the function.
+//!   let app = TeraWeb::builder("templates/**/*.html").build()?;
+//!   run(service_fn(|event: Request| {
+//!     let app = app.clone();
+//!     async move { Ok::<_, Error>(snapfire::lambda::render(&app, &event, "index.html", tera::Context::new())) }
+//!   }))
+//!   .await
+//! }
+//! ```
+//!
+//! The page sees the request's `Host` header (for host globals) and path,
+//! but not the values Actix middleware would provide (`canonical_url`, the
+//! CSP nonce, consent). A `RenderedResponse` also converts into an
+//! `http::Response` directly.
+
+use crate::core::app::{RequestParts, TeraWeb};
+use crate::core::response::{Caching, RenderedResponse, into_http_response};
+use tera::Context;
+
+/// Renders `tpl` for `request`: a `200` with the page, a `500` if the render
+/// failed, or a `503` with `Retry-After` while its circuit is open.
+///
+/// # Arguments
+///
+/// * `app` - The app rendering the page.
+/// * `request` - The request, for its host and path.
+/// * `tpl` - The template to render.
+/// * `context` - The page's context.
+pub fn render<B>(app: &TeraWeb, request: &http::Request<B>, tpl: &str, context: Context) -> http::Response<Vec<u8>> {
+  let host = request
+    .headers()
+    .get(http::header::HOST)
+    .and_then(|host| host.to_str().ok())
+    .or_else(|| request.uri().authority().map(|authority| authority.as_str()));
+  let parts = RequestParts {
+    host,
+    path: Some(request.uri().path()),
+    ..RequestParts::default()
+  };
+  app.render(tpl, context).into_rendered_for(parts, Caching::default()).into()
+}
+
+impl From<RenderedResponse> for http::Response<Vec<u8>> {
+  fn from(rendered: RenderedResponse) -> Self {
+    into_http_response!(http; rendered, Vec<u8>)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_renders_for_the_requests_host() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("index.html"), "<h1>{{ brand }}</h1>").unwrap();
+    std::fs::write(temp_dir.path().join("broken.html"), "{{ missing }}").unwrap();
+    let mut acme = Context::new();
+    acme.insert("brand", "Acme");
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .add_global("brand", "Example")
+      .add_host_globals("acme.test", acme)
+      .build()
+      .unwrap();
+
+    let request = |host: &str| http::Request::builder().uri("/").header("host", host).body(()).unwrap();
+    let response = render(&app, &request("acme.test"), "index.html", Context::new());
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(response.headers()[http::header::CONTENT_TYPE], "text/html; charset=utf-8");
+    assert_eq!(response.body(), b"<h1>Acme</h1>");
+    let response = render(&app, &request("example.test"), "index.html", Context::new());
+    assert_eq!(response.body(), b"<h1>Example</h1>");
+
+    let response = render(&app, &request("acme.test"), "broken.html", Context::new());
+    assert_eq!(response.status(), http::StatusCode::INTERNAL_SERVER_ERROR);
+  }
+}
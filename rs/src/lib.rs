@@ -62,10 +62,44 @@
 //! ```sh
 //! cargo build --release
 //! ```
+//!
+//! ### Without Actix
+//!
+//! The Actix integration is the default `actix` feature. With
+//! `default-features = false`, `TeraWeb`, its globals, and the reload
+//! machinery build without actix-web, for other servers (see the `warp`,
+//! `hyper`, and `edge` features) or non-web binaries.
 
+#[cfg(feature = "actix")]
 pub mod actix;
+#[cfg(feature = "bench")]
+pub mod bench;
 pub mod core;
+#[cfg(feature = "edge")]
+pub mod edge;
 pub mod error;
+#[cfg(feature = "hyper")]
+pub mod hyper;
+#[cfg(feature = "lambda")]
+pub mod lambda;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "warp")]
+pub mod warp;
 
 pub use crate::core::app::{Template, TeraWeb, TeraWebBuilder};
+pub use crate::core::bundle::TemplateBundle;
+pub use crate::core::context_ext::SnapFireContextExt;
+pub use crate::core::hooks::RenderInfo;
+pub use crate::core::lenient::LenientRender;
+pub use crate::core::resolver::TemplateResolver;
+pub use crate::core::schema::ContextSchema;
+pub use crate::core::view::IntoTemplateContext;
 pub use crate::error::{Result, SnapFireError};
+pub use snapfire_macros::IntoTemplateContext;
+
+/// Paths the derive macros expand to; not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+  pub use tera::Context;
+}
@@ -0,0 +1,300 @@
+//! Which templates and blocks a test suite renders.
+//!
+//! With the `testing` feature, every render records the template, the
+//! parents it extends, the templates it includes, and the blocks that make
+//! it onto the page (a block overridden by a child only counts when the
+//! child calls `super()`). [`report`] compares the record with every
+//! template an app has loaded:
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! use snapfire::{TeraWeb, testing::coverage};
+//!
+//! let app = TeraWeb::builder("templates/**/*.html").build()?;
+//! // ... render pages through `app` ...
+//! let report = coverage::report(&app);
+//! report.save()?; // writes snapfire-coverage.json and prints a summary
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Renders are recorded process-wide, so the report covers every test run
+//! in the same binary before it. Call it last, e.g. from the end of a test
+//! that renders the site's pages, or from a custom test harness. Blocks are
+//! counted when their template is rendered, whatever `if` they sit in.
+
+use crate::core::app::TeraWeb;
+use crate::error::Result;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::LazyLock;
+use tera::Tera;
+use tera::ast::Node;
+
+/// Where [`CoverageReport::save`] writes the report.
+pub const REPORT_FILE: &str = "snapfire-coverage.json";
+
+/// What has been rendered so far, by template name.
+static RECORDED: LazyLock<Mutex<HashMap<String, Hits>>> = LazyLock::new(Default::default);
+
+#[derive(Default)]
+struct Hits {
+  /// How often the template was rendered itself, not as a parent or include.
+  renders: u64,
+  blocks: HashSet<String>,
+}
+
+/// Records a render of `tpl`.
+pub(crate) fn record(tera: &Tera, tpl: &str) {
+  let mut recorded = RECORDED.lock();
+  recorded.entry(tpl.to_string()).or_default().renders += 1;
+  record_used(tera, tpl, &mut recorded, &mut HashSet::new());
+}
+
+/// Records `tpl`, its parents, its effective blocks, and its includes as used.
+fn record_used(tera: &Tera, tpl: &str, recorded: &mut HashMap<String, Hits>, visited: &mut HashSet<String>) {
+  if !visited.insert(tpl.to_string()) {
+    return;
+  }
+  let Ok(template) = tera.get_template(tpl) else {
+    return;
+  };
+  recorded.entry(tpl.to_string()).or_default();
+  for parent in &template.parents {
+    recorded.entry(parent.clone()).or_default();
+  }
+  // The template, then its parents, nearest first.
+  let chain: Vec<_> = std::iter::once(tpl)
+    .chain(template.parents.iter().map(String::as_str))
+    .filter_map(|name| tera.get_template(name).ok())
+    .collect();
+  let block_names: HashSet<&String> = chain.iter().flat_map(|template| template.blocks.keys()).collect();
+  for name in block_names {
+    // The most derived definition renders, and the ones it reaches with `super()`.
+    for template in chain.iter().filter(|template| template.blocks.contains_key(name)) {
+      recorded.entry(template.name.clone()).or_default().blocks.insert(name.clone());
+      if !calls_super(&template.blocks[name].body) {
+        break;
+      }
+    }
+  }
+
+  let mut includes = Vec::new();
+  for template in &chain {
+    collect_includes(&template.ast, &mut includes);
+  }
+  for names in includes {
+    if let Some(name) = names.iter().find(|name| tera.get_template(name).is_ok()) {
+      record_used(tera, name, recorded, visited);
+    }
+  }
+}
+
+/// Whether `nodes` call `super()`, outside nested blocks.
+fn calls_super(nodes: &[Node]) -> bool {
+  nodes.iter().any(|node| match node {
+    Node::Super => true,
+    Node::FilterSection(_, section, _) => calls_super(&section.body),
+    Node::Forloop(_, forloop, _) => {
+      calls_super(&forloop.body) || forloop.empty_body.as_deref().is_some_and(calls_super)
+    }
+    Node::If(branches, _) => {
+      branches.conditions.iter().any(|(_, _, body)| calls_super(body))
+        || branches.otherwise.as_ref().is_some_and(|(_, body)| calls_super(body))
+    }
+    _ => false,
+  })
+}
+
+/// The candidate names of every `include` in `nodes`.
+fn collect_includes<'a>(nodes: &'a [Node], includes: &mut Vec<&'a [String]>) {
+  for node in nodes {
+    match node {
+      Node::Include(_, names, _) => includes.push(names),
+      Node::Block(_, block, _) => collect_includes(&block.body, includes),
+      Node::FilterSection(_, section, _) => collect_includes(&section.body, includes),
+      Node::Forloop(_, forloop, _) => {
+        collect_includes(&forloop.body, includes);
+        if let Some(body) = &forloop.empty_body {
+          collect_includes(body, includes);
+        }
+      }
+      Node::If(branches, _) => {
+        for (_, _, body) in &branches.conditions {
+          collect_includes(body, includes);
+        }
+        if let Some((_, body)) = &branches.otherwise {
+          collect_includes(body, includes);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Forgets every recorded render, e.g. between two measured runs.
+pub fn reset() {
+  RECORDED.lock().clear();
+}
+
+/// The coverage of one template.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateCoverage {
+  pub name: String,
+  /// Whether the template was rendered, directly or as a parent or include.
+  pub covered: bool,
+  /// How often it was rendered directly.
+  pub renders: u64,
+  /// Whether each block the template defines was rendered.
+  pub blocks: BTreeMap<String, bool>,
+}
+
+/// The coverage of every template of an app.
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageReport {
+  pub covered: usize,
+  pub total: usize,
+  /// Every template, by name.
+  pub templates: Vec<TemplateCoverage>,
+}
+
+/// The coverage of `app`'s templates by the renders recorded so far.
+pub fn report(app: &TeraWeb) -> CoverageReport {
+  let tera = app.tera.read();
+  let recorded = RECORDED.lock();
+  let mut names: Vec<_> = tera.get_template_names().filter(|name| !name.starts_with("__tera")).collect();
+  names.sort_unstable();
+
+  let templates: Vec<_> = names
+    .into_iter()
+    .map(|name| {
+      let hits = recorded.get(name);
+      let blocks = tera
+        .get_template(name)
+        .map(|template| {
+          template
+            .blocks
+            .keys()
+            .map(|block| (block.clone(), hits.is_some_and(|hits| hits.blocks.contains(block))))
+            .collect()
+        })
+        .unwrap_or_default();
+      TemplateCoverage {
+        name: name.to_string(),
+        covered: hits.is_some(),
+        renders: hits.map_or(0, |hits| hits.renders),
+        blocks,
+      }
+    })
+    .collect();
+  CoverageReport {
+    covered: templates.iter().filter(|template| template.covered).count(),
+    total: templates.len(),
+    templates,
+  }
+}
+
+impl CoverageReport {
+  /// The templates no test rendered at all.
+  pub fn uncovered(&self) -> impl Iterator<Item = &str> {
+    self
+      .templates
+      .iter()
+      .filter(|template| !template.covered)
+      .map(|template| template.name.as_str())
+  }
+
+  /// A short, human-readable summary, e.g. for the console.
+  pub fn summary(&self) -> String {
+    let percent = if self.total == 0 {
+      100.0
+    } else {
+      self.covered as f64 * 100.0 / self.total as f64
+    };
+    let mut summary = format!(
+      "Template coverage: {} of {} templates rendered ({percent:.0}%)\n",
+      self.covered, self.total
+    );
+    let uncovered: Vec<_> = self.uncovered().collect();
+    if !uncovered.is_empty() {
+      let _ = writeln!(summary, "  never rendered: {}", uncovered.join(", "));
+    }
+    let blocks: Vec<_> = self
+      .templates
+      .iter()
+      .filter(|template| template.covered)
+      .flat_map(|template| {
+        template
+          .blocks
+          .iter()
+          .filter(|(_, rendered)| !**rendered)
+          .map(move |(block, _)| format!("{}#{block}", template.name))
+      })
+      .collect();
+    if !blocks.is_empty() {
+      let _ = writeln!(summary, "  blocks never rendered: {}", blocks.join(", "));
+    }
+    summary
+  }
+
+  /// Writes the report as JSON to `path`.
+  pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+    let json = serde_json::to_string_pretty(self).expect("the report serializes");
+    std::fs::write(path, json)?;
+    Ok(())
+  }
+
+  /// Writes the report to [`REPORT_FILE`] in the working directory and
+  /// prints the summary.
+  pub fn save(&self) -> Result<()> {
+    self.write(REPORT_FILE)?;
+    print!("{}", self.summary());
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_reports_rendered_templates_and_blocks() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    // Names no other test uses, since renders are recorded process-wide.
+    for (name, source) in [
+      (
+        "coverage_base.html",
+        "{% block head %}{% endblock %}{% block body %}base{% endblock %}{% block footer %}{% endblock %}",
+      ),
+      (
+        "coverage_page.html",
+        "{% extends \"coverage_base.html\" %}{% block body %}{{ super() }}{% include \"coverage_card.html\" %}{% endblock %}{% block footer %}page{% endblock %}",
+      ),
+      ("coverage_card.html", "{% block card %}card{% endblock %}"),
+      ("coverage_admin.html", "admin"),
+    ] {
+      std::fs::write(temp_dir.path().join(name), source).unwrap();
+    }
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path).build().unwrap();
+    app.render_to_string("coverage_page.html", tera::Context::new()).unwrap();
+
+    let report = report(&app);
+    let coverage = |name: &str| report.templates.iter().find(|template| template.name == name).unwrap();
+    assert!(report.uncovered().any(|name| name == "coverage_admin.html"));
+    assert_eq!(coverage("coverage_page.html").renders, 1);
+    assert!(coverage("coverage_card.html").covered && coverage("coverage_card.html").renders == 0);
+    assert_eq!(
+      coverage("coverage_base.html").blocks,
+      BTreeMap::from([
+        ("body".to_string(), true),
+        ("footer".to_string(), false),
+        ("head".to_string(), true)
+      ])
+    );
+    assert!(report.summary().contains("coverage_base.html#footer"));
+  }
+}
@@ -0,0 +1,315 @@
+//! Property-based fuzzing of templates.
+//!
+//! [`Fuzzer`] generates random contexts shaped like an example context and
+//! renders a template with each of them, collecting panics, render errors
+//! (such as indexing into an empty list) and markup that reached the output
+//! unescaped. Runs are deterministic for a given seed, so failures reproduce.
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! use snapfire::{TeraWeb, testing::fuzz::Fuzzer};
+//! use tera::Context;
+//!
+//! let app = TeraWeb::builder("templates/**/*.html").build()?;
+//! let mut example = Context::new();
+//! example.insert("user", &serde_json::json!({ "name": "Alice", "tags": ["admin"] }));
+//!
+//! Fuzzer::new(&app, "user.html").example(example).iterations(500).run().assert_clean();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::core::app::TeraWeb;
+use crate::error::{error_chain, panic_message};
+use serde_json::{Map, Number, Value};
+use std::fmt;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use tera::Context;
+
+/// A marker injected into generated strings; seeing it verbatim in the output
+/// means a value was rendered without escaping.
+const MARKUP_PROBE: &str = "<snapfire-probe>";
+
+/// The default number of generated contexts per run.
+const DEFAULT_ITERATIONS: u32 = 100;
+/// The longest generated list.
+const MAX_LIST_LEN: u64 = 5;
+
+/// Tricky strings mixed into the generated data.
+const STRING_SAMPLES: &[&str] = &[
+  "",
+  " ",
+  "plain text",
+  "\"quoted\" & 'apostrophes'",
+  "ünïcödé ✓ 漢字 🔥",
+  "line\nbreaks\r\n",
+  "{{ not_a_variable }}",
+  "a very long string that goes on and on and on and on and on and on and on and on and on",
+];
+
+/// Configures and runs a fuzzing session for one template.
+pub struct Fuzzer<'a> {
+  app: &'a TeraWeb,
+  template: String,
+  example: Value,
+  iterations: u32,
+  seed: u64,
+}
+
+impl<'a> Fuzzer<'a> {
+  /// Creates a fuzzer for `template`, rendered through `app`.
+  pub fn new(app: &'a TeraWeb, template: &str) -> Self {
+    Self {
+      app,
+      template: template.to_string(),
+      example: Value::Object(Map::new()),
+      iterations: DEFAULT_ITERATIONS,
+      seed: 0x5eed,
+    }
+  }
+
+  /// Sets the example context whose shape the generated contexts follow.
+  ///
+  /// Every generated context has the same keys and value types as the example;
+  /// only the values (string contents, numbers, list lengths) vary.
+  pub fn example(mut self, example: Context) -> Self {
+    self.example = example.into_json();
+    self
+  }
+
+  /// Sets the number of generated contexts. Defaults to 100.
+  pub fn iterations(mut self, iterations: u32) -> Self {
+    self.iterations = iterations;
+    self
+  }
+
+  /// Sets the random seed, to reproduce or vary a run.
+  pub fn seed(mut self, seed: u64) -> Self {
+    self.seed = seed;
+    self
+  }
+
+  /// Renders the template once per generated context and reports every failure.
+  pub fn run(self) -> FuzzReport {
+    let mut rng = Rng::new(self.seed);
+    let mut failures = Vec::new();
+
+    for iteration in 0..self.iterations {
+      let generated = generate(&self.example, &mut rng);
+      let context = match Context::from_value(generated.clone()) {
+        Ok(context) => context,
+        Err(e) => {
+          failures.push(FuzzFailure {
+            iteration,
+            kind: FailureKind::RenderError(e.to_string()),
+            context: generated,
+          });
+          continue;
+        }
+      };
+
+      let rendered = catch_unwind(AssertUnwindSafe(|| self.app.render_to_string(&self.template, context)));
+      let kind = match rendered {
+        Err(panic) => Some(FailureKind::Panic(panic_message(panic.as_ref()))),
+        Ok(Err(e)) => Some(FailureKind::RenderError(error_chain(&e))),
+        Ok(Ok(output)) if output.contains(MARKUP_PROBE) => Some(FailureKind::UnescapedMarkup),
+        Ok(Ok(_)) => None,
+      };
+
+      if let Some(kind) = kind {
+        failures.push(FuzzFailure {
+          iteration,
+          kind,
+          context: generated,
+        });
+      }
+    }
+
+    FuzzReport {
+      template: self.template,
+      iterations: self.iterations,
+      failures,
+    }
+  }
+}
+
+/// The outcome of a fuzzing run.
+#[derive(Debug)]
+pub struct FuzzReport {
+  /// The template that was fuzzed.
+  pub template: String,
+  /// How many contexts were rendered.
+  pub iterations: u32,
+  /// Every failing render, in order.
+  pub failures: Vec<FuzzFailure>,
+}
+
+impl FuzzReport {
+  /// Whether every render succeeded cleanly.
+  pub fn is_clean(&self) -> bool {
+    self.failures.is_empty()
+  }
+
+  /// Panics with a readable summary if any render failed.
+  ///
+  /// Intended for use at the end of a test.
+  pub fn assert_clean(&self) {
+    if !self.is_clean() {
+      panic!("{}", self);
+    }
+  }
+}
+
+impl fmt::Display for FuzzReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(
+      f,
+      "fuzzing `{}`: {} of {} renders failed",
+      self.template,
+      self.failures.len(),
+      self.iterations
+    )?;
+    for failure in self.failures.iter().take(5) {
+      writeln!(f, "  #{}: {}", failure.iteration, failure.kind)?;
+      writeln!(f, "    context: {}", failure.context)?;
+    }
+    if self.failures.len() > 5 {
+      writeln!(f, "  ... and {} more", self.failures.len() - 5)?;
+    }
+    Ok(())
+  }
+}
+
+/// A single failing render.
+#[derive(Debug, Clone)]
+pub struct FuzzFailure {
+  /// The iteration that failed; rerunning with the same seed reproduces it.
+  pub iteration: u32,
+  /// What went wrong.
+  pub kind: FailureKind,
+  /// The generated context that triggered the failure.
+  pub context: Value,
+}
+
+/// The ways a fuzzed render can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureKind {
+  /// The render panicked (typically inside a custom filter or function).
+  Panic(String),
+  /// Tera returned an error, such as a missing variable or bad index.
+  RenderError(String),
+  /// A generated string containing markup appeared in the output unescaped.
+  UnescapedMarkup,
+}
+
+impl fmt::Display for FailureKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FailureKind::Panic(message) => write!(f, "panic: {}", message),
+      FailureKind::RenderError(message) => write!(f, "render error: {}", message),
+      FailureKind::UnescapedMarkup => write!(f, "unescaped markup `{}` in output", MARKUP_PROBE),
+    }
+  }
+}
+
+/// Generates a value with the same shape as `example`.
+fn generate(example: &Value, rng: &mut Rng) -> Value {
+  match example {
+    Value::Null => Value::Null,
+    Value::Bool(_) => Value::Bool(rng.below(2) == 0),
+    Value::Number(n) if n.is_f64() => {
+      let samples = [0.0, -1.5, 0.1, 1e12, f64::from(rng.below(1000) as u32) / 7.0];
+      Number::from_f64(samples[rng.below(samples.len() as u64) as usize]).map_or(Value::Null, Value::Number)
+    }
+    Value::Number(_) => {
+      let samples = [0, 1, -1, i64::from(i32::MAX), i64::MIN, rng.below(1000) as i64];
+      Value::from(samples[rng.below(samples.len() as u64) as usize])
+    }
+    Value::String(_) => Value::String(generate_string(rng)),
+    Value::Array(items) => match items.first() {
+      // Lists keep the shape of their first element, with a random length (possibly empty).
+      Some(item) => Value::Array((0..rng.below(MAX_LIST_LEN + 1)).map(|_| generate(item, rng)).collect()),
+      None => Value::Array(Vec::new()),
+    },
+    Value::Object(fields) => Value::Object(
+      fields
+        .iter()
+        .map(|(key, value)| (key.clone(), generate(value, rng)))
+        .collect(),
+    ),
+  }
+}
+
+fn generate_string(rng: &mut Rng) -> String {
+  // About one string in four carries the markup probe.
+  if rng.below(4) == 0 {
+    return format!("{}{}", MARKUP_PROBE, rng.below(1000));
+  }
+  STRING_SAMPLES[rng.below(STRING_SAMPLES.len() as u64) as usize].to_string()
+}
+
+/// A small, deterministic xorshift generator; plenty for test data.
+struct Rng(u64);
+
+impl Rng {
+  fn new(seed: u64) -> Self {
+    // Xorshift must never be seeded with zero.
+    Self(seed.max(1))
+  }
+
+  fn next(&mut self) -> u64 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    self.0
+  }
+
+  fn below(&mut self, bound: u64) -> u64 {
+    self.next() % bound
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+
+  fn fuzz(template: &str, example: Value) -> FuzzReport {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("page.html"), template).unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let app = TeraWeb::builder(&glob_path).build().unwrap();
+
+    Fuzzer::new(&app, "page.html")
+      .example(Context::from_value(example).unwrap())
+      .iterations(50)
+      .run()
+  }
+
+  #[test]
+  fn test_clean_template_passes() {
+    let report = fuzz(
+      "{{ user.name }}{% for tag in user.tags %}<i>{{ tag }}</i>{% endfor %}",
+      serde_json::json!({ "user": { "name": "Alice", "tags": ["a"] } }),
+    );
+    report.assert_clean();
+  }
+
+  #[test]
+  fn test_detects_unescaped_markup() {
+    let report = fuzz("{{ bio | safe }}", serde_json::json!({ "bio": "hello" }));
+    assert!(report.failures.iter().any(|f| f.kind == FailureKind::UnescapedMarkup));
+  }
+
+  #[test]
+  fn test_detects_errors_on_empty_lists() {
+    let report = fuzz("{{ items[0] }}", serde_json::json!({ "items": ["first"] }));
+    assert!(
+      report
+        .failures
+        .iter()
+        .any(|f| matches!(f.kind, FailureKind::RenderError(_)))
+    );
+  }
+}
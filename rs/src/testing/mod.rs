@@ -0,0 +1,10 @@
+//! Utilities for testing applications built on SnapFire, available with the
+//! `testing` feature.
+
+pub mod coverage;
+pub mod fuzz;
+#[cfg(feature = "devel")]
+pub mod reload;
+
+#[cfg(feature = "devel")]
+pub use reload::FakeReloader;
@@ -0,0 +1,118 @@
+//! Deterministic live-reload events for tests.
+//!
+//! Tests of live reloading that write files and sleep depend on the file
+//! watcher's timing. [`TeraWeb::with_fake_reloader`] swaps the watcher for a
+//! [`FakeReloader`], whose events happen synchronously when the test asks:
+//!
+//! ```rust,no_run
+//! # fn main() -> snapfire::Result<()> {
+//! let (app, reloader) = snapfire::TeraWeb::builder("templates/**/*.html").build()?.with_fake_reloader()?;
+//! // ... serve `app`, connect a WebSocket client ...
+//! reloader.reload_templates()?;
+//! assert_eq!(reloader.take_messages(), ["reload"]);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::core::app::{SharedTera, TeraWeb, reload_templates};
+use crate::core::broadcast::Broadcaster;
+use crate::core::reload::{DevReloader, ReloadMessage, ReloadReceiver};
+use crate::error::Result;
+use std::sync::Arc;
+
+/// Emits live-reload events on demand, in place of the file watcher.
+pub struct FakeReloader {
+  tera: SharedTera,
+  broadcaster: Broadcaster,
+  /// Records every message sent, for `take_messages`.
+  sent: ReloadReceiver,
+}
+
+impl FakeReloader {
+  /// Re-parses the templates now, then tells browsers to reload. On a parse
+  /// error the previous templates stay active and nothing is sent.
+  pub fn reload_templates(&self) -> Result<()> {
+    reload_templates(&self.tera, "fake reloader")?;
+    self.send(ReloadMessage::Reload);
+    Ok(())
+  }
+
+  /// Tells browsers to reload the page.
+  pub fn reload_page(&self) {
+    self.send(ReloadMessage::Reload);
+  }
+
+  /// Tells browsers to reload their stylesheets.
+  pub fn reload_css(&self) {
+    self.send(ReloadMessage::ReloadCss);
+  }
+
+  /// The number of browsers connected to the live-reload WebSocket, so a test
+  /// can wait for its client before emitting events.
+  pub fn clients(&self) -> usize {
+    // One receiver is our own record of sent messages.
+    self.broadcaster.receiver_count().saturating_sub(1)
+  }
+
+  /// The messages sent since the last call, as sent over the WebSocket
+  /// (`reload`, `reload-css`, or the JSON of a `dev_push`).
+  pub fn take_messages(&self) -> Vec<String> {
+    std::iter::from_fn(|| self.sent.try_recv().ok()).map(|message| message.as_str().to_string()).collect()
+  }
+
+  fn send(&self, message: ReloadMessage) {
+    // There's always our own receiver, so this can't fail.
+    let _ = self.broadcaster.send(message);
+  }
+}
+
+impl TeraWeb {
+  /// Replaces the live-reload file watcher with a [`FakeReloader`].
+  ///
+  /// Call this before cloning the app into a server: clones made earlier
+  /// keep the real watcher.
+  pub fn with_fake_reloader(mut self) -> Result<(Self, FakeReloader)> {
+    let options = self.reloader.options.clone();
+    let reloader = DevReloader::with_sources(Arc::clone(&self.tera), Vec::new(), Vec::new(), options)?;
+    let fake = FakeReloader {
+      tera: Arc::clone(&self.tera),
+      broadcaster: reloader.broadcaster.clone(),
+      sent: reloader.broadcaster.subscribe(),
+    };
+    self.reloader = Arc::new(reloader);
+    Ok((self, fake))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::tempdir;
+  use tera::Context;
+
+  #[test]
+  fn test_fake_reloader_is_synchronous() {
+    let temp_dir = tempdir().unwrap();
+    let template_path = temp_dir.path().join("index.html");
+    fs::write(&template_path, "old").unwrap();
+    let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+    let (app, reloader) = TeraWeb::builder(&glob_path).build().unwrap().with_fake_reloader().unwrap();
+
+    fs::write(&template_path, "new").unwrap();
+    reloader.reload_templates().unwrap();
+    // No waiting: the new template is active as soon as the call returns.
+    assert_eq!(app.render_to_string("index.html", Context::new()).unwrap(), "new");
+    let audit = app.template_audit();
+    assert_eq!((audit.len(), audit[0].actor.as_str()), (1, "fake reloader"));
+    assert_eq!(audit[0].modified, ["index.html"]);
+
+    reloader.reload_css();
+    assert_eq!(reloader.take_messages(), ["reload", "reload-css"]);
+    assert!(reloader.take_messages().is_empty());
+
+    fs::write(&template_path, "{% if %}").unwrap();
+    assert!(reloader.reload_templates().is_err());
+    assert!(reloader.take_messages().is_empty());
+  }
+}
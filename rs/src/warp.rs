@@ -0,0 +1,189 @@
+//! Warp integration. Only available with the `warp` feature.
+//!
+//! A [`Template`] is a warp `Reply`, so handlers return `app.render(...)` as
+//! they would with Actix, and [`dev_routes`] serves the live-reload WebSocket
+//! and client script in dev mode:
+//!
+//! ```rust,no_run
+//! use snapfire::TeraWeb;
+//! use tera::Context;
+//! use warp::Filter;
+//!
+//! # async fn run() {
+//! let app = TeraWeb::builder("templates/**/*.html").build().unwrap();
+//! let index = {
+//!   let app = app.clone();
+//!   warp::path::end().map(move || app.render("index.html", Context::new()))
+//! };
+//! warp::serve(snapfire::warp::dev_routes(&app).or(index)).run(([127, 0, 0, 1], 3000)).await;
+//! # }
+//! ```
+//!
+//! Without the `devel` feature, `dev_routes` matches no request, so it can be
+//! mounted unconditionally. In dev mode, the reply injects the live-reload
+//! script into HTML pages itself, as the `InjectSnapFireScript` middleware
+//! does for Actix.
+//!
+//! Warp replies don't see the request, so pages render without its host,
+//! path, or the values Actix middleware provides (`canonical_url`, host
+//! globals, the CSP nonce).
+
+use crate::core::app::{Template, TeraWeb};
+#[cfg(feature = "devel")]
+use crate::core::response::RenderedResponse;
+use crate::core::response::into_http_response;
+use warp::filters::BoxedFilter;
+use warp::reply::{Reply, Response};
+use warp::Filter;
+
+impl Reply for Template {
+  fn into_response(self) -> Response {
+    #[cfg(feature = "devel")]
+    let app = self.app_state.clone();
+    let rendered = self.into_rendered();
+    #[cfg(feature = "devel")]
+    let rendered = if rendered.status == 500 {
+      rendered
+    } else {
+      RenderedResponse {
+        body: crate::core::client::with_reload_script(&app, rendered.body),
+        ..rendered
+      }
+    };
+    into_http_response!(warp::http; rendered, warp::hyper::Body)
+  }
+}
+
+
+/// The live-reload routes: the WebSocket at the builder's `ws_path`, and the
+/// client script at `/_snapfire/client.js`. Without the `devel` feature, it
+/// rejects every request.
+pub fn dev_routes(app: &TeraWeb) -> BoxedFilter<(Response,)> {
+  #[cfg(feature = "devel")]
+  {
+    use crate::core::client::{CLIENT_SCRIPT_PATH, ClientScript};
+    use crate::core::reload::{Heartbeat, forward_reloads};
+
+    let options = &app.reloader.options;
+    log::info!("🔥 SnapFire devel enabled. Attaching WebSocket at {}", options.ws_path);
+    let heartbeat = Heartbeat {
+      interval: options.heartbeat_interval,
+      timeout: options.client_timeout,
+    };
+
+    let socket_app = app.clone();
+    let socket = exact_path(options.ws_path.clone())
+      .and(warp::ws())
+      .map(move |ws: warp::ws::Ws| {
+        let reloads = socket_app.subscribe_reloads();
+        ws.on_upgrade(move |socket| forward_reloads(socket, reloads, heartbeat))
+          .into_response()
+      });
+
+    let script = std::sync::Arc::new(ClientScript::new(&options.ws_path, CLIENT_SCRIPT_PATH));
+    let query = warp::query::raw().or(warp::any().map(String::new)).unify();
+    let client = exact_path(CLIENT_SCRIPT_PATH.to_string())
+      .and(warp::get())
+      .and(query)
+      .and(warp::header::optional::<String>("if-none-match"))
+      .map(move |query: String, if_none_match: Option<String>| {
+        into_http_response!(warp::http; script.response(&query, if_none_match.as_deref()), warp::hyper::Body)
+      });
+
+    socket.or(client).unify().boxed()
+  }
+  #[cfg(not(feature = "devel"))]
+  {
+    let _ = app;
+    warp::any()
+      .and_then(|| async { Err::<Response, _>(warp::reject::not_found()) })
+      .boxed()
+  }
+}
+
+/// Matches requests for exactly `path`.
+#[cfg(feature = "devel")]
+fn exact_path(path: String) -> BoxedFilter<()> {
+  warp::path::full()
+    .and_then(move |full: warp::path::FullPath| {
+      let matches = full.as_str() == path;
+      async move { if matches { Ok(()) } else { Err(warp::reject::not_found()) } }
+    })
+    .untuple_one()
+    .boxed()
+}
+
+#[cfg(feature = "devel")]
+mod dev {
+  use crate::core::reload::SocketMessage;
+  use warp::ws::Message;
+
+  impl SocketMessage for Message {
+    fn ping() -> Self {
+      Message::ping(Vec::new())
+    }
+
+    fn text(text: &str) -> Self {
+      Message::text(text)
+    }
+
+    fn is_close(&self) -> bool {
+      self.is_close()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tera::Context;
+  use warp::http::{StatusCode, header};
+
+  #[tokio::test]
+  async fn test_templates_are_replies() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("index.html"), "<body><h1>{{ title }}</h1></body>").unwrap();
+    std::fs::write(temp_dir.path().join("broken.html"), "{{ missing }}").unwrap();
+    let app = TeraWeb::builder(temp_dir.path().join("*.html").to_str().unwrap())
+      .add_global("title", "Welcome")
+      .build()
+      .unwrap();
+
+    let routes = {
+      let app = app.clone();
+      warp::path::param()
+        .and(warp::path::end())
+        .map(move |page: String| app.render(&page, Context::new()))
+    };
+    let routes = dev_routes(&app).or(routes);
+
+    let response = warp::test::request().path("/index.html").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html; charset=utf-8");
+    let body = String::from_utf8(response.body().to_vec()).unwrap();
+    assert!(body.starts_with("<body><h1>Welcome</h1>"));
+    assert_eq!(body.contains("data-snapfire-reload"), cfg!(feature = "devel"));
+
+    let response = warp::test::request().path("/broken.html").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let response = warp::test::request().path("/_snapfire/client.js").reply(&routes).await;
+    assert_eq!(response.status().is_success(), cfg!(feature = "devel"));
+    if cfg!(feature = "devel") {
+      assert_eq!(response.headers()[header::CONTENT_TYPE], "text/javascript; charset=utf-8");
+      assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+      let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+      let versioned = warp::test::request()
+        .path(&format!("/_snapfire/client.js?v={}", etag.trim_matches('"')))
+        .reply(&routes)
+        .await;
+      assert_eq!(versioned.headers()[header::CACHE_CONTROL], "public, max-age=31536000, immutable");
+      let revalidated = warp::test::request()
+        .path("/_snapfire/client.js")
+        .header("if-none-match", &etag)
+        .reply(&routes)
+        .await;
+      assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+    }
+  }
+}
@@ -4,7 +4,9 @@ use std::time::Duration;
 use std::{collections::HashSet, fs};
 
 use actix_web::{App, HttpResponse, HttpServer, rt, test, web};
-use futures_util::{SinkExt, StreamExt};
+use futures_util::StreamExt;
+#[cfg(feature = "devel")]
+use snapfire::core::reload::{ChangeSink, WatchSource};
 use snapfire::{TeraWeb, actix::dev::InjectSnapFireScript};
 use tempfile::tempdir;
 use tokio::net::TcpStream;
@@ -14,6 +16,7 @@ use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungsten
 use crate::common::test_handler;
 
 // Helper to create a fully configured dev-mode server for testing
+#[cfg(feature = "devel")]
 async fn setup_dev_server() -> (
   impl actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>,
   String,            // base_url
@@ -52,6 +55,7 @@ async fn setup_dev_server() -> (
 }
 
 // Helper function for the websocket test to get the next meaningful message
+#[cfg(feature = "devel")]
 async fn get_next_text_message(ws_stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>) -> String {
   loop {
     let msg = timeout(Duration::from_secs(2), ws_stream.next())
@@ -114,6 +118,391 @@ async fn test_middleware_injects_script() {
   assert!(body_str.contains("window.location.reload()"));
 }
 
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_external_client_script_is_cacheable() {
+  let temp_dir = tempdir().unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).external_script(true).build().unwrap();
+
+  let app_state_clone = snapfire_app.clone();
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap(InjectSnapFireScript::default())
+      .configure(move |cfg| app_state_clone.configure_routes(cfg))
+      .route("/", web::get().to(simple_html_handler)),
+  )
+  .await;
+
+  // The page references the script instead of inlining it.
+  let req = test::TestRequest::get().uri("/").to_request();
+  let body = test::read_body(test::call_service(&app, req).await).await;
+  let body_str = std::str::from_utf8(&body).unwrap();
+  assert!(!body_str.contains("window.location.reload()"));
+  let start = body_str.find("<script src=\"").unwrap() + "<script src=\"".len();
+  let script_url = &body_str[start..start + body_str[start..].find('"').unwrap()];
+  assert!(script_url.starts_with("/_snapfire/client.js?v="));
+
+  // The versioned URL is immutable.
+  let resp = test::call_service(&app, test::TestRequest::get().uri(script_url).to_request()).await;
+  assert!(resp.status().is_success());
+  assert!(resp.headers().get("cache-control").unwrap().to_str().unwrap().contains("immutable"));
+  let etag = resp.headers().get("etag").unwrap().clone();
+  let script = test::read_body(resp).await;
+  assert!(std::str::from_utf8(&script).unwrap().contains("\"/_snapfire/ws\""));
+
+  // The plain URL revalidates against the ETag.
+  let req = test::TestRequest::get()
+    .uri("/_snapfire/client.js")
+    .insert_header(("if-none-match", etag))
+    .to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_MODIFIED);
+  assert_eq!(resp.headers().get("cache-control").unwrap(), "no-cache");
+}
+
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_routes_mounted_in_scope() {
+  let temp_dir = tempdir().unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).external_script(true).build().unwrap();
+
+  let app_state_clone = snapfire_app.clone();
+  let app = test::init_service(
+    App::new().app_data(web::Data::new(snapfire_app)).service(
+      web::scope("/app")
+        .wrap(InjectSnapFireScript::default())
+        .configure(move |cfg| app_state_clone.configure_routes(cfg))
+        .route("/", web::get().to(simple_html_handler)),
+    ),
+  )
+  .await;
+
+  // The page points at the script inside the scope...
+  let req = test::TestRequest::get().uri("/app/").to_request();
+  let body = test::read_body(test::call_service(&app, req).await).await;
+  let body_str = std::str::from_utf8(&body).unwrap();
+  let start = body_str.find("<script src=\"").unwrap() + "<script src=\"".len();
+  let script_url = &body_str[start..start + body_str[start..].find('"').unwrap()];
+  assert!(script_url.starts_with("/app/_snapfire/client.js?v="));
+
+  // ...which connects to the WebSocket inside the scope.
+  let resp = test::call_service(&app, test::TestRequest::get().uri(script_url).to_request()).await;
+  assert!(resp.headers().get("cache-control").unwrap().to_str().unwrap().contains("immutable"));
+  let script = test::read_body(resp).await;
+  assert!(std::str::from_utf8(&script).unwrap().contains("\"/app/_snapfire/ws\""));
+}
+
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_dev_server_serves_pages_with_the_client() {
+  let (server, _base_url, _temp_dir) = setup_dev_server().await;
+
+  let req = test::TestRequest::get().uri("/").to_request();
+  let body = test::read_body(test::call_service(&server, req).await).await;
+  let body_str = std::str::from_utf8(&body).unwrap();
+  assert!(body_str.contains("Hello"));
+  assert!(body_str.contains("window.location.reload()"));
+
+  let req = test::TestRequest::get().uri("/_snapfire/client.js").to_request();
+  let resp = test::call_service(&server, req).await;
+  assert!(resp.status().is_success());
+  assert!(resp.headers().get("content-type").unwrap().to_str().unwrap().contains("javascript"));
+}
+
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_styleguide_renders_components_with_fixtures() {
+  let temp_dir = tempdir().unwrap();
+  let components = temp_dir.path().join("components");
+  fs::create_dir(&components).unwrap();
+  fs::write(components.join("button.html"), "<button>{{ label }}</button>").unwrap();
+  fs::write(components.join("button.json"), r#"[{ "label": "Save" }, { "label": "Cancel" }]"#).unwrap();
+  fs::write(components.join("card.html"), "<div class=\"card\">{{ title }}</div>").unwrap();
+  fs::write(components.join("_macros.html"), "hidden").unwrap();
+  fs::write(components.join("badge.html"), "<em>{{ text }}</em>").unwrap();
+  fs::create_dir(components.join("badge.html.fixtures")).unwrap();
+  fs::write(components.join("badge.html.fixtures/new.json"), r#"{ "text": "New" }"#).unwrap();
+  fs::write(temp_dir.path().join("index.html"), "page").unwrap();
+  fs::write(temp_dir.path().join("data.json"), "{}").unwrap();
+  let glob_path = temp_dir.path().join("**/*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .styleguide("components", ["/static/site.css"])
+    .build()
+    .unwrap();
+
+  // Fixtures reload the gallery; other JSON files don't.
+  let sink = snapfire_app.change_sink();
+  assert!(sink.file_changed(&components.join("button.json")));
+  assert!(sink.file_changed(&components.join("badge.html.fixtures/new.json")));
+  assert!(!sink.file_changed(&temp_dir.path().join("data.json")));
+
+  let app_state = snapfire_app.clone();
+  let app = test::init_service(App::new().configure(move |cfg| app_state.configure_routes(cfg))).await;
+  let req = test::TestRequest::get().uri("/_snapfire/styleguide").to_request();
+  let body = test::call_and_read_body(&app, req).await;
+  let body = std::str::from_utf8(&body).unwrap();
+
+  assert!(body.contains("<link rel=\"stylesheet\" href=\"&#x2F;static&#x2F;site.css\">"));
+  assert!(body.contains("<h3>Example 1</h3><button>Save</button></div><div class=\"snapfire-example\"><h3>Example 2</h3><button>Cancel</button>"));
+  assert!(body.contains("<h3>new</h3><em>New</em>"));
+  // A component that fails to render shows the error in its place.
+  assert!(body.contains("id=\"components&#x2F;card.html\""));
+  assert!(body.contains("<pre class=\"snapfire-error\">") && body.contains("title"));
+  assert!(!body.contains("hidden") && !body.contains("page"));
+}
+
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_preview_renders_templates_with_fixtures() {
+  let temp_dir = tempdir().unwrap();
+  let fixtures = temp_dir.path().join("user.html.fixtures");
+  fs::create_dir(&fixtures).unwrap();
+  fs::write(temp_dir.path().join("user.html"), "{{ name | default(value=\"nobody\") }}").unwrap();
+  fs::write(temp_dir.path().join("empty.html"), "{{ name | default(value=\"nobody\") }}").unwrap();
+  fs::write(fixtures.join("default.json"), r#"{ "name": "Ada" }"#).unwrap();
+  fs::write(fixtures.join("admin.json"), r#"{ "name": "Grace" }"#).unwrap();
+  fs::write(fixtures.join("broken.json"), "[1, 2]").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).build().unwrap();
+
+  let app = test::init_service(App::new().configure(move |cfg| snapfire_app.configure_routes(cfg))).await;
+  let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+  assert_eq!(test::call_and_read_body(&app, get("/_snapfire/preview/user.html")).await, "Ada");
+  assert_eq!(test::call_and_read_body(&app, get("/_snapfire/preview/user.html?fixture=admin")).await, "Grace");
+  assert_eq!(test::call_and_read_body(&app, get("/_snapfire/preview/empty.html")).await, "nobody");
+
+  let resp = test::call_service(&app, get("/_snapfire/preview/user.html?fixture=../user")).await;
+  assert_eq!(resp.status(), 404);
+  assert_eq!(
+    test::read_body(resp).await,
+    "`user.html` has no fixture `../user`; available: admin, broken, default"
+  );
+  assert_eq!(test::call_service(&app, get("/_snapfire/preview/missing.html")).await.status(), 404);
+  assert_eq!(test::call_service(&app, get("/_snapfire/preview/user.html?fixture=broken")).await.status(), 500);
+}
+
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_lsp_endpoint_describes_the_current_templates() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("index.html"), "{% block body %}{% endblock %}").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).add_global("site_name", "SnapFire").build().unwrap();
+
+  let app_state = snapfire_app.clone();
+  let app = test::init_service(App::new().configure(move |cfg| app_state.configure_routes(cfg))).await;
+  let get = || test::TestRequest::get().uri("/_snapfire/lsp.json").to_request();
+
+  let before: serde_json::Value = test::call_and_read_body_json(&app, get()).await;
+  assert_eq!(before["templates"][0]["name"], "index.html");
+  assert_eq!(before["templates"][0]["blocks"], serde_json::json!(["body"]));
+  assert_eq!(before["globals"]["site_name"], "\"SnapFire\"");
+
+  // It reflects template reloads.
+  fs::write(temp_dir.path().join("about.html"), "about").unwrap();
+  snapfire_app.reload_templates().unwrap();
+  let after: serde_json::Value = test::call_and_read_body_json(&app, get()).await;
+  assert_eq!(after["templates"][0]["name"], "about.html");
+}
+
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_a11y_audit_reports_issues_and_outline_of_a_route() {
+  use snapfire::actix::dev::A11yAudit;
+
+  let app = test::init_service(
+    App::new()
+      .wrap(A11yAudit)
+      .route(
+        "/profile/{name}",
+        web::get().to(|path: web::Path<String>, query: web::Query<std::collections::HashMap<String, String>>| async move {
+          let tab = query.get("tab").cloned().unwrap_or_default();
+          HttpResponse::Ok()
+            .content_type("text/html")
+            .body(format!("<html lang=\"en\"><title>{path}</title><h1>{tab}</h1>\n<img src=\"me.png\"></html>"))
+        }),
+      )
+      .route("/plain", web::get().to(|| async { "text" })),
+  )
+  .await;
+  let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+  let report = test::call_and_read_body(&app, get("/_snapfire/a11y?path=%2Fprofile%2Fada%3Ftab%3Dposts")).await;
+  let report = String::from_utf8(report.to_vec()).unwrap();
+  assert!(report.contains("<title>Accessibility audit: &#x2F;profile&#x2F;ada?tab=posts</title>"), "{report}");
+  assert!(report.contains("1 issue found."), "{report}");
+  assert!(report.contains("<tr><td>2</td><td>img-alt</td>"), "{report}");
+
+  let outline = test::call_and_read_body(&app, get("/_snapfire/outline?path=/profile/ada")).await;
+  let outline = String::from_utf8(outline.to_vec()).unwrap();
+  assert!(outline.contains("<nav><a href=\"/_snapfire/a11y?path=%2Fprofile%2Fada\">"), "{outline}");
+  assert!(outline.contains("<code>h1</code> <em>(empty heading)</em> <small>line 1</small>"), "{outline}");
+
+  let report = test::call_and_read_body(&app, get("/_snapfire/a11y?path=/plain")).await;
+  assert!(String::from_utf8_lossy(&report).contains("status 200 and no HTML page"));
+  assert_eq!(test::call_service(&app, get("/_snapfire/a11y?path=https://example.com/")).await.status(), 400);
+  assert_eq!(test::call_and_read_body(&app, get("/plain")).await, "text");
+}
+
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_request_replay_resends_recorded_requests() {
+  use snapfire::actix::dev::RequestReplay;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+
+  // The page changes on every submission, as a template edit would change it.
+  let submissions = Arc::new(AtomicUsize::new(0));
+  let app = test::init_service(
+    App::new()
+      .wrap(RequestReplay::new())
+      .app_data(web::Data::new(submissions))
+      .route(
+        "/signup",
+        web::post().to(|form: web::Form<std::collections::HashMap<String, String>>, count: web::Data<Arc<AtomicUsize>>| async move {
+          let count = count.fetch_add(1, Ordering::SeqCst) + 1;
+          HttpResponse::Ok()
+            .content_type("text/html")
+            .body(format!("<h1>Welcome {}</h1>\n<p>Submission {count}</p>", form["name"]))
+        }),
+      )
+      .route("/data.json", web::get().to(|| async { HttpResponse::Ok().json(1) })),
+  )
+  .await;
+  let signup = test::TestRequest::post()
+    .uri("/signup")
+    .insert_header(("content-type", "application/x-www-form-urlencoded"))
+    .set_payload("name=ada&password=hunter2")
+    .to_request();
+  assert_eq!(test::call_and_read_body(&app, signup).await, "<h1>Welcome ada</h1>\n<p>Submission 1</p>");
+  test::call_service(&app, test::TestRequest::get().uri("/data.json").to_request()).await;
+
+  let panel = test::call_and_read_body(&app, test::TestRequest::get().uri("/_snapfire/replay").to_request()).await;
+  let panel = String::from_utf8(panel.to_vec()).unwrap();
+  assert!(panel.contains("<code>POST &#x2F;signup</code></td><td><code>name=ada&amp;password=[scrubbed]</code>"), "{panel}");
+  assert!(!panel.contains("data.json"), "{panel}");
+
+  let replay = test::TestRequest::post().uri("/_snapfire/replay?id=1").to_request();
+  let result = String::from_utf8(test::call_and_read_body(&app, replay).await.to_vec()).unwrap();
+  assert!(result.contains("<h2>POST &#x2F;signup: 200 → 200</h2>"), "{result}");
+  assert!(result.contains("<span class=\"added\">+&lt;p&gt;Submission 2&lt;&#x2F;p&gt;</span>"), "{result}");
+  let missing = test::TestRequest::post().uri("/_snapfire/replay?id=9").to_request();
+  assert_eq!(test::call_service(&app, missing).await.status(), 404);
+}
+
+#[actix_rt::test]
+async fn test_security_headers_allow_what_snapfire_emits() {
+  use snapfire::actix::security::security_headers;
+
+  let temp_dir = tempdir().unwrap();
+  fs::write(
+    temp_dir.path().join("index.html"),
+    "<body><script nonce=\"{{ csp_nonce }}\">go()</script>{{ 1700000000 | timeago }}</body>",
+  )
+  .unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).timeago_script(true).build().unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap(InjectSnapFireScript::default())
+      .wrap(
+        security_headers()
+          .csp_source("img-src", "https://images.example.com")
+          .csp_directive("object-src", &[])
+          .header("x-frame-options", Some("SAMEORIGIN")),
+      )
+      .route(
+        "/",
+        web::get().to(|app: web::Data<TeraWeb>| async move { app.render("index.html", tera::Context::new()) }),
+      )
+      .route(
+        "/api",
+        web::get().to(|| async { HttpResponse::Ok().insert_header(("referrer-policy", "no-referrer")).finish() }),
+      ),
+  )
+  .await;
+
+  let resp = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+  let header = |name: &str| resp.headers().get(name).unwrap().to_str().unwrap().to_string();
+  let policy = header("content-security-policy");
+  let nonce = policy.split("'nonce-").nth(1).unwrap().split('\'').next().unwrap().to_string();
+  assert_eq!(nonce.len(), 32);
+  assert!(
+    policy.starts_with(&format!(
+      "default-src 'self'; script-src 'self' 'nonce-{nonce}'; style-src 'self' 'nonce-{nonce}'; \
+       img-src 'self' https://images.example.com data: https://www.gravatar.com; base-uri 'self'; \
+       form-action 'self'; frame-ancestors 'none'"
+    )),
+    "{policy}"
+  );
+  #[cfg(feature = "devel")]
+  assert!(policy.ends_with("; connect-src 'self' ws://localhost:8080"), "{policy}");
+  assert_eq!(header("x-frame-options"), "SAMEORIGIN");
+  assert_eq!(header("x-content-type-options"), "nosniff");
+  assert_eq!(header("referrer-policy"), "strict-origin-when-cross-origin");
+  let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+  assert!(body.starts_with(&format!("<body><script nonce=\"{nonce}\">go()</script>")), "{body}");
+  assert!(body.contains(&format!("</time><script nonce=\"{nonce}\">")), "{body}");
+  #[cfg(feature = "devel")]
+  assert!(body.contains(&format!("<script nonce=\"{nonce}\" data-snapfire-reload=\"true\">")), "{body}");
+
+  // Each request gets its own nonce, and handlers' headers win.
+  let resp = test::call_service(&app, test::TestRequest::get().uri("/api").to_request()).await;
+  assert!(!resp.headers().get("content-security-policy").unwrap().to_str().unwrap().contains(&nonce));
+  assert_eq!(resp.headers().get("referrer-policy").unwrap(), "no-referrer");
+}
+
+#[actix_web::test]
+async fn test_trusted_proxies_set_the_request_origin() {
+  use snapfire::actix::security::security_headers;
+
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("index.html"), "<link rel=\"canonical\" href=\"{{ canonical_url }}\">").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).trusted_proxies(["10.0.0.0/8"]).build().unwrap();
+  assert!(TeraWeb::builder(&glob_path).trusted_proxies(["proxy.internal"]).build().is_err());
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap(security_headers())
+      .route(
+        "/docs",
+        web::get().to(|app: web::Data<TeraWeb>| async move { app.render("index.html", tera::Context::new()) }),
+      ),
+  )
+  .await;
+
+  let request = |peer: &str| {
+    test::TestRequest::get()
+      .uri("/docs")
+      .peer_addr(peer.parse().unwrap())
+      .insert_header(("host", "app:8080"))
+      .insert_header(("x-forwarded-proto", "https"))
+      .insert_header(("x-forwarded-host", "example.com"))
+      .to_request()
+  };
+
+  let resp = test::call_service(&app, request("10.0.0.1:4000")).await;
+  #[cfg(feature = "devel")]
+  {
+    let policy = resp.headers().get("content-security-policy").unwrap().to_str().unwrap();
+    assert!(policy.ends_with("; connect-src 'self' wss://example.com"), "{policy}");
+  }
+  let body = test::read_body(resp).await;
+  assert!(std::str::from_utf8(&body).unwrap().starts_with("<link rel=\"canonical\" href=\"https:&#x2F;&#x2F;example.com&#x2F;docs\">"));
+
+  // Anyone else's forwarding headers are ignored.
+  let body = test::read_body(test::call_service(&app, request("203.0.113.5:4000")).await).await;
+  assert!(std::str::from_utf8(&body).unwrap().starts_with("<link rel=\"canonical\" href=\"http:&#x2F;&#x2F;app:8080&#x2F;docs\">"));
+}
+
 // This helper now collects all available text messages for a short duration.
 async fn collect_ws_messages(
   ws_stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
@@ -200,3 +589,120 @@ async fn test_full_reload_pipeline() {
   // 6. Shutdown server
   server_handle.stop(true).await;
 }
+
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_configurable_heartbeat() {
+  let temp_dir = tempdir().unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .ws_heartbeat_interval(Duration::from_millis(50))
+    .ws_client_timeout(Duration::from_secs(5))
+    .reload_channel_capacity(256)
+    .build()
+    .unwrap();
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  let server = HttpServer::new(move || {
+    let app_state = snapfire_app.clone();
+    App::new().configure(move |cfg| app_state.configure_routes(cfg))
+  })
+  .listen(listener)
+  .unwrap()
+  .run();
+  let server_handle = server.handle();
+  rt::spawn(server);
+
+  let (mut ws_stream, _) = connect_async(format!("ws://{}/_snapfire/ws", addr))
+    .await
+    .expect("Failed to connect");
+
+  // Several pings arrive well within the default 5 second interval.
+  let mut pings = 0;
+  let _ = timeout(Duration::from_millis(500), async {
+    while let Some(Ok(message)) = ws_stream.next().await {
+      if let Message::Ping(_) = message {
+        pings += 1;
+      }
+    }
+  })
+  .await;
+  assert!(pings >= 3, "expected frequent pings, got {}", pings);
+
+  server_handle.stop(true).await;
+}
+
+/// A source that hands its sink to the test, like a message queue consumer would keep it.
+#[cfg(feature = "devel")]
+struct SyntheticSource(std::sync::Arc<std::sync::Mutex<Option<ChangeSink>>>);
+
+#[cfg(feature = "devel")]
+impl WatchSource for SyntheticSource {
+  fn start(&mut self, sink: ChangeSink) -> snapfire::Result<()> {
+    *self.0.lock().unwrap() = Some(sink);
+    Ok(())
+  }
+}
+
+#[cfg(feature = "devel")]
+#[actix_rt::test]
+async fn test_reloads_from_watch_sources_poke_endpoint_and_dev_push() {
+  let temp_dir = tempdir().unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let synthetic_sink = std::sync::Arc::new(std::sync::Mutex::new(None));
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .watch_source(SyntheticSource(synthetic_sink.clone()))
+    .reload_poke_path("/_snapfire/poke")
+    .reload_quiet_period(Duration::from_millis(10))
+    .reload_min_interval(Duration::ZERO)
+    .build()
+    .unwrap();
+  let push_app = snapfire_app.clone();
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  let server = HttpServer::new(move || {
+    let app_state = snapfire_app.clone();
+    App::new().configure(move |cfg| app_state.configure_routes(cfg))
+  })
+  .listen(listener)
+  .unwrap()
+  .run();
+  let server_handle = server.handle();
+  rt::spawn(server);
+
+  let (mut ws_stream, _) = connect_async(format!("ws://{}/_snapfire/ws", addr))
+    .await
+    .expect("Failed to connect");
+
+  // A synthetic event, without touching the file system.
+  synthetic_sink.lock().unwrap().as_ref().unwrap().page_changed();
+  assert_eq!(get_next_text_message(&mut ws_stream).await, "reload");
+
+  // A poke from CI tooling.
+  let response = rt::task::spawn_blocking(move || {
+    use std::io::{Read, Write};
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    stream
+      .write_all(b"POST /_snapfire/poke?kind=css HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+      .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+  })
+  .await
+  .unwrap();
+  assert!(response.starts_with("HTTP/1.1 204"), "{}", response);
+  assert_eq!(get_next_text_message(&mut ws_stream).await, "reload-css");
+
+  // An app-defined message for `SnapFire.on("custom", ...)`.
+  assert_eq!(push_app.dev_push(serde_json::json!({ "unread": 3 })), 1);
+  assert_eq!(
+    get_next_text_message(&mut ws_stream).await,
+    r#"{"data":{"unread":3},"event":"custom"}"#
+  );
+
+  drop(ws_stream);
+  server_handle.stop(true).await;
+}
@@ -2,8 +2,28 @@ mod common;
 
 use crate::common::test_handler;
 
-use actix_web::{App, Responder, test, web};
-use snapfire::{TeraWeb};
+use actix_web::{
+  App, HttpMessage, Responder,
+  body::{BodySize, MessageBody},
+  cookie::{Cookie, Key},
+  dev::Service,
+  http::StatusCode,
+  test, web,
+};
+use snapfire::{
+  IntoTemplateContext, SnapFireContextExt, TemplateResolver, TeraWeb,
+  actix::{UserRoles, consent::CookieConsent, i18n::LocalePrefix, request_id::RequestIds, signed::SignedUrls},
+  core::{
+    breaker::CircuitBreaker,
+    consent::ConsentState,
+    env::RenderEnv,
+    i18n::Locales,
+    scope::{RenderScope, ScopeValues},
+    search::SearchPage,
+    signed::UrlSigner,
+    vary::VaryOn,
+  },
+};
 use std::fs;
 use tempfile::tempdir;
 use tera::Context;
@@ -44,3 +64,1130 @@ async fn test_render_in_actix_server() {
   let expected_html = "<html><head><title>SnapFire App | Integration Test</title></head></html>";
   assert_eq!(body_str, expected_html);
 }
+
+async fn consent_handler(app_state: web::Data<TeraWeb>) -> impl Responder {
+  app_state.render("consent.html", Context::new())
+}
+
+#[actix_rt::test]
+async fn test_consent_state_in_render_context() {
+  let temp_dir = tempdir().unwrap();
+  let template_content = "{% if consent is consented(\"analytics\") %}analytics{% endif %}|{{ consent.marketing }}";
+  fs::write(temp_dir.path().join("consent.html"), template_content).unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+  let snapfire_app = TeraWeb::builder(&glob_path).build().unwrap();
+  let consent = CookieConsent::new(Key::generate());
+  let signed_cookie = consent.cookie(ConsentState {
+    analytics: true,
+    marketing: false,
+  });
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap(consent)
+      .route("/", web::get().to(consent_handler)),
+  )
+  .await;
+
+  // A valid signed cookie grants analytics only.
+  let req = test::TestRequest::get().uri("/").cookie(signed_cookie.clone()).to_request();
+  let body = test::call_and_read_body(&app, req).await;
+  assert_eq!(std::str::from_utf8(&body).unwrap(), "analytics|false");
+
+  // A tampered cookie falls back to no consent at all.
+  let mut forged = signed_cookie;
+  forged.set_value("analytics,marketing");
+  let req = test::TestRequest::get().uri("/").cookie(forged).to_request();
+  let body = test::call_and_read_body(&app, req).await;
+  assert_eq!(std::str::from_utf8(&body).unwrap(), "|false");
+}
+
+#[actix_rt::test]
+async fn test_early_hints_link_header() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("base.html"), "{% block body %}{% endblock %}").unwrap();
+  fs::write(
+    temp_dir.path().join("index.html"),
+    "{% extends \"base.html\" %}{% block body %}{{ page_title }}{% endblock %}",
+  )
+  .unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .early_hints("base.html", ["/static/site.css"])
+    .early_hints("index.html", ["/static/app.js"])
+    .build()
+    .unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .route("/", web::get().to(test_handler)),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/").to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(
+    resp.headers().get("link").unwrap(),
+    "</static/site.css>; rel=preload; as=style, </static/app.js>; rel=preload; as=script"
+  );
+}
+
+#[actix_rt::test]
+async fn test_search_index_route() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("index.html"), "<h1>{{ page_title }}</h1>").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+  let mut context = Context::new();
+  context.insert("page_title", "Home");
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .search_index(vec![SearchPage::new("/", "index.html").context(context)])
+    .search_index_path("/search.json")
+    .build()
+    .unwrap();
+
+  let app_state = snapfire_app.clone();
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .configure(move |cfg| app_state.configure_routes(cfg)),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/search.json").to_request();
+  let body = test::call_and_read_body(&app, req).await;
+  assert_eq!(
+    std::str::from_utf8(&body).unwrap(),
+    r#"[{"url":"/","title":"Home","body":"Home"}]"#
+  );
+}
+
+#[actix_rt::test]
+async fn test_icon_sprite_route() {
+  let temp_dir = tempdir().unwrap();
+  let icons = temp_dir.path().join("icons");
+  fs::create_dir(&icons).unwrap();
+  fs::write(icons.join("check.svg"), "<svg viewBox=\"0 0 8 8\"><path d=\"M0 0\"/></svg>").unwrap();
+  fs::write(temp_dir.path().join("index.html"), "{{ icon(name=\"check\") }}").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).icons_dir(&icons).build().unwrap();
+  let (_, url) = snapfire_app.icon_sprite().unwrap();
+
+  let app_state = snapfire_app.clone();
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .configure(move |cfg| app_state.configure_routes(cfg))
+      .route("/", web::get().to(|app: web::Data<TeraWeb>| async move { app.render("index.html", Context::new()) })),
+  )
+  .await;
+
+  let body = test::call_and_read_body(&app, test::TestRequest::get().uri("/").to_request()).await;
+  assert_eq!(
+    std::str::from_utf8(&body).unwrap(),
+    format!("<svg aria-hidden=\"true\" focusable=\"false\"><use href=\"{url}#icon-check\"></use></svg>")
+  );
+
+  let resp = test::call_service(&app, test::TestRequest::get().uri(&url).to_request()).await;
+  assert_eq!(resp.headers().get("cache-control").unwrap(), "public, max-age=31536000, immutable");
+  assert_eq!(resp.headers().get("content-type").unwrap(), "image/svg+xml");
+  let body = test::read_body(resp).await;
+  assert!(std::str::from_utf8(&body).unwrap().contains("<symbol id=\"icon-check\" viewBox=\"0 0 8 8\">"));
+}
+
+#[actix_rt::test]
+async fn test_signed_asset_links_pass_the_middleware() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(
+    temp_dir.path().join("index.html"),
+    "{{ signed_asset(path=\"/downloads/report.pdf\", ttl=60) | replace(from=\"&amp;\", to=\"&\") | safe }}",
+  )
+  .unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let signer = UrlSigner::new("a long random key");
+  let snapfire_app = TeraWeb::builder(&glob_path).url_signer(signer.clone()).build().unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .route("/", web::get().to(|app: web::Data<TeraWeb>| async move { app.render("index.html", Context::new()) }))
+      .service(
+        web::scope("/downloads")
+          .wrap(SignedUrls::new(signer))
+          .route("/{file}", web::get().to(|| async { "PDF" })),
+      ),
+  )
+  .await;
+
+  let body = test::call_and_read_body(&app, test::TestRequest::get().uri("/").to_request()).await;
+  let url = std::str::from_utf8(&body).unwrap().to_string();
+  assert!(url.starts_with("/downloads/report.pdf?expires="));
+  let body = test::call_and_read_body(&app, test::TestRequest::get().uri(&url).to_request()).await;
+  assert_eq!(body, "PDF");
+
+  for uri in [
+    "/downloads/report.pdf".to_string(),
+    url.replace("report.pdf", "other.pdf"),
+  ] {
+    let resp = app.call(test::TestRequest::get().uri(&uri).to_request()).await;
+    assert_eq!(resp.err().unwrap().as_response_error().status_code(), 403);
+  }
+}
+
+#[actix_rt::test]
+async fn test_serve_assets_with_caching_ranges_and_precompression() {
+  let temp_dir = tempdir().unwrap();
+  let static_dir = temp_dir.path().join("static");
+  fs::create_dir_all(static_dir.join("assets")).unwrap();
+  fs::write(static_dir.join("manifest.json"), r#"{"app.js": "assets/app-3f9c.js"}"#).unwrap();
+  fs::write(static_dir.join("assets/app-3f9c.js"), "console.log(1);").unwrap();
+  fs::write(static_dir.join("assets/app-3f9c.js.br"), "BROTLI").unwrap();
+  fs::write(static_dir.join("logo.txt"), "0123456789").unwrap();
+  fs::write(static_dir.join(".env"), "SECRET=1").unwrap();
+  fs::write(temp_dir.path().join("index.html"), "").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .asset_manifest(static_dir.join("manifest.json"), "/static/")
+    .serve_assets(&static_dir)
+    .build()
+    .unwrap();
+  let app = test::init_service(App::new().configure(move |cfg| snapfire_app.configure_routes(cfg))).await;
+
+  let resp = test::call_service(&app, test::TestRequest::get().uri("/static/assets/app-3f9c.js").to_request()).await;
+  assert_eq!(resp.status(), 200);
+  assert_eq!(resp.headers().get("cache-control").unwrap(), "public, max-age=31536000, immutable");
+  assert_eq!(resp.headers().get("content-type").unwrap(), "text/javascript; charset=utf-8");
+  assert_eq!(resp.headers().get("vary").unwrap(), "Accept-Encoding");
+  let etag = resp.headers().get("etag").unwrap().clone();
+  assert_eq!(test::read_body(resp).await, "console.log(1);");
+
+  let req = test::TestRequest::get()
+    .uri("/static/assets/app-3f9c.js")
+    .insert_header(("accept-encoding", "gzip, br"))
+    .to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(resp.headers().get("content-encoding").unwrap(), "br");
+  let br_etag = resp.headers().get("etag").unwrap().clone();
+  assert_eq!(test::read_body(resp).await, "BROTLI");
+  let req = test::TestRequest::get()
+    .uri("/static/assets/app-3f9c.js")
+    .insert_header(("accept-encoding", "gzip, br"))
+    .insert_header(("if-none-match", br_etag))
+    .to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(resp.status(), 304);
+  assert!(resp.headers().get("content-encoding").is_none());
+
+  let req = test::TestRequest::get()
+    .uri("/static/assets/app-3f9c.js")
+    .insert_header(("if-none-match", etag))
+    .to_request();
+  assert_eq!(test::call_service(&app, req).await.status(), 304);
+
+  // Files outside the manifest are revalidated, and ranges are served.
+  let req = test::TestRequest::get().uri("/static/logo.txt").insert_header(("range", "bytes=2-4")).to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(resp.status(), 206);
+  assert_eq!(resp.headers().get("cache-control").unwrap(), "no-cache");
+  assert_eq!(resp.headers().get("content-range").unwrap(), "bytes 2-4/10");
+  assert_eq!(test::read_body(resp).await, "234");
+
+  let req = test::TestRequest::get().uri("/static/logo.txt").insert_header(("range", "bytes=0-0,-2")).to_request();
+  let resp = test::call_service(&app, req).await;
+  let content_type = resp.headers().get("content-type").unwrap().to_str().unwrap().to_string();
+  let boundary = content_type.strip_prefix("multipart/byteranges; boundary=").unwrap().to_string();
+  assert_eq!(
+    std::str::from_utf8(&test::read_body(resp).await).unwrap(),
+    format!(
+      "--{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 0-0/10\r\n\r\n0\r\n\
+       --{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 8-9/10\r\n\r\n89\r\n\
+       --{boundary}--\r\n"
+    )
+  );
+
+  // Overlapping and adjacent ranges are served as one.
+  let req = test::TestRequest::get().uri("/static/logo.txt").insert_header(("range", "bytes=4-6,0-2,3-3")).to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(resp.headers().get("content-range").unwrap(), "bytes 0-6/10");
+  assert_eq!(test::read_body(resp).await, "0123456");
+
+  // Large files are streamed in chunks.
+  let large: String = (0..100_000).map(|i| char::from(b'a' + (i % 26) as u8)).collect();
+  fs::write(static_dir.join("large.txt"), &large).unwrap();
+  let resp = test::call_service(&app, test::TestRequest::get().uri("/static/large.txt").to_request()).await;
+  assert_eq!(resp.response().body().size(), BodySize::Sized(100_000));
+  assert_eq!(test::read_body(resp).await, large);
+
+  let req = test::TestRequest::get().uri("/static/logo.txt").insert_header(("range", "bytes=20-")).to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(resp.status(), 416);
+  assert_eq!(resp.headers().get("content-range").unwrap(), "bytes */10");
+
+  for uri in ["/static/.env", "/static/%2E%2E/index.html", "/static/missing.js", "/static/assets"] {
+    let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    assert_eq!(resp.status(), 404, "{uri}");
+  }
+}
+
+#[actix_rt::test]
+async fn test_early_flush_streams_the_page_in_chunks() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(
+    temp_dir.path().join("index.html"),
+    "<html><head><title>{{ title }}</title></head><body>top{{ flush() }}{% for i in [1, 2] %}{{ i }}{% endfor %}</body></html>",
+  )
+  .unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).early_flush(true).build().unwrap();
+  let app = test::init_service(App::new().app_data(web::Data::new(snapfire_app)).route(
+    "/",
+    web::get().to(|app: web::Data<TeraWeb>| async move {
+      let mut context = Context::new();
+      context.insert("title", "Report");
+      app.render("index.html", context)
+    }),
+  ))
+  .await;
+
+  let resp = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+  let mut body = std::pin::pin!(resp.into_body());
+  let mut chunks = Vec::new();
+  while let Some(chunk) = std::future::poll_fn(|cx| actix_web::body::MessageBody::poll_next(body.as_mut(), cx)).await {
+    chunks.push(String::from_utf8(chunk.unwrap().to_vec()).unwrap());
+  }
+  assert_eq!(
+    chunks,
+    [
+      "<html><head><title>Report</title></head>",
+      "<body>top",
+      "12</body></html>"
+    ]
+  );
+}
+
+#[actix_rt::test]
+async fn test_deferred_blocks_stream_after_the_page() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(
+    temp_dir.path().join("index.html"),
+    "<html><head></head><body><aside>{{ await_block(name=\"recs\", user=\"ana\") }}</aside>end</body></html>",
+  )
+  .unwrap();
+  fs::write(temp_dir.path().join("recs.html"), "<p>{{ user }}: {{ items | join(sep=\", \") }}</p>").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .early_flush(true)
+    .deferred_block("recs", "recs.html", |args| {
+      let mut context = Context::new();
+      context.insert("user", &args["user"]);
+      context.insert("items", &["a", "b"]);
+      Ok(context)
+    })
+    .build()
+    .unwrap();
+  let app = test::init_service(App::new().app_data(web::Data::new(snapfire_app)).route(
+    "/",
+    web::get().to(|app: web::Data<TeraWeb>| async move { app.render("index.html", Context::new()) }),
+  ))
+  .await;
+
+  let resp = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+  let mut body = std::pin::pin!(resp.into_body());
+  let mut chunks = Vec::new();
+  while let Some(chunk) = std::future::poll_fn(|cx| actix_web::body::MessageBody::poll_next(body.as_mut(), cx)).await {
+    chunks.push(String::from_utf8(chunk.unwrap().to_vec()).unwrap());
+  }
+  assert_eq!(chunks.len(), 3);
+  assert_eq!(chunks[0], "<html><head></head>");
+  assert_eq!(
+    chunks[1],
+    "<body><aside><template id=\"snapfire-await-0\"></template></aside>end</body></html>"
+  );
+  assert!(chunks[2].starts_with("<template data-snapfire-fill=\"snapfire-await-0\"><p>ana: a, b</p></template><script>"));
+}
+
+async fn about_handler(app_state: web::Data<TeraWeb>) -> impl Responder {
+  app_state.render("about.html", Context::new())
+}
+
+#[actix_rt::test]
+async fn test_locale_prefixed_routes() {
+  let temp_dir = tempdir().unwrap();
+  let template_content = "{{ locale }}|{{ localized_url(path=\"/about\", locale=\"en\") }}|{{ alternate_links() }}";
+  fs::write(temp_dir.path().join("about.html"), template_content).unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .locales(Locales::new("en", &["fr"]))
+    .build()
+    .unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap(LocalePrefix)
+      .route("/about", web::get().to(about_handler)),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/fr/about?ref=1").to_request();
+  let body = test::call_and_read_body(&app, req).await;
+  assert_eq!(
+    std::str::from_utf8(&body).unwrap(),
+    "fr|/about|<link rel=\"alternate\" hreflang=\"en\" href=\"/about\">\n\
+     <link rel=\"alternate\" hreflang=\"fr\" href=\"/fr/about\">\n\
+     <link rel=\"alternate\" hreflang=\"x-default\" href=\"/about\">"
+  );
+
+  let req = test::TestRequest::get().uri("/about").to_request();
+  let body = test::call_and_read_body(&app, req).await;
+  assert!(std::str::from_utf8(&body).unwrap().starts_with("en|"));
+}
+
+#[actix_rt::test]
+async fn test_plural_follows_request_locale() {
+  let temp_dir = tempdir().unwrap();
+  let template_content = "{{ 3 | plural(one=\"{count} file\", few=\"{count} файла\", other=\"{count} files\") }}";
+  fs::write(temp_dir.path().join("about.html"), template_content).unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .locales(Locales::new("en", &["ru"]))
+    .build()
+    .unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap(LocalePrefix)
+      .route("/about", web::get().to(about_handler)),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/ru/about").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "3 файла");
+
+  let req = test::TestRequest::get().uri("/about").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "3 files");
+}
+
+#[actix_rt::test]
+async fn test_rtl_locales_get_direction_flags() {
+  let temp_dir = tempdir().unwrap();
+  let template_content = "<html dir=\"{{ dir }}\">{{ is_rtl }}|{{ text_align_start() }}|{{ \"margin-left: 1em\" | logical_css }}";
+  fs::write(temp_dir.path().join("about.html"), template_content).unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .locales(Locales::new("en", &["ar"]))
+    .build()
+    .unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap(LocalePrefix)
+      .route("/about", web::get().to(about_handler)),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/ar/about").to_request();
+  assert_eq!(
+    test::call_and_read_body(&app, req).await,
+    "<html dir=\"rtl\">true|right|margin-inline-start: 1em"
+  );
+
+  let req = test::TestRequest::get().uri("/about").to_request();
+  assert_eq!(
+    test::call_and_read_body(&app, req).await,
+    "<html dir=\"ltr\">false|left|margin-inline-start: 1em"
+  );
+}
+
+#[actix_rt::test]
+async fn test_middleware_context_is_merged() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("index.html"), "{{ current_user }}@{{ tenant }}").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).build().unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap_fn(|req, srv| {
+        SnapFireContextExt::insert_into(&req, "current_user", "ada");
+        SnapFireContextExt::insert_into(&req, "tenant", "acme");
+        srv.call(req)
+      })
+      .route(
+        "/",
+        web::get().to(|app: web::Data<TeraWeb>| async move {
+          // The handler's own values win over the middleware's.
+          let mut context = Context::new();
+          context.insert("tenant", "globex");
+          app.render("index.html", context)
+        }),
+      ),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "ada@globex");
+}
+
+#[actix_rt::test]
+async fn test_render_env_is_resolved_per_request() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(
+    temp_dir.path().join("index.html"),
+    "{{ env.locale }}|{{ env.theme }}|{{ env.currency }}|{{ locale }}|{{ dir() }}",
+  )
+  .unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .locales(Locales::new("en", &["ar"]))
+    .render_env(RenderEnv {
+      currency: Some("USD".to_string()),
+      theme: Some("light".to_string()),
+      ..RenderEnv::default()
+    })
+    .build()
+    .unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap_fn(|req, srv| {
+        if req.query_string() == "dark" {
+          req.extensions_mut().insert(RenderEnv {
+            theme: Some("dark".to_string()),
+            ..RenderEnv::default()
+          });
+        }
+        srv.call(req)
+      })
+      .wrap(LocalePrefix)
+      .route("/", web::get().to(|app: web::Data<TeraWeb>| async move { app.render("index.html", Context::new()) })),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/ar/?dark").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "ar|dark|USD|ar|rtl");
+  let req = test::TestRequest::get().uri("/").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "en|light|USD|en|ltr");
+}
+
+#[actix_rt::test]
+async fn test_templates_set_their_status_and_headers() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("404.html"), "<h1>Not found: {{ path | safe }}</h1>").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).build().unwrap();
+
+  let app = test::init_service(
+    App::new().app_data(web::Data::new(snapfire_app)).default_service(web::to(
+      |app: web::Data<TeraWeb>, req: actix_web::HttpRequest| async move {
+        let mut context = Context::new();
+        context.insert("path", req.path());
+        app
+          .render("404.html", context)
+          .with_status(StatusCode::NOT_FOUND)
+          .insert_header("Cache-Control", "no-store")
+          .add_cookie(Cookie::new("seen", "1"))
+          .add_cookie(Cookie::build("theme", "dark").path("/").finish())
+      },
+    )),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/missing").to_request();
+  let response = test::call_service(&app, req).await;
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  assert_eq!(response.headers().get("cache-control").unwrap(), "no-store");
+  let cookies: Vec<_> = response.headers().get_all("set-cookie").map(|value| value.to_str().unwrap()).collect();
+  assert_eq!(cookies, ["seen=1", "theme=dark; Path=/"]);
+  assert_eq!(test::read_body(response).await, "<h1>Not found: /missing</h1>");
+}
+
+#[actix_rt::test]
+async fn test_request_data_sets_the_caching_headers() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("index.html"), "{{ env.theme }}").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .cache_key_dimensions(&["Accept-Language"], |scope| vec![scope.env().locale.clone().unwrap_or_default()])
+    .build()
+    .unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap_fn(|req, srv| {
+        if req.query_string() != "" {
+          req.extensions_mut().insert(RenderEnv {
+            theme: Some("dark".to_string()),
+            ..RenderEnv::default()
+          });
+        }
+        // Only this one says where the theme came from.
+        if req.query_string() == "cookie" {
+          VaryOn::record(&req, "cookie, Accept-Language");
+        }
+        srv.call(req)
+      })
+      .route("/", web::get().to(|app: web::Data<TeraWeb>| async move { app.render("index.html", Context::new()) })),
+  )
+  .await;
+
+  let headers = |uri: &str| {
+    let req = test::TestRequest::get().uri(uri).to_request();
+    let app = &app;
+    async move {
+      let response = test::call_service(app, req).await;
+      let header = |name| response.headers().get(name).map(|value| value.to_str().unwrap().to_string());
+      (header("vary"), header("cache-control"))
+    }
+  };
+  assert_eq!(headers("/").await, (Some("Accept-Language".to_string()), None));
+  assert_eq!(headers("/?unknown").await, (Some("Accept-Language".to_string()), Some("private".to_string())));
+  assert_eq!(headers("/?cookie").await, (Some("cookie, Accept-Language".to_string()), None));
+}
+
+struct CurrentUser(String);
+
+#[actix_rt::test]
+async fn test_filters_read_the_render_scope() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("index.html"), "{{ \"Hello\" | greet | safe }}").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .configure_tera(|tera| {
+      tera.register_filter(
+        "greet",
+        |value: &tera::Value, _: &std::collections::HashMap<String, tera::Value>| {
+          let scope = RenderScope::current().expect("filters run inside a render");
+          let user = scope.get::<CurrentUser>().map_or("stranger", |user| user.0.as_str());
+          Ok(tera::Value::String(format!(
+            "{}, {user} ({} on {})",
+            value.as_str().unwrap(),
+            scope.template(),
+            scope.path().unwrap()
+          )))
+        },
+      );
+    })
+    .build()
+    .unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap_fn(|req, srv| {
+        if req.query_string() == "signed-in" {
+          let mut values = ScopeValues::new();
+          values.insert(CurrentUser("ana".to_string()));
+          req.extensions_mut().insert(values);
+        }
+        srv.call(req)
+      })
+      .route("/home", web::get().to(|app: web::Data<TeraWeb>| async move { app.render("index.html", Context::new()) })),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/home?signed-in").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "Hello, ana (index.html on /home)");
+  let req = test::TestRequest::get().uri("/home").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "Hello, stranger (index.html on /home)");
+  assert!(RenderScope::current().is_none());
+}
+
+#[actix_rt::test]
+async fn test_request_ids_reach_templates_and_responses() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("index.html"), "Request {{ request_id }}").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).build().unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap(RequestIds::new().echo(true))
+      .route("/", web::get().to(|app: web::Data<TeraWeb>| async move { app.render("index.html", Context::new()) })),
+  )
+  .await;
+
+  // An id assigned upstream is kept.
+  let req = test::TestRequest::get().uri("/").insert_header(("x-request-id", "lb-42")).to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(resp.headers().get("x-request-id").unwrap(), "lb-42");
+  assert_eq!(test::read_body(resp).await, "Request lb-42");
+
+  // Otherwise one is generated, and an unusable one is replaced.
+  let req = test::TestRequest::get().uri("/").insert_header(("x-request-id", "has spaces")).to_request();
+  let resp = test::call_service(&app, req).await;
+  let id = resp.headers().get("x-request-id").unwrap().to_str().unwrap().to_string();
+  assert_eq!(id.len(), 32);
+  assert!(id.bytes().all(|byte| byte.is_ascii_hexdigit()));
+  assert_eq!(test::read_body(resp).await, format!("Request {id}").as_bytes());
+}
+
+#[actix_rt::test]
+async fn test_failed_renders_show_the_error_template() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("report.html"), "Total: {{ report.total }}").unwrap();
+  fs::write(temp_dir.path().join("500.html"), "<h1>Error {{ status }}</h1><p>Reference {{ request_id }}</p>").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).error_template("500.html").build().unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap(RequestIds::new())
+      .route("/", web::get().to(|app: web::Data<TeraWeb>| async move { app.render("report.html", Context::new()) })),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/").insert_header(("x-request-id", "lb-42")).to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(resp.status(), 500);
+  assert_eq!(resp.headers().get("content-type").unwrap(), "text/html; charset=utf-8");
+  assert_eq!(resp.headers().get("cache-control").unwrap(), "no-store");
+  assert_eq!(test::read_body(resp).await, "<h1>Error 500</h1><p>Reference lb-42</p>");
+
+  // Without an error template, the error is a bare 500.
+  let plain = test::init_service(
+    App::new()
+      .app_data(web::Data::new(TeraWeb::builder(&glob_path).build().unwrap()))
+      .route("/", web::get().to(|app: web::Data<TeraWeb>| async move { app.render("report.html", Context::new()) })),
+  )
+  .await;
+  let resp = test::call_service(&plain, test::TestRequest::get().uri("/").to_request()).await;
+  assert_eq!(resp.status(), 500);
+  assert_eq!(resp.headers().get("cache-control").unwrap(), "no-store");
+}
+
+#[actix_rt::test]
+async fn test_circuit_breaker_serves_the_fallback_while_open() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("report.html"), "Total: {{ report.total }}").unwrap();
+  fs::write(temp_dir.path().join("unavailable.html"), "{{ unavailable_template }} is down").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .circuit_breaker(CircuitBreaker::new("unavailable.html").min_renders(2).error_rate(1.0))
+    .build()
+    .unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .route("/", web::get().to(|app: web::Data<TeraWeb>| async move { app.render("report.html", Context::new()) })),
+  )
+  .await;
+
+  // Failures still surface until the circuit opens.
+  for _ in 0..2 {
+    let req = test::TestRequest::get().uri("/").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 500);
+  }
+  let req = test::TestRequest::get().uri("/").to_request();
+  let resp = test::call_service(&app, req).await;
+  assert_eq!(resp.status(), 503);
+  assert_eq!(resp.headers().get("retry-after").unwrap(), "30");
+  assert_eq!(test::read_body(resp).await, "report.html is down");
+
+  assert!(
+    TeraWeb::builder(&glob_path)
+      .circuit_breaker(CircuitBreaker::new("missing.html"))
+      .build()
+      .is_err()
+  );
+}
+
+#[derive(IntoTemplateContext)]
+#[template_context(computed = "display_name")]
+struct ProfilePage {
+  first_name: String,
+  last_name: String,
+  #[template_context(rename = "joined")]
+  created_at: u32,
+  #[template_context(skip)]
+  #[allow(dead_code)]
+  password_hash: String,
+}
+
+impl ProfilePage {
+  fn display_name(&self) -> String {
+    format!("{} {}", self.first_name, self.last_name)
+  }
+}
+
+#[actix_rt::test]
+async fn test_view_models_expose_only_their_view_fields() {
+  let temp_dir = tempdir().unwrap();
+  fs::write(
+    temp_dir.path().join("profile.html"),
+    "{{ display_name }} ({{ first_name }}), {{ joined }}{% if password_hash %}, leaked{% endif %}",
+  )
+  .unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).build().unwrap();
+  let app = test::init_service(App::new().app_data(web::Data::new(snapfire_app)).route(
+    "/",
+    web::get().to(|app: web::Data<TeraWeb>| async move {
+      let page = ProfilePage {
+        first_name: "Ada".to_string(),
+        last_name: "Lovelace".to_string(),
+        created_at: 1843,
+        password_hash: "secret".to_string(),
+      };
+      app.render("profile.html", page)
+    }),
+  ))
+  .await;
+
+  let req = test::TestRequest::get().uri("/").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "Ada Lovelace (Ada), 1843");
+}
+
+#[actix_rt::test]
+async fn test_extensionless_template_names_resolve() {
+  let temp_dir = tempdir().unwrap();
+  fs::create_dir_all(temp_dir.path().join("blog/archive")).unwrap();
+  fs::write(temp_dir.path().join("blog/post.html"), "post").unwrap();
+  fs::write(temp_dir.path().join("blog/archive/index.html"), "archive").unwrap();
+  let glob_path = temp_dir.path().join("**/*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .template_resolver(TemplateResolver::new().extensions(["html"]).index_files(["index.html"]))
+    .build()
+    .unwrap();
+  assert_eq!(snapfire_app.resolve_template("blog/archive").as_deref(), Some("blog/archive/index.html"));
+  assert_eq!(snapfire_app.resolve_template("blog/missing"), None);
+
+  let app = test::init_service(App::new().route(
+    "/{name:.*}",
+    web::get().to(move |name: web::Path<String>| {
+      let snapfire_app = snapfire_app.clone();
+      async move { snapfire_app.render(&name, Context::new()) }
+    }),
+  ))
+  .await;
+
+  let req = test::TestRequest::get().uri("/blog/post").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "post");
+  let req = test::TestRequest::get().uri("/blog/archive").to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "archive");
+}
+
+#[actix_rt::test]
+async fn test_pages_handler_maps_paths_to_templates() {
+  let temp_dir = tempdir().unwrap();
+  fs::create_dir_all(temp_dir.path().join("pages/about")).unwrap();
+  fs::write(temp_dir.path().join("pages/index.html"), "home").unwrap();
+  fs::write(temp_dir.path().join("pages/about/team.html"), "team of {{ site_name }}").unwrap();
+  fs::write(temp_dir.path().join("pages/about/index.html"), "about").unwrap();
+  fs::write(temp_dir.path().join("pages/_partial.html"), "partial").unwrap();
+  let glob_path = temp_dir.path().join("**/*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).add_global("site_name", "Acme").build().unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .route("/api", web::get().to(|| async { "api" }))
+      .default_service(snapfire::actix::pages_handler("pages")),
+  )
+  .await;
+
+  for (uri, body) in [("/", "home"), ("/about/team", "team of Acme"), ("/about", "about"), ("/api", "api")] {
+    let req = test::TestRequest::get().uri(uri).to_request();
+    assert_eq!(test::call_and_read_body(&app, req).await, body, "{uri}");
+  }
+  for uri in ["/missing", "/_partial", "/about/../index"] {
+    let req = test::TestRequest::get().uri(uri).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404, "{uri}");
+  }
+  let req = test::TestRequest::post().uri("/about").to_request();
+  assert_eq!(test::call_service(&app, req).await.status(), 405);
+}
+
+#[actix_rt::test]
+async fn test_pages_honor_front_matter() {
+  let temp_dir = tempdir().unwrap();
+  fs::create_dir_all(temp_dir.path().join("pages")).unwrap();
+  fs::write(
+    temp_dir.path().join("layout.html"),
+    "<title>{{ page.title }}</title>{% block content %}{% endblock content %}",
+  )
+  .unwrap();
+  fs::write(
+    temp_dir.path().join("pages/team.html"),
+    "---\ntitle: Team\nlayout: layout.html\ncache: public, max-age=60\n---\n<h1>{{ page.title }}</h1>",
+  )
+  .unwrap();
+  fs::write(
+    temp_dir.path().join("pages/admin.html"),
+    "+++\ntitle = \"Admin\"\nrole = \"staff\"\n+++\nsecret",
+  )
+  .unwrap();
+  let glob_path = temp_dir.path().join("**/*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).build().unwrap();
+
+  let app = test::init_service(
+    App::new()
+      .app_data(web::Data::new(snapfire_app))
+      .wrap_fn(|req, srv| {
+        if req.headers().contains_key("x-staff") {
+          req.extensions_mut().insert(UserRoles(vec!["staff".to_string()]));
+        }
+        srv.call(req)
+      })
+      .default_service(snapfire::actix::pages_handler("pages")),
+  )
+  .await;
+
+  let req = test::TestRequest::get().uri("/team").to_request();
+  let res = test::call_service(&app, req).await;
+  assert_eq!(res.headers().get("cache-control").unwrap(), "public, max-age=60");
+  assert_eq!(test::read_body(res).await, "<title>Team</title><h1>Team</h1>");
+
+  let req = test::TestRequest::get().uri("/admin").to_request();
+  assert_eq!(test::call_service(&app, req).await.status(), 403);
+  let req = test::TestRequest::get().uri("/admin").insert_header(("x-staff", "1")).to_request();
+  assert_eq!(test::call_and_read_body(&app, req).await, "secret");
+}
+
+#[actix_rt::test]
+async fn test_spam_guard_rejects_honeypot_and_rate_limits() {
+  use snapfire::actix::forms::SpamGuard;
+  use std::time::Duration;
+
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("form.html"), "<form method=\"post\">{{ honeypot_field() }}</form>").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).build().unwrap();
+
+  let app = test::init_service(
+    App::new().app_data(web::Data::new(snapfire_app)).service(
+      web::resource("/comments")
+        .wrap(SpamGuard::new().rate_limit(2, Duration::from_secs(60)))
+        .route(web::get().to(|app: web::Data<TeraWeb>| async move { app.render("form.html", Context::new()) }))
+        .route(web::post().to(|body: String| async move { body })),
+    ),
+  )
+  .await;
+
+  let form = test::call_and_read_body(&app, test::TestRequest::get().uri("/comments").to_request()).await;
+  assert!(std::str::from_utf8(&form).unwrap().contains("name=\"website\" value=\"\" tabindex=\"-1\""));
+
+  let submit = |body: &'static str| {
+    test::TestRequest::post()
+      .uri("/comments")
+      .peer_addr("192.0.2.1:4000".parse().unwrap())
+      .insert_header(("content-type", "application/x-www-form-urlencoded"))
+      .set_payload(body)
+      .to_request()
+  };
+  // The handler still gets the whole body after the check.
+  assert_eq!(test::call_and_read_body(&app, submit("body=hi&website=")).await, "body=hi&website=");
+  let resp = test::try_call_service(&app, submit("body=buy&website=spam.example")).await;
+  assert_eq!(resp.unwrap_err().as_response_error().status_code(), 400);
+  let resp = test::try_call_service(&app, submit("body=hi")).await;
+  let error = resp.unwrap_err();
+  let response = error.error_response();
+  assert_eq!(response.status(), 429);
+  assert_eq!(response.headers().get("retry-after").unwrap(), "60");
+}
+
+#[cfg(feature = "uploads")]
+#[actix_rt::test]
+async fn test_read_uploads_validates_and_renders_errors() {
+  use actix_multipart::Multipart;
+  use snapfire::{Template, actix::uploads::read_uploads, core::uploads::UploadRule};
+
+  let temp_dir = tempdir().unwrap();
+  fs::write(
+    temp_dir.path().join("upload.html"),
+    "{{ upload_field(name=\"avatar\", errors=upload_errors) }}{{ upload_field(name=\"cv\", errors=upload_errors) }}",
+  )
+  .unwrap();
+  fs::write(temp_dir.path().join("done.html"), "{{ title }}: {{ files | join(sep=\",\") }}").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path)
+    .upload_field("avatar", UploadRule::new().max_size(10).accept(&["image/*"]).required(true))
+    .upload_field("cv", UploadRule::new().accept(&[".pdf"]))
+    .build()
+    .unwrap();
+  #[cfg(feature = "devel")]
+  let progress = snapfire_app.subscribe_reloads();
+
+  async fn upload(app: web::Data<TeraWeb>, form: Multipart) -> actix_web::Result<Template> {
+    let uploads = read_uploads(&app, form).await?;
+    let mut context = Context::new();
+    context.insert("upload_errors", &uploads.errors);
+    context.insert("files", &uploads.files.iter().map(|file| &file.filename).collect::<Vec<_>>());
+    context.insert("title", &uploads.fields.get("title"));
+    let name = if uploads.is_valid() { "done.html" } else { "upload.html" };
+    Ok(app.render(name, context))
+  }
+  let app =
+    test::init_service(App::new().app_data(web::Data::new(snapfire_app)).route("/upload", web::post().to(upload))).await;
+
+  // A field name, its file name and content type if it is a file, and its value.
+  type Part<'a> = (&'a str, Option<(&'a str, &'a str)>, &'a str);
+  let submit = |parts: &[Part]| {
+    let mut body = String::new();
+    for (name, file, value) in parts {
+      body.push_str("--XYZ\r\n");
+      match file {
+        Some((filename, content_type)) => body.push_str(&format!(
+          "Content-Disposition: form-data; name=\"{name}\"; filename=\"{filename}\"\r\nContent-Type: {content_type}\r\n\r\n"
+        )),
+        None => body.push_str(&format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n")),
+      }
+      body.push_str(value);
+      body.push_str("\r\n");
+    }
+    body.push_str("--XYZ--\r\n");
+    test::TestRequest::post()
+      .uri("/upload")
+      .insert_header(("content-type", "multipart/form-data; boundary=XYZ"))
+      .set_payload(body)
+      .to_request()
+  };
+
+  let body = test::call_and_read_body(
+    &app,
+    submit(&[
+      ("title", None, "Me"),
+      ("avatar", Some(("me.png", "image/png")), "png bytes"),
+      ("cv", Some(("", "application/octet-stream")), ""),
+    ]),
+  )
+  .await;
+  assert_eq!(body, "Me: me.png");
+  // In development, pages hear how the file arrived.
+  #[cfg(feature = "devel")]
+  {
+    let message: serde_json::Value = serde_json::from_str(progress.try_recv().unwrap().as_str()).unwrap();
+    assert_eq!(
+      message,
+      serde_json::json!({ "event": "upload-progress", "data": { "field": "avatar", "filename": "me.png", "received": 9, "done": true } })
+    );
+  }
+
+  let body = test::call_and_read_body(
+    &app,
+    submit(&[
+      ("avatar", Some(("huge.png", "image/png")), "far too many bytes"),
+      ("cv", Some(("cv.docx", "application/msword")), "doc"),
+    ]),
+  )
+  .await;
+  let body = std::str::from_utf8(&body).unwrap();
+  assert!(body.contains("aria-describedby=\"avatar-error\"><p class=\"upload-error\" id=\"avatar-error\">huge.png is larger than 10 bytes</p>"));
+  assert!(body.contains("cv.docx is not an accepted file type (.pdf)"));
+
+  let body = test::call_and_read_body(&app, submit(&[("title", None, "Me")])).await;
+  assert!(std::str::from_utf8(&body).unwrap().contains(">Please choose a file</p>"));
+}
+
+#[cfg(feature = "pdf")]
+#[actix_rt::test]
+async fn test_render_pdf_with_custom_backend() {
+  use snapfire::core::pdf::{PdfBackend, PdfOptions};
+
+  /// Returns the HTML it was given, to check what reaches the converter.
+  struct EchoBackend;
+
+  impl PdfBackend for EchoBackend {
+    fn name(&self) -> &str {
+      "echo"
+    }
+
+    fn convert(&self, html: &str) -> snapfire::Result<Vec<u8>> {
+      Ok(html.as_bytes().to_vec())
+    }
+  }
+
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("invoice.html"), "<html><head></head><body>#{{ number }}</body></html>").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).pdf_backend(EchoBackend).build().unwrap();
+
+  let app = test::init_service(App::new().route(
+    "/invoice",
+    web::get().to(move || {
+      let snapfire_app = snapfire_app.clone();
+      async move {
+        let mut context = Context::new();
+        context.insert("number", &42);
+        let options = PdfOptions::new().page_size("A4");
+        web::block(move || snapfire_app.render_pdf("invoice.html", context, &options))
+          .await
+          .unwrap()
+          .unwrap()
+          .attachment("invoice-42.pdf")
+      }
+    }),
+  ))
+  .await;
+
+  let resp = test::call_service(&app, test::TestRequest::get().uri("/invoice").to_request()).await;
+  assert_eq!(resp.headers().get("content-type").unwrap(), "application/pdf");
+  assert_eq!(
+    resp.headers().get("content-disposition").unwrap(),
+    "attachment; filename=\"invoice-42.pdf\""
+  );
+  let body = test::read_body(resp).await;
+  assert_eq!(body, "<html><head><style>@page { size: A4 }</style></head><body>#42</body></html>");
+}
+
+#[cfg(feature = "screenshot")]
+#[actix_rt::test]
+async fn test_render_screenshot_with_custom_backend() {
+  use snapfire::core::screenshot::{ScreenshotBackend, Viewport};
+
+  /// Returns the viewport size and the HTML it was given.
+  struct EchoBackend;
+
+  impl ScreenshotBackend for EchoBackend {
+    fn name(&self) -> &str {
+      "echo"
+    }
+
+    fn capture(&self, html: &str, viewport: &Viewport) -> snapfire::Result<Vec<u8>> {
+      Ok(format!("{}x{}@{} {}", viewport.width, viewport.height, viewport.device_scale, html).into_bytes())
+    }
+  }
+
+  let temp_dir = tempdir().unwrap();
+  fs::write(temp_dir.path().join("og.html"), "<head></head><h1>{{ title }}</h1>").unwrap();
+  let glob_path = temp_dir.path().join("*.html").to_str().unwrap().to_string();
+  let snapfire_app = TeraWeb::builder(&glob_path).screenshot_backend(EchoBackend).build().unwrap();
+
+  let app = test::init_service(App::new().route(
+    "/og.png",
+    web::get().to(move || {
+      let snapfire_app = snapfire_app.clone();
+      async move {
+        let mut context = Context::new();
+        context.insert("title", "Hello");
+        let viewport = Viewport::open_graph().device_scale(2.0).base_url("https://example.com/");
+        web::block(move || snapfire_app.render_screenshot("og.html", context, &viewport))
+          .await
+          .unwrap()
+          .unwrap()
+      }
+    }),
+  ))
+  .await;
+
+  let resp = test::call_service(&app, test::TestRequest::get().uri("/og.png").to_request()).await;
+  assert_eq!(resp.headers().get("content-type").unwrap(), "image/png");
+  let body = test::read_body(resp).await;
+  assert_eq!(body, "1200x630@2 <head><base href=\"https://example.com/\"></head><h1>Hello</h1>");
+}